@@ -0,0 +1,217 @@
+//! Declaration Blocks
+//!
+//! An editable list of declarations as written, such as the `style`
+//! attribute of an element. Values are kept as text so the block
+//! serializes back the way it was set.
+
+use std::fmt;
+
+use crate::parser::token_to_string;
+use crate::tokenizer::{Token, Tokenizer};
+
+/// A declaration of a block, its value as CSS text
+#[derive(Debug, Clone, PartialEq)]
+struct BlockDeclaration {
+    property: String,
+    value: String,
+    important: bool,
+}
+
+/// Declarations in the order they were set, at most one per property
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeclarationBlock {
+    declarations: Vec<BlockDeclaration>,
+}
+
+impl DeclarationBlock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse declarations, skipping those that are not valid
+    ///
+    /// A property declared twice keeps the later value.
+    pub fn parse(text: &str) -> Self {
+        let tokens = Tokenizer::new(text).tokenize_all().unwrap_or_default();
+        let mut block = Self::new();
+        for tokens in split_declarations(&tokens) {
+            if let Some(declaration) = parse_declaration(tokens) {
+                block.insert(declaration);
+            }
+        }
+        block
+    }
+
+    pub fn len(&self) -> usize {
+        self.declarations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.declarations.is_empty()
+    }
+
+    /// Property of the declaration at an index
+    pub fn item(&self, index: usize) -> Option<&str> {
+        self.declarations.get(index).map(|d| d.property.as_str())
+    }
+
+    /// Value of a property as CSS text
+    pub fn get(&self, property: &str) -> Option<&str> {
+        self.find(property).map(|d| d.value.as_str())
+    }
+
+    /// Check if a property is declared `!important`
+    pub fn is_important(&self, property: &str) -> bool {
+        self.find(property).is_some_and(|d| d.important)
+    }
+
+    /// Set a property, returns false and leaves the block as it was when
+    /// the value is not valid
+    ///
+    /// An empty value removes the property.
+    pub fn set(&mut self, property: &str, value: &str, important: bool) -> bool {
+        if value.trim().is_empty() {
+            self.remove(property);
+            return true;
+        }
+        let parsed = Self::parse(&format!("{}: {}", property, value));
+        match parsed.declarations.as_slice() {
+            [declaration] if declaration.property == property.trim().to_ascii_lowercase() => {
+                self.insert(BlockDeclaration { important, ..declaration.clone() });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Remove a property, returns its value
+    pub fn remove(&mut self, property: &str) -> Option<String> {
+        let property = property.to_ascii_lowercase();
+        let index = self.declarations.iter().position(|d| d.property == property)?;
+        Some(self.declarations.remove(index).value)
+    }
+
+    /// Serialize the block, as for the `style` attribute
+    pub fn to_css(&self) -> String {
+        self.to_string()
+    }
+
+    fn find(&self, property: &str) -> Option<&BlockDeclaration> {
+        let property = property.to_ascii_lowercase();
+        self.declarations.iter().find(|d| d.property == property)
+    }
+
+    /// Add a declaration, replacing one of the same property in place
+    fn insert(&mut self, declaration: BlockDeclaration) {
+        match self.declarations.iter_mut().find(|d| d.property == declaration.property) {
+            Some(existing) => *existing = declaration,
+            None => self.declarations.push(declaration),
+        }
+    }
+}
+
+impl fmt::Display for DeclarationBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, declaration) in self.declarations.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}: {}", declaration.property, declaration.value)?;
+            if declaration.important {
+                f.write_str(" !important")?;
+            }
+            f.write_str(";")?;
+        }
+        Ok(())
+    }
+}
+
+/// Split tokens at the semicolons that are not inside parentheses,
+/// brackets or braces
+fn split_declarations(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Function(_) | Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBracket | Token::RightBrace => depth = depth.saturating_sub(1),
+            Token::Semicolon if depth == 0 => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+/// Parse `property: value [!important]`
+fn parse_declaration(tokens: &[Token]) -> Option<BlockDeclaration> {
+    let mut tokens = trim_whitespace(tokens);
+    let property = match tokens.first()? {
+        Token::Ident(name) => name.to_ascii_lowercase(),
+        _ => return None,
+    };
+    tokens = trim_whitespace(&tokens[1..]);
+    if tokens.first() != Some(&Token::Colon) {
+        return None;
+    }
+    tokens = trim_whitespace(&tokens[1..]);
+
+    let mut important = false;
+    if let [rest @ .., Token::Delim('!'), Token::Ident(word)]
+    | [rest @ .., Token::Delim('!'), Token::Whitespace, Token::Ident(word)] = tokens
+    {
+        if word.eq_ignore_ascii_case("important") {
+            important = true;
+            tokens = trim_whitespace(rest);
+        }
+    }
+    let stray = |t: &Token| matches!(t, Token::Delim('!') | Token::LeftBrace | Token::RightBrace);
+    if tokens.is_empty() || tokens.iter().any(stray) {
+        return None;
+    }
+
+    let value: String = tokens.iter().map(token_to_string).collect();
+    Some(BlockDeclaration { property, value, important })
+}
+
+fn trim_whitespace(tokens: &[Token]) -> &[Token] {
+    let start = tokens.iter().position(|t| *t != Token::Whitespace).unwrap_or(tokens.len());
+    let end = tokens.iter().rposition(|t| *t != Token::Whitespace).map_or(start, |i| i + 1);
+    &tokens[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_serialize() {
+        let block =
+            DeclarationBlock::parse("color: red;  MARGIN : 0 auto ; junk; color: blue !important; width:calc(100% - 4px)");
+        assert_eq!(block.len(), 3);
+        assert_eq!(block.item(0), Some("color"));
+        assert_eq!(block.get("color"), Some("blue"));
+        assert!(block.is_important("color"));
+        assert_eq!(block.get("margin"), Some("0 auto"));
+        assert_eq!(block.to_css(), "color: blue !important; margin: 0 auto; width: calc(100% - 4px);");
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let mut block = DeclarationBlock::parse("display: block; color: red");
+        assert!(block.set("display", "none", false));
+        assert!(block.set("background-color", "#ffcc00", false));
+        assert_eq!(block.to_css(), "display: none; color: red; background-color: #ffcc00;");
+
+        // Values that would add other declarations are not valid
+        assert!(!block.set("color", "blue; width: 10px", false));
+        assert!(block.set("color", "", false));
+        assert_eq!(block.get("color"), None);
+        assert_eq!(block.remove("display"), Some("none".to_string()));
+        assert_eq!(block.to_css(), "background-color: #ffcc00;");
+    }
+}
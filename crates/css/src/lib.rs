@@ -20,6 +20,7 @@ mod tokenizer;
 mod value;
 mod selector;
 mod parser;
+mod declarations;
 
 // Re-export main types
 pub use error::{CssError, CssResult, SourceLocation};
@@ -31,6 +32,7 @@ pub use parser::{
     ImportRule, MediaRule, FontFaceRule, KeyframesRule, Keyframe,
     CssParser,
 };
+pub use declarations::DeclarationBlock;
 
 /// Parse inline style declarations from a style attribute value.
 ///
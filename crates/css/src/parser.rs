@@ -696,7 +696,7 @@ impl<'a> CssParser<'a> {
 }
 
 /// Convert a token to its string representation (for collecting selector text)
-fn token_to_string(token: &Token) -> String {
+pub(crate) fn token_to_string(token: &Token) -> String {
     match token {
         Token::Ident(s) => s.clone(),
         Token::Function(s) => format!("{}(", s),
@@ -899,6 +899,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_fr_values_in_repeat() {
+        use crate::value::LengthUnit;
+
+        let css = "div { grid-template-columns: 100px repeat(2, 1fr); }";
+        let stylesheet = Stylesheet::parse(css).unwrap();
+
+        if let Rule::Style(rule) = &stylesheet.rules[0] {
+            assert_eq!(
+                rule.declarations[0].value,
+                CssValue::List(vec![
+                    CssValue::Length(100.0, LengthUnit::Px),
+                    CssValue::Function("repeat".to_string(), vec![CssValue::Number(2.0), CssValue::Flex(1.0)]),
+                ])
+            );
+        } else {
+            panic!("Expected style rule");
+        }
+    }
+
     #[test]
     fn test_var_function() {
         let css = "p { color: var(--main-color); }";
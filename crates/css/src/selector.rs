@@ -127,9 +127,20 @@ impl Selector {
     }
 
     /// Parse a comma-separated list of selectors
+    ///
+    /// The whole input must be selectors, as for querySelector(), anything
+    /// left over is an error.
     pub fn parse_list(input: &str) -> CssResult<Vec<Self>> {
         let mut parser = SelectorParser::new(input);
-        parser.parse_selector_list()
+        let selectors = parser.parse_selector_list()?;
+        parser.skip_whitespace()?;
+        if selectors.is_empty() || !matches!(parser.peek(), None | Some(Token::Eof)) {
+            return Err(CssError::InvalidSelector {
+                selector: input.to_string(),
+                location: parser.location(),
+            });
+        }
+        Ok(selectors)
     }
 }
 
@@ -184,6 +195,14 @@ impl<'a> SelectorParser<'a> {
             match self.peek() {
                 Some(Token::Comma) => {
                     self.advance()?;
+                    // A selector must follow the comma
+                    self.skip_whitespace()?;
+                    if matches!(self.peek(), None | Some(Token::Eof)) {
+                        return Err(CssError::InvalidSelector {
+                            selector: String::new(),
+                            location: self.location(),
+                        });
+                    }
                 }
                 _ => break,
             }
@@ -231,7 +250,8 @@ impl<'a> SelectorParser<'a> {
             }
         }
 
-        if parts.is_empty() {
+        // A combinator needs a compound selector on both sides
+        if parts.is_empty() || matches!(parts.last(), Some(SelectorPart::Combinator(_))) {
             return Err(CssError::InvalidSelector {
                 selector: String::new(),
                 location: self.location(),
@@ -701,4 +721,15 @@ mod tests {
             SelectorPart::Attribute { case_insensitive: true, .. }
         ));
     }
+
+    #[test]
+    fn test_parse_list_rejects_leftovers() {
+        let list = Selector::parse_list("nav a.active, li:not(.done)").unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(Selector::parse_list("div $").is_err());
+        assert!(Selector::parse_list("div,").is_err());
+        assert!(Selector::parse_list("div >").is_err());
+        assert!(Selector::parse_list("").is_err());
+        assert!(Selector::parse_list("li:not(").is_err());
+    }
 }
@@ -2,6 +2,8 @@
 //!
 //! Parses CSS values including colors, lengths, and other types.
 
+use serde::{Deserialize, Serialize};
+
 use crate::error::{CssError, CssResult, SourceLocation};
 use crate::tokenizer::Token;
 
@@ -30,6 +32,10 @@ pub enum CssValue {
     CommaSeparated(Vec<CssValue>),
     /// Time value (for transitions/animations)
     Time(f32, TimeUnit),
+    /// Flexible length in fr units (for grid tracks)
+    Flex(f32),
+    /// Angle in degrees
+    Angle(f32),
 }
 
 /// Length units
@@ -137,7 +143,7 @@ impl LengthUnit {
 }
 
 /// Color value (RGBA)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -418,6 +424,10 @@ impl ValueParser {
                     Ok(CssValue::Time(*n, time_unit))
                 } else if let Some(length_unit) = LengthUnit::from_str(unit) {
                     Ok(CssValue::Length(*n, length_unit))
+                } else if unit.eq_ignore_ascii_case("fr") {
+                    Ok(CssValue::Flex(*n))
+                } else if let Some(degrees) = angle_to_degrees(*n, unit) {
+                    Ok(CssValue::Angle(degrees))
                 } else {
                     // Unknown unit - treat as keyword for now
                     Err(CssError::parse_error(format!("Unknown unit: {}", unit), location))
@@ -516,6 +526,17 @@ impl ValueParser {
     }
 }
 
+/// Convert an angle in a unit to degrees, None for other units
+fn angle_to_degrees(value: f32, unit: &str) -> Option<f32> {
+    match unit.to_ascii_lowercase().as_str() {
+        "deg" => Some(value),
+        "rad" => Some(value.to_degrees()),
+        "grad" => Some(value * 0.9),
+        "turn" => Some(value * 360.0),
+        _ => None,
+    }
+}
+
 /// Convert HSL to RGB
 fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (f32, f32, f32) {
     let h = (h % 360.0 + 360.0) % 360.0 / 360.0;
@@ -584,6 +605,15 @@ mod tests {
         assert_eq!(Color::from_name("transparent"), Some(Color::rgba(0, 0, 0, 0)));
     }
 
+    #[test]
+    fn test_angle_units() {
+        let angle = |n, unit: &str| ValueParser::parse_token(&Token::Dimension(n, unit.to_string()), SourceLocation::default());
+        assert_eq!(angle(45.0, "deg").unwrap(), CssValue::Angle(45.0));
+        assert_eq!(angle(0.5, "turn").unwrap(), CssValue::Angle(180.0));
+        assert_eq!(angle(100.0, "grad").unwrap(), CssValue::Angle(90.0));
+        assert!(angle(1.0, "parsec").is_err());
+    }
+
     #[test]
     fn test_length_unit_parse() {
         assert_eq!(LengthUnit::from_str("px"), Some(LengthUnit::Px));
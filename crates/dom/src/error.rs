@@ -16,4 +16,16 @@ pub enum DomError {
 
     #[error("Invalid node type for operation")]
     InvalidNodeType,
+
+    #[error("The node would contain itself")]
+    HierarchyRequest,
+
+    #[error("The node is not a child of this node")]
+    NotAChild,
+
+    #[error("The token must not be empty")]
+    EmptyToken,
+
+    #[error("The token '{0}' must not contain whitespace")]
+    TokenWithWhitespace(String),
 }
@@ -7,7 +7,7 @@ mod tree;
 mod error;
 mod query;
 
-pub use node::{Node, NodeId, NodeType, ElementData};
+pub use node::{Node, NodeId, NodeType, ElementData, check_class_token};
 pub use tree::DomTree;
 pub use error::{DomError, DomResult};
 pub use query::Queryable;
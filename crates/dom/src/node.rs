@@ -1,11 +1,14 @@
 //! DOM Node representation
 
 use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use std::fmt;
 
+use crate::error::{DomError, DomResult};
+
 /// Unique identifier for a node in the DOM tree
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NodeId(pub u32);
 
 impl NodeId {
@@ -43,6 +46,9 @@ pub enum NodeType {
     Text(String),
     /// HTML comment
     Comment(String),
+    /// Container of nodes that are inserted together, never in a tree
+    /// itself
+    DocumentFragment,
 }
 
 /// Element-specific data
@@ -96,6 +102,94 @@ impl ElementData {
             .map(|c| c.split_whitespace().collect())
             .unwrap_or_default()
     }
+
+    /// Get the classes as classList sees them, each once in the order
+    /// they first appear
+    pub fn class_list(&self) -> Vec<&str> {
+        let mut list = Vec::new();
+        for class in self.classes() {
+            if !list.contains(&class) {
+                list.push(class);
+            }
+        }
+        list
+    }
+
+    /// Add classes that are not there yet
+    pub fn add_classes(&mut self, classes: &[&str]) -> DomResult<()> {
+        classes.iter().try_for_each(|class| check_class_token(class))?;
+        let mut list = self.owned_class_list();
+        for class in classes {
+            if !list.iter().any(|c| c == class) {
+                list.push(class.to_string());
+            }
+        }
+        self.set_class_list(list);
+        Ok(())
+    }
+
+    /// Remove classes
+    pub fn remove_classes(&mut self, classes: &[&str]) -> DomResult<()> {
+        classes.iter().try_for_each(|class| check_class_token(class))?;
+        let mut list = self.owned_class_list();
+        list.retain(|c| !classes.contains(&c.as_str()));
+        self.set_class_list(list);
+        Ok(())
+    }
+
+    /// Remove a class when it is there, else add it, or add or remove it
+    /// as `force` says. Returns whether the class is there afterwards.
+    pub fn toggle_class(&mut self, class: &str, force: Option<bool>) -> DomResult<bool> {
+        check_class_token(class)?;
+        let present = self.class_list().contains(&class);
+        match (present, force) {
+            (true, Some(true)) => Ok(true),
+            (false, Some(false)) => Ok(false),
+            (true, _) => self.remove_classes(&[class]).map(|_| false),
+            (false, _) => self.add_classes(&[class]).map(|_| true),
+        }
+    }
+
+    /// Replace a class in place, returns false when it isn't there
+    pub fn replace_class(&mut self, old: &str, new: &str) -> DomResult<bool> {
+        check_class_token(old)?;
+        check_class_token(new)?;
+        let list = self.owned_class_list();
+        if !list.iter().any(|c| c == old) {
+            return Ok(false);
+        }
+        let mut replaced: Vec<String> = Vec::new();
+        for class in list {
+            let class = if class == old { new.to_string() } else { class };
+            if !replaced.contains(&class) {
+                replaced.push(class);
+            }
+        }
+        self.set_class_list(replaced);
+        Ok(true)
+    }
+
+    fn owned_class_list(&self) -> Vec<String> {
+        self.class_list().into_iter().map(str::to_string).collect()
+    }
+
+    /// Write the class attribute back, unless there is none and no classes
+    fn set_class_list(&mut self, list: Vec<String>) {
+        if !list.is_empty() || self.get_attribute("class").is_some() {
+            self.set_attribute("class", list.join(" "));
+        }
+    }
+}
+
+/// Check that a token can be a class, as classList requires
+pub fn check_class_token(token: &str) -> DomResult<()> {
+    if token.is_empty() {
+        Err(DomError::EmptyToken)
+    } else if token.contains(|c: char| c.is_ascii_whitespace()) {
+        Err(DomError::TokenWithWhitespace(token.to_string()))
+    } else {
+        Ok(())
+    }
 }
 
 /// A node in the DOM tree
@@ -148,6 +242,11 @@ impl Node {
         matches!(self.node_type, NodeType::Comment(_))
     }
 
+    /// Check if this is a document fragment
+    pub fn is_document_fragment(&self) -> bool {
+        matches!(self.node_type, NodeType::DocumentFragment)
+    }
+
     /// Get element data if this is an element
     pub fn as_element(&self) -> Option<&ElementData> {
         match &self.node_type {
@@ -177,3 +276,29 @@ impl Node {
         self.as_element().map(|e| e.tag_name.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class_list_edits() {
+        let mut element = ElementData::new("div");
+        element.set_attribute("class", " menu  item menu ");
+        assert_eq!(element.class_list(), ["menu", "item"]);
+
+        element.add_classes(&["open", "item"]).unwrap();
+        assert_eq!(element.get_attribute("class"), Some("menu item open"));
+        assert!(!element.toggle_class("open", None).unwrap());
+        assert!(element.toggle_class("open", Some(true)).unwrap());
+        assert!(element.replace_class("menu", "open").unwrap());
+        assert_eq!(element.get_attribute("class"), Some("open item"));
+        element.remove_classes(&["item"]).unwrap();
+        assert_eq!(element.get_attribute("class"), Some("open"));
+
+        // Tokens are checked before anything changes
+        assert!(matches!(element.add_classes(&["a", ""]), Err(DomError::EmptyToken)));
+        assert!(matches!(element.toggle_class("a b", None), Err(DomError::TokenWithWhitespace(_))));
+        assert_eq!(element.get_attribute("class"), Some("open"));
+    }
+}
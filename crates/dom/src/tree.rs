@@ -70,6 +70,17 @@ impl DomTree {
         id
     }
 
+    /// Create a new document fragment
+    pub fn create_document_fragment(&mut self) -> NodeId {
+        let id = NodeId::new(self.next_id);
+        self.next_id += 1;
+
+        let node = Node::new(id, NodeType::DocumentFragment);
+        self.nodes.insert(id, node);
+
+        id
+    }
+
     /// Create a new comment node
     pub fn create_comment(&mut self, content: impl Into<String>) -> NodeId {
         let id = NodeId::new(self.next_id);
@@ -104,35 +115,96 @@ impl DomTree {
         id
     }
 
-    /// Append a child node to a parent
+    /// Append a child node to a parent, taking it from its parent first
     pub fn append_child(&mut self, parent_id: NodeId, child_id: NodeId) -> DomResult<()> {
-        // Get the last child of parent to set sibling links
-        let last_child = {
-            let parent = self.get(parent_id).ok_or(DomError::NodeNotFound(parent_id.0))?;
-            parent.children.last().copied()
+        self.insert_before(parent_id, child_id, None)
+    }
+
+    /// Insert a child node before `reference`, a child of the parent, or
+    /// last without one, taking it from its parent first
+    ///
+    /// A document fragment has its children inserted instead, which
+    /// leaves it empty.
+    pub fn insert_before(&mut self, parent_id: NodeId, child_id: NodeId, reference: Option<NodeId>) -> DomResult<()> {
+        self.check_insertion(parent_id, child_id)?;
+        if let Some(reference_id) = reference {
+            if self.parent(reference_id) != Some(parent_id) {
+                return Err(DomError::NotAChild);
+            }
+            if reference_id == child_id {
+                return Ok(());
+            }
+        }
+        if self.get(child_id).is_some_and(|n| n.is_document_fragment()) {
+            for fragment_child in self.children(child_id) {
+                self.insert_before(parent_id, fragment_child, reference)?;
+            }
+            return Ok(());
+        }
+        if let Some(old_parent) = self.parent(child_id) {
+            self.remove_child(old_parent, child_id)?;
+        }
+
+        let (prev_sibling, next_sibling) = {
+            let parent = self.get_mut(parent_id).ok_or(DomError::NodeNotFound(parent_id.0))?;
+            let index = reference
+                .and_then(|r| parent.children.iter().position(|&id| id == r))
+                .unwrap_or(parent.children.len());
+            parent.children.insert(index, child_id);
+            (index.checked_sub(1).map(|i| parent.children[i]), parent.children.get(index + 1).copied())
         };
 
-        // Update the new child's prev_sibling and parent
+        // Update the new child's siblings and parent
         {
             let child = self.get_mut(child_id).ok_or(DomError::NodeNotFound(child_id.0))?;
             child.parent = Some(parent_id);
-            child.prev_sibling = last_child;
-            child.next_sibling = None;
+            child.prev_sibling = prev_sibling;
+            child.next_sibling = next_sibling;
+        }
+        if let Some(prev) = prev_sibling.and_then(|id| self.get_mut(id)) {
+            prev.next_sibling = Some(child_id);
+        }
+        if let Some(next) = next_sibling.and_then(|id| self.get_mut(id)) {
+            next.prev_sibling = Some(child_id);
         }
 
-        // Update the previous last child's next_sibling
-        if let Some(last_child_id) = last_child {
-            if let Some(last) = self.get_mut(last_child_id) {
-                last.next_sibling = Some(child_id);
-            }
+        Ok(())
+    }
+
+    /// Put a node in place of a child of the parent, taking it from its
+    /// parent first
+    pub fn replace_child(&mut self, parent_id: NodeId, new_id: NodeId, old_id: NodeId) -> DomResult<()> {
+        if self.parent(old_id) != Some(parent_id) {
+            return Err(DomError::NotAChild);
+        }
+        self.check_insertion(parent_id, new_id)?;
+        if new_id == old_id {
+            return Ok(());
         }
+        self.insert_before(parent_id, new_id, Some(old_id))?;
+        self.remove_child(parent_id, old_id)
+    }
 
-        // Add child to parent's children list
-        {
-            let parent = self.get_mut(parent_id).ok_or(DomError::NodeNotFound(parent_id.0))?;
-            parent.children.push(child_id);
+    /// Check if `id` is `ancestor` or one of its descendants
+    pub fn contains(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut current = Some(id);
+        while let Some(node_id) = current {
+            if node_id == ancestor {
+                return true;
+            }
+            current = self.parent(node_id);
         }
+        false
+    }
 
+    /// Check a node can become a child of the parent: both exist, and it
+    /// is neither the document nor the parent or one of its ancestors
+    fn check_insertion(&self, parent_id: NodeId, child_id: NodeId) -> DomResult<()> {
+        self.get(parent_id).ok_or(DomError::NodeNotFound(parent_id.0))?;
+        let child = self.get(child_id).ok_or(DomError::NodeNotFound(child_id.0))?;
+        if matches!(child.node_type, NodeType::Document) || self.contains(child_id, parent_id) {
+            return Err(DomError::HierarchyRequest);
+        }
         Ok(())
     }
 
@@ -140,6 +212,9 @@ impl DomTree {
     pub fn remove_child(&mut self, parent_id: NodeId, child_id: NodeId) -> DomResult<()> {
         let (prev_sibling, next_sibling) = {
             let child = self.get(child_id).ok_or(DomError::NodeNotFound(child_id.0))?;
+            if child.parent != Some(parent_id) {
+                return Err(DomError::NotAChild);
+            }
             (child.prev_sibling, child.next_sibling)
         };
 
@@ -172,6 +247,25 @@ impl DomTree {
         Ok(())
     }
 
+    /// Set the text content of a node: the text of a text or comment node,
+    /// or for others their children replaced by a text node, none when
+    /// `text` is empty
+    pub fn set_text_content(&mut self, id: NodeId, text: &str) -> DomResult<()> {
+        let node = self.get_mut(id).ok_or(DomError::NodeNotFound(id.0))?;
+        if let NodeType::Text(content) | NodeType::Comment(content) = &mut node.node_type {
+            *content = text.to_string();
+            return Ok(());
+        }
+        for child_id in self.children(id) {
+            self.remove_child(id, child_id)?;
+        }
+        if !text.is_empty() {
+            let text_id = self.create_text(text);
+            self.append_child(id, text_id)?;
+        }
+        Ok(())
+    }
+
     /// Get all children of a node
     pub fn children(&self, id: NodeId) -> Vec<NodeId> {
         self.get(id)
@@ -288,6 +382,9 @@ impl DomTree {
                 NodeType::Comment(text) => {
                     output.push_str(&format!("{}<!-- {} -->\n", indent, text));
                 }
+                NodeType::DocumentFragment => {
+                    output.push_str(&format!("{}#document-fragment\n", indent));
+                }
             }
 
             for &child_id in &node.children {
@@ -327,4 +424,79 @@ mod tests {
         assert_eq!(tree.len(), 4); // document + html + body + text
         assert_eq!(tree.text_content(body), "Hello, World!");
     }
+
+    #[test]
+    fn test_set_text_content() {
+        let mut tree = DomTree::new();
+        let p = tree.create_element("p");
+        let b = tree.create_element("b");
+        let text = tree.create_text("bold");
+        tree.append_child(p, b).unwrap();
+        tree.append_child(b, text).unwrap();
+
+        tree.set_text_content(text, "strong").unwrap();
+        assert_eq!(tree.text_content(p), "strong");
+
+        tree.set_text_content(p, "plain").unwrap();
+        assert_eq!(tree.text_content(p), "plain");
+        assert_eq!(tree.children(p).len(), 1);
+        assert_eq!(tree.parent(b), None);
+
+        tree.set_text_content(p, "").unwrap();
+        assert!(tree.children(p).is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_replace_children() {
+        let mut tree = DomTree::new();
+        let ul = tree.create_element("ul");
+        let [a, b, c] = ["a", "b", "c"].map(|text| tree.create_text(text));
+        tree.append_child(ul, a).unwrap();
+        tree.append_child(ul, c).unwrap();
+        tree.insert_before(ul, b, Some(c)).unwrap();
+        assert_eq!(tree.children(ul), vec![a, b, c]);
+        assert_eq!((tree.get(b).unwrap().prev_sibling, tree.get(b).unwrap().next_sibling), (Some(a), Some(c)));
+
+        // A node already in the tree moves
+        tree.append_child(ul, a).unwrap();
+        assert_eq!(tree.text_content(ul), "bca");
+        assert_eq!(tree.get(b).unwrap().prev_sibling, None);
+
+        let d = tree.create_text("d");
+        tree.replace_child(ul, d, c).unwrap();
+        assert_eq!(tree.text_content(ul), "bda");
+        assert_eq!(tree.parent(c), None);
+
+        // A node cannot contain itself, nor leave a parent it is not in
+        let li = tree.create_element("li");
+        tree.append_child(ul, li).unwrap();
+        assert!(matches!(tree.append_child(li, ul), Err(DomError::HierarchyRequest)));
+        assert!(matches!(tree.insert_before(ul, li, Some(c)), Err(DomError::NotAChild)));
+        assert!(matches!(tree.remove_child(li, a), Err(DomError::NotAChild)));
+        assert_eq!(tree.children(ul), vec![b, d, a, li]);
+    }
+
+    #[test]
+    fn test_insert_document_fragment() {
+        let mut tree = DomTree::new();
+        let ul = tree.create_element("ul");
+        let last = tree.create_element("li");
+        tree.append_child(ul, last).unwrap();
+
+        let fragment = tree.create_document_fragment();
+        let items: Vec<NodeId> = (0..3).map(|_| tree.create_element("li")).collect();
+        for &item in &items {
+            tree.append_child(fragment, item).unwrap();
+        }
+        tree.insert_before(ul, fragment, Some(last)).unwrap();
+
+        assert_eq!(tree.children(ul), vec![items[0], items[1], items[2], last]);
+        assert!(tree.children(fragment).is_empty());
+        assert_eq!(tree.parent(fragment), None);
+
+        // A fragment cannot go into one of its own children
+        let inner = tree.create_element("div");
+        tree.append_child(fragment, inner).unwrap();
+        assert!(matches!(tree.append_child(inner, fragment), Err(DomError::HierarchyRequest)));
+    }
 }
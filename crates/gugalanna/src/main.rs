@@ -12,7 +12,7 @@ use url::Url;
 use gugalanna_dom::{DomTree, Queryable};
 use gugalanna_html::HtmlParser;
 use gugalanna_net::HttpClient;
-use gugalanna_shell::{Browser, BrowserConfig};
+use gugalanna_shell::{dump_display_list, replay_in_window, replay_to_png, screenshot_url, Browser, BrowserConfig};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -51,12 +51,17 @@ async fn main() -> ExitCode {
             }
         }
         "--render" => {
-            // Render a URL in a window
-            if args.len() < 3 {
-                eprintln!("Usage: {} --render <URL>", args[0]);
-                return ExitCode::FAILURE;
+            // Render a URL in a window, or the homepage without one
+            if let Err(e) = run_browser(args.get(2).map(String::as_str)) {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
             }
-            if let Err(e) = run_browser(&args[2]) {
+        }
+        "--restore" => {
+            // Reopen the tabs of the last session in a window
+            if let Err(e) = run_restored() {
                 eprintln!("Error: {}", e);
                 ExitCode::FAILURE
             } else {
@@ -76,6 +81,50 @@ async fn main() -> ExitCode {
                 ExitCode::SUCCESS
             }
         }
+        "--screenshot" => {
+            // Render a URL to a PNG file without a window
+            if args.len() < 4 {
+                eprintln!("Usage: {} --screenshot <URL> <OUT.png>", args[0]);
+                return ExitCode::FAILURE;
+            }
+            if let Err(e) = screenshot_url(&args[2], 1024, 768, Path::new(&args[3])) {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        "--dump" => {
+            // Write the display list of a URL to a JSON file
+            if args.len() < 4 {
+                eprintln!("Usage: {} --dump <URL> <OUT.json>", args[0]);
+                return ExitCode::FAILURE;
+            }
+            if let Err(e) = dump_display_list(&args[2], 1024, 768, Path::new(&args[3])) {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        "--replay" => {
+            // Paint a dumped display list in a window, or to a PNG file
+            if args.len() < 3 {
+                eprintln!("Usage: {} --replay <LIST.json> [OUT.png]", args[0]);
+                return ExitCode::FAILURE;
+            }
+            let list_path = Path::new(&args[2]);
+            let result = match args.get(3) {
+                Some(out) => replay_to_png(list_path, 1024, 768, Path::new(out)),
+                None => replay_in_window(list_path, 1024, 768),
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
         url_str => {
             // Text-only mode: fetch and display DOM tree
             if let Err(e) = fetch_and_display(url_str).await {
@@ -99,17 +148,28 @@ OPTIONS:
     -h, --help        Print this help message
     -V, --version     Print version information
     --demo            Run a rendering demo (Hello World)
-    --render <URL>    Render a URL in a window
+    --render [URL]    Render a URL in a window, the configured homepage
+                      without one
+    --restore         Reopen the tabs open when the browser last quit
     --file <PATH>     Render a local HTML file
+    --screenshot <URL> <OUT.png>
+                      Render a URL or local file to a PNG, without a window
+    --dump <URL> <OUT.json>
+                      Write the display list of a URL or local file to JSON
+    --replay <LIST.json> [OUT.png]
+                      Paint a dumped display list in a window, or to a PNG
 
 EXAMPLES:
     {} https://example.com
     {} --demo
     {} --render https://example.com
     {} --file test-pages/basic.html
+    {} --screenshot test-pages/basic.html basic.png
+    {} --dump test-pages/basic.html basic.json
+    {} --replay basic.json
 
 "#,
-        VERSION, program, program, program, program, program
+        VERSION, program, program, program, program, program, program, program, program
     );
 }
 
@@ -119,6 +179,7 @@ fn run_demo() -> Result<(), String> {
         title: "Gugalanna Demo".to_string(),
         width: 800,
         height: 600,
+        ..BrowserConfig::default()
     };
 
     let mut browser = Browser::new(config)?;
@@ -130,21 +191,36 @@ fn run_demo() -> Result<(), String> {
 }
 
 /// Run browser with a URL
-fn run_browser(url_str: &str) -> Result<(), String> {
+fn run_browser(url_str: Option<&str>) -> Result<(), String> {
     let config = BrowserConfig {
         title: "Gugalanna".to_string(),
-        width: 1024,
-        height: 768,
+        ..BrowserConfig::load()
     };
 
     let mut browser = Browser::new(config)?;
 
     // Navigate to the URL
-    browser.navigate(url_str)?;
+    match url_str {
+        Some(url_str) => browser.navigate(url_str)?,
+        None => browser.open_homepage(),
+    }
 
     browser.run()
 }
 
+/// Run the browser with the tabs of the last session
+fn run_restored() -> Result<(), String> {
+    let config = BrowserConfig {
+        title: "Gugalanna".to_string(),
+        restore_session: true,
+        ..BrowserConfig::load()
+    };
+
+    let mut browser = Browser::new(config)?;
+    browser.open_homepage();
+    browser.run()
+}
+
 /// Run browser with a local HTML file
 fn run_file(path_str: &str) -> Result<(), String> {
     let path = Path::new(path_str);
@@ -173,8 +249,7 @@ fn run_file(path_str: &str) -> Result<(), String> {
 
     let config = BrowserConfig {
         title: format!("Gugalanna - {}", title),
-        width: 1024,
-        height: 768,
+        ..BrowserConfig::load()
     };
 
     let mut browser = Browser::new(config)?;
@@ -186,36 +261,10 @@ fn run_file(path_str: &str) -> Result<(), String> {
 }
 
 /// Demo HTML content
-const DEMO_HTML: &str = r#"
-<html>
-<body>
-    <h1>Hello World!</h1>
-    <p>Welcome to Gugalanna, a browser built from scratch.</p>
-</body>
-</html>
-"#;
+const DEMO_HTML: &str = include_str!("../../../test-pages/demo.html");
 
 /// Demo CSS styling
-const DEMO_CSS: &str = r#"
-body {
-    background-color: white;
-    color: black;
-    font-size: 16px;
-}
-h1 {
-    display: block;
-    font-size: 32px;
-    color: #333333;
-    margin-top: 20px;
-    margin-bottom: 10px;
-    margin-left: 20px;
-}
-p {
-    display: block;
-    margin-left: 20px;
-    margin-top: 10px;
-}
-"#;
+const DEMO_CSS: &str = include_str!("../../../test-pages/demo.css");
 
 /// Text-only mode: Fetch a URL and display DOM tree
 async fn fetch_and_display(url_str: &str) -> Result<(), Box<dyn std::error::Error>> {
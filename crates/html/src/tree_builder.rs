@@ -31,6 +31,8 @@ pub struct HtmlParser {
     head_element: Option<NodeId>,
     form_element: Option<NodeId>,
     foster_parenting: bool,
+    /// Drop a newline right after <pre>, <listing> or <textarea>
+    skip_next_newline: bool,
 }
 
 impl HtmlParser {
@@ -43,6 +45,7 @@ impl HtmlParser {
             head_element: None,
             form_element: None,
             foster_parenting: false,
+            skip_next_newline: false,
         }
     }
 
@@ -63,6 +66,8 @@ impl HtmlParser {
 
     /// Process a single token
     fn process_token(&mut self, token: Token) -> HtmlResult<()> {
+        let skip_newline = std::mem::take(&mut self.skip_next_newline);
+
         match token {
             Token::Doctype { name, public_id, system_id, .. } => {
                 let doctype = self.tree.create_doctype(
@@ -81,6 +86,8 @@ impl HtmlParser {
                 self.handle_end_tag(&name)?;
             }
 
+            Token::Character('\n') if skip_newline => {}
+
             Token::Character(c) => {
                 self.handle_character(c)?;
             }
@@ -132,6 +139,7 @@ impl HtmlParser {
         match name {
             "head" => self.head_element = Some(element_id),
             "form" => self.form_element = Some(element_id),
+            "pre" | "listing" | "textarea" => self.skip_next_newline = true,
             _ => {}
         }
 
@@ -220,11 +228,17 @@ impl HtmlParser {
             }
         }
 
-        // Handle table-related implicit closes
-        if incoming_tag == "tr" {
+        // Handle table-related implicit closes, inserting the row groups and
+        // rows that were left out
+        if matches!(incoming_tag, "tbody" | "thead" | "tfoot") {
+            self.clear_stack_to_table_context();
+        } else if incoming_tag == "tr" {
             self.clear_stack_to_table_body_context();
+            self.insert_implied_element(&["table"], "tbody");
         } else if incoming_tag == "td" || incoming_tag == "th" {
             self.clear_stack_to_table_row_context();
+            self.insert_implied_element(&["table"], "tbody");
+            self.insert_implied_element(&["tbody", "thead", "tfoot"], "tr");
         }
     }
 
@@ -576,11 +590,23 @@ impl HtmlParser {
         }
     }
 
+    /// Clear stack to table context
+    fn clear_stack_to_table_context(&mut self) {
+        while let Some(&node_id) = self.open_elements.last() {
+            if let Some(tag) = self.get_tag_name(node_id) {
+                if matches!(tag.as_str(), "table" | "template" | "html") {
+                    break;
+                }
+            }
+            self.open_elements.pop();
+        }
+    }
+
     /// Clear stack to table body context
     fn clear_stack_to_table_body_context(&mut self) {
         while let Some(&node_id) = self.open_elements.last() {
             if let Some(tag) = self.get_tag_name(node_id) {
-                if matches!(tag.as_str(), "tbody" | "tfoot" | "thead" | "template" | "html") {
+                if matches!(tag.as_str(), "tbody" | "tfoot" | "thead" | "table" | "template" | "html") {
                     break;
                 }
             }
@@ -592,13 +618,23 @@ impl HtmlParser {
     fn clear_stack_to_table_row_context(&mut self) {
         while let Some(&node_id) = self.open_elements.last() {
             if let Some(tag) = self.get_tag_name(node_id) {
-                if matches!(tag.as_str(), "tr" | "template" | "html") {
+                if matches!(tag.as_str(), "tr" | "tbody" | "tfoot" | "thead" | "table" | "template" | "html") {
                     break;
                 }
             }
             self.open_elements.pop();
         }
     }
+
+    /// Insert and open an implied element if the current node is one of `parents`
+    fn insert_implied_element(&mut self, parents: &[&str], tag: &str) {
+        let current = self.current_node();
+        if self.get_tag_name(current).is_some_and(|t| parents.contains(&t.as_str())) {
+            let element = self.tree.create_element(tag);
+            self.tree.append_child(current, element).ok();
+            self.open_elements.push(element);
+        }
+    }
 }
 
 impl Default for HtmlParser {
@@ -779,6 +815,13 @@ mod tests {
 
     // === Implicit tag closing tests ===
 
+    #[test]
+    fn test_pre_leading_newline_ignored() {
+        let tree = parse("<pre>\n  code\n</pre>");
+        let pre = tree.get_elements_by_tag_name("pre")[0];
+        assert_eq!(tree.text_content(pre), "  code\n");
+    }
+
     #[test]
     fn test_implicit_p_close() {
         // <p> should be implicitly closed by block elements
@@ -1156,6 +1199,12 @@ mod tests {
 
         let tds = tree.get_elements_by_tag_name("td");
         assert_eq!(tds.len(), 1);
+
+        // The row gets an implied tbody inside the table
+        let tbody = tree.get_elements_by_tag_name("tbody")[0];
+        assert_eq!(tree.get(tbody).unwrap().parent, Some(tables[0]));
+        let tr = tree.get_elements_by_tag_name("tr")[0];
+        assert_eq!(tree.get(tr).unwrap().parent, Some(tbody));
     }
 
     // === List tests ===
@@ -0,0 +1,201 @@
+//! Class Lists
+//!
+//! Provides `element.classList`, a DOMTokenList over the element's `class`
+//! attribute. Tokens are checked and the attribute edited on the Rust side,
+//! changes are recorded in the mutation journal and for mutation observers.
+
+use gugalanna_dom::{check_class_token, DomResult, ElementData, NodeId};
+use rquickjs::{Ctx, Function, Object, Result};
+
+use crate::mutation_observer::{MutationRecord, SharedObservers};
+use crate::{dom_exception, MutationJournal, SharedDom};
+
+/// Edit the classes of an element, journaling it when its `class`
+/// attribute changed
+fn edit_classes<R: Default>(
+    dom: &SharedDom,
+    journal: &MutationJournal,
+    observers: &SharedObservers,
+    node_id: i32,
+    edit: impl FnOnce(&mut ElementData) -> DomResult<R>,
+) -> R {
+    let id = NodeId::new(node_id as u32);
+    let mut dom = dom.borrow_mut();
+    let Some(element) = dom.get_mut(id).and_then(|n| n.as_element_mut()) else {
+        return R::default();
+    };
+    let before = element.get_attribute("class").map(str::to_string);
+    // Tokens were checked before the edit was made
+    let result = edit(element).unwrap_or_default();
+    if element.get_attribute("class") != before.as_deref() {
+        journal.borrow_mut().push(id);
+        observers.borrow_mut().record(&dom, MutationRecord::attribute(id, "class", before));
+    }
+    result
+}
+
+/// Register the class list natives on `document` and the `classList`
+/// property of elements
+pub fn register_class_list<'js>(
+    ctx: &Ctx<'js>,
+    document: &Object<'js>,
+    dom: SharedDom,
+    journal: MutationJournal,
+    observers: SharedObservers,
+) -> Result<()> {
+    document.set(
+        "_classTokenError",
+        Function::new(ctx.clone(), |token: String| -> Option<String> {
+            check_class_token(&token).err().map(|e| dom_exception(&e))
+        })?,
+    )?;
+
+    let dom_clone = dom.clone();
+    document.set(
+        "_classList",
+        Function::new(ctx.clone(), move |node_id: i32| -> Vec<String> {
+            let dom = dom_clone.borrow();
+            dom.get(NodeId::new(node_id as u32))
+                .and_then(|n| n.as_element())
+                .map(|e| e.class_list().into_iter().map(str::to_string).collect())
+                .unwrap_or_default()
+        })?,
+    )?;
+
+    let (dom_clone, journal_clone, observers_clone) = (dom.clone(), journal.clone(), observers.clone());
+    document.set(
+        "_classListAdd",
+        Function::new(ctx.clone(), move |node_id: i32, classes: Vec<String>| {
+            let classes: Vec<&str> = classes.iter().map(String::as_str).collect();
+            edit_classes(&dom_clone, &journal_clone, &observers_clone, node_id, |e| e.add_classes(&classes));
+        })?,
+    )?;
+
+    let (dom_clone, journal_clone, observers_clone) = (dom.clone(), journal.clone(), observers.clone());
+    document.set(
+        "_classListRemove",
+        Function::new(ctx.clone(), move |node_id: i32, classes: Vec<String>| {
+            let classes: Vec<&str> = classes.iter().map(String::as_str).collect();
+            edit_classes(&dom_clone, &journal_clone, &observers_clone, node_id, |e| e.remove_classes(&classes));
+        })?,
+    )?;
+
+    let (dom_clone, journal_clone, observers_clone) = (dom.clone(), journal.clone(), observers.clone());
+    document.set(
+        "_classListToggle",
+        Function::new(ctx.clone(), move |node_id: i32, class: String, force: Option<bool>| -> bool {
+            edit_classes(&dom_clone, &journal_clone, &observers_clone, node_id, |e| e.toggle_class(&class, force))
+        })?,
+    )?;
+
+    let (dom_clone, journal_clone, observers_clone) = (dom, journal, observers);
+    document.set(
+        "_classListReplace",
+        Function::new(ctx.clone(), move |node_id: i32, old: String, new: String| -> bool {
+            edit_classes(&dom_clone, &journal_clone, &observers_clone, node_id, |e| e.replace_class(&old, &new))
+        })?,
+    )?;
+
+    // The list is read again on each access, so it follows changes made
+    // through className or setAttribute
+    let class_list_code = r#"
+        (function() {
+            function checkTokens(tokens) {
+                for (var i = 0; i < tokens.length; i++) {
+                    var error = document._classTokenError(tokens[i]);
+                    if (error !== undefined && error !== null) {
+                        var split = error.indexOf(':');
+                        var exception = new Error(error.slice(split + 1));
+                        exception.name = error.slice(0, split);
+                        throw exception;
+                    }
+                }
+            }
+
+            function tokensOf(args) {
+                return Array.prototype.map.call(args, String);
+            }
+
+            function DOMTokenList(nodeId) {
+                this.__nodeId = nodeId;
+            }
+
+            DOMTokenList.prototype.item = function(index) {
+                var value = document._classList(this.__nodeId)[Number(index) | 0];
+                return value === undefined ? null : value;
+            };
+
+            DOMTokenList.prototype.contains = function(token) {
+                return document._classList(this.__nodeId).indexOf(String(token)) !== -1;
+            };
+
+            DOMTokenList.prototype.add = function() {
+                var tokens = tokensOf(arguments);
+                checkTokens(tokens);
+                document._classListAdd(this.__nodeId, tokens);
+            };
+
+            DOMTokenList.prototype.remove = function() {
+                var tokens = tokensOf(arguments);
+                checkTokens(tokens);
+                document._classListRemove(this.__nodeId, tokens);
+            };
+
+            DOMTokenList.prototype.toggle = function(token, force) {
+                token = String(token);
+                checkTokens([token]);
+                return document._classListToggle(this.__nodeId, token, force === undefined ? undefined : !!force);
+            };
+
+            DOMTokenList.prototype.replace = function(token, newToken) {
+                token = String(token);
+                newToken = String(newToken);
+                checkTokens([token, newToken]);
+                return document._classListReplace(this.__nodeId, token, newToken);
+            };
+
+            DOMTokenList.prototype.forEach = function(callback, thisArg) {
+                var list = document._classList(this.__nodeId);
+                for (var i = 0; i < list.length; i++) {
+                    callback.call(thisArg, list[i], i, this);
+                }
+            };
+
+            DOMTokenList.prototype.toString = function() {
+                return document._getAttribute(this.__nodeId, 'class');
+            };
+
+            DOMTokenList.prototype[Symbol.iterator] = function() {
+                return document._classList(this.__nodeId)[Symbol.iterator]();
+            };
+
+            Object.defineProperty(DOMTokenList.prototype, 'length', {
+                get: function() { return document._classList(this.__nodeId).length; }
+            });
+
+            Object.defineProperty(DOMTokenList.prototype, 'value', {
+                get: function() { return document._getAttribute(this.__nodeId, 'class'); },
+                set: function(value) { document._setAttribute(this.__nodeId, 'class', String(value)); }
+            });
+
+            Object.defineProperty(Element.prototype, 'classList', {
+                get: function() {
+                    return new Proxy(new DOMTokenList(this.__nodeId), {
+                        get: function(target, prop) {
+                            if (typeof prop === 'string' && /^[0-9]+$/.test(prop)) {
+                                var value = target.item(prop);
+                                return value === null ? undefined : value;
+                            }
+                            return target[prop];
+                        }
+                    });
+                },
+                set: function(value) { this.classList.value = value; }
+            });
+
+            globalThis.DOMTokenList = DOMTokenList;
+        })();
+    "#;
+
+    ctx.eval::<(), _>(class_list_code)
+}
@@ -1,11 +1,27 @@
 //! Console API implementation
 //!
-//! Provides console.log, console.warn, console.error, etc.
+//! Provides console.log, console.warn, console.error, etc., along with
+//! console.table, console.group, console.time and console.clear, which
+//! empties the stored messages. Arguments are formatted
+//! on the Rust side: strings as they are, other values as the console
+//! shows them, nested objects cut at a depth and cycles marked. A first
+//! argument with %s, %d, %i, %f, %o, %O or %c is a format string for the
+//! arguments after it.
 
-use rquickjs::{Ctx, Function, Object, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+use rquickjs::{Array, Ctx, Function, Object, Result, Type, Value};
+
+/// Levels of nested objects shown, deeper ones are collapsed
+const MAX_DEPTH: usize = 3;
+
+/// Items of an array or properties of an object shown
+const MAX_ITEMS: usize = 100;
+
 /// Log level for console messages
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LogLevel {
@@ -16,14 +32,97 @@ pub enum LogLevel {
     Debug,
 }
 
+/// Where a message was logged from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+/// The script element whose code logged a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptOrigin {
+    pub node_id: u32,
+    /// URL of its file, None for inline scripts
+    pub url: Option<String>,
+}
+
+/// A piece of a console message
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsolePart {
+    /// Text of strings and format strings, with the CSS of the %c before it
+    Text { text: String, style: Option<String> },
+    /// A value other than a string, formatted
+    Value(String),
+    /// The rows of console.table, cells formatted, the first column the
+    /// index of the row
+    Table { columns: Vec<String>, rows: Vec<Vec<String>> },
+}
+
+impl ConsolePart {
+    fn plain(text: impl Into<String>) -> Self {
+        ConsolePart::Text { text: text.into(), style: None }
+    }
+
+    /// The part as the text of a message, tables with aligned columns
+    pub fn to_text(&self) -> String {
+        match self {
+            ConsolePart::Text { text, .. } | ConsolePart::Value(text) => text.clone(),
+            ConsolePart::Table { columns, rows } => render_table(columns, rows),
+        }
+    }
+}
+
 /// A captured console message
 #[derive(Debug, Clone)]
 pub struct ConsoleMessage {
     pub level: LogLevel,
+    /// The text of the parts, one after another
     pub message: String,
+    pub parts: Vec<ConsolePart>,
+    /// Groups the message was logged in, 0 outside of console.group
+    pub group_depth: usize,
+    /// Where the message was logged from, when known
+    pub source: Option<SourceLocation>,
+    /// Stack of an uncaught error, a frame per line
+    pub stack: Option<String>,
+    /// The script running when the message was logged, for uncaught errors
+    pub script: Option<ScriptOrigin>,
     pub timestamp: Instant,
 }
 
+impl ConsoleMessage {
+    /// A message of plain text, as the browser logs for pages
+    pub fn text(level: LogLevel, message: impl Into<String>) -> Self {
+        Self::from_parts(level, vec![ConsolePart::plain(message)], 0, None)
+    }
+
+    /// An error no script caught, located where it was thrown
+    pub(crate) fn uncaught(message: String, stack: Option<String>, script: Option<ScriptOrigin>) -> Self {
+        let source = stack.as_deref().and_then(|stack| frame_location(stack.lines().next()?));
+        Self { stack, script, ..Self::from_parts(LogLevel::Error, vec![ConsolePart::plain(message)], 0, source) }
+    }
+
+    fn from_parts(
+        level: LogLevel,
+        parts: Vec<ConsolePart>,
+        group_depth: usize,
+        source: Option<SourceLocation>,
+    ) -> Self {
+        Self {
+            level,
+            message: parts.iter().map(ConsolePart::to_text).collect(),
+            parts,
+            group_depth,
+            source,
+            stack: None,
+            script: None,
+            timestamp: Instant::now(),
+        }
+    }
+}
+
 /// Shared console message storage
 pub type ConsoleMessages = Arc<Mutex<Vec<ConsoleMessage>>>;
 
@@ -32,100 +131,518 @@ pub fn new_console_messages() -> ConsoleMessages {
     Arc::new(Mutex::new(Vec::new()))
 }
 
-/// Register the console object in the global scope with message storage
-pub fn register_console(ctx: &Ctx<'_>, messages: ConsoleMessages) -> Result<()> {
-    let globals = ctx.globals();
+/// A JS value as the console shows it
+#[derive(Debug, Clone, PartialEq)]
+enum Inspected {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Function(String),
+    Error { name: String, message: String },
+    /// A DOM element, by tag and id
+    Element { tag: String, id: Option<String> },
+    /// Items shown and how many more there are
+    Array { items: Vec<Inspected>, more: usize },
+    Object { entries: Vec<(String, Inspected)>, more: usize },
+    /// An object inside itself
+    Circular,
+    /// An array or object nested deeper than MAX_DEPTH
+    Collapsed { array: bool },
+    Other(String),
+}
 
-    let console = Object::new(ctx.clone())?;
+/// Take what the console shows of a value, `ancestors` being the objects
+/// it is nested in
+fn inspect<'js>(value: &Value<'js>, ancestors: &mut Vec<Value<'js>>) -> Inspected {
+    match value.type_of() {
+        Type::Undefined => return Inspected::Undefined,
+        Type::Null => return Inspected::Null,
+        Type::Bool => return Inspected::Bool(value.as_bool().unwrap_or_default()),
+        Type::Int => return Inspected::Number(value.as_int().unwrap_or_default() as f64),
+        Type::Float => return Inspected::Number(value.as_float().unwrap_or(f64::NAN)),
+        Type::String => {
+            return Inspected::String(value.as_string().and_then(|s| s.to_string().ok()).unwrap_or_default())
+        }
+        Type::Symbol => return Inspected::Other("Symbol()".to_string()),
+        _ => {}
+    }
+    let Some(object) = value.as_object() else {
+        return Inspected::Other("[value]".to_string());
+    };
+    let text = |key: &str| object.get::<_, Option<String>>(key).ok().flatten();
 
-    // console.log - simplified to accept string arguments
-    let log_messages = messages.clone();
-    console.set(
-        "log",
-        Function::new(ctx.clone(), move |msg: String| {
-            log::info!("[JS] {}", msg);
-            println!("[console.log] {}", msg);
-            if let Ok(mut msgs) = log_messages.lock() {
-                msgs.push(ConsoleMessage {
-                    level: LogLevel::Log,
-                    message: msg,
-                    timestamp: Instant::now(),
-                });
+    if value.is_function() {
+        return Inspected::Function(text("name").unwrap_or_default());
+    }
+    // Errors carry their stack
+    if text("stack").is_some() {
+        return Inspected::Error {
+            name: text("name").unwrap_or_else(|| "Error".to_string()),
+            message: text("message").unwrap_or_default(),
+        };
+    }
+    if object.get::<_, Option<i32>>("__nodeId").ok().flatten().is_some() {
+        if let Some(tag) = text("tagName") {
+            let id = text("id").filter(|id| !id.is_empty());
+            return Inspected::Element { tag: tag.to_lowercase(), id };
+        }
+    }
+
+    if ancestors.contains(value) {
+        return Inspected::Circular;
+    }
+    if ancestors.len() >= MAX_DEPTH {
+        return Inspected::Collapsed { array: value.is_array() };
+    }
+
+    ancestors.push(value.clone());
+    let inspected = match value.as_array() {
+        Some(array) => Inspected::Array {
+            items: array
+                .iter::<Value>()
+                .take(MAX_ITEMS)
+                .map(|item| item.map_or(Inspected::Undefined, |item| inspect(&item, ancestors)))
+                .collect(),
+            more: array.len().saturating_sub(MAX_ITEMS),
+        },
+        None => {
+            let keys: Vec<String> = object.keys::<String>().filter_map(|key| key.ok()).collect();
+            Inspected::Object {
+                entries: keys
+                    .iter()
+                    .take(MAX_ITEMS)
+                    .map(|key| {
+                        let item = object.get::<_, Value>(key.as_str());
+                        (key.clone(), item.map_or(Inspected::Undefined, |item| inspect(&item, ancestors)))
+                    })
+                    .collect(),
+                more: keys.len().saturating_sub(MAX_ITEMS),
             }
-        })?,
-    )?;
+        }
+    };
+    ancestors.pop();
+    inspected
+}
 
-    // console.warn
-    let warn_messages = messages.clone();
-    console.set(
-        "warn",
-        Function::new(ctx.clone(), move |msg: String| {
-            log::warn!("[JS] {}", msg);
-            println!("[console.warn] {}", msg);
-            if let Ok(mut msgs) = warn_messages.lock() {
-                msgs.push(ConsoleMessage {
-                    level: LogLevel::Warn,
-                    message: msg,
-                    timestamp: Instant::now(),
-                });
+fn format_number(n: f64) -> String {
+    if n.is_infinite() {
+        if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if n == 0.0 && n.is_sign_negative() {
+        "-0".to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+/// Property names that need no quotes
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'").replace('\n', "\\n"))
+}
+
+/// Format a value, strings quoted when nested in another
+fn format_value(value: &Inspected, nested: bool) -> String {
+    let more = |count: usize| format!("… {} more", count);
+    match value {
+        Inspected::Undefined => "undefined".to_string(),
+        Inspected::Null => "null".to_string(),
+        Inspected::Bool(b) => b.to_string(),
+        Inspected::Number(n) => format_number(*n),
+        Inspected::String(s) if nested => quote(s),
+        Inspected::String(s) => s.clone(),
+        Inspected::Function(name) if name.is_empty() => "[Function (anonymous)]".to_string(),
+        Inspected::Function(name) => format!("[Function: {}]", name),
+        Inspected::Error { name, message } if message.is_empty() => name.clone(),
+        Inspected::Error { name, message } => format!("{}: {}", name, message),
+        Inspected::Element { tag, id: Some(id) } => format!("<{}#{}>", tag, id),
+        Inspected::Element { tag, id: None } => format!("<{}>", tag),
+        Inspected::Array { items, more: hidden } => {
+            let mut shown: Vec<String> = items.iter().map(|item| format_value(item, true)).collect();
+            if *hidden > 0 {
+                shown.push(more(*hidden));
             }
-        })?,
-    )?;
+            format!("[{}]", shown.join(", "))
+        }
+        Inspected::Object { entries, more: hidden } => {
+            let mut shown: Vec<String> = entries
+                .iter()
+                .map(|(key, item)| {
+                    let key = if is_identifier(key) { key.clone() } else { quote(key) };
+                    format!("{}: {}", key, format_value(item, true))
+                })
+                .collect();
+            if *hidden > 0 {
+                shown.push(more(*hidden));
+            }
+            if shown.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{{}}}", shown.join(", "))
+            }
+        }
+        Inspected::Circular => "[Circular]".to_string(),
+        Inspected::Collapsed { array: true } => "[Array]".to_string(),
+        Inspected::Collapsed { array: false } => "[Object]".to_string(),
+        Inspected::Other(text) => text.clone(),
+    }
+}
+
+/// Number a value is taken as by %d and %f
+fn to_number(value: &Inspected) -> f64 {
+    match value {
+        Inspected::Number(n) => *n,
+        Inspected::Bool(b) => f64::from(u8::from(*b)),
+        Inspected::Null => 0.0,
+        Inspected::String(s) => s.trim().parse().unwrap_or(f64::NAN),
+        _ => f64::NAN,
+    }
+}
+
+/// The parts of the arguments of a console call
+fn format_args(args: &[Inspected]) -> Vec<ConsolePart> {
+    let mut parts = Vec::new();
+    let mut rest = args;
 
-    // console.error
-    let error_messages = messages.clone();
-    console.set(
-        "error",
-        Function::new(ctx.clone(), move |msg: String| {
-            log::error!("[JS] {}", msg);
-            eprintln!("[console.error] {}", msg);
-            if let Ok(mut msgs) = error_messages.lock() {
-                msgs.push(ConsoleMessage {
-                    level: LogLevel::Error,
-                    message: msg,
-                    timestamp: Instant::now(),
-                });
+    // A first string is a format string for the arguments after it
+    if let Some((Inspected::String(format), after)) = args.split_first() {
+        rest = after;
+        let mut text = String::new();
+        let mut style = None;
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            let spec = match (c, chars.peek()) {
+                ('%', Some(&spec)) if "sdifoOc%".contains(spec) => spec,
+                _ => {
+                    text.push(c);
+                    continue;
+                }
+            };
+            chars.next();
+            if spec == '%' {
+                text.push('%');
+                continue;
             }
-        })?,
-    )?;
+            let Some((arg, after)) = rest.split_first() else {
+                // Nothing left to substitute
+                text.push('%');
+                text.push(spec);
+                continue;
+            };
+            rest = after;
+            match spec {
+                's' => text.push_str(&format_value(arg, false)),
+                'd' | 'i' => {
+                    let n = to_number(arg);
+                    text.push_str(&if n.is_finite() { format_number(n.trunc()) } else { "NaN".to_string() });
+                }
+                'f' => text.push_str(&format_number(to_number(arg))),
+                'o' | 'O' => text.push_str(&format_value(arg, true)),
+                // Later text is styled with the CSS
+                _ => {
+                    if !text.is_empty() {
+                        parts.push(ConsolePart::Text { text: std::mem::take(&mut text), style: style.clone() });
+                    }
+                    style = Some(format_value(arg, false)).filter(|css| !css.trim().is_empty());
+                }
+            }
+        }
+        if !text.is_empty() || parts.is_empty() {
+            parts.push(ConsolePart::Text { text, style });
+        }
+    }
+
+    for arg in rest {
+        if !parts.is_empty() {
+            parts.push(ConsolePart::plain(" "));
+        }
+        parts.push(match arg {
+            Inspected::String(s) => ConsolePart::plain(s.clone()),
+            other => ConsolePart::Value(format_value(other, false)),
+        });
+    }
+    parts
+}
+
+/// The table console.table shows of an array or object, None for other
+/// values
+fn table(data: &Inspected, only: Option<&[String]>) -> Option<ConsolePart> {
+    let rows: Vec<(String, &Inspected)> = match data {
+        Inspected::Array { items, .. } => items.iter().enumerate().map(|(i, item)| (i.to_string(), item)).collect(),
+        Inspected::Object { entries, .. } => entries.iter().map(|(key, item)| (key.clone(), item)).collect(),
+        _ => return None,
+    };
 
-    // console.info (alias for log)
-    let info_messages = messages.clone();
-    console.set(
-        "info",
-        Function::new(ctx.clone(), move |msg: String| {
-            log::info!("[JS] {}", msg);
-            println!("[console.info] {}", msg);
-            if let Ok(mut msgs) = info_messages.lock() {
-                msgs.push(ConsoleMessage {
-                    level: LogLevel::Info,
-                    message: msg,
-                    timestamp: Instant::now(),
-                });
+    // Columns are the properties of the rows, in the order first seen,
+    // and Values for rows that are not objects
+    let cells = |row: &Inspected| -> Option<Vec<(String, String)>> {
+        match row {
+            Inspected::Object { entries, .. } => {
+                Some(entries.iter().map(|(key, item)| (key.clone(), format_value(item, true))).collect())
             }
-        })?,
-    )?;
+            Inspected::Array { items, .. } => {
+                Some(items.iter().enumerate().map(|(i, item)| (i.to_string(), format_value(item, true))).collect())
+            }
+            _ => None,
+        }
+    };
+    let mut columns: Vec<String> = Vec::new();
+    let mut values = false;
+    for (_, row) in &rows {
+        match cells(row) {
+            Some(cells) => {
+                for (key, _) in cells {
+                    if !columns.contains(&key) {
+                        columns.push(key);
+                    }
+                }
+            }
+            None => values = true,
+        }
+    }
+    if let Some(only) = only {
+        columns = only.to_vec();
+    }
 
-    // console.debug
-    let debug_messages = messages.clone();
-    console.set(
-        "debug",
-        Function::new(ctx.clone(), move |msg: String| {
-            log::debug!("[JS] {}", msg);
-            println!("[console.debug] {}", msg);
-            if let Ok(mut msgs) = debug_messages.lock() {
-                msgs.push(ConsoleMessage {
-                    level: LogLevel::Debug,
-                    message: msg,
-                    timestamp: Instant::now(),
-                });
+    let table_rows = rows
+        .iter()
+        .map(|(index, row)| {
+            let row_cells = cells(row);
+            let mut line = vec![index.clone()];
+            for column in &columns {
+                let cell = row_cells.as_ref().and_then(|cells| cells.iter().find(|(key, _)| key == column));
+                line.push(cell.map(|(_, text)| text.clone()).unwrap_or_default());
+            }
+            if values {
+                line.push(if row_cells.is_some() { String::new() } else { format_value(row, true) });
+            }
+            line
+        })
+        .collect();
+
+    let mut header = vec!["(index)".to_string()];
+    header.extend(columns);
+    if values {
+        header.push("Values".to_string());
+    }
+    Some(ConsolePart::Table { columns: header, rows: table_rows })
+}
+
+/// A table as text, its columns aligned
+fn render_table(columns: &[String], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            std::iter::once(&columns[i])
+                .chain(rows.iter().filter_map(|row| row.get(i)))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    // Empty cells at the end of a row are left out
+    let line = |cells: &[String]| {
+        let used = cells.iter().rposition(|cell| !cell.is_empty()).map_or(0, |i| i + 1);
+        let padded: Vec<String> =
+            cells[..used].iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        padded.join(" | ").trim_end().to_string()
+    };
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+
+    let mut lines = vec![line(columns), separator.join("-+-")];
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+/// The location of the caller of a console method, from a stack taken in
+/// the method, as `    at foo (script.js:12:5)`
+fn caller_location(stack: &str) -> Option<SourceLocation> {
+    frame_location(stack.lines().nth(1)?)
+}
+
+/// Location of a frame of a stack, as `at f (file.js:3:5)`
+fn frame_location(frame: &str) -> Option<SourceLocation> {
+    let frame = frame.trim().strip_prefix("at ")?;
+    let place = match (frame.rfind('('), frame.rfind(')')) {
+        (Some(open), Some(close)) if open < close => &frame[open + 1..close],
+        _ => frame,
+    };
+    let mut pieces = place.rsplitn(3, ':');
+    let last: u32 = pieces.next()?.parse().ok()?;
+    let before = pieces.next()?;
+    match before.parse::<u32>() {
+        Ok(line) => Some(SourceLocation { file: pieces.next()?.to_string(), line, column: Some(last) }),
+        Err(_) => {
+            let file = match pieces.next() {
+                Some(start) => format!("{}:{}", start, before),
+                None => before.to_string(),
+            };
+            Some(SourceLocation { file, line: last, column: None })
+        }
+    }
+}
+
+/// Groups and timers of a console
+#[derive(Debug, Default)]
+struct ConsoleState {
+    group_depth: usize,
+    /// When each console.time timer started, by label
+    timers: HashMap<String, Instant>,
+}
+
+impl ConsoleState {
+    /// Handle a call of a console method, returning the message it logs
+    fn call(&mut self, method: &str, args: &[Inspected], now: Instant) -> Option<(LogLevel, Vec<ConsolePart>, usize)> {
+        let depth = self.group_depth;
+        let label = || match args.first() {
+            None | Some(Inspected::Undefined) => "default".to_string(),
+            Some(label) => format_value(label, false),
+        };
+        let logged = |level, parts| Some((level, parts, depth));
+        let warned = |text: String| Some((LogLevel::Warn, vec![ConsolePart::plain(text)], depth));
+
+        match method {
+            "log" => logged(LogLevel::Log, format_args(args)),
+            "info" => logged(LogLevel::Info, format_args(args)),
+            "warn" => logged(LogLevel::Warn, format_args(args)),
+            "error" => logged(LogLevel::Error, format_args(args)),
+            "debug" => logged(LogLevel::Debug, format_args(args)),
+            "table" => {
+                let only = match args.get(1) {
+                    Some(Inspected::Array { items, .. }) => {
+                        Some(items.iter().map(|item| format_value(item, false)).collect::<Vec<_>>())
+                    }
+                    _ => None,
+                };
+                match args.first().and_then(|data| table(data, only.as_deref())) {
+                    Some(part) => logged(LogLevel::Log, vec![part]),
+                    None => logged(LogLevel::Log, format_args(args)),
+                }
+            }
+            "group" | "groupCollapsed" => {
+                self.group_depth += 1;
+                let parts = if args.is_empty() { vec![ConsolePart::plain("console.group")] } else { format_args(args) };
+                logged(LogLevel::Log, parts)
+            }
+            "groupEnd" => {
+                self.group_depth = self.group_depth.saturating_sub(1);
+                None
+            }
+            "clear" => {
+                self.group_depth = 0;
+                None
+            }
+            "time" => {
+                let label = label();
+                if self.timers.contains_key(&label) {
+                    return warned(format!("Timer '{}' already exists", label));
+                }
+                self.timers.insert(label, now);
+                None
+            }
+            "timeLog" | "timeEnd" => {
+                let label = label();
+                let started = match method {
+                    "timeEnd" => self.timers.remove(&label),
+                    _ => self.timers.get(&label).copied(),
+                };
+                let Some(started) = started else {
+                    return warned(format!("Timer '{}' does not exist", label));
+                };
+                let elapsed = now.duration_since(started).as_secs_f64() * 1000.0;
+                let mut parts = vec![ConsolePart::plain(format!("{}: {:.3} ms", label, elapsed))];
+                for arg in args.iter().skip(1) {
+                    parts.push(ConsolePart::plain(" "));
+                    parts.push(ConsolePart::Value(format_value(arg, false)));
+                }
+                logged(LogLevel::Log, parts)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Store a message, and echo it to the log and the terminal
+pub(crate) fn record(messages: &ConsoleMessages, message: ConsoleMessage) {
+    let text = format!("{}{}", "  ".repeat(message.group_depth), message.message);
+    match message.level {
+        LogLevel::Log => {
+            log::info!("[JS] {}", text);
+            println!("[console.log] {}", text);
+        }
+        LogLevel::Info => {
+            log::info!("[JS] {}", text);
+            println!("[console.info] {}", text);
+        }
+        LogLevel::Warn => {
+            log::warn!("[JS] {}", text);
+            println!("[console.warn] {}", text);
+        }
+        LogLevel::Error => {
+            log::error!("[JS] {}", text);
+            eprintln!("[console.error] {}", text);
+        }
+        LogLevel::Debug => {
+            log::debug!("[JS] {}", text);
+            println!("[console.debug] {}", text);
+        }
+    }
+    if let Ok(mut msgs) = messages.lock() {
+        msgs.push(message);
+    }
+}
+
+/// Register the console object in the global scope with message storage
+pub fn register_console<'js>(ctx: &Ctx<'js>, messages: ConsoleMessages) -> Result<()> {
+    let globals = ctx.globals();
+    let state = Rc::new(RefCell::new(ConsoleState::default()));
+
+    // Methods pass the stack they were called with, for the caller's
+    // location
+    globals.set(
+        "__consoleWrite",
+        Function::new(ctx.clone(), move |method: String, stack: Option<String>, args: Array<'js>| {
+            let mut ancestors = Vec::new();
+            let args: Vec<Inspected> = args
+                .iter::<Value>()
+                .map(|arg| arg.map_or(Inspected::Undefined, |arg| inspect(&arg, &mut ancestors)))
+                .collect();
+            if method == "clear" {
+                if let Ok(mut msgs) = messages.lock() {
+                    msgs.clear();
+                }
+            }
+            let call = state.borrow_mut().call(&method, &args, Instant::now());
+            if let Some((level, parts, depth)) = call {
+                let source = stack.as_deref().and_then(caller_location);
+                record(&messages, ConsoleMessage::from_parts(level, parts, depth, source));
             }
         })?,
     )?;
 
+    let console = Object::new(ctx.clone())?;
     globals.set("console", console)?;
 
-    Ok(())
+    let console_code = r#"
+        (function() {
+            var write = __consoleWrite;
+            [
+                'log', 'info', 'warn', 'error', 'debug', 'table',
+                'group', 'groupCollapsed', 'groupEnd', 'time', 'timeLog', 'timeEnd', 'clear'
+            ].forEach(function(method) {
+                console[method] = function() {
+                    write(method, new Error().stack, Array.prototype.slice.call(arguments));
+                };
+            });
+        })();
+    "#;
+
+    ctx.eval::<(), _>(console_code)
 }
 
 #[cfg(test)]
@@ -192,4 +709,152 @@ mod tests {
         assert_eq!(msgs[3].level, LogLevel::Error);
         assert_eq!(msgs[4].level, LogLevel::Debug);
     }
+
+    /// Run code against a fresh console and return what it logged
+    fn console_output(code: &str) -> Vec<ConsoleMessage> {
+        let rt = Runtime::new().unwrap();
+        let ctx = rquickjs::Context::full(&rt).unwrap();
+
+        let messages = new_console_messages();
+        ctx.with(|ctx| {
+            register_console(&ctx, messages.clone()).unwrap();
+            let _: () = ctx.eval(code).unwrap();
+        });
+        let msgs = messages.lock().unwrap();
+        msgs.clone()
+    }
+
+    #[test]
+    fn test_nested_objects() {
+        let msgs = console_output(r#"
+            console.log({a: 1}, [1, 2, 3]);
+            console.log({name: 'x', 'data-id': null, list: [true, undefined], f: function go() {}});
+            console.log({a: {b: {c: {d: 1}}}}, new Array(102).fill(0).length);
+            console.log('error:', new TypeError('bad'));
+        "#);
+        assert_eq!(msgs[0].message, "{a: 1} [1, 2, 3]");
+        assert_eq!(msgs[0].parts[0], ConsolePart::Value("{a: 1}".to_string()));
+        assert_eq!(msgs[1].message, "{name: 'x', 'data-id': null, list: [true, undefined], f: [Function: go]}");
+        assert_eq!(msgs[2].message, "{a: {b: {c: [Object]}}} 102");
+        assert_eq!(msgs[3].message, "error: TypeError: bad");
+
+        let long = console_output("console.log(new Array(105).fill(1))");
+        assert!(long[0].message.ends_with("1, 1, … 5 more]"));
+    }
+
+    #[test]
+    fn test_cyclic_references() {
+        let msgs = console_output(r#"
+            var node = {name: 'root', children: []};
+            node.children.push({name: 'leaf', parent: node});
+            node.self = node;
+            console.log(node);
+            var shared = {x: 1};
+            console.log([shared, shared]);
+        "#);
+        assert_eq!(
+            msgs[0].message,
+            "{name: 'root', children: [{name: 'leaf', parent: [Circular]}], self: [Circular]}"
+        );
+        // The same object twice is not a cycle
+        assert_eq!(msgs[1].message, "[{x: 1}, {x: 1}]");
+    }
+
+    #[test]
+    fn test_format_specifiers() {
+        let msgs = console_output(r#"
+            console.log('%s has %d items (%i%%), %f kg', 'cart', 3.7, '42', 1.5, 'extra');
+            console.log('%cBig%c small', 'font-size: 20px', '', {a: 1});
+            console.log('%o and %s', 'quoted', {b: 2});
+            console.log('100%', 'sure');
+        "#);
+        assert_eq!(msgs[0].message, "cart has 3 items (42%), 1.5 kg extra");
+        assert_eq!(msgs[1].message, "Big small {a: 1}");
+        assert_eq!(
+            msgs[1].parts[..2],
+            [
+                ConsolePart::Text { text: "Big".to_string(), style: Some("font-size: 20px".to_string()) },
+                ConsolePart::Text { text: " small".to_string(), style: None },
+            ]
+        );
+        assert_eq!(msgs[2].message, "'quoted' and {b: 2}");
+        assert_eq!(msgs[3].message, "100% sure");
+    }
+
+    #[test]
+    fn test_console_table() {
+        let msgs = console_output(r#"
+            console.table([{name: 'Ada', age: 36}, {name: 'Alan'}, 7]);
+            console.table({x: {v: 1}}, ['v']);
+        "#);
+        assert_eq!(
+            msgs[0].message,
+            "(index) | name   | age | Values\n\
+             --------+--------+-----+-------\n\
+             0       | 'Ada'  | 36\n\
+             1       | 'Alan'\n\
+             2       |        |     | 7"
+        );
+        assert!(matches!(&msgs[1].parts[0], ConsolePart::Table { columns, .. } if columns == &["(index)", "v"]));
+    }
+
+    #[test]
+    fn test_groups_and_timers() {
+        let msgs = console_output(r#"
+            console.group('Outer');
+            console.log('in outer');
+            console.groupCollapsed();
+            console.log('in inner');
+            console.groupEnd();
+            console.groupEnd();
+            console.groupEnd();
+            console.log('outside');
+            console.time('load');
+            console.timeEnd('load');
+            console.timeEnd('load');
+        "#);
+        let depths: Vec<_> = msgs.iter().map(|m| (m.message.as_str(), m.group_depth)).collect();
+        assert_eq!(
+            depths[..5],
+            [("Outer", 0), ("in outer", 1), ("console.group", 1), ("in inner", 2), ("outside", 0)]
+        );
+        assert!(msgs[5].message.starts_with("load: ") && msgs[5].message.ends_with(" ms"));
+        assert_eq!((msgs[6].level, msgs[6].message.as_str()), (LogLevel::Warn, "Timer 'load' does not exist"));
+    }
+
+    #[test]
+    fn test_clear_empties_messages() {
+        let msgs = console_output(r#"
+            console.group('Outer');
+            console.log('before');
+            console.clear();
+            console.log('after');
+        "#);
+        let logged: Vec<_> = msgs.iter().map(|m| (m.message.as_str(), m.group_depth)).collect();
+        assert_eq!(logged, [("after", 0)]);
+    }
+
+    #[test]
+    fn test_timer_uses_real_time() {
+        let mut state = ConsoleState::default();
+        let start = Instant::now();
+        assert_eq!(state.call("time", &[], start), None);
+        let (_, parts, _) = state.call("timeEnd", &[], start + std::time::Duration::from_millis(1500)).unwrap();
+        assert_eq!(parts[0].to_text(), "default: 1500.000 ms");
+    }
+
+    #[test]
+    fn test_caller_location() {
+        let stack = "    at <anonymous> (<console>:9:21)\n    at render (app.js:12:5)\n";
+        assert_eq!(
+            caller_location(stack),
+            Some(SourceLocation { file: "app.js".to_string(), line: 12, column: Some(5) })
+        );
+        let stack = "    at <anonymous> (<console>:9)\n    at <eval> (http://a.test/app.js:3)\n";
+        assert_eq!(
+            caller_location(stack),
+            Some(SourceLocation { file: "http://a.test/app.js".to_string(), line: 3, column: None })
+        );
+        assert_eq!(caller_location("    at <anonymous> (<console>:9)\n"), None);
+    }
 }
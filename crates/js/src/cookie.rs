@@ -0,0 +1,70 @@
+//! Cookies
+//!
+//! Provides `document.cookie` over the embedder's cookie jar, for the URL
+//! of the page's current history entry. Reading it gives the cookies that
+//! URL sends but those marked HttpOnly; writing it sets one cookie from a
+//! `Set-Cookie` style string, which the embedder may refuse. Without a jar
+//! the page has no cookies.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rquickjs::{Ctx, Function, Object, Result};
+use url::Url;
+
+use crate::history::SharedHistory;
+
+/// Reads and sets the cookies of pages
+pub trait CookieAccess {
+    /// The cookies of `url` scripts may see, as `name=value` pairs joined
+    /// by `; `
+    fn cookies(&self, url: &Url) -> String;
+
+    /// Set a cookie for `url` from a `Set-Cookie` style string, refusing
+    /// HttpOnly cookies and cookies for other domains
+    fn set_cookie(&self, url: &Url, cookie: &str);
+}
+
+/// Shared reference to the embedder's cookie access
+pub type SharedCookieAccess = Rc<dyn CookieAccess>;
+
+/// The cookie access set by the embedder, None before it does
+pub(crate) type CookieHandle = Rc<RefCell<Option<SharedCookieAccess>>>;
+
+/// Register the cookie natives and `document.cookie`
+pub fn register_cookies<'js>(
+    ctx: &Ctx<'js>,
+    document: &Object<'js>,
+    cookies: CookieHandle,
+    history: SharedHistory,
+) -> Result<()> {
+    let (access, view) = (cookies.clone(), history.clone());
+    document.set(
+        "_getCookie",
+        Function::new(ctx.clone(), move || -> String {
+            let access = access.borrow().clone();
+            match (access, view.borrow().url()) {
+                (Some(access), Some(url)) => access.cookies(url),
+                _ => String::new(),
+            }
+        })?,
+    )?;
+    document.set(
+        "_setCookie",
+        Function::new(ctx.clone(), move |cookie: String| {
+            let access = cookies.borrow().clone();
+            if let (Some(access), Some(url)) = (access, history.borrow().url()) {
+                access.set_cookie(url, &cookie);
+            }
+        })?,
+    )?;
+
+    let cookie_code = r#"
+        Object.defineProperty(document, 'cookie', {
+            get: function() { return document._getCookie(); },
+            set: function(value) { document._setCookie(String(value)); }
+        });
+    "#;
+
+    ctx.eval::<(), _>(cookie_code)
+}
@@ -0,0 +1,171 @@
+//! Dialogs
+//!
+//! Provides `alert()`, `confirm()` and `prompt()`. Each call waits for the
+//! embedder's dialog handler to return how the user answered; without a
+//! handler the page gets the answer of a dismissed dialog. Messages are
+//! capped, and a page opening dialogs in quick succession gets that answer
+//! without them showing, so it cannot lock the user out.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use rquickjs::{Ctx, Function, Result};
+
+use crate::limits::SharedLimits;
+
+/// Characters of a message shown, the rest is cut
+pub const MAX_DIALOG_MESSAGE: usize = 1000;
+
+/// A dialog opened within this long of the last one closing continues a
+/// streak
+const DIALOG_STREAK_GAP: Duration = Duration::from_secs(1);
+
+/// Dialogs of a streak shown before the rest are suppressed
+const MAX_DIALOG_STREAK: u32 = 3;
+
+/// Which of the dialogs a script opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogKind {
+    Alert,
+    Confirm,
+    Prompt,
+}
+
+/// A dialog for the embedder to show
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogRequest {
+    pub kind: DialogKind,
+    pub message: String,
+    /// Text the field of a prompt starts with
+    pub default_value: String,
+}
+
+/// Shows the dialogs of a page
+pub trait DialogHandler {
+    /// Show a dialog until the user answers it: Some with the text of the
+    /// field when accepted, empty but for prompts, None when dismissed
+    fn show(&self, request: &DialogRequest) -> Option<String>;
+}
+
+/// Shared reference to the embedder's dialog handler
+pub type SharedDialogHandler = Rc<dyn DialogHandler>;
+
+/// The handler set by the embedder, None before it does
+pub(crate) type DialogHandle = Rc<RefCell<Option<SharedDialogHandler>>>;
+
+/// Counts dialogs opened in quick succession
+#[derive(Debug, Default)]
+struct DialogLimiter {
+    last_closed: Option<Instant>,
+    streak: u32,
+}
+
+impl DialogLimiter {
+    /// Check if a dialog opening at `now` may show
+    fn allow(&mut self, now: Instant) -> bool {
+        match self.last_closed {
+            Some(closed) if now.duration_since(closed) < DIALOG_STREAK_GAP => self.streak += 1,
+            _ => self.streak = 0,
+        }
+        self.streak < MAX_DIALOG_STREAK
+    }
+
+    fn closed(&mut self, now: Instant) {
+        self.last_closed = Some(now);
+    }
+}
+
+/// A message cut to MAX_DIALOG_MESSAGE characters
+fn cap_message(message: &str) -> String {
+    match message.char_indices().nth(MAX_DIALOG_MESSAGE) {
+        Some((end, _)) => format!("{}…", &message[..end]),
+        None => message.to_string(),
+    }
+}
+
+/// Register `alert`, `confirm` and `prompt` in the global scope
+pub fn register_dialogs(ctx: &Ctx<'_>, handler: DialogHandle, limits: SharedLimits) -> Result<()> {
+    let limiter = RefCell::new(DialogLimiter::default());
+    ctx.globals().set(
+        "__showDialog",
+        Function::new(ctx.clone(), move |kind: String, message: String, default_value: String| -> Option<String> {
+            let kind = match kind.as_str() {
+                "confirm" => DialogKind::Confirm,
+                "prompt" => DialogKind::Prompt,
+                _ => DialogKind::Alert,
+            };
+            // Out of the cell, the handler may run scripts while it waits
+            let handler = handler.borrow().clone()?;
+            if !limiter.borrow_mut().allow(Instant::now()) {
+                // Counts as closing, a page that keeps trying stays suppressed
+                limiter.borrow_mut().closed(Instant::now());
+                log::warn!("Suppressed a {:?} dialog opened in quick succession", kind);
+                return None;
+            }
+            let request = DialogRequest { kind, message: cap_message(&message), default_value };
+            // The user taking their time does not count against the script
+            let answer = limits.paused(|| handler.show(&request));
+            limiter.borrow_mut().closed(Instant::now());
+            answer
+        })?,
+    )?;
+
+    let dialog_code = r#"
+        (function() {
+            function text(value) {
+                return value === undefined || value === null ? '' : String(value);
+            }
+
+            function answered(answer) {
+                return answer !== undefined && answer !== null;
+            }
+
+            globalThis.alert = function(message) {
+                __showDialog('alert', text(message), '');
+            };
+
+            globalThis.confirm = function(message) {
+                return answered(__showDialog('confirm', text(message), ''));
+            };
+
+            globalThis.prompt = function(message, defaultValue) {
+                var answer = __showDialog('prompt', text(message), text(defaultValue));
+                return answered(answer) ? answer : null;
+            };
+        })();
+    "#;
+
+    ctx.eval::<(), _>(dialog_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dialog_streaks_are_limited() {
+        let mut limiter = DialogLimiter::default();
+        let start = Instant::now();
+        for i in 0..3 {
+            let now = start + Duration::from_millis(i * 100);
+            assert!(limiter.allow(now));
+            limiter.closed(now);
+        }
+        assert!(!limiter.allow(start + Duration::from_millis(300)));
+
+        // Trying again soon is suppressed too, a pause ends the streak
+        limiter.closed(start + Duration::from_millis(300));
+        assert!(!limiter.allow(start + Duration::from_millis(900)));
+        assert!(limiter.allow(start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_cap_message() {
+        assert_eq!(cap_message("Saved"), "Saved");
+        let long = "é".repeat(MAX_DIALOG_MESSAGE + 10);
+        let capped = cap_message(&long);
+        assert_eq!(capped.chars().count(), MAX_DIALOG_MESSAGE + 1);
+        assert!(capped.ends_with('…'));
+    }
+}
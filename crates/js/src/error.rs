@@ -22,6 +22,10 @@ pub enum JsError {
 
     #[error("Syntax error: {0}")]
     Syntax(String),
+
+    /// The script ran past its execution limit or was stopped
+    #[error("Script terminated")]
+    Terminated,
 }
 
 impl JsError {
@@ -0,0 +1,170 @@
+//! Uncaught Errors
+//!
+//! Reports exceptions no script caught, thrown by a script, an event
+//! listener or a timer, and promise rejections nothing handled once the
+//! promise reactions ran. Each is dispatched on the window first, as
+//! `error` or `unhandledrejection`; unless a handler cancels it, it is
+//! logged at error level with its stack and the script that was running.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rquickjs::{Ctx, Function, Result, Value};
+
+use crate::console::{record, ConsoleMessage, ConsoleMessages, ScriptOrigin};
+use crate::JsError;
+
+/// The script element whose code runs, None between scripts, as when
+/// timers and event listeners run
+pub(crate) type CurrentScript = Rc<RefCell<Option<ScriptOrigin>>>;
+
+/// Register the reporting of uncaught errors in the global scope
+pub fn register_errors(ctx: &Ctx<'_>, messages: ConsoleMessages, current: CurrentScript) -> Result<()> {
+    let globals = ctx.globals();
+
+    let script = current.clone();
+    globals.set(
+        "__currentScriptUrl",
+        Function::new(ctx.clone(), move || -> Option<String> {
+            script.borrow().as_ref().and_then(|origin| origin.url.clone())
+        })?,
+    )?;
+    globals.set(
+        "__recordUncaught",
+        Function::new(ctx.clone(), move |message: String, stack: Option<String>| {
+            let script = current.borrow().clone();
+            record(&messages, ConsoleMessage::uncaught(message, stack, script));
+        })?,
+    )?;
+
+    let errors_code = r#"
+        (function() {
+            // What an error says, as `TypeError: x is not a function`
+            function describe(error) {
+                if (error instanceof Error) return error.name + ': ' + error.message;
+                try {
+                    return String(error);
+                } catch (e) {
+                    return Object.prototype.toString.call(error);
+                }
+            }
+
+            function stackOf(error) {
+                return error instanceof Error && typeof error.stack === 'string' ? error.stack : undefined;
+            }
+
+            // Set while the handlers of an error run, errors they throw are
+            // only logged
+            var reporting = false;
+
+            // Report an exception nothing caught, returns what it says
+            globalThis.__reportException = function(error) {
+                var message = describe(error);
+                var stack = stackOf(error);
+                var handled = false;
+                if (!reporting) {
+                    reporting = true;
+                    try {
+                        var at = /:(\d+)(?::(\d+))?\)?\s*$/.exec((stack || '').split('\n')[0]) || [];
+                        handled = !__dispatchWindowEvent('error', {
+                            message: message,
+                            filename: __currentScriptUrl() || '',
+                            lineno: Number(at[1]) || 0,
+                            colno: Number(at[2]) || 0,
+                            error: error
+                        });
+                    } finally {
+                        reporting = false;
+                    }
+                }
+                if (!handled) __recordUncaught('Uncaught ' + message, stack);
+                return message;
+            };
+
+            // Promises rejected with no handler, a handler added later
+            // takes them out
+            var rejections = [];
+
+            globalThis.__trackRejection = function(promise, reason, handled) {
+                if (!handled) {
+                    rejections.push({ promise: promise, reason: reason });
+                } else {
+                    rejections = rejections.filter(function(rejection) { return rejection.promise !== promise; });
+                }
+            };
+
+            // Report the rejections still unhandled, returns how many
+            globalThis.__reportRejections = function() {
+                var unhandled = rejections;
+                rejections = [];
+                unhandled.forEach(function(rejection) {
+                    var properties = { promise: rejection.promise, reason: rejection.reason };
+                    if (__dispatchWindowEvent('unhandledrejection', properties)) {
+                        var message = 'Uncaught (in promise) ' + describe(rejection.reason);
+                        __recordUncaught(message, stackOf(rejection.reason));
+                    }
+                });
+                return unhandled.length;
+            };
+        })();
+    "#;
+
+    ctx.eval::<(), _>(errors_code)
+}
+
+/// Host tracker of promise rejections, called when a promise is rejected
+/// with no handler and when one is added to it later
+pub(crate) fn track_rejection<'js>(ctx: Ctx<'js>, promise: Value<'js>, reason: Value<'js>, handled: bool) {
+    let tracked = ctx
+        .globals()
+        .get::<_, Function>("__trackRejection")
+        .and_then(|track| track.call::<_, ()>((promise, reason, handled)));
+    if let Err(e) = tracked {
+        log::warn!("Failed to track a promise rejection: {}", e);
+    }
+}
+
+/// Report the exception that escaped code the runtime ran, turning it
+/// into the error of the call
+pub(crate) fn report_exception(ctx: &Ctx<'_>, error: rquickjs::Error) -> JsError {
+    if !matches!(error, rquickjs::Error::Exception) {
+        return error.into();
+    }
+    let thrown = ctx.catch();
+    if is_interrupt(&thrown) {
+        // Reporting it would run code, which the interrupt stops too
+        return JsError::Terminated;
+    }
+    let stack = thrown.as_exception().and_then(|e| e.stack());
+    let reported = ctx
+        .globals()
+        .get::<_, Function>("__reportException")
+        .and_then(|report| report.call::<_, String>((thrown,)));
+    match reported {
+        Ok(message) => JsError::Runtime { message, stack },
+        Err(e) => e.into(),
+    }
+}
+
+/// Whether a thrown value is the uncatchable error of an interrupted run
+fn is_interrupt(thrown: &Value<'_>) -> bool {
+    thrown.as_exception().is_some_and(|e| {
+        let internal = e.get::<_, String>("name").is_ok_and(|name| name == "InternalError");
+        internal && e.message().as_deref() == Some("interrupted")
+    })
+}
+
+/// Report the promise rejections nothing handled, returns how many
+pub(crate) fn report_rejections(ctx: &Ctx<'_>) -> usize {
+    let reported = ctx
+        .globals()
+        .get::<_, Function>("__reportRejections")
+        .and_then(|report| report.call::<_, i32>(()));
+    match reported {
+        Ok(count) => count.max(0) as usize,
+        Err(e) => {
+            log::warn!("Failed to report promise rejections: {}", e);
+            0
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Fetch API
+//!
+//! Provides fetch() with Response and Headers objects. Requests wait in a
+//! queue for the embedder to send them, it settles each one's promise once
+//! the response arrives.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rquickjs::{Ctx, Function, Result};
+
+/// A request made by a script, waiting to be sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchRequest {
+    /// ID the response is reported back under
+    pub id: u32,
+    /// URL as the script passed it, relative to the page
+    pub url: String,
+    /// HTTP method, uppercase
+    pub method: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+/// The response to a request made by a script
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchResponse {
+    /// Final URL after redirects
+    pub url: String,
+    pub status: u16,
+    /// Headers by lowercase name
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// Requests made by scripts that are not sent yet
+pub type FetchQueue = Rc<RefCell<Vec<FetchRequest>>>;
+
+/// Register fetch() in the global scope, queueing its requests in `queue`
+pub fn register_fetch(ctx: &Ctx<'_>, queue: FetchQueue) -> Result<()> {
+    let globals = ctx.globals();
+
+    let next_id = Cell::new(1u32);
+    globals.set(
+        "__fetchStart",
+        Function::new(
+            ctx.clone(),
+            move |url: String, method: String, headers: HashMap<String, String>, body: Option<String>| -> i32 {
+                let id = next_id.get();
+                next_id.set(id + 1);
+                queue.borrow_mut().push(FetchRequest { id, url, method, headers, body });
+                id as i32
+            },
+        )?,
+    )?;
+
+    let fetch_code = r#"
+        (function() {
+            // Callbacks of the promises of requests in flight, by ID
+            var pending = {};
+
+            function Headers(init) {
+                var map = {};
+                if (init instanceof Headers) {
+                    init = init.__map;
+                }
+                if (Array.isArray(init)) {
+                    init.forEach(function(pair) { map[String(pair[0]).toLowerCase()] = String(pair[1]); });
+                } else if (init) {
+                    Object.keys(init).forEach(function(name) { map[name.toLowerCase()] = String(init[name]); });
+                }
+                this.__map = map;
+            }
+
+            Headers.prototype.get = function(name) {
+                var value = this.__map[String(name).toLowerCase()];
+                return value === undefined ? null : value;
+            };
+
+            Headers.prototype.has = function(name) {
+                return this.get(name) !== null;
+            };
+
+            function Response(status, url, headers, body) {
+                this.status = status;
+                this.ok = status >= 200 && status < 300;
+                this.url = url;
+                this.headers = new Headers(headers);
+                this.__body = body;
+            }
+
+            Response.prototype.text = function() {
+                return Promise.resolve(this.__body);
+            };
+
+            Response.prototype.json = function() {
+                var body = this.__body;
+                return new Promise(function(resolve) { resolve(JSON.parse(body)); });
+            };
+
+            globalThis.fetch = function(input, init) {
+                init = init || {};
+                var headers = new Headers(init.headers).__map;
+                var method = String(init.method || 'GET').toUpperCase();
+                var body = init.body === undefined || init.body === null ? null : String(init.body);
+                var id = __fetchStart(String(input), method, headers, body);
+                return new Promise(function(resolve, reject) {
+                    pending[id] = { resolve: resolve, reject: reject };
+                });
+            };
+
+            // Called from Rust when the response to a request arrives
+            globalThis.__resolveFetch = function(id, status, url, headers, body) {
+                var request = pending[id];
+                if (!request) return;
+                delete pending[id];
+                request.resolve(new Response(status, url, headers, body));
+            };
+
+            // Called from Rust when a request could not be made
+            globalThis.__rejectFetch = function(id, message) {
+                var request = pending[id];
+                if (!request) return;
+                delete pending[id];
+                request.reject(new TypeError('Failed to fetch: ' + message));
+            };
+
+            globalThis.Headers = Headers;
+            globalThis.Response = Response;
+        })();
+    "#;
+
+    ctx.eval::<(), _>(fetch_code)
+}
@@ -0,0 +1,363 @@
+//! Form Controls
+//!
+//! Provides the `value`, `checked`, `disabled` and `selectedIndex`
+//! properties of form controls, and `elements`, `submit()` and `reset()`
+//! of forms. The embedder keeps the state of the controls and hands it
+//! over once the page is loaded; until then, and for controls neither the
+//! user nor a script has touched, the properties follow the content
+//! attributes. Writes mark the page for repainting, submissions are
+//! queued for the embedder to send.
+//!
+//! Constraint validation asks the embedder too: `validationMessage`,
+//! `checkValidity()` and `reportValidity()` use the checks it runs before
+//! sending a form, and the controls `reportValidity()` reports are queued
+//! for it to show.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gugalanna_dom::{DomTree, NodeId};
+use rquickjs::{Ctx, Function, Object, Result};
+
+use crate::SharedDom;
+
+/// The state of a page's form controls, as the embedder keeps it
+pub trait FormControls {
+    /// What a text field holds, None when it was not edited
+    fn value(&self, node_id: NodeId) -> Option<String>;
+    fn set_value(&mut self, node_id: NodeId, value: &str);
+    /// Whether a checkbox or radio button is checked
+    fn checked(&self, node_id: NodeId) -> bool;
+    fn set_checked(&mut self, node_id: NodeId, checked: bool);
+    /// Whether a control is disabled, None when it was not set
+    fn disabled(&self, node_id: NodeId) -> Option<bool>;
+    fn set_disabled(&mut self, node_id: NodeId, disabled: bool);
+    /// Index of the selected option of a select, -1 for none, None when
+    /// it was not set
+    fn selected_index(&self, node_id: NodeId) -> Option<i32>;
+    fn set_selected_index(&mut self, node_id: NodeId, index: i32);
+    /// Forget the edits of a control, back to what its attributes say
+    fn reset(&mut self, node_id: NodeId);
+    /// Why a control does not meet its constraints, None when it does or
+    /// is not validated
+    fn validation_message(&self, dom: &DomTree, node_id: NodeId) -> Option<String>;
+}
+
+/// Shared reference to the embedder's form controls
+pub type SharedFormControls = Rc<RefCell<dyn FormControls>>;
+
+/// The controls handed over by the embedder, None before it does
+pub(crate) type FormHandle = Rc<RefCell<Option<SharedFormControls>>>;
+
+/// Set when scripts changed a control, so it is painted again
+pub(crate) type PaintDirty = Rc<Cell<bool>>;
+
+/// Forms scripts submitted with `submit()`, not sent yet
+pub(crate) type FormSubmissions = Rc<RefCell<Vec<NodeId>>>;
+
+/// Invalid controls scripts reported with `reportValidity()`, not shown
+/// yet
+pub(crate) type ValidityReports = Rc<RefCell<Vec<NodeId>>>;
+
+/// Read the controls, R::default() before they are handed over
+fn read<R: Default>(handle: &FormHandle, get: impl FnOnce(&dyn FormControls) -> R) -> R {
+    handle.borrow().as_ref().map(|c| get(&*c.borrow())).unwrap_or_default()
+}
+
+/// Write to the controls and mark the page for repainting
+fn write(handle: &FormHandle, dirty: &PaintDirty, set: impl FnOnce(&mut dyn FormControls)) {
+    if let Some(controls) = handle.borrow().as_ref() {
+        set(&mut *controls.borrow_mut());
+        dirty.set(true);
+    }
+}
+
+/// Register the form control natives on `document` and the control and
+/// form properties of elements
+pub fn register_form<'js>(
+    ctx: &Ctx<'js>,
+    document: &Object<'js>,
+    controls: FormHandle,
+    dirty: PaintDirty,
+    submissions: FormSubmissions,
+) -> Result<()> {
+    let handle = controls.clone();
+    document.set(
+        "_formValue",
+        Function::new(ctx.clone(), move |node_id: i32| -> Option<String> {
+            read(&handle, |c| c.value(NodeId::new(node_id as u32)))
+        })?,
+    )?;
+
+    let (handle, paint) = (controls.clone(), dirty.clone());
+    document.set(
+        "_setFormValue",
+        Function::new(ctx.clone(), move |node_id: i32, value: String| {
+            write(&handle, &paint, |c| c.set_value(NodeId::new(node_id as u32), &value));
+        })?,
+    )?;
+
+    let handle = controls.clone();
+    document.set(
+        "_formChecked",
+        Function::new(ctx.clone(), move |node_id: i32| -> bool {
+            read(&handle, |c| c.checked(NodeId::new(node_id as u32)))
+        })?,
+    )?;
+
+    let (handle, paint) = (controls.clone(), dirty.clone());
+    document.set(
+        "_setFormChecked",
+        Function::new(ctx.clone(), move |node_id: i32, checked: bool| {
+            write(&handle, &paint, |c| c.set_checked(NodeId::new(node_id as u32), checked));
+        })?,
+    )?;
+
+    let handle = controls.clone();
+    document.set(
+        "_formDisabled",
+        Function::new(ctx.clone(), move |node_id: i32| -> Option<bool> {
+            read(&handle, |c| c.disabled(NodeId::new(node_id as u32)))
+        })?,
+    )?;
+
+    let (handle, paint) = (controls.clone(), dirty.clone());
+    document.set(
+        "_setFormDisabled",
+        Function::new(ctx.clone(), move |node_id: i32, disabled: bool| {
+            write(&handle, &paint, |c| c.set_disabled(NodeId::new(node_id as u32), disabled));
+        })?,
+    )?;
+
+    let handle = controls.clone();
+    document.set(
+        "_formSelectedIndex",
+        Function::new(ctx.clone(), move |node_id: i32| -> Option<i32> {
+            read(&handle, |c| c.selected_index(NodeId::new(node_id as u32)))
+        })?,
+    )?;
+
+    let (handle, paint) = (controls.clone(), dirty.clone());
+    document.set(
+        "_setFormSelectedIndex",
+        Function::new(ctx.clone(), move |node_id: i32, index: i32| {
+            write(&handle, &paint, |c| c.set_selected_index(NodeId::new(node_id as u32), index));
+        })?,
+    )?;
+
+    let (handle, paint) = (controls, dirty);
+    document.set(
+        "_resetFormControl",
+        Function::new(ctx.clone(), move |node_id: i32| {
+            write(&handle, &paint, |c| c.reset(NodeId::new(node_id as u32)));
+        })?,
+    )?;
+
+    document.set(
+        "_submitForm",
+        Function::new(ctx.clone(), move |node_id: i32| {
+            submissions.borrow_mut().push(NodeId::new(node_id as u32));
+        })?,
+    )?;
+
+    let form_code = r#"
+        (function() {
+            function isSet(value) {
+                return value !== undefined && value !== null;
+            }
+
+            function typeOf(element) {
+                return (element.getAttribute('type') || '').toLowerCase();
+            }
+
+            // What a control holds before it is edited
+            function defaultValue(element) {
+                var tag = element.tagName.toLowerCase();
+                if (tag === 'textarea') return element.textContent;
+                if (element.hasAttribute('value')) return element.getAttribute('value') || '';
+                if (tag === 'option') return element.textContent.trim();
+                var type = typeOf(element);
+                return type === 'checkbox' || type === 'radio' ? 'on' : '';
+            }
+
+            // Options of a select, directly or in option groups
+            function optionsOf(select) {
+                return Array.prototype.slice.call(select.querySelectorAll('option'));
+            }
+
+            function isForm(element) {
+                return element.tagName.toLowerCase() === 'form';
+            }
+
+            // Controls of a form, in document order
+            function controlsOf(form) {
+                return form.querySelectorAll('input, select, textarea, button');
+            }
+
+            Object.defineProperty(Element.prototype, 'value', {
+                get: function() {
+                    if (this.tagName.toLowerCase() === 'select') {
+                        var option = optionsOf(this)[this.selectedIndex];
+                        return option ? option.value : '';
+                    }
+                    var value = document._formValue(this.__nodeId);
+                    return isSet(value) ? value : defaultValue(this);
+                },
+                set: function(value) {
+                    value = isSet(value) ? String(value) : '';
+                    if (this.tagName.toLowerCase() === 'select') {
+                        this.selectedIndex = optionsOf(this).findIndex(function(o) { return o.value === value; });
+                    } else if (this.tagName.toLowerCase() === 'option') {
+                        this.setAttribute('value', value);
+                    } else {
+                        document._setFormValue(this.__nodeId, value);
+                    }
+                }
+            });
+
+            // Checking a radio button unchecks the others of its group
+            Object.defineProperty(Element.prototype, 'checked', {
+                get: function() { return document._formChecked(this.__nodeId); },
+                set: function(checked) {
+                    var element = this;
+                    var name = element.getAttribute('name');
+                    if (checked && typeOf(element) === 'radio' && name) {
+                        document.getElementsByTagName('input').forEach(function(other) {
+                            if (other.__nodeId !== element.__nodeId && typeOf(other) === 'radio' && other.getAttribute('name') === name) {
+                                document._setFormChecked(other.__nodeId, false);
+                            }
+                        });
+                    }
+                    document._setFormChecked(element.__nodeId, !!checked);
+                }
+            });
+
+            Object.defineProperty(Element.prototype, 'disabled', {
+                get: function() {
+                    var disabled = document._formDisabled(this.__nodeId);
+                    return isSet(disabled) ? disabled : this.hasAttribute('disabled');
+                },
+                set: function(disabled) { document._setFormDisabled(this.__nodeId, !!disabled); }
+            });
+
+            // Without a script setting it, the first option marked
+            // selected, or the first option
+            Object.defineProperty(Element.prototype, 'selectedIndex', {
+                get: function() {
+                    var index = document._formSelectedIndex(this.__nodeId);
+                    if (isSet(index)) return index;
+                    var options = optionsOf(this);
+                    var selected = options.findIndex(function(o) { return o.hasAttribute('selected'); });
+                    return selected !== -1 ? selected : Math.min(options.length - 1, 0);
+                },
+                set: function(index) {
+                    index = Number(index) | 0;
+                    if (index < 0 || index >= optionsOf(this).length) index = -1;
+                    document._setFormSelectedIndex(this.__nodeId, index);
+                }
+            });
+
+            Object.defineProperty(Element.prototype, 'elements', {
+                get: function() {
+                    if (!isForm(this)) return undefined;
+                    var elements = controlsOf(this);
+                    elements.namedItem = function(name) {
+                        for (var i = 0; i < this.length; i++) {
+                            if (this[i].id === name || this[i].getAttribute('name') === name) return this[i];
+                        }
+                        return null;
+                    };
+                    return elements;
+                }
+            });
+
+            // Submitting from a script skips the submit event
+            Element.prototype.submit = function() {
+                if (isForm(this)) document._submitForm(this.__nodeId);
+            };
+
+            // Resetting fires reset, which listeners may cancel
+            Element.prototype.reset = function() {
+                if (!isForm(this)) return;
+                if (!__dispatchEvent(document._eventPath(this.__nodeId), 'reset', true)) return;
+                controlsOf(this).forEach(function(control) {
+                    document._resetFormControl(control.__nodeId);
+                });
+            };
+        })();
+    "#;
+
+    ctx.eval::<(), _>(form_code)
+}
+
+/// Register the constraint validation natives on `document` and the
+/// validation properties and methods of elements
+pub fn register_validation<'js>(
+    ctx: &Ctx<'js>,
+    document: &Object<'js>,
+    dom: SharedDom,
+    controls: FormHandle,
+    reports: ValidityReports,
+) -> Result<()> {
+    document.set(
+        "_formValidationMessage",
+        Function::new(ctx.clone(), move |node_id: i32| -> String {
+            let dom = dom.borrow();
+            read(&controls, |c| c.validation_message(&dom, NodeId::new(node_id as u32))).unwrap_or_default()
+        })?,
+    )?;
+
+    document.set(
+        "_reportValidity",
+        Function::new(ctx.clone(), move |node_id: i32| {
+            reports.borrow_mut().push(NodeId::new(node_id as u32));
+        })?,
+    )?;
+
+    let validation_code = r#"
+        (function() {
+            Object.defineProperty(Element.prototype, 'validationMessage', {
+                get: function() { return document._formValidationMessage(this.__nodeId); }
+            });
+
+            Object.defineProperty(Element.prototype, 'validity', {
+                get: function() { return { valid: !this.validationMessage }; }
+            });
+
+            // A form checks its controls, a control itself
+            function invalidControls(element) {
+                var controls = element.tagName.toLowerCase() === 'form'
+                    ? Array.prototype.slice.call(element.querySelectorAll('input, select, textarea'))
+                    : [element];
+                return controls.filter(function(control) { return control.validationMessage; });
+            }
+
+            // Each invalid control hears invalid, which does not bubble;
+            // true when it was not canceled
+            function fireInvalid(control) {
+                return __dispatchEvent(document._eventPath(control.__nodeId), 'invalid', false);
+            }
+
+            Element.prototype.checkValidity = function() {
+                var invalid = invalidControls(this);
+                invalid.forEach(fireInvalid);
+                return invalid.length === 0;
+            };
+
+            // The first control invalid is not canceled for is reported
+            Element.prototype.reportValidity = function() {
+                var invalid = invalidControls(this);
+                var reported = false;
+                invalid.forEach(function(control) {
+                    if (fireInvalid(control) && !reported) {
+                        document._reportValidity(control.__nodeId);
+                        reported = true;
+                    }
+                });
+                return invalid.length === 0;
+            };
+        })();
+    "#;
+
+    ctx.eval::<(), _>(validation_code)
+}
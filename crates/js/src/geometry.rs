@@ -0,0 +1,363 @@
+//! Element Geometry
+//!
+//! Provides `getBoundingClientRect()`, `offsetWidth`, `offsetHeight`,
+//! `offsetTop`, `offsetLeft`, `clientWidth` and `clientHeight` of
+//! elements, and `scrollY` of the window. They read the boxes of the last
+//! layout the embedder handed over. A read after scripts changed the DOM
+//! has the embedder's layout provider lay the page out first, so scripts
+//! never measure a stale layout.
+//!
+//! Scripts scroll the page with `scrollTo()`, `scrollBy()` and
+//! `scrollIntoView()`. The offset is clamped to the content and queued as a
+//! [`ScrollRequest`] for the embedder, which moves the page; a jump shows in
+//! `scrollY` at once, a smooth scroll as the embedder animates it.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gugalanna_dom::{DomTree, NodeId};
+use rquickjs::{Ctx, Function, Object, Result};
+
+use crate::window::Viewport;
+use crate::{MutationJournal, SharedDom};
+
+/// A rectangle in CSS pixels, from the top left of the document
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BoxRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl BoxRect {
+    /// The smallest rectangle holding both
+    pub fn union(&self, other: &BoxRect) -> BoxRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        BoxRect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// Where an element was laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ElementBox {
+    pub border_box: BoxRect,
+    pub padding_box: BoxRect,
+    /// Inside a position: fixed box, placed in the viewport rather than
+    /// the document
+    pub fixed: bool,
+}
+
+/// Boxes of the elements of a layout, elements that were not laid out
+/// left out
+pub type ElementBoxes = HashMap<NodeId, ElementBox>;
+
+/// Lays the page out for scripts measuring it after changing the DOM
+pub trait LayoutProvider {
+    /// Lay out the document in a viewport of `width` by `height`
+    fn layout(&self, dom: &DomTree, width: f32, height: f32) -> ElementBoxes;
+}
+
+/// Shared reference to the embedder's layout provider
+pub type SharedLayoutProvider = Rc<dyn LayoutProvider>;
+
+/// A scroll of the page asked for by a script
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollRequest {
+    /// Offset from the top of the document, clamped to the content
+    pub top: f32,
+    /// Animate the page to the offset rather than jump there
+    pub smooth: bool,
+}
+
+/// The boxes scripts measure and how far the page is scrolled
+#[derive(Default)]
+pub(crate) struct Geometry {
+    boxes: ElementBoxes,
+    /// Length of the mutation journal when the boxes were measured, None
+    /// when they may be stale
+    measured_at: Option<usize>,
+    provider: Option<SharedLayoutProvider>,
+    scroll_y: f32,
+    /// Height of the laid out document, None before the embedder says
+    content_height: Option<f32>,
+    /// Scrolls asked for since the embedder last took them
+    scroll_requests: Vec<ScrollRequest>,
+}
+
+impl Geometry {
+    /// Take the boxes of a layout of the document as it is now
+    pub(crate) fn set_boxes(&mut self, boxes: ElementBoxes, journal_len: usize) {
+        self.boxes = boxes;
+        self.measured_at = Some(journal_len);
+    }
+
+    pub(crate) fn set_provider(&mut self, provider: SharedLayoutProvider) {
+        self.provider = Some(provider);
+    }
+
+    pub(crate) fn set_scroll_y(&mut self, scroll_y: f32) {
+        self.scroll_y = scroll_y;
+    }
+
+    pub(crate) fn set_content_height(&mut self, height: f32) {
+        self.content_height = Some(height);
+    }
+
+    /// Ask to scroll to `top` in a viewport `viewport_height` tall, a jump
+    /// moving `scroll_y` right away
+    pub(crate) fn request_scroll(&mut self, top: f32, smooth: bool, viewport_height: f32) {
+        let max_scroll = self.content_height.map_or(f32::INFINITY, |h| (h - viewport_height).max(0.0));
+        let top = top.clamp(0.0, max_scroll);
+        if !smooth {
+            self.scroll_y = top;
+        }
+        self.scroll_requests.push(ScrollRequest { top, smooth });
+    }
+
+    pub(crate) fn take_scroll_requests(&mut self) -> Vec<ScrollRequest> {
+        std::mem::take(&mut self.scroll_requests)
+    }
+
+    /// The mutation journal of `len` entries was taken, the boxes stay
+    /// current if they were
+    pub(crate) fn journal_taken(&mut self, len: usize) {
+        self.measured_at = match self.measured_at {
+            Some(at) if at == len => Some(0),
+            _ => None,
+        };
+    }
+}
+
+/// Shared geometry of the page
+pub(crate) type SharedGeometry = Rc<RefCell<Geometry>>;
+
+/// The box of an element, laying the page out first when scripts changed
+/// the DOM since it last was
+fn element_box(
+    geometry: &SharedGeometry,
+    dom: &SharedDom,
+    journal: &MutationJournal,
+    viewport: &Viewport,
+    id: NodeId,
+) -> Option<ElementBox> {
+    let journal_len = journal.borrow().len();
+    let provider = {
+        let geometry = geometry.borrow();
+        match &geometry.provider {
+            Some(provider) if geometry.measured_at != Some(journal_len) => Some(provider.clone()),
+            _ => None,
+        }
+    };
+    if let Some(provider) = provider {
+        let (width, height) = viewport.get();
+        let boxes = provider.layout(&dom.borrow(), width, height);
+        geometry.borrow_mut().set_boxes(boxes, journal_len);
+    }
+    geometry.borrow().boxes.get(&id).copied()
+}
+
+/// Register the geometry natives on `document` and the measuring
+/// properties of elements
+pub fn register_geometry<'js>(
+    ctx: &Ctx<'js>,
+    document: &Object<'js>,
+    dom: SharedDom,
+    journal: MutationJournal,
+    viewport: Viewport,
+    geometry: SharedGeometry,
+) -> Result<()> {
+    let (shared, size) = (geometry.clone(), viewport.clone());
+    document.set(
+        "_elementBox",
+        Function::new(ctx.clone(), move |node_id: i32| -> Vec<f64> {
+            let Some(b) = element_box(&shared, &dom, &journal, &size, NodeId::new(node_id as u32)) else {
+                return Vec::new();
+            };
+            let (border, padding) = (b.border_box, b.padding_box);
+            [border.x, border.y, border.width, border.height, padding.x, padding.y, padding.width, padding.height]
+                .into_iter()
+                .map(f64::from)
+                .chain(std::iter::once(if b.fixed { 1.0 } else { 0.0 }))
+                .collect()
+        })?,
+    )?;
+    let shared = geometry.clone();
+    document.set(
+        "_scrollY",
+        Function::new(ctx.clone(), move || -> f64 { shared.borrow().scroll_y as f64 })?,
+    )?;
+    document.set(
+        "_scrollTo",
+        Function::new(ctx.clone(), move |top: f64, smooth: bool| {
+            geometry.borrow_mut().request_scroll(top as f32, smooth, viewport.get().1);
+        })?,
+    )?;
+
+    // Elements not laid out, as with display: none, measure zero
+    let geometry_code = r#"
+        (function() {
+            function measure(element) {
+                var b = document._elementBox(element.__nodeId);
+                if (b.length === 0) {
+                    return { border: [0, 0, 0, 0], padding: [0, 0, 0, 0], fixed: false };
+                }
+                return { border: b.slice(0, 4), padding: b.slice(4, 8), fixed: b[8] === 1 };
+            }
+
+            function DOMRect(x, y, width, height) {
+                this.x = x;
+                this.y = y;
+                this.width = width;
+                this.height = height;
+                this.top = Math.min(y, y + height);
+                this.right = Math.max(x, x + width);
+                this.bottom = Math.max(y, y + height);
+                this.left = Math.min(x, x + width);
+            }
+
+            DOMRect.prototype.toJSON = function() {
+                var rect = this;
+                var json = {};
+                ['x', 'y', 'width', 'height', 'top', 'right', 'bottom', 'left'].forEach(function(key) {
+                    json[key] = rect[key];
+                });
+                return json;
+            };
+
+            // The border box in the viewport
+            Element.prototype.getBoundingClientRect = function() {
+                var box = measure(this);
+                var scrollY = box.fixed ? 0 : document._scrollY();
+                return new DOMRect(box.border[0], box.border[1] - scrollY, box.border[2], box.border[3]);
+            };
+
+            // Offsets are from the document, as if the body were every
+            // element's offset parent
+            var measures = {
+                offsetLeft: function(box) { return box.border[0]; },
+                offsetTop: function(box) { return box.border[1]; },
+                offsetWidth: function(box) { return box.border[2]; },
+                offsetHeight: function(box) { return box.border[3]; },
+                clientWidth: function(box) { return box.padding[2]; },
+                clientHeight: function(box) { return box.padding[3]; }
+            };
+            Object.keys(measures).forEach(function(name) {
+                Object.defineProperty(Element.prototype, name, {
+                    get: function() { return Math.round(measures[name](measure(this))); }
+                });
+            });
+
+            Object.defineProperty(globalThis, 'scrollY', { get: function() { return document._scrollY(); } });
+            Object.defineProperty(globalThis, 'pageYOffset', { get: function() { return document._scrollY(); } });
+
+            // The page never scrolls sideways
+            globalThis.scrollX = 0;
+            globalThis.pageXOffset = 0;
+
+            function finite(value) {
+                value = Number(value);
+                return isFinite(value) ? value : 0;
+            }
+
+            // The vertical offset and behavior of scrollTo(x, y) or
+            // scrollTo({ top, behavior }), top undefined when the options
+            // leave it out
+            function scrollOptions(args) {
+                var options = args[0];
+                if (args.length === 1 && options !== null && typeof options === 'object') {
+                    var top = options.top === undefined ? undefined : finite(options.top);
+                    return { top: top, smooth: options.behavior === 'smooth' };
+                }
+                return { top: finite(args[1]), smooth: false };
+            }
+
+            globalThis.scrollTo = function() {
+                var options = scrollOptions(arguments);
+                if (options.top !== undefined) document._scrollTo(options.top, options.smooth);
+            };
+            globalThis.scroll = globalThis.scrollTo;
+            globalThis.scrollBy = function() {
+                var options = scrollOptions(arguments);
+                document._scrollTo(document._scrollY() + (options.top || 0), options.smooth);
+            };
+
+            // Scroll the page so the border box is at the top, middle or
+            // bottom of the viewport, or just inside it for 'nearest'
+            Element.prototype.scrollIntoView = function(arg) {
+                var options = arg === false ? { block: 'end' } : (arg !== null && typeof arg === 'object' ? arg : {});
+                var box = measure(this);
+                if (box.fixed) return;
+                var top = box.border[1], height = box.border[3], viewport = innerHeight;
+                var scrollY = document._scrollY();
+                var target;
+                switch (options.block) {
+                    case 'center': target = top + height / 2 - viewport / 2; break;
+                    case 'end': target = top + height - viewport; break;
+                    case 'nearest':
+                        if (top < scrollY) target = top;
+                        else if (top + height > scrollY + viewport) target = top + height - viewport;
+                        else return;
+                        break;
+                    default: target = top;
+                }
+                document._scrollTo(target, options.behavior === 'smooth');
+            };
+
+            globalThis.DOMRect = DOMRect;
+        })();
+    "#;
+
+    ctx.eval::<(), _>(geometry_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boxes_stay_current_across_journal_takes() {
+        let mut geometry = Geometry::default();
+        geometry.set_boxes(ElementBoxes::new(), 0);
+
+        // Taking a journal the boxes were measured with keeps them current
+        geometry.journal_taken(0);
+        assert_eq!(geometry.measured_at, Some(0));
+
+        // Scripts changed the DOM after they were measured
+        geometry.set_boxes(ElementBoxes::new(), 2);
+        geometry.journal_taken(3);
+        assert_eq!(geometry.measured_at, None);
+    }
+
+    #[test]
+    fn test_box_rect_union() {
+        let a = BoxRect { x: 0.0, y: 10.0, width: 20.0, height: 5.0 };
+        let b = BoxRect { x: 5.0, y: 0.0, width: 30.0, height: 5.0 };
+        assert_eq!(a.union(&b), BoxRect { x: 0.0, y: 0.0, width: 35.0, height: 15.0 });
+    }
+
+    #[test]
+    fn test_scroll_requests_clamped_to_content() {
+        let mut geometry = Geometry::default();
+        geometry.set_content_height(1000.0);
+        geometry.request_scroll(900.0, false, 600.0);
+        assert_eq!(geometry.scroll_y, 400.0);
+
+        // A smooth scroll leaves the offset to the embedder
+        geometry.request_scroll(-50.0, true, 600.0);
+        assert_eq!(geometry.scroll_y, 400.0);
+        assert_eq!(
+            geometry.take_scroll_requests(),
+            [ScrollRequest { top: 400.0, smooth: false }, ScrollRequest { top: 0.0, smooth: true }]
+        );
+        assert!(geometry.take_scroll_requests().is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+//! History
+//!
+//! Provides `history`, the page's view of its tab's session history. The
+//! embedder owns the history and hands over the current entry, changes
+//! scripts make are queued for it to apply.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rquickjs::{Ctx, Function, Result};
+use url::{Position, Url};
+
+/// A change scripts made to the session history
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryChange {
+    /// Add an entry for the document, from `pushState`
+    Push { url: Url, state: Option<String> },
+    /// Change the current entry, from `replaceState`
+    Replace { url: Url, state: Option<String> },
+    /// Move through the history, from `back`, `forward` and `go`
+    Go(i32),
+}
+
+/// The current entry as the page sees it, states serialized as JSON
+#[derive(Debug, Default)]
+pub struct HistoryView {
+    url: Option<Url>,
+    state: Option<String>,
+    index: usize,
+    length: usize,
+    /// Changes not taken by the embedder yet
+    changes: Vec<HistoryChange>,
+}
+
+impl HistoryView {
+    pub fn set(&mut self, url: &Url, state: Option<&str>, index: usize, length: usize) {
+        self.url = Some(url.clone());
+        self.state = state.map(str::to_string);
+        self.index = index;
+        self.length = length.max(index + 1);
+    }
+
+    /// The URL of the current entry, None before the embedder hands one
+    /// over
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    pub fn take_changes(&mut self) -> Vec<HistoryChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Resolve the URL a script passed against the current one, which it
+    /// defaults to, checking it is of the same origin
+    fn resolve(&self, url: Option<&str>) -> std::result::Result<Url, String> {
+        let current = self.url.as_ref().ok_or("The document has no URL.")?;
+        let Some(url) = url else {
+            return Ok(current.clone());
+        };
+        let resolved = current.join(url).map_err(|_| format!("'{}' is not a valid URL.", url))?;
+        if !same_origin(&resolved, current) {
+            return Err(format!("'{}' is not of the origin of '{}'.", resolved, current));
+        }
+        Ok(resolved)
+    }
+
+    fn push(&mut self, url: Url, state: Option<String>) {
+        self.index += 1;
+        self.length = self.index + 1;
+        self.url = Some(url.clone());
+        self.state = state.clone();
+        self.changes.push(HistoryChange::Push { url, state });
+    }
+
+    fn replace(&mut self, url: Url, state: Option<String>) {
+        self.url = Some(url.clone());
+        self.state = state.clone();
+        self.changes.push(HistoryChange::Replace { url, state });
+    }
+}
+
+/// Shared reference to the history view
+pub type SharedHistory = Rc<RefCell<HistoryView>>;
+
+/// Check if a script may change the URL `current` to `url`
+///
+/// Opaque origins, as of file: URLs, never equal another, so for them
+/// only the query and fragment may change.
+fn same_origin(url: &Url, current: &Url) -> bool {
+    let origin = current.origin();
+    if origin.is_tuple() {
+        url.origin() == origin
+    } else {
+        url[..Position::AfterPath] == current[..Position::AfterPath]
+    }
+}
+
+/// Register `history` in the global scope
+pub fn register_history(ctx: &Ctx<'_>, history: SharedHistory) -> Result<()> {
+    let globals = ctx.globals();
+
+    // Push and replace return why the URL was refused, if it was
+    let view = history.clone();
+    globals.set(
+        "__historyPush",
+        Function::new(ctx.clone(), move |state: Option<String>, url: Option<String>| -> Option<String> {
+            let mut view = view.borrow_mut();
+            match view.resolve(url.as_deref()) {
+                Ok(url) => {
+                    view.push(url, state);
+                    None
+                }
+                Err(message) => Some(message),
+            }
+        })?,
+    )?;
+
+    let view = history.clone();
+    globals.set(
+        "__historyReplace",
+        Function::new(ctx.clone(), move |state: Option<String>, url: Option<String>| -> Option<String> {
+            let mut view = view.borrow_mut();
+            match view.resolve(url.as_deref()) {
+                Ok(url) => {
+                    view.replace(url, state);
+                    None
+                }
+                Err(message) => Some(message),
+            }
+        })?,
+    )?;
+
+    let view = history.clone();
+    globals.set(
+        "__historyGo",
+        Function::new(ctx.clone(), move |delta: i32| {
+            view.borrow_mut().changes.push(HistoryChange::Go(delta));
+        })?,
+    )?;
+
+    let view = history.clone();
+    globals.set(
+        "__historyLength",
+        Function::new(ctx.clone(), move || -> i32 { view.borrow().length as i32 })?,
+    )?;
+
+    let view = history;
+    globals.set(
+        "__historyState",
+        Function::new(ctx.clone(), move || -> Option<String> { view.borrow().state.clone() })?,
+    )?;
+
+    // States are kept as JSON, so the page gets a copy back as it would a
+    // structured clone
+    let history_code = r#"
+        (function() {
+            function serialize(state) {
+                try {
+                    return JSON.stringify(state);
+                } catch (e) {
+                    var error = new Error('The state object could not be cloned: ' + e.message);
+                    error.name = 'DataCloneError';
+                    throw error;
+                }
+            }
+
+            function deserialize(json) {
+                return json === undefined || json === null ? null : JSON.parse(json);
+            }
+
+            function change(native, state, url) {
+                var refused = native(serialize(state), url === undefined || url === null ? undefined : String(url));
+                if (refused !== undefined && refused !== null) {
+                    var error = new Error(refused);
+                    error.name = 'SecurityError';
+                    throw error;
+                }
+            }
+
+            var history = {
+                pushState: function(state, title, url) { change(__historyPush, state, url); },
+                replaceState: function(state, title, url) { change(__historyReplace, state, url); },
+                back: function() { __historyGo(-1); },
+                forward: function() { __historyGo(1); },
+                go: function(delta) { __historyGo(Number(delta) | 0); }
+            };
+
+            Object.defineProperty(history, 'length', { get: function() { return __historyLength(); } });
+            Object.defineProperty(history, 'state', { get: function() { return deserialize(__historyState()); } });
+
+            globalThis.history = history;
+
+            // Called from Rust when the user moved to another entry of the
+            // same document
+            globalThis.__dispatchPopState = function(json) {
+                __dispatchWindowEvent('popstate', { state: deserialize(json) });
+            };
+        })();
+    "#;
+
+    ctx.eval::<(), _>(history_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_origin() {
+        let current = Url::parse("https://app.test/inbox").unwrap();
+        assert!(same_origin(&Url::parse("https://app.test/sent?page=2").unwrap(), &current));
+        assert!(!same_origin(&Url::parse("http://app.test/sent").unwrap(), &current));
+        assert!(!same_origin(&Url::parse("https://evil.test/").unwrap(), &current));
+
+        let file = Url::parse("file:///home/page.html").unwrap();
+        assert!(same_origin(&Url::parse("file:///home/page.html#top").unwrap(), &file));
+        assert!(!same_origin(&Url::parse("file:///etc/passwd").unwrap(), &file));
+    }
+}
@@ -2,27 +2,150 @@
 //!
 //! JavaScript execution via QuickJS with DOM bindings.
 
+mod class_list;
 mod console;
+mod cookie;
+mod dialog;
 mod error;
-
-pub use console::{ConsoleMessage, ConsoleMessages, LogLevel, new_console_messages};
+mod errors;
+mod fetch;
+mod form;
+mod geometry;
+mod history;
+mod limits;
+mod module;
+mod mutation_observer;
+mod navigator;
+mod storage;
+mod style;
+mod text;
+mod timers;
+mod window;
+
+pub use console::{
+    ConsoleMessage, ConsoleMessages, ConsolePart, LogLevel, ScriptOrigin, SourceLocation, new_console_messages,
+};
+pub use cookie::{CookieAccess, SharedCookieAccess};
+pub use dialog::{DialogHandler, DialogKind, DialogRequest, SharedDialogHandler, MAX_DIALOG_MESSAGE};
 pub use error::JsError;
-
-use std::cell::RefCell;
+pub use fetch::{FetchRequest, FetchResponse};
+pub use form::{FormControls, SharedFormControls};
+pub use geometry::{BoxRect, ElementBox, ElementBoxes, LayoutProvider, ScrollRequest, SharedLayoutProvider};
+pub use history::HistoryChange;
+pub use limits::{DEFAULT_EXECUTION_LIMIT, MEMORY_LIMIT};
+pub use module::{ModuleFetcher, SharedModuleFetcher};
+pub use navigator::DEFAULT_USER_AGENT;
+pub use storage::{SharedStorage, StorageArea, StorageStore, STORAGE_QUOTA};
+
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use gugalanna_dom::{DomTree, NodeId, Queryable};
-use rquickjs::{Context, Function, Object, Runtime};
+use gugalanna_css::Selector;
+use gugalanna_dom::{DomError, DomResult, DomTree, NodeId, NodeType, Queryable};
+use gugalanna_style::{SelectorQuery, StyleTree};
+use rquickjs::{Context, Function, Module, Object, Promise, Runtime};
+
+use crate::limits::RunKind;
+use crate::mutation_observer::{MutationRecord, SharedObservers};
 
 /// Shared reference to the DOM tree
 pub type SharedDom = Rc<RefCell<DomTree>>;
 
+/// Nodes whose style scripts may have changed, by changing their
+/// attributes, inline style or children
+pub type MutationJournal = Rc<RefCell<Vec<NodeId>>>;
+
+/// A DOM error as `Name:message`, the name of the DOMException scripts
+/// get for it
+pub(crate) fn dom_exception(error: &DomError) -> String {
+    let name = match error {
+        DomError::EmptyToken => "SyntaxError",
+        DomError::TokenWithWhitespace(_) => "InvalidCharacterError",
+        DomError::HierarchyRequest => "HierarchyRequestError",
+        DomError::NotAChild | DomError::NodeNotFound(_) => "NotFoundError",
+        _ => "Error",
+    };
+    format!("{}:{}", name, error)
+}
+
+/// Move `node` within the tree for a script, journaling the parent it
+/// left and `parent` and recording the move for observers, or return the
+/// error it gets
+fn edit_children(
+    dom: &SharedDom,
+    journal: &MutationJournal,
+    observers: &SharedObservers,
+    parent: NodeId,
+    node: NodeId,
+    edit: impl FnOnce(&mut DomTree) -> DomResult<()>,
+) -> Option<String> {
+    let mut dom = dom.borrow_mut();
+    let old_parent = dom.parent(node);
+    // Children are only compared when someone watches them change
+    let before = observers.borrow().is_observing().then(|| {
+        let siblings = old_parent.map(|id| (id, dom.children(id)));
+        (dom.children(parent), siblings)
+    });
+    match edit(&mut dom) {
+        Ok(()) => {
+            let left = old_parent.filter(|&id| id != parent);
+            journal.borrow_mut().extend(left.into_iter().chain(Some(parent)));
+            if let Some((children, siblings)) = before {
+                observers.borrow_mut().record_move(&dom, parent, &children, node, siblings);
+            }
+            None
+        }
+        Err(e) => Some(dom_exception(&e)),
+    }
+}
+
 /// JavaScript runtime wrapper
 pub struct JsRuntime {
     runtime: Runtime,
     context: Context,
     dom: Option<SharedDom>,
     console_messages: ConsoleMessages,
+    /// Clock timers are scheduled against, advanced by `run_due_timers`
+    timer_clock: timers::TimerClock,
+    created: Instant,
+    /// Requests made with fetch() that are not sent yet
+    fetch_queue: fetch::FetchQueue,
+    /// Nodes changed by scripts since the last `take_style_dirty`
+    style_dirty: MutationJournal,
+    /// Nodes scripts removed since the wrappers of those left out were
+    /// let go of
+    removed_nodes: MutationJournal,
+    viewport: window::Viewport,
+    /// Styles getComputedStyle() reads, from the last style pass
+    computed_styles: window::ComputedStyles,
+    /// The tab's current history entry and the changes scripts made
+    history: history::SharedHistory,
+    /// The embedder's form controls, for `value`, `checked` and the like
+    form_controls: form::FormHandle,
+    /// Scripts changed a form control since the last `take_paint_dirty`
+    paint_dirty: form::PaintDirty,
+    /// Forms submitted with `submit()` since the last `take_form_submissions`
+    form_submissions: form::FormSubmissions,
+    /// Controls reported with `reportValidity()` since the last
+    /// `take_validity_reports`
+    validity_reports: form::ValidityReports,
+    /// The user agent `navigator.userAgent` reports
+    user_agent: navigator::UserAgent,
+    /// Text written with `navigator.clipboard.writeText()`
+    clipboard: navigator::Clipboard,
+    /// The embedder's handler of alert(), confirm() and prompt()
+    dialogs: dialog::DialogHandle,
+    /// The script element whose code runs, for the errors it throws
+    current_script: errors::CurrentScript,
+    /// Boxes of the last layout, which scripts measure elements by
+    geometry: geometry::SharedGeometry,
+    /// The embedder's cookie jar, for `document.cookie`
+    cookies: cookie::CookieHandle,
+    /// The embedder's fetcher of the modules scripts import
+    module_fetcher: module::ModuleFetcherHandle,
+    /// How long scripts may run before they are stopped
+    limits: limits::SharedLimits,
 }
 
 impl JsRuntime {
@@ -31,11 +154,43 @@ impl JsRuntime {
         let runtime = Runtime::new()?;
         let context = Context::full(&runtime)?;
         let console_messages = new_console_messages();
-
-        // Register console
+        let timer_clock = Rc::new(Cell::new(Duration::ZERO));
+        let fetch_queue = fetch::FetchQueue::default();
+        let viewport = window::Viewport::default();
+        let computed_styles = window::ComputedStyles::default();
+        let history = history::SharedHistory::default();
+        let user_agent = Rc::new(RefCell::new(DEFAULT_USER_AGENT.to_string()));
+        let clipboard = navigator::Clipboard::default();
+        let dialogs = dialog::DialogHandle::default();
+        let current_script = errors::CurrentScript::default();
+        let module_fetcher = module::ModuleFetcherHandle::default();
+        let limits = limits::SharedLimits::default();
+        runtime.set_host_promise_rejection_tracker(Some(Box::new(errors::track_rejection)));
+        runtime.set_loader(module::UrlResolver, module::UrlLoader::new(module_fetcher.clone(), limits.clone()));
+        let interrupt = limits.clone();
+        runtime.set_interrupt_handler(Some(Box::new(move || interrupt.should_interrupt())));
+        runtime.set_memory_limit(MEMORY_LIMIT);
+
+        // Register console, timers, fetch, window, uncaught errors, module
+        // settling, history, navigator and dialogs
         let msgs = console_messages.clone();
+        let clock = timer_clock.clone();
+        let queue = fetch_queue.clone();
+        let (size, styles) = (viewport.clone(), computed_styles.clone());
+        let view = history.clone();
+        let (agent, written) = (user_agent.clone(), clipboard.clone());
+        let (handler, waits) = (dialogs.clone(), limits.clone());
+        let (reported, script) = (console_messages.clone(), current_script.clone());
         context.with(|ctx| {
-            console::register_console(&ctx, msgs)
+            console::register_console(&ctx, msgs)?;
+            timers::register_timers(&ctx, clock)?;
+            fetch::register_fetch(&ctx, queue)?;
+            window::register_window(&ctx, size, styles)?;
+            errors::register_errors(&ctx, reported, script)?;
+            module::register_modules(&ctx)?;
+            history::register_history(&ctx, view)?;
+            navigator::register_navigator(&ctx, agent, written)?;
+            dialog::register_dialogs(&ctx, handler, waits)
         })?;
 
         Ok(Self {
@@ -43,6 +198,26 @@ impl JsRuntime {
             context,
             dom: None,
             console_messages,
+            timer_clock,
+            created: Instant::now(),
+            fetch_queue,
+            viewport,
+            computed_styles,
+            history,
+            style_dirty: MutationJournal::default(),
+            removed_nodes: MutationJournal::default(),
+            form_controls: form::FormHandle::default(),
+            paint_dirty: form::PaintDirty::default(),
+            form_submissions: form::FormSubmissions::default(),
+            validity_reports: form::ValidityReports::default(),
+            user_agent,
+            clipboard,
+            dialogs,
+            current_script,
+            geometry: geometry::SharedGeometry::default(),
+            cookies: cookie::CookieHandle::default(),
+            module_fetcher,
+            limits,
         })
     }
 
@@ -52,27 +227,112 @@ impl JsRuntime {
         let context = Context::full(&runtime)?;
         let shared_dom = Rc::new(RefCell::new(dom));
         let console_messages = new_console_messages();
-
-        // Register console
+        let timer_clock = Rc::new(Cell::new(Duration::ZERO));
+        let fetch_queue = fetch::FetchQueue::default();
+        let viewport = window::Viewport::default();
+        let computed_styles = window::ComputedStyles::default();
+        let history = history::SharedHistory::default();
+        let user_agent = Rc::new(RefCell::new(DEFAULT_USER_AGENT.to_string()));
+        let clipboard = navigator::Clipboard::default();
+        let dialogs = dialog::DialogHandle::default();
+        let current_script = errors::CurrentScript::default();
+        let module_fetcher = module::ModuleFetcherHandle::default();
+        let limits = limits::SharedLimits::default();
+        runtime.set_host_promise_rejection_tracker(Some(Box::new(errors::track_rejection)));
+        runtime.set_loader(module::UrlResolver, module::UrlLoader::new(module_fetcher.clone(), limits.clone()));
+        let interrupt = limits.clone();
+        runtime.set_interrupt_handler(Some(Box::new(move || interrupt.should_interrupt())));
+        runtime.set_memory_limit(MEMORY_LIMIT);
+
+        // Register console, timers, fetch, window, uncaught errors, module
+        // settling, history, navigator and dialogs
         let msgs = console_messages.clone();
+        let clock = timer_clock.clone();
+        let queue = fetch_queue.clone();
+        let (size, styles) = (viewport.clone(), computed_styles.clone());
+        let view = history.clone();
+        let (agent, written) = (user_agent.clone(), clipboard.clone());
+        let (handler, waits) = (dialogs.clone(), limits.clone());
+        let (reported, script) = (console_messages.clone(), current_script.clone());
         context.with(|ctx| {
-            console::register_console(&ctx, msgs)
+            console::register_console(&ctx, msgs)?;
+            timers::register_timers(&ctx, clock)?;
+            fetch::register_fetch(&ctx, queue)?;
+            window::register_window(&ctx, size, styles)?;
+            errors::register_errors(&ctx, reported, script)?;
+            module::register_modules(&ctx)?;
+            history::register_history(&ctx, view)?;
+            navigator::register_navigator(&ctx, agent, written)?;
+            dialog::register_dialogs(&ctx, handler, waits)
         })?;
 
         // Register simplified DOM API
         let dom_clone = shared_dom.clone();
+        let style_dirty = MutationJournal::default();
+        let journal = style_dirty.clone();
+        let removed_nodes = MutationJournal::default();
+        let removed = removed_nodes.clone();
+        let form_controls = form::FormHandle::default();
+        let paint_dirty = form::PaintDirty::default();
+        let form_submissions = form::FormSubmissions::default();
+        let (controls, dirty, submissions) = (form_controls.clone(), paint_dirty.clone(), form_submissions.clone());
         context.with(|ctx| {
-            register_dom_api(&ctx, dom_clone).map_err(|e| {
+            register_dom_api(&ctx, dom_clone, journal, removed, controls, dirty, submissions).map_err(|e| {
                 eprintln!("Failed to register DOM API: {:?}", e);
                 e
             })
         })?;
 
+        // Register constraint validation
+        let validity_reports = form::ValidityReports::default();
+        let (dom_clone, controls, reports) = (shared_dom.clone(), form_controls.clone(), validity_reports.clone());
+        context.with(|ctx| {
+            let document: Object = ctx.globals().get("document")?;
+            form::register_validation(&ctx, &document, dom_clone, controls, reports)
+        })?;
+
+        // Register element geometry
+        let element_geometry = geometry::SharedGeometry::default();
+        let (dom_clone, journal, size, shared) =
+            (shared_dom.clone(), style_dirty.clone(), viewport.clone(), element_geometry.clone());
+        context.with(|ctx| {
+            let document: Object = ctx.globals().get("document")?;
+            geometry::register_geometry(&ctx, &document, dom_clone, journal, size, shared)
+        })?;
+
+        // Register document.cookie
+        let cookies = cookie::CookieHandle::default();
+        let (jar, view) = (cookies.clone(), history.clone());
+        context.with(|ctx| {
+            let document: Object = ctx.globals().get("document")?;
+            cookie::register_cookies(&ctx, &document, jar, view)
+        })?;
+
         Ok(Self {
             runtime,
             context,
             dom: Some(shared_dom),
             console_messages,
+            timer_clock,
+            created: Instant::now(),
+            fetch_queue,
+            viewport,
+            computed_styles,
+            history,
+            style_dirty,
+            removed_nodes,
+            form_controls,
+            paint_dirty,
+            form_submissions,
+            validity_reports,
+            user_agent,
+            clipboard,
+            dialogs,
+            current_script,
+            geometry: element_geometry,
+            cookies,
+            module_fetcher,
+            limits,
         })
     }
 
@@ -81,6 +341,16 @@ impl JsRuntime {
         self.dom.as_ref()
     }
 
+    /// Give scripts the localStorage and sessionStorage of the page's
+    /// origin
+    pub fn set_storage(&self, local: SharedStorage, session: SharedStorage) -> Result<(), JsError> {
+        self.context.with(|ctx| {
+            storage::register_storage(&ctx, "localStorage", local)?;
+            storage::register_storage(&ctx, "sessionStorage", session)
+        })?;
+        Ok(())
+    }
+
     /// Get all console messages
     pub fn get_console_messages(&self) -> Vec<ConsoleMessage> {
         self.console_messages.lock().unwrap().clone()
@@ -97,19 +367,126 @@ impl JsRuntime {
     }
 
     /// Evaluate JavaScript code and return the result as a JsValue
+    ///
+    /// An exception the code throws is reported as uncaught.
     pub fn eval(&self, code: &str) -> Result<JsValue, JsError> {
-        self.context.with(|ctx| {
-            let result: rquickjs::Value = ctx.eval(code)?;
-            Ok(convert_value(&result))
-        })
+        let run = self.limits.begin(RunKind::Callback);
+        let value = self.context.with(|ctx| {
+            let result: rquickjs::Value = ctx.eval(code).map_err(|e| errors::report_exception(&ctx, e))?;
+            Ok::<_, JsError>(convert_value(&result))
+        });
+        self.run_pending_jobs();
+        run.finish(value)
     }
 
     /// Evaluate JavaScript code without returning a value
+    ///
+    /// An exception the code throws is reported as uncaught.
     pub fn exec(&self, code: &str) -> Result<(), JsError> {
-        self.context.with(|ctx| {
-            let _: () = ctx.eval(code)?;
-            Ok(())
-        })
+        let run = self.limits.begin(RunKind::Callback);
+        let result = self
+            .context
+            .with(|ctx| ctx.eval::<(), _>(code).map_err(|e| errors::report_exception(&ctx, e)));
+        self.run_pending_jobs();
+        run.finish(result)
+    }
+
+    /// Run the promise reactions queued by the code that ran last, then
+    /// report the rejections they left unhandled
+    fn run_pending_jobs(&self) {
+        self.drain_jobs();
+        // The jobs of a stopped run wait for the next one
+        if !self.limits.interrupted() && self.context.with(|ctx| errors::report_rejections(&ctx)) > 0 {
+            // Handlers of unhandledrejection may have queued more
+            self.drain_jobs();
+        }
+        if !self.limits.interrupted() {
+            self.forget_removed_wrappers();
+        }
+    }
+
+    /// Let go of the wrappers of the nodes scripts removed and left out of
+    /// any tree, once the jobs that could hand them out, such as mutation
+    /// observers, have run
+    ///
+    /// A script holding one may put it back, which keeps it again.
+    fn forget_removed_wrappers(&self) {
+        let Some(dom) = &self.dom else {
+            return;
+        };
+        let removed = std::mem::take(&mut *self.removed_nodes.borrow_mut());
+        if removed.is_empty() {
+            return;
+        }
+        // A removed node takes its subtree out of the tree with it
+        let detached: Vec<i32> = {
+            let dom = dom.borrow();
+            removed
+                .into_iter()
+                .filter(|&id| dom.parent(id).is_none())
+                .flat_map(|id| std::iter::once(id).chain(dom.descendants(id)))
+                .map(|id| id.0 as i32)
+                .collect()
+        };
+        let forgotten = self.context.with(|ctx| {
+            let forget: Function = ctx.globals().get("__forgetWrappers")?;
+            forget.call::<_, ()>((detached,))
+        });
+        if let Err(e) = forgotten {
+            log::warn!("Failed to let go of removed node wrappers: {}", e);
+        }
+    }
+
+    fn drain_jobs(&self) {
+        while self.runtime.is_job_pending() && !self.limits.interrupted() {
+            if let Err(exception) = self.runtime.execute_pending_job() {
+                let job_ctx = &exception.0;
+                log::warn!("Promise job threw an exception");
+                // Only an uncatchable error, such as a stopped run, fails a
+                // job; clear it so it does not stay pending
+                job_ctx.with(|ctx| drop(ctx.catch()));
+                // The context of the error is the runtime's own, handed out
+                // without a reference of its own, yet dropping it releases
+                // one; take one for it so the context is not freed twice
+                unsafe { rquickjs::qjs::JS_DupContext(job_ctx.as_raw().as_ptr()) };
+            }
+        }
+    }
+
+    /// Take the requests scripts made with fetch() since the last call,
+    /// for the embedder to send
+    pub fn take_fetch_requests(&self) -> Vec<FetchRequest> {
+        std::mem::take(&mut *self.fetch_queue.borrow_mut())
+    }
+
+    /// Settle the promise of a request with its response, or reject it
+    /// with the reason it failed, and run what was waiting on it
+    pub fn complete_fetch(&self, id: u32, result: Result<FetchResponse, String>) -> Result<(), JsError> {
+        let run = self.limits.begin(RunKind::Callback);
+        let completed = self.context.with(|ctx| {
+            let globals = ctx.globals();
+            match result {
+                Ok(response) => {
+                    let resolve: Function = globals.get("__resolveFetch")?;
+                    resolve.call::<_, ()>((
+                        id as i32,
+                        response.status as i32,
+                        response.url,
+                        response.headers,
+                        response.body,
+                    ))?;
+                }
+                Err(message) => {
+                    let reject: Function = globals.get("__rejectFetch")?;
+                    reject.call::<_, ()>((id as i32, message))?;
+                }
+            }
+            Ok::<_, JsError>(())
+        });
+        if completed.is_ok() {
+            self.run_pending_jobs();
+        }
+        run.finish(completed)
     }
 
     /// Execute a script from a file (for <script> tags)
@@ -117,24 +494,256 @@ impl JsRuntime {
         self.exec(code)
     }
 
-    /// Dispatch a click event to an element by its node ID
-    pub fn dispatch_click(&self, node_id: u32) -> Result<(), JsError> {
-        let code = format!(
-            "if (typeof __dispatchEvent === 'function') {{ __dispatchEvent({}, 'click'); }}",
-            node_id
-        );
-        self.exec(&code)
+    /// Set the viewport size scripts see as innerWidth and innerHeight
+    pub fn set_viewport(&self, width: f32, height: f32) {
+        self.viewport.set((width, height));
     }
 
-    /// Dispatch a custom event to an element
-    pub fn dispatch_event(&self, node_id: u32, event_type: &str) -> Result<(), JsError> {
-        let code = format!(
-            "if (typeof __dispatchEvent === 'function') {{ __dispatchEvent({}, '{}'); }}",
-            node_id, event_type
-        );
+    /// Set the user agent `navigator.userAgent` reports, that of the
+    /// embedder's requests
+    pub fn set_user_agent(&self, user_agent: &str) {
+        *self.user_agent.borrow_mut() = user_agent.to_string();
+    }
+
+    /// Take the text scripts last wrote to the clipboard, for the
+    /// embedder to put on the system clipboard
+    pub fn take_clipboard_text(&self) -> Option<String> {
+        self.clipboard.borrow_mut().take()
+    }
+
+    /// Set what shows the dialogs of alert(), confirm() and prompt()
+    pub fn set_dialog_handler(&self, handler: SharedDialogHandler) {
+        *self.dialogs.borrow_mut() = Some(handler);
+    }
+
+    /// Hand over the styles of a style pass for getComputedStyle()
+    pub fn set_computed_styles(&self, styles: Rc<StyleTree>) {
+        *self.computed_styles.borrow_mut() = Some(styles);
+    }
+
+    /// Value of a property of a node as the last style pass computed it,
+    /// as getComputedStyle() reports it
+    pub fn computed_style(&self, node_id: NodeId, property: &str) -> Option<String> {
+        self.computed_styles.borrow().as_ref()?.get_style(node_id)?.property_value(property)
+    }
+
+    /// Hand over the element boxes of a layout of the document as it is
+    /// now, for getBoundingClientRect() and the offset and client sizes
+    pub fn set_element_boxes(&self, boxes: ElementBoxes) {
+        let journal_len = self.style_dirty.borrow().len();
+        self.geometry.borrow_mut().set_boxes(boxes, journal_len);
+    }
+
+    /// Set what lays the page out when scripts measure elements after
+    /// changing the DOM
+    pub fn set_layout_provider(&self, provider: SharedLayoutProvider) {
+        self.geometry.borrow_mut().set_provider(provider);
+    }
+
+    /// Set how far the page is scrolled, which getBoundingClientRect()
+    /// and scrollY follow
+    pub fn set_scroll_y(&self, scroll_y: f32) {
+        self.geometry.borrow_mut().set_scroll_y(scroll_y);
+    }
+
+    /// Set how tall the laid out document is, which scrolls asked for by
+    /// scripts are clamped to
+    pub fn set_content_height(&self, height: f32) {
+        self.geometry.borrow_mut().set_content_height(height);
+    }
+
+    /// Take the scrolls of the page scripts asked for since the last call,
+    /// for the embedder to apply
+    pub fn take_scroll_requests(&self) -> Vec<ScrollRequest> {
+        self.geometry.borrow_mut().take_scroll_requests()
+    }
+
+    /// Set the cookie jar `document.cookie` reads and writes
+    pub fn set_cookie_access(&self, cookies: SharedCookieAccess) {
+        *self.cookies.borrow_mut() = Some(cookies);
+    }
+
+    /// Set what fetches the modules scripts import
+    pub fn set_module_fetcher(&self, fetcher: SharedModuleFetcher) {
+        if let Ok(mut handle) = self.module_fetcher.lock() {
+            *handle = Some(fetcher);
+        }
+    }
+
+    /// Set how long the script of a script element may run before it is
+    /// stopped with `JsError::Terminated`, DEFAULT_EXECUTION_LIMIT until
+    /// set; listeners, timers and other callbacks get at most 2 seconds
+    pub fn set_execution_limit(&self, limit: Duration) {
+        self.limits.set_limit(limit);
+    }
+
+    /// Stop the scripts running now, as the stop button does
+    ///
+    /// A script waiting on a dialog stops once the dialog closes. Nothing
+    /// happens when no script runs.
+    pub fn request_interrupt(&self) {
+        self.limits.request_interrupt();
+    }
+
+    /// Dispatch an event to the listeners on the window, such as `resize`
+    pub fn dispatch_window_event(&self, event_type: &str) -> Result<(), JsError> {
+        let code = format!("__dispatchWindowEvent('{}')", event_type);
         self.exec(&code)
     }
 
+    /// Hand over the tab's current history entry, its state serialized as
+    /// JSON, for `history`
+    pub fn set_history(&self, url: &url::Url, state: Option<&str>, index: usize, length: usize) {
+        self.history.borrow_mut().set(url, state, index, length);
+    }
+
+    /// Take the changes scripts made to the history since the last call,
+    /// for the embedder to apply
+    pub fn take_history_changes(&self) -> Vec<HistoryChange> {
+        self.history.borrow_mut().take_changes()
+    }
+
+    /// Dispatch `popstate` with the state of the entry the user moved to,
+    /// after handing the entry over with `set_history`
+    pub fn dispatch_popstate(&self, state: Option<&str>) -> Result<(), JsError> {
+        let run = self.limits.begin(RunKind::Callback);
+        let dispatched = self.context.with(|ctx| {
+            let dispatch: Function = ctx.globals().get("__dispatchPopState")?;
+            dispatch.call::<_, ()>((state,))?;
+            Ok::<_, JsError>(())
+        });
+        if dispatched.is_ok() {
+            self.run_pending_jobs();
+        }
+        run.finish(dispatched)
+    }
+
+    /// Tell scripts the document is parsed and its scripts have run:
+    /// readyState becomes `interactive` and `DOMContentLoaded` is
+    /// dispatched on the document
+    pub fn dispatch_dom_content_loaded(&self) -> Result<(), JsError> {
+        self.exec("if (typeof __setReadyState === 'function') { __setReadyState('interactive'); }")
+    }
+
+    /// Tell scripts the page is loaded and laid out: readyState becomes
+    /// `complete` and `load` is dispatched on the window
+    pub fn dispatch_load(&self) -> Result<(), JsError> {
+        self.exec("if (typeof __setReadyState === 'function') { __setReadyState('complete'); } __dispatchWindowEvent('load');")
+    }
+
+    /// Hand over the state of the page's form controls, which `value`,
+    /// `checked`, `disabled` and `selectedIndex` read and write from then on
+    pub fn set_form_controls(&self, controls: SharedFormControls) {
+        *self.form_controls.borrow_mut() = Some(controls);
+    }
+
+    /// Check if scripts changed a form control since the last call, so
+    /// the page must be painted again
+    pub fn take_paint_dirty(&self) -> bool {
+        self.paint_dirty.take()
+    }
+
+    /// Take the forms scripts submitted with `submit()` since the last
+    /// call, for the embedder to send without a submit event
+    pub fn take_form_submissions(&self) -> Vec<NodeId> {
+        std::mem::take(&mut *self.form_submissions.borrow_mut())
+    }
+
+    /// Take the invalid controls scripts reported with `reportValidity()`
+    /// since the last call, for the embedder to show their messages
+    pub fn take_validity_reports(&self) -> Vec<NodeId> {
+        std::mem::take(&mut *self.validity_reports.borrow_mut())
+    }
+
+    /// Take the nodes scripts changed since the last call, each once, so
+    /// their style can be computed again
+    pub fn take_style_dirty(&self) -> Vec<NodeId> {
+        let mut nodes = std::mem::take(&mut *self.style_dirty.borrow_mut());
+        self.geometry.borrow_mut().journal_taken(nodes.len());
+        nodes.sort_by_key(|id| id.0);
+        nodes.dedup();
+        nodes
+    }
+
+    /// Dispatch a click event to an element by its node ID
+    ///
+    /// Returns false when a listener canceled the click, so what clicking
+    /// the element does, such as following a link, must not happen.
+    pub fn dispatch_click(&self, node_id: u32) -> Result<bool, JsError> {
+        self.dispatch_event(node_id, "click")
+    }
+
+    /// Dispatch an event to an element
+    ///
+    /// Listeners of the element's ancestors run too: capture listeners
+    /// from the document down, then those of the element, then the others
+    /// back up for event types that bubble. Returns false when a listener
+    /// called preventDefault().
+    pub fn dispatch_event(&self, node_id: u32, event_type: &str) -> Result<bool, JsError> {
+        self.dispatch(node_id, event_type, None)
+    }
+
+    /// Dispatch a keyboard event, such as `keydown`, to an element or the
+    /// document, as dispatch_event() does
+    pub fn dispatch_key_event(&self, node_id: u32, event_type: &str, key: &KeyEvent) -> Result<bool, JsError> {
+        let properties = serde_json::json!({
+            "key": key.key,
+            "code": key.code,
+            "ctrlKey": key.ctrl_key,
+            "shiftKey": key.shift_key,
+            "altKey": key.alt_key,
+            "metaKey": false,
+        });
+        self.dispatch(node_id, event_type, Some(properties.to_string()))
+    }
+
+    /// Dispatch an event with more properties, as JSON, than its type
+    fn dispatch(&self, node_id: u32, event_type: &str, properties: Option<String>) -> Result<bool, JsError> {
+        let Some(dom) = &self.dom else {
+            return Ok(true);
+        };
+        let path = event_path(&dom.borrow(), NodeId::new(node_id));
+        let run = self.limits.begin(RunKind::Callback);
+        let not_canceled = self.context.with(|ctx| {
+            let dispatch: Function = ctx.globals().get("__dispatchEvent")?;
+            let not_canceled: bool = dispatch.call((path, event_type, bubbles(event_type), properties))?;
+            Ok::<_, JsError>(not_canceled)
+        });
+        if not_canceled.is_ok() {
+            self.run_pending_jobs();
+        }
+        run.finish(not_canceled)
+    }
+
+    /// Time since the runtime was created, on the clock of a real event
+    /// loop
+    pub fn elapsed(&self) -> Duration {
+        self.created.elapsed()
+    }
+
+    /// Advance the timer clock to `now`, the time since the runtime was
+    /// created, and run the timers due by then
+    ///
+    /// Returns how many timers ran, each of which may have changed the DOM.
+    pub fn run_due_timers(&self, now: Duration) -> Result<usize, JsError> {
+        self.timer_clock.set(now.max(self.timer_clock.get()));
+        let code = format!("__runDueTimers({})", self.timer_clock.get().as_secs_f64() * 1000.0);
+        let ran = self.eval(&code)?;
+        Ok(ran.as_number().unwrap_or(0.0) as usize)
+    }
+
+    /// Run the requestAnimationFrame callbacks of a frame being rendered,
+    /// `now` being the time since the runtime was created
+    ///
+    /// Callbacks get `now` in milliseconds as their timestamp, which should
+    /// increase from frame to frame. Returns how many ran, each of which
+    /// may have changed the DOM.
+    pub fn run_animation_frames(&self, now: Duration) -> Result<usize, JsError> {
+        let code = format!("__runAnimationFrames({})", now.as_secs_f64() * 1000.0);
+        let ran = self.eval(&code)?;
+        Ok(ran.as_number().unwrap_or(0.0) as usize)
+    }
+
     /// Check if an element has event listeners
     pub fn has_event_listeners(&self, node_id: u32, event_type: &str) -> bool {
         let code = format!(
@@ -148,46 +757,235 @@ impl JsRuntime {
 
     /// Execute all inline <script> tags from the DOM
     ///
-    /// Scripts are executed in document order. This only handles inline scripts,
-    /// not external script sources (src attribute).
+    /// Scripts are executed in document order. This only handles inline
+    /// classic scripts, those with a `src` and modules, which resolve
+    /// their imports against the page's URL, are for the embedder to run
+    /// with run_script() and run_module().
     pub fn execute_scripts(&self) -> Result<Vec<ScriptResult>, JsError> {
-        let dom = match &self.dom {
-            Some(d) => d,
-            None => return Ok(vec![]),
-        };
+        // Collect all scripts first, so scripts can change the DOM
+        let results = self
+            .scripts()
+            .into_iter()
+            .filter_map(|script| match script.source {
+                ScriptSource::Inline(code) if !script.module => Some(self.run_script(script.node_id, &code)),
+                _ => None,
+            })
+            .collect();
+        Ok(results)
+    }
 
-        // Collect all scripts first while holding the borrow, then release it
-        // before executing (so scripts can access the DOM)
-        let scripts: Vec<(u32, String)> = {
-            let dom_ref = dom.borrow();
-            let script_nodes = dom_ref.get_elements_by_tag_name("script");
-            script_nodes
-                .into_iter()
-                .map(|id| (id.0, dom_ref.text_content(id)))
-                .filter(|(_, content)| !content.trim().is_empty())
-                .collect()
+    /// The script elements of the document, in document order
+    ///
+    /// Inline scripts without code are left out, and so are `nomodule`
+    /// scripts, the fallbacks of browsers without modules.
+    pub fn scripts(&self) -> Vec<PageScript> {
+        let Some(dom) = &self.dom else {
+            return vec![];
         };
+        let dom = dom.borrow();
+        dom.get_elements_by_tag_name("script")
+            .into_iter()
+            .filter_map(|id| {
+                let element = dom.get(id)?.as_element()?;
+                let module = element.get_attribute("type").is_some_and(|t| t.trim().eq_ignore_ascii_case("module"));
+                if !module && element.get_attribute("nomodule").is_some() {
+                    return None;
+                }
+                let source = match element.get_attribute("src") {
+                    Some(src) => {
+                        // async wins over defer, modules are always deferred
+                        let timing = if element.get_attribute("async").is_some() {
+                            ScriptTiming::Async
+                        } else if module || element.get_attribute("defer").is_some() {
+                            ScriptTiming::Defer
+                        } else {
+                            ScriptTiming::Classic
+                        };
+                        ScriptSource::External { src: src.to_string(), timing }
+                    }
+                    None => {
+                        let code = dom.text_content(id);
+                        if code.trim().is_empty() {
+                            return None;
+                        }
+                        ScriptSource::Inline(code)
+                    }
+                };
+                Some(PageScript { node_id: id.0, source, module })
+            })
+            .collect()
+    }
 
-        // Now execute scripts without holding the DOM borrow
-        let mut results = Vec::new();
-        for (node_id, content) in scripts {
-            let result = self.exec(&content);
-            results.push(ScriptResult {
-                node_id,
-                success: result.is_ok(),
-                error: result.err().map(|e| e.to_string()),
-            });
+    /// Run the code of a script element
+    pub fn run_script(&self, node_id: u32, code: &str) -> ScriptResult {
+        self.run_script_from(ScriptOrigin { node_id, url: None }, code)
+    }
+
+    /// Run the code of a script element fetched from `src`
+    pub fn run_external_script(&self, node_id: u32, src: &str, fetch_status: Option<u16>, code: &str) -> ScriptResult {
+        ScriptResult {
+            src: Some(src.to_string()),
+            fetch_status,
+            ..self.run_script_from(ScriptOrigin { node_id, url: Some(src.to_string()) }, code)
+        }
+    }
+
+    /// Run the code of a script element, errors it throws reported as
+    /// coming from it
+    fn run_script_from(&self, origin: ScriptOrigin, code: &str) -> ScriptResult {
+        let node_id = origin.node_id;
+        // Restored after, the script may run another, as a dialog's loop
+        let outer = self.current_script.replace(Some(origin));
+        let run = self.limits.begin(RunKind::Script);
+        let result = run.finish(self.exec(code));
+        self.current_script.replace(outer);
+        ScriptResult {
+            node_id,
+            src: None,
+            fetch_status: None,
+            module: false,
+            success: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
         }
+    }
 
-        Ok(results)
+    /// Run the code of an inline module script of a document whose base
+    /// URL is `base`, which its imports resolve against
+    pub fn run_module(&self, node_id: u32, base: &url::Url, code: &str) -> ScriptResult {
+        let origin = ScriptOrigin { node_id, url: None };
+        self.run_module_from(origin, &module::inline_module_name(base, node_id), code)
+    }
+
+    /// Run the code of a module script fetched from `url`
+    pub fn run_external_module(
+        &self,
+        node_id: u32,
+        url: &url::Url,
+        fetch_status: Option<u16>,
+        code: &str,
+    ) -> ScriptResult {
+        ScriptResult {
+            src: Some(url.to_string()),
+            fetch_status,
+            ..self.run_module_from(ScriptOrigin { node_id, url: Some(url.to_string()) }, url.as_str(), code)
+        }
+    }
+
+    /// Compile a module named `name` and run it with what it imports,
+    /// errors it throws while it runs reported as coming from the element
+    fn run_module_from(&self, origin: ScriptOrigin, name: &str, code: &str) -> ScriptResult {
+        let node_id = origin.node_id;
+        let outer = self.current_script.replace(Some(origin));
+        let run = self.limits.begin(RunKind::Script);
+        let evaluated = self.context.with(|ctx| {
+            // Modules that fail to compile or import throw right away
+            let promise = Module::evaluate(ctx.clone(), name, code).map_err(|e| errors::report_exception(&ctx, e))?;
+            let settle: Function = ctx.globals().get("__settleModule")?;
+            settle.call::<(Promise,), ()>((promise,))?;
+            Ok::<_, JsError>(())
+        });
+        self.run_pending_jobs();
+        let result = evaluated.and_then(|()| {
+            let failed: Option<String> = self.context.with(|ctx| {
+                let take: Function = ctx.globals().get("__takeModuleError")?;
+                take.call::<_, Option<String>>(())
+            })?;
+            failed.map_or(Ok(()), |message| Err(JsError::runtime(message)))
+        });
+        let result = run.finish(result);
+        self.current_script.replace(outer);
+        ScriptResult {
+            node_id,
+            src: None,
+            fetch_status: None,
+            module: true,
+            success: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+
+    /// Report that the file of a script element could not be fetched: the
+    /// console shows why and `error` is dispatched on the element
+    pub fn script_fetch_failed(&self, node_id: u32, src: &str, fetch_status: Option<u16>, error: &str) -> ScriptResult {
+        let message = format!("Failed to load script {}: {}", src, error);
+        if let Ok(mut messages) = self.console_messages.lock() {
+            messages.push(ConsoleMessage::text(LogLevel::Error, message.clone()));
+        }
+        if let Err(e) = self.dispatch_event(node_id, "error") {
+            log::warn!("Script error dispatch failed: {}", e);
+        }
+        ScriptResult {
+            node_id,
+            src: Some(src.to_string()),
+            fetch_status,
+            module: false,
+            success: false,
+            error: Some(message),
+        }
     }
 }
 
+/// Propagation path of an event, the target and its ancestors up to the
+/// document
+fn event_path(dom: &DomTree, target: NodeId) -> Vec<i32> {
+    std::iter::successors(Some(target), |&id| dom.get(id).and_then(|n| n.parent))
+        .map(|id| id.0 as i32)
+        .collect()
+}
+
+/// The properties of a keyboard event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyEvent {
+    /// What the key means, as `a`, `A` or `ArrowDown`
+    pub key: String,
+    /// Which physical key it is, as `KeyA` or `ArrowDown`
+    pub code: String,
+    pub ctrl_key: bool,
+    pub shift_key: bool,
+    pub alt_key: bool,
+}
+
+/// A script element of the document
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageScript {
+    pub node_id: u32,
+    pub source: ScriptSource,
+    /// A module script, `type="module"`
+    pub module: bool,
+}
+
+/// Where the code of a script element comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptSource {
+    /// The element's text
+    Inline(String),
+    /// A file, `src` as written in the attribute
+    External { src: String, timing: ScriptTiming },
+}
+
+/// When an external script runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptTiming {
+    /// In document order, the scripts after it wait until it is fetched
+    Classic,
+    /// After the document is parsed, in document order
+    Defer,
+    /// As soon as it is fetched
+    Async,
+}
+
 /// Result of executing a script tag
 #[derive(Debug, Clone)]
 pub struct ScriptResult {
     /// The node ID of the script element
     pub node_id: u32,
+    /// URL the script was fetched from, None for inline scripts
+    pub src: Option<String>,
+    /// HTTP status of fetching the script, None for inline scripts and
+    /// when no response came
+    pub fetch_status: Option<u16>,
+    /// Whether it ran as a module script
+    pub module: bool,
     /// Whether the script executed successfully
     pub success: bool,
     /// Error message if the script failed
@@ -200,11 +998,31 @@ impl Default for JsRuntime {
     }
 }
 
+/// Check if events of a type go back up through the target's ancestors
+fn bubbles(event_type: &str) -> bool {
+    !matches!(
+        event_type,
+        "focus" | "blur" | "load" | "unload" | "error" | "scroll" | "mouseenter" | "mouseleave" | "invalid"
+    )
+}
+
 /// Register simplified DOM API
-fn register_dom_api(ctx: &rquickjs::Ctx<'_>, dom: SharedDom) -> Result<(), rquickjs::Error> {
+fn register_dom_api(
+    ctx: &rquickjs::Ctx<'_>,
+    dom: SharedDom,
+    journal: MutationJournal,
+    removed: MutationJournal,
+    controls: form::FormHandle,
+    paint_dirty: form::PaintDirty,
+    form_submissions: form::FormSubmissions,
+) -> Result<(), rquickjs::Error> {
     let globals = ctx.globals();
 
     let document = Object::new(ctx.clone())?;
+    let observers = SharedObservers::default();
+
+    // Node ID of the document, the target of document-level events
+    document.set("_documentId", dom.borrow().document_id().0 as i32)?;
 
     // document.getElementById returns element ID or -1
     let dom_clone = dom.clone();
@@ -244,6 +1062,42 @@ fn register_dom_api(ctx: &rquickjs::Ctx<'_>, dom: SharedDom) -> Result<(), rquic
         })?,
     )?;
 
+    // querySelector and querySelectorAll run the selector engine within a
+    // scope node; selectors are checked first, _selectorError returns why
+    // they are not valid
+    document.set(
+        "_selectorError",
+        Function::new(ctx.clone(), |selectors: String| -> Option<String> {
+            Selector::parse_list(&selectors).err().map(|e| e.to_string())
+        })?,
+    )?;
+
+    let dom_clone = dom.clone();
+    document.set(
+        "_querySelector",
+        Function::new(ctx.clone(), move |scope: i32, selectors: String| -> i32 {
+            let dom = dom_clone.borrow();
+            dom.query_selector(NodeId::new(scope as u32), &selectors)
+                .ok()
+                .flatten()
+                .map(|nid| nid.0 as i32)
+                .unwrap_or(-1)
+        })?,
+    )?;
+
+    let dom_clone = dom.clone();
+    document.set(
+        "_querySelectorAll",
+        Function::new(ctx.clone(), move |scope: i32, selectors: String| -> Vec<i32> {
+            let dom = dom_clone.borrow();
+            dom.query_selector_all(NodeId::new(scope as u32), &selectors)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|nid| nid.0 as i32)
+                .collect()
+        })?,
+    )?;
+
     // document.createElement returns new element ID
     let dom_clone = dom.clone();
     document.set(
@@ -264,6 +1118,13 @@ fn register_dom_api(ctx: &rquickjs::Ctx<'_>, dom: SharedDom) -> Result<(), rquic
         })?,
     )?;
 
+    // document.createDocumentFragment returns new fragment ID
+    let dom_clone = dom.clone();
+    document.set(
+        "_createDocumentFragment",
+        Function::new(ctx.clone(), move || -> i32 { dom_clone.borrow_mut().create_document_fragment().0 as i32 })?,
+    )?;
+
     // _getTagName
     let dom_clone = dom.clone();
     document.set(
@@ -293,69 +1154,338 @@ fn register_dom_api(ctx: &rquickjs::Ctx<'_>, dom: SharedDom) -> Result<(), rquic
         })?,
     )?;
 
+    // _eventPath, for events scripts dispatch
+    let dom_clone = dom.clone();
+    document.set(
+        "_eventPath",
+        Function::new(ctx.clone(), move |node_id: i32| -> Vec<i32> {
+            event_path(&dom_clone.borrow(), NodeId::new(node_id as u32))
+        })?,
+    )?;
+
+    // _hasAttribute, as boolean attributes are empty
+    let dom_clone = dom.clone();
+    document.set(
+        "_hasAttribute",
+        Function::new(ctx.clone(), move |node_id: i32, name: String| -> bool {
+            let dom = dom_clone.borrow();
+            dom.get(NodeId::new(node_id as u32))
+                .and_then(|n| n.as_element())
+                .is_some_and(|e| e.get_attribute(&name).is_some())
+        })?,
+    )?;
+
     // _setAttribute
     let dom_clone = dom.clone();
+    let journal_clone = journal.clone();
+    let observers_clone = observers.clone();
     document.set(
         "_setAttribute",
         Function::new(ctx.clone(), move |node_id: i32, name: String, value: String| {
             let mut dom = dom_clone.borrow_mut();
             let nid = NodeId::new(node_id as u32);
+            let old_value = dom.get(nid).and_then(|n| n.as_element()).and_then(|e| e.get_attribute(&name));
+            let old_value = old_value.map(str::to_string);
             dom.set_attribute(nid, &name, &value);
+            journal_clone.borrow_mut().push(nid);
+            observers_clone.borrow_mut().record(&dom, MutationRecord::attribute(nid, &name, old_value));
         })?,
     )?;
 
-    // _appendChild
+    // _appendChild, _insertBefore, _replaceChild and _removeChild return
+    // the error a script gets, or nothing
     let dom_clone = dom.clone();
+    let journal_clone = journal.clone();
+    let observers_clone = observers.clone();
     document.set(
         "_appendChild",
-        Function::new(ctx.clone(), move |parent_id: i32, child_id: i32| {
-            let mut dom = dom_clone.borrow_mut();
-            let parent = NodeId::new(parent_id as u32);
-            let child = NodeId::new(child_id as u32);
-            let _ = dom.append_child(parent, child);
+        Function::new(ctx.clone(), move |parent_id: i32, child_id: i32| -> Option<String> {
+            let (parent, child) = (NodeId::new(parent_id as u32), NodeId::new(child_id as u32));
+            edit_children(&dom_clone, &journal_clone, &observers_clone, parent, child, |dom| {
+                dom.append_child(parent, child)
+            })
         })?,
     )?;
 
-    // _getTextContent
     let dom_clone = dom.clone();
+    let journal_clone = journal.clone();
+    let observers_clone = observers.clone();
     document.set(
-        "_getTextContent",
-        Function::new(ctx.clone(), move |node_id: i32| -> String {
-            let dom = dom_clone.borrow();
-            let nid = NodeId::new(node_id as u32);
-            dom.text_content(nid)
+        "_insertBefore",
+        Function::new(ctx.clone(), move |parent_id: i32, child_id: i32, reference_id: Option<i32>| -> Option<String> {
+            let (parent, child) = (NodeId::new(parent_id as u32), NodeId::new(child_id as u32));
+            let reference = reference_id.map(|id| NodeId::new(id as u32));
+            edit_children(&dom_clone, &journal_clone, &observers_clone, parent, child, |dom| {
+                dom.insert_before(parent, child, reference)
+            })
         })?,
     )?;
 
-    globals.set("document", document)?;
-
-    // Now inject JavaScript wrappers to create a nicer API
-    let wrapper_code = r#"
-        (function() {
-            // Event listener storage: Map<nodeId, Map<eventType, Array<listener>>>
-            var __eventListeners = {};
-
-            // Element wrapper class
-            function Element(nodeId) {
-                this.__nodeId = nodeId;
-            }
-
-            Object.defineProperty(Element.prototype, 'tagName', {
-                get: function() { return document._getTagName(this.__nodeId); }
+    let dom_clone = dom.clone();
+    let journal_clone = journal.clone();
+    let observers_clone = observers.clone();
+    let removed_clone = removed.clone();
+    document.set(
+        "_replaceChild",
+        Function::new(ctx.clone(), move |parent_id: i32, new_id: i32, old_id: i32| -> Option<String> {
+            let (parent, new, old) =
+                (NodeId::new(parent_id as u32), NodeId::new(new_id as u32), NodeId::new(old_id as u32));
+            let error = edit_children(&dom_clone, &journal_clone, &observers_clone, parent, new, |dom| {
+                dom.replace_child(parent, new, old)
             });
+            if error.is_none() {
+                removed_clone.borrow_mut().push(old);
+            }
+            error
+        })?,
+    )?;
 
-            Object.defineProperty(Element.prototype, 'id', {
-                get: function() { return document._getAttribute(this.__nodeId, 'id'); },
-                set: function(v) { document._setAttribute(this.__nodeId, 'id', v); }
+    let dom_clone = dom.clone();
+    let journal_clone = journal.clone();
+    let observers_clone = observers.clone();
+    let removed_clone = removed.clone();
+    document.set(
+        "_removeChild",
+        Function::new(ctx.clone(), move |parent_id: i32, child_id: i32| -> Option<String> {
+            let (parent, child) = (NodeId::new(parent_id as u32), NodeId::new(child_id as u32));
+            let error = edit_children(&dom_clone, &journal_clone, &observers_clone, parent, child, |dom| {
+                dom.remove_child(parent, child)
             });
+            if error.is_none() {
+                removed_clone.borrow_mut().push(child);
+            }
+            error
+        })?,
+    )?;
 
-            Object.defineProperty(Element.prototype, 'className', {
-                get: function() { return document._getAttribute(this.__nodeId, 'class'); },
-                set: function(v) { document._setAttribute(this.__nodeId, 'class', v); }
+    // _parentElement, -1 for a node whose parent is not an element
+    let dom_clone = dom.clone();
+    document.set(
+        "_parentElement",
+        Function::new(ctx.clone(), move |node_id: i32| -> i32 {
+            let dom = dom_clone.borrow();
+            dom.parent(NodeId::new(node_id as u32))
+                .filter(|&id| dom.get(id).is_some_and(|n| n.is_element()))
+                .map_or(-1, |id| id.0 as i32)
+        })?,
+    )?;
+
+    // _contains, a node contains itself
+    let dom_clone = dom.clone();
+    document.set(
+        "_contains",
+        Function::new(ctx.clone(), move |node_id: i32, other_id: i32| -> bool {
+            dom_clone.borrow().contains(NodeId::new(node_id as u32), NodeId::new(other_id as u32))
+        })?,
+    )?;
+
+    // _parentNode, -1 for a node not in a tree
+    let dom_clone = dom.clone();
+    document.set(
+        "_parentNode",
+        Function::new(ctx.clone(), move |node_id: i32| -> i32 {
+            dom_clone.borrow().parent(NodeId::new(node_id as u32)).map_or(-1, |id| id.0 as i32)
+        })?,
+    )?;
+
+    // _childNodes, only the elements with `elementsOnly`
+    let dom_clone = dom.clone();
+    document.set(
+        "_childNodes",
+        Function::new(ctx.clone(), move |node_id: i32, elements_only: bool| -> Vec<i32> {
+            let dom = dom_clone.borrow();
+            dom.children(NodeId::new(node_id as u32))
+                .into_iter()
+                .filter(|&id| !elements_only || dom.get(id).is_some_and(|n| n.is_element()))
+                .map(|id| id.0 as i32)
+                .collect()
+        })?,
+    )?;
+
+    // _nextSibling, -1 for the last child
+    let dom_clone = dom.clone();
+    document.set(
+        "_nextSibling",
+        Function::new(ctx.clone(), move |node_id: i32| -> i32 {
+            let dom = dom_clone.borrow();
+            dom.get(NodeId::new(node_id as u32)).and_then(|n| n.next_sibling).map_or(-1, |id| id.0 as i32)
+        })?,
+    )?;
+
+    // _removeAttribute
+    let dom_clone = dom.clone();
+    let journal_clone = journal.clone();
+    let observers_clone = observers.clone();
+    document.set(
+        "_removeAttribute",
+        Function::new(ctx.clone(), move |node_id: i32, name: String| {
+            let mut dom = dom_clone.borrow_mut();
+            let nid = NodeId::new(node_id as u32);
+            let old_value = dom.get(nid).and_then(|n| n.as_element()).and_then(|e| e.get_attribute(&name));
+            let old_value = old_value.map(str::to_string);
+            dom.remove_attribute(nid, &name);
+            journal_clone.borrow_mut().push(nid);
+            if old_value.is_some() {
+                observers_clone.borrow_mut().record(&dom, MutationRecord::attribute(nid, &name, old_value));
+            }
+        })?,
+    )?;
+
+    // _setTextContent, the parent of a text node is what restyles
+    let dom_clone = dom.clone();
+    let journal_clone = journal.clone();
+    let observers_clone = observers.clone();
+    let removed_clone = removed.clone();
+    document.set(
+        "_setTextContent",
+        Function::new(ctx.clone(), move |node_id: i32, text: String| {
+            let mut dom = dom_clone.borrow_mut();
+            let nid = NodeId::new(node_id as u32);
+            let old_text = match dom.get(nid).map(|n| &n.node_type) {
+                Some(NodeType::Text(text) | NodeType::Comment(text)) => Some(text.clone()),
+                _ => None,
+            };
+            let children = dom.children(nid);
+            if dom.set_text_content(nid, &text).is_ok() {
+                let changed = if dom.get(nid).is_some_and(|n| n.is_element()) { Some(nid) } else { dom.parent(nid) };
+                journal_clone.borrow_mut().extend(changed);
+                removed_clone.borrow_mut().extend(&children);
+                let mut observers = observers_clone.borrow_mut();
+                match old_text {
+                    Some(old_text) => observers.record(&dom, MutationRecord::character_data(nid, old_text)),
+                    None => observers.record_children(&dom, nid, &children),
+                }
+            }
+        })?,
+    )?;
+
+    // _getTextContent
+    let dom_clone = dom.clone();
+    document.set(
+        "_getTextContent",
+        Function::new(ctx.clone(), move |node_id: i32| -> String {
+            let dom = dom_clone.borrow();
+            let nid = NodeId::new(node_id as u32);
+            dom.text_content(nid)
+        })?,
+    )?;
+
+    globals.set("document", document.clone())?;
+
+    // Now inject JavaScript wrappers to create a nicer API
+    let wrapper_code = r#"
+        (function() {
+            // Event listener storage: Map<nodeId, Map<eventType, Array<entry>>>,
+            // each entry { listener, capture, once, passive }
+            var __eventListeners = {};
+            var documentId = document._documentId;
+            var readyState = 'loading';
+
+            // addEventListener options are an object or the capture flag
+            function listenerOptions(options) {
+                if (typeof options === 'object' && options !== null) {
+                    return { capture: !!options.capture, once: !!options.once, passive: !!options.passive };
+                }
+                return { capture: !!options, once: false, passive: false };
+            }
+
+            function findListener(list, listener, capture) {
+                for (var i = 0; i < list.length; i++) {
+                    if (list[i].listener === listener && list[i].capture === capture) return i;
+                }
+                return -1;
+            }
+
+            function addListener(nodeId, type, listener, options) {
+                if (typeof listener !== 'function') return;
+                options = listenerOptions(options);
+                if (!__eventListeners[nodeId]) {
+                    __eventListeners[nodeId] = {};
+                }
+                if (!__eventListeners[nodeId][type]) {
+                    __eventListeners[nodeId][type] = [];
+                }
+                // Avoid duplicate listeners
+                var list = __eventListeners[nodeId][type];
+                if (findListener(list, listener, options.capture) === -1) {
+                    list.push({ listener: listener, capture: options.capture, once: options.once, passive: options.passive });
+                }
+            }
+
+            function removeListener(nodeId, type, listener, options) {
+                if (!__eventListeners[nodeId] || !__eventListeners[nodeId][type]) {
+                    return;
+                }
+                var list = __eventListeners[nodeId][type];
+                var idx = findListener(list, listener, listenerOptions(options).capture);
+                if (idx !== -1) {
+                    list[idx].removed = true;
+                    list.splice(idx, 1);
+                }
+            }
+
+            // Element wrapper class. A node has one wrapper, so lookups of
+            // it return the same object, with the properties scripts gave it
+            var wrappers = new Map();
+            // Wrappers of nodes out of any tree, held weakly so they go
+            // once scripts let go of them
+            var detachedWrappers = new Map();
+
+            function Element(nodeId) {
+                var wrapper = wrappers.get(nodeId) || detachedWrapper(nodeId);
+                if (wrapper) return wrapper;
+                this.__nodeId = nodeId;
+                wrappers.set(nodeId, this);
+            }
+
+            function detachedWrapper(nodeId) {
+                var held = detachedWrappers.get(nodeId);
+                return held && held.deref();
+            }
+
+            // Wrappers of nodes removed and left out of any tree are let
+            // go of; putting a node back keeps those scripts still hold in
+            // its subtree again
+            globalThis.__forgetWrappers = function(ids) {
+                ids.forEach(function(id) {
+                    var wrapper = wrappers.get(id);
+                    if (!wrapper) return;
+                    wrappers.delete(id);
+                    detachedWrappers.set(id, new WeakRef(wrapper));
+                });
+            };
+            globalThis.__wrapperCount = function() { return wrappers.size; };
+
+            function keepWrapper(node) {
+                if (!wrappers.has(node.__nodeId)) wrappers.set(node.__nodeId, node);
+                detachedWrappers.forEach(function(held, id) {
+                    var wrapper = held.deref();
+                    if (!wrapper) {
+                        detachedWrappers.delete(id);
+                    } else if (document._contains(node.__nodeId, id)) {
+                        wrappers.set(id, wrapper);
+                        detachedWrappers.delete(id);
+                    }
+                });
+            }
+
+            Object.defineProperty(Element.prototype, 'tagName', {
+                get: function() { return document._getTagName(this.__nodeId); }
+            });
+
+            Object.defineProperty(Element.prototype, 'id', {
+                get: function() { return document._getAttribute(this.__nodeId, 'id'); },
+                set: function(v) { document._setAttribute(this.__nodeId, 'id', v); }
+            });
+
+            Object.defineProperty(Element.prototype, 'className', {
+                get: function() { return document._getAttribute(this.__nodeId, 'class'); },
+                set: function(v) { document._setAttribute(this.__nodeId, 'class', v); }
             });
 
             Object.defineProperty(Element.prototype, 'textContent', {
-                get: function() { return document._getTextContent(this.__nodeId); }
+                get: function() { return document._getTextContent(this.__nodeId); },
+                set: function(v) { document._setTextContent(this.__nodeId, v == null ? '' : String(v)); }
             });
 
             Element.prototype.getAttribute = function(name) {
@@ -363,42 +1493,133 @@ fn register_dom_api(ctx: &rquickjs::Ctx<'_>, dom: SharedDom) -> Result<(), rquic
                 return val === '' ? null : val;
             };
 
+            Element.prototype.hasAttribute = function(name) {
+                return document._hasAttribute(this.__nodeId, String(name));
+            };
+
             Element.prototype.setAttribute = function(name, value) {
                 document._setAttribute(this.__nodeId, name, String(value));
             };
 
+            // Throw the error a native returned as `Name:message`
+            function checkDomError(error) {
+                if (error === undefined || error === null) return;
+                var split = error.indexOf(':');
+                var exception = new Error(error.slice(split + 1));
+                exception.name = error.slice(0, split);
+                throw exception;
+            }
+
+            function nodeIdOf(node) {
+                if (!(node instanceof Element)) {
+                    throw new TypeError('The argument is not a node.');
+                }
+                return node.__nodeId;
+            }
+
             Element.prototype.appendChild = function(child) {
-                document._appendChild(this.__nodeId, child.__nodeId);
+                checkDomError(document._appendChild(this.__nodeId, nodeIdOf(child)));
+                keepWrapper(child);
                 return child;
             };
 
-            // Event handling methods
-            Element.prototype.addEventListener = function(type, listener) {
-                if (typeof listener !== 'function') return;
-                var nodeId = this.__nodeId;
-                if (!__eventListeners[nodeId]) {
-                    __eventListeners[nodeId] = {};
-                }
-                if (!__eventListeners[nodeId][type]) {
-                    __eventListeners[nodeId][type] = [];
+            Element.prototype.insertBefore = function(node, reference) {
+                var referenceId = reference === null || reference === undefined ? undefined : nodeIdOf(reference);
+                checkDomError(document._insertBefore(this.__nodeId, nodeIdOf(node), referenceId));
+                keepWrapper(node);
+                return node;
+            };
+
+            Element.prototype.replaceChild = function(node, child) {
+                checkDomError(document._replaceChild(this.__nodeId, nodeIdOf(node), nodeIdOf(child)));
+                keepWrapper(node);
+                return child;
+            };
+
+            Element.prototype.removeChild = function(child) {
+                checkDomError(document._removeChild(this.__nodeId, nodeIdOf(child)));
+                return child;
+            };
+
+            Element.prototype.remove = function() {
+                var parentId = document._parentNode(this.__nodeId);
+                if (parentId >= 0) document._removeChild(parentId, this.__nodeId);
+            };
+
+            Object.defineProperty(Element.prototype, 'parentNode', {
+                get: function() {
+                    var parentId = document._parentNode(this.__nodeId);
+                    return parentId >= 0 ? nodeObject(parentId) : null;
                 }
-                // Avoid duplicate listeners
-                if (__eventListeners[nodeId][type].indexOf(listener) === -1) {
-                    __eventListeners[nodeId][type].push(listener);
+            });
+
+            Object.defineProperty(Element.prototype, 'parentElement', {
+                get: function() {
+                    var parentId = document._parentElement(this.__nodeId);
+                    return parentId >= 0 ? new Element(parentId) : null;
                 }
+            });
+
+            // Whether a node is this one or one of its descendants
+            function containsNode(nodeId, other) {
+                if (other === document) return nodeId === documentId;
+                return other instanceof Element && document._contains(nodeId, other.__nodeId);
+            }
+
+            Element.prototype.contains = function(other) {
+                return containsNode(this.__nodeId, other);
             };
 
-            Element.prototype.removeEventListener = function(type, listener) {
-                var nodeId = this.__nodeId;
-                if (!__eventListeners[nodeId] || !__eventListeners[nodeId][type]) {
-                    return;
-                }
-                var idx = __eventListeners[nodeId][type].indexOf(listener);
-                if (idx !== -1) {
-                    __eventListeners[nodeId][type].splice(idx, 1);
+            document.contains = function(other) {
+                return containsNode(documentId, other);
+            };
+
+            Object.defineProperty(Element.prototype, 'childNodes', {
+                get: function() { return new NodeList(document._childNodes(this.__nodeId, false)); }
+            });
+
+            Object.defineProperty(Element.prototype, 'children', {
+                get: function() { return new NodeList(document._childNodes(this.__nodeId, true)); }
+            });
+
+            Object.defineProperty(Element.prototype, 'firstChild', {
+                get: function() { return this.childNodes[0] || null; }
+            });
+
+            Object.defineProperty(Element.prototype, 'nextSibling', {
+                get: function() {
+                    var siblingId = document._nextSibling(this.__nodeId);
+                    return siblingId >= 0 ? new Element(siblingId) : null;
                 }
+            });
+
+            Element.prototype.removeAttribute = function(name) {
+                document._removeAttribute(this.__nodeId, String(name));
+            };
+
+            // Event handling methods
+            Element.prototype.addEventListener = function(type, listener, options) {
+                addListener(this.__nodeId, type, listener, options);
             };
 
+            Element.prototype.removeEventListener = function(type, listener, options) {
+                removeListener(this.__nodeId, type, listener, options);
+            };
+
+            // The document is a target too, for DOMContentLoaded and
+            // readystatechange
+            document.addEventListener = function(type, listener, options) {
+                addListener(documentId, type, listener, options);
+            };
+
+            document.removeEventListener = function(type, listener, options) {
+                removeListener(documentId, type, listener, options);
+            };
+
+            Object.defineProperty(document, 'readyState', {
+                get: function() { return readyState; }
+            });
+
             // Document API wrappers
             document.getElementById = function(id) {
                 var nodeId = document._getElementId(id);
@@ -423,42 +1644,159 @@ fn register_dom_api(ctx: &rquickjs::Ctx<'_>, dom: SharedDom) -> Result<(), rquic
                 return new Element(document._createTextNode(text));
             };
 
-            document.querySelector = function(selector) {
-                if (selector.charAt(0) === '#') {
-                    return document.getElementById(selector.slice(1));
+            // Inserting a fragment inserts its children, in one change
+            document.createDocumentFragment = function() {
+                return new Element(document._createDocumentFragment());
+            };
+
+            // A static list of elements, as querySelectorAll returns
+            function NodeList(ids) {
+                for (var i = 0; i < ids.length; i++) {
+                    this[i] = new Element(ids[i]);
+                }
+                Object.defineProperty(this, 'length', { value: ids.length });
+            }
+
+            NodeList.prototype.item = function(index) {
+                return this[Number(index) | 0] || null;
+            };
+
+            NodeList.prototype.forEach = function(callback, thisArg) {
+                for (var i = 0; i < this.length; i++) {
+                    callback.call(thisArg, this[i], i, this);
                 }
-                if (selector.charAt(0) === '.') {
-                    var els = document.getElementsByClassName(selector.slice(1));
-                    return els.length > 0 ? els[0] : null;
+            };
+
+            NodeList.prototype[Symbol.iterator] = Array.prototype[Symbol.iterator];
+
+            function checkSelectors(selectors) {
+                var error = document._selectorError(selectors);
+                if (error !== undefined && error !== null) {
+                    var exception = new Error("'" + selectors + "' is not a valid selector: " + error);
+                    exception.name = 'SyntaxError';
+                    throw exception;
                 }
-                var els = document.getElementsByTagName(selector);
-                return els.length > 0 ? els[0] : null;
+            }
+
+            function querySelector(scope, selectors) {
+                selectors = String(selectors);
+                checkSelectors(selectors);
+                var nodeId = document._querySelector(scope, selectors);
+                return nodeId >= 0 ? new Element(nodeId) : null;
+            }
+
+            function querySelectorAll(scope, selectors) {
+                selectors = String(selectors);
+                checkSelectors(selectors);
+                return new NodeList(document._querySelectorAll(scope, selectors));
+            }
+
+            document.querySelector = function(selectors) {
+                return querySelector(documentId, selectors);
+            };
+
+            document.querySelectorAll = function(selectors) {
+                return querySelectorAll(documentId, selectors);
+            };
+
+            // Scoped to the element's descendants
+            Element.prototype.querySelector = function(selectors) {
+                return querySelector(this.__nodeId, selectors);
+            };
+
+            Element.prototype.querySelectorAll = function(selectors) {
+                return querySelectorAll(this.__nodeId, selectors);
             };
 
             // Global functions for Rust to call into JS for event dispatching
-            globalThis.__dispatchEvent = function(nodeId, eventType) {
-                if (!__eventListeners[nodeId] || !__eventListeners[nodeId][eventType]) {
-                    return;
+            function nodeObject(nodeId) {
+                return nodeId === documentId ? document : new Element(nodeId);
+            }
+
+            // Run the capture or the other listeners of one node for a
+            // phase of the event
+            function invokeListeners(event, nodeId, phase, capture) {
+                var entries = (__eventListeners[nodeId] && __eventListeners[nodeId][event.type] || []).slice();
+                // The document also has handler properties, as onreadystatechange
+                var handler = nodeId === documentId ? document['on' + event.type] : undefined;
+                if (typeof handler === 'function') {
+                    entries.push({ listener: handler, capture: false, once: false, passive: false });
                 }
-                // Create a simple event object
-                var event = {
-                    type: eventType,
-                    target: new Element(nodeId),
-                    currentTarget: new Element(nodeId),
-                    preventDefault: function() { this.defaultPrevented = true; },
-                    stopPropagation: function() { this.propagationStopped = true; },
-                    defaultPrevented: false,
-                    propagationStopped: false
-                };
-                // Call all listeners
-                var listeners = __eventListeners[nodeId][eventType].slice();
-                for (var i = 0; i < listeners.length; i++) {
+                event.currentTarget = nodeObject(nodeId);
+                event.eventPhase = phase;
+                for (var i = 0; i < entries.length; i++) {
+                    var entry = entries[i];
+                    if (entry.removed || entry.capture !== capture) {
+                        continue;
+                    }
+                    if (entry.once) {
+                        removeListener(nodeId, event.type, entry.listener, entry.capture);
+                    }
+                    event.__passive = entry.passive;
                     try {
-                        listeners[i].call(event.target, event);
+                        entry.listener.call(event.currentTarget, event);
                     } catch (e) {
-                        console.error('Event listener error: ' + e);
+                        __reportException(e);
+                    }
+                    event.__passive = false;
+                    if (event.__immediateStopped) break;
+                }
+            }
+
+            // Dispatch an event along its propagation path, the target
+            // first and the document last, with the properties of its kind
+            // as JSON. Returns false when a listener canceled it with
+            // preventDefault().
+            globalThis.__dispatchEvent = function(path, eventType, bubbles, properties) {
+                var event = Object.assign(properties ? JSON.parse(properties) : {}, {
+                    type: eventType,
+                    target: nodeObject(path[0]),
+                    currentTarget: null,
+                    eventPhase: 0,
+                    bubbles: bubbles,
+                    cancelable: true,
+                    defaultPrevented: false,
+                    __passive: false,
+                    __stopped: false,
+                    __immediateStopped: false,
+                    preventDefault: function() {
+                        if (!this.__passive) this.defaultPrevented = true;
+                    },
+                    stopPropagation: function() { this.__stopped = true; },
+                    stopImmediatePropagation: function() {
+                        this.__stopped = true;
+                        this.__immediateStopped = true;
+                    }
+                });
+                // Capture listeners run on the way down, the others on the
+                // way up, at the target the capture ones first
+                for (var i = path.length - 1; i > 0 && !event.__stopped; i--) {
+                    invokeListeners(event, path[i], 1, true);
+                }
+                // Stopping propagation still runs the rest of the target's
+                // listeners, stopping it immediately doesn't
+                if (!event.__stopped) {
+                    invokeListeners(event, path[0], 2, true);
+                    if (!event.__immediateStopped) {
+                        invokeListeners(event, path[0], 2, false);
                     }
                 }
+                for (var j = 1; bubbles && j < path.length && !event.__stopped; j++) {
+                    invokeListeners(event, path[j], 3, false);
+                }
+                event.currentTarget = null;
+                event.eventPhase = 0;
+                return !event.defaultPrevented;
+            };
+
+            // Move the document on to a later ready state, as the page
+            // finishes loading
+            globalThis.__setReadyState = function(state) {
+                readyState = state;
+                __dispatchEvent([documentId], 'readystatechange', false);
+                if (state === 'interactive') {
+                    __dispatchEvent([documentId], 'DOMContentLoaded', true);
+                }
             };
 
             globalThis.__hasEventListeners = function(nodeId, eventType) {
@@ -469,6 +1807,7 @@ fn register_dom_api(ctx: &rquickjs::Ctx<'_>, dom: SharedDom) -> Result<(), rquic
 
             // Store Element constructor globally
             globalThis.Element = Element;
+            globalThis.NodeList = NodeList;
         })();
     "#;
 
@@ -487,7 +1826,12 @@ fn register_dom_api(ctx: &rquickjs::Ctx<'_>, dom: SharedDom) -> Result<(), rquic
         e
     })?;
 
-    Ok(())
+    style::register_style(ctx, &document, dom.clone(), journal.clone(), observers.clone())?;
+    class_list::register_class_list(ctx, &document, dom.clone(), journal.clone(), observers.clone())?;
+    text::register_text(ctx, &document, dom, journal, removed, observers.clone())?;
+    // Last, wrapping the natives that change the DOM
+    mutation_observer::register_mutation_observers(ctx, &document, observers)?;
+    form::register_form(ctx, &document, controls, paint_dirty, form_submissions)
 }
 
 /// JavaScript value representation
@@ -584,6 +1928,8 @@ fn convert_value(value: &rquickjs::Value) -> JsValue {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::*;
 
     #[test]
@@ -954,4 +2300,1143 @@ mod tests {
         let result = runtime.eval("globalThis.third").unwrap();
         assert_eq!(result.as_bool(), Some(true));
     }
+
+    #[test]
+    fn test_timeout_changes_dom() {
+        use gugalanna_html::HtmlParser;
+
+        let dom = HtmlParser::new().parse(r#"<div id="box"></div>"#).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+
+        runtime.exec(r#"
+            setTimeout(function(value) {
+                document.getElementById('box').setAttribute('data-state', value);
+            }, 100, 'done');
+        "#).unwrap();
+
+        // Not due yet
+        assert_eq!(runtime.run_due_timers(Duration::from_millis(50)).unwrap(), 0);
+        let result = runtime.eval("document.getElementById('box').getAttribute('data-state')").unwrap();
+        assert!(matches!(result, JsValue::Null));
+
+        // The callback ran and changed the shared DOM
+        assert_eq!(runtime.run_due_timers(Duration::from_millis(100)).unwrap(), 1);
+        let dom = runtime.dom().unwrap().borrow();
+        let id = dom.get_element_by_id("box").unwrap();
+        let element = dom.get(id).and_then(|n| n.as_element()).unwrap();
+        assert_eq!(element.get_attribute("data-state"), Some("done"));
+        drop(dom);
+
+        // A timeout only runs once
+        assert_eq!(runtime.run_due_timers(Duration::from_millis(500)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_interval_repeats_until_cleared() {
+        let runtime = JsRuntime::new().unwrap();
+        runtime.exec(r#"
+            globalThis.ticks = 0;
+            globalThis.interval = setInterval(function() { globalThis.ticks++; }, 100);
+        "#).unwrap();
+
+        for ms in [100, 150, 200, 300] {
+            runtime.run_due_timers(Duration::from_millis(ms)).unwrap();
+        }
+        assert_eq!(runtime.eval("globalThis.ticks").unwrap().as_number(), Some(3.0));
+
+        runtime.exec("clearInterval(globalThis.interval)").unwrap();
+        assert_eq!(runtime.run_due_timers(Duration::from_millis(1000)).unwrap(), 0);
+        assert_eq!(runtime.eval("globalThis.ticks").unwrap().as_number(), Some(3.0));
+
+        // Cleared timeouts never run
+        runtime.exec("clearTimeout(setTimeout(function() { globalThis.ticks = -1; }, 0))").unwrap();
+        assert_eq!(runtime.run_due_timers(Duration::from_millis(2000)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_animation_frames_run_once_per_frame() {
+        let runtime = JsRuntime::new().unwrap();
+        runtime.exec(r#"
+            globalThis.stamps = [];
+            function tick(timestamp) {
+                stamps.push(timestamp);
+                requestAnimationFrame(tick);
+            }
+            requestAnimationFrame(tick);
+            cancelAnimationFrame(requestAnimationFrame(function() { stamps.push(-1); }));
+        "#).unwrap();
+
+        // The frame a callback requests is the next one, not the same
+        for ms in [16, 33, 50] {
+            assert_eq!(runtime.run_animation_frames(Duration::from_millis(ms)).unwrap(), 1);
+        }
+        assert_eq!(runtime.eval("stamps.join(',')").unwrap().as_str(), Some("16,33,50"));
+    }
+
+    #[test]
+    fn test_storage_kept_per_origin() {
+        let mut local = StorageStore::new();
+        let mut session = StorageStore::new();
+        let page = |origin: &str, local: &mut StorageStore, session: &mut StorageStore| {
+            let runtime = JsRuntime::new().unwrap();
+            runtime.set_storage(local.area(origin), session.area(origin)).unwrap();
+            runtime
+        };
+
+        let runtime = page("https://a.test", &mut local, &mut session);
+        runtime.exec(r#"
+            localStorage.setItem('theme', 'dark');
+            localStorage.count = 3;
+            sessionStorage.setItem('step', 2);
+        "#).unwrap();
+        drop(runtime);
+
+        // Reloading the page finds the items, stored as strings
+        let runtime = page("https://a.test", &mut local, &mut session);
+        assert_eq!(runtime.eval("localStorage.getItem('theme')").unwrap().as_str(), Some("dark"));
+        assert_eq!(runtime.eval("localStorage.getItem('count')").unwrap().as_str(), Some("3"));
+        assert_eq!(runtime.eval("sessionStorage.step").unwrap().as_str(), Some("2"));
+        assert_eq!(runtime.eval("localStorage.length").unwrap().as_number(), Some(2.0));
+        assert_eq!(runtime.eval("localStorage.key(0)").unwrap().as_str(), Some("count"));
+        runtime.exec("localStorage.removeItem('count')").unwrap();
+        assert_eq!(runtime.eval("localStorage.length").unwrap().as_number(), Some(1.0));
+
+        // Other origins don't see them
+        let runtime = page("https://b.test", &mut local, &mut session);
+        assert!(matches!(runtime.eval("localStorage.getItem('theme')").unwrap(), JsValue::Null));
+        assert_eq!(runtime.eval("sessionStorage.length").unwrap().as_number(), Some(0.0));
+
+        // Going over the quota throws
+        let result = runtime.eval(r#"
+            try {
+                localStorage.setItem('big', 'x'.repeat(6 * 1024 * 1024));
+                'stored';
+            } catch (e) {
+                e.name;
+            }
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("QuotaExceededError"));
+    }
+
+    #[test]
+    fn test_fetch_settles_when_completed() {
+        use gugalanna_html::HtmlParser;
+        use std::collections::HashMap;
+
+        let dom = HtmlParser::new().parse(r#"<div id="user"></div>"#).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+
+        runtime.exec(r#"
+            fetch('/api/user', { method: 'post', headers: { 'X-Token': 'abc' }, body: 'q=1' })
+                .then(function(r) {
+                    globalThis.contentType = r.headers.get('Content-Type');
+                    return r.json();
+                })
+                .then(function(user) {
+                    document.getElementById('user').setAttribute('data-name', user.name);
+                });
+            fetch('/missing').catch(function(e) { globalThis.failure = e.message; });
+        "#).unwrap();
+
+        let requests = runtime.take_fetch_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].url, "/api/user");
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].headers.get("x-token").map(String::as_str), Some("abc"));
+        assert_eq!(requests[0].body.as_deref(), Some("q=1"));
+        assert_eq!(requests[1].body, None);
+        assert!(runtime.take_fetch_requests().is_empty());
+
+        let response = FetchResponse {
+            url: "http://localhost/api/user".to_string(),
+            status: 200,
+            headers: HashMap::from([("content-type".to_string(), "application/json".to_string())]),
+            body: r#"{"name": "Ada"}"#.to_string(),
+        };
+        runtime.complete_fetch(requests[0].id, Ok(response)).unwrap();
+        runtime.complete_fetch(requests[1].id, Err("connection refused".to_string())).unwrap();
+
+        // Both promise chains ran to the end
+        let result = runtime.eval("document.getElementById('user').getAttribute('data-name')").unwrap();
+        assert_eq!(result.as_str(), Some("Ada"));
+        let result = runtime.eval("globalThis.contentType").unwrap();
+        assert_eq!(result.as_str(), Some("application/json"));
+        let result = runtime.eval("globalThis.failure").unwrap();
+        assert_eq!(result.as_str(), Some("Failed to fetch: connection refused"));
+    }
+
+    #[test]
+    fn test_dom_mutations_journaled() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"<ul id="list"><li id="first" title="x">one</li></ul><p id="other"></p>"#;
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        let (list, first, other) = {
+            let dom = runtime.dom().unwrap().borrow();
+            let id = |name| dom.get_element_by_id(name).unwrap();
+            (id("list"), id("first"), id("other"))
+        };
+
+        runtime.exec("var first = document.getElementById('first'); first.textContent = 'uno';").unwrap();
+        assert_eq!(runtime.take_style_dirty(), vec![first]);
+        runtime.exec("first.removeAttribute('title');").unwrap();
+        assert_eq!(runtime.take_style_dirty(), vec![first]);
+        assert!(matches!(runtime.eval("first.getAttribute('title')").unwrap(), JsValue::Null));
+
+        // Appending a node in the document moves it
+        runtime.exec("document.getElementById('other').appendChild(first);").unwrap();
+        assert_eq!(runtime.take_style_dirty(), vec![list, other]);
+        assert_eq!(runtime.eval("document.getElementById('list').textContent").unwrap().as_str(), Some(""));
+
+        let result = runtime.eval(r#"
+            var error;
+            try { document.getElementById('list').removeChild(first); } catch (e) { error = e.name; }
+            document.getElementById('other').removeChild(first);
+            error + ' ' + document.getElementById('other').textContent.length
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("NotFoundError 0"));
+        assert_eq!(runtime.take_style_dirty(), vec![other]);
+    }
+
+    #[test]
+    fn test_build_reorder_and_dismantle_list() {
+        use gugalanna_html::HtmlParser;
+
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse("<div id='root'></div>").unwrap()).unwrap();
+        let root = runtime.dom().unwrap().borrow().get_element_by_id("root").unwrap();
+
+        // Build
+        runtime.exec(r#"
+            var list = document.createElement('ul');
+            ['b', 'd', 'a'].forEach(function(name) {
+                var item = document.createElement('li');
+                item.appendChild(document.createTextNode(name));
+                list.appendChild(item);
+            });
+            document.getElementById('root').appendChild(list);
+        "#).unwrap();
+        let serialized = runtime.dom().unwrap().borrow().pretty_print();
+        assert!(serialized.contains("<div id=\"root\">\n        <ul>\n          <li>\n            #text: \"b\""));
+        let list = runtime.dom().unwrap().borrow().children(root)[0];
+        let dirty = runtime.take_style_dirty();
+        assert!(dirty.contains(&root) && dirty.contains(&list));
+
+        // Reorder
+        let result = runtime.eval(r#"
+            var items = list.children;
+            list.insertBefore(items[2], items[0]);
+            var c = document.createElement('li');
+            c.textContent = 'c';
+            var d = list.replaceChild(c, list.children[2]);
+            list.appendChild(d);
+            var names = [];
+            for (var node = list.firstChild; node; node = node.nextSibling) names.push(node.textContent);
+            names.join('') + ' ' + list.childNodes.length + ' ' + (c.parentNode.parentNode.id)
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("abcd 4 root"));
+        assert_eq!(runtime.dom().unwrap().borrow().text_content(root), "abcd");
+
+        // Errors are thrown where scripts can catch them
+        let result = runtime.eval(r#"
+            var errors = [];
+            try { list.firstChild.appendChild(list); } catch (e) { errors.push(e.name); }
+            try { list.insertBefore(document.createElement('li'), c.firstChild); } catch (e) { errors.push(e.name); }
+            try { list.removeChild(document.getElementById('root')); } catch (e) { errors.push(e.name); }
+            try { list.appendChild('li'); } catch (e) { errors.push(e.name); }
+            errors.join(' ')
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("HierarchyRequestError NotFoundError NotFoundError TypeError"));
+        assert_eq!(runtime.dom().unwrap().borrow().text_content(root), "abcd");
+
+        // Dismantle
+        runtime.take_style_dirty();
+        let result = runtime.eval(r#"
+            list.firstChild.remove();
+            while (list.firstChild) list.removeChild(list.firstChild);
+            list.remove();
+            list.remove();
+            list.parentNode === null && document.getElementById('root').childNodes.length === 0
+        "#).unwrap();
+        assert_eq!(result.as_bool(), Some(true));
+        assert_eq!(runtime.take_style_dirty(), vec![root, list]);
+        assert!(runtime.dom().unwrap().borrow().children(root).is_empty());
+    }
+
+    #[test]
+    fn test_document_fragment_inserted_at_once() {
+        use gugalanna_html::HtmlParser;
+
+        let html = "<ul id='list'><li id='last'>last</li></ul>";
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        let list = runtime.dom().unwrap().borrow().get_element_by_id("list").unwrap();
+        runtime.exec(r#"
+            var fragment = document.createDocumentFragment();
+            for (var i = 0; i < 100; i++) {
+                var item = document.createElement('li');
+                item.textContent = 'item ' + i;
+                fragment.appendChild(item);
+            }
+        "#).unwrap();
+
+        // Detached fragments are not in the document
+        assert_eq!(runtime.eval("document.querySelectorAll('li').length").unwrap().as_number(), Some(1.0));
+        assert_eq!(runtime.eval("fragment.querySelectorAll('li').length").unwrap().as_number(), Some(100.0));
+
+        runtime.take_style_dirty();
+        runtime.exec("var list = document.getElementById('list');").unwrap();
+        runtime.exec("list.insertBefore(fragment, list.firstChild);").unwrap();
+        assert_eq!(*runtime.style_dirty.borrow(), vec![list]);
+        let result = runtime.eval("list.children.length + ' ' + fragment.childNodes.length").unwrap();
+        assert_eq!(result.as_str(), Some("101 0"));
+        let text = runtime.dom().unwrap().borrow().text_content(list);
+        assert!(text.starts_with("item 0item 1") && text.ends_with("item 99last"));
+    }
+
+    #[test]
+    fn test_text_content_and_inner_text_setters() {
+        use gugalanna_html::HtmlParser;
+
+        let html = "<div id='status'><b>Ready</b> <i>now</i></div><p id='note'>a</p>";
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        let (status, note) = {
+            let dom = runtime.dom().unwrap().borrow();
+            (dom.get_element_by_id("status").unwrap(), dom.get_element_by_id("note").unwrap())
+        };
+
+        // Children are replaced by one text node
+        runtime.exec("var status = document.getElementById('status'); status.textContent = 'Loading…';").unwrap();
+        let result = runtime.eval("status.childNodes.length + ' ' + status.textContent").unwrap();
+        assert_eq!(result.as_str(), Some("1 Loading…"));
+        assert_eq!(runtime.take_style_dirty(), vec![status]);
+
+        // The empty string removes them all
+        runtime.exec("status.textContent = '';").unwrap();
+        assert!(runtime.dom().unwrap().borrow().children(status).is_empty());
+
+        // Line breaks become <br> elements, read back as line breaks
+        runtime.exec("var note = document.getElementById('note'); note.innerText = 'one\\ntwo';").unwrap();
+        let result = runtime.eval("note.children.length + ' ' + note.children[0].tagName").unwrap();
+        assert_eq!(result.as_str(), Some("1 BR"));
+        assert_eq!(runtime.eval("note.innerText").unwrap().as_str(), Some("one\ntwo"));
+        assert_eq!(runtime.take_style_dirty(), vec![status, note]);
+    }
+
+    #[test]
+    fn test_mutation_observer_reports_grandchild_once() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"<div id="list"><p id="item"></p></div>"#;
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        runtime
+            .exec(
+                r#"
+                var batches = [];
+                var list = document.getElementById('list');
+                var observer = new MutationObserver(function(records) {
+                    batches.push(records.map(function(r) {
+                        return r.type + ':' + r.target.id + ':' + r.addedNodes.length + ':' + r.addedNodes[0].tagName;
+                    }).join(','));
+                });
+                observer.observe(list, { childList: true, subtree: true });
+                observer.observe(document.getElementById('item'), { childList: true });
+                document.getElementById('item').appendChild(document.createElement('span'));
+                list.setAttribute('title', 'ignored');
+                globalThis.delivered = batches.length;
+            "#,
+            )
+            .unwrap();
+
+        // Delivered in a microtask, after the script that made the change
+        let read = |code: &str| runtime.eval(code).unwrap();
+        assert_eq!(read("delivered").as_number(), Some(0.0));
+        assert_eq!(read("batches.join('|')").as_str(), Some("childList:item:1:SPAN"));
+
+        runtime.exec("observer.disconnect(); list.appendChild(document.createElement('b'))").unwrap();
+        assert_eq!(read("batches.length").as_number(), Some(1.0));
+        assert_eq!(read("observer.takeRecords().length").as_number(), Some(0.0));
+    }
+
+    #[test]
+    fn test_element_wrappers_are_stable() {
+        use gugalanna_html::HtmlParser;
+
+        let html = "<div id='list'><p id='a'>a</p>text<p id='b'>b</p></div>";
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        let result = runtime.eval(r#"
+            var list = document.getElementById('list');
+            var a = document.getElementById('a');
+            a.note = 'kept';
+            var seen = new Map([[a, 1]]);
+            [
+                a === document.getElementById('a'),
+                document.querySelector('#list p') === a,
+                list.children[0] === a && a.parentNode === list && a.parentElement === list,
+                seen.get(document.getElementsByTagName('p')[0]) === 1,
+                document.getElementById('a').note,
+                list.childNodes.length + '/' + list.children.length,
+                list.contains(a) && a.contains(a) && !a.contains(list) && document.contains(a),
+                list.parentElement.tagName + ' ' + document.getElementsByTagName('html')[0].parentElement
+            ].join()
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("true,true,true,true,kept,3/2,true,BODY null"));
+
+        // A removed node's wrapper keeps working, and is the same once back
+        runtime.exec("a.remove(); list.textContent = '';").unwrap();
+        let result = runtime.eval(r#"
+            a.setAttribute('title', 'x');
+            var state = [a.parentNode, a.parentElement, list.contains(a), document.contains(a), a.note].join();
+            list.appendChild(a);
+            state + ' ' + (document.getElementById('a') === a) + ' ' + a.getAttribute('title')
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some(",,false,false,kept true x"));
+    }
+
+    #[test]
+    fn test_removed_subtree_wrappers_are_forgotten() {
+        use gugalanna_html::HtmlParser;
+
+        let html = "<div><ul id='menu'><li><a>one</a></li><li>two</li></ul></div>";
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        let count = || runtime.eval("__wrapperCount()").unwrap().as_number().unwrap();
+
+        let before = count();
+        runtime.exec("var menu = document.getElementById('menu'); document.querySelectorAll('li, a');").unwrap();
+        assert_eq!(count(), before + 4.0);
+
+        // The list goes with its items and their links
+        runtime.exec("menu.remove();").unwrap();
+        assert_eq!(count(), before);
+    }
+
+    #[test]
+    fn test_reinserted_subtree_keeps_wrappers() {
+        use gugalanna_html::HtmlParser;
+
+        let html = "<div id='host'><ul id='menu'><li id='one'><a>one</a></li></ul></div>";
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        runtime.exec(r#"
+            var host = document.getElementById('host'), menu = document.getElementById('menu');
+            var one = document.getElementById('one');
+            one.note = 'kept';
+            menu.remove();
+        "#).unwrap();
+
+        // The items scripts held are the same objects once the list is back,
+        // and stay when scripts let go of them
+        runtime.exec("host.appendChild(menu); var same = document.getElementById('one') === one; one = null;").unwrap();
+        let result = runtime.eval(r#"
+            var item = document.getElementById('one');
+            [same, item.note, item.parentNode === menu].join()
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("true,kept,true"));
+    }
+
+    #[test]
+    fn test_element_style_writes_attribute() {
+        use gugalanna_html::HtmlParser;
+
+        let dom = HtmlParser::new().parse(r#"<div id="box" style="color: red; margin: 0 auto"></div>"#).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+
+        runtime.exec(r#"
+            var style = document.getElementById('box').style;
+            style.backgroundColor = '#ffcc00';
+            style.color = 'blue';
+            style.setProperty('display', 'none', 'important');
+            style.removeProperty('margin');
+            style.webkitTransform = 'rotate(5deg)';
+        "#).unwrap();
+
+        let result = runtime.eval("document.getElementById('box').getAttribute('style')").unwrap();
+        assert_eq!(
+            result.as_str(),
+            Some("color: blue; background-color: #ffcc00; display: none !important; -webkit-transform: rotate(5deg);")
+        );
+        let result = runtime.eval("var s = document.getElementById('box').style; s.length + ' ' + s[1] + ' ' + s.getPropertyPriority('display')").unwrap();
+        assert_eq!(result.as_str(), Some("4 background-color important"));
+
+        // A value that is not valid leaves the declaration as it was
+        runtime.exec("document.getElementById('box').style.color = 'red; width: 5px'").unwrap();
+        let result = runtime.eval("document.getElementById('box').style.color").unwrap();
+        assert_eq!(result.as_str(), Some("blue"));
+
+        runtime.exec("document.getElementById('box').style.cssText = 'width:10px;;'").unwrap();
+        let result = runtime.eval("document.getElementById('box').style.cssText").unwrap();
+        assert_eq!(result.as_str(), Some("width: 10px;"));
+
+        let box_id = runtime.dom().unwrap().borrow().get_element_by_id("box").unwrap();
+        assert_eq!(runtime.take_style_dirty(), vec![box_id]);
+        assert!(runtime.take_style_dirty().is_empty());
+    }
+
+    #[test]
+    fn test_class_list() {
+        use gugalanna_html::HtmlParser;
+
+        let dom = HtmlParser::new().parse(r#"<nav id="menu" class="menu  menu closed"></nav>"#).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+
+        let result = runtime.eval(r#"
+            var list = document.getElementById('menu').classList;
+            var seen = [];
+            for (var name of list) seen.push(name);
+            list.length + ' ' + list[1] + ' ' + seen.join(',')
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("2 closed menu,closed"));
+
+        runtime.exec(r#"
+            var list = document.getElementById('menu').classList;
+            list.replace('closed', 'open');
+            list.add('a', 'b');
+            list.remove('a');
+            globalThis.toggled = list.toggle('b') + ',' + list.toggle('dark', true) + ',' + list.contains('open');
+        "#).unwrap();
+        let result = runtime.eval("document.getElementById('menu').className").unwrap();
+        assert_eq!(result.as_str(), Some("menu open dark"));
+        let result = runtime.eval("globalThis.toggled").unwrap();
+        assert_eq!(result.as_str(), Some("false,true,true"));
+
+        // className and classList see the same attribute
+        runtime.exec("document.getElementById('menu').className = 'x y'").unwrap();
+        let result = runtime.eval("document.getElementById('menu').classList.contains('y')").unwrap();
+        assert_eq!(result.as_bool(), Some(true));
+
+        // Bad tokens throw before anything changes
+        let result = runtime.eval(r#"
+            var error;
+            try { document.getElementById('menu').classList.add('z', 'a b'); } catch (e) { error = e.name; }
+            error + ' ' + document.getElementById('menu').className
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("InvalidCharacterError x y"));
+
+        let menu = runtime.dom().unwrap().borrow().get_element_by_id("menu").unwrap();
+        assert_eq!(runtime.take_style_dirty(), vec![menu]);
+    }
+
+    #[test]
+    fn test_window_viewport_and_computed_style() {
+        use gugalanna_css::Stylesheet;
+        use gugalanna_html::HtmlParser;
+        use gugalanna_style::Cascade;
+
+        let dom = HtmlParser::new().parse(r#"<p id="note">Hi</p>"#).unwrap();
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(Stylesheet::parse("#note { color: #336699; }").unwrap());
+        let styles = StyleTree::build(&dom, &cascade, 1024.0, 700.0);
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+        runtime.set_viewport(1024.0, 700.0);
+        runtime.set_computed_styles(Rc::new(styles));
+
+        let result = runtime.eval("innerWidth + 'x' + window.innerHeight + ' ' + devicePixelRatio").unwrap();
+        assert_eq!(result.as_str(), Some("1024x700 1"));
+        let result = runtime.eval(r#"
+            var style = getComputedStyle(document.getElementById('note'));
+            style.color + ' ' + style.fontSize + ' ' + style.getPropertyValue('color')
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("rgb(51, 102, 153) 16px rgb(51, 102, 153)"));
+
+        runtime.exec("window.addEventListener('resize', function() { globalThis.resizedTo = innerWidth; })").unwrap();
+        runtime.set_viewport(800.0, 600.0);
+        runtime.dispatch_window_event("resize").unwrap();
+        let result = runtime.eval("globalThis.resizedTo").unwrap();
+        assert_eq!(result.as_number(), Some(800.0));
+    }
+
+    #[test]
+    fn test_history_push_state() {
+        let runtime = JsRuntime::new().unwrap();
+        let start = url::Url::parse("https://app.test/inbox").unwrap();
+        runtime.set_history(&start, None, 0, 1);
+
+        let result = runtime.eval(r#"
+            history.pushState({ page: 2 }, '', '?page=2');
+            history.length + ' ' + history.state.page
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("2 2"));
+
+        // Another origin is refused and nothing is queued for it
+        let result = runtime.eval(r#"
+            var error;
+            try { history.replaceState(null, '', 'https://evil.test/'); } catch (e) { error = e.name; }
+            error + ' ' + history.length
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("SecurityError 2"));
+
+        runtime.exec("history.back()").unwrap();
+        let pushed = url::Url::parse("https://app.test/inbox?page=2").unwrap();
+        assert_eq!(
+            runtime.take_history_changes(),
+            vec![
+                HistoryChange::Push { url: pushed, state: Some(r#"{"page":2}"#.to_string()) },
+                HistoryChange::Go(-1),
+            ]
+        );
+
+        // Going back delivers the state of the entry gone to
+        runtime.exec("addEventListener('popstate', function(e) { globalThis.popped = e.state; })").unwrap();
+        runtime.set_history(&start, None, 0, 2);
+        runtime.dispatch_popstate(None).unwrap();
+        let result = runtime.eval("popped === null && history.state === null").unwrap();
+        assert_eq!(result.as_bool(), Some(true));
+    }
+
+    #[test]
+    fn test_dom_content_loaded_and_load() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"
+            <script>
+                var log = [document.readyState];
+                document.addEventListener('DOMContentLoaded', function(e) {
+                    log.push(e.target === document && document.getElementById('later').textContent);
+                });
+                document.onreadystatechange = function() { log.push(document.readyState); };
+                window.onload = function(e) { log.push(e.type); };
+            </script>
+            <p id="later">Below the script</p>
+        "#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+        runtime.execute_scripts().unwrap();
+        runtime.dispatch_dom_content_loaded().unwrap();
+        runtime.dispatch_load().unwrap();
+
+        let result = runtime.eval("log.join(',')").unwrap();
+        assert_eq!(result.as_str(), Some("loading,interactive,Below the script,complete,load"));
+    }
+
+    #[test]
+    fn test_query_selector() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"
+            <nav><a href="/" class="active">Home</a><a href="/about">About</a></nav>
+            <ul id="tasks"><li class="done">Shop</li><li>Cook</li><li data-late="yes">Clean</li></ul>
+        "#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+
+        let result = runtime.eval("document.querySelector('nav a.active').textContent").unwrap();
+        assert_eq!(result.as_str(), Some("Home"));
+        let result = runtime.eval(r#"
+            var open = document.getElementById('tasks').querySelectorAll('li:not(.done)');
+            open.length + ' ' + Array.from(open).map(function(li) { return li.textContent; }).join(',')
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("2 Cook,Clean"));
+        let result = runtime.eval("document.querySelector('ul [data-late=yes]').textContent").unwrap();
+        assert_eq!(result.as_str(), Some("Clean"));
+        let result = runtime.eval("document.querySelector('nav').querySelector('li')").unwrap();
+        assert!(matches!(result, JsValue::Null));
+
+        let result = runtime.eval(r#"
+            var error;
+            try { document.querySelectorAll('li:not('); } catch (e) { error = e.name; }
+            error
+        "#).unwrap();
+        assert_eq!(result.as_str(), Some("SyntaxError"));
+    }
+
+    #[test]
+    fn test_event_propagation() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"<ul id="list"><li id="first"><b id="label">One</b></li></ul>"#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+        runtime.exec(r#"
+            var log = [];
+            var list = document.getElementById('list');
+            list.addEventListener('click', function(e) {
+                log.push('bubble ' + e.target.id + ' ' + e.currentTarget.id + ' ' + e.eventPhase);
+            });
+            list.addEventListener('click', function(e) { log.push('capture ' + e.eventPhase); }, true);
+            document.getElementById('first').addEventListener('click', function(e) {
+                log.push('once');
+                e.preventDefault();
+            }, { once: true });
+        "#).unwrap();
+
+        let label = runtime.dom().unwrap().borrow().get_element_by_id("label").unwrap();
+        assert!(!runtime.dispatch_click(label.0).unwrap());
+        assert!(runtime.dispatch_click(label.0).unwrap());
+        let result = runtime.eval("log.join(',')").unwrap();
+        assert_eq!(
+            result.as_str(),
+            Some("capture 1,once,bubble label list 3,capture 1,bubble label list 3")
+        );
+
+        // Events that don't bubble only reach capture listeners above
+        runtime.exec("log = []; list.addEventListener('focus', function() { log.push('focus'); })").unwrap();
+        runtime.dispatch_event(label.0, "focus").unwrap();
+        assert_eq!(runtime.eval("log.length").unwrap().as_number(), Some(0.0));
+    }
+
+    /// Form controls kept in maps, as an embedder would
+    #[derive(Default)]
+    struct Controls {
+        values: HashMap<NodeId, String>,
+        checked: HashMap<NodeId, bool>,
+        disabled: HashMap<NodeId, bool>,
+        selected: HashMap<NodeId, i32>,
+    }
+
+    impl FormControls for Controls {
+        fn value(&self, node_id: NodeId) -> Option<String> {
+            self.values.get(&node_id).cloned()
+        }
+        fn set_value(&mut self, node_id: NodeId, value: &str) {
+            self.values.insert(node_id, value.to_string());
+        }
+        fn checked(&self, node_id: NodeId) -> bool {
+            self.checked.get(&node_id).copied().unwrap_or(false)
+        }
+        fn set_checked(&mut self, node_id: NodeId, checked: bool) {
+            self.checked.insert(node_id, checked);
+        }
+        fn disabled(&self, node_id: NodeId) -> Option<bool> {
+            self.disabled.get(&node_id).copied()
+        }
+        fn set_disabled(&mut self, node_id: NodeId, disabled: bool) {
+            self.disabled.insert(node_id, disabled);
+        }
+        fn selected_index(&self, node_id: NodeId) -> Option<i32> {
+            self.selected.get(&node_id).copied()
+        }
+        fn set_selected_index(&mut self, node_id: NodeId, index: i32) {
+            self.selected.insert(node_id, index);
+        }
+        fn reset(&mut self, node_id: NodeId) {
+            self.values.remove(&node_id);
+            self.checked.remove(&node_id);
+            self.selected.remove(&node_id);
+        }
+        // Only `required` is checked
+        fn validation_message(&self, dom: &DomTree, node_id: NodeId) -> Option<String> {
+            let required = dom.get(node_id)?.as_element()?.get_attribute("required").is_some();
+            let empty = self.values.get(&node_id).is_none_or(|value| value.is_empty());
+            (required && empty).then(|| "Please fill out this field.".to_string())
+        }
+    }
+
+    #[test]
+    fn test_form_control_values() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"<input id="name" value="initial" disabled>
+            <input id="a" type="radio" name="pick"><input id="b" type="radio" name="pick">
+            <textarea id="area">text</textarea>
+            <select id="size"><option>S</option><option value="m" selected>M</option></select>"#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+        let read = "var $ = function(id) { return document.getElementById(id); };\
+                    [$('name').value, $('name').disabled, $('a').value, $('area').value, $('size').selectedIndex, $('size').value].join(',')";
+        assert_eq!(runtime.eval(read).unwrap().as_str(), Some("initial,true,on,text,1,m"));
+
+        // Writes go to the embedder's controls and mark the page for painting
+        let controls = Rc::new(RefCell::new(Controls::default()));
+        runtime.set_form_controls(controls.clone());
+        assert!(!runtime.take_paint_dirty());
+        runtime.exec("$('name').value = 'typed'; $('name').disabled = false; $('area').value = 'more'; $('size').value = 'S';").unwrap();
+        assert!(runtime.take_paint_dirty());
+        assert_eq!(runtime.eval(read).unwrap().as_str(), Some("typed,false,on,more,0,S"));
+
+        // Checking a radio button unchecks the others of its group
+        runtime.exec("$('a').checked = true; $('b').checked = true;").unwrap();
+        assert_eq!(runtime.eval("[$('a').checked, $('b').checked].join(',')").unwrap().as_str(), Some("false,true"));
+        let size = runtime.dom().unwrap().borrow().get_element_by_id("size").unwrap();
+        assert_eq!(controls.borrow().selected_index(size), Some(0));
+    }
+
+    #[test]
+    fn test_form_submit_and_reset() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"<form id="login"><input id="user" name="user"><input type="checkbox" id="keep"><button>Go</button></form>"#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+        runtime.set_form_controls(Rc::new(RefCell::new(Controls::default())));
+        runtime.exec(r#"
+            var form = document.getElementById('login');
+            var resets = 0;
+            form.addEventListener('reset', function(e) { if (resets++ === 0) e.preventDefault(); });
+            form.elements.namedItem('user').value = 'ana';
+            document.getElementById('keep').checked = true;
+        "#).unwrap();
+        assert_eq!(runtime.eval("form.elements.length").unwrap().as_number(), Some(3.0));
+
+        // The first reset is canceled, the second one clears the controls
+        runtime.exec("form.reset()").unwrap();
+        assert_eq!(runtime.eval("form.elements[0].value").unwrap().as_str(), Some("ana"));
+        runtime.exec("form.reset()").unwrap();
+        assert_eq!(runtime.eval("form.elements[0].value + form.elements[1].checked").unwrap().as_str(), Some("false"));
+
+        // submit() is queued for the embedder without an event
+        runtime.exec("var submits = 0; form.addEventListener('submit', function() { submits++; }); form.submit();").unwrap();
+        let login = runtime.dom().unwrap().borrow().get_element_by_id("login").unwrap();
+        assert_eq!(runtime.take_form_submissions(), vec![login]);
+        assert_eq!(runtime.eval("submits").unwrap().as_number(), Some(0.0));
+        assert!(runtime.take_form_submissions().is_empty());
+    }
+
+    #[test]
+    fn test_form_validity() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"<form id="signup"><input id="name" required><input id="email" required><input id="note"></form>"#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+        runtime.set_form_controls(Rc::new(RefCell::new(Controls::default())));
+        runtime.exec(r#"
+            var $ = function(id) { return document.getElementById(id); };
+            var form = $('signup');
+            var invalid = [];
+            form.addEventListener('invalid', function() { invalid.push('form'); });
+            ['name', 'email'].forEach(function(id) {
+                $(id).addEventListener('invalid', function(e) {
+                    invalid.push(id);
+                    if (id === 'name') e.preventDefault();
+                });
+            });
+        "#).unwrap();
+        assert_eq!(runtime.eval("$('name').validationMessage").unwrap().as_str(), Some("Please fill out this field."));
+        assert_eq!(runtime.eval("$('note').validity.valid").unwrap().as_bool(), Some(true));
+
+        // Each invalid control hears invalid, which does not bubble
+        assert_eq!(runtime.eval("form.checkValidity()").unwrap().as_bool(), Some(false));
+        assert_eq!(runtime.eval("invalid.join(',')").unwrap().as_str(), Some("name,email"));
+        assert!(runtime.take_validity_reports().is_empty());
+
+        // The first control whose invalid event is not canceled is reported
+        assert_eq!(runtime.eval("form.reportValidity()").unwrap().as_bool(), Some(false));
+        let email = runtime.dom().unwrap().borrow().get_element_by_id("email").unwrap();
+        assert_eq!(runtime.take_validity_reports(), vec![email]);
+
+        runtime.exec("$('name').value = 'Ana'; $('email').value = 'ana@example.com';").unwrap();
+        assert_eq!(runtime.eval("form.reportValidity()").unwrap().as_bool(), Some(true));
+        assert!(runtime.take_validity_reports().is_empty());
+    }
+
+    #[test]
+    fn test_page_scripts_and_fetch_failures() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"
+            <script src="a.js"></script>
+            <script src="b.js" defer></script>
+            <script src="c.js" async defer></script>
+            <script>globalThis.failed = [];</script>
+            <script>   </script>
+        "#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+
+        let sources: Vec<_> = runtime.scripts().into_iter().map(|s| s.source).collect();
+        let external = |src: &str, timing| ScriptSource::External { src: src.to_string(), timing };
+        assert_eq!(
+            sources,
+            vec![
+                external("a.js", ScriptTiming::Classic),
+                external("b.js", ScriptTiming::Defer),
+                external("c.js", ScriptTiming::Async),
+                ScriptSource::Inline("globalThis.failed = [];".to_string()),
+            ]
+        );
+
+        // Inline scripts do not wait for external ones
+        let results = runtime.execute_scripts().unwrap();
+        assert_eq!(results.len(), 1);
+
+        let first = runtime.scripts()[0].node_id;
+        runtime
+            .exec("document.getElementsByTagName('script')[0].addEventListener('error', function(e) { failed.push(e.type); });")
+            .unwrap();
+        let result = runtime.script_fetch_failed(first, "https://example.com/a.js", Some(404), "HTTP error: 404");
+        assert!(!result.success);
+        assert_eq!(result.fetch_status, Some(404));
+        assert_eq!(runtime.eval("failed.join(',')").unwrap().as_str(), Some("error"));
+        assert!(runtime
+            .get_console_messages()
+            .iter()
+            .any(|m| m.level == LogLevel::Error && m.message.contains("https://example.com/a.js")));
+    }
+
+    #[test]
+    fn test_navigator() {
+        let runtime = JsRuntime::new().unwrap();
+        runtime.set_user_agent("Gugalanna/9.9 (Test)");
+
+        // Feature detection reads it before anything else
+        let result = runtime
+            .eval("navigator.userAgent.includes('Firefox') ? 'gecko' : navigator.userAgent.indexOf('Gugalanna') === 0 ? 'ours' : 'other'")
+            .unwrap();
+        assert_eq!(result.as_str(), Some("ours"));
+        assert_eq!(runtime.eval("navigator.userAgent").unwrap().as_str(), Some("Gugalanna/9.9 (Test)"));
+        assert_eq!(runtime.eval("navigator.language === navigator.languages[0]").unwrap().as_bool(), Some(true));
+        assert_eq!(runtime.eval("typeof navigator.platform").unwrap().as_str(), Some("string"));
+        assert_eq!(runtime.eval("navigator.cookieEnabled && navigator.onLine").unwrap().as_bool(), Some(true));
+
+        // Read-only, writes throw in strict code
+        for write in ["navigator.userAgent = 'Other'", "navigator.onLine = false", "navigator = null"] {
+            runtime.exec(&format!("try {{ {}; }} catch (e) {{}}", write)).unwrap();
+        }
+        assert_eq!(runtime.eval("navigator.userAgent").unwrap().as_str(), Some("Gugalanna/9.9 (Test)"));
+        assert_eq!(runtime.eval("navigator.onLine").unwrap().as_bool(), Some(true));
+
+        runtime.exec("navigator.clipboard.writeText('copied').then(function() { globalThis.copied = true; });").unwrap();
+        assert_eq!(runtime.eval("globalThis.copied").unwrap().as_bool(), Some(true));
+        assert_eq!(runtime.take_clipboard_text().as_deref(), Some("copied"));
+        assert_eq!(runtime.take_clipboard_text(), None);
+    }
+
+    #[test]
+    fn test_dialogs() {
+        struct Answers(RefCell<Vec<DialogRequest>>);
+
+        impl DialogHandler for Answers {
+            fn show(&self, request: &DialogRequest) -> Option<String> {
+                self.0.borrow_mut().push(request.clone());
+                match request.kind {
+                    DialogKind::Prompt => Some(format!("{}!", request.default_value)),
+                    _ => None,
+                }
+            }
+        }
+
+        let runtime = JsRuntime::new().unwrap();
+
+        // Without a handler dialogs are dismissed
+        assert_eq!(runtime.eval("confirm('Delete?')").unwrap().as_bool(), Some(false));
+
+        let answers = Rc::new(Answers(RefCell::new(Vec::new())));
+        runtime.set_dialog_handler(answers.clone());
+        runtime.exec("alert(42);").unwrap();
+        assert_eq!(runtime.eval("confirm('Delete?')").unwrap().as_bool(), Some(false));
+        assert_eq!(runtime.eval("prompt('Name?', 'Ada')").unwrap().as_str(), Some("Ada!"));
+
+        let requests = answers.0.borrow();
+        assert_eq!(requests.len(), 3);
+        assert_eq!((requests[0].kind, requests[0].message.as_str()), (DialogKind::Alert, "42"));
+        assert_eq!(requests[1].kind, DialogKind::Confirm);
+        assert_eq!(requests[2].default_value, "Ada");
+    }
+
+    #[test]
+    fn test_uncaught_error_in_script() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"<html><body><script>
+            function fail() { throw new TypeError('broken'); }
+            fail();
+        </script></body></html>"#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+
+        let results = runtime.execute_scripts().unwrap();
+        assert!(!results[0].success);
+        assert_eq!(results[0].error.as_deref(), Some("JavaScript error: TypeError: broken"));
+
+        // One error entry, with the stack and the script it came from
+        let messages = runtime.get_console_messages();
+        let errors: Vec<_> = messages.iter().filter(|m| m.level == LogLevel::Error).collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Uncaught TypeError: broken");
+        assert!(errors[0].stack.as_deref().is_some_and(|stack| stack.contains("fail")));
+        assert_eq!(errors[0].script, Some(ScriptOrigin { node_id: results[0].node_id, url: None }));
+    }
+
+    #[test]
+    fn test_error_handlers_mark_errors_handled() {
+        let runtime = JsRuntime::new().unwrap();
+        runtime
+            .exec(
+                r#"
+                var seen = [];
+                window.addEventListener('error', function(event) {
+                    seen.push(event.message);
+                    event.preventDefault();
+                });
+                window.onunhandledrejection = function(event) {
+                    seen.push('rejected ' + event.reason);
+                    event.preventDefault();
+                };
+                setTimeout(function() { throw new Error('late'); }, 0);
+                Promise.reject('nope');
+            "#,
+            )
+            .unwrap();
+        runtime.run_due_timers(Duration::from_millis(10)).unwrap();
+        assert_eq!(runtime.eval("seen.join(',')").unwrap().as_str(), Some("rejected nope,Error: late"));
+        assert!(runtime.get_console_messages().is_empty());
+
+        // onerror returning true handles errors too, rejections nothing
+        // handled are logged
+        let runtime = JsRuntime::new().unwrap();
+        runtime
+            .exec(
+                r#"
+                window.onerror = function(message) { return message === 'Error: expected'; };
+                setTimeout(function() { throw new Error('expected'); }, 0);
+                Promise.resolve().then(function() { throw new RangeError('lost'); });
+                Promise.reject('caught later').catch(function() {});
+            "#,
+            )
+            .unwrap();
+        runtime.run_due_timers(Duration::from_millis(10)).unwrap();
+        let messages: Vec<_> = runtime.get_console_messages().into_iter().map(|m| m.message).collect();
+        assert_eq!(messages, vec!["Uncaught (in promise) RangeError: lost"]);
+    }
+
+    #[test]
+    fn test_element_geometry() {
+        use gugalanna_html::HtmlParser;
+
+        // Lays `#box` out as a row of the viewport, as tall as its
+        // data-height says
+        struct Rows(Cell<u32>);
+
+        impl LayoutProvider for Rows {
+            fn layout(&self, dom: &DomTree, width: f32, _height: f32) -> ElementBoxes {
+                self.0.set(self.0.get() + 1);
+                let id = dom.get_element_by_id("box").unwrap();
+                let height = dom
+                    .get(id)
+                    .and_then(|n| n.as_element())
+                    .and_then(|e| e.get_attribute("data-height"))
+                    .and_then(|h| h.parse().ok())
+                    .unwrap_or(0.0);
+                let border_box = BoxRect { x: 10.0, y: 100.0, width, height };
+                let padding_box = BoxRect { x: 11.0, y: 101.0, width: width - 2.0, height: height - 2.0 };
+                ElementBoxes::from([(id, ElementBox { border_box, padding_box, fixed: false })])
+            }
+        }
+
+        let html = r#"<div id="box" data-height="20"></div><p id="hidden"></p>"#;
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        let rows = Rc::new(Rows(Cell::new(0)));
+        runtime.set_viewport(300.0, 200.0);
+        runtime.set_layout_provider(rows.clone());
+        runtime.set_element_boxes(rows.layout(&runtime.dom().unwrap().borrow(), 300.0, 200.0));
+        runtime.set_scroll_y(40.0);
+
+        // Rects are in the viewport, offsets in the document
+        let read = |code: &str| runtime.eval(code).unwrap().as_number();
+        runtime.exec("var box = document.getElementById('box'); var rect = box.getBoundingClientRect();").unwrap();
+        assert_eq!(read("rect.top"), Some(60.0));
+        assert_eq!(read("rect.right"), Some(310.0));
+        assert_eq!(read("box.offsetTop"), Some(100.0));
+        assert_eq!(read("box.offsetWidth"), Some(300.0));
+        assert_eq!(read("box.clientHeight"), Some(18.0));
+        assert_eq!(rows.0.get(), 1);
+
+        // Measuring after a change lays the page out first, once
+        runtime.exec("box.setAttribute('data-height', '50')").unwrap();
+        assert_eq!(read("box.offsetHeight"), Some(50.0));
+        assert_eq!(read("box.getBoundingClientRect().bottom"), Some(110.0));
+        assert_eq!(rows.0.get(), 2);
+
+        // Elements not laid out measure zero
+        assert_eq!(read("document.getElementById('hidden').getBoundingClientRect().width"), Some(0.0));
+        assert_eq!(read("document.getElementById('hidden').offsetHeight"), Some(0.0));
+    }
+
+    #[test]
+    fn test_scroll_to_clamps_to_content() {
+        use gugalanna_html::HtmlParser;
+
+        let html = r#"<p id="end">end</p>"#;
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse(html).unwrap()).unwrap();
+        runtime.set_viewport(800.0, 600.0);
+        runtime.set_content_height(900.0);
+        let end = runtime.dom().unwrap().borrow().get_element_by_id("end").unwrap();
+        let border_box = BoxRect { x: 0.0, y: 850.0, width: 800.0, height: 20.0 };
+        runtime.set_element_boxes(ElementBoxes::from([(end, ElementBox { border_box, ..ElementBox::default() })]));
+
+        let read = |code: &str| runtime.eval(code).unwrap().as_number();
+        runtime.exec("scrollTo(0, 500)").unwrap();
+        assert_eq!(read("scrollY"), Some(300.0));
+        runtime.exec("scrollBy({ top: -100 })").unwrap();
+        assert_eq!(read("pageYOffset"), Some(200.0));
+
+        // A smooth scroll moves the page as the embedder animates it
+        runtime.exec("document.getElementById('end').scrollIntoView({ block: 'end', behavior: 'smooth' })").unwrap();
+        assert_eq!(read("scrollY"), Some(200.0));
+        assert_eq!(
+            runtime.take_scroll_requests(),
+            [
+                ScrollRequest { top: 300.0, smooth: false },
+                ScrollRequest { top: 200.0, smooth: false },
+                ScrollRequest { top: 270.0, smooth: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_runaway_script_is_terminated() {
+        use gugalanna_html::HtmlParser;
+
+        let dom = HtmlParser::new().parse("<p id='p'></p>").unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+        runtime.set_execution_limit(Duration::from_millis(200));
+
+        let started = Instant::now();
+        let result = runtime.run_script(1, "var n = 0; while (true) { n++; }");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("Script terminated"));
+
+        // Listeners looping in promise jobs are stopped too
+        runtime.exec(r#"
+            document.getElementById('p').addEventListener('click', function() {
+                Promise.resolve().then(function() { for (;;) {} });
+            });
+        "#).unwrap();
+        let p = runtime.eval("document.getElementById('p').__nodeId").unwrap().as_number().unwrap() as u32;
+        assert!(matches!(runtime.dispatch_click(p), Err(JsError::Terminated)));
+
+        // The runtime goes on running scripts
+        assert_eq!(runtime.eval("n > 0").unwrap().as_bool(), Some(true));
+    }
+
+    #[test]
+    fn test_document_cookie() {
+        use gugalanna_html::HtmlParser;
+        use url::Url;
+
+        // A jar keeping the last value of each cookie, refusing HttpOnly
+        struct Jar(RefCell<Vec<(Url, String)>>);
+
+        impl CookieAccess for Jar {
+            fn cookies(&self, url: &Url) -> String {
+                let cookies = self.0.borrow();
+                let pairs: Vec<&str> =
+                    cookies.iter().filter(|(u, _)| u.host() == url.host()).map(|(_, c)| c.as_str()).collect();
+                pairs.join("; ")
+            }
+
+            fn set_cookie(&self, url: &Url, cookie: &str) {
+                if cookie.to_lowercase().contains("httponly") {
+                    return;
+                }
+                let pair = cookie.split(';').next().unwrap_or("").trim().to_string();
+                let name = pair.split('=').next().unwrap_or("").to_string();
+                let mut cookies = self.0.borrow_mut();
+                cookies.retain(|(_, c)| c.split('=').next() != Some(name.as_str()));
+                cookies.push((url.clone(), pair));
+            }
+        }
+
+        let runtime = JsRuntime::with_dom(HtmlParser::new().parse("<p></p>").unwrap()).unwrap();
+
+        // Without a jar the page has no cookies
+        runtime.exec("document.cookie = 'lost=1';").unwrap();
+        assert_eq!(runtime.eval("document.cookie").unwrap().as_str(), Some(""));
+
+        let jar = Rc::new(Jar(RefCell::new(Vec::new())));
+        runtime.set_cookie_access(jar.clone());
+        runtime.set_history(&Url::parse("https://example.com/page").unwrap(), None, 0, 1);
+        runtime.exec("document.cookie = 'theme=dark; path=/; max-age=60';").unwrap();
+        runtime.exec("document.cookie = 'id=7; HttpOnly'; document.cookie = 'lang=en';").unwrap();
+        assert_eq!(runtime.eval("document.cookie").unwrap().as_str(), Some("theme=dark; lang=en"));
+        assert_eq!(jar.0.borrow()[0].0.as_str(), "https://example.com/page");
+    }
 }
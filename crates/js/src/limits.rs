@@ -0,0 +1,215 @@
+//! Execution Limits
+//!
+//! Keeps runaway scripts from hanging the browser. While code runs, QuickJS
+//! asks the interrupt handler from time to time whether to go on, and it
+//! stops the code once the run took longer than its budget or the embedder
+//! asked for it to stop. A run is a call into the runtime with the promise
+//! jobs it leaves, which take from its budget too. Script elements get the
+//! whole execution limit; event listeners, timers and other callbacks get
+//! a shorter one, as they should return quickly. Time spent waiting on the
+//! user or the network, as in a dialog, does not count.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::JsError;
+
+/// How long the script of a script element may run by default
+pub const DEFAULT_EXECUTION_LIMIT: Duration = Duration::from_secs(5);
+
+/// How long a callback may run, when the execution limit is not shorter
+const CALLBACK_EXECUTION_LIMIT: Duration = Duration::from_secs(2);
+
+/// Bytes the runtime may allocate, past which allocations throw
+pub const MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+
+/// What a run executes, which decides its budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RunKind {
+    /// The code of a script element
+    Script,
+    /// A listener, timer or other code the embedder calls into
+    Callback,
+}
+
+/// The outermost run under way
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    started: Instant,
+    budget: Duration,
+}
+
+#[derive(Debug)]
+struct LimitState {
+    limit: Duration,
+    run: Option<Run>,
+    /// Runs under way, those nested in the outermost one counted too
+    depth: usize,
+    /// Runs paused while the user or network is waited on, with their
+    /// depth and the time their run had used
+    paused: Vec<(usize, Option<(Duration, Duration)>)>,
+    stop_requested: bool,
+    /// The run was stopped, the code it still runs stopping right away
+    interrupted: bool,
+}
+
+/// The execution limit of a runtime and the runs it bounds
+#[derive(Debug)]
+pub(crate) struct ExecutionLimits {
+    state: Mutex<LimitState>,
+}
+
+/// Shared with the interrupt handler of the runtime, which must be sendable
+pub(crate) type SharedLimits = Arc<ExecutionLimits>;
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(LimitState {
+                limit: DEFAULT_EXECUTION_LIMIT,
+                run: None,
+                depth: 0,
+                paused: Vec::new(),
+                stop_requested: false,
+                interrupted: false,
+            }),
+        }
+    }
+}
+
+impl ExecutionLimits {
+    fn state(&self) -> MutexGuard<'_, LimitState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn set_limit(&self, limit: Duration) {
+        self.state().limit = limit;
+    }
+
+    /// Start a run, which ends when the returned guard drops
+    ///
+    /// A run started within another takes from the budget of the outer one.
+    pub(crate) fn begin(&self, kind: RunKind) -> LimitedRun<'_> {
+        let mut state = self.state();
+        if state.depth == 0 {
+            let budget = match kind {
+                RunKind::Script => state.limit,
+                RunKind::Callback => state.limit.min(CALLBACK_EXECUTION_LIMIT),
+            };
+            state.run = Some(Run { started: Instant::now(), budget });
+        }
+        state.depth += 1;
+        LimitedRun { limits: self }
+    }
+
+    /// Whether to stop the code running, asked by QuickJS while it runs
+    pub(crate) fn should_interrupt(&self) -> bool {
+        let mut state = self.state();
+        let over_budget = state.run.is_some_and(|run| run.started.elapsed() > run.budget);
+        if state.depth > 0 && (state.stop_requested || over_budget) {
+            state.interrupted = true;
+        }
+        state.interrupted
+    }
+
+    pub(crate) fn interrupted(&self) -> bool {
+        self.state().interrupted
+    }
+
+    /// Stop the scripts running now, or those waiting on a dialog once it
+    /// closes; without any running this does nothing
+    pub(crate) fn request_interrupt(&self) {
+        let mut state = self.state();
+        if state.depth > 0 || !state.paused.is_empty() {
+            state.stop_requested = true;
+        }
+    }
+
+    /// Call `wait`, which waits on the user or the network, without the
+    /// time it takes counting against the run; scripts it runs meanwhile
+    /// get a budget of their own
+    pub(crate) fn paused<R>(&self, wait: impl FnOnce() -> R) -> R {
+        {
+            let mut state = self.state();
+            let used = state.run.take().map(|run| (run.started.elapsed(), run.budget));
+            let depth = std::mem::take(&mut state.depth);
+            state.paused.push((depth, used));
+        }
+        let result = wait();
+        let mut state = self.state();
+        if let Some((depth, used)) = state.paused.pop() {
+            let now = Instant::now();
+            state.depth = depth;
+            state.run = used.map(|(used, budget)| Run { started: now.checked_sub(used).unwrap_or(now), budget });
+        }
+        result
+    }
+}
+
+/// A run under way, ended when dropped
+pub(crate) struct LimitedRun<'a> {
+    limits: &'a ExecutionLimits,
+}
+
+impl LimitedRun<'_> {
+    /// The result of the run, or the termination error when it was stopped
+    pub(crate) fn finish<T>(self, result: Result<T, JsError>) -> Result<T, JsError> {
+        if self.limits.interrupted() {
+            return Err(JsError::Terminated);
+        }
+        result
+    }
+}
+
+impl Drop for LimitedRun<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limits.state();
+        state.depth = state.depth.saturating_sub(1);
+        if state.depth == 0 {
+            state.run = None;
+            state.interrupted = false;
+            if state.paused.is_empty() {
+                state.stop_requested = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_stop_past_budget() {
+        let limits = ExecutionLimits::default();
+        limits.set_limit(Duration::from_millis(20));
+        let run = limits.begin(RunKind::Script);
+        assert!(!limits.should_interrupt());
+        // Waiting on a dialog does not count
+        limits.paused(|| std::thread::sleep(Duration::from_millis(40)));
+        assert!(!limits.should_interrupt());
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(limits.should_interrupt());
+        assert!(matches!(run.finish(Ok(())), Err(JsError::Terminated)));
+
+        // The next run starts over
+        let run = limits.begin(RunKind::Callback);
+        assert!(!limits.should_interrupt());
+        assert!(run.finish(Ok(())).is_ok());
+    }
+
+    #[test]
+    fn test_interrupt_requests_stop_running_code_only() {
+        let limits = ExecutionLimits::default();
+        limits.request_interrupt();
+        let outer = limits.begin(RunKind::Script);
+        let inner = limits.begin(RunKind::Callback);
+        assert!(!limits.should_interrupt());
+        limits.request_interrupt();
+        assert!(limits.should_interrupt());
+        drop(inner);
+        assert!(limits.interrupted());
+        assert!(outer.finish(Ok(())).is_err());
+        assert!(!limits.interrupted());
+    }
+}
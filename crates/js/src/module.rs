@@ -0,0 +1,141 @@
+//! Module Scripts
+//!
+//! Runs `<script type="module">` and the modules it imports. A module is
+//! named by its URL, inline ones by the document's base URL with the
+//! element in the fragment, and its `import` specifiers resolve against
+//! that name: URLs and paths starting with `/`, `./` or `../` do, bare
+//! names like `lodash` are an error. Imported modules are fetched through
+//! the embedder's fetcher while the import waits. The runtime keeps every
+//! module it compiled by name, so a module imported by several others is
+//! fetched and compiled once.
+
+use std::sync::{Arc, Mutex};
+
+use rquickjs::loader::{Loader, Resolver};
+use rquickjs::module::Declared;
+use rquickjs::{Ctx, Error, Module, Result};
+use url::Url;
+
+use crate::limits::SharedLimits;
+
+/// Fetches the source of the modules pages import
+pub trait ModuleFetcher: Send + Sync {
+    /// The source of the module at `url`, or why it could not be fetched
+    fn fetch(&self, url: &Url) -> std::result::Result<String, String>;
+}
+
+/// Shared reference to the embedder's module fetcher
+pub type SharedModuleFetcher = Arc<dyn ModuleFetcher>;
+
+/// The module fetcher set by the embedder, None before it does
+///
+/// Shared with the loader of the runtime, which must be sendable.
+pub(crate) type ModuleFetcherHandle = Arc<Mutex<Option<SharedModuleFetcher>>>;
+
+/// Name of the inline module script `node_id` of a document whose base URL
+/// is `base`
+pub(crate) fn inline_module_name(base: &Url, node_id: u32) -> String {
+    let mut name = base.clone();
+    name.set_fragment(Some(&format!("module-{}", node_id)));
+    name.to_string()
+}
+
+/// Resolve an import specifier against the name of the importing module
+pub(crate) fn resolve_specifier(base: &str, specifier: &str) -> std::result::Result<Url, String> {
+    if let Ok(url) = Url::parse(specifier) {
+        return Ok(url);
+    }
+    if !["/", "./", "../"].iter().any(|prefix| specifier.starts_with(prefix)) {
+        return Err(format!(
+            "Failed to resolve module specifier \"{}\". Relative references must start with \"/\", \"./\" or \"../\".",
+            specifier
+        ));
+    }
+    Url::parse(base).and_then(|base| base.join(specifier)).map_err(|e| e.to_string())
+}
+
+/// Resolves the specifiers of imports to the URLs of the modules
+pub(crate) struct UrlResolver;
+
+impl Resolver for UrlResolver {
+    fn resolve<'js>(&mut self, _ctx: &Ctx<'js>, base: &str, name: &str) -> Result<String> {
+        resolve_specifier(base, name)
+            .map(String::from)
+            .map_err(|message| Error::new_resolving_message(base, name, message))
+    }
+}
+
+/// Loads imported modules from the embedder's fetcher
+pub(crate) struct UrlLoader {
+    fetcher: ModuleFetcherHandle,
+    /// Fetching does not count against the importing script's time
+    limits: SharedLimits,
+}
+
+impl UrlLoader {
+    pub(crate) fn new(fetcher: ModuleFetcherHandle, limits: SharedLimits) -> Self {
+        Self { fetcher, limits }
+    }
+}
+
+impl Loader for UrlLoader {
+    fn load<'js>(&mut self, ctx: &Ctx<'js>, name: &str) -> Result<Module<'js, Declared>> {
+        let url = Url::parse(name).map_err(|e| Error::new_loading_message(name, e.to_string()))?;
+        let fetcher = self.fetcher.lock().ok().and_then(|fetcher| fetcher.clone());
+        let Some(fetcher) = fetcher else {
+            return Err(Error::new_loading_message(name, "Modules cannot be fetched"));
+        };
+        let source = self.limits.paused(|| fetcher.fetch(&url)).map_err(|e| Error::new_loading_message(name, e))?;
+        Module::declare(ctx.clone(), name, source)
+    }
+}
+
+/// Register the settling of module evaluations in the global scope
+pub fn register_modules(ctx: &Ctx<'_>) -> Result<()> {
+    // A module that throws rejects the promise of its evaluation, which is
+    // reported as the exception it threw
+    let module_code = r#"
+        (function() {
+            var moduleError = null;
+
+            globalThis.__settleModule = function(promise) {
+                moduleError = null;
+                promise.then(null, function(error) { moduleError = __reportException(error); });
+            };
+
+            // The error the last module evaluated failed with, once it
+            // settled
+            globalThis.__takeModuleError = function() {
+                var error = moduleError;
+                moduleError = null;
+                return error;
+            };
+        })();
+    "#;
+
+    ctx.eval::<(), _>(module_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_specifiers_resolve_against_importer() {
+        let base = "https://a.test/app/main.js";
+        let resolve = |specifier| resolve_specifier(base, specifier).map(String::from);
+        assert_eq!(resolve("./util.js"), Ok("https://a.test/app/util.js".to_string()));
+        assert_eq!(resolve("../lib/x.js"), Ok("https://a.test/lib/x.js".to_string()));
+        assert_eq!(resolve("/root.js"), Ok("https://a.test/root.js".to_string()));
+        assert_eq!(resolve("https://b.test/y.js"), Ok("https://b.test/y.js".to_string()));
+        assert!(resolve("lodash").unwrap_err().contains("Failed to resolve module specifier \"lodash\""));
+    }
+
+    #[test]
+    fn test_inline_modules_resolve_against_base() {
+        let base = Url::parse("https://a.test/docs/page.html#intro").unwrap();
+        let name = inline_module_name(&base, 7);
+        assert_eq!(name, "https://a.test/docs/page.html#module-7");
+        assert_eq!(resolve_specifier(&name, "./x.js").map(String::from), Ok("https://a.test/docs/x.js".to_string()));
+    }
+}
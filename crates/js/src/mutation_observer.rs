@@ -0,0 +1,437 @@
+//! Mutation Observers
+//!
+//! Provides `MutationObserver`. The natives that change the DOM for
+//! scripts record each change here, and it is queued for every observer
+//! watching it: one observing the changed node, or an ancestor of it with
+//! `subtree`. The records a script's changes leave are delivered together,
+//! in a microtask after them.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use gugalanna_dom::{DomTree, NodeId};
+use rquickjs::{Ctx, Function, Object, Result};
+use serde_json::{json, Value};
+
+/// What a mutation changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MutationKind {
+    ChildList,
+    Attributes,
+    CharacterData,
+}
+
+impl MutationKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MutationKind::ChildList => "childList",
+            MutationKind::Attributes => "attributes",
+            MutationKind::CharacterData => "characterData",
+        }
+    }
+}
+
+/// A change scripts made to the DOM, as observers are told of it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MutationRecord {
+    kind: MutationKind,
+    target: NodeId,
+    added: Vec<NodeId>,
+    removed: Vec<NodeId>,
+    attribute_name: Option<String>,
+    old_value: Option<String>,
+}
+
+impl MutationRecord {
+    pub(crate) fn child_list(target: NodeId, added: Vec<NodeId>, removed: Vec<NodeId>) -> Self {
+        Self { kind: MutationKind::ChildList, target, added, removed, attribute_name: None, old_value: None }
+    }
+
+    pub(crate) fn attribute(target: NodeId, name: &str, old_value: Option<String>) -> Self {
+        Self {
+            kind: MutationKind::Attributes,
+            target,
+            added: Vec::new(),
+            removed: Vec::new(),
+            attribute_name: Some(name.to_string()),
+            old_value,
+        }
+    }
+
+    pub(crate) fn character_data(target: NodeId, old_value: String) -> Self {
+        Self {
+            kind: MutationKind::CharacterData,
+            target,
+            added: Vec::new(),
+            removed: Vec::new(),
+            attribute_name: None,
+            old_value: Some(old_value),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let ids = |nodes: &[NodeId]| nodes.iter().map(|id| id.0).collect::<Vec<_>>();
+        json!({
+            "type": self.kind.as_str(),
+            "target": self.target.0,
+            "addedNodes": ids(&self.added),
+            "removedNodes": ids(&self.removed),
+            "attributeName": self.attribute_name,
+            "oldValue": self.old_value,
+        })
+    }
+}
+
+/// What an observer watches for, the options of `observe()`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ObserveOptions {
+    child_list: bool,
+    attributes: bool,
+    character_data: bool,
+    subtree: bool,
+    attribute_old_value: bool,
+    character_data_old_value: bool,
+    /// Attributes watched, None for all of them
+    attribute_filter: Option<Vec<String>>,
+}
+
+impl ObserveOptions {
+    /// Options as `observe()` hands them over, missing ones false
+    fn from_json(options: &Value) -> Self {
+        let flag = |name: &str| options[name].as_bool().unwrap_or(false);
+        Self {
+            child_list: flag("childList"),
+            attributes: flag("attributes"),
+            character_data: flag("characterData"),
+            subtree: flag("subtree"),
+            attribute_old_value: flag("attributeOldValue"),
+            character_data_old_value: flag("characterDataOldValue"),
+            attribute_filter: options["attributeFilter"]
+                .as_array()
+                .map(|names| names.iter().filter_map(|name| name.as_str().map(str::to_string)).collect()),
+        }
+    }
+
+    /// The record as an observer with these options is told of it, None
+    /// when they leave it out
+    fn filter(&self, record: &MutationRecord) -> Option<MutationRecord> {
+        let (watched, old_value) = match record.kind {
+            MutationKind::ChildList => (self.child_list, false),
+            MutationKind::Attributes => {
+                let name = record.attribute_name.as_deref().unwrap_or_default();
+                let filtered = self.attribute_filter.as_ref().is_some_and(|names| !names.iter().any(|n| n == name));
+                (self.attributes && !filtered, self.attribute_old_value)
+            }
+            MutationKind::CharacterData => (self.character_data, self.character_data_old_value),
+        };
+        if !watched {
+            return None;
+        }
+        let mut record = record.clone();
+        if !old_value {
+            record.old_value = None;
+        }
+        Some(record)
+    }
+}
+
+/// An observer watching a node
+struct Registration {
+    observer: u32,
+    target: NodeId,
+    options: ObserveOptions,
+}
+
+/// What each observer watches and the records waiting for it
+#[derive(Default)]
+pub(crate) struct MutationObservers {
+    registrations: Vec<Registration>,
+    /// Records not yet delivered, by observer, observers in the order they
+    /// were created
+    pending: BTreeMap<u32, Vec<MutationRecord>>,
+}
+
+impl MutationObservers {
+    /// Whether any observer watches a node, changes going unrecorded when
+    /// none does
+    pub(crate) fn is_observing(&self) -> bool {
+        !self.registrations.is_empty()
+    }
+
+    /// Watch `target`, replacing the options the observer watched it with
+    pub(crate) fn observe(&mut self, observer: u32, target: NodeId, options: ObserveOptions) {
+        match self.registrations.iter_mut().find(|r| r.observer == observer && r.target == target) {
+            Some(registration) => registration.options = options,
+            None => self.registrations.push(Registration { observer, target, options }),
+        }
+    }
+
+    /// Stop an observer watching, dropping the records waiting for it
+    pub(crate) fn disconnect(&mut self, observer: u32) {
+        self.registrations.retain(|r| r.observer != observer);
+        self.pending.remove(&observer);
+    }
+
+    pub(crate) fn take_records(&mut self, observer: u32) -> Vec<MutationRecord> {
+        self.pending.remove(&observer).unwrap_or_default()
+    }
+
+    /// Queue a change for each observer watching it, once however many of
+    /// its registrations do
+    pub(crate) fn record(&mut self, dom: &DomTree, record: MutationRecord) {
+        let mut queued = Vec::new();
+        for registration in &self.registrations {
+            if queued.contains(&registration.observer) {
+                continue;
+            }
+            let watched = registration.target == record.target
+                || (registration.options.subtree && dom.contains(registration.target, record.target));
+            if let Some(filtered) = registration.options.filter(&record).filter(|_| watched) {
+                self.pending.entry(registration.observer).or_default().push(filtered);
+                queued.push(registration.observer);
+            }
+        }
+    }
+
+    /// Record the children `parent` gained and lost since it had `before`
+    pub(crate) fn record_children(&mut self, dom: &DomTree, parent: NodeId, before: &[NodeId]) {
+        let after = dom.children(parent);
+        let added = difference(&after, before);
+        let removed = difference(before, &after);
+        if !added.is_empty() || !removed.is_empty() {
+            self.record(dom, MutationRecord::child_list(parent, added, removed));
+        }
+    }
+
+    /// Record an edit of `parent`'s children that moved `node`, from the
+    /// children `parent` had before and the parent `node` had with its
+    /// children then
+    pub(crate) fn record_move(
+        &mut self,
+        dom: &DomTree,
+        parent: NodeId,
+        before: &[NodeId],
+        node: NodeId,
+        old_parent: Option<(NodeId, Vec<NodeId>)>,
+    ) {
+        match old_parent {
+            Some((old_parent, siblings)) if old_parent != parent => self.record_children(dom, old_parent, &siblings),
+            // A child moved within its parent is removed, then inserted
+            Some(_) if dom.parent(node) == Some(parent) => {
+                self.record(dom, MutationRecord::child_list(parent, Vec::new(), vec![node]));
+                let before: Vec<NodeId> = before.iter().copied().filter(|&id| id != node).collect();
+                self.record_children(dom, parent, &before);
+                return;
+            }
+            _ => {}
+        }
+        self.record_children(dom, parent, before);
+    }
+}
+
+/// Nodes of `a` not in `b`, in their order in `a`
+fn difference(a: &[NodeId], b: &[NodeId]) -> Vec<NodeId> {
+    a.iter().copied().filter(|id| !b.contains(id)).collect()
+}
+
+/// Shared mutation observers of the page
+pub(crate) type SharedObservers = Rc<RefCell<MutationObservers>>;
+
+/// Register the mutation observer natives on `document` and
+/// `MutationObserver`
+pub fn register_mutation_observers<'js>(
+    ctx: &Ctx<'js>,
+    document: &Object<'js>,
+    observers: SharedObservers,
+) -> Result<()> {
+    let shared = observers.clone();
+    document.set(
+        "_observeMutations",
+        Function::new(ctx.clone(), move |observer: u32, target: i32, options: String| {
+            let options = serde_json::from_str(&options).unwrap_or(Value::Null);
+            shared.borrow_mut().observe(observer, NodeId::new(target as u32), ObserveOptions::from_json(&options));
+        })?,
+    )?;
+    let shared = observers.clone();
+    document.set(
+        "_disconnectMutations",
+        Function::new(ctx.clone(), move |observer: u32| shared.borrow_mut().disconnect(observer))?,
+    )?;
+    let shared = observers.clone();
+    document.set(
+        "_takeMutationRecords",
+        Function::new(ctx.clone(), move |observer: u32| -> String {
+            let records = shared.borrow_mut().take_records(observer);
+            Value::Array(records.iter().map(MutationRecord::to_json).collect()).to_string()
+        })?,
+    )?;
+    document.set(
+        "_pendingMutationObservers",
+        Function::new(ctx.clone(), move || -> Vec<u32> { observers.borrow().pending.keys().copied().collect() })?,
+    )?;
+
+    let observer_code = r#"
+        (function() {
+            var observers = {};
+            var nextObserverId = 1;
+            var deliveryQueued = false;
+
+            function nodeObject(nodeId) {
+                return nodeId === document._documentId ? document : new Element(nodeId);
+            }
+
+            function toRecord(record) {
+                return {
+                    type: record.type,
+                    target: nodeObject(record.target),
+                    addedNodes: new NodeList(record.addedNodes),
+                    removedNodes: new NodeList(record.removedNodes),
+                    previousSibling: null,
+                    nextSibling: null,
+                    attributeName: record.attributeName,
+                    attributeNamespace: null,
+                    oldValue: record.oldValue
+                };
+            }
+
+            // Hand each observer with records waiting the records, an
+            // exception in one callback not keeping the others from theirs
+            function deliver() {
+                deliveryQueued = false;
+                document._pendingMutationObservers().forEach(function(id) {
+                    var observer = observers[id];
+                    var records = observer ? observer.takeRecords() : [];
+                    if (records.length === 0) return;
+                    try {
+                        observer.__callback.call(observer, records, observer);
+                    } catch (e) {
+                        __reportException(e);
+                    }
+                });
+            }
+
+            // The natives that change the DOM queue the delivery of the
+            // records they leave
+            var mutatingNatives = [
+                '_setAttribute', '_removeAttribute', '_appendChild', '_insertBefore', '_replaceChild', '_removeChild',
+                '_setTextContent', '_setInnerText', '_setStyle', '_removeStyle', '_setStyleText', '_classListAdd',
+                '_classListRemove', '_classListToggle', '_classListReplace'
+            ];
+            mutatingNatives.forEach(function(name) {
+                var native = document[name];
+                document[name] = function() {
+                    var result = native.apply(document, arguments);
+                    if (!deliveryQueued && document._pendingMutationObservers().length > 0) {
+                        deliveryQueued = true;
+                        Promise.resolve().then(deliver);
+                    }
+                    return result;
+                };
+            });
+
+            function MutationObserver(callback) {
+                if (typeof callback !== 'function') {
+                    throw new TypeError("Failed to construct 'MutationObserver': parameter 1 is not a function.");
+                }
+                this.__id = nextObserverId++;
+                this.__callback = callback;
+            }
+
+            MutationObserver.prototype.observe = function(target, options) {
+                if (target !== document && !(target instanceof Element)) {
+                    throw new TypeError("MutationObserver can only observe nodes.");
+                }
+                options = options || {};
+                // Asking for old values or a filter implies watching them
+                var attributes = options.attributes;
+                var attributeOptions = options.attributeOldValue !== undefined || options.attributeFilter !== undefined;
+                if (attributes === undefined && attributeOptions) {
+                    attributes = true;
+                }
+                var characterData = options.characterData;
+                if (characterData === undefined && options.characterDataOldValue !== undefined) {
+                    characterData = true;
+                }
+                if (!options.childList && !attributes && !characterData) {
+                    throw new TypeError("The options must set 'attributes', 'characterData' or 'childList'.");
+                }
+                var filter = options.attributeFilter;
+                var targetId = target === document ? document._documentId : target.__nodeId;
+                document._observeMutations(this.__id, targetId, JSON.stringify({
+                    childList: !!options.childList,
+                    attributes: !!attributes,
+                    characterData: !!characterData,
+                    subtree: !!options.subtree,
+                    attributeOldValue: !!options.attributeOldValue,
+                    characterDataOldValue: !!options.characterDataOldValue,
+                    attributeFilter: filter === undefined ? null : Array.prototype.map.call(filter, String)
+                }));
+                observers[this.__id] = this;
+            };
+
+            MutationObserver.prototype.disconnect = function() {
+                document._disconnectMutations(this.__id);
+                delete observers[this.__id];
+            };
+
+            MutationObserver.prototype.takeRecords = function() {
+                return JSON.parse(document._takeMutationRecords(this.__id)).map(toRecord);
+            };
+
+            globalThis.MutationObserver = MutationObserver;
+        })();
+    "#;
+
+    ctx.eval::<(), _>(observer_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_dom::Queryable;
+    use gugalanna_html::HtmlParser;
+
+    fn options(json: &str) -> ObserveOptions {
+        ObserveOptions::from_json(&serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_subtree_records_reach_observer_once() {
+        let mut dom = HtmlParser::new().parse("<div id='outer'><p id='inner'></p></div>").unwrap();
+        let (outer, inner) = (dom.get_element_by_id("outer").unwrap(), dom.get_element_by_id("inner").unwrap());
+        let mut observers = MutationObservers::default();
+        observers.observe(1, outer, options(r#"{"childList": true, "subtree": true}"#));
+        observers.observe(1, inner, options(r#"{"childList": true}"#));
+        observers.observe(2, outer, options(r#"{"childList": true}"#));
+
+        let before = dom.children(inner);
+        let span = dom.create_element("span");
+        dom.append_child(inner, span).unwrap();
+        observers.record_children(&dom, inner, &before);
+
+        assert_eq!(observers.take_records(1), [MutationRecord::child_list(inner, vec![span], Vec::new())]);
+        assert!(observers.take_records(2).is_empty());
+    }
+
+    #[test]
+    fn test_attribute_filter_and_old_values() {
+        let dom = HtmlParser::new().parse("<div id='box'></div>").unwrap();
+        let b = dom.get_element_by_id("box").unwrap();
+        let mut observers = MutationObservers::default();
+        observers.observe(1, b, options(r#"{"attributes": true, "attributeFilter": ["class"]}"#));
+        observers.observe(2, b, options(r#"{"attributes": true, "attributeOldValue": true}"#));
+
+        observers.record(&dom, MutationRecord::attribute(b, "class", Some("old".to_string())));
+        observers.record(&dom, MutationRecord::attribute(b, "title", None));
+
+        assert_eq!(observers.take_records(1), [MutationRecord::attribute(b, "class", None)]);
+        let old_class = MutationRecord::attribute(b, "class", Some("old".to_string()));
+        assert_eq!(observers.take_records(2), [old_class, MutationRecord::attribute(b, "title", None)]);
+
+        // Disconnected observers are told of nothing more
+        observers.disconnect(2);
+        observers.record(&dom, MutationRecord::attribute(b, "class", None));
+        assert!(observers.take_records(2).is_empty());
+    }
+}
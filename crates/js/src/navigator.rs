@@ -0,0 +1,118 @@
+//! Navigator
+//!
+//! Provides `navigator`, what scripts know of the browser and the system
+//! it runs on. The user agent is the one the embedder's network layer
+//! sends, languages come from the system locale. Text written with
+//! `navigator.clipboard.writeText()` is kept for the embedder to take.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rquickjs::{Ctx, Function, Result};
+
+/// The user agent scripts see, as the embedder sends it
+pub type UserAgent = Rc<RefCell<String>>;
+
+/// Text scripts wrote to the clipboard, not taken by the embedder yet
+pub type Clipboard = Rc<RefCell<Option<String>>>;
+
+/// User agent until the embedder hands over its own
+pub const DEFAULT_USER_AGENT: &str = concat!("Gugalanna/", env!("CARGO_PKG_VERSION"));
+
+/// Language tag of a POSIX locale, as `en-US` for `en_US.UTF-8`
+fn locale_language(locale: &str) -> Option<String> {
+    let name = locale.split(['.', '@']).next()?;
+    if name.is_empty() || name == "C" || name == "POSIX" {
+        return None;
+    }
+    Some(name.replace('_', "-"))
+}
+
+/// Languages of the system locale, most preferred first, as `en-US` and
+/// then `en`
+fn system_languages() -> Vec<String> {
+    let language = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find_map(|locale| locale_language(&locale))
+        .unwrap_or_else(|| "en-US".to_string());
+    let mut languages = vec![language.clone()];
+    if let Some((primary, _)) = language.split_once('-') {
+        languages.push(primary.to_string());
+    }
+    languages
+}
+
+/// The platform as browsers name it
+fn platform() -> String {
+    match std::env::consts::OS {
+        "macos" => "MacIntel".to_string(),
+        "windows" => "Win32".to_string(),
+        "linux" => format!("Linux {}", std::env::consts::ARCH),
+        os => os.to_string(),
+    }
+}
+
+/// Register `navigator` in the global scope
+pub fn register_navigator(ctx: &Ctx<'_>, user_agent: UserAgent, clipboard: Clipboard) -> Result<()> {
+    let globals = ctx.globals();
+
+    globals.set(
+        "__navigatorUserAgent",
+        Function::new(ctx.clone(), move || -> String { user_agent.borrow().clone() })?,
+    )?;
+    globals.set("__navigatorLanguages", system_languages())?;
+    globals.set("__navigatorPlatform", platform())?;
+    globals.set(
+        "__clipboardWrite",
+        Function::new(ctx.clone(), move |text: String| {
+            *clipboard.borrow_mut() = Some(text);
+        })?,
+    )?;
+
+    // The navigator is frozen, so scripts cannot change what later checks
+    // see
+    let navigator_code = r#"
+        (function() {
+            var languages = Object.freeze(__navigatorLanguages.slice());
+            var platform = __navigatorPlatform;
+
+            var clipboard = Object.freeze({
+                writeText: function(text) {
+                    __clipboardWrite(String(text));
+                    return Promise.resolve();
+                }
+            });
+
+            var navigator = {};
+            Object.defineProperties(navigator, {
+                userAgent: { get: function() { return __navigatorUserAgent(); }, enumerable: true },
+                language: { value: languages[0], enumerable: true },
+                languages: { value: languages, enumerable: true },
+                platform: { value: platform, enumerable: true },
+                cookieEnabled: { value: true, enumerable: true },
+                onLine: { value: true, enumerable: true },
+                clipboard: { value: clipboard, enumerable: true }
+            });
+
+            Object.defineProperty(globalThis, 'navigator', { value: Object.freeze(navigator), enumerable: true });
+        })();
+    "#;
+
+    ctx.eval::<(), _>(navigator_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_language() {
+        assert_eq!(locale_language("en_US.UTF-8").as_deref(), Some("en-US"));
+        assert_eq!(locale_language("pt_BR").as_deref(), Some("pt-BR"));
+        assert_eq!(locale_language("de_DE@euro").as_deref(), Some("de-DE"));
+        assert_eq!(locale_language("C.UTF-8"), None);
+        assert_eq!(locale_language("POSIX"), None);
+        assert_eq!(locale_language(""), None);
+    }
+}
@@ -0,0 +1,272 @@
+//! Web Storage
+//!
+//! Provides localStorage and sessionStorage. The items of each origin are
+//! kept in a storage area owned by the embedder, which decides how long
+//! they live and where they are saved.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::rc::Rc;
+
+use rquickjs::{Ctx, Function, Object, Result};
+
+/// Most bytes of keys and values one origin may store
+pub const STORAGE_QUOTA: usize = 5 * 1024 * 1024;
+
+/// The items of one origin
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageArea {
+    items: BTreeMap<String, String>,
+    /// Items changed since the last `take_changed`
+    changed: bool,
+}
+
+impl StorageArea {
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Key of the item at an index, in a stable order
+    pub fn key(&self, index: usize) -> Option<&str> {
+        self.items.keys().nth(index).map(String::as_str)
+    }
+
+    pub fn get_item(&self, key: &str) -> Option<&str> {
+        self.items.get(key).map(String::as_str)
+    }
+
+    /// Set an item, returns false and leaves the area as it was when it
+    /// would go over the quota
+    pub fn set_item(&mut self, key: &str, value: &str) -> bool {
+        let replaced = self.items.get(key).map_or(0, |old| key.len() + old.len());
+        if self.size() - replaced + key.len() + value.len() > STORAGE_QUOTA {
+            return false;
+        }
+        if self.get_item(key) != Some(value) {
+            self.items.insert(key.to_string(), value.to_string());
+            self.changed = true;
+        }
+        true
+    }
+
+    pub fn remove_item(&mut self, key: &str) {
+        self.changed |= self.items.remove(key).is_some();
+    }
+
+    pub fn clear(&mut self) {
+        self.changed |= !self.items.is_empty();
+        self.items.clear();
+    }
+
+    /// Bytes of keys and values stored, counted against the quota
+    pub fn size(&self) -> usize {
+        self.items.iter().map(|(key, value)| key.len() + value.len()).sum()
+    }
+
+    /// Check if items changed since the last call
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// Shared reference to a storage area
+pub type SharedStorage = Rc<RefCell<StorageArea>>;
+
+/// Storage areas by origin
+#[derive(Debug, Default)]
+pub struct StorageStore {
+    areas: HashMap<String, SharedStorage>,
+}
+
+impl StorageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Storage area of an origin, serialized as in `Origin::ascii_serialization`
+    pub fn area(&mut self, origin: &str) -> SharedStorage {
+        self.areas.entry(origin.to_string()).or_default().clone()
+    }
+
+    /// Check if items of any origin changed since the last call
+    pub fn take_changed(&self) -> bool {
+        self.areas.values().fold(false, |changed, area| area.borrow_mut().take_changed() | changed)
+    }
+
+    /// Load the areas saved to a file, none when it doesn't exist yet
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e),
+        };
+        let saved: HashMap<String, BTreeMap<String, String>> = serde_json::from_str(&json)?;
+        let areas = saved
+            .into_iter()
+            .map(|(origin, items)| (origin, Rc::new(RefCell::new(StorageArea { items, changed: false }))))
+            .collect();
+        Ok(Self { areas })
+    }
+
+    /// Save the areas that have items to a file, creating its directory
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let saved: HashMap<&str, BTreeMap<String, String>> = self
+            .areas
+            .iter()
+            .filter(|(_, area)| !area.borrow().is_empty())
+            .map(|(origin, area)| (origin.as_str(), area.borrow().items.clone()))
+            .collect();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(&saved)?)
+    }
+}
+
+/// Register a Storage object in the global scope under `name`, backed by
+/// a storage area
+pub fn register_storage(ctx: &Ctx<'_>, name: &str, area: SharedStorage) -> Result<()> {
+    let native = Object::new(ctx.clone())?;
+
+    let store = area.clone();
+    native.set(
+        "_getItem",
+        Function::new(ctx.clone(), move |key: String| -> Option<String> {
+            store.borrow().get_item(&key).map(str::to_string)
+        })?,
+    )?;
+
+    let store = area.clone();
+    native.set(
+        "_setItem",
+        Function::new(ctx.clone(), move |key: String, value: String| -> bool {
+            store.borrow_mut().set_item(&key, &value)
+        })?,
+    )?;
+
+    let store = area.clone();
+    native.set(
+        "_removeItem",
+        Function::new(ctx.clone(), move |key: String| {
+            store.borrow_mut().remove_item(&key);
+        })?,
+    )?;
+
+    let store = area.clone();
+    native.set(
+        "_clear",
+        Function::new(ctx.clone(), move || {
+            store.borrow_mut().clear();
+        })?,
+    )?;
+
+    let store = area.clone();
+    native.set(
+        "_key",
+        Function::new(ctx.clone(), move |index: i32| -> Option<String> {
+            let store = store.borrow();
+            usize::try_from(index).ok().and_then(|i| store.key(i)).map(str::to_string)
+        })?,
+    )?;
+
+    let store = area;
+    native.set(
+        "_length",
+        Function::new(ctx.clone(), move || -> i32 { store.borrow().len() as i32 })?,
+    )?;
+
+    // Keys and values are strings, items can also be read and written as
+    // properties
+    let wrapper_code = r#"
+        (function(native) {
+            var methods = {
+                getItem: function(key) {
+                    var value = native._getItem(String(key));
+                    return value === undefined ? null : value;
+                },
+                setItem: function(key, value) {
+                    if (!native._setItem(String(key), String(value))) {
+                        var error = new Error("The quota has been exceeded.");
+                        error.name = 'QuotaExceededError';
+                        error.code = 22;
+                        throw error;
+                    }
+                },
+                removeItem: function(key) { native._removeItem(String(key)); },
+                clear: function() { native._clear(); },
+                key: function(index) {
+                    var key = native._key(Number(index) | 0);
+                    return key === undefined ? null : key;
+                }
+            };
+
+            return new Proxy({}, {
+                get: function(target, prop) {
+                    if (prop === 'length') return native._length();
+                    if (typeof prop !== 'string') return undefined;
+                    if (Object.prototype.hasOwnProperty.call(methods, prop)) return methods[prop];
+                    var value = native._getItem(prop);
+                    return value === undefined ? undefined : value;
+                },
+                set: function(target, prop, value) {
+                    methods.setItem(prop, value);
+                    return true;
+                },
+                has: function(target, prop) {
+                    return native._getItem(String(prop)) !== undefined;
+                },
+                deleteProperty: function(target, prop) {
+                    native._removeItem(String(prop));
+                    return true;
+                }
+            });
+        })
+    "#;
+
+    let wrap: Function = ctx.eval(wrapper_code)?;
+    let storage: Object = wrap.call((native,))?;
+    ctx.globals().set(name, storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_area_quota() {
+        let mut area = StorageArea::default();
+        let big = "x".repeat(STORAGE_QUOTA - 4);
+
+        assert!(area.set_item("big", &big));
+        assert!(area.take_changed());
+        // One byte over, the item is left as it was
+        assert!(!area.set_item("more", "xx"));
+        assert_eq!(area.get_item("more"), None);
+        assert!(!area.take_changed());
+        // Replacing an item only counts the difference
+        assert!(area.set_item("big", "small"));
+        assert!(area.set_item("more", "xx"));
+    }
+
+    #[test]
+    fn test_store_saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("gugalanna-storage-{}.json", std::process::id()));
+        let mut store = StorageStore::new();
+        store.area("https://a.test").borrow_mut().set_item("theme", "dark");
+        store.area("https://b.test");
+        assert!(store.take_changed());
+        assert!(!store.take_changed());
+        store.save(&path).unwrap();
+
+        let mut loaded = StorageStore::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.area("https://a.test").borrow().get_item("theme"), Some("dark"));
+        assert!(loaded.area("https://b.test").borrow().is_empty());
+        assert!(!loaded.take_changed());
+    }
+}
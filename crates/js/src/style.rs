@@ -0,0 +1,195 @@
+//! Inline Styles
+//!
+//! Provides `element.style`, a CSSStyleDeclaration that reads and writes
+//! the declarations of the element's `style` attribute. Each change
+//! parses the attribute into a declaration block, edits it and writes the
+//! block back, recording the element in the mutation journal and the
+//! change for mutation observers.
+
+use gugalanna_css::DeclarationBlock;
+use gugalanna_dom::{DomTree, NodeId};
+use rquickjs::{Ctx, Function, Object, Result};
+
+use crate::mutation_observer::{MutationRecord, SharedObservers};
+use crate::{MutationJournal, SharedDom};
+
+/// Declarations of an element's `style` attribute
+fn inline_style(dom: &DomTree, id: NodeId) -> DeclarationBlock {
+    dom.get(id)
+        .and_then(|n| n.as_element())
+        .and_then(|e| e.get_attribute("style"))
+        .map(DeclarationBlock::parse)
+        .unwrap_or_default()
+}
+
+/// Edit the declarations of an element's `style` attribute, writing them
+/// back and journaling the element when they changed
+fn edit_inline_style<R>(
+    dom: &SharedDom,
+    journal: &MutationJournal,
+    observers: &SharedObservers,
+    node_id: i32,
+    edit: impl FnOnce(&mut DeclarationBlock) -> R,
+) -> R {
+    let id = NodeId::new(node_id as u32);
+    let mut dom = dom.borrow_mut();
+    let mut block = inline_style(&dom, id);
+    let before = block.clone();
+    let result = edit(&mut block);
+    if block != before && dom.get(id).is_some_and(|n| n.as_element().is_some()) {
+        let old_value = dom.get(id).and_then(|n| n.as_element()).and_then(|e| e.get_attribute("style"));
+        let record = MutationRecord::attribute(id, "style", old_value.map(str::to_string));
+        dom.set_attribute(id, "style", &block.to_css());
+        journal.borrow_mut().push(id);
+        observers.borrow_mut().record(&dom, record);
+    }
+    result
+}
+
+/// Register the inline style natives on `document` and the `style`
+/// property of elements
+pub fn register_style<'js>(
+    ctx: &Ctx<'js>,
+    document: &Object<'js>,
+    dom: SharedDom,
+    journal: MutationJournal,
+    observers: SharedObservers,
+) -> Result<()> {
+    let dom_clone = dom.clone();
+    document.set(
+        "_getStyle",
+        Function::new(ctx.clone(), move |node_id: i32, property: String| -> String {
+            let block = inline_style(&dom_clone.borrow(), NodeId::new(node_id as u32));
+            block.get(&property).unwrap_or_default().to_string()
+        })?,
+    )?;
+
+    let dom_clone = dom.clone();
+    document.set(
+        "_getStylePriority",
+        Function::new(ctx.clone(), move |node_id: i32, property: String| -> String {
+            let block = inline_style(&dom_clone.borrow(), NodeId::new(node_id as u32));
+            let priority = if block.is_important(&property) { "important" } else { "" };
+            priority.to_string()
+        })?,
+    )?;
+
+    let (dom_clone, journal_clone, observers_clone) = (dom.clone(), journal.clone(), observers.clone());
+    document.set(
+        "_setStyle",
+        Function::new(ctx.clone(), move |node_id: i32, property: String, value: String, priority: String| {
+            let important = priority.eq_ignore_ascii_case("important");
+            edit_inline_style(&dom_clone, &journal_clone, &observers_clone, node_id, |block| {
+                block.set(&property, &value, important);
+            });
+        })?,
+    )?;
+
+    let (dom_clone, journal_clone, observers_clone) = (dom.clone(), journal.clone(), observers.clone());
+    document.set(
+        "_removeStyle",
+        Function::new(ctx.clone(), move |node_id: i32, property: String| -> String {
+            edit_inline_style(&dom_clone, &journal_clone, &observers_clone, node_id, |block| block.remove(&property))
+                .unwrap_or_default()
+        })?,
+    )?;
+
+    let dom_clone = dom.clone();
+    document.set(
+        "_getStyleText",
+        Function::new(ctx.clone(), move |node_id: i32| -> String {
+            inline_style(&dom_clone.borrow(), NodeId::new(node_id as u32)).to_css()
+        })?,
+    )?;
+
+    let (dom_clone, journal_clone, observers_clone) = (dom.clone(), journal, observers);
+    document.set(
+        "_setStyleText",
+        Function::new(ctx.clone(), move |node_id: i32, text: String| {
+            edit_inline_style(&dom_clone, &journal_clone, &observers_clone, node_id, |block| {
+                *block = DeclarationBlock::parse(&text);
+            });
+        })?,
+    )?;
+
+    let dom_clone = dom.clone();
+    document.set(
+        "_styleLength",
+        Function::new(ctx.clone(), move |node_id: i32| -> i32 {
+            inline_style(&dom_clone.borrow(), NodeId::new(node_id as u32)).len() as i32
+        })?,
+    )?;
+
+    let dom_clone = dom;
+    document.set(
+        "_styleItem",
+        Function::new(ctx.clone(), move |node_id: i32, index: i32| -> String {
+            let block = inline_style(&dom_clone.borrow(), NodeId::new(node_id as u32));
+            usize::try_from(index).ok().and_then(|i| block.item(i)).unwrap_or_default().to_string()
+        })?,
+    )?;
+
+    // Properties can be read and written in camelCase, as `style.fontSize`
+    let style_code = r#"
+        (function() {
+            function CSSStyleDeclaration(nodeId) {
+                this.__nodeId = nodeId;
+            }
+
+            CSSStyleDeclaration.prototype.getPropertyValue = function(property) {
+                return document._getStyle(this.__nodeId, String(property));
+            };
+
+            CSSStyleDeclaration.prototype.getPropertyPriority = function(property) {
+                return document._getStylePriority(this.__nodeId, String(property));
+            };
+
+            CSSStyleDeclaration.prototype.setProperty = function(property, value, priority) {
+                value = value === undefined || value === null ? '' : String(value);
+                document._setStyle(this.__nodeId, String(property), value, priority ? String(priority) : '');
+            };
+
+            CSSStyleDeclaration.prototype.removeProperty = function(property) {
+                return document._removeStyle(this.__nodeId, String(property));
+            };
+
+            CSSStyleDeclaration.prototype.item = function(index) {
+                return document._styleItem(this.__nodeId, Number(index) | 0);
+            };
+
+            Object.defineProperty(CSSStyleDeclaration.prototype, 'cssText', {
+                get: function() { return document._getStyleText(this.__nodeId); },
+                set: function(text) { document._setStyleText(this.__nodeId, String(text)); }
+            });
+
+            Object.defineProperty(CSSStyleDeclaration.prototype, 'length', {
+                get: function() { return document._styleLength(this.__nodeId); }
+            });
+
+            Object.defineProperty(Element.prototype, 'style', {
+                get: function() {
+                    return new Proxy(new CSSStyleDeclaration(this.__nodeId), {
+                        get: function(target, prop) {
+                            if (typeof prop !== 'string' || prop in target) return target[prop];
+                            if (/^[0-9]+$/.test(prop)) return target.item(prop);
+                            return target.getPropertyValue(__cssProperty(prop));
+                        },
+                        set: function(target, prop, value) {
+                            if (typeof prop !== 'string' || prop in target) {
+                                target[prop] = value;
+                            } else {
+                                target.setProperty(__cssProperty(prop), value);
+                            }
+                            return true;
+                        }
+                    });
+                },
+                set: function(text) { this.style.cssText = text; }
+            });
+
+            globalThis.CSSStyleDeclaration = CSSStyleDeclaration;
+        })();
+    "#;
+
+    ctx.eval::<(), _>(style_code)
+}
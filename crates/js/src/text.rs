@@ -0,0 +1,220 @@
+//! Rendered Text
+//!
+//! Provides `element.innerText`. The text is read as it would render,
+//! approximated from the tree without a layout: whitespace collapses,
+//! block elements break lines, `<br>` is a line break and hidden elements
+//! such as scripts are left out. Setting it replaces the children like
+//! `textContent`, with each line break becoming a `<br>`.
+
+use gugalanna_dom::{DomResult, DomTree, NodeId, NodeType};
+use rquickjs::{Ctx, Function, Object, Result};
+
+use crate::mutation_observer::SharedObservers;
+use crate::{MutationJournal, SharedDom};
+
+/// Elements laid out as blocks, which start and end a line
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "dd", "details", "div", "dl", "dt", "fieldset", "figcaption",
+    "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "main", "nav", "ol",
+    "p", "pre", "section", "summary", "table", "tr", "ul",
+];
+
+/// Elements that never render
+const HIDDEN_ELEMENTS: &[&str] = &["head", "noscript", "script", "style", "template", "title"];
+
+/// Rendered text as it is collected, runs of whitespace collapsed and
+/// line breaks held back until text follows them
+#[derive(Default)]
+struct TextBuilder {
+    text: String,
+    /// Line breaks owed before the next text
+    breaks: usize,
+    /// The last character written was a collapsible space
+    space: bool,
+}
+
+impl TextBuilder {
+    fn push_text(&mut self, text: &str, preserve: bool) {
+        for c in text.chars() {
+            if !preserve && c.is_ascii_whitespace() {
+                self.space = true;
+                continue;
+            }
+            if self.breaks > 0 {
+                if !self.text.is_empty() {
+                    self.text.push_str(&"\n".repeat(self.breaks));
+                }
+                self.breaks = 0;
+            } else if self.space && !self.text.is_empty() && !self.text.ends_with('\n') {
+                self.text.push(' ');
+            }
+            self.space = false;
+            self.text.push(c);
+        }
+    }
+
+    /// Owe `count` line breaks, those owed already counting toward them
+    fn require_breaks(&mut self, count: usize) {
+        if count > 0 {
+            self.breaks = self.breaks.max(count);
+            self.space = false;
+        }
+    }
+
+    /// A `<br>`, which breaks the line even when others break there too
+    fn line_break(&mut self) {
+        if !self.text.is_empty() {
+            self.text.push_str(&"\n".repeat(self.breaks));
+        }
+        self.text.push('\n');
+        self.breaks = 0;
+        self.space = false;
+    }
+}
+
+fn collect_text(dom: &DomTree, id: NodeId, preserve: bool, builder: &mut TextBuilder) {
+    let Some(node) = dom.get(id) else {
+        return;
+    };
+    match &node.node_type {
+        NodeType::Text(text) => builder.push_text(text, preserve),
+        NodeType::Element(element) => {
+            let tag = element.tag_name.as_str();
+            if HIDDEN_ELEMENTS.contains(&tag) || element.get_attribute("hidden").is_some() {
+                return;
+            }
+            if tag == "br" {
+                builder.line_break();
+                return;
+            }
+            // Paragraphs are a blank line apart
+            let breaks = match tag {
+                "p" => 2,
+                _ if BLOCK_ELEMENTS.contains(&tag) => 1,
+                _ => 0,
+            };
+            builder.require_breaks(breaks);
+            for &child in &node.children {
+                collect_text(dom, child, preserve || tag == "pre", builder);
+            }
+            builder.require_breaks(breaks);
+        }
+        NodeType::Document | NodeType::DocumentFragment => {
+            for &child in &node.children {
+                collect_text(dom, child, preserve, builder);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The text of a node as it would render
+pub fn inner_text(dom: &DomTree, id: NodeId) -> String {
+    let mut builder = TextBuilder::default();
+    if let Some(node) = dom.get(id) {
+        let preserve = node.tag_name() == Some("pre");
+        for &child in &node.children {
+            collect_text(dom, child, preserve, &mut builder);
+        }
+    }
+    builder.text
+}
+
+/// Replace the children of a node with `text`, its line breaks as `<br>`
+/// elements
+pub fn set_inner_text(dom: &mut DomTree, id: NodeId, text: &str) -> DomResult<()> {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    if !dom.get(id).is_some_and(|n| n.is_element()) {
+        return dom.set_text_content(id, &text);
+    }
+    dom.set_text_content(id, "")?;
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            let br = dom.create_element("br");
+            dom.append_child(id, br)?;
+        }
+        if !line.is_empty() {
+            let text_id = dom.create_text(line);
+            dom.append_child(id, text_id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Register the innerText natives on `document` and the property of
+/// elements
+pub fn register_text<'js>(
+    ctx: &Ctx<'js>,
+    document: &Object<'js>,
+    dom: SharedDom,
+    journal: MutationJournal,
+    removed: MutationJournal,
+    observers: SharedObservers,
+) -> Result<()> {
+    let dom_clone = dom.clone();
+    document.set(
+        "_getInnerText",
+        Function::new(ctx.clone(), move |node_id: i32| -> String {
+            inner_text(&dom_clone.borrow(), NodeId::new(node_id as u32))
+        })?,
+    )?;
+    document.set(
+        "_setInnerText",
+        Function::new(ctx.clone(), move |node_id: i32, text: String| {
+            let id = NodeId::new(node_id as u32);
+            let mut dom = dom.borrow_mut();
+            let children = dom.children(id);
+            if set_inner_text(&mut dom, id, &text).is_ok() {
+                journal.borrow_mut().push(id);
+                removed.borrow_mut().extend(&children);
+                observers.borrow_mut().record_children(&dom, id, &children);
+            }
+        })?,
+    )?;
+
+    let text_code = r#"
+        Object.defineProperty(Element.prototype, 'innerText', {
+            get: function() { return document._getInnerText(this.__nodeId); },
+            set: function(v) { document._setInnerText(this.__nodeId, v == null ? '' : String(v)); }
+        });
+    "#;
+
+    ctx.eval::<(), _>(text_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_dom::Queryable;
+    use gugalanna_html::HtmlParser;
+
+    #[test]
+    fn test_inner_text_of_blocks_and_inlines() {
+        let html = "<div id='c'><p>Hello   <b>big</b>\n world</p><ul><li>one</li><li> two </li></ul>\
+                    tail<br>end<script>var x;</script><span hidden>gone</span></div>";
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let c = dom.get_element_by_id("c").unwrap();
+        assert_eq!(inner_text(&dom, c), "Hello big world\n\none\ntwo\ntail\nend");
+    }
+
+    #[test]
+    fn test_pre_keeps_whitespace() {
+        let dom = HtmlParser::new().parse("<pre id='code'>a  b\n  c</pre>").unwrap();
+        let code = dom.get_element_by_id("code").unwrap();
+        assert_eq!(inner_text(&dom, code), "a  b\n  c");
+    }
+
+    #[test]
+    fn test_set_inner_text_breaks_lines() {
+        let mut dom = HtmlParser::new().parse("<p id='p'><b>old</b></p>").unwrap();
+        let p = dom.get_element_by_id("p").unwrap();
+        set_inner_text(&mut dom, p, "one\ntwo\n").unwrap();
+        let tags: Vec<Option<String>> =
+            dom.children(p).iter().map(|&id| dom.get(id).unwrap().tag_name().map(str::to_string)).collect();
+        assert_eq!(tags, [None, Some("br".to_string()), None, Some("br".to_string())]);
+        assert_eq!(inner_text(&dom, p), "one\ntwo\n");
+
+        set_inner_text(&mut dom, p, "").unwrap();
+        assert!(dom.children(p).is_empty());
+    }
+}
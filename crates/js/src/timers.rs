@@ -0,0 +1,138 @@
+//! Timers
+//!
+//! Provides setTimeout, setInterval, requestAnimationFrame and their clear
+//! functions. Callbacks wait in a queue until the embedder runs the ones
+//! that are due, animation frames once per frame it renders.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use rquickjs::{Ctx, Function, Result};
+
+/// Time on the clock timers are due by, since the runtime was created
+pub type TimerClock = Rc<Cell<Duration>>;
+
+/// Register the timer functions in the global scope, scheduling against
+/// `clock`
+pub fn register_timers(ctx: &Ctx<'_>, clock: TimerClock) -> Result<()> {
+    let globals = ctx.globals();
+
+    globals.set(
+        "__timerNow",
+        Function::new(ctx.clone(), move || -> f64 { clock.get().as_secs_f64() * 1000.0 })?,
+    )?;
+
+    let timer_code = r#"
+        (function() {
+            // Pending timers by ID
+            var timers = {};
+            var nextId = 1;
+
+            function addTimer(callback, delay, args, repeat) {
+                if (typeof callback !== 'function') return 0;
+                delay = Math.max(0, Number(delay) || 0);
+                var id = nextId++;
+                timers[id] = {
+                    id: id,
+                    callback: callback,
+                    args: args,
+                    delay: delay,
+                    due: __timerNow() + delay,
+                    repeat: repeat
+                };
+                return id;
+            }
+
+            function clearTimer(id) {
+                delete timers[id];
+            }
+
+            globalThis.setTimeout = function(callback, delay) {
+                return addTimer(callback, delay, Array.prototype.slice.call(arguments, 2), false);
+            };
+
+            globalThis.setInterval = function(callback, delay) {
+                return addTimer(callback, delay, Array.prototype.slice.call(arguments, 2), true);
+            };
+
+            globalThis.clearTimeout = clearTimer;
+            globalThis.clearInterval = clearTimer;
+
+            // Run the timers due by `now` in the order they are due, returns
+            // how many ran. Timers added while running wait for the next run.
+            globalThis.__runDueTimers = function(now) {
+                var due = [];
+                for (var id in timers) {
+                    if (timers[id].due <= now) due.push(timers[id]);
+                }
+                due.sort(function(a, b) { return a.due - b.due || a.id - b.id; });
+
+                var ran = 0;
+                for (var i = 0; i < due.length; i++) {
+                    var timer = due[i];
+                    // Cleared by a timer that ran before it
+                    if (timers[timer.id] !== timer) continue;
+                    if (timer.repeat) {
+                        timer.due = now + timer.delay;
+                    } else {
+                        delete timers[timer.id];
+                    }
+                    try {
+                        timer.callback.apply(globalThis, timer.args);
+                    } catch (e) {
+                        __reportException(e);
+                    }
+                    ran++;
+                }
+                return ran;
+            };
+
+            // Animation frame callbacks in the order they were requested,
+            // and by ID until they run or are canceled
+            var frames = [];
+            var framesById = {};
+            var nextFrameId = 1;
+
+            globalThis.requestAnimationFrame = function(callback) {
+                if (typeof callback !== 'function') {
+                    throw new TypeError('requestAnimationFrame: argument is not a function');
+                }
+                var frame = { id: nextFrameId++, callback: callback, canceled: false };
+                frames.push(frame);
+                framesById[frame.id] = frame;
+                return frame.id;
+            };
+
+            globalThis.cancelAnimationFrame = function(id) {
+                var frame = framesById[id];
+                if (!frame) return;
+                frame.canceled = true;
+                delete framesById[id];
+            };
+
+            // Run the callbacks requested before this frame with its
+            // timestamp, returns how many ran. Callbacks they request wait
+            // for the next frame.
+            globalThis.__runAnimationFrames = function(timestamp) {
+                var due = frames;
+                frames = [];
+                var ran = 0;
+                for (var i = 0; i < due.length; i++) {
+                    var frame = due[i];
+                    if (frame.canceled) continue;
+                    delete framesById[frame.id];
+                    try {
+                        frame.callback.call(globalThis, timestamp);
+                    } catch (e) {
+                        __reportException(e);
+                    }
+                    ran++;
+                }
+                return ran;
+            };
+        })();
+    "#;
+
+    ctx.eval::<(), _>(timer_code)
+}
@@ -0,0 +1,137 @@
+//! Window
+//!
+//! Provides `window` with the viewport size, devicePixelRatio, event
+//! listeners and getComputedStyle(). Computed styles are those of the
+//! last style pass the embedder handed over.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gugalanna_dom::NodeId;
+use gugalanna_style::StyleTree;
+use rquickjs::{Ctx, Function, Result};
+
+/// Width and height of the viewport in CSS pixels
+pub type Viewport = Rc<Cell<(f32, f32)>>;
+
+/// Styles of the last style pass, None before the first
+pub type ComputedStyles = Rc<RefCell<Option<Rc<StyleTree>>>>;
+
+/// Register `window` and its properties in the global scope
+pub fn register_window(ctx: &Ctx<'_>, viewport: Viewport, styles: ComputedStyles) -> Result<()> {
+    let globals = ctx.globals();
+
+    let size = viewport.clone();
+    globals.set("__viewportWidth", Function::new(ctx.clone(), move || -> f64 { size.get().0 as f64 })?)?;
+    let size = viewport;
+    globals.set("__viewportHeight", Function::new(ctx.clone(), move || -> f64 { size.get().1 as f64 })?)?;
+
+    globals.set(
+        "__computedStyle",
+        Function::new(ctx.clone(), move |node_id: i32, property: String| -> String {
+            let styles = styles.borrow();
+            styles
+                .as_ref()
+                .and_then(|tree| tree.get_style(NodeId::new(node_id as u32)))
+                .and_then(|style| style.property_value(&property))
+                .unwrap_or_default()
+        })?,
+    )?;
+
+    let window_code = r#"
+        (function() {
+            // CSS property of a camelCase name, vendor prefixes start
+            // lowercase (webkitTransform) or uppercase (WebkitTransform)
+            globalThis.__cssProperty = function(name) {
+                if (name === 'cssFloat') return 'float';
+                if (name.indexOf('-') !== -1) return name.toLowerCase();
+                return name
+                    .replace(/^(webkit|moz|ms)(?=[A-Z])/, '-$1')
+                    .replace(/[A-Z]/g, function(c) { return '-' + c.toLowerCase(); });
+            };
+
+            globalThis.window = globalThis;
+            globalThis.self = globalThis;
+            globalThis.devicePixelRatio = 1.0;
+
+            Object.defineProperty(globalThis, 'innerWidth', { get: function() { return __viewportWidth(); } });
+            Object.defineProperty(globalThis, 'innerHeight', { get: function() { return __viewportHeight(); } });
+
+            // Listeners of events on the window, by type
+            var listeners = {};
+
+            globalThis.addEventListener = function(type, listener) {
+                if (typeof listener !== 'function') return;
+                listeners[type] = listeners[type] || [];
+                if (listeners[type].indexOf(listener) === -1) listeners[type].push(listener);
+            };
+
+            globalThis.removeEventListener = function(type, listener) {
+                var list = listeners[type];
+                if (!list) return;
+                var index = list.indexOf(listener);
+                if (index !== -1) list.splice(index, 1);
+            };
+
+            // Called from Rust, as when the window was resized, with the
+            // properties of the event beyond its type. Returns false when a
+            // listener canceled it with preventDefault().
+            globalThis.__dispatchWindowEvent = function(type, properties) {
+                var event = Object.assign({}, properties, {
+                    type: type,
+                    target: globalThis,
+                    currentTarget: globalThis,
+                    cancelable: true,
+                    defaultPrevented: false,
+                    preventDefault: function() { this.defaultPrevented = true; }
+                });
+                var handlers = (listeners[type] || []).slice();
+                // Handler properties, as window.onload. onerror takes the
+                // properties as arguments, returning true cancels.
+                var handler = globalThis['on' + type];
+                if (typeof handler === 'function' && type === 'error') {
+                    handlers.push(function(event) {
+                        var args = [event.message, event.filename, event.lineno, event.colno, event.error];
+                        if (handler.apply(globalThis, args) === true) event.preventDefault();
+                    });
+                } else if (typeof handler === 'function') {
+                    handlers.push(handler);
+                }
+                handlers.forEach(function(listener) {
+                    try {
+                        listener.call(globalThis, event);
+                    } catch (e) {
+                        __reportException(e);
+                    }
+                });
+                return !event.defaultPrevented;
+            };
+
+            // A read-only view of the element's computed style
+            globalThis.getComputedStyle = function(element) {
+                if (!element || element.__nodeId === undefined) {
+                    throw new TypeError('getComputedStyle: argument is not an Element');
+                }
+                var nodeId = element.__nodeId;
+                var methods = {
+                    getPropertyValue: function(property) {
+                        return __computedStyle(nodeId, String(property));
+                    }
+                };
+                return new Proxy(methods, {
+                    get: function(target, prop) {
+                        if (typeof prop !== 'string' || prop in target) return target[prop];
+                        return __computedStyle(nodeId, __cssProperty(prop));
+                    },
+                    set: function() {
+                        var error = new Error('The computed style is read-only.');
+                        error.name = 'NoModificationAllowedError';
+                        throw error;
+                    }
+                });
+            };
+        })();
+    "#;
+
+    ctx.eval::<(), _>(window_code)
+}
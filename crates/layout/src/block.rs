@@ -2,17 +2,31 @@
 //!
 //! Implements the CSS block formatting context layout algorithm.
 
-use crate::boxtree::LayoutBox;
-use crate::flex::layout_flex;
-use crate::inline::layout_inline_children;
-use crate::ContainingBlock;
-use gugalanna_style::{Display, Position};
+use crate::boxtree::{BoxType, LayoutBox};
+use crate::flex::layout_flex_container;
+use crate::float::FloatContext;
+use crate::grid::{is_grid, layout_grid};
+use crate::inline::{layout_inline_box, layout_inline_children, layout_inline_children_with_floats};
+use crate::intrinsic::intrinsic_widths;
+use crate::overflow::record_scroll_overflow;
+use crate::positioned::{
+    apply_relative_offset, layout_absolute_descendants, place_absolute_descendants, place_fixed_descendants,
+};
+use crate::relayout::{cache_layout, mark_laid_out, reuse_layout};
+use crate::table::{is_table, layout_table};
+use crate::{ContainingBlock, Rect};
+use gugalanna_style::{Direction, Display, Position};
 
 /// Layout a block-level element and its descendants
+///
+/// The element establishes a new block formatting context, so its height
+/// grows to contain any floats placed inside it.
 pub fn layout_block(
     layout_box: &mut LayoutBox,
     containing_block: ContainingBlock,
 ) {
+    mark_laid_out(layout_box);
+
     // Calculate width first (depends on containing block)
     calculate_block_width(layout_box, containing_block);
 
@@ -20,10 +34,151 @@ pub fn layout_block(
     calculate_block_position(layout_box, containing_block);
 
     // Layout children and calculate height
-    layout_block_children(layout_box);
+    let height = layout_box.specified_height(containing_block);
+    let mut floats = FloatContext::new();
+    let content_height = layout_block_children(layout_box, &mut floats, height);
 
     // Height calculation (may be auto)
-    calculate_block_height(layout_box);
+    calculate_block_height(layout_box, content_height.max(floats.bottom()), height);
+
+    if layout_box.is_positioned() {
+        layout_absolute_descendants(layout_box);
+    }
+}
+
+/// Layout the root box of a document in the viewport
+///
+/// Absolutely positioned boxes without a positioned ancestor and fixed
+/// boxes are placed against the viewport, the initial containing block.
+pub fn layout_document(root: &mut LayoutBox, viewport: ContainingBlock) {
+    layout_block(root, viewport);
+
+    apply_relative_offset(root, viewport);
+
+    let content = root.dimensions.content;
+    let initial = Rect::new(-content.x, -content.y, viewport.width, viewport.height);
+    if !root.is_positioned() {
+        place_absolute_descendants(root, initial);
+    }
+    place_fixed_descendants(root, initial);
+
+    record_scroll_overflow(root);
+}
+
+/// Layout a block whose content width was decided by its container
+///
+/// Used for table cells, which get the width of the columns they span.
+/// Their percentage heights behave as auto.
+pub(crate) fn layout_block_with_width(layout_box: &mut LayoutBox, content_width: f32) {
+    mark_laid_out(layout_box);
+    layout_box.dimensions.content.width = content_width;
+    let containing_block = ContainingBlock::with_auto_height(content_width);
+    calculate_block_position(layout_box, containing_block);
+
+    let height = layout_box.specified_height(containing_block);
+    let mut floats = FloatContext::new();
+    let content_height = layout_block_children(layout_box, &mut floats, height);
+    calculate_block_height(layout_box, content_height.max(floats.bottom()), height);
+
+    if layout_box.is_positioned() {
+        layout_absolute_descendants(layout_box);
+    }
+}
+
+/// Layout a block that participates in its parent's block formatting context
+///
+/// `y` is the top of the block's margin box within the parent's content area.
+fn layout_block_in_flow(
+    layout_box: &mut LayoutBox,
+    containing_block: ContainingBlock,
+    floats: &mut FloatContext,
+    y: f32,
+) {
+    if establishes_formatting_context(layout_box) {
+        layout_block(layout_box, containing_block);
+        layout_box.dimensions.content.y += y;
+        cache_layout(layout_box, containing_block, y, !floats.is_empty());
+        return;
+    }
+
+    mark_laid_out(layout_box);
+    calculate_block_width(layout_box, containing_block);
+    calculate_block_position(layout_box, containing_block);
+    layout_box.dimensions.content.y += y;
+
+    // Floats are shared with the parent, seen from this block's content box
+    let height = layout_box.specified_height(containing_block);
+    let (dx, dy) = (layout_box.dimensions.content.x, layout_box.dimensions.content.y);
+    floats.translate(dx, dy);
+    let content_height = layout_block_children(layout_box, floats, height);
+    floats.translate(-dx, -dy);
+
+    calculate_block_height(layout_box, content_height, height);
+
+    if layout_box.is_positioned() {
+        layout_absolute_descendants(layout_box);
+    }
+    cache_layout(layout_box, containing_block, y, !floats.is_empty());
+}
+
+/// Check if a block box establishes a new block formatting context
+fn establishes_formatting_context(layout_box: &LayoutBox) -> bool {
+    match layout_box.style() {
+        Some(style) => {
+            style.display != Display::Block
+                || layout_box.is_floated()
+                || matches!(style.position, Position::Absolute | Position::Fixed)
+                || layout_box.clips_overflow()
+        }
+        None => false,
+    }
+}
+
+/// Layout a floated box and place it against the current float edges
+///
+/// `y` is the earliest vertical position (within the parent's content area)
+/// the float may be placed at.
+pub(crate) fn layout_float(
+    layout_box: &mut LayoutBox,
+    containing_block: ContainingBlock,
+    floats: &mut FloatContext,
+    y: f32,
+) {
+    let side = match layout_box.style() {
+        Some(style) => style.float,
+        None => return,
+    };
+
+    if let BoxType::Block(_, style) = layout_box.box_type {
+        // Auto width floats shrink to fit their content
+        let containing_block = match style.width {
+            Some(_) => containing_block,
+            None => {
+                layout_box.apply_style_edges(containing_block.width);
+                let d = &layout_box.dimensions;
+                let edges = d.margin.horizontal() + d.padding.horizontal() + d.border.horizontal();
+                let available = (containing_block.width - edges).max(0.0);
+                let width = intrinsic_widths(layout_box).shrink_to_fit(available);
+                ContainingBlock { width: width + edges, ..containing_block }
+            }
+        };
+        layout_block(layout_box, containing_block);
+    } else {
+        // Replaced elements keep their intrinsic size
+        layout_inline_box(layout_box, containing_block.width);
+    }
+
+    let d = &mut layout_box.dimensions;
+    let (x, float_y) = floats.place(
+        side,
+        d.margin_box_width(),
+        d.margin_box_height(),
+        y,
+        containing_block.width,
+    );
+    d.content.x = x + d.margin.left + d.border.left + d.padding.left;
+    d.content.y = float_y + d.margin.top + d.border.top + d.padding.top;
+    apply_relative_offset(layout_box, containing_block);
 }
 
 /// Calculate the width of a block element
@@ -38,7 +193,10 @@ fn calculate_block_width(layout_box: &mut LayoutBox, containing_block: Containin
     };
 
     // Copy edge sizes from style
-    layout_box.apply_style_edges();
+    layout_box.apply_style_edges(containing_block.width);
+
+    // Floats are sized by their margin box and never absorb the underflow
+    let is_floated = layout_box.is_floated();
 
     // Get the specified width or auto
     let width = style.width;
@@ -61,15 +219,32 @@ fn calculate_block_width(layout_box: &mut LayoutBox, containing_block: Containin
 
     d.content.width = content_width;
 
-    // Handle auto margins for centering
+    // Auto margins take up the space a specified width leaves, floats
+    // keep them at zero
+    if width.is_none() || is_floated {
+        return;
+    }
     let underflow = containing_block.width - content_width - total_horizontal;
-    if underflow > 0.0 && width.is_some() {
-        // Check if both margins are auto (for centering)
-        if style.margin_left == 0.0 && style.margin_right == 0.0 {
-            // Could implement auto margin centering here
-            // For now, just add underflow to right margin
-            d.margin.right += underflow;
+
+    // Auto margins count as zero when the box is already too wide
+    let (auto_left, auto_right) = if underflow < 0.0 {
+        (false, false)
+    } else {
+        (style.margin_left.is_none(), style.margin_right.is_none())
+    };
+    match (auto_left, auto_right) {
+        (true, true) => {
+            d.margin.left = underflow / 2.0;
+            d.margin.right = underflow / 2.0;
         }
+        (true, false) => d.margin.left = underflow,
+        (false, true) => d.margin.right = underflow,
+        // Over-constrained: the end margin gives way. Direction is
+        // inherited, so the box's own stands in for its containing block's
+        (false, false) => match style.direction {
+            Direction::Ltr => d.margin.right += underflow,
+            Direction::Rtl => d.margin.left += underflow,
+        },
     }
 }
 
@@ -84,71 +259,81 @@ fn calculate_block_position(layout_box: &mut LayoutBox, _containing_block: Conta
     // Y position will be set by parent during child layout
     // For now, just account for top edges
     d.content.y = d.margin.top + d.border.top + d.padding.top;
+}
 
-    // Apply relative positioning offset
-    if let Some(style) = layout_box.style() {
-        if style.position == Position::Relative {
-            // Apply left/right offset (left takes precedence)
-            if let Some(left) = style.left {
-                layout_box.dimensions.content.x += left;
-            } else if let Some(right) = style.right {
-                layout_box.dimensions.content.x -= right;
-            }
+/// Layout all children of a block element, returns the auto content height
+///
+/// `height` is the block's own content height when it is definite, the
+/// containing block height its children resolve percentages against.
+fn layout_block_children(layout_box: &mut LayoutBox, floats: &mut FloatContext, height: Option<f32>) -> f32 {
+    // Tables lay out their rows and cells on a grid
+    if is_table(layout_box) {
+        return layout_table(layout_box);
+    }
 
-            // Apply top/bottom offset (top takes precedence)
-            if let Some(top) = style.top {
-                layout_box.dimensions.content.y += top;
-            } else if let Some(bottom) = style.bottom {
-                layout_box.dimensions.content.y -= bottom;
-            }
-        }
+    if is_grid(layout_box) {
+        return layout_grid(layout_box, height);
     }
-}
 
-/// Layout all children of a block element
-fn layout_block_children(layout_box: &mut LayoutBox) {
     // Check if this is a flex container
     if let Some(style) = layout_box.style() {
         if style.display == Display::Flex {
             // Use flex layout
-            let containing = ContainingBlock::new(
-                layout_box.dimensions.content.width,
-                layout_box.style().and_then(|s| s.height).unwrap_or(0.0),
-            );
-            layout_flex(layout_box, containing);
-            return;
+            let containing = ContainingBlock::with_height(layout_box.dimensions.content.width, height);
+            layout_flex_container(layout_box, containing, height);
+            return layout_box.dimensions.content.height;
         }
     }
 
     // Separate block and inline children
-    let has_block_children = layout_box.children.iter().any(|c| c.is_block());
+    let has_block_children = layout_box.children.iter().any(|c| c.is_in_flow_block());
 
     if has_block_children {
         // Block formatting context
-        layout_block_children_as_blocks(layout_box);
+        layout_block_children_as_blocks(layout_box, floats, height)
     } else {
         // All inline - create inline formatting context
-        layout_inline_children(layout_box);
+        layout_inline_children_with_floats(layout_box, floats, height)
     }
 }
 
-/// Layout children in block formatting context
-fn layout_block_children_as_blocks(layout_box: &mut LayoutBox) {
-    let content_width = layout_box.dimensions.content.width;
-    let containing = ContainingBlock::new(content_width, 0.0);
+/// Layout children in block formatting context, returns the content height
+fn layout_block_children_as_blocks(layout_box: &mut LayoutBox, floats: &mut FloatContext, height: Option<f32>) -> f32 {
+    let containing = ContainingBlock::with_height(layout_box.dimensions.content.width, height);
 
     let mut cursor_y = 0.0;
 
     for child in &mut layout_box.children {
+        if child.is_floated() {
+            // Out of flow - positioned against the float edges
+            layout_float(child, containing, floats, cursor_y);
+            continue;
+        }
+
+        if child.is_absolutely_positioned() {
+            // Out of flow - placed once the containing block is laid out
+            child.dimensions.content = Rect::new(0.0, cursor_y, 0.0, 0.0);
+            continue;
+        }
+
+        // Clearance moves the block below the relevant floats
+        if let Some(clear_y) = child.style().and_then(|s| floats.clearance(s.clear)) {
+            cursor_y = f32::max(cursor_y, clear_y);
+        }
+
         if child.is_block() {
-            // Layout this block child
-            layout_block(child, containing);
+            // Clean blocks keep their layout while no floats can reach them
+            if floats.is_empty() && reuse_layout(child, containing, cursor_y) {
+                cursor_y += child.dimensions.margin_box_height();
+                continue;
+            }
 
-            // Position it vertically
-            child.dimensions.content.y += cursor_y;
+            // Layout this block child at the current vertical position
+            layout_block_in_flow(child, containing, floats, cursor_y);
 
-            // Move cursor down
+            // Move cursor down, relative offsets leave the space reserved
             cursor_y += child.dimensions.margin_box_height();
+            apply_relative_offset(child, containing);
         } else {
             // Inline content in block context - should be wrapped in anonymous block
             // Just lay it out as inline
@@ -157,26 +342,16 @@ fn layout_block_children_as_blocks(layout_box: &mut LayoutBox) {
             cursor_y += child.dimensions.margin_box_height();
         }
     }
+
+    cursor_y
 }
 
 /// Calculate the height of a block element
-fn calculate_block_height(layout_box: &mut LayoutBox) {
-    // Check for explicit height
-    if let Some(style) = layout_box.style() {
-        if let Some(h) = style.height {
-            layout_box.dimensions.content.height = h;
-            return;
-        }
-    }
-
-    // Auto height - sum of children's margin boxes
-    let children_height: f32 = layout_box
-        .children
-        .iter()
-        .map(|c| c.dimensions.margin_box_height())
-        .sum();
-
-    layout_box.dimensions.content.height = children_height;
+///
+/// `height` is the resolved `height` property, `None` when it is auto.
+fn calculate_block_height(layout_box: &mut LayoutBox, content_height: f32, height: Option<f32>) {
+    // Auto height - extent of the in-flow children
+    layout_box.dimensions.content.height = height.unwrap_or(content_height);
 }
 
 #[cfg(test)]
@@ -273,4 +448,260 @@ mod tests {
         // Content width reduced by margins
         assert_eq!(layout.dimensions.content.width, 760.0);
     }
+
+    #[test]
+    fn test_nested_percentage_heights() {
+        let layout = setup_and_layout(
+            "<div><div class='a'><div class='b'>x</div></div></div>",
+            "div { display: block; height: 50%; }",
+            800.0,
+        );
+
+        // Each level resolves against its parent's definite height
+        assert_eq!(layout.dimensions.content.height, 300.0);
+        assert_eq!(layout.children[0].dimensions.content.height, 150.0);
+        assert_eq!(layout.children[0].children[0].dimensions.content.height, 75.0);
+    }
+
+    #[test]
+    fn test_percentage_height_of_auto_height_parent() {
+        let layout = setup_and_layout(
+            "<div><div class='outer'><div class='inner'>x</div></div></div>",
+            "div { display: block; line-height: 20px; } .outer { height: auto; } .inner { height: 50%; }",
+            800.0,
+        );
+
+        // The parent's height depends on its content, so 50% behaves as auto
+        let inner = &layout.children[0].children[0];
+        assert_eq!(inner.dimensions.content.height, 20.0);
+        assert_eq!(layout.children[0].dimensions.content.height, 20.0);
+    }
+
+    #[test]
+    fn test_percentage_vertical_padding_uses_width() {
+        let layout = setup_and_layout(
+            "<div></div>",
+            "div { display: block; width: 200px; padding-top: 10%; margin-bottom: 5%; }",
+            400.0,
+        );
+
+        // Vertical percentages resolve against the containing block width
+        assert_eq!(layout.dimensions.padding.top, 40.0);
+        assert_eq!(layout.dimensions.margin.bottom, 20.0);
+    }
+
+    #[test]
+    fn test_auto_margins_center_block() {
+        let layout = setup_and_layout(
+            "<div><div class='c'></div></div>",
+            "div { display: block; } .c { width: 200px; margin: 0 auto; }",
+            800.0,
+        );
+
+        let centered = &layout.children[0].dimensions;
+        assert_eq!(centered.content.x, 300.0);
+        assert_eq!(centered.margin.left, 300.0);
+        assert_eq!(centered.margin.right, 300.0);
+    }
+
+    #[test]
+    fn test_auto_left_margin_pushes_block_right() {
+        let layout = setup_and_layout(
+            "<div><div class='r'></div></div>",
+            "div { display: block; } .r { width: 200px; margin-left: auto; margin-right: 50px; }",
+            800.0,
+        );
+
+        let right = &layout.children[0].dimensions;
+        assert_eq!(right.content.x, 550.0);
+        assert_eq!(right.margin.right, 50.0);
+    }
+
+    #[test]
+    fn test_over_constrained_margins_adjust_right() {
+        let layout = setup_and_layout(
+            "<div><div class='a'></div><div class='w'></div></div>",
+            "div { display: block; } .a { width: 200px; margin-left: 100px; margin-right: 100px; } \
+             .w { width: 900px; margin-left: auto; margin-right: auto; }",
+            800.0,
+        );
+
+        // Margin-right absorbs the leftover space
+        let narrow = &layout.children[0].dimensions;
+        assert_eq!(narrow.content.x, 100.0);
+        assert_eq!(narrow.margin.right, 500.0);
+
+        // Too wide to center: auto margins are zero and margin-right goes negative
+        let wide = &layout.children[1].dimensions;
+        assert_eq!(wide.content.x, 0.0);
+        assert_eq!(wide.margin.right, -100.0);
+    }
+
+    #[test]
+    fn test_rtl_over_constrained_margins_adjust_left() {
+        let layout = setup_and_layout(
+            "<div dir='rtl'><div class='a'></div><div class='w'></div></div>",
+            "div { display: block; } .a { width: 200px; margin-left: 100px; margin-right: 100px; } \
+             .w { width: 900px; }",
+            800.0,
+        );
+
+        // Margin-left absorbs the leftover space, the box hugs the right edge
+        let narrow = &layout.children[0].dimensions;
+        assert_eq!(narrow.margin.left, 500.0);
+        assert_eq!(narrow.content.x, 500.0);
+
+        // Too wide: it overflows to the left
+        let wide = &layout.children[1].dimensions;
+        assert_eq!(wide.content.x, -100.0);
+    }
+
+    #[test]
+    fn test_logical_float_in_rtl() {
+        let layout = setup_and_layout(
+            "<div dir='rtl'><div class='s'></div><div class='e'></div></div>",
+            "div { display: block; } \
+             .s { float: inline-start; width: 100px; height: 50px; } \
+             .e { float: inline-end; width: 50px; height: 50px; }",
+            300.0,
+        );
+
+        // The start side is the right one
+        assert_eq!(layout.children[0].dimensions.content.x, 200.0);
+        assert_eq!(layout.children[1].dimensions.content.x, 0.0);
+    }
+
+    #[test]
+    fn test_hr_is_a_rule_across_the_content_width() {
+        let layout = setup_and_layout("<div><hr></div>", "div { display: block; }", 800.0);
+
+        // A 1px border on each side of an empty content box
+        let rule = &layout.children[0].dimensions;
+        assert_eq!(rule.content.height, 0.0);
+        assert_eq!(rule.border_box().height, 2.0);
+        assert_eq!(rule.border_box().width, 800.0);
+        assert_eq!(rule.margin.top, 8.0);
+
+        let restyled = setup_and_layout(
+            "<div><hr></div>",
+            "div { display: block; } hr { border: none; height: 4px; }",
+            800.0,
+        );
+        let rule = &restyled.children[0].dimensions;
+        assert_eq!(rule.border_box().height, 4.0);
+    }
+
+    const FLOAT_CSS: &str = "div { display: block; font-size: 10px; line-height: 20px; } \
+        .l { float: left; width: 100px; height: 50px; } \
+        .r { float: right; width: 100px; height: 30px; }";
+
+    #[test]
+    fn test_inline_content_wraps_around_left_float() {
+        // Ten 60px wide words: three fit beside the float on each line
+        let spans = "<span>aaaaaaaaaa </span>".repeat(10);
+        let layout = setup_and_layout(
+            &format!("<div><div class='l'></div>{}</div>", spans),
+            FLOAT_CSS,
+            300.0,
+        );
+
+        let float = &layout.children[0];
+        assert_eq!(float.dimensions.content.x, 0.0);
+        assert_eq!(float.dimensions.content.y, 0.0);
+
+        let spans: Vec<_> = layout.children[1..].iter().collect();
+        assert_eq!(spans.len(), 10);
+
+        // Lines beside the float start at its right edge
+        assert_eq!(spans[0].dimensions.content.x, 100.0);
+        assert_eq!(spans[2].dimensions.content.x, 232.0);
+        assert_eq!(spans[3].dimensions.content.x, 100.0);
+        assert_eq!(spans[3].dimensions.content.y, 20.0);
+        assert_eq!(spans[6].dimensions.content.y, 40.0);
+
+        // Once past the float the line gets the full width back
+        assert_eq!(spans[9].dimensions.content.x, 0.0);
+        assert_eq!(spans[9].dimensions.content.y, 60.0);
+    }
+
+    #[test]
+    fn test_auto_width_float_shrinks_to_fit() {
+        let layout = setup_and_layout(
+            "<div><div class='f'>aaaa aa</div><div class='f'>aaaaaaaaaa aaaaaaaaaa aaaaaaaaaa</div></div>",
+            "div { display: block; font-size: 10px; line-height: 20px; } \
+             .f { float: left; padding-left: 5px; padding-right: 5px; }",
+            200.0,
+        );
+
+        // As wide as its unwrapped text
+        assert_eq!(layout.children[0].dimensions.content.width, 42.0);
+
+        // Too wide to fit beside the first float: takes the available width
+        // and wraps, below the first float
+        let second = &layout.children[1];
+        assert_eq!(second.dimensions.content.width, 190.0);
+        assert_eq!(second.dimensions.content.height, 40.0);
+    }
+
+    #[test]
+    fn test_stacked_floats() {
+        let layout = setup_and_layout(
+            "<div><div class='l'></div><div class='l'></div><div class='r'></div><div class='l'></div></div>",
+            FLOAT_CSS,
+            300.0,
+        );
+
+        let positions: Vec<(f32, f32)> = layout
+            .children
+            .iter()
+            .map(|c| (c.dimensions.content.x, c.dimensions.content.y))
+            .collect();
+
+        // Two left floats side by side, the right float against the right edge
+        assert_eq!(positions[0], (0.0, 0.0));
+        assert_eq!(positions[1], (100.0, 0.0));
+        assert_eq!(positions[2], (200.0, 0.0));
+        // No room left: the next float drops below the shortest float
+        assert_eq!(positions[3], (200.0, 30.0));
+    }
+
+    #[test]
+    fn test_clear_moves_block_below_floats() {
+        let layout = setup_and_layout(
+            "<div><div class='l'></div><div class='r'></div><p style='clear: left'>a</p><p style='clear: right'>b</p></div>",
+            &format!("{} p {{ display: block; }}", FLOAT_CSS),
+            300.0,
+        );
+
+        // The floats are wrapped in an anonymous block ahead of the paragraphs
+        assert!(matches!(layout.children[0].box_type, BoxType::AnonymousBlock));
+
+        let cleared_left = &layout.children[1];
+        assert_eq!(cleared_left.dimensions.margin_box().y, 50.0);
+
+        // Already below both floats, clear has no further effect
+        let cleared_right = &layout.children[2];
+        assert_eq!(cleared_right.dimensions.margin_box().y, 90.0);
+    }
+
+    #[test]
+    fn test_formatting_context_contains_floats() {
+        let visible = setup_and_layout(
+            "<div><div class='l'></div></div>",
+            FLOAT_CSS,
+            300.0,
+        );
+        // The entry block is a formatting context root
+        assert_eq!(visible.dimensions.content.height, 50.0);
+
+        let nested = setup_and_layout(
+            "<div><div class='c'><div class='l'></div></div><div class='c' style='overflow: hidden'><div class='l'></div></div></div>",
+            &format!("{} .c {{ display: block; }}", FLOAT_CSS),
+            300.0,
+        );
+        // A normal block lets its floats overhang...
+        assert_eq!(nested.children[0].dimensions.content.height, 0.0);
+        // ...while overflow: hidden grows to contain them
+        assert_eq!(nested.children[1].dimensions.content.height, 50.0);
+    }
 }
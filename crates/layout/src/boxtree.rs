@@ -2,10 +2,17 @@
 //!
 //! Builds a layout box tree from the style tree.
 
+use std::cell::Cell;
+use std::sync::Arc;
+
 use gugalanna_dom::{DomTree, NodeId};
-use gugalanna_style::{ComputedStyle, Display, StyleTree};
+use serde::{Deserialize, Serialize};
+use gugalanna_style::{ComputedStyle, Display, Float, LengthPercentage, Overflow, Position, StyleTree, WhiteSpace};
 
-use crate::{Dimensions, EdgeSizes};
+use crate::inline::InlineBox;
+use crate::intrinsic::IntrinsicWidths;
+use crate::table::{fix_table_structure, MAX_SPAN};
+use crate::{ContainingBlock, Dimensions, EdgeSizes};
 
 /// A layout box in the box tree
 #[derive(Debug)]
@@ -16,6 +23,36 @@ pub struct LayoutBox<'a> {
     pub box_type: BoxType<'a>,
     /// Child boxes
     pub children: Vec<LayoutBox<'a>>,
+    /// Line fragments of a text box, relative to its content box
+    pub fragments: Vec<InlineBox>,
+    /// Width and height of the scrollable overflow of a scroll container,
+    /// measured from its padding box origin
+    pub scroll_size: Option<(f32, f32)>,
+    /// Decoded pixels of the style's background image (None if not yet
+    /// loaded or failed)
+    pub background_pixels: Option<ImagePixels>,
+    /// Intrinsic widths of the content, measured on first use
+    pub(crate) intrinsic_widths: Cell<Option<IntrinsicWidths>>,
+    /// The box itself changed since it was last laid out
+    pub(crate) needs_layout: bool,
+    /// A descendant changed since the box was last laid out
+    pub(crate) child_needs_layout: bool,
+    /// Where the box was last laid out in its parent's block flow
+    pub(crate) layout_cache: Option<LayoutCache>,
+}
+
+/// Placement of a block laid out in its parent's block flow, kept so the
+/// box can be moved instead of laid out again while it is clean
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LayoutCache {
+    /// Containing block the box was laid out in
+    pub containing_block: ContainingBlock,
+    /// Top of the margin box within the parent's content area
+    pub y: f32,
+    /// Floats inside the box were left in the parent's float context
+    pub leaks_floats: bool,
+    /// Absolutely positioned boxes inside are placed by an ancestor
+    pub has_positioned: bool,
 }
 
 /// Type of form input element for layout purposes
@@ -35,6 +72,8 @@ pub enum InputType {
     Button,
     /// Hidden input (no visual representation)
     Hidden,
+    /// File chooser, a button and the name of the file chosen
+    File,
 }
 
 impl InputType {
@@ -47,6 +86,7 @@ impl InputType {
             "submit" => InputType::Submit,
             "button" => InputType::Button,
             "hidden" => InputType::Hidden,
+            "file" => InputType::File,
             _ => InputType::Text, // Default to text
         }
     }
@@ -72,28 +112,106 @@ pub struct ImageData {
     pub pixels: Option<ImagePixels>,
 }
 
+/// Two images are the same when they show the same source at the same
+/// size, their pixels are not compared
+impl PartialEq for ImageData {
+    fn eq(&self, other: &Self) -> bool {
+        self.src == other.src
+            && self.intrinsic_width == other.intrinsic_width
+            && self.intrinsic_height == other.intrinsic_height
+            && self.alt == other.alt
+    }
+}
+
+/// Data for a `<textarea>` element
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextAreaData {
+    /// Visible lines of text, from the rows attribute
+    pub rows: u32,
+    /// Visible characters per line, from the cols attribute
+    pub cols: u32,
+    /// Initial text, the element's text content
+    pub text: String,
+}
+
+/// Data for a `<select>` element
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectData {
+    /// Text of each option, in document order
+    pub options: Vec<String>,
+    /// Index of the selected option
+    pub selected: usize,
+}
+
 /// Decoded image pixel data
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ImagePixels {
     /// Image width in pixels
     pub width: u32,
     /// Image height in pixels
     pub height: u32,
-    /// RGBA pixel data, 4 bytes per pixel
-    pub data: Vec<u8>,
+    /// RGBA pixel data, 4 bytes per pixel, shared by the copies of the
+    /// image in display lists
+    #[serde(with = "hex_bytes")]
+    pub data: Arc<[u8]>,
+}
+
+/// Pixel data written as a string of hex digits, much shorter than a
+/// list of numbers
+mod hex_bytes {
+    use std::sync::Arc;
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &Arc<[u8]>, serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<[u8]>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if !hex.is_ascii() || hex.len() % 2 != 0 {
+            return Err(D::Error::custom("pixel data is not pairs of hex digits"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// Number of columns and rows a table cell spans
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellSpan {
+    pub columns: usize,
+    /// Rows spanned; 0 spans the rest of the table
+    pub rows: usize,
+}
+
+impl Default for CellSpan {
+    fn default() -> Self {
+        Self { columns: 1, rows: 1 }
+    }
 }
 
 /// Type of layout box
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum BoxType<'a> {
     /// Block-level box with associated style
     Block(NodeId, &'a ComputedStyle),
     /// Inline-level box with associated style
     Inline(NodeId, &'a ComputedStyle),
+    /// Atomic inline-level box that lays out its contents as a block
+    InlineBlock(NodeId, &'a ComputedStyle),
     /// Text content
     Text(NodeId, String, &'a ComputedStyle),
+    /// Table cell with the columns and rows it spans
+    TableCell(NodeId, &'a ComputedStyle, CellSpan),
     /// Anonymous block box (wraps inline content in block context)
     AnonymousBlock,
+    /// Anonymous table (wraps table rows and cells outside a table)
+    AnonymousTable,
     /// Anonymous inline box
     AnonymousInline,
     /// Form input element (replaced element with intrinsic size)
@@ -102,6 +220,12 @@ pub enum BoxType<'a> {
     Button(NodeId, String, &'a ComputedStyle),
     /// Image element (replaced element with intrinsic size)
     Image(NodeId, ImageData, &'a ComputedStyle),
+    /// Multi-line text field (`<textarea>`)
+    TextArea(NodeId, TextAreaData, &'a ComputedStyle),
+    /// Dropdown list (`<select>`)
+    Select(NodeId, SelectData, &'a ComputedStyle),
+    /// Forced line break (`<br>`)
+    LineBreak(NodeId, &'a ComputedStyle),
 }
 
 impl<'a> LayoutBox<'a> {
@@ -111,6 +235,13 @@ impl<'a> LayoutBox<'a> {
             dimensions: Dimensions::default(),
             box_type: BoxType::Block(node_id, style),
             children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
         }
     }
 
@@ -120,6 +251,29 @@ impl<'a> LayoutBox<'a> {
             dimensions: Dimensions::default(),
             box_type: BoxType::Inline(node_id, style),
             children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
+        }
+    }
+
+    /// Create a new inline-block box
+    pub fn new_inline_block(node_id: NodeId, style: &'a ComputedStyle) -> Self {
+        Self {
+            dimensions: Dimensions::default(),
+            box_type: BoxType::InlineBlock(node_id, style),
+            children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
         }
     }
 
@@ -129,6 +283,13 @@ impl<'a> LayoutBox<'a> {
             dimensions: Dimensions::default(),
             box_type: BoxType::Text(node_id, text, style),
             children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
         }
     }
 
@@ -138,6 +299,13 @@ impl<'a> LayoutBox<'a> {
             dimensions: Dimensions::default(),
             box_type: BoxType::Input(node_id, input_type, style),
             children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
         }
     }
 
@@ -147,6 +315,13 @@ impl<'a> LayoutBox<'a> {
             dimensions: Dimensions::default(),
             box_type: BoxType::Button(node_id, label, style),
             children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
         }
     }
 
@@ -156,6 +331,93 @@ impl<'a> LayoutBox<'a> {
             dimensions: Dimensions::default(),
             box_type: BoxType::Image(node_id, image_data, style),
             children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
+        }
+    }
+
+    /// Create a new textarea box
+    pub fn new_textarea(node_id: NodeId, data: TextAreaData, style: &'a ComputedStyle) -> Self {
+        Self {
+            dimensions: Dimensions::default(),
+            box_type: BoxType::TextArea(node_id, data, style),
+            children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
+        }
+    }
+
+    /// Create a new select box
+    pub fn new_select(node_id: NodeId, data: SelectData, style: &'a ComputedStyle) -> Self {
+        Self {
+            dimensions: Dimensions::default(),
+            box_type: BoxType::Select(node_id, data, style),
+            children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
+        }
+    }
+
+    /// Create a new line break box
+    pub fn new_line_break(node_id: NodeId, style: &'a ComputedStyle) -> Self {
+        Self {
+            dimensions: Dimensions::default(),
+            box_type: BoxType::LineBreak(node_id, style),
+            children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
+        }
+    }
+
+    /// Create a new table cell box
+    pub fn new_table_cell(node_id: NodeId, span: CellSpan, style: &'a ComputedStyle) -> Self {
+        Self {
+            dimensions: Dimensions::default(),
+            box_type: BoxType::TableCell(node_id, style, span),
+            children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
+        }
+    }
+
+    /// Create an anonymous table box
+    pub fn new_anonymous_table() -> Self {
+        Self {
+            dimensions: Dimensions::default(),
+            box_type: BoxType::AnonymousTable,
+            children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
         }
     }
 
@@ -165,6 +427,13 @@ impl<'a> LayoutBox<'a> {
             dimensions: Dimensions::default(),
             box_type: BoxType::AnonymousBlock,
             children: Vec::new(),
+            fragments: Vec::new(),
+            scroll_size: None,
+            background_pixels: None,
+            intrinsic_widths: Cell::new(None),
+            needs_layout: false,
+            child_needs_layout: false,
+            layout_cache: None,
         }
     }
 
@@ -173,11 +442,16 @@ impl<'a> LayoutBox<'a> {
         match &self.box_type {
             BoxType::Block(_, style) => Some(style),
             BoxType::Inline(_, style) => Some(style),
+            BoxType::InlineBlock(_, style) => Some(style),
             BoxType::Text(_, _, style) => Some(style),
             BoxType::Input(_, _, style) => Some(style),
             BoxType::Button(_, _, style) => Some(style),
+            BoxType::TextArea(_, _, style) => Some(style),
+            BoxType::Select(_, _, style) => Some(style),
             BoxType::Image(_, _, style) => Some(style),
-            BoxType::AnonymousBlock | BoxType::AnonymousInline => None,
+            BoxType::TableCell(_, style, _) => Some(style),
+            BoxType::LineBreak(_, style) => Some(style),
+            BoxType::AnonymousBlock | BoxType::AnonymousInline | BoxType::AnonymousTable => None,
         }
     }
 
@@ -186,32 +460,125 @@ impl<'a> LayoutBox<'a> {
         match &self.box_type {
             BoxType::Block(id, _) => Some(*id),
             BoxType::Inline(id, _) => Some(*id),
+            BoxType::InlineBlock(id, _) => Some(*id),
             BoxType::Text(id, _, _) => Some(*id),
             BoxType::Input(id, _, _) => Some(*id),
             BoxType::Button(id, _, _) => Some(*id),
+            BoxType::TextArea(id, _, _) => Some(*id),
+            BoxType::Select(id, _, _) => Some(*id),
             BoxType::Image(id, _, _) => Some(*id),
-            BoxType::AnonymousBlock | BoxType::AnonymousInline => None,
+            BoxType::TableCell(id, _, _) => Some(*id),
+            BoxType::LineBreak(id, _) => Some(*id),
+            BoxType::AnonymousBlock | BoxType::AnonymousInline | BoxType::AnonymousTable => None,
         }
     }
 
     /// Check if this is a block-level box
     pub fn is_block(&self) -> bool {
-        matches!(self.box_type, BoxType::Block(_, _) | BoxType::AnonymousBlock)
+        matches!(
+            self.box_type,
+            BoxType::Block(_, _) | BoxType::TableCell(_, _, _) | BoxType::AnonymousBlock | BoxType::AnonymousTable
+        )
+    }
+
+    /// Check if this box is floated (taken out of normal flow)
+    pub fn is_floated(&self) -> bool {
+        match &self.box_type {
+            // Text boxes share their parent's style, but never float themselves
+            BoxType::Text(_, _, _) => false,
+            // Absolute positioning wins over float
+            _ => self.style().is_some_and(|s| s.float != Float::None) && !self.is_absolutely_positioned(),
+        }
+    }
+
+    /// Check if this box is absolutely positioned (taken out of normal flow)
+    pub fn is_absolutely_positioned(&self) -> bool {
+        match &self.box_type {
+            BoxType::Text(_, _, _) => false,
+            _ => self
+                .style()
+                .is_some_and(|s| matches!(s.position, Position::Absolute | Position::Fixed)),
+        }
+    }
+
+    /// Check if this box is fixed to the viewport
+    pub fn is_fixed_positioned(&self) -> bool {
+        match &self.box_type {
+            BoxType::Text(_, _, _) => false,
+            _ => self.style().is_some_and(|s| s.position == Position::Fixed),
+        }
+    }
+
+    /// Check if this box is positioned, making it the containing block
+    /// of its absolutely positioned descendants
+    pub fn is_positioned(&self) -> bool {
+        match &self.box_type {
+            BoxType::Text(_, _, _) => false,
+            _ => self.style().is_some_and(|s| s.position != Position::Static),
+        }
+    }
+
+    /// Check if this box is out of normal flow
+    pub fn is_out_of_flow(&self) -> bool {
+        self.is_floated() || self.is_absolutely_positioned()
+    }
+
+    /// Check if this is a block-level box that participates in normal flow
+    pub fn is_in_flow_block(&self) -> bool {
+        self.is_block() && !self.is_out_of_flow()
+    }
+
+    /// Check if this box clips its descendants to its padding box
+    pub fn clips_overflow(&self) -> bool {
+        match &self.box_type {
+            BoxType::Text(_, _, _) => false,
+            _ => self.style().is_some_and(|s| {
+                s.overflow != Overflow::Visible
+                    || s.overflow_x != Overflow::Visible
+                    || s.overflow_y != Overflow::Visible
+            }),
+        }
+    }
+
+    /// Check if this box can be scrolled (overflow: auto or scroll)
+    pub fn is_scroll_container(&self) -> bool {
+        let scrolls = |o: Overflow| matches!(o, Overflow::Auto | Overflow::Scroll);
+        match &self.box_type {
+            BoxType::Text(_, _, _) => false,
+            _ => self
+                .style()
+                .is_some_and(|s| scrolls(s.overflow) || scrolls(s.overflow_x) || scrolls(s.overflow_y)),
+        }
+    }
+
+    /// Content height set by the `height` property, `None` when it is auto
+    ///
+    /// Percentage heights only apply when the containing block height is
+    /// definite, otherwise they behave as auto.
+    pub fn specified_height(&self, containing_block: ContainingBlock) -> Option<f32> {
+        if let BoxType::Text(_, _, _) = self.box_type {
+            return None;
+        }
+        match self.style()?.height? {
+            LengthPercentage::Px(px) => Some(px),
+            LengthPercentage::Percent(fraction) => containing_block.definite_height().map(|h| fraction * h),
+        }
     }
 
     /// Check if this is an inline-level box
     pub fn is_inline(&self) -> bool {
         matches!(
             self.box_type,
-            BoxType::Inline(_, _) | BoxType::Text(_, _, _) | BoxType::AnonymousInline
-                | BoxType::Input(_, _, _) | BoxType::Button(_, _, _) | BoxType::Image(_, _, _)
+            BoxType::Inline(_, _) | BoxType::InlineBlock(_, _) | BoxType::Text(_, _, _)
+                | BoxType::AnonymousInline | BoxType::Input(_, _, _) | BoxType::Button(_, _, _) | BoxType::Image(_, _, _)
+                | BoxType::TextArea(_, _, _) | BoxType::Select(_, _, _)
         )
     }
 
     /// Get or create an anonymous block for inline children
     fn get_inline_container(&mut self) -> &mut LayoutBox<'a> {
         // If the last child is an anonymous block, use it
-        let dominated_by_blocks = self.children.iter().any(|c| c.is_block());
+        let dominated_by_blocks = self.children.iter().any(|c| c.is_in_flow_block());
 
         if dominated_by_blocks {
             // Need anonymous blocks to wrap inline content
@@ -226,20 +593,36 @@ impl<'a> LayoutBox<'a> {
         }
     }
 
+    /// Add an in-flow block child, wrapping any preceding inline-level
+    /// children in an anonymous block so block and inline content never mix
+    fn push_block_child(&mut self, child: LayoutBox<'a>) {
+        let has_blocks = self.children.iter().any(|c| c.is_in_flow_block());
+        if !has_blocks && !self.children.is_empty() {
+            let mut wrapper = LayoutBox::new_anonymous_block();
+            wrapper.children = std::mem::take(&mut self.children);
+            self.children.push(wrapper);
+        }
+        self.children.push(child);
+    }
+
     /// Copy edge sizes from computed style
-    pub fn apply_style_edges(&mut self) {
+    ///
+    /// Percentage margins and padding, vertical ones included, resolve
+    /// against the width of the containing block. Auto margins start out
+    /// as zero.
+    pub fn apply_style_edges(&mut self, containing_width: f32) {
         if let Some(style) = self.style() {
             self.dimensions.margin = EdgeSizes {
-                top: style.margin_top,
-                right: style.margin_right,
-                bottom: style.margin_bottom,
-                left: style.margin_left,
+                top: style.margin_top.map_or(0.0, |m| m.resolve(containing_width)),
+                right: style.margin_right.map_or(0.0, |m| m.resolve(containing_width)),
+                bottom: style.margin_bottom.map_or(0.0, |m| m.resolve(containing_width)),
+                left: style.margin_left.map_or(0.0, |m| m.resolve(containing_width)),
             };
             self.dimensions.padding = EdgeSizes {
-                top: style.padding_top,
-                right: style.padding_right,
-                bottom: style.padding_bottom,
-                left: style.padding_left,
+                top: style.padding_top.resolve(containing_width),
+                right: style.padding_right.resolve(containing_width),
+                bottom: style.padding_bottom.resolve(containing_width),
+                left: style.padding_left.resolve(containing_width),
             };
             self.dimensions.border = EdgeSizes {
                 top: style.border_top_width,
@@ -251,6 +634,32 @@ impl<'a> LayoutBox<'a> {
     }
 }
 
+/// Apply the white-space property to the text of a text node
+fn process_whitespace(text: &str, white_space: WhiteSpace) -> String {
+    match white_space {
+        // Collapse whitespace according to CSS rules:
+        // - Multiple whitespace → single space
+        // - Preserve leading/trailing space if present (important for inline flow)
+        WhiteSpace::Normal | WhiteSpace::NoWrap => collapse_whitespace(text),
+        WhiteSpace::Pre | WhiteSpace::PreWrap => text.replace("\r\n", "\n"),
+        WhiteSpace::PreLine => {
+            // Collapse each line, dropping spaces around the newlines
+            let text = text.replace("\r\n", "\n");
+            let lines: Vec<String> = text.split('\n').map(collapse_whitespace).collect();
+            let last = lines.len() - 1;
+            lines
+                .iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    let line = if i > 0 { line.trim_start() } else { line };
+                    if i < last { line.trim_end() } else { line }
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+}
+
 /// Collapse whitespace in text according to CSS rules
 /// - Multiple whitespace characters become a single space
 /// - Preserves a single space at start/end if there was any whitespace
@@ -300,8 +709,16 @@ pub fn build_layout_tree<'a>(
     }
 
     let mut root = match style.display {
-        Display::Block | Display::Flex => LayoutBox::new_block(root_id, style),
-        Display::Inline | Display::InlineBlock => LayoutBox::new_inline(root_id, style),
+        Display::Block
+        | Display::Flex
+        | Display::Grid
+        | Display::Table
+        | Display::TableRowGroup
+        | Display::TableRow
+        | Display::TableCaption => LayoutBox::new_block(root_id, style),
+        Display::TableCell => LayoutBox::new_table_cell(root_id, cell_span(dom, root_id), style),
+        Display::Inline => LayoutBox::new_inline(root_id, style),
+        Display::InlineBlock => LayoutBox::new_inline_block(root_id, style),
         Display::None => return None,
     };
 
@@ -358,6 +775,37 @@ fn build_children<'a>(
                             container.children.push(child_box);
                             continue;
                         }
+                        "textarea" => {
+                            // Text content is the initial value, not child boxes
+                            let attribute = |name: &str, default: u32| {
+                                elem.get_attribute(name)
+                                    .and_then(|s| s.trim().parse::<u32>().ok())
+                                    .filter(|&n| n > 0)
+                                    .unwrap_or(default)
+                            };
+                            let data = TextAreaData {
+                                rows: attribute("rows", 2),
+                                cols: attribute("cols", 20),
+                                text: get_text_content(dom, child_id),
+                            };
+
+                            let child_box = LayoutBox::new_textarea(child_id, data, child_style);
+                            let container = parent_box.get_inline_container();
+                            container.children.push(child_box);
+                            continue;
+                        }
+                        "select" => {
+                            let child_box = LayoutBox::new_select(child_id, select_data(dom, child_id), child_style);
+                            let container = parent_box.get_inline_container();
+                            container.children.push(child_box);
+                            continue;
+                        }
+                        "br" => {
+                            let child_box = LayoutBox::new_line_break(child_id, child_style);
+                            let container = parent_box.get_inline_container();
+                            container.children.push(child_box);
+                            continue;
+                        }
                         "img" => {
                             // Get image attributes
                             let src = elem.get_attribute("src").unwrap_or("").to_string();
@@ -384,24 +832,53 @@ fn build_children<'a>(
                     }
                 }
 
-                let child_box = match child_style.display {
-                    Display::Block | Display::Flex => {
+                // Floats and absolutely positioned boxes are blockified
+                let out_of_flow = child_style.float != Float::None
+                    || matches!(child_style.position, Position::Absolute | Position::Fixed);
+                let display = match child_style.display {
+                    Display::Inline
+                    | Display::InlineBlock
+                    | Display::TableRowGroup
+                    | Display::TableRow
+                    | Display::TableCell
+                    | Display::TableCaption if out_of_flow => Display::Block,
+                    d => d,
+                };
+
+                let child_box = match display {
+                    Display::Block
+                    | Display::Flex
+                    | Display::Grid
+                    | Display::Table
+                    | Display::TableRowGroup
+                    | Display::TableRow
+                    | Display::TableCaption => {
                         let mut b = LayoutBox::new_block(child_id, child_style);
                         build_children(dom, style_tree, child_id, &mut b);
                         b
                     }
-                    Display::Inline | Display::InlineBlock => {
+                    Display::TableCell => {
+                        let mut b = LayoutBox::new_table_cell(child_id, cell_span(dom, child_id), child_style);
+                        build_children(dom, style_tree, child_id, &mut b);
+                        b
+                    }
+                    Display::Inline => {
                         let mut b = LayoutBox::new_inline(child_id, child_style);
                         build_children(dom, style_tree, child_id, &mut b);
                         b
                     }
+                    Display::InlineBlock => {
+                        let mut b = LayoutBox::new_inline_block(child_id, child_style);
+                        build_children(dom, style_tree, child_id, &mut b);
+                        b
+                    }
                     Display::None => continue,
                 };
 
-                if child_box.is_block() {
-                    parent_box.children.push(child_box);
+                if child_box.is_in_flow_block() {
+                    parent_box.push_block_child(child_box);
                 } else {
-                    // Inline content may need wrapping
+                    // Inline content and out-of-flow boxes may need wrapping
                     let container = parent_box.get_inline_container();
                     container.children.push(child_box);
                 }
@@ -409,17 +886,14 @@ fn build_children<'a>(
         } else if node.is_text() {
             // Text node - create text box
             if let Some(text) = node.as_text() {
-                // Collapse whitespace according to CSS rules:
-                // - Multiple whitespace → single space
-                // - Preserve leading/trailing space if present (important for inline flow)
-                let collapsed = collapse_whitespace(text);
-                if !collapsed.is_empty() {
-                    // Inherit style from parent element
-                    // Walk up to find nearest element with style
-                    if let Some(parent_style) = find_parent_style(dom, style_tree, parent_id) {
+                // Inherit style from parent element
+                // Walk up to find nearest element with style
+                if let Some(parent_style) = find_parent_style(dom, style_tree, parent_id) {
+                    let text = process_whitespace(text, parent_style.white_space);
+                    if !text.is_empty() {
                         let text_box = LayoutBox::new_text(
                             child_id,
-                            collapsed,
+                            text,
                             parent_style,
                         );
                         let container = parent_box.get_inline_container();
@@ -429,6 +903,50 @@ fn build_children<'a>(
             }
         }
     }
+
+    // Rows and cells need to sit inside a table, a row group and a row
+    fix_table_structure(parent_box);
+}
+
+/// Collect the options of a select element, directly or in option groups
+pub fn select_data(dom: &DomTree, select_id: NodeId) -> SelectData {
+    let mut options = Vec::new();
+    let mut selected = None;
+    let mut pending = dom.children(select_id);
+    pending.reverse();
+
+    while let Some(id) = pending.pop() {
+        let Some(elem) = dom.get(id).and_then(|n| n.as_element()) else {
+            continue;
+        };
+        match elem.tag_name.as_str() {
+            "option" => {
+                if selected.is_none() && elem.get_attribute("selected").is_some() {
+                    selected = Some(options.len());
+                }
+                options.push(get_text_content(dom, id).split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+            "optgroup" => pending.extend(dom.children(id).into_iter().rev()),
+            _ => {}
+        }
+    }
+
+    SelectData { options, selected: selected.unwrap_or(0) }
+}
+
+/// Read the colspan and rowspan attributes of a table cell
+fn cell_span(dom: &DomTree, node_id: NodeId) -> CellSpan {
+    let attribute = |name: &str| {
+        dom.get(node_id)
+            .and_then(|n| n.as_element())
+            .and_then(|e| e.get_attribute(name))
+            .and_then(|v| v.trim().parse::<usize>().ok())
+    };
+
+    CellSpan {
+        columns: attribute("colspan").unwrap_or(1).clamp(1, MAX_SPAN),
+        rows: attribute("rowspan").unwrap_or(1).min(MAX_SPAN),
+    }
 }
 
 /// Find the style of the nearest ancestor element
@@ -583,6 +1101,14 @@ mod tests {
         assert_eq!(collapse_whitespace("\t\n"), " ");
     }
 
+    #[test]
+    fn test_process_whitespace() {
+        let text = "  a  b \n c ";
+        assert_eq!(process_whitespace(text, WhiteSpace::Normal), " a b c ");
+        assert_eq!(process_whitespace(text, WhiteSpace::Pre), text);
+        assert_eq!(process_whitespace(text, WhiteSpace::PreLine), " a b\nc ");
+    }
+
     #[test]
     fn test_collapse_whitespace_empty() {
         assert_eq!(collapse_whitespace(""), "");
@@ -5,8 +5,9 @@
 use crate::boxtree::LayoutBox;
 use crate::block::layout_block;
 use crate::inline::layout_inline_children;
+use crate::positioned::apply_relative_offset;
 use crate::ContainingBlock;
-use gugalanna_style::{AlignItems, AlignSelf, Display, FlexDirection, JustifyContent};
+use gugalanna_style::{AlignContent, AlignItems, AlignSelf, Display, FlexDirection, FlexWrap, JustifyContent};
 
 /// Simple struct to hold flex item calculations
 #[derive(Debug)]
@@ -18,18 +19,38 @@ struct FlexItemData {
     flex_basis: f32,
     main_size: f32,
     cross_size: f32,
+    /// Margins on the main axis
+    main_margins: f32,
     frozen: bool,
 }
 
+/// A run of flex items laid out on one line, `start..end` into the sorted items
+#[derive(Debug)]
+struct FlexLine {
+    start: usize,
+    end: usize,
+    cross_size: f32,
+}
+
 /// Layout a flex container and its children
 pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock) {
+    let height = layout_box.specified_height(containing_block);
+    layout_flex_container(layout_box, containing_block, height);
+}
+
+/// Layout a flex container whose `height` property is already resolved
+pub(crate) fn layout_flex_container(
+    layout_box: &mut LayoutBox,
+    containing_block: ContainingBlock,
+    container_height: Option<f32>,
+) {
     let style = match layout_box.style() {
         Some(s) => s.clone(),
         None => return,
     };
 
     // Apply padding/border/margin from style
-    layout_box.apply_style_edges();
+    layout_box.apply_style_edges(containing_block.width);
 
     // Determine main axis direction
     let flex_direction = style.flex_direction;
@@ -44,8 +65,6 @@ pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock
             - layout_box.dimensions.padding.horizontal()
     );
 
-    let container_height = style.height;
-
     // Set container content width for now
     layout_box.dimensions.content.width = container_width;
 
@@ -62,14 +81,19 @@ pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock
         Some(container_width)
     };
 
+    // Items resolve percentages against the container's content box
+    let items_containing = ContainingBlock::with_height(container_width, container_height);
+
     // Step 1: Collect flex items and compute their base sizes
     let mut flex_items: Vec<FlexItemData> = Vec::new();
 
     for (index, child) in layout_box.children.iter_mut().enumerate() {
         let child_style = child.style().cloned();
 
-        // Skip items with display: none
-        if child_style.as_ref().map(|s| s.display == Display::None).unwrap_or(false) {
+        // Skip items with display: none and absolutely positioned children
+        if child_style.as_ref().map(|s| s.display == Display::None).unwrap_or(false)
+            || child.is_absolutely_positioned()
+        {
             continue;
         }
 
@@ -80,7 +104,7 @@ pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock
         };
 
         // Apply edges to child for correct margin box calculation
-        child.apply_style_edges();
+        child.apply_style_edges(container_width);
 
         // Determine base size
         let base_size = if let Some(basis) = flex_basis {
@@ -90,12 +114,12 @@ pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock
             let explicit_size = if is_row {
                 child_style.as_ref().and_then(|s| s.width)
             } else {
-                child_style.as_ref().and_then(|s| s.height)
+                child.specified_height(items_containing)
             };
 
             explicit_size.unwrap_or_else(|| {
                 // Estimate based on content - need to do preliminary layout
-                compute_intrinsic_main_size(child, is_row, available_main)
+                compute_intrinsic_main_size(child, is_row, items_containing)
             })
         };
 
@@ -107,6 +131,11 @@ pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock
             flex_basis: base_size,
             main_size: base_size,
             cross_size: 0.0,
+            main_margins: if is_row {
+                child.dimensions.margin.horizontal()
+            } else {
+                child.dimensions.margin.vertical()
+            },
             frozen: flex_grow == 0.0 && flex_shrink == 0.0,
         });
     }
@@ -114,10 +143,20 @@ pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock
     // Step 2: Sort by order property (stable sort preserves original order for equal values)
     flex_items.sort_by_key(|item| item.order);
 
-    // Step 3: Resolve flexible lengths (flex-grow/flex-shrink algorithm)
-    resolve_flexible_lengths(&mut flex_items, available_main);
+    // Step 3: Collect items into flex lines
+    let is_wrapping = style.flex_wrap != FlexWrap::NoWrap;
+    let mut lines = if is_wrapping {
+        collect_flex_lines(&flex_items, available_main)
+    } else {
+        vec![FlexLine { start: 0, end: flex_items.len(), cross_size: 0.0 }]
+    };
+
+    // Step 4: Resolve flexible lengths (flex-grow/flex-shrink algorithm) per line
+    for line in &lines {
+        resolve_flexible_lengths(&mut flex_items[line.start..line.end], available_main);
+    }
 
-    // Step 4: Layout each child and determine cross sizes
+    // Step 5: Layout each child and determine cross sizes
     for item_data in &mut flex_items {
         let child = &mut layout_box.children[item_data.index];
 
@@ -134,15 +173,9 @@ pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock
 
         // Create containing block for child layout
         let child_containing = if is_row {
-            ContainingBlock::new(
-                item_data.main_size,
-                available_cross.unwrap_or(0.0),
-            )
+            ContainingBlock::with_height(item_data.main_size, available_cross)
         } else {
-            ContainingBlock::new(
-                available_cross.unwrap_or(container_width),
-                item_data.main_size,
-            )
+            ContainingBlock::with_height(available_cross.unwrap_or(container_width), container_height)
         };
 
         // Layout the child
@@ -156,101 +189,173 @@ pub fn layout_flex(layout_box: &mut LayoutBox, containing_block: ContainingBlock
         };
     }
 
-    // Step 5: Determine cross size of flex container
-    let max_cross_size: f32 = flex_items.iter()
-        .map(|i| i.cross_size)
-        .fold(0.0_f32, f32::max);
-
-    let container_cross = available_cross.unwrap_or(max_cross_size);
-
-    // Step 6: Position items along main axis (justify-content)
-    let total_main_size: f32 = flex_items.iter().map(|i| i.main_size).sum();
-    let free_space = (available_main - total_main_size).max(0.0);
+    // Step 6: Determine cross size of each line and of the flex container
+    for line in &mut lines {
+        line.cross_size = flex_items[line.start..line.end].iter()
+            .map(|i| i.cross_size)
+            .fold(0.0_f32, f32::max);
+    }
 
-    let (initial_offset, gap) = compute_main_axis_spacing(
-        style.justify_content,
-        free_space,
-        flex_items.len(),
-        is_reversed,
-    );
+    let lines_cross_size: f32 = lines.iter().map(|l| l.cross_size).sum();
+    let container_cross = available_cross.unwrap_or(lines_cross_size);
 
-    let mut main_cursor = initial_offset;
+    // A single-line container's line fills it
+    if !is_wrapping {
+        lines[0].cross_size = container_cross;
+    }
 
-    // Iterate in correct order based on direction
-    let item_indices: Vec<usize> = if is_reversed {
-        (0..flex_items.len()).rev().collect()
+    // Distribute the lines on the cross axis (align-content)
+    let free_cross = (container_cross - lines_cross_size).max(0.0);
+    let (mut cross_cursor, line_gap) = if is_wrapping {
+        compute_line_spacing(style.align_content, free_cross, &mut lines)
     } else {
-        (0..flex_items.len()).collect()
+        (0.0, 0.0)
     };
 
-    for i in item_indices {
-        let item_data = &flex_items[i];
-        let child = &mut layout_box.children[item_data.index];
+    // wrap-reverse stacks lines from the cross end
+    if style.flex_wrap == FlexWrap::WrapReverse {
+        lines.reverse();
+    }
 
-        // Position on main axis
-        if is_row {
-            child.dimensions.content.x = main_cursor + child.dimensions.margin.left;
-        } else {
-            child.dimensions.content.y = main_cursor + child.dimensions.margin.top;
-        }
+    // Step 7: Position items along main axis (justify-content) line by line
+    let mut total_main_size: f32 = 0.0;
+
+    for line in &lines {
+        let line_items = &flex_items[line.start..line.end];
+        let line_main_size: f32 = line_items.iter().map(|i| i.main_size).sum();
+        total_main_size = total_main_size.max(line_main_size);
+        let free_space = (available_main - line_main_size).max(0.0);
 
-        // Position on cross axis based on align-items/align-self
-        let child_align = child.style()
-            .map(|s| s.align_self)
-            .unwrap_or(AlignSelf::Auto);
+        let (initial_offset, gap) = compute_main_axis_spacing(
+            style.justify_content,
+            free_space,
+            line_items.len(),
+            is_reversed,
+        );
+
+        let mut main_cursor = initial_offset;
 
-        let effective_align = if child_align == AlignSelf::Auto {
-            style.align_items
+        // Iterate in correct order based on direction
+        let item_indices: Vec<usize> = if is_reversed {
+            (line.start..line.end).rev().collect()
         } else {
-            match child_align {
-                AlignSelf::FlexStart => AlignItems::FlexStart,
-                AlignSelf::FlexEnd => AlignItems::FlexEnd,
-                AlignSelf::Center => AlignItems::Center,
-                AlignSelf::Stretch => AlignItems::Stretch,
-                AlignSelf::Baseline => AlignItems::Baseline,
-                AlignSelf::Auto => style.align_items,
-            }
+            (line.start..line.end).collect()
         };
 
-        let child_cross_size = item_data.cross_size;
-        let cross_pos = compute_cross_position(
-            effective_align,
-            child_cross_size,
-            container_cross,
-        );
+        for i in item_indices {
+            let item_data = &flex_items[i];
+            let child = &mut layout_box.children[item_data.index];
 
-        if is_row {
-            child.dimensions.content.y = cross_pos + child.dimensions.margin.top;
-        } else {
-            child.dimensions.content.x = cross_pos + child.dimensions.margin.left;
+            // Position on main axis
+            if is_row {
+                child.dimensions.content.x = main_cursor + child.dimensions.margin.left;
+            } else {
+                child.dimensions.content.y = main_cursor + child.dimensions.margin.top;
+            }
+
+            // Position on cross axis based on align-items/align-self
+            let child_align = child.style()
+                .map(|s| s.align_self)
+                .unwrap_or(AlignSelf::Auto);
+
+            let effective_align = if child_align == AlignSelf::Auto {
+                style.align_items
+            } else {
+                match child_align {
+                    AlignSelf::FlexStart => AlignItems::FlexStart,
+                    AlignSelf::FlexEnd => AlignItems::FlexEnd,
+                    AlignSelf::Center => AlignItems::Center,
+                    AlignSelf::Stretch => AlignItems::Stretch,
+                    AlignSelf::Baseline => AlignItems::Baseline,
+                    AlignSelf::Auto => style.align_items,
+                }
+            };
+
+            let child_cross_size = item_data.cross_size;
+            let cross_pos = cross_cursor + compute_cross_position(
+                effective_align,
+                child_cross_size,
+                line.cross_size,
+            );
+
+            if is_row {
+                child.dimensions.content.y = cross_pos + child.dimensions.margin.top;
+            } else {
+                child.dimensions.content.x = cross_pos + child.dimensions.margin.left;
+            }
+
+            apply_relative_offset(
+                child,
+                items_containing,
+            );
+
+            // Advance cursor
+            main_cursor += item_data.main_size + gap;
         }
 
-        // Advance cursor
-        main_cursor += item_data.main_size + gap;
+        cross_cursor += line.cross_size + line_gap;
     }
 
-    // Step 7: Set container final dimensions
+    // Step 8: Set container final dimensions
     if is_row {
         layout_box.dimensions.content.width = container_width;
-        layout_box.dimensions.content.height = if style.height.is_some() {
-            container_height.unwrap()
-        } else {
-            container_cross
-        };
+        layout_box.dimensions.content.height = container_height.unwrap_or(container_cross);
     } else {
         layout_box.dimensions.content.width = container_width;
-        layout_box.dimensions.content.height = if style.height.is_some() {
-            container_height.unwrap()
-        } else {
-            total_main_size
-        };
+        layout_box.dimensions.content.height = container_height.unwrap_or(total_main_size);
     }
 }
 
+/// Collect sorted flex items into lines, breaking before an item whose
+/// hypothetical main size no longer fits
+///
+/// Every line holds at least one item.
+fn collect_flex_lines(items: &[FlexItemData], available_main: f32) -> Vec<FlexLine> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut line_main_size = 0.0;
+
+    for (i, item) in items.iter().enumerate() {
+        let item_main_size = item.flex_basis + item.main_margins;
+        if i > start && line_main_size + item_main_size > available_main {
+            lines.push(FlexLine { start, end: i, cross_size: 0.0 });
+            start = i;
+            line_main_size = 0.0;
+        }
+        line_main_size += item_main_size;
+    }
+    lines.push(FlexLine { start, end: items.len(), cross_size: 0.0 });
+
+    lines
+}
+
+/// Compute the start offset and gap of flex lines based on align-content
+///
+/// For `stretch`, the free space is added to the lines' cross sizes instead.
+fn compute_line_spacing(align: AlignContent, free_space: f32, lines: &mut [FlexLine]) -> (f32, f32) {
+    let justify = match align {
+        AlignContent::FlexStart => JustifyContent::FlexStart,
+        AlignContent::FlexEnd => JustifyContent::FlexEnd,
+        AlignContent::Center => JustifyContent::Center,
+        AlignContent::SpaceBetween => JustifyContent::SpaceBetween,
+        AlignContent::SpaceAround => JustifyContent::SpaceAround,
+        AlignContent::SpaceEvenly => JustifyContent::SpaceEvenly,
+        AlignContent::Stretch => {
+            let extra = free_space / lines.len() as f32;
+            for line in lines.iter_mut() {
+                line.cross_size += extra;
+            }
+            return (0.0, 0.0);
+        }
+    };
+
+    compute_main_axis_spacing(justify, free_space, lines.len(), false)
+}
+
 /// Compute intrinsic main size of a flex item (content-based sizing)
-fn compute_intrinsic_main_size(child: &mut LayoutBox, is_row: bool, _available: f32) -> f32 {
+fn compute_intrinsic_main_size(child: &mut LayoutBox, is_row: bool, containing_block: ContainingBlock) -> f32 {
     // Apply edges first
-    child.apply_style_edges();
+    child.apply_style_edges(containing_block.width);
 
     // For text or simple content, estimate based on font metrics
     if child.children.is_empty() {
@@ -268,7 +373,7 @@ fn compute_intrinsic_main_size(child: &mut LayoutBox, is_row: bool, _available:
         if is_row {
             child.style().and_then(|s| s.width).unwrap_or(100.0)
         } else {
-            child.style().and_then(|s| s.height).unwrap_or(
+            child.specified_height(containing_block).unwrap_or(
                 child.style().map(|s| s.line_height).unwrap_or(20.0)
             )
         }
@@ -326,7 +431,7 @@ fn layout_flex_item(
     main_size: f32,
 ) {
     // Apply edges from style
-    child.apply_style_edges();
+    child.apply_style_edges(containing_block.width);
 
     // Set the appropriate dimension
     if is_row {
@@ -342,7 +447,7 @@ fn layout_flex_item(
     }
 
     // Check if this is a block or inline context
-    let has_block_children = child.children.iter().any(|c| c.is_block());
+    let has_block_children = child.children.iter().any(|c| c.is_in_flow_block());
 
     if has_block_children {
         // Use block layout for children
@@ -369,6 +474,13 @@ fn layout_flex_item(
         child.dimensions.content.width = children_width;
     }
 
+    // A specified cross size wins over the content's
+    if is_row {
+        if let Some(height) = child.specified_height(containing_block) {
+            child.dimensions.content.height = height;
+        }
+    }
+
     // For items with no children, ensure minimum size
     if child.children.is_empty() {
         if child.dimensions.content.height == 0.0 {
@@ -441,6 +553,80 @@ fn compute_cross_position(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::setup_and_layout;
+
+    const CARDS: &str = "<div class='row'><div></div><div></div><div></div><div></div><div></div><div></div></div>";
+
+    fn positions(layout: &LayoutBox) -> Vec<(f32, f32)> {
+        layout.children.iter()
+            .map(|c| (c.dimensions.content.x, c.dimensions.content.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_flex_wrap_breaks_lines() {
+        let layout = setup_and_layout(
+            CARDS,
+            ".row { display: flex; flex-wrap: wrap; width: 500px; } \
+             .row div { width: 200px; height: 50px; flex-shrink: 0; }",
+            800.0,
+        );
+
+        assert_eq!(
+            positions(&layout),
+            vec![(0.0, 0.0), (200.0, 0.0), (0.0, 50.0), (200.0, 50.0), (0.0, 100.0), (200.0, 100.0)]
+        );
+        assert_eq!(layout.dimensions.content.height, 150.0);
+    }
+
+    #[test]
+    fn test_flex_nowrap_stays_on_one_line() {
+        let layout = setup_and_layout(
+            CARDS,
+            ".row { display: flex; width: 500px; } \
+             .row div { width: 200px; height: 50px; flex-shrink: 0; }",
+            800.0,
+        );
+        assert!(positions(&layout).iter().all(|&(_, y)| y == 0.0));
+    }
+
+    #[test]
+    fn test_align_content_space_between() {
+        let layout = setup_and_layout(
+            CARDS,
+            ".row { display: flex; flex-wrap: wrap; align-content: space-between; width: 500px; height: 400px; } \
+             .row div { width: 200px; height: 50px; }",
+            800.0,
+        );
+
+        // 250px of free space split between the three lines
+        let ys: Vec<f32> = positions(&layout).iter().map(|&(_, y)| y).collect();
+        assert_eq!(ys, vec![0.0, 0.0, 175.0, 175.0, 350.0, 350.0]);
+    }
+
+    #[test]
+    fn test_flex_wrap_reverse() {
+        let layout = setup_and_layout(
+            CARDS,
+            ".row { display: flex; flex-wrap: wrap-reverse; width: 500px; } \
+             .row div { width: 200px; height: 50px; }",
+            800.0,
+        );
+        let ys: Vec<f32> = positions(&layout).iter().map(|&(_, y)| y).collect();
+        assert_eq!(ys, vec![100.0, 100.0, 50.0, 50.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_flex_lines_grow_independently() {
+        let layout = setup_and_layout(
+            "<div class='row'><div></div><div></div><div></div></div>",
+            ".row { display: flex; flex-wrap: wrap; width: 500px; } \
+             .row div { width: 200px; height: 50px; flex-grow: 1; }",
+            800.0,
+        );
+        let widths: Vec<f32> = layout.children.iter().map(|c| c.dimensions.content.width).collect();
+        assert_eq!(widths, vec![250.0, 250.0, 500.0]);
+    }
 
     #[test]
     fn test_main_axis_spacing_flex_start() {
@@ -507,11 +693,11 @@ mod tests {
         let mut items = vec![
             FlexItemData {
                 index: 0, order: 0, flex_grow: 1.0, flex_shrink: 1.0,
-                flex_basis: 100.0, main_size: 100.0, cross_size: 0.0, frozen: false,
+                flex_basis: 100.0, main_size: 100.0, cross_size: 0.0, main_margins: 0.0, frozen: false,
             },
             FlexItemData {
                 index: 1, order: 0, flex_grow: 2.0, flex_shrink: 1.0,
-                flex_basis: 100.0, main_size: 100.0, cross_size: 0.0, frozen: false,
+                flex_basis: 100.0, main_size: 100.0, cross_size: 0.0, main_margins: 0.0, frozen: false,
             },
         ];
 
@@ -527,11 +713,11 @@ mod tests {
         let mut items = vec![
             FlexItemData {
                 index: 0, order: 0, flex_grow: 0.0, flex_shrink: 1.0,
-                flex_basis: 200.0, main_size: 200.0, cross_size: 0.0, frozen: false,
+                flex_basis: 200.0, main_size: 200.0, cross_size: 0.0, main_margins: 0.0, frozen: false,
             },
             FlexItemData {
                 index: 1, order: 0, flex_grow: 0.0, flex_shrink: 1.0,
-                flex_basis: 200.0, main_size: 200.0, cross_size: 0.0, frozen: false,
+                flex_basis: 200.0, main_size: 200.0, cross_size: 0.0, main_margins: 0.0, frozen: false,
             },
         ];
 
@@ -0,0 +1,188 @@
+//! Float Layout
+//!
+//! Tracks floated boxes within a block formatting context and answers
+//! the questions block and inline layout need to flow content around them.
+
+use gugalanna_style::{Clear, Float};
+
+use crate::Rect;
+
+/// A float that has been placed in the formatting context
+#[derive(Debug, Clone, Copy)]
+struct PlacedFloat {
+    side: Float,
+    /// Margin box in formatting context coordinates
+    rect: Rect,
+}
+
+/// Floats of a block formatting context
+///
+/// Rectangles are stored relative to the formatting context root's content box.
+/// Queries and placements use coordinates local to the block currently being
+/// laid out; `translate` moves that local origin while descending into children.
+#[derive(Debug, Default)]
+pub struct FloatContext {
+    floats: Vec<PlacedFloat>,
+    origin_x: f32,
+    origin_y: f32,
+}
+
+impl FloatContext {
+    /// Create an empty float context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if no floats have been placed
+    pub fn is_empty(&self) -> bool {
+        self.floats.is_empty()
+    }
+
+    /// Move the local origin (used when descending into a child block)
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.origin_x += dx;
+        self.origin_y += dy;
+    }
+
+    /// Horizontal band (left, right) available for content spanning
+    /// `y..y + height` in a container of the given width.
+    pub fn available_band(&self, y: f32, height: f32, container_width: f32) -> (f32, f32) {
+        let mut left = 0.0_f32;
+        let mut right = container_width;
+
+        for float in self.overlapping(y, height) {
+            let x = float.rect.x - self.origin_x;
+            match float.side {
+                Float::Left => left = left.max(x + float.rect.width),
+                Float::Right => right = right.min(x),
+                Float::None => {}
+            }
+        }
+
+        (left.min(container_width), right.max(left))
+    }
+
+    /// Find the first position at or below `y` where a band at least
+    /// `min_width` wide is available, returns (y, left, right).
+    ///
+    /// If no such band exists the position below all floats is returned.
+    pub fn find_band(&self, y: f32, height: f32, min_width: f32, container_width: f32) -> (f32, f32, f32) {
+        let mut y = y;
+        loop {
+            let (left, right) = self.available_band(y, height, container_width);
+            if right - left >= min_width {
+                return (y, left, right);
+            }
+            match self.next_edge_below(y) {
+                Some(next) => y = next,
+                None => return (y, left, right),
+            }
+        }
+    }
+
+    /// Place a float with the given margin box size, returns its local (x, y)
+    pub fn place(&mut self, side: Float, width: f32, height: f32, y: f32, container_width: f32) -> (f32, f32) {
+        // A float may not be placed higher than any earlier float
+        let min_y = self.floats.iter()
+            .map(|f| f.rect.y - self.origin_y)
+            .fold(y, f32::max);
+
+        let (y, left, right) = self.find_band(min_y, height, width, container_width);
+        let x = match side {
+            Float::Right => right - width,
+            _ => left,
+        };
+
+        self.floats.push(PlacedFloat {
+            side,
+            rect: Rect::new(x + self.origin_x, y + self.origin_y, width, height),
+        });
+
+        (x, y)
+    }
+
+    /// Local y position below the floats that `clear` applies to
+    pub fn clearance(&self, clear: Clear) -> Option<f32> {
+        let applies = |side: Float| match clear {
+            Clear::None => false,
+            Clear::Left => side == Float::Left,
+            Clear::Right => side == Float::Right,
+            Clear::Both => true,
+        };
+
+        self.floats.iter()
+            .filter(|f| applies(f.side))
+            .map(|f| f.rect.bottom() - self.origin_y)
+            .reduce(f32::max)
+    }
+
+    /// Local y position of the lowest float bottom edge (0 if there are none)
+    pub fn bottom(&self) -> f32 {
+        self.floats.iter()
+            .map(|f| f.rect.bottom() - self.origin_y)
+            .fold(0.0, f32::max)
+    }
+
+    /// Floats whose vertical extent overlaps `y..y + height`
+    fn overlapping(&self, y: f32, height: f32) -> impl Iterator<Item = &PlacedFloat> {
+        let top = y + self.origin_y;
+        let bottom = top + height;
+        self.floats.iter().filter(move |f| {
+            f.rect.bottom() > top && (f.rect.y < bottom || f.rect.y <= top)
+        })
+    }
+
+    /// Closest float bottom edge strictly below `y`
+    fn next_edge_below(&self, y: f32) -> Option<f32> {
+        self.floats.iter()
+            .map(|f| f.rect.bottom() - self.origin_y)
+            .filter(|&b| b > y)
+            .reduce(f32::min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_band_without_floats() {
+        let floats = FloatContext::new();
+        assert_eq!(floats.available_band(0.0, 20.0, 300.0), (0.0, 300.0));
+    }
+
+    #[test]
+    fn test_left_and_right_floats_narrow_band() {
+        let mut floats = FloatContext::new();
+        floats.place(Float::Left, 100.0, 50.0, 0.0, 300.0);
+        floats.place(Float::Right, 50.0, 30.0, 0.0, 300.0);
+
+        assert_eq!(floats.available_band(0.0, 20.0, 300.0), (100.0, 250.0));
+        assert_eq!(floats.available_band(40.0, 20.0, 300.0), (100.0, 300.0));
+        assert_eq!(floats.available_band(50.0, 20.0, 300.0), (0.0, 300.0));
+    }
+
+    #[test]
+    fn test_float_moves_down_when_it_does_not_fit() {
+        let mut floats = FloatContext::new();
+        floats.place(Float::Left, 100.0, 50.0, 0.0, 150.0);
+        let (x, y) = floats.place(Float::Left, 100.0, 30.0, 0.0, 150.0);
+
+        assert_eq!((x, y), (0.0, 50.0));
+    }
+
+    #[test]
+    fn test_clearance_and_translation() {
+        let mut floats = FloatContext::new();
+        floats.place(Float::Left, 100.0, 50.0, 0.0, 300.0);
+        floats.place(Float::Right, 100.0, 80.0, 0.0, 300.0);
+
+        assert_eq!(floats.clearance(Clear::Left), Some(50.0));
+        assert_eq!(floats.clearance(Clear::Both), Some(80.0));
+        assert_eq!(floats.clearance(Clear::None), None);
+
+        floats.translate(10.0, 20.0);
+        assert_eq!(floats.clearance(Clear::Left), Some(30.0));
+        assert_eq!(floats.available_band(0.0, 10.0, 280.0), (90.0, 190.0));
+    }
+}
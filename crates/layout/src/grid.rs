@@ -0,0 +1,335 @@
+//! Grid Layout
+//!
+//! Implements a basic grid: items are placed on explicit and implicit
+//! tracks by line number or auto-placed in row-major order. Fixed tracks
+//! are sized first, auto tracks fit their items and fr tracks share the
+//! space left. Every item occupies a single cell.
+
+use std::collections::HashSet;
+
+use gugalanna_style::{Display, LengthPercentage, TrackSize};
+
+use crate::block::layout_block_with_width;
+use crate::boxtree::LayoutBox;
+use crate::intrinsic::outer_widths;
+use crate::positioned::apply_relative_offset;
+use crate::table::is_ignorable;
+use crate::ContainingBlock;
+
+/// Highest grid line honored by line placement
+const MAX_LINE: usize = 1000;
+
+/// A grid item and the cell it was placed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GridItem {
+    /// Index among the container's children
+    index: usize,
+    row: usize,
+    column: usize,
+}
+
+/// Check if a box lays out its children on a grid
+pub(crate) fn is_grid(layout_box: &LayoutBox) -> bool {
+    layout_box.style().is_some_and(|s| s.display == Display::Grid)
+}
+
+/// Lay out the children of a grid container, returns the auto content height
+///
+/// `height` is the container's definite content height, if it has one.
+pub(crate) fn layout_grid(grid: &mut LayoutBox, height: Option<f32>) -> f32 {
+    let style = match grid.style() {
+        Some(style) => style,
+        None => return 0.0,
+    };
+    let width = grid.dimensions.content.width;
+
+    let items = place_items(grid, style.grid_template_columns.len());
+    let column_count = items
+        .iter()
+        .map(|i| i.column + 1)
+        .fold(style.grid_template_columns.len().max(1), usize::max);
+    let row_count = items
+        .iter()
+        .map(|i| i.row + 1)
+        .fold(style.grid_template_rows.len(), usize::max);
+
+    // Columns: auto tracks fit the widest item placed in them
+    let mut content_widths = vec![0.0_f32; column_count];
+    for item in &items {
+        let widths = outer_widths(&grid.children[item.index]);
+        content_widths[item.column] = content_widths[item.column].max(widths.max);
+    }
+    let column_tracks = explicit_tracks(&style.grid_template_columns, column_count);
+    let columns = size_tracks(&column_tracks, &content_widths, Some(width), style.column_gap);
+
+    // Items fill the width of their column
+    for item in &items {
+        let child = &mut grid.children[item.index];
+        child.apply_style_edges(columns[item.column]);
+        let d = &child.dimensions;
+        let edges = d.margin.horizontal() + d.padding.horizontal() + d.border.horizontal();
+        layout_block_with_width(child, (columns[item.column] - edges).max(0.0));
+    }
+
+    // Rows: auto tracks fit the tallest item placed in them
+    let mut content_heights = vec![0.0_f32; row_count];
+    for item in &items {
+        let height = grid.children[item.index].dimensions.margin_box_height();
+        content_heights[item.row] = content_heights[item.row].max(height);
+    }
+    let row_tracks = explicit_tracks(&style.grid_template_rows, row_count);
+    let rows = size_tracks(&row_tracks, &content_heights, height, style.row_gap);
+
+    let column_x = track_offsets(&columns, style.column_gap);
+    let row_y = track_offsets(&rows, style.row_gap);
+    let height = height.unwrap_or_else(|| {
+        rows.iter().sum::<f32>() + style.row_gap * rows.len().saturating_sub(1) as f32
+    });
+
+    for item in &items {
+        let child = &mut grid.children[item.index];
+        let auto_height = child.style().is_none_or(|s| s.height.is_none());
+
+        // Items without a height stretch to the rows they sit in
+        let d = &mut child.dimensions;
+        if auto_height {
+            let edges = d.margin.vertical() + d.padding.vertical() + d.border.vertical();
+            d.content.height = d.content.height.max(rows[item.row] - edges);
+        }
+
+        d.content.x += column_x[item.column];
+        d.content.y += row_y[item.row];
+        apply_relative_offset(child, ContainingBlock::new(width, height));
+    }
+
+    height
+}
+
+/// Place the in-flow children of a grid container on cells
+///
+/// Items with a row line go first, in the first free column of their row
+/// unless a column line is given too. The rest are auto-placed in
+/// row-major order, wrapping after the explicit columns.
+fn place_items(grid: &LayoutBox, explicit_columns: usize) -> Vec<GridItem> {
+    // Lines are 1-based, cells 0-based
+    let line = |line: Option<usize>| line.map(|l| l.min(MAX_LINE) - 1);
+    let candidates: Vec<(usize, Option<usize>, Option<usize>)> = grid
+        .children
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| !c.is_absolutely_positioned() && !is_ignorable(c))
+        .map(|(index, c)| {
+            let style = c.style();
+            (index, line(style.and_then(|s| s.grid_row_start)), line(style.and_then(|s| s.grid_column_start)))
+        })
+        .collect();
+
+    let columns = candidates
+        .iter()
+        .filter_map(|(_, _, column)| column.map(|c| c + 1))
+        .fold(explicit_columns.max(1), usize::max);
+
+    let mut occupied = HashSet::new();
+    let mut items = Vec::with_capacity(candidates.len());
+    for &(index, row, column) in &candidates {
+        if let Some(row) = row {
+            let column = column.unwrap_or_else(|| (0..).find(|c| !occupied.contains(&(row, *c))).unwrap_or(0));
+            occupied.insert((row, column));
+            items.push(GridItem { index, row, column });
+        }
+    }
+
+    let (mut row, mut column) = (0, 0);
+    for &(index, _, fixed_column) in candidates.iter().filter(|(_, row, _)| row.is_none()) {
+        match fixed_column {
+            Some(c) => {
+                // Move down a row rather than backwards
+                if c < column {
+                    row += 1;
+                }
+                column = c;
+                while occupied.contains(&(row, column)) {
+                    row += 1;
+                }
+            }
+            None => loop {
+                if column >= columns {
+                    column = 0;
+                    row += 1;
+                }
+                if !occupied.contains(&(row, column)) {
+                    break;
+                }
+                column += 1;
+            },
+        }
+        occupied.insert((row, column));
+        items.push(GridItem { index, row, column });
+        column += 1;
+    }
+
+    items
+}
+
+/// Explicit track sizes, with auto tracks for the implicit ones
+fn explicit_tracks(template: &[TrackSize], count: usize) -> Vec<TrackSize> {
+    (0..count).map(|i| template.get(i).copied().unwrap_or(TrackSize::Auto)).collect()
+}
+
+/// Size tracks from their sizing functions
+///
+/// `content` is what the items of each track need. Fixed tracks are sized
+/// first, then auto tracks to their content; fr tracks share the space
+/// left, or stretch the auto tracks when there are none. When `available`
+/// is indefinite, fr tracks and percentages size like auto tracks.
+fn size_tracks(tracks: &[TrackSize], content: &[f32], available: Option<f32>, gap: f32) -> Vec<f32> {
+    let mut sizes: Vec<f32> = tracks
+        .iter()
+        .zip(content)
+        .map(|(track, content)| match (track, available) {
+            (TrackSize::Length(length), Some(available)) => length.resolve(available),
+            (TrackSize::Length(LengthPercentage::Px(px)), None) => *px,
+            (TrackSize::Length(LengthPercentage::Percent(_)), None) => *content,
+            (TrackSize::Flex(_), Some(_)) => 0.0,
+            (TrackSize::Flex(_), None) | (TrackSize::Auto, _) => *content,
+        })
+        .collect();
+
+    let available = match available {
+        Some(available) => available,
+        None => return sizes,
+    };
+    let gaps = gap * tracks.len().saturating_sub(1) as f32;
+    let free = (available - gaps - sizes.iter().sum::<f32>()).max(0.0);
+
+    let total_fr: f32 = tracks
+        .iter()
+        .map(|t| match t {
+            TrackSize::Flex(fr) => *fr,
+            _ => 0.0,
+        })
+        .sum();
+    let has_flex = tracks.iter().any(|t| matches!(t, TrackSize::Flex(_)));
+
+    if has_flex {
+        // Fractions summing to less than 1 leave part of the space free
+        let divisor = total_fr.max(1.0);
+        for (size, track) in sizes.iter_mut().zip(tracks) {
+            if let TrackSize::Flex(fr) = track {
+                *size = free * fr / divisor;
+            }
+        }
+    } else {
+        let auto_count = tracks.iter().filter(|t| **t == TrackSize::Auto).count();
+        if auto_count > 0 {
+            let extra = free / auto_count as f32;
+            for (size, track) in sizes.iter_mut().zip(tracks) {
+                if *track == TrackSize::Auto {
+                    *size += extra;
+                }
+            }
+        }
+    }
+
+    sizes
+}
+
+/// Start offset of each track
+fn track_offsets(sizes: &[f32], gap: f32) -> Vec<f32> {
+    let mut offset = 0.0;
+    sizes
+        .iter()
+        .map(|size| {
+            let start = offset;
+            offset += size + gap;
+            start
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::setup_and_layout;
+
+    /// Position of each grid item within the container's content box
+    fn positions(layout: &LayoutBox) -> Vec<(f32, f32)> {
+        layout
+            .children
+            .iter()
+            .map(|c| (c.dimensions.content.x, c.dimensions.content.y))
+            .collect()
+    }
+
+    fn items(count: usize) -> String {
+        format!("<div class='grid'>{}</div>", "<div></div>".repeat(count))
+    }
+
+    #[test]
+    fn test_three_columns_with_gaps() {
+        let layout = setup_and_layout(
+            &items(9),
+            "div { display: block; } \
+             .grid { display: grid; width: 320px; grid-template-columns: repeat(3, 1fr); gap: 10px; } \
+             .grid div { height: 50px; }",
+            800.0,
+        );
+
+        let mut expected = Vec::new();
+        for y in [0.0, 60.0, 120.0] {
+            for x in [0.0, 110.0, 220.0] {
+                expected.push((x, y));
+            }
+        }
+        assert_eq!(positions(&layout), expected);
+        assert!(layout.children.iter().all(|c| c.dimensions.content.width == 100.0));
+        assert_eq!(layout.dimensions.content.height, 170.0);
+    }
+
+    #[test]
+    fn test_fixed_tracks_sized_before_fr() {
+        let layout = setup_and_layout(
+            &items(3),
+            "div { display: block; } \
+             .grid { display: grid; width: 400px; grid-template-columns: 100px 1fr 3fr; } \
+             .grid div { height: 20px; }",
+            800.0,
+        );
+        let widths: Vec<f32> = layout.children.iter().map(|c| c.dimensions.content.width).collect();
+        assert_eq!(widths, vec![100.0, 75.0, 225.0]);
+    }
+
+    #[test]
+    fn test_line_placement_and_auto_flow() {
+        let layout = setup_and_layout(
+            "<div class='grid'><div></div><div class='placed'></div><div></div></div>",
+            "div { display: block; } \
+             .grid { display: grid; width: 300px; grid-template-columns: repeat(3, 100px); } \
+             .grid div { height: 20px; } \
+             .placed { grid-column: 1; grid-row: 1; }",
+            800.0,
+        );
+
+        // The placed item takes the first cell, auto items flow around it
+        assert_eq!(positions(&layout), vec![(100.0, 0.0), (0.0, 0.0), (200.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_auto_rows_stretch_items() {
+        let layout = setup_and_layout(
+            "<div class='grid'><div class='tall'></div><div></div></div>",
+            "div { display: block; } \
+             .grid { display: grid; width: 200px; grid-template-columns: 1fr 1fr; } \
+             .tall { height: 80px; }",
+            800.0,
+        );
+        let heights: Vec<f32> = layout.children.iter().map(|c| c.dimensions.content.height).collect();
+        assert_eq!(heights, vec![80.0, 80.0]);
+    }
+
+    #[test]
+    fn test_size_tracks_fr_below_one() {
+        let tracks = [TrackSize::Flex(0.25), TrackSize::Flex(0.25)];
+        assert_eq!(size_tracks(&tracks, &[0.0, 0.0], Some(400.0), 0.0), vec![100.0, 100.0]);
+    }
+}
@@ -2,10 +2,16 @@
 //!
 //! Implements inline formatting context and line box layout.
 
-use crate::boxtree::{LayoutBox, BoxType, InputType, ImageData};
+use crate::block::{layout_block, layout_float};
+use crate::boxtree::{LayoutBox, BoxType, InputType, ImageData, SelectData, TextAreaData};
+use crate::float::FloatContext;
+use crate::positioned::apply_relative_offset;
+use crate::intrinsic::intrinsic_widths;
 use crate::text::measure_text;
-use crate::Rect;
-use gugalanna_style::{ComputedStyle, Position};
+use crate::{ContainingBlock, Rect};
+use std::collections::HashMap;
+use std::ops::Range;
+use gugalanna_style::{ComputedStyle, Display, LengthPercentage, TextAlign, VerticalAlign};
 
 /// A line box containing inline content
 #[derive(Debug)]
@@ -38,79 +44,904 @@ pub struct InlineBox {
     pub rect: Rect,
     /// Associated text (if text box)
     pub text: Option<String>,
+    /// Distance from the top of the rect down to the baseline
+    pub baseline: f32,
 }
 
 impl InlineBox {
-    pub fn new(rect: Rect, text: Option<String>) -> Self {
-        Self { rect, text }
+    pub fn new(rect: Rect, text: Option<String>, baseline: f32) -> Self {
+        Self { rect, text, baseline }
     }
 }
 
 /// Layout inline children of a block element
 pub fn layout_inline_children(parent: &mut LayoutBox) {
-    let available_width = parent.dimensions.content.width;
+    let containing = ContainingBlock::with_auto_height(parent.dimensions.content.width);
+    let height = parent.specified_height(containing);
+    let mut floats = FloatContext::new();
+    let content_height = layout_inline_children_with_floats(parent, &mut floats, height);
+
+    if parent.dimensions.content.height == 0.0 {
+        parent.dimensions.content.height = content_height;
+    }
+}
+
+/// Soft hyphen: an invisible break opportunity inside a word
+const SOFT_HYPHEN: char = '\u{AD}';
+
+/// A piece of inline content, in document order
+///
+/// Paths index into the children of the formatting context root,
+/// descending through the inline boxes the content sits in.
+#[derive(Debug)]
+enum InlineItem<'a> {
+    /// A run of text without break opportunities
+    Word { path: Vec<usize>, text: String, width: f32, style: &'a ComputedStyle },
+    /// Collapsible whitespace, a break opportunity
+    Space { path: Vec<usize>, width: f32, style: &'a ComputedStyle },
+    /// Soft hyphen, a break opportunity shown as "-" when taken
+    SoftHyphen { path: Vec<usize>, style: &'a ComputedStyle },
+    /// Preserved newline or `<br>`, a forced line break
+    LineBreak { path: Vec<usize>, style: &'a ComputedStyle },
+    /// Start of an inline box, advances by its left margin, border and padding
+    OpenBox { path: Vec<usize>, width: f32 },
+    /// End of an inline box, advances by its right margin, border and padding
+    CloseBox { width: f32 },
+    /// Inline-level box laid out as a unit (inline-block, replaced elements)
+    Atomic { path: Vec<usize>, width: f32, height: f32, baseline: f32, align: VerticalAlign },
+    /// Floated box, placed when the line breaker reaches it
+    Float { path: Vec<usize> },
+    /// Absolutely positioned box, only its static position is recorded
+    Absolute { path: Vec<usize>, block_level: bool },
+}
+
+impl InlineItem<'_> {
+    /// Horizontal space taken on the line
+    fn width(&self) -> f32 {
+        match self {
+            InlineItem::Word { width, .. }
+            | InlineItem::Space { width, .. }
+            | InlineItem::OpenBox { width, .. }
+            | InlineItem::CloseBox { width }
+            | InlineItem::Atomic { width, .. } => *width,
+            InlineItem::SoftHyphen { .. }
+            | InlineItem::LineBreak { .. }
+            | InlineItem::Float { .. }
+            | InlineItem::Absolute { .. } => 0.0,
+        }
+    }
+
+    /// Height the item needs on a line
+    fn height(&self) -> f32 {
+        match self {
+            InlineItem::Word { style, .. } => style.line_height,
+            InlineItem::Atomic { height, .. } => *height,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Kind of text placed on a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextKind {
+    Word,
+    Space,
+    SoftHyphen,
+    /// Ends a line at a preserved newline or `<br>`, keeps an empty line
+    /// one line tall
+    LineBreak,
+}
+
+/// Content placed on the line currently being built
+#[derive(Debug)]
+enum Placed<'a> {
+    Text { path: Vec<usize>, x: f32, width: f32, text: String, kind: TextKind, style: &'a ComputedStyle },
+    Atomic { path: Vec<usize>, x: f32, width: f32, height: f32, baseline: f32, align: VerticalAlign },
+    /// Content edge of an inline box, positions boxes without content
+    Open { path: Vec<usize>, x: f32 },
+}
+
+/// Positions found by the line breaker, in formatting context coordinates
+#[derive(Debug, Default)]
+struct LineResults {
+    /// Line fragments of each text box
+    fragments: HashMap<Vec<usize>, Vec<InlineBox>>,
+    /// Margin box origin of each atomic box
+    atomics: HashMap<Vec<usize>, (f32, f32)>,
+    /// Content origin of each inline box where it starts
+    opens: HashMap<Vec<usize>, (f32, f32)>,
+    /// Margin box origin each absolutely positioned box would have in flow
+    statics: HashMap<Vec<usize>, (f32, f32)>,
+}
+
+/// Greedy line breaker for one inline formatting context
+struct LineBreaker<'a, 'f> {
+    floats: &'f mut FloatContext,
+    /// Content width of the formatting context root
+    container_width: f32,
+    /// Top of the current line
+    top: f32,
+    /// Band available to the current line beside the floats
+    left: f32,
+    right: f32,
+    /// Where the next piece of content goes
+    x: f32,
+    line: Vec<Placed<'a>>,
+    /// Items since the last break opportunity, placed together
+    pending: Vec<InlineItem<'a>>,
+    max_width: f32,
+    results: LineResults,
+    /// Height and baseline of the root inline box, the minimum every line
+    /// with content is sized to
+    strut: Option<(f32, f32)>,
+    /// Physical alignment of the content within each line
+    align: TextAlign,
+    /// Physical start side, the alignment of lines that aren't justified
+    start: TextAlign,
+    /// Indent of the first line from the start side, zero after it
+    indent: f32,
+}
+
+impl<'a, 'f> LineBreaker<'a, 'f> {
+    fn new(
+        floats: &'f mut FloatContext,
+        container_width: f32,
+        strut: Option<(f32, f32)>,
+        align: TextAlign,
+        start: TextAlign,
+        indent: f32,
+    ) -> Self {
+        let (left, right) = floats.available_band(0.0, 0.0, container_width);
+        let mut breaker = Self {
+            floats,
+            container_width,
+            strut,
+            align,
+            start,
+            indent,
+            top: 0.0,
+            left: 0.0,
+            right: 0.0,
+            x: 0.0,
+            line: Vec::new(),
+            pending: Vec::new(),
+            max_width: 0.0,
+            results: LineResults::default(),
+        };
+        (breaker.left, breaker.right) = breaker.indented(left, right);
+        breaker.x = breaker.left;
+        breaker
+    }
+
+    /// Narrow a band beside the floats by the indent of the current line
+    fn indented(&self, left: f32, right: f32) -> (f32, f32) {
+        match self.start {
+            TextAlign::Right => (left, right - self.indent),
+            _ => (left + self.indent, right),
+        }
+    }
+
+    /// Check if the current line has no content besides collapsible spaces
+    fn line_is_empty(&self) -> bool {
+        !self.line.iter().any(|p| match p {
+            Placed::Text { kind, .. } => *kind == TextKind::Word,
+            Placed::Atomic { .. } => true,
+            Placed::Open { .. } => false,
+        })
+    }
+
+    /// Height of the content placed on the current line so far
+    fn line_height(&self) -> f32 {
+        self.line
+            .iter()
+            .map(|p| match p {
+                Placed::Text { style, .. } => style.line_height,
+                Placed::Atomic { height, .. } => *height,
+                Placed::Open { .. } => 0.0,
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Feed the next item in document order
+    fn push(&mut self, item: InlineItem<'a>, root: &mut LayoutBox) {
+        match item {
+            InlineItem::Word { .. } | InlineItem::OpenBox { .. } | InlineItem::CloseBox { .. } => {
+                self.pending.push(item);
+            }
+            InlineItem::Space { path, width, style } => {
+                if style.white_space.wraps() {
+                    self.flush();
+                    self.place_space(path, width, style);
+                } else {
+                    // Not a break opportunity: keep with the surrounding text
+                    self.pending.push(InlineItem::Space { path, width, style });
+                }
+            }
+            InlineItem::SoftHyphen { path, style } => {
+                self.flush();
+                if !self.line_is_empty() {
+                    self.line.push(Placed::Text {
+                        path,
+                        x: self.x,
+                        width: 0.0,
+                        text: String::new(),
+                        kind: TextKind::SoftHyphen,
+                        style,
+                    });
+                }
+            }
+            InlineItem::LineBreak { path, style } => {
+                self.flush();
+                self.force_break(path, style);
+            }
+            InlineItem::Atomic { .. } => {
+                // Atomic boxes have break opportunities on both sides
+                self.flush();
+                self.pending.push(item);
+                self.flush();
+            }
+            InlineItem::Float { path } => {
+                self.flush();
+                self.place_float(&path, root);
+            }
+            InlineItem::Absolute { path, block_level } => {
+                // Block-level boxes would have started on a line of their own
+                self.flush();
+                let position = if !block_level {
+                    (self.x, self.top)
+                } else if self.line_is_empty() {
+                    (self.left, self.top)
+                } else {
+                    (self.left, self.top + self.line_height())
+                };
+                self.results.statics.insert(path, position);
+            }
+        }
+    }
+
+    /// Place the pending items, moving to a new line first if they don't fit
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let width: f32 = pending.iter().map(InlineItem::width).sum();
+        let height = pending.iter().map(InlineItem::height).fold(0.0, f32::max);
+
+        if self.x + width > self.right && !self.line_is_empty() {
+            self.break_line(width, height);
+        } else if self.x + width > self.right && !self.floats.is_empty() {
+            // Empty line too narrow beside the floats - move down past them
+            let (top, left, right) =
+                self.floats.find_band(self.top, height, width, self.container_width);
+            self.top = top;
+            self.move_line(left, right);
+        }
+
+        for item in pending {
+            match item {
+                InlineItem::Word { path, text, width, style } => self.place_word(path, text, width, style),
+                InlineItem::OpenBox { path, width } => {
+                    self.x += width;
+                    self.line.push(Placed::Open { path, x: self.x });
+                }
+                InlineItem::CloseBox { width } => self.x += width,
+                InlineItem::Space { path, width, style } => self.place_space(path, width, style),
+                InlineItem::Atomic { path, width, height, baseline, align } => {
+                    self.line.push(Placed::Atomic { path, x: self.x, width, height, baseline, align });
+                    self.x += width;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Place a word, breaking it between characters if it is wider than a whole line
+    fn place_word(&mut self, path: Vec<usize>, text: String, width: f32, style: &'a ComputedStyle) {
+        let (mut text, mut width) = (text, width);
+
+        while self.x + width > self.right && style.white_space.wraps() {
+            if !self.line_is_empty() {
+                self.break_line(width, style.line_height);
+                continue;
+            }
+
+            let (head, tail) = split_word(&text, style, self.right - self.x);
+            if tail.is_empty() {
+                break;
+            }
+            let head_width = measure_text(&head, style).width;
+            self.line.push(Placed::Text {
+                path: path.clone(),
+                x: self.x,
+                width: head_width,
+                text: head,
+                kind: TextKind::Word,
+                style,
+            });
+            self.x += head_width;
+
+            width = measure_text(&tail, style).width;
+            text = tail;
+            self.break_line(width, style.line_height);
+        }
+
+        self.line.push(Placed::Text { path, x: self.x, width, text, kind: TextKind::Word, style });
+        self.x += width;
+    }
+
+    /// Place whitespace
+    ///
+    /// Collapsible spaces are dropped at line starts and after other spaces;
+    /// preserved ones are kept like any other text.
+    fn place_space(&mut self, path: Vec<usize>, width: f32, style: &'a ComputedStyle) {
+        if !style.white_space.collapses_spaces() {
+            self.line.push(Placed::Text {
+                path,
+                x: self.x,
+                width,
+                text: " ".to_string(),
+                kind: TextKind::Word,
+                style,
+            });
+            self.x += width;
+            return;
+        }
+
+        let after_space = matches!(
+            self.line.iter().rev().find(|p| !matches!(p, Placed::Open { .. })),
+            Some(Placed::Text { kind: TextKind::Space, .. })
+        );
+        if self.line_is_empty() || after_space {
+            return;
+        }
+
+        self.line.push(Placed::Text {
+            path,
+            x: self.x,
+            width,
+            text: " ".to_string(),
+            kind: TextKind::Space,
+            style,
+        });
+        self.x += width;
+    }
+
+    /// End the current line at a preserved newline
+    fn force_break(&mut self, path: Vec<usize>, style: &'a ComputedStyle) {
+        // Collapsible spaces before the newline are removed
+        while matches!(self.line.last(), Some(Placed::Text { kind: TextKind::Space, .. })) {
+            self.line.pop();
+        }
+
+        self.line.push(Placed::Text {
+            path,
+            x: self.x,
+            width: 0.0,
+            text: String::new(),
+            kind: TextKind::LineBreak,
+            style,
+        });
+        self.break_line(0.0, 0.0);
+    }
+
+    /// Lay out a floated box and shorten the current line around it
+    fn place_float(&mut self, path: &[usize], root: &mut LayoutBox) {
+        // A float fits on the current line only while it is still empty
+        let empty = self.line_is_empty();
+        let float_y = if empty { self.top } else { self.top + self.line_height() };
+        let containing = ContainingBlock::with_auto_height(self.container_width);
+        layout_float(box_at_path(root, path), containing, self.floats, float_y);
+
+        if empty {
+            let (left, right) =
+                self.floats.available_band(self.top, self.line_height(), self.container_width);
+            self.move_line(left, right);
+        }
+    }
+
+    /// Move the band of an empty line, shifting what has been placed on it
+    fn move_line(&mut self, left: f32, right: f32) {
+        let (left, right) = self.indented(left, right);
+        let dx = left - self.left;
+        for placed in &mut self.line {
+            match placed {
+                Placed::Text { x, .. } | Placed::Atomic { x, .. } | Placed::Open { x, .. } => *x += dx,
+            }
+        }
+        self.x += dx;
+        self.left = left;
+        self.right = right;
+    }
+
+    /// Finish the current line and start the next one where content of
+    /// the given size fits beside the floats
+    fn break_line(&mut self, next_width: f32, next_height: f32) {
+        let height = self.finish_line(false);
+        self.indent = 0.0;
+        let (top, left, right) =
+            self.floats.find_band(self.top + height, next_height, next_width, self.container_width);
+        self.top = top;
+        self.left = left;
+        self.right = right;
+        self.x = left;
+    }
+
+    /// Trim the current line, align and position its content and record
+    /// the results, returns the line height
+    ///
+    /// `last` is set for the last line of the formatting context.
+    fn finish_line(&mut self, last: bool) -> f32 {
+        let mut line = std::mem::take(&mut self.line);
+        let forced = matches!(line.last(), Some(Placed::Text { kind: TextKind::LineBreak, .. }));
+
+        // Spaces hang at the end of the line and are removed
+        while matches!(line.last(), Some(Placed::Text { kind: TextKind::Space, .. })) {
+            line.pop();
+        }
+
+        // A line ending in a soft hyphen was broken there: show the hyphen
+        if let Some(Placed::Text { kind, text, width, style, .. }) = line.last_mut() {
+            if *kind == TextKind::SoftHyphen {
+                *kind = TextKind::Word;
+                *text = "-".to_string();
+                *width = measure_text("-", style).width;
+            }
+        }
+
+        // Shrink-wrapped containers have no edge to align to
+        let stretch = if self.container_width < f32::MAX {
+            self.align_line(&mut line, last || forced)
+        } else {
+            0.0
+        };
+
+        // (height, baseline, alignment) of each piece
+        let metrics = |placed: &Placed| match placed {
+            Placed::Text { style, .. } => {
+                let (height, baseline) = text_line_metrics(style);
+                Some((height, baseline, VerticalAlign::Baseline))
+            }
+            Placed::Atomic { height, baseline, align, .. } => Some((*height, *baseline, *align)),
+            Placed::Open { .. } => None,
+        };
+
+        // Baseline-aligned content determines where the baseline sits, the
+        // strut takes part unless the line is empty
+        let strut = self
+            .strut
+            .filter(|_| line.iter().any(|p| !matches!(p, Placed::Open { .. })))
+            .map(|(height, baseline)| (height, baseline, VerticalAlign::Baseline));
+        let baseline_items = line
+            .iter()
+            .filter_map(metrics)
+            .chain(strut)
+            .filter(|(_, _, align)| *align == VerticalAlign::Baseline);
+        let above = baseline_items.clone().map(|(_, b, _)| b).fold(0.0_f32, f32::max);
+        let below = baseline_items.map(|(h, b, _)| h - b).fold(0.0_f32, f32::max);
+
+        // Content aligned to the line edges may still make the line taller
+        let height = line
+            .iter()
+            .filter_map(metrics)
+            .map(|(h, _, _)| h)
+            .fold(above + below, f32::max);
 
-    // Track current position
-    let mut cursor_x = 0.0;
-    let mut cursor_y = 0.0;
-    let mut line_height = 0.0_f32;
-    let mut max_width = 0.0_f32;
-
-    for child in &mut parent.children {
-        let (child_width, child_height) = layout_inline_box(child, available_width - cursor_x);
-
-        // Check if we need to wrap to next line
-        if cursor_x + child_width > available_width && cursor_x > 0.0 {
-            // Start new line
-            cursor_y += line_height;
-            cursor_x = 0.0;
-            line_height = 0.0;
-        }
-
-        // Position this inline box
-        child.dimensions.content.x = cursor_x;
-        child.dimensions.content.y = cursor_y;
-
-        // Apply relative positioning offset
-        if let Some(style) = child.style() {
-            if style.position == Position::Relative {
-                // Apply left/right offset (left takes precedence)
-                if let Some(left) = style.left {
-                    child.dimensions.content.x += left;
-                } else if let Some(right) = style.right {
-                    child.dimensions.content.x -= right;
+        let top = self.top;
+        let item_y = |item_height: f32, baseline: f32, align: VerticalAlign| match align {
+            VerticalAlign::Baseline => top + above - baseline,
+            VerticalAlign::Top => top,
+            VerticalAlign::Middle => top + (height - item_height) / 2.0,
+            VerticalAlign::Bottom => top + height - item_height,
+        };
+
+        // Consecutive text from the same box becomes one fragment, except
+        // across stretched spaces, which the text would be painted over
+        let mut fragments: Vec<(Vec<usize>, InlineBox)> = Vec::new();
+        let mut joinable = true;
+        for placed in line {
+            match placed {
+                Placed::Text { kind: TextKind::LineBreak, .. } => {}
+                Placed::Text { path, x, width, text, style, kind } => {
+                    self.max_width = self.max_width.max(x + width);
+                    let join = std::mem::replace(&mut joinable, kind != TextKind::Space || stretch == 0.0);
+                    match fragments.last_mut() {
+                        Some((last, fragment)) if *last == path && join => {
+                            fragment.rect.width = x + width - fragment.rect.x;
+                            if let Some(t) = fragment.text.as_mut() {
+                                t.push_str(&text);
+                            }
+                        }
+                        _ => {
+                            let (text_height, baseline) = text_line_metrics(style);
+                            let y = item_y(text_height, baseline, VerticalAlign::Baseline);
+                            let rect = Rect::new(x, y, width, text_height);
+                            fragments.push((path, InlineBox::new(rect, Some(text), baseline)));
+                        }
+                    }
+                }
+                Placed::Atomic { path, x, width, height: item_height, baseline, align } => {
+                    self.max_width = self.max_width.max(x + width);
+                    let y = item_y(item_height, baseline, align);
+                    self.results.atomics.insert(path, (x, y));
+                }
+                Placed::Open { path, x } => {
+                    self.results.opens.entry(path).or_insert((x, top));
                 }
+            }
+        }
+
+        for (path, fragment) in fragments {
+            self.results.fragments.entry(path).or_default().push(fragment);
+        }
+
+        height
+    }
 
-                // Apply top/bottom offset (top takes precedence)
-                if let Some(top) = style.top {
-                    child.dimensions.content.y += top;
-                } else if let Some(bottom) = style.bottom {
-                    child.dimensions.content.y -= bottom;
+    /// Move the content of a finished line to its alignment in the band
+    ///
+    /// Justified lines stretch their spaces to fill the band, except for
+    /// the last line and lines ended by a forced break, which are aligned
+    /// to the start side. Returns the width added to each space.
+    fn align_line(&self, line: &mut [Placed], unjustified: bool) -> f32 {
+        let end = line
+            .iter()
+            .map(|p| match p {
+                Placed::Text { x, width, .. } | Placed::Atomic { x, width, .. } => x + width,
+                Placed::Open { x, .. } => *x,
+            })
+            .fold(self.left, f32::max);
+        let free = (self.right - end).max(0.0);
+
+        let align = match self.align {
+            TextAlign::Justify if unjustified => self.start,
+            align => align,
+        };
+        let spaces = line
+            .iter()
+            .filter(|p| matches!(p, Placed::Text { kind: TextKind::Space, .. }))
+            .count();
+        let (mut dx, stretch) = match align {
+            TextAlign::Right => (free, 0.0),
+            TextAlign::Center => (free / 2.0, 0.0),
+            TextAlign::Justify if spaces > 0 => (0.0, free / spaces as f32),
+            _ => return 0.0,
+        };
+
+        // Content after each stretched space moves by the extra width so far
+        for placed in line {
+            match placed {
+                Placed::Text { x, width, kind, .. } => {
+                    *x += dx;
+                    if *kind == TextKind::Space {
+                        *width += stretch;
+                        dx += stretch;
+                    }
                 }
+                Placed::Atomic { x, .. } | Placed::Open { x, .. } => *x += dx,
+            }
+        }
+        stretch
+    }
+
+    /// Place what is left and finish the last line, returns the total height
+    fn finish(&mut self) -> f32 {
+        self.flush();
+
+        // Soft hyphens and spaces at the very end are never shown
+        while matches!(
+            self.line.last(),
+            Some(Placed::Text { kind: TextKind::Space | TextKind::SoftHyphen, .. })
+        ) {
+            self.line.pop();
+        }
+
+        self.top + self.finish_line(true)
+    }
+}
+
+/// Style that aligns the lines of an inline formatting context
+///
+/// Anonymous blocks have none of their own; their content inherits it from
+/// the same parent, so it stands in.
+fn line_style<'a>(root: &LayoutBox<'a>) -> Option<&'a ComputedStyle> {
+    match root.box_type {
+        BoxType::AnonymousBlock => root.children.iter().find_map(|c| c.style()),
+        _ => root.style(),
+    }
+}
+
+/// Height and baseline position of a line of text in the given style
+///
+/// The line height's leading is split evenly above and below the glyphs.
+fn text_line_metrics(style: &ComputedStyle) -> (f32, f32) {
+    let metrics = measure_text("", style);
+    let half_leading = (metrics.height - metrics.ascent - metrics.descent) / 2.0;
+    (metrics.height, half_leading + metrics.ascent)
+}
+
+/// Split a word so the head fits the given width, keeping at least one character
+fn split_word(text: &str, style: &ComputedStyle, available: f32) -> (String, String) {
+    let mut split = 0;
+    for (index, ch) in text.char_indices() {
+        let end = index + ch.len_utf8();
+        if split > 0 && measure_text(&text[..end], style).width > available {
+            break;
+        }
+        split = end;
+    }
+    (text[..split].to_string(), text[split..].to_string())
+}
+
+/// Check if a character is whitespace that allows a line break
+fn is_breaking_space(ch: char) -> bool {
+    // No-break spaces are whitespace but keep words together
+    ch.is_whitespace() && !matches!(ch, '\u{A0}' | '\u{2007}' | '\u{202F}')
+}
+
+/// Tab stops are this many spaces apart in preserved whitespace
+const TAB_SIZE: usize = 8;
+
+/// Split the text of a text box into words, spaces, soft hyphens and
+/// preserved newlines
+fn push_text_items<'a>(
+    text: &str,
+    style: &'a ComputedStyle,
+    path: &[usize],
+    items: &mut Vec<InlineItem<'a>>,
+) {
+    let mut word = String::new();
+    let flush_word = |word: &mut String, items: &mut Vec<InlineItem<'a>>| {
+        if !word.is_empty() {
+            let text = std::mem::take(word);
+            let width = measure_text(&text, style).width;
+            items.push(InlineItem::Word { path: path.to_vec(), text, width, style });
+        }
+    };
+
+    let white_space = style.white_space;
+    let mut column = 0;
+
+    for ch in text.chars() {
+        if ch == '\n' && white_space.preserves_newlines() {
+            flush_word(&mut word, items);
+            items.push(InlineItem::LineBreak { path: path.to_vec(), style });
+            column = 0;
+        } else if is_breaking_space(ch) {
+            flush_word(&mut word, items);
+
+            // Preserved tabs advance to the next tab stop
+            let count = if ch == '\t' && !white_space.collapses_spaces() {
+                TAB_SIZE - column % TAB_SIZE
+            } else {
+                1
+            };
+            let width = measure_text(" ", style).width;
+            for _ in 0..count {
+                items.push(InlineItem::Space { path: path.to_vec(), width, style });
             }
+            column += count;
+        } else if ch == SOFT_HYPHEN {
+            flush_word(&mut word, items);
+            items.push(InlineItem::SoftHyphen { path: path.to_vec(), style });
+        } else {
+            word.push(ch);
+            column += 1;
+        }
+    }
+    flush_word(&mut word, items);
+}
+
+/// Check if a box's content flows into the surrounding line boxes
+fn is_inline_container(layout_box: &LayoutBox) -> bool {
+    matches!(layout_box.box_type, BoxType::Inline(_, _) | BoxType::AnonymousInline)
+        && !layout_box.children.iter().any(|c| c.is_in_flow_block())
+}
+
+/// Flatten the content of an inline formatting context into line breaker items
+///
+/// Atomic boxes are laid out here so their size is known.
+fn collect_inline_items<'a>(
+    parent: &mut LayoutBox<'a>,
+    path: &mut Vec<usize>,
+    available_width: f32,
+    items: &mut Vec<InlineItem<'a>>,
+) {
+    for (index, child) in parent.children.iter_mut().enumerate() {
+        path.push(index);
+
+        if child.is_floated() {
+            items.push(InlineItem::Float { path: path.clone() });
+        } else if child.is_absolutely_positioned() {
+            let block_level = child
+                .style()
+                .is_some_and(|s| !matches!(s.display, Display::Inline | Display::InlineBlock));
+            items.push(InlineItem::Absolute { path: path.clone(), block_level });
+        } else if let BoxType::Text(_, text, style) = &child.box_type {
+            push_text_items(text, style, path, items);
+        } else if let BoxType::LineBreak(_, style) = child.box_type {
+            items.push(InlineItem::LineBreak { path: path.clone(), style });
+        } else if is_inline_container(child) {
+            child.apply_style_edges(available_width);
+            let d = &child.dimensions;
+            let open = d.margin.left + d.border.left + d.padding.left;
+            let close = d.margin.right + d.border.right + d.padding.right;
+
+            items.push(InlineItem::OpenBox { path: path.clone(), width: open });
+            collect_inline_items(child, path, available_width, items);
+            items.push(InlineItem::CloseBox { width: close });
+        } else {
+            let (width, height) = layout_inline_box(child, available_width);
+            let baseline = box_baseline(child).unwrap_or(height);
+            let align = child.style().map(|s| s.vertical_align).unwrap_or_default();
+            items.push(InlineItem::Atomic { path: path.clone(), width, height, baseline, align });
         }
 
-        // Update cursor
-        cursor_x += child_width;
-        max_width = max_width.max(cursor_x);
-        line_height = line_height.max(child_height);
+        path.pop();
     }
+}
 
-    // Final line
-    cursor_y += line_height;
+/// Descendant of a box at the given child index path
+fn box_at_path<'b, 'a>(root: &'b mut LayoutBox<'a>, path: &[usize]) -> &'b mut LayoutBox<'a> {
+    path.iter().fold(root, |b, &index| &mut b.children[index])
+}
+
+/// Layout inline children, shortening line boxes around the given floats
+///
+/// Text is broken into lines at spaces and soft hyphens; inline boxes are
+/// walked into so their text wraps with the rest of the line. Floated
+/// children are placed into the float context as they are met.
+/// `parent_height` is the parent's definite content height, if it has one.
+/// Returns the height of the line boxes.
+pub(crate) fn layout_inline_children_with_floats(
+    parent: &mut LayoutBox,
+    floats: &mut FloatContext,
+    parent_height: Option<f32>,
+) -> f32 {
+    let available_width = parent.dimensions.content.width;
+
+    let mut items = Vec::new();
+    collect_inline_items(parent, &mut Vec::new(), available_width, &mut items);
+
+    let strut = parent.style().map(text_line_metrics);
+    let (align, start) = line_style(parent).map_or((TextAlign::Left, TextAlign::Left), |s| {
+        (s.text_align.physical(s.direction), TextAlign::Start.physical(s.direction))
+    });
+    // Percentages of an unknown width are treated as zero
+    let indent = line_style(parent).map_or(0.0, |s| match s.text_indent {
+        LengthPercentage::Percent(_) if available_width == f32::MAX => 0.0,
+        indent => indent.resolve(available_width),
+    });
+    let mut breaker = LineBreaker::new(floats, available_width, strut, align, start, indent);
+    for item in items {
+        breaker.push(item, parent);
+    }
+    let height = breaker.finish();
+    let max_width = breaker.max_width;
+    let mut results = breaker.results;
+
+    // Percentage offsets need a definite containing block height
+    let containing = ContainingBlock::with_height(available_width, parent_height);
+    place_inline_children(parent, &mut Vec::new(), &mut results, containing);
 
     // Set parent dimensions based on inline content
     // For inline elements (which set width to f32::MAX), shrink-wrap to content
     if parent.dimensions.content.width == f32::MAX || parent.dimensions.content.width == 0.0 {
         parent.dimensions.content.width = max_width;
     }
-    if parent.dimensions.content.height == 0.0 {
-        parent.dimensions.content.height = cursor_y;
+
+    height
+}
+
+/// Position the children of a box in an inline formatting context
+///
+/// Children are left in formatting context coordinates; returns the
+/// bounding box of the ones that were placed on a line.
+fn place_inline_children(
+    parent: &mut LayoutBox,
+    path: &mut Vec<usize>,
+    results: &mut LineResults,
+    containing: ContainingBlock,
+) -> Option<Rect> {
+    let mut bounds: Option<Rect> = None;
+
+    for (index, child) in parent.children.iter_mut().enumerate() {
+        path.push(index);
+
+        let rect = if child.is_floated() {
+            // Already placed by the float context
+            None
+        } else if child.is_absolutely_positioned() {
+            // Placed once its containing block is laid out, starting from here
+            let (x, y) = results.statics.remove(path.as_slice()).unwrap_or_default();
+            child.dimensions.content = Rect::new(x, y, 0.0, 0.0);
+            None
+        } else if let BoxType::Text(_, _, _) = child.box_type {
+            let mut fragments = results.fragments.remove(path.as_slice()).unwrap_or_default();
+            let rect = fragments.iter().map(|f| f.rect).reduce(|a, b| a.union(&b));
+            if let Some(rect) = rect {
+                for fragment in &mut fragments {
+                    fragment.rect.x -= rect.x;
+                    fragment.rect.y -= rect.y;
+                }
+                child.dimensions.content = rect;
+            } else {
+                // Nothing left after collapsing whitespace
+                child.dimensions.content = Rect::default();
+            }
+            child.fragments = fragments;
+            rect
+        } else if is_inline_container(child) {
+            let content = place_inline_children(child, path, results, containing).unwrap_or_else(|| {
+                let (x, y) = results.opens.get(path.as_slice()).copied().unwrap_or_default();
+                Rect::new(x, y, 0.0, 0.0)
+            });
+            child.dimensions.content = content;
+
+            // Make the box's own children relative to it
+            for grandchild in &mut child.children {
+                if matches!(grandchild.box_type, BoxType::Text(_, _, _)) && grandchild.fragments.is_empty() {
+                    continue;
+                }
+                grandchild.dimensions.content.x -= content.x;
+                grandchild.dimensions.content.y -= content.y;
+            }
+
+            let margin_box = child.dimensions.margin_box();
+            apply_relative_offset(child, containing);
+            Some(margin_box)
+        } else if let Some((x, y)) = results.atomics.remove(path.as_slice()) {
+            let d = &mut child.dimensions;
+            d.content.x = x + d.margin.left + d.border.left + d.padding.left;
+            d.content.y = y + d.margin.top + d.border.top + d.padding.top;
+
+            let margin_box = child.dimensions.margin_box();
+            apply_relative_offset(child, containing);
+            Some(margin_box)
+        } else {
+            None
+        };
+
+        if let Some(rect) = rect {
+            bounds = Some(bounds.map_or(rect, |b| b.union(&rect)));
+        }
+        path.pop();
+    }
+
+    bounds
+}
+
+/// Distance from the top of a laid out box's margin box to its baseline
+///
+/// This is the baseline of its last line of text; replaced elements and
+/// boxes without text have none.
+fn box_baseline(layout_box: &LayoutBox) -> Option<f32> {
+    match &layout_box.box_type {
+        BoxType::Text(_, _, style) => {
+            let last_line = layout_box.fragments.last().map_or(0.0, |f| f.rect.y);
+            Some(last_line + text_line_metrics(style).1)
+        }
+        BoxType::Input(_, _, _)
+        | BoxType::Button(_, _, _)
+        | BoxType::Image(_, _, _)
+        | BoxType::TextArea(_, _, _)
+        | BoxType::Select(_, _, _) => None,
+        _ => {
+            let d = &layout_box.dimensions;
+            let top_edges = d.margin.top + d.border.top + d.padding.top;
+            layout_box
+                .children
+                .iter()
+                .rev()
+                .filter(|c| !c.is_out_of_flow())
+                .find_map(|c| box_baseline(c).map(|b| c.dimensions.margin_box().y + b))
+                .map(|b| top_edges + b)
+        }
     }
 }
 
 /// Layout a single inline box, returns (width, height)
-fn layout_inline_box(layout_box: &mut LayoutBox, _available_width: f32) -> (f32, f32) {
+pub(crate) fn layout_inline_box(layout_box: &mut LayoutBox, available_width: f32) -> (f32, f32) {
     match &layout_box.box_type {
         BoxType::Text(_, text, style) => {
             // Measure text
@@ -119,29 +950,24 @@ fn layout_inline_box(layout_box: &mut LayoutBox, _available_width: f32) -> (f32,
             layout_box.dimensions.content.height = metrics.height;
             (metrics.width, metrics.height)
         }
-        BoxType::Inline(_, _style) => {
-            // Apply style edges
-            layout_box.apply_style_edges();
+        BoxType::Inline(_, _) if layout_box.children.iter().any(|c| c.is_in_flow_block()) => {
+            // Inline box containing blocks - lay it out like an inline-block
+            // taking the whole line
+            layout_block(layout_box, ContainingBlock::with_auto_height(available_width));
 
-            // Check for inline-block with explicit dimensions
-            let style = layout_box.style();
-            let has_explicit_width = style.as_ref().and_then(|s| s.width).is_some();
-            let has_explicit_height = style.as_ref().and_then(|s| s.height).is_some();
-            let is_inline_block = style.as_ref().map(|s| s.display == gugalanna_style::Display::InlineBlock).unwrap_or(false);
-
-            if is_inline_block && has_explicit_width {
-                // Use explicit width for inline-block
-                layout_box.dimensions.content.width = style.as_ref().unwrap().width.unwrap();
-            } else {
-                // For inline elements, set a large available width so children don't wrap
-                // The inline element will shrink-wrap to its content
-                layout_box.dimensions.content.width = f32::MAX;
-            }
+            (
+                layout_box.dimensions.margin_box_width(),
+                layout_box.dimensions.margin_box_height(),
+            )
+        }
+        BoxType::Inline(_, _) => {
+            // Apply style edges
+            layout_box.apply_style_edges(available_width);
 
-            if is_inline_block && has_explicit_height {
-                // Use explicit height for inline-block
-                layout_box.dimensions.content.height = style.as_ref().unwrap().height.unwrap();
-            }
+            // For inline elements, set a large available width so children don't wrap
+            // The inline element will shrink-wrap to its content
+            layout_box.dimensions.content.width = f32::MAX;
+            layout_box.dimensions.content.height = 0.0;
 
             // Layout children (if any)
             if !layout_box.children.is_empty() {
@@ -152,6 +978,25 @@ fn layout_inline_box(layout_box: &mut LayoutBox, _available_width: f32) -> (f32,
             let height = layout_box.dimensions.margin_box_height();
             (width, height)
         }
+        BoxType::InlineBlock(_, style) => {
+            // Atomic box: sized as a unit, contents laid out as a block
+            let specified_width = style.width;
+            layout_box.apply_style_edges(available_width);
+
+            let d = &layout_box.dimensions;
+            let edges = d.margin.horizontal() + d.padding.horizontal() + d.border.horizontal();
+            let width = match specified_width {
+                Some(w) => w,
+                None => intrinsic_widths(layout_box).shrink_to_fit((available_width - edges).max(0.0)),
+            };
+
+            layout_block(layout_box, ContainingBlock::with_auto_height(width + edges));
+
+            (
+                layout_box.dimensions.margin_box_width(),
+                layout_box.dimensions.margin_box_height(),
+            )
+        }
         BoxType::AnonymousInline | BoxType::AnonymousBlock => {
             // Layout children
             layout_inline_children(layout_box);
@@ -160,16 +1005,21 @@ fn layout_inline_box(layout_box: &mut LayoutBox, _available_width: f32) -> (f32,
             let height = layout_box.dimensions.content.height;
             (width, height)
         }
-        BoxType::Block(_, _) => {
+        BoxType::Block(_, _) | BoxType::TableCell(_, _, _) | BoxType::AnonymousTable => {
             // Block inside inline - treat as inline-block
             // This shouldn't happen in well-formed content
             (0.0, 0.0)
         }
+        BoxType::LineBreak(_, _) => {
+            // Only breaks lines inside an inline formatting context
+            layout_box.dimensions.content = Rect::default();
+            (0.0, 0.0)
+        }
         BoxType::Input(_, input_type, _) => {
             // Form input elements have intrinsic dimensions
             // Copy input_type before mutable borrow
             let input_type = *input_type;
-            layout_box.apply_style_edges();
+            layout_box.apply_style_edges(available_width);
 
             let (width, height) = input_intrinsic_size(input_type);
             layout_box.dimensions.content.width = width;
@@ -180,17 +1030,36 @@ fn layout_inline_box(layout_box: &mut LayoutBox, _available_width: f32) -> (f32,
                 layout_box.dimensions.margin_box_height(),
             )
         }
-        BoxType::Button(_, label, _) => {
-            // Button size based on label text - get style from layout_box
-            // Clone label before mutable borrow
-            let label = label.clone();
-            layout_box.apply_style_edges();
+        BoxType::TextArea(_, data, style) => {
+            // Sized by its rows and columns unless CSS says otherwise
+            let (width, height) = control_size(style, textarea_intrinsic_size(data, style));
+            layout_box.apply_style_edges(available_width);
 
-            let style = layout_box.style().unwrap();
-            let metrics = measure_text(&label, style);
-            // Add padding for button appearance
-            let width = metrics.width + 16.0; // 8px padding on each side
-            let height = metrics.height.max(24.0); // Minimum height of 24px
+            layout_box.dimensions.content.width = width;
+            layout_box.dimensions.content.height = height;
+
+            (
+                layout_box.dimensions.margin_box_width(),
+                layout_box.dimensions.margin_box_height(),
+            )
+        }
+        BoxType::Select(_, data, style) => {
+            // As wide as its widest option unless CSS says otherwise
+            let (width, height) = control_size(style, select_intrinsic_size(data, style));
+            layout_box.apply_style_edges(available_width);
+
+            layout_box.dimensions.content.width = width;
+            layout_box.dimensions.content.height = height;
+
+            (
+                layout_box.dimensions.margin_box_width(),
+                layout_box.dimensions.margin_box_height(),
+            )
+        }
+        BoxType::Button(_, label, style) => {
+            // Button size based on label text
+            let (width, height) = button_intrinsic_size(label, style);
+            layout_box.apply_style_edges(available_width);
 
             layout_box.dimensions.content.width = width;
             layout_box.dimensions.content.height = height;
@@ -204,7 +1073,7 @@ fn layout_inline_box(layout_box: &mut LayoutBox, _available_width: f32) -> (f32,
             // Image element with intrinsic dimensions
             // Clone image_data reference before mutable borrow
             let image_data = image_data.clone();
-            layout_box.apply_style_edges();
+            layout_box.apply_style_edges(available_width);
 
             let style = layout_box.style().unwrap();
             let (width, height) = compute_image_dimensions(style, &image_data);
@@ -220,8 +1089,105 @@ fn layout_inline_box(layout_box: &mut LayoutBox, _available_width: f32) -> (f32,
     }
 }
 
+/// Get intrinsic size for a button from its label
+pub(crate) fn button_intrinsic_size(label: &str, style: &ComputedStyle) -> (f32, f32) {
+    let metrics = measure_text(label, style);
+    // Add padding for button appearance
+    let width = metrics.width + 16.0; // 8px padding on each side
+    let height = metrics.height.max(24.0); // Minimum height of 24px
+    (width, height)
+}
+
+/// Space between the edge of a form control and its text
+pub const CONTROL_PADDING: f32 = 4.0;
+
+/// Width of the dropdown arrow at the end of a select
+pub const SELECT_ARROW_WIDTH: f32 = 16.0;
+
+/// Get intrinsic size for a textarea from its rows and columns
+pub(crate) fn textarea_intrinsic_size(data: &TextAreaData, style: &ComputedStyle) -> (f32, f32) {
+    let advance = measure_text("0", style).width;
+    let width = data.cols as f32 * advance + 2.0 * CONTROL_PADDING;
+    let height = data.rows as f32 * style.line_height + 2.0 * CONTROL_PADDING;
+    (width, height)
+}
+
+/// Lines a text area shows its text in, as byte ranges of the text: one
+/// for each of its lines, those wider than `width` wrapped at spaces, or
+/// between characters for a word wider still. The newlines are in no line.
+pub fn text_area_lines(text: &str, width: f32, mut measure: impl FnMut(&str) -> f32) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in text.split('\n') {
+        wrap_text_area_line(text, start..start + line.len(), width, &mut measure, &mut lines);
+        start += line.len() + 1;
+    }
+    lines
+}
+
+fn wrap_text_area_line(
+    text: &str,
+    range: Range<usize>,
+    width: f32,
+    measure: &mut impl FnMut(&str) -> f32,
+    lines: &mut Vec<Range<usize>>,
+) {
+    let mut line_start = range.start;
+    let mut line_width = 0.0;
+    let mut at = range.start;
+    // Words keep the spaces after them, which may hang past the edge
+    for word in text[range.clone()].split_inclusive(' ') {
+        let word_width = measure(word.trim_end_matches(' '));
+        if line_width + word_width > width && at > line_start {
+            lines.push(line_start..at);
+            line_start = at;
+            line_width = 0.0;
+        }
+        if word_width <= width {
+            line_width += measure(word);
+        } else {
+            for (i, c) in word.char_indices() {
+                let char_width = measure(&word[i..i + c.len_utf8()]);
+                if line_width + char_width > width && at + i > line_start {
+                    lines.push(line_start..at + i);
+                    line_start = at + i;
+                    line_width = 0.0;
+                }
+                line_width += char_width;
+            }
+        }
+        at += word.len();
+    }
+    lines.push(line_start..range.end);
+}
+
+/// Get intrinsic size for a select from its widest option
+pub(crate) fn select_intrinsic_size(data: &SelectData, style: &ComputedStyle) -> (f32, f32) {
+    let widest = data
+        .options
+        .iter()
+        .map(|option| measure_text(option, style).width)
+        .fold(0.0, f32::max);
+    let width = widest + 2.0 * CONTROL_PADDING + SELECT_ARROW_WIDTH;
+    let height = style.line_height + 2.0 * CONTROL_PADDING;
+    (width, height)
+}
+
+/// Size of a form control, its intrinsic size where CSS leaves width or
+/// height auto
+///
+/// Controls are sized before the line they sit on, so percentage heights
+/// behave as auto.
+pub(crate) fn control_size(style: &ComputedStyle, (width, height): (f32, f32)) -> (f32, f32) {
+    let height = match style.height {
+        Some(LengthPercentage::Px(h)) => h,
+        _ => height,
+    };
+    (style.width.unwrap_or(width), height)
+}
+
 /// Get intrinsic size for a form input based on type
-fn input_intrinsic_size(input_type: InputType) -> (f32, f32) {
+pub(crate) fn input_intrinsic_size(input_type: InputType) -> (f32, f32) {
     match input_type {
         InputType::Text | InputType::Password => {
             // Default text input size
@@ -235,6 +1201,10 @@ fn input_intrinsic_size(input_type: InputType) -> (f32, f32) {
             // Button with default size
             (80.0, 24.0)
         }
+        InputType::File => {
+            // Room for the button and a short file name
+            (240.0, 24.0)
+        }
         InputType::Hidden => {
             // Hidden inputs have no size
             (0.0, 0.0)
@@ -244,7 +1214,7 @@ fn input_intrinsic_size(input_type: InputType) -> (f32, f32) {
 
 /// Compute image dimensions based on CSS, attributes, and intrinsic size
 /// Priority: CSS > HTML attributes > intrinsic (from decoded image) > placeholder (300x150)
-fn compute_image_dimensions(style: &ComputedStyle, image_data: &ImageData) -> (f32, f32) {
+pub(crate) fn compute_image_dimensions(style: &ComputedStyle, image_data: &ImageData) -> (f32, f32) {
     const PLACEHOLDER_WIDTH: f32 = 300.0;
     const PLACEHOLDER_HEIGHT: f32 = 150.0;
 
@@ -262,9 +1232,13 @@ fn compute_image_dimensions(style: &ComputedStyle, image_data: &ImageData) -> (f
         _ => None,
     };
 
-    // CSS width/height are Option<f32>
+    // Images are sized before the line they sit on, so percentage heights
+    // behave as auto
     let css_width = style.width;
-    let css_height = style.height;
+    let css_height = match style.height {
+        Some(LengthPercentage::Px(h)) => Some(h),
+        _ => None,
+    };
 
     match (css_width, css_height) {
         // Both CSS dimensions specified
@@ -319,6 +1293,381 @@ pub fn calculate_line_height(boxes: &[LayoutBox]) -> f32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::setup_and_layout;
+
+    const INLINE_BLOCK_CSS: &str = "div { display: block; font-size: 10px; line-height: 20px; } \
+        .b { display: inline-block; width: 100px; height: 20px; }";
+
+    fn positions(layout: &LayoutBox) -> Vec<(f32, f32)> {
+        layout
+            .children
+            .iter()
+            .map(|c| (c.dimensions.content.x, c.dimensions.content.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_inline_blocks_share_a_line() {
+        let layout = setup_and_layout(
+            "<div><span class='b'>A</span><span class='b'>B</span><span class='b'>C</span></div>",
+            INLINE_BLOCK_CSS,
+            400.0,
+        );
+
+        assert!(layout.children.iter().all(|c| matches!(c.box_type, BoxType::InlineBlock(_, _))));
+        assert_eq!(positions(&layout), vec![(0.0, 0.0), (100.0, 0.0), (200.0, 0.0)]);
+        assert_eq!(layout.dimensions.content.height, 20.0);
+    }
+
+    #[test]
+    fn test_inline_blocks_wrap_when_narrow() {
+        let layout = setup_and_layout(
+            "<div><span class='b'>A</span><span class='b'>B</span><span class='b'>C</span></div>",
+            INLINE_BLOCK_CSS,
+            250.0,
+        );
+
+        assert_eq!(positions(&layout), vec![(0.0, 0.0), (100.0, 0.0), (0.0, 20.0)]);
+        assert_eq!(layout.dimensions.content.height, 40.0);
+    }
+
+    #[test]
+    fn test_inline_block_shrink_to_fit() {
+        let layout = setup_and_layout(
+            "<div><span class='ib'>aaaa</span><span class='ib'>aa aa aa aa</span></div>",
+            "div { display: block; font-size: 10px; line-height: 20px; } \
+             .ib { display: inline-block; padding-left: 5px; padding-right: 5px; }",
+            60.0,
+        );
+
+        // Fits its content: 4 characters at 6px each
+        let first = &layout.children[0];
+        assert_eq!(first.dimensions.content.width, 24.0);
+        assert_eq!(first.dimensions.content.x, 5.0);
+
+        // Too wide for the container: shrinks to the available width
+        let second = &layout.children[1];
+        assert_eq!(second.dimensions.content.width, 50.0);
+        assert_eq!(second.dimensions.content.y, 20.0);
+    }
+
+    const CONTROL_CSS: &str = "div { display: block; font-size: 10px; line-height: 20px; }";
+
+    #[test]
+    fn test_textarea_sized_by_rows_and_cols() {
+        let layout = setup_and_layout(
+            "<div><textarea rows='5' cols='40'>hi</textarea><textarea></textarea></div>",
+            CONTROL_CSS,
+            800.0,
+        );
+
+        // 40 characters at 6px and 5 lines of 20px, inside the padding
+        let sized = &layout.children[0];
+        assert!(matches!(&sized.box_type, BoxType::TextArea(_, data, _) if data.text == "hi"));
+        assert_eq!(sized.dimensions.content.width, 248.0);
+        assert_eq!(sized.dimensions.content.height, 108.0);
+
+        // Without attributes, 20 columns and 2 rows
+        let default = &layout.children[1];
+        assert_eq!(default.dimensions.content.width, 128.0);
+        assert_eq!(default.dimensions.content.height, 48.0);
+    }
+
+    #[test]
+    fn test_css_size_overrides_textarea_rows_and_cols() {
+        let layout = setup_and_layout(
+            "<div><textarea rows='5' cols='40'></textarea></div>",
+            "div { display: block; font-size: 10px; line-height: 20px; } \
+             textarea { width: 300px; height: 50px; }",
+            800.0,
+        );
+
+        let d = &layout.children[0].dimensions;
+        assert_eq!((d.content.width, d.content.height), (300.0, 50.0));
+    }
+
+    #[test]
+    fn test_select_sized_by_widest_option() {
+        let layout = setup_and_layout(
+            "<div><select><option>a</option><optgroup><option selected>longer one</option></optgroup></select></div>",
+            CONTROL_CSS,
+            800.0,
+        );
+
+        let select = &layout.children[0];
+        let BoxType::Select(_, data, _) = &select.box_type else {
+            panic!("expected a select box");
+        };
+        assert_eq!(data.options, vec!["a", "longer one"]);
+        assert_eq!(data.selected, 1);
+
+        // The widest option and the arrow, inside the padding
+        assert_eq!(select.dimensions.content.width, 60.0 + 8.0 + SELECT_ARROW_WIDTH);
+        assert_eq!(select.dimensions.content.height, 28.0);
+    }
+
+    #[test]
+    fn test_vertical_align_in_line_box() {
+        let layout = setup_and_layout(
+            "<div><span class='tall'></span><span class='top'></span><span class='bottom'></span></div>",
+            "div { display: block; font-size: 10px; line-height: 20px; } \
+             span { display: inline-block; width: 10px; height: 10px; } \
+             .tall { height: 40px; } \
+             .top { vertical-align: top; } \
+             .bottom { vertical-align: bottom; }",
+            400.0,
+        );
+
+        // The strut hangs 7px below the baseline the tall box sits on
+        assert_eq!(positions(&layout), vec![(0.0, 0.0), (10.0, 0.0), (20.0, 37.0)]);
+    }
+
+    #[test]
+    fn test_mixed_font_sizes_share_baseline() {
+        let layout = setup_and_layout(
+            "<div><span>small</span><span class='big'>big</span></div>",
+            "div { display: block; font-size: 16px; line-height: 1.5; } .big { font-size: 32px; }",
+            400.0,
+        );
+
+        // The line is as tall as the 32px text's line height, 1.5 times its font size
+        assert_eq!(layout.dimensions.content.height, 48.0);
+
+        // (top, baseline) of the text of a span, in the div's coordinates
+        let text_line = |span: &LayoutBox| {
+            let text = &span.children[0];
+            let fragment = &text.fragments[0];
+            let top = span.dimensions.content.y + text.dimensions.content.y + fragment.rect.y;
+            (top, top + fragment.baseline)
+        };
+        let (small_top, small_baseline) = text_line(&layout.children[0]);
+        let (big_top, big_baseline) = text_line(&layout.children[1]);
+        assert_eq!(big_top, 0.0);
+        assert!(small_top > big_top);
+
+        // Both runs of text sit on the same baseline
+        assert!((small_baseline - big_baseline).abs() < 0.01);
+    }
+
+    const TEXT_CSS: &str = "div { display: block; font-size: 10px; line-height: 20px; }";
+
+    /// (text, x, y) of each line fragment of a text box
+    fn fragments(text_box: &LayoutBox) -> Vec<(String, f32, f32)> {
+        text_box
+            .fragments
+            .iter()
+            .map(|f| (f.text.clone().unwrap(), f.rect.x, f.rect.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_text_wraps_at_spaces() {
+        // 6px per character: "aaa bbb" is 42px, "aaa bbb ccc" would be 66px
+        let layout = setup_and_layout("<div>aaa bbb ccc ddd</div>", TEXT_CSS, 50.0);
+
+        let text = &layout.children[0];
+        assert_eq!(
+            fragments(text),
+            vec![("aaa bbb".to_string(), 0.0, 0.0), ("ccc ddd".to_string(), 0.0, 20.0)]
+        );
+        assert_eq!(text.fragments[0].rect.width, 42.0);
+        assert_eq!(layout.dimensions.content.height, 40.0);
+    }
+
+    #[test]
+    fn test_br_forces_line_break() {
+        let layout = setup_and_layout("<div>a<br>b</div>", TEXT_CSS, 400.0);
+
+        assert_eq!(fragments(&layout.children[0]), vec![("a".to_string(), 0.0, 0.0)]);
+        let b = &layout.children[2];
+        assert_eq!(b.dimensions.content.y, 20.0);
+        assert_eq!(layout.dimensions.content.height, 40.0);
+
+        // A break at the end of the content adds no empty line
+        let trailing = setup_and_layout("<div>a<br></div>", TEXT_CSS, 400.0);
+        assert_eq!(trailing.dimensions.content.height, 20.0);
+    }
+
+    #[test]
+    fn test_rtl_lines_align_to_the_right_edge() {
+        let layout = setup_and_layout("<div dir='rtl'>aaaa aaaa aaaa<br>aa</div>", TEXT_CSS, 60.0);
+
+        // Each line ends at the right edge: 54px, 24px and 12px of text
+        let first = &layout.children[0];
+        let x = first.dimensions.content.x;
+        let starts: Vec<_> = first.fragments.iter().map(|f| x + f.rect.x).collect();
+        assert_eq!(starts, vec![6.0, 36.0]);
+        let last = &layout.children[2];
+        assert_eq!(last.dimensions.content.x, 48.0);
+        assert_eq!(last.dimensions.content.y, 40.0);
+
+        // Explicit end alignment flips back to the left
+        let end = setup_and_layout("<div dir='rtl' style='text-align: end'>aa</div>", TEXT_CSS, 60.0);
+        assert_eq!(end.children[0].dimensions.content.x, 0.0);
+    }
+
+    /// Start of each line fragment of a text box in its parent's content box
+    fn line_starts(text_box: &LayoutBox) -> Vec<(f32, f32)> {
+        let content = text_box.dimensions.content;
+        text_box.fragments.iter().map(|f| (content.x + f.rect.x, content.y + f.rect.y)).collect()
+    }
+
+    #[test]
+    fn test_text_align_center_and_right() {
+        let css = |align: &str| format!("{} .a {{ text-align: {}; }}", TEXT_CSS, align);
+
+        // 30px of text on each line of a 60px container
+        let center = setup_and_layout("<div class='a'>aa bb ccccc</div>", &css("center"), 60.0);
+        assert_eq!(line_starts(&center.children[0]), vec![(15.0, 0.0), (15.0, 20.0)]);
+
+        let right = setup_and_layout("<div class='a'>aa bb ccccc</div>", &css("right"), 60.0);
+        assert_eq!(line_starts(&right.children[0]), vec![(30.0, 0.0), (30.0, 20.0)]);
+    }
+
+    #[test]
+    fn test_text_align_justify() {
+        let css = format!("{} .a {{ text-align: justify; }}", TEXT_CSS);
+        let layout = setup_and_layout("<div class='a'>aa <span>bb</span> ccccc</div>", &css, 60.0);
+
+        // The space takes all 30px left over, pushing the second word to
+        // the right edge
+        let first = &layout.children[0];
+        assert_eq!(first.fragments[0].rect.width, 48.0);
+        assert_eq!(layout.children[1].dimensions.content.x, 48.0);
+
+        // Words after a stretched space get fragments of their own
+        let single = setup_and_layout("<div class='a'>aa bb ccccc</div>", &css, 60.0);
+        assert_eq!(line_starts(&single.children[0]), vec![(0.0, 0.0), (48.0, 0.0), (0.0, 20.0)]);
+
+        // The last line keeps its start alignment
+        assert_eq!(line_starts(&layout.children[2]), vec![(0.0, 20.0)]);
+
+        // So does a line ended by a forced break
+        let forced = setup_and_layout("<div class='a'>aa bb<br>cc</div>", &css, 60.0);
+        assert_eq!(forced.children[0].fragments[0].rect.width, 30.0);
+    }
+
+    #[test]
+    fn test_text_indent_moves_the_first_line() {
+        let css = |indent: &str| format!("{} .a {{ text-indent: {}; }}", TEXT_CSS, indent);
+
+        // "aaaa" fits beside the indent, the rest wraps to the start edge
+        let layout = setup_and_layout("<div class='a'>aaaa bbbb cccc</div>", &css("32px"), 60.0);
+        assert_eq!(line_starts(&layout.children[0]), vec![(32.0, 0.0), (0.0, 20.0)]);
+
+        // A hanging indent starts the first line before the content box and
+        // leaves it more room
+        let hanging = setup_and_layout("<div class='a'>aaaaaaaaaaa bb</div>", &css("-12px"), 60.0);
+        assert_eq!(line_starts(&hanging.children[0]), vec![(-12.0, 0.0), (0.0, 20.0)]);
+
+        // Right to left, the indent is on the right
+        let rtl = setup_and_layout("<div class='a' dir='rtl'>aaaa bbbb</div>", &css("50%"), 60.0);
+        assert_eq!(line_starts(&rtl.children[0]), vec![(6.0, 0.0), (36.0, 20.0)]);
+    }
+
+    #[test]
+    fn test_consecutive_brs_leave_empty_line() {
+        let layout = setup_and_layout("<div>a<br><br>b</div>", TEXT_CSS, 400.0);
+
+        let b = &layout.children[3];
+        assert_eq!(b.dimensions.content.y, 40.0);
+        assert_eq!(layout.dimensions.content.height, 60.0);
+    }
+
+    #[test]
+    fn test_long_word_breaks_between_characters() {
+        let layout = setup_and_layout("<div>b aaaaaaaaaa</div>", TEXT_CSS, 40.0);
+
+        assert_eq!(
+            fragments(&layout.children[0]),
+            vec![
+                ("b".to_string(), 0.0, 0.0),
+                ("aaaaaa".to_string(), 0.0, 20.0),
+                ("aaaa".to_string(), 0.0, 40.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_wraps_across_inline_boxes() {
+        let layout = setup_and_layout("<div>aaa <b>bbb ccc</b> ddd</div>", TEXT_CSS, 50.0);
+
+        // The inline box spans both lines
+        let bold = &layout.children[1];
+        assert_eq!(bold.dimensions.content, Rect::new(0.0, 0.0, 42.0, 40.0));
+        assert_eq!(
+            fragments(&bold.children[0]),
+            vec![("bbb".to_string(), 24.0, 0.0), ("ccc".to_string(), 0.0, 20.0)]
+        );
+
+        // The space after the inline box belongs to the following text
+        let last = &layout.children[2];
+        assert_eq!((last.dimensions.content.x, last.dimensions.content.y), (18.0, 20.0));
+        assert_eq!(fragments(last), vec![(" ddd".to_string(), 0.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_soft_hyphen_break() {
+        let layout = setup_and_layout("<div>aaaa\u{AD}bbbb</div>", TEXT_CSS, 40.0);
+
+        assert_eq!(
+            fragments(&layout.children[0]),
+            vec![("aaaa-".to_string(), 0.0, 0.0), ("bbbb".to_string(), 0.0, 20.0)]
+        );
+    }
+
+    #[test]
+    fn test_text_area_lines() {
+        // Ten pixels a character
+        let measure = |text: &str| text.chars().count() as f32 * 10.0;
+        let text = "one two three\n\nabcdefghij\n";
+        let lines: Vec<_> = text_area_lines(text, 80.0, measure).into_iter().map(|line| &text[line]).collect();
+        assert_eq!(lines, ["one two ", "three", "", "abcdefgh", "ij", ""]);
+    }
+
+    /// Line fragment texts of "aa bb\ncc  dd" laid out 30px wide
+    fn wrap_with_white_space(white_space: &str) -> Vec<String> {
+        let css = format!("{} div {{ white-space: {}; }}", TEXT_CSS, white_space);
+        let layout = setup_and_layout("<div>aa bb\ncc  dd</div>", &css, 30.0);
+        fragments(&layout.children[0]).into_iter().map(|(text, _, _)| text).collect()
+    }
+
+    #[test]
+    fn test_white_space_normal() {
+        assert_eq!(wrap_with_white_space("normal"), vec!["aa bb", "cc dd"]);
+    }
+
+    #[test]
+    fn test_white_space_nowrap() {
+        assert_eq!(wrap_with_white_space("nowrap"), vec!["aa bb cc dd"]);
+    }
+
+    #[test]
+    fn test_white_space_pre() {
+        assert_eq!(wrap_with_white_space("pre"), vec!["aa bb", "cc  dd"]);
+    }
+
+    #[test]
+    fn test_white_space_pre_wrap() {
+        assert_eq!(wrap_with_white_space("pre-wrap"), vec!["aa bb", "cc  ", "dd"]);
+    }
+
+    #[test]
+    fn test_white_space_pre_line() {
+        assert_eq!(wrap_with_white_space("pre-line"), vec!["aa bb", "cc dd"]);
+    }
+
+    #[test]
+    fn test_pre_keeps_blank_lines() {
+        let css = format!("{} div {{ white-space: pre; }}", TEXT_CSS);
+        let layout = setup_and_layout("<div>a\n\n\tb</div>", &css, 300.0);
+
+        assert_eq!(
+            fragments(&layout.children[0]),
+            vec![("a".to_string(), 0.0, 0.0), ("        b".to_string(), 0.0, 40.0)]
+        );
+        assert_eq!(layout.dimensions.content.height, 60.0);
+    }
 
     #[test]
     fn test_split_words() {
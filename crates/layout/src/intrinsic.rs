@@ -0,0 +1,253 @@
+//! Intrinsic Sizing
+//!
+//! Measures the minimum and preferred widths of a box's contents without
+//! laying it out, for shrink-to-fit sizing.
+
+use gugalanna_style::{ComputedStyle, LengthPercentage};
+
+use crate::boxtree::{BoxType, LayoutBox};
+use crate::inline::{
+    button_intrinsic_size, compute_image_dimensions, control_size, input_intrinsic_size, select_intrinsic_size,
+    textarea_intrinsic_size,
+};
+use crate::table::{is_table, table_intrinsic_widths};
+use crate::text::measure_text;
+
+/// Intrinsic widths of a box's content area
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IntrinsicWidths {
+    /// Narrowest width the content can take without overflowing (min-content)
+    pub min: f32,
+    /// Width the content takes when nothing wraps (max-content)
+    pub max: f32,
+}
+
+impl IntrinsicWidths {
+    fn fixed(width: f32) -> Self {
+        Self { min: width, max: width }
+    }
+
+    /// Shrink-to-fit width for the given available width
+    pub fn shrink_to_fit(&self, available: f32) -> f32 {
+        self.max.min(available.max(self.min))
+    }
+}
+
+/// Measure the min-content and max-content widths of a box's content area
+pub fn measure_intrinsic_widths(layout_box: &LayoutBox) -> (f32, f32) {
+    let widths = intrinsic_widths(layout_box);
+    (widths.min, widths.max)
+}
+
+/// Intrinsic content widths of a box, cached on the box after the first measurement
+pub(crate) fn intrinsic_widths(layout_box: &LayoutBox) -> IntrinsicWidths {
+    if let Some(widths) = layout_box.intrinsic_widths.get() {
+        return widths;
+    }
+    let widths = measure(layout_box);
+    layout_box.intrinsic_widths.set(Some(widths));
+    widths
+}
+
+fn measure(layout_box: &LayoutBox) -> IntrinsicWidths {
+    match &layout_box.box_type {
+        BoxType::Text(_, text, style) => text_widths(text, style),
+        BoxType::Input(_, input_type, _) => IntrinsicWidths::fixed(input_intrinsic_size(*input_type).0),
+        BoxType::Button(_, label, style) => IntrinsicWidths::fixed(button_intrinsic_size(label, style).0),
+        BoxType::TextArea(_, data, style) => {
+            IntrinsicWidths::fixed(control_size(style, textarea_intrinsic_size(data, style)).0)
+        }
+        BoxType::Select(_, data, style) => {
+            IntrinsicWidths::fixed(control_size(style, select_intrinsic_size(data, style)).0)
+        }
+        BoxType::Image(_, image_data, style) => {
+            IntrinsicWidths::fixed(compute_image_dimensions(style, image_data).0)
+        }
+        _ => {
+            if let Some(width) = layout_box.style().and_then(|s| s.width) {
+                return IntrinsicWidths::fixed(width);
+            }
+            if is_table(layout_box) {
+                return table_intrinsic_widths(layout_box);
+            }
+            children_widths(layout_box)
+        }
+    }
+}
+
+/// Intrinsic widths of a box including its horizontal margins, borders and padding
+pub(crate) fn outer_widths(layout_box: &LayoutBox) -> IntrinsicWidths {
+    let inner = intrinsic_widths(layout_box);
+    let edges = match (&layout_box.box_type, layout_box.style()) {
+        (BoxType::Text(_, _, _), _) | (_, None) => 0.0,
+        (_, Some(style)) => horizontal_edges(style),
+    };
+    IntrinsicWidths {
+        min: inner.min + edges,
+        max: inner.max + edges,
+    }
+}
+
+/// Combine the widths of a box's children
+fn children_widths(layout_box: &LayoutBox) -> IntrinsicWidths {
+    let has_block_children = layout_box.children.iter().any(|c| c.is_in_flow_block());
+    let mut result = IntrinsicWidths::default();
+    let mut line = 0.0_f32;
+    // Percentages depend on the width being computed, so they count as zero
+    let mut indent = layout_box.style().map_or(0.0, |s| match s.text_indent {
+        LengthPercentage::Px(px) => px,
+        LengthPercentage::Percent(_) => 0.0,
+    });
+
+    for child in layout_box.children.iter().filter(|c| !c.is_absolutely_positioned()) {
+        let child_widths = outer_widths(child);
+
+        // The first inline content starts after the indent
+        let first_indent = if has_block_children && child.is_in_flow_block() {
+            0.0
+        } else {
+            std::mem::take(&mut indent)
+        };
+        result.min = result.min.max(child_widths.min + first_indent);
+        line += first_indent;
+
+        if let BoxType::LineBreak(_, _) = child.box_type {
+            // A forced break ends the line
+            result.max = result.max.max(line);
+            line = 0.0;
+        } else if has_block_children && child.is_in_flow_block() {
+            // Blocks stack vertically
+            result.max = result.max.max(line).max(child_widths.max);
+            line = 0.0;
+        } else {
+            // Inline content and floats sit side by side on one line
+            line += child_widths.max;
+        }
+    }
+
+    result.max = result.max.max(line);
+    result
+}
+
+/// Text is at least as wide as its longest word, or its longest line
+/// when it may not wrap
+fn text_widths(text: &str, style: &ComputedStyle) -> IntrinsicWidths {
+    let max = if style.white_space.preserves_newlines() {
+        text.split('\n')
+            .map(|line| measure_text(line, style).width)
+            .fold(0.0_f32, f32::max)
+    } else {
+        measure_text(text, style).width
+    };
+
+    if !style.white_space.wraps() {
+        return IntrinsicWidths::fixed(max);
+    }
+
+    let min = text
+        .split_whitespace()
+        .map(|word| measure_text(word, style).width)
+        .fold(0.0_f32, f32::max);
+    IntrinsicWidths { min, max }
+}
+
+/// Sum of the horizontal margins, borders and padding of a style
+///
+/// Percentages depend on the width being computed, so they count as zero,
+/// like auto margins.
+fn horizontal_edges(style: &ComputedStyle) -> f32 {
+    let px = |length: LengthPercentage| match length {
+        LengthPercentage::Px(px) => px,
+        LengthPercentage::Percent(_) => 0.0,
+    };
+    style.margin_left.map_or(0.0, px)
+        + style.margin_right.map_or(0.0, px)
+        + style.border_left_width
+        + style.border_right_width
+        + px(style.padding_left)
+        + px(style.padding_right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::build;
+    use gugalanna_style::WhiteSpace;
+
+    #[test]
+    fn test_text_widths() {
+        let style = ComputedStyle {
+            font_size: 10.0,
+            ..ComputedStyle::default()
+        };
+
+        // 6px per character
+        let widths = text_widths("aa aaaa a", &style);
+        assert_eq!(widths.min, 24.0);
+        assert_eq!(widths.max, 54.0);
+    }
+
+    #[test]
+    fn test_preformatted_text_widths() {
+        let style = ComputedStyle {
+            font_size: 10.0,
+            white_space: WhiteSpace::Pre,
+            ..ComputedStyle::default()
+        };
+
+        // Cannot wrap, so as wide as the longest line
+        let widths = text_widths("aa bb\ncccc", &style);
+        assert_eq!(widths, IntrinsicWidths { min: 30.0, max: 30.0 });
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let widths = IntrinsicWidths { min: 50.0, max: 200.0 };
+        assert_eq!(widths.shrink_to_fit(300.0), 200.0);
+        assert_eq!(widths.shrink_to_fit(100.0), 100.0);
+        assert_eq!(widths.shrink_to_fit(20.0), 50.0);
+    }
+
+    #[test]
+    fn test_measure_text_only_content() {
+        let (_, layout) = build("<div>aaa aaaaa aa</div>", "div { display: block; font-size: 10px; }");
+
+        // Longest word, then the whole run on one line
+        assert_eq!(measure_intrinsic_widths(&layout), (30.0, 72.0));
+    }
+
+    #[test]
+    fn test_measure_indented_text() {
+        let (_, layout) = build(
+            "<div>aaa aaaaa aa</div>",
+            "div { display: block; font-size: 10px; text-indent: 20px; }",
+        );
+
+        // The indent is added to the first line and the word starting it
+        assert_eq!(measure_intrinsic_widths(&layout), (50.0, 92.0));
+    }
+
+    #[test]
+    fn test_measure_mixed_content() {
+        let (_, layout) = build(
+            "<div>aa <img width='40' height='10'> aaa<p class='p'>aaaaaa</p></div>",
+            "div { display: block; font-size: 10px; } \
+             .p { display: block; padding-left: 10px; padding-right: 10px; }",
+        );
+
+        // The image is the widest unbreakable piece of the first line, the
+        // padded paragraph the widest block
+        let (min, max) = measure_intrinsic_widths(&layout);
+        assert_eq!(min, 56.0);
+        assert_eq!(max, 18.0 + 40.0 + 24.0);
+    }
+
+    #[test]
+    fn test_widths_are_cached() {
+        let (_, layout) = build("<div>aa aaaa</div>", "div { display: block; font-size: 10px; }");
+        assert_eq!(layout.intrinsic_widths.get(), None);
+
+        let widths = intrinsic_widths(&layout);
+        assert_eq!(layout.intrinsic_widths.get(), Some(widths));
+    }
+}
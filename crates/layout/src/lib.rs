@@ -5,14 +5,30 @@
 mod boxtree;
 mod block;
 mod flex;
+mod float;
+mod grid;
 mod inline;
+mod intrinsic;
+mod overflow;
+mod positioned;
+mod relayout;
+mod table;
+#[cfg(test)]
+mod test_util;
 mod text;
 
-pub use boxtree::{LayoutBox, BoxType, InputType, ImageData, ImagePixels, build_layout_tree};
-pub use block::layout_block;
+pub use boxtree::{
+    LayoutBox, BoxType, CellSpan, InputType, ImageData, ImagePixels, SelectData, TextAreaData, build_layout_tree,
+    select_data,
+};
+pub use block::{layout_block, layout_document};
 pub use flex::layout_flex;
-pub use inline::{LineBox, InlineBox};
-pub use text::TextMetrics;
+pub use inline::{text_area_lines, LineBox, InlineBox, CONTROL_PADDING, SELECT_ARROW_WIDTH};
+pub use relayout::{mark_node_dirty, relayout_document, reuse_layout_of};
+pub use intrinsic::measure_intrinsic_widths;
+pub use text::{measure_text, set_font_measurer, SimpleTextMeasurer, TextMeasurer, TextMetrics};
+
+use serde::{Deserialize, Serialize};
 
 /// Box dimensions
 #[derive(Debug, Clone, Copy, Default)]
@@ -28,7 +44,7 @@ pub struct Dimensions {
 }
 
 /// A rectangle
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -115,6 +131,35 @@ impl Rect {
     pub fn bottom(&self) -> f32 {
         self.y + self.height
     }
+
+    /// Smallest rectangle containing both rectangles
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.bottom().max(other.bottom()) - y,
+        }
+    }
+
+    /// Area covered by both rectangles, empty if they don't overlap
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        Rect {
+            x,
+            y,
+            width: (self.right().min(other.right()) - x).max(0.0),
+            height: (self.bottom().min(other.bottom()) - y).max(0.0),
+        }
+    }
+
+    /// Check if the rectangle has no area
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
 }
 
 impl EdgeSizes {
@@ -140,14 +185,35 @@ impl EdgeSizes {
 }
 
 /// Containing block for layout
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ContainingBlock {
     pub width: f32,
     pub height: f32,
+    /// The height is known before the content is laid out, so percentage
+    /// heights can be resolved against it
+    pub height_is_definite: bool,
 }
 
 impl ContainingBlock {
     pub fn new(width: f32, height: f32) -> Self {
-        Self { width, height }
+        Self { width, height, height_is_definite: true }
+    }
+
+    /// Containing block whose height depends on its content
+    pub fn with_auto_height(width: f32) -> Self {
+        Self { width, height: 0.0, height_is_definite: false }
+    }
+
+    /// Containing block with a definite height, if there is one
+    pub fn with_height(width: f32, height: Option<f32>) -> Self {
+        match height {
+            Some(height) => Self::new(width, height),
+            None => Self::with_auto_height(width),
+        }
+    }
+
+    /// Height percentages resolve against, if it is definite
+    pub fn definite_height(&self) -> Option<f32> {
+        self.height_is_definite.then_some(self.height)
     }
 }
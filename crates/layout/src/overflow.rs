@@ -0,0 +1,95 @@
+//! Scrollable Overflow
+//!
+//! Measures how far the content of scroll containers (overflow: auto or
+//! scroll) reaches, so they can be scrolled by that much.
+
+use crate::boxtree::LayoutBox;
+use crate::Rect;
+
+/// Record the scrollable overflow size of every scroll container in a laid
+/// out tree
+pub(crate) fn record_scroll_overflow(layout_box: &mut LayoutBox) {
+    for child in &mut layout_box.children {
+        record_scroll_overflow(child);
+    }
+
+    if !layout_box.is_scroll_container() {
+        return;
+    }
+
+    // Children are positioned relative to the content box, the scroll
+    // origin is the padding box
+    let d = &layout_box.dimensions;
+    let padding_box = d.padding_box();
+    let (x, y) = (d.content.x - padding_box.x, d.content.y - padding_box.y);
+
+    let mut extent = Rect::new(0.0, 0.0, padding_box.width, padding_box.height);
+    for child in layout_box.children.iter().filter(|c| !c.is_fixed_positioned()) {
+        extent = extent.union(&overflow_bounds(child, x, y));
+    }
+
+    // Content above or left of the scroll origin can't be scrolled to
+    layout_box.scroll_size = Some((extent.right(), extent.bottom()));
+}
+
+/// Border box of a box united with the overflow it doesn't clip
+///
+/// `offset_x` and `offset_y` are the position of its parent's content box.
+fn overflow_bounds(layout_box: &LayoutBox, offset_x: f32, offset_y: f32) -> Rect {
+    let d = &layout_box.dimensions;
+    let border_box = d.border_box();
+    let mut bounds = Rect::new(offset_x + border_box.x, offset_y + border_box.y, border_box.width, border_box.height);
+
+    if !layout_box.clips_overflow() {
+        let (x, y) = (offset_x + d.content.x, offset_y + d.content.y);
+        for child in layout_box.children.iter().filter(|c| !c.is_fixed_positioned()) {
+            bounds = bounds.union(&overflow_bounds(child, x, y));
+        }
+    }
+
+    bounds
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::setup_and_layout;
+
+    #[test]
+    fn test_scroll_size_of_tall_content() {
+        let layout = setup_and_layout(
+            "<div class='scroller'><div class='tall'></div></div>",
+            "div { display: block; } \
+             .scroller { overflow: auto; width: 200px; height: 100px; padding-top: 10px; padding-left: 10px; } \
+             .tall { width: 50px; height: 300px; }",
+            800.0,
+        );
+
+        // Measured from the padding box, so the top and left padding count
+        assert_eq!(layout.scroll_size, Some((210.0, 310.0)));
+    }
+
+    #[test]
+    fn test_scroll_size_of_fitting_content() {
+        let layout = setup_and_layout(
+            "<div class='scroller'><div class='short'></div></div>",
+            "div { display: block; } \
+             .scroller { overflow: scroll; width: 200px; height: 100px; } \
+             .short { height: 20px; }",
+            800.0,
+        );
+        assert_eq!(layout.scroll_size, Some((200.0, 100.0)));
+    }
+
+    #[test]
+    fn test_hidden_overflow_is_not_scrollable() {
+        let layout = setup_and_layout(
+            "<div class='clipper'><div class='tall'></div></div>",
+            "div { display: block; } \
+             .clipper { overflow: hidden; height: 100px; } \
+             .tall { height: 300px; }",
+            800.0,
+        );
+        assert!(layout.clips_overflow());
+        assert_eq!(layout.scroll_size, None);
+    }
+}
@@ -0,0 +1,270 @@
+//! Positioned Layout
+//!
+//! Shifts relatively positioned boxes after normal flow, places absolutely
+//! positioned boxes against their containing block, the padding box of the
+//! nearest positioned ancestor, and fixed boxes against the viewport.
+
+use gugalanna_style::Position;
+
+use crate::block::layout_block;
+use crate::boxtree::{BoxType, LayoutBox};
+use crate::inline::layout_inline_box;
+use crate::intrinsic::intrinsic_widths;
+use crate::{ContainingBlock, Rect};
+
+/// Shift a relatively positioned box from where normal flow put it
+///
+/// Applied once the box is in place, so siblings keep the space it was
+/// laid out in. Percentages resolve against the containing block; when
+/// both insets on an axis are set, left and top win.
+pub(crate) fn apply_relative_offset(layout_box: &mut LayoutBox, containing_block: ContainingBlock) {
+    let style = match layout_box.style() {
+        Some(style) if style.position == Position::Relative => style,
+        _ => return,
+    };
+
+    let dx = match (style.left, style.right) {
+        (Some(left), _) => left.resolve(containing_block.width),
+        (None, Some(right)) => -right.resolve(containing_block.width),
+        (None, None) => 0.0,
+    };
+    let dy = match (style.top, style.bottom) {
+        (Some(top), _) => top.resolve(containing_block.height),
+        (None, Some(bottom)) => -bottom.resolve(containing_block.height),
+        (None, None) => 0.0,
+    };
+
+    layout_box.dimensions.content.x += dx;
+    layout_box.dimensions.content.y += dy;
+}
+
+/// Place the absolutely positioned descendants of a positioned box
+///
+/// Runs once the box is laid out, so its size is known.
+pub(crate) fn layout_absolute_descendants(layout_box: &mut LayoutBox) {
+    let d = &layout_box.dimensions;
+    let padding_box = Rect::new(
+        -d.padding.left,
+        -d.padding.top,
+        d.content.width + d.padding.horizontal(),
+        d.content.height + d.padding.vertical(),
+    );
+    place_absolute_descendants(layout_box, padding_box);
+}
+
+/// Place the absolutely positioned descendants whose containing block is `cb`
+///
+/// `cb` is in the coordinates of `parent`'s content box. Positioned
+/// descendants have already placed their own.
+pub(crate) fn place_absolute_descendants(parent: &mut LayoutBox, cb: Rect) {
+    for child in &mut parent.children {
+        if child.is_fixed_positioned() {
+            // Placed against the viewport once the document is laid out
+            continue;
+        }
+
+        if child.is_absolutely_positioned() {
+            layout_absolute(child, cb);
+        } else if !child.is_positioned() {
+            let content = child.dimensions.content;
+            let cb = Rect::new(cb.x - content.x, cb.y - content.y, cb.width, cb.height);
+            place_absolute_descendants(child, cb);
+        }
+    }
+}
+
+/// Place all fixed descendants against the viewport
+///
+/// `viewport` is in the coordinates of `parent`'s content box.
+pub(crate) fn place_fixed_descendants(parent: &mut LayoutBox, viewport: Rect) {
+    for child in &mut parent.children {
+        if child.is_fixed_positioned() {
+            layout_absolute(child, viewport);
+        }
+
+        let content = child.dimensions.content;
+        let viewport = Rect::new(viewport.x - content.x, viewport.y - content.y, viewport.width, viewport.height);
+        place_fixed_descendants(child, viewport);
+    }
+}
+
+/// Size and place an absolutely positioned box
+///
+/// The box's content origin holds its static position, the margin box
+/// origin it would have had in normal flow. Insets that are auto on both
+/// sides fall back to it; auto widths shrink to fit.
+fn layout_absolute(layout_box: &mut LayoutBox, cb: Rect) {
+    let style = match layout_box.style() {
+        Some(s) => s,
+        None => return,
+    };
+    let (static_x, static_y) = (layout_box.dimensions.content.x, layout_box.dimensions.content.y);
+
+    let left = style.left.map(|l| l.resolve(cb.width));
+    let right = style.right.map(|r| r.resolve(cb.width));
+    let top = style.top.map(|t| t.resolve(cb.height));
+    let bottom = style.bottom.map(|b| b.resolve(cb.height));
+
+    if let BoxType::Block(_, _) = layout_box.box_type {
+        layout_box.apply_style_edges(cb.width);
+        let d = &layout_box.dimensions;
+        let horizontal = d.margin.horizontal() + d.border.horizontal() + d.padding.horizontal();
+        let vertical = d.margin.vertical() + d.border.vertical() + d.padding.vertical();
+
+        let width = match (style.width, left, right) {
+            (Some(width), _, _) => width,
+            (None, Some(left), Some(right)) => (cb.width - left - right - horizontal).max(0.0),
+            (None, left, right) => {
+                let available = cb.width - left.unwrap_or(0.0) - right.unwrap_or(0.0) - horizontal;
+                intrinsic_widths(layout_box).shrink_to_fit(available)
+            }
+        };
+        layout_block(layout_box, ContainingBlock::new(width + horizontal, cb.height));
+
+        // Auto height stretches between the insets when both are set
+        let height = layout_box.specified_height(ContainingBlock::new(cb.width, cb.height));
+        if let (None, Some(top), Some(bottom)) = (height, top, bottom) {
+            layout_box.dimensions.content.height = (cb.height - top - bottom - vertical).max(0.0);
+        }
+    } else {
+        // Replaced elements keep their intrinsic size
+        layout_inline_box(layout_box, cb.width);
+    }
+
+    // Over-constrained insets ignore right and bottom
+    let d = &mut layout_box.dimensions;
+    let x = match (left, right) {
+        (Some(left), _) => cb.x + left,
+        (None, Some(right)) => cb.right() - right - d.margin_box_width(),
+        (None, None) => static_x,
+    };
+    let y = match (top, bottom) {
+        (Some(top), _) => cb.y + top,
+        (None, Some(bottom)) => cb.bottom() - bottom - d.margin_box_height(),
+        (None, None) => static_y,
+    };
+    d.content.x = x + d.margin.left + d.border.left + d.padding.left;
+    d.content.y = y + d.margin.top + d.border.top + d.padding.top;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::setup_and_layout;
+
+    const CSS: &str = "div { display: block; font-size: 10px; line-height: 20px; } \
+        .parent { position: relative; width: 200px; height: 100px; padding-left: 5px; } \
+        .abs { position: absolute; }";
+
+    #[test]
+    fn test_relative_offset_keeps_siblings_in_place() {
+        let layout = setup_and_layout(
+            "<div><div>a</div><div class='nudge'>b</div><div>c</div></div>",
+            "div { display: block; font-size: 10px; line-height: 20px; } \
+             .nudge { position: relative; top: -4px; left: 10%; }",
+            400.0,
+        );
+
+        // The nudged box moves, left resolving against the 400px container
+        let nudged = layout.children[1].dimensions.content;
+        assert_eq!((nudged.x, nudged.y), (40.0, 16.0));
+
+        // Its space stays reserved
+        assert_eq!(layout.children[2].dimensions.content.y, 40.0);
+    }
+
+    #[test]
+    fn test_relative_offset_over_constrained() {
+        let layout = setup_and_layout(
+            "<div><div class='nudge'>a</div></div>",
+            "div { display: block; font-size: 10px; line-height: 20px; } \
+             .nudge { position: relative; top: 5px; bottom: 50px; left: 3px; right: 50px; }",
+            400.0,
+        );
+
+        // Top and left win over bottom and right
+        let nudged = layout.children[0].dimensions.content;
+        assert_eq!((nudged.x, nudged.y), (3.0, 5.0));
+    }
+
+    #[test]
+    fn test_absolute_in_relative_parent_corner() {
+        let layout = setup_and_layout(
+            "<div><div class='parent'><div class='abs corner'>x</div></div></div>",
+            &format!("{} .corner {{ top: 10px; right: 10px; width: 50px; height: 20px; }}", CSS),
+            400.0,
+        );
+
+        // Right inset is measured from the parent's padding box
+        let parent = &layout.children[0];
+        let corner = parent.children[0].dimensions.border_box();
+        assert_eq!(corner, Rect::new(140.0, 10.0, 50.0, 20.0));
+    }
+
+    #[test]
+    fn test_absolute_removed_from_flow() {
+        let layout = setup_and_layout(
+            "<div><div class='parent'><div class='abs'>overlay</div><div>after</div></div></div>",
+            CSS,
+            400.0,
+        );
+
+        let parent = &layout.children[0];
+        let overlay = &parent.children[0].children[0];
+        let after = &parent.children[1];
+
+        // The following block takes the overlay's place
+        assert_eq!(after.dimensions.content.y, 0.0);
+
+        // Auto insets keep the static position, auto width shrinks to fit
+        assert_eq!(overlay.dimensions.content.y, 0.0);
+        assert_eq!(overlay.dimensions.content.width, 42.0);
+    }
+
+    #[test]
+    fn test_absolute_insets_stretch() {
+        let layout = setup_and_layout(
+            "<div><div class='parent'><div class='abs fill'></div></div></div>",
+            &format!("{} .fill {{ top: 10px; bottom: 10px; left: 0px; right: 0px; }}", CSS),
+            400.0,
+        );
+
+        let fill = layout.children[0].children[0].dimensions.border_box();
+        assert_eq!(fill, Rect::new(-5.0, 10.0, 205.0, 80.0));
+    }
+
+    #[test]
+    fn test_fixed_ignores_positioned_ancestor() {
+        let layout = setup_and_layout(
+            "<div><div class='parent'><div class='fixed'></div></div></div>",
+            &format!("{} .parent {{ margin-top: 50px; }} \
+                .fixed {{ position: fixed; top: 0px; right: 0px; width: 10px; height: 10px; }}", CSS),
+            400.0,
+        );
+
+        // Sum the content origins down to the box's parent
+        let parent = &layout.children[0];
+        let origin_x = layout.dimensions.content.x + parent.dimensions.content.x;
+        let origin_y = layout.dimensions.content.y + parent.dimensions.content.y;
+        let fixed = parent.children[0].dimensions.border_box();
+        assert_eq!(origin_x + fixed.x, 390.0);
+        assert_eq!(origin_y + fixed.y, 0.0);
+    }
+
+    #[test]
+    fn test_absolute_without_positioned_ancestor_uses_viewport() {
+        let layout = setup_and_layout(
+            "<div><p>text</p><div class='abs bottom'></div></div>",
+            &format!("{} .bottom {{ bottom: 0px; left: 0px; width: 10px; height: 10px; }}", CSS),
+            400.0,
+        );
+
+        // Sum the content origins down to the box's parent
+        let wrapper = &layout.children[1];
+        let origin_x = layout.dimensions.content.x + wrapper.dimensions.content.x;
+        let origin_y = layout.dimensions.content.y + wrapper.dimensions.content.y;
+        let bottom = wrapper.children[0].dimensions.border_box();
+        assert_eq!(origin_x + bottom.x, 0.0);
+        assert_eq!(origin_y + bottom.y, 590.0);
+    }
+}
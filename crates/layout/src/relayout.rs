@@ -0,0 +1,285 @@
+//! Incremental Relayout
+//!
+//! Lays a document out again after some of its boxes changed. Boxes are
+//! marked dirty when their node or style changes, and their ancestors are
+//! marked as having dirty descendants. Clean blocks laid out before in the
+//! same containing block keep their layout and are only moved, so size
+//! changes still push the blocks after them down.
+
+use std::cell::Cell;
+
+use gugalanna_dom::NodeId;
+
+use crate::block::layout_document;
+use crate::boxtree::{BoxType, LayoutBox, LayoutCache};
+use crate::ContainingBlock;
+
+thread_local! {
+    /// Blocks laid out on this thread
+    static BLOCKS_LAID_OUT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Mark the box generated by a node for layout after the node or its style
+/// changed, returns false when the node has no box in the tree
+pub fn mark_node_dirty(root: &mut LayoutBox, node_id: NodeId) -> bool {
+    let found = if root.node_id() == Some(node_id) {
+        root.needs_layout = true;
+        true
+    } else {
+        let mut found = false;
+        for child in &mut root.children {
+            found |= mark_node_dirty(child, node_id);
+        }
+        root.child_needs_layout |= found;
+        found
+    };
+
+    // The content the intrinsic widths were measured from changed
+    if found {
+        root.intrinsic_widths.set(None);
+    }
+    found
+}
+
+/// Take over the layout of the tree a document was last laid out as, so
+/// [`relayout_document`] only lays out what changed since
+///
+/// `tree` is built from the document again. A box keeps the layout of the
+/// box at the same place in `old` when both have the same type, style and
+/// content, the others are marked dirty. The line fragments and the
+/// decoded images of the same sources are moved out of `old`. Returns
+/// whether any box of `tree` differs from `old`.
+pub fn reuse_layout_of(tree: &mut LayoutBox, old: &mut LayoutBox) -> bool {
+    take_images(tree, old);
+    let changed = tree.box_type != old.box_type;
+    let mut child_changed = tree.children.len() != old.children.len();
+    if !child_changed {
+        for (child, old_child) in tree.children.iter_mut().zip(&mut old.children) {
+            child_changed |= reuse_layout_of(child, old_child);
+        }
+    }
+
+    tree.dimensions = old.dimensions;
+    tree.fragments = std::mem::take(&mut old.fragments);
+    tree.scroll_size = old.scroll_size;
+    tree.layout_cache = old.layout_cache;
+    tree.needs_layout = changed;
+    tree.child_needs_layout = child_changed;
+    if !changed && !child_changed {
+        tree.intrinsic_widths.set(old.intrinsic_widths.get());
+    }
+    changed || child_changed
+}
+
+/// Move the decoded images of a box over from the box it was built as
+/// before, when they are of the same source
+fn take_images(tree: &mut LayoutBox, old: &mut LayoutBox) {
+    let background = |layout_box: &LayoutBox| layout_box.style().and_then(|style| style.background_image.clone());
+    if tree.background_pixels.is_none() && background(tree) == background(old) {
+        tree.background_pixels = old.background_pixels.take();
+    }
+
+    if let (BoxType::Image(_, image, _), BoxType::Image(_, old_image, _)) = (&mut tree.box_type, &mut old.box_type) {
+        if image.pixels.is_none() && image.src == old_image.src && old_image.pixels.is_some() {
+            // The size of a loaded image is the size it decoded to
+            image.pixels = old_image.pixels.take();
+            image.intrinsic_width = old_image.intrinsic_width;
+            image.intrinsic_height = old_image.intrinsic_height;
+        }
+    }
+}
+
+/// Lay out a document again after boxes were marked with
+/// [`mark_node_dirty`], returns how many blocks were laid out
+///
+/// A tree that was never laid out is laid out in full.
+pub fn relayout_document(root: &mut LayoutBox, viewport: ContainingBlock) -> usize {
+    BLOCKS_LAID_OUT.with(|count| count.set(0));
+    layout_document(root, viewport);
+    BLOCKS_LAID_OUT.with(Cell::get)
+}
+
+/// Count a block being laid out and clear its dirty bits
+pub(crate) fn mark_laid_out(layout_box: &mut LayoutBox) {
+    BLOCKS_LAID_OUT.with(|count| count.set(count.get() + 1));
+    layout_box.needs_layout = false;
+    layout_box.child_needs_layout = false;
+}
+
+/// Remember where a block was laid out in its parent's block flow
+///
+/// `floats_left` is whether the parent's float context holds floats after it.
+pub(crate) fn cache_layout(layout_box: &mut LayoutBox, containing_block: ContainingBlock, y: f32, floats_left: bool) {
+    layout_box.layout_cache = Some(LayoutCache {
+        containing_block,
+        y,
+        leaks_floats: floats_left,
+        has_positioned: has_positioned_descendants(layout_box),
+    });
+}
+
+/// Move a clean block to `y` instead of laying it out again
+///
+/// Returns false when the block has to be laid out: it or a descendant
+/// changed, its containing block did, or its layout depends on boxes
+/// outside of it.
+pub(crate) fn reuse_layout(layout_box: &mut LayoutBox, containing_block: ContainingBlock, y: f32) -> bool {
+    let cache = match layout_box.layout_cache {
+        Some(cache)
+            if !layout_box.needs_layout
+                && !layout_box.child_needs_layout
+                && cache.containing_block == containing_block
+                && !cache.leaks_floats
+                && !cache.has_positioned =>
+        {
+            cache
+        }
+        _ => return false,
+    };
+
+    // Descendants are positioned relative to the content box, so only the
+    // box itself moves
+    layout_box.dimensions.content.y += y - cache.y;
+    layout_box.layout_cache = Some(LayoutCache { y, ..cache });
+    true
+}
+
+/// Check if absolutely positioned boxes are placed somewhere inside a box
+fn has_positioned_descendants(layout_box: &LayoutBox) -> bool {
+    layout_box.children.iter().any(|child| {
+        child.is_absolutely_positioned()
+            || match child.layout_cache {
+                Some(cache) => cache.has_positioned,
+                None => has_positioned_descendants(child),
+            }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boxtree::{ImageData, ImagePixels};
+    use crate::test_util::build;
+    use gugalanna_dom::{DomTree, Queryable};
+
+    const CSS: &str = "div { display: block; font-size: 10px; line-height: 20px; }";
+
+    /// Replace the text of the text box under an element
+    fn set_text(layout: &mut LayoutBox, dom: &DomTree, id: &str, text: &str) -> NodeId {
+        let element = dom.get_element_by_id(id).unwrap();
+        let text_id = dom.children(element)[0];
+        fn find<'b, 'a>(b: &'b mut LayoutBox<'a>, id: NodeId) -> Option<&'b mut LayoutBox<'a>> {
+            if b.node_id() == Some(id) {
+                return Some(b);
+            }
+            b.children.iter_mut().find_map(|c| find(c, id))
+        }
+        if let BoxType::Text(_, content, _) = &mut find(layout, text_id).unwrap().box_type {
+            *content = text.to_string();
+        }
+        text_id
+    }
+
+    #[test]
+    fn test_relayout_moves_following_blocks() {
+        let (dom, mut layout) = build("<div><div><div id='a'>aa</div></div><div>b</div><div>c</div></div>", CSS);
+        let viewport = ContainingBlock::new(60.0, 600.0);
+        relayout_document(&mut layout, viewport);
+        assert_eq!(layout.children[2].dimensions.content.y, 40.0);
+
+        // The text now wraps onto a second line
+        let text_id = set_text(&mut layout, dom, "a", "aaaaaa aaaaaa");
+        assert!(mark_node_dirty(&mut layout, text_id));
+
+        // Only the root and the changed branch are laid out, the blocks
+        // after it move down by a line
+        assert_eq!(relayout_document(&mut layout, viewport), 3);
+        assert_eq!(layout.children[0].dimensions.content.height, 40.0);
+        assert_eq!(layout.children[1].dimensions.content.y, 40.0);
+        assert_eq!(layout.children[2].dimensions.content.y, 60.0);
+    }
+
+    #[test]
+    fn test_rebuilt_tree_reuses_layout() {
+        let viewport = ContainingBlock::new(60.0, 600.0);
+        let (_, mut old) = build("<div><div>aa</div><div>b</div></div>", CSS);
+        relayout_document(&mut old, viewport);
+
+        // The same document built again keeps all of its layout
+        let (_, mut same) = build("<div><div>aa</div><div>b</div></div>", CSS);
+        assert!(!reuse_layout_of(&mut same, &mut old));
+        assert_eq!(relayout_document(&mut same, viewport), 1);
+        assert_eq!(same.children[1].dimensions.content.y, 20.0);
+
+        // Only the box whose text changed is laid out again
+        let (_, mut changed) = build("<div><div>aaaaaa aaaaaa</div><div>b</div></div>", CSS);
+        assert!(reuse_layout_of(&mut changed, &mut same));
+        assert_eq!(relayout_document(&mut changed, viewport), 2);
+        assert_eq!(changed.children[1].dimensions.content.y, 40.0);
+    }
+
+    /// The data of the first image box in a tree
+    fn image<'t>(layout: &'t mut LayoutBox<'static>) -> Option<&'t mut ImageData> {
+        if let BoxType::Image(_, image, _) = &mut layout.box_type {
+            return Some(image);
+        }
+        layout.children.iter_mut().find_map(image)
+    }
+
+    #[test]
+    fn test_rebuilt_tree_keeps_decoded_images() {
+        let html = "<div><img src='a.png' width='5' height='5'></div>";
+        let (_, mut old) = build(html, CSS);
+        let pixels = ImagePixels { width: 2, height: 3, data: vec![255; 24].into() };
+        let loaded = image(&mut old).unwrap();
+        loaded.pixels = Some(pixels.clone());
+        loaded.intrinsic_width = Some(2.0);
+        loaded.intrinsic_height = Some(3.0);
+        relayout_document(&mut old, ContainingBlock::new(60.0, 600.0));
+
+        // The image is taken over with the size it decoded to
+        let (_, mut same) = build(html, CSS);
+        assert!(!reuse_layout_of(&mut same, &mut old));
+        let kept = image(&mut same).unwrap();
+        assert_eq!(kept.pixels, Some(pixels));
+        assert_eq!(kept.intrinsic_width, Some(2.0));
+
+        // An image of another source is not
+        let (_, mut other) = build("<div><img src='b.png' width='5' height='5'></div>", CSS);
+        assert!(reuse_layout_of(&mut other, &mut same));
+        assert_eq!(image(&mut other).unwrap().pixels, None);
+    }
+
+    #[test]
+    fn test_relayout_in_a_new_viewport_width() {
+        let (_, mut layout) = build("<div><div>aaaa aaaa</div><div>b</div></div>", CSS);
+        relayout_document(&mut layout, ContainingBlock::new(100.0, 600.0));
+        assert_eq!(layout.children[1].dimensions.content.y, 20.0);
+
+        // Nothing changed, but every block sees a narrower containing block
+        assert_eq!(relayout_document(&mut layout, ContainingBlock::new(50.0, 600.0)), 3);
+        assert_eq!(layout.children[1].dimensions.content.y, 40.0);
+    }
+
+    #[test]
+    fn test_relayout_one_leaf_of_a_large_tree() {
+        let sections = "<div>".to_string() + &"<div>a</div>".repeat(100) + "</div>";
+        let html = format!("<div><div id='leaf'>a</div>{}</div>", sections.repeat(100));
+        let (dom, mut layout) = build(&html, CSS);
+        let viewport = ContainingBlock::new(800.0, 600.0);
+
+        let full = relayout_document(&mut layout, viewport);
+        assert!(full > 10_000);
+        let last = layout.children[100].dimensions.content.y;
+
+        let text_id = set_text(&mut layout, dom, "leaf", &"a ".repeat(300));
+        assert!(mark_node_dirty(&mut layout, text_id));
+        let partial = relayout_document(&mut layout, viewport);
+        assert!(partial * 1000 < full, "{} of {} blocks laid out", partial, full);
+
+        // Every section after the leaf moved down by its new lines
+        let grown = layout.children[0].dimensions.content.height - 20.0;
+        assert!(grown > 0.0);
+        assert_eq!(layout.children[100].dimensions.content.y, last + grown);
+    }
+}
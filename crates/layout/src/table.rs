@@ -0,0 +1,602 @@
+//! Table Layout
+//!
+//! Implements a simplified automatic table layout: cells are placed on a
+//! grid, columns are sized from the cells' intrinsic widths and each row
+//! is as tall as its tallest cell.
+
+use gugalanna_style::{Display, VerticalAlign};
+
+use crate::block::{layout_block, layout_block_with_width};
+use crate::boxtree::{BoxType, CellSpan, LayoutBox};
+use crate::intrinsic::{outer_widths, IntrinsicWidths};
+use crate::positioned::apply_relative_offset;
+use crate::{ContainingBlock, EdgeSizes, Rect};
+
+/// Largest colspan and rowspan honored
+pub(crate) const MAX_SPAN: usize = 1000;
+
+/// Role a box plays in table structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TablePart {
+    Table,
+    RowGroup,
+    Row,
+    Cell,
+    Caption,
+}
+
+/// Role of a box from its display type
+fn table_part(layout_box: &LayoutBox) -> Option<TablePart> {
+    if layout_box.is_out_of_flow() {
+        return None;
+    }
+
+    match layout_box.box_type {
+        BoxType::AnonymousTable => Some(TablePart::Table),
+        BoxType::TableCell(_, _, _) => Some(TablePart::Cell),
+        BoxType::Block(_, style) => match style.display {
+            Display::Table => Some(TablePart::Table),
+            Display::TableRowGroup => Some(TablePart::RowGroup),
+            Display::TableRow => Some(TablePart::Row),
+            Display::TableCaption => Some(TablePart::Caption),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Check if a box lays out its children as a table
+pub(crate) fn is_table(layout_box: &LayoutBox) -> bool {
+    table_part(layout_box) == Some(TablePart::Table)
+}
+
+/// Whitespace between table parts or grid items, which generates no boxes
+pub(crate) fn is_ignorable(layout_box: &LayoutBox) -> bool {
+    match &layout_box.box_type {
+        BoxType::Text(_, text, _) => text.chars().all(|c| c.is_ascii_whitespace()),
+        BoxType::AnonymousBlock => layout_box.children.iter().all(is_ignorable),
+        _ => false,
+    }
+}
+
+/// Wrap table parts in the anonymous boxes their parent requires
+///
+/// Children of a table or row group that aren't rows get wrapped in
+/// anonymous rows, children of a row that aren't cells in anonymous cells,
+/// and rows or cells outside a table in an anonymous table.
+pub(crate) fn fix_table_structure(parent: &mut LayoutBox) {
+    match table_part(parent) {
+        Some(TablePart::Table) => fix_table(parent),
+        Some(TablePart::RowGroup) => fix_row_group(parent),
+        Some(TablePart::Row) => fix_row(parent),
+        _ => wrap_orphans(parent),
+    }
+}
+
+fn fix_table(table: &mut LayoutBox) {
+    table.children.retain(|c| !is_ignorable(c));
+    wrap_runs(
+        table,
+        |c| {
+            !matches!(c.box_type, BoxType::AnonymousBlock)
+                && !matches!(
+                    table_part(c),
+                    Some(TablePart::RowGroup | TablePart::Row | TablePart::Caption)
+                )
+        },
+        LayoutBox::new_anonymous_block,
+    );
+
+    for child in &mut table.children {
+        match table_part(child) {
+            Some(TablePart::RowGroup) => fix_row_group(child),
+            Some(TablePart::Caption) => {}
+            _ => fix_row(child),
+        }
+    }
+}
+
+fn fix_row_group(group: &mut LayoutBox) {
+    group.children.retain(|c| !is_ignorable(c));
+    wrap_runs(
+        group,
+        |c| !matches!(c.box_type, BoxType::AnonymousBlock) && table_part(c) != Some(TablePart::Row),
+        LayoutBox::new_anonymous_block,
+    );
+
+    for row in &mut group.children {
+        fix_row(row);
+    }
+}
+
+fn fix_row(row: &mut LayoutBox) {
+    // Anonymous blocks holding inline content serve as anonymous cells
+    row.children.retain(|c| !is_ignorable(c));
+    wrap_runs(
+        row,
+        |c| !matches!(c.box_type, BoxType::AnonymousBlock) && table_part(c) != Some(TablePart::Cell),
+        LayoutBox::new_anonymous_block,
+    );
+}
+
+/// Give rows and cells outside a table an anonymous table
+fn wrap_orphans(parent: &mut LayoutBox) {
+    let is_orphan = |c: &LayoutBox| {
+        matches!(table_part(c), Some(TablePart::RowGroup | TablePart::Row | TablePart::Cell))
+    };
+    if !parent.children.iter().any(is_orphan) {
+        return;
+    }
+
+    let mut in_table = false;
+    for child in std::mem::take(&mut parent.children) {
+        // Whitespace between orphans stays with them and is dropped
+        if is_orphan(&child) || (in_table && is_ignorable(&child)) {
+            if !in_table {
+                parent.children.push(LayoutBox::new_anonymous_table());
+                in_table = true;
+            }
+            parent.children.last_mut().unwrap().children.push(child);
+        } else {
+            in_table = false;
+            parent.children.push(child);
+        }
+    }
+
+    for child in &mut parent.children {
+        if matches!(child.box_type, BoxType::AnonymousTable) {
+            fix_table(child);
+        }
+    }
+}
+
+/// Replace each run of children matching `wrap` with one new box
+fn wrap_runs<'a>(
+    parent: &mut LayoutBox<'a>,
+    wrap: impl Fn(&LayoutBox) -> bool,
+    make: fn() -> LayoutBox<'a>,
+) {
+    let mut in_run = false;
+    for child in std::mem::take(&mut parent.children) {
+        if wrap(&child) {
+            if !in_run {
+                parent.children.push(make());
+                in_run = true;
+            }
+            parent.children.last_mut().unwrap().children.push(child);
+        } else {
+            in_run = false;
+            parent.children.push(child);
+        }
+    }
+}
+
+/// Where a row sits among the table's children
+#[derive(Debug, Clone, Copy)]
+struct RowRef {
+    /// Index of the row group, if the row is in one
+    group: Option<usize>,
+    /// Index of the row within its parent
+    index: usize,
+}
+
+/// A cell placed on the table grid
+#[derive(Debug, Clone, Copy)]
+struct GridCell {
+    row: usize,
+    column: usize,
+    /// Columns and rows covered, clamped to the table
+    columns: usize,
+    rows: usize,
+    /// Index of the cell within its row
+    index: usize,
+}
+
+/// Rows and cells of a table placed on a grid
+#[derive(Debug)]
+struct TableGrid {
+    rows: Vec<RowRef>,
+    cells: Vec<GridCell>,
+    columns: usize,
+}
+
+fn row_box<'b, 'a>(table: &'b LayoutBox<'a>, row: RowRef) -> &'b LayoutBox<'a> {
+    match row.group {
+        Some(group) => &table.children[group].children[row.index],
+        None => &table.children[row.index],
+    }
+}
+
+fn row_box_mut<'b, 'a>(table: &'b mut LayoutBox<'a>, row: RowRef) -> &'b mut LayoutBox<'a> {
+    match row.group {
+        Some(group) => &mut table.children[group].children[row.index],
+        None => &mut table.children[row.index],
+    }
+}
+
+/// Place the cells of a table on a grid, skipping slots taken by row spans
+fn build_grid(table: &LayoutBox) -> TableGrid {
+    let mut rows = Vec::new();
+    for (i, child) in table.children.iter().enumerate() {
+        match table_part(child) {
+            Some(TablePart::Caption) => {}
+            Some(TablePart::RowGroup) => {
+                rows.extend((0..child.children.len()).map(|index| RowRef { group: Some(i), index }));
+            }
+            _ => rows.push(RowRef { group: None, index: i }),
+        }
+    }
+
+    let mut occupied: Vec<Vec<bool>> = vec![Vec::new(); rows.len()];
+    let mut cells = Vec::new();
+    let mut columns = 0;
+
+    for (r, row) in rows.iter().enumerate() {
+        let mut column = 0;
+        for (index, cell) in row_box(table, *row).children.iter().enumerate() {
+            let span = match cell.box_type {
+                BoxType::TableCell(_, _, span) => span,
+                _ => CellSpan::default(),
+            };
+
+            while occupied[r].get(column).copied().unwrap_or(false) {
+                column += 1;
+            }
+
+            // A row span of 0 (or one past the end) covers the remaining rows
+            let remaining = rows.len() - r;
+            let row_span = if span.rows == 0 { remaining } else { span.rows.min(remaining) };
+            let end = column + span.columns;
+            for slots in &mut occupied[r..r + row_span] {
+                if slots.len() < end {
+                    slots.resize(end, false);
+                }
+                slots[column..end].fill(true);
+            }
+
+            cells.push(GridCell { row: r, column, columns: span.columns, rows: row_span, index });
+            column = end;
+            columns = columns.max(end);
+        }
+    }
+
+    TableGrid { rows, cells, columns }
+}
+
+/// Horizontal and vertical border spacing of a table
+fn border_spacing(table: &LayoutBox) -> (f32, f32) {
+    table
+        .style()
+        .map(|s| (s.border_spacing_horizontal, s.border_spacing_vertical))
+        .unwrap_or((0.0, 0.0))
+}
+
+/// Grow a span of sizes evenly so they add up to at least `total`
+fn grow_span(sizes: &mut [f32], total: f32) {
+    let current: f32 = sizes.iter().sum();
+    if total > current {
+        let extra = (total - current) / sizes.len() as f32;
+        for size in sizes {
+            *size += extra;
+        }
+    }
+}
+
+/// Minimum and preferred width of each column, (min, max)
+fn column_widths(table: &LayoutBox, grid: &TableGrid, spacing: f32) -> (Vec<f32>, Vec<f32>) {
+    let mut min = vec![0.0; grid.columns];
+    let mut max = vec![0.0; grid.columns];
+
+    // Single column cells first, so spanning cells only add what is missing
+    let mut cells: Vec<&GridCell> = grid.cells.iter().collect();
+    cells.sort_by_key(|c| c.columns);
+
+    for cell in cells {
+        let cell_box = &row_box(table, grid.rows[cell.row]).children[cell.index];
+        let widths = outer_widths(cell_box);
+        let gaps = spacing * (cell.columns - 1) as f32;
+        let span = cell.column..cell.column + cell.columns;
+
+        grow_span(&mut min[span.clone()], widths.min - gaps);
+        grow_span(&mut max[span], widths.max - gaps);
+    }
+
+    for (max, min) in max.iter_mut().zip(&min) {
+        *max = max.max(*min);
+    }
+    (min, max)
+}
+
+/// Total border spacing across a table with the given number of columns
+fn horizontal_spacing(columns: usize, spacing: f32) -> f32 {
+    if columns == 0 { 0.0 } else { spacing * (columns + 1) as f32 }
+}
+
+/// Intrinsic widths of a table's content box
+pub(crate) fn table_intrinsic_widths(table: &LayoutBox) -> IntrinsicWidths {
+    let grid = build_grid(table);
+    let (spacing, _) = border_spacing(table);
+    let (min, max) = column_widths(table, &grid, spacing);
+    let spacing = horizontal_spacing(grid.columns, spacing);
+
+    IntrinsicWidths {
+        min: min.iter().sum::<f32>() + spacing,
+        max: max.iter().sum::<f32>() + spacing,
+    }
+}
+
+/// Share the width available to columns out between them
+fn distribute_width(min: &[f32], max: &[f32], width: f32) -> Vec<f32> {
+    let min_total: f32 = min.iter().sum();
+    let max_total: f32 = max.iter().sum();
+
+    if width >= max_total {
+        // Everything fits: extra space goes to columns by their preferred width
+        let extra = width - max_total;
+        max.iter()
+            .map(|w| if max_total > 0.0 { w + extra * w / max_total } else { extra / max.len() as f32 })
+            .collect()
+    } else if width > min_total {
+        // Columns get their minimum plus a share of the remaining space
+        let ratio = (width - min_total) / (max_total - min_total);
+        min.iter().zip(max).map(|(lo, hi)| lo + (hi - lo) * ratio).collect()
+    } else {
+        min.to_vec()
+    }
+}
+
+/// Layout a table's captions, rows and cells, returns the content height
+pub(crate) fn layout_table(table: &mut LayoutBox) -> f32 {
+    let grid = build_grid(table);
+    let (h_spacing, v_spacing) = border_spacing(table);
+    let (min, max) = column_widths(table, &grid, h_spacing);
+    let spacing = horizontal_spacing(grid.columns, h_spacing);
+    let min_total = min.iter().sum::<f32>() + spacing;
+    let max_total = max.iter().sum::<f32>() + spacing;
+
+    // Auto width tables shrink to fit their columns
+    let available = table.dimensions.content.width;
+    let width = match table.style().and_then(|s| s.width) {
+        Some(w) => w.max(min_total),
+        None => max_total.min(available.max(min_total)),
+    };
+    table.dimensions.content.width = width;
+
+    let columns = distribute_width(&min, &max, width - spacing);
+    let mut column_x = Vec::with_capacity(columns.len() + 1);
+    let mut x = h_spacing;
+    for column in &columns {
+        column_x.push(x);
+        x += column + h_spacing;
+    }
+    column_x.push(x);
+
+    // Lay out each cell at the width of the columns it spans
+    for cell in &grid.cells {
+        let cell_width = column_x[cell.column + cell.columns] - h_spacing - column_x[cell.column];
+        let cell_box = &mut row_box_mut(table, grid.rows[cell.row]).children[cell.index];
+
+        // Margins don't apply to table cells
+        cell_box.apply_style_edges(cell_width);
+        cell_box.dimensions.margin = EdgeSizes::default();
+        let d = &cell_box.dimensions;
+        let edges = d.padding.horizontal() + d.border.horizontal();
+        layout_block_with_width(cell_box, (cell_width - edges).max(0.0));
+    }
+
+    // Rows are as tall as their tallest cell, row spans grow the rows they cover
+    let mut row_heights: Vec<f32> = grid
+        .rows
+        .iter()
+        .map(|row| row_box(table, *row).specified_height(ContainingBlock::with_auto_height(width)).unwrap_or(0.0))
+        .collect();
+    let mut cells: Vec<&GridCell> = grid.cells.iter().collect();
+    cells.sort_by_key(|c| c.rows);
+    for cell in cells {
+        let cell_box = &row_box(table, grid.rows[cell.row]).children[cell.index];
+        let height = cell_box.dimensions.border_box().height;
+        let gaps = v_spacing * (cell.rows - 1) as f32;
+        grow_span(&mut row_heights[cell.row..cell.row + cell.rows], height - gaps);
+    }
+
+    // Captions sit above the rows
+    let mut y = 0.0;
+    for caption in table.children.iter_mut().filter(|c| table_part(c) == Some(TablePart::Caption)) {
+        layout_block(caption, ContainingBlock::with_auto_height(width));
+        caption.dimensions.content.y += y;
+        y += caption.dimensions.margin_box_height();
+    }
+
+    if grid.rows.is_empty() {
+        return y;
+    }
+    y += v_spacing;
+
+    let rows = RowLayout {
+        grid: &grid,
+        heights: &row_heights,
+        column_x: &column_x,
+        width,
+        spacing: v_spacing,
+    };
+    let mut r = 0;
+    for child in &mut table.children {
+        match table_part(child) {
+            Some(TablePart::Caption) => {}
+            Some(TablePart::RowGroup) => {
+                let group_top = y;
+                for row in &mut child.children {
+                    rows.place_row(row, r, y - group_top);
+                    y += row_heights[r] + v_spacing;
+                    r += 1;
+                }
+                let height = (y - group_top - v_spacing).max(0.0);
+                child.dimensions.content = Rect::new(0.0, group_top, width, height);
+            }
+            _ => {
+                rows.place_row(child, r, y);
+                y += row_heights[r] + v_spacing;
+                r += 1;
+            }
+        }
+    }
+
+    y
+}
+
+/// Sizes decided by table layout, used to position rows and cells
+struct RowLayout<'g> {
+    grid: &'g TableGrid,
+    heights: &'g [f32],
+    column_x: &'g [f32],
+    width: f32,
+    spacing: f32,
+}
+
+impl RowLayout<'_> {
+    /// Position a row at `y` within its parent, and its cells within it
+    fn place_row(&self, row: &mut LayoutBox, r: usize, y: f32) {
+        row.dimensions.content = Rect::new(0.0, y, self.width, self.heights[r]);
+
+        for cell in self.grid.cells.iter().filter(|c| c.row == r) {
+            let cell_box = &mut row.children[cell.index];
+            let spanned: f32 = self.heights[r..r + cell.rows].iter().sum::<f32>()
+                + self.spacing * (cell.rows - 1) as f32;
+
+            // Cells stretch to the rows they span, aligning their content
+            let d = &mut cell_box.dimensions;
+            let natural = d.content.height;
+            d.content.height = (spanned - d.padding.vertical() - d.border.vertical()).max(natural);
+            d.content.x = self.column_x[cell.column] + d.border.left + d.padding.left;
+            d.content.y = d.border.top + d.padding.top;
+
+            let free = d.content.height - natural;
+            let offset = match cell_box.style().map(|s| s.vertical_align) {
+                Some(VerticalAlign::Middle) => free / 2.0,
+                Some(VerticalAlign::Bottom) => free,
+                _ => 0.0,
+            };
+            if offset > 0.0 {
+                for child in &mut cell_box.children {
+                    child.dimensions.content.y += offset;
+                }
+            }
+            apply_relative_offset(cell_box, ContainingBlock::with_auto_height(self.width));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::setup_and_layout;
+
+    const TABLE_CSS: &str = "div { display: block; font-size: 10px; line-height: 20px; } \
+        table { display: table; border-spacing: 2px; } \
+        tbody { display: table-row-group; } \
+        tr { display: table-row; } \
+        td { display: table-cell; padding-left: 1px; padding-right: 1px; }";
+
+    /// Absolute x and width of each cell in each row of the first table
+    fn cell_columns(layout: &LayoutBox) -> Vec<Vec<(f32, f32)>> {
+        let table = &layout.children[0];
+        let rows: Vec<&LayoutBox> = table
+            .children
+            .iter()
+            .flat_map(|c| match table_part(c) {
+                Some(TablePart::RowGroup) => c.children.iter().collect(),
+                _ => vec![c],
+            })
+            .collect();
+
+        rows.iter()
+            .map(|row| {
+                row.children
+                    .iter()
+                    .map(|cell| {
+                        let border = cell.dimensions.border_box();
+                        (table.dimensions.content.x + border.x, border.width)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_columns_align_across_rows() {
+        let layout = setup_and_layout(
+            "<div><table>\
+                <tr><td>a</td><td>bbbbbbbbbb</td><td>c</td></tr>\
+                <tr><td colspan='2'>dd</td><td>eeee</td></tr>\
+                <tr><td>fff</td><td>g</td><td>h</td></tr>\
+             </table></div>",
+            TABLE_CSS,
+            400.0,
+        );
+
+        // Columns: "fff" 18px, "bbbbbbbbbb" 60px, "eeee" 24px, plus 2px padding
+        let rows = cell_columns(&layout);
+        assert_eq!(rows[0], vec![(2.0, 20.0), (24.0, 62.0), (88.0, 26.0)]);
+        assert_eq!(rows[1], vec![(2.0, 84.0), (88.0, 26.0)]);
+        assert_eq!(rows[2], vec![(2.0, 20.0), (24.0, 62.0), (88.0, 26.0)]);
+
+        // The table shrinks to fit its columns
+        let table = &layout.children[0];
+        assert_eq!(table.dimensions.content.width, 116.0);
+        // Rows are a line plus 1px UA padding above and below
+        assert_eq!(table.dimensions.content.height, 3.0 * 22.0 + 4.0 * 2.0);
+    }
+
+    #[test]
+    fn test_row_height_from_tallest_cell() {
+        let layout = setup_and_layout(
+            "<div><table><tr><td>a</td><td class='tall'>b</td></tr><tr><td>c</td></tr></table></div>",
+            &format!("{} .tall {{ height: 50px; }}", TABLE_CSS),
+            400.0,
+        );
+
+        let table = &layout.children[0];
+        let body = &table.children[0];
+        assert_eq!(body.children[0].dimensions.content.height, 52.0);
+        assert_eq!(body.children[1].dimensions.content.y, 54.0);
+
+        // Short cells stretch to the row
+        assert_eq!(body.children[0].children[0].dimensions.content.height, 50.0);
+    }
+
+    #[test]
+    fn test_rowspan_skips_occupied_slots() {
+        let layout = setup_and_layout(
+            "<div><table>\
+                <tr><td rowspan='2'>a</td><td>b</td></tr>\
+                <tr><td>c</td></tr>\
+             </table></div>",
+            TABLE_CSS,
+            400.0,
+        );
+
+        let rows = cell_columns(&layout);
+        assert_eq!(rows[1], vec![(12.0, 8.0)]);
+
+        let spanning = &layout.children[0].children[0].children[0].children[0];
+        assert_eq!(spanning.dimensions.border_box().height, 46.0);
+    }
+
+    #[test]
+    fn test_anonymous_table_for_orphan_cells() {
+        let layout = setup_and_layout(
+            "<div><span class='cell'>aa</span> <span class='cell'>bbbb</span></div>",
+            "div { display: block; font-size: 10px; line-height: 20px; } \
+             .cell { display: table-cell; }",
+            400.0,
+        );
+
+        // One anonymous table with one anonymous row holding both cells
+        assert_eq!(layout.children.len(), 1);
+        let table = &layout.children[0];
+        assert!(matches!(table.box_type, BoxType::AnonymousTable));
+        let row = &table.children[0];
+        assert_eq!(row.children.len(), 2);
+        assert_eq!(row.children[1].dimensions.content.x, 12.0);
+    }
+}
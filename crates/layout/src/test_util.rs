@@ -0,0 +1,31 @@
+//! Fixtures shared by the layout tests
+
+use gugalanna_css::Stylesheet;
+use gugalanna_dom::{DomTree, Queryable};
+use gugalanna_html::HtmlParser;
+use gugalanna_style::{Cascade, StyleTree};
+
+use crate::block::layout_document;
+use crate::boxtree::{build_layout_tree, LayoutBox};
+use crate::ContainingBlock;
+
+/// Parse `html`, style it with `css` and build the layout tree of its
+/// first div, not laid out yet
+pub fn build(html: &str, css: &str) -> (&'static DomTree, LayoutBox<'static>) {
+    // Leak the trees: LayoutBox borrows from the StyleTree
+    let dom = Box::leak(Box::new(HtmlParser::new().parse(html).unwrap()));
+    let mut cascade = Cascade::new();
+    cascade.add_author_stylesheet(Stylesheet::parse(css).unwrap());
+    let style_tree = Box::leak(Box::new(StyleTree::build(dom, &cascade, 1024.0, 768.0)));
+
+    let root_id = dom.get_elements_by_tag_name("div")[0];
+    (dom, build_layout_tree(dom, style_tree, root_id).unwrap())
+}
+
+/// Build the layout tree of the first div and lay it out as the root of a
+/// document `width` wide
+pub fn setup_and_layout(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+    let (_, mut layout) = build(html, css);
+    layout_document(&mut layout, ContainingBlock::new(width, 600.0));
+    layout
+}
@@ -2,8 +2,25 @@
 //!
 //! Interface for measuring text dimensions.
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use gugalanna_style::ComputedStyle;
 
+thread_local! {
+    /// Measurer installed for fonts loaded at runtime, used instead of the
+    /// simple measurer on this thread
+    static FONT_MEASURER: RefCell<Option<Rc<dyn TextMeasurer>>> = const { RefCell::new(None) };
+}
+
+/// Measure text laid out on this thread with the given measurer, or with
+/// the simple measurer again for `None`
+///
+/// The measurer has to measure text in the fonts it will be drawn with.
+pub fn set_font_measurer(measurer: Option<Rc<dyn TextMeasurer>>) {
+    FONT_MEASURER.with(|m| *m.borrow_mut() = measurer);
+}
+
 /// Text metrics for layout
 #[derive(Debug, Clone, Copy)]
 pub struct TextMetrics {
@@ -63,14 +80,17 @@ impl TextMeasurer for SimpleTextMeasurer {
 
 /// Measure text width using the simple measurer
 pub fn measure_text_width(text: &str, style: &ComputedStyle) -> f32 {
-    let measurer = SimpleTextMeasurer::new();
-    measurer.measure(text, style).width
+    measure_text(text, style).width
 }
 
-/// Measure full text metrics using the simple measurer
+/// Measure full text metrics using the installed font measurer, if any,
+/// or the simple measurer
 pub fn measure_text(text: &str, style: &ComputedStyle) -> TextMetrics {
-    let measurer = SimpleTextMeasurer::new();
-    measurer.measure(text, style)
+    let installed = FONT_MEASURER.with(|m| m.borrow().clone());
+    match installed {
+        Some(measurer) => measurer.measure(text, style),
+        None => SimpleTextMeasurer::new().measure(text, style),
+    }
 }
 
 #[cfg(test)]
@@ -90,6 +110,22 @@ mod tests {
         assert_eq!(metrics.height, 20.0);
     }
 
+    #[test]
+    fn test_installed_font_measurer() {
+        struct Wide;
+        impl TextMeasurer for Wide {
+            fn measure(&self, text: &str, style: &ComputedStyle) -> TextMetrics {
+                TextMetrics { width: text.len() as f32 * style.font_size, ..SimpleTextMeasurer.measure(text, style) }
+            }
+        }
+
+        let style = ComputedStyle { font_size: 10.0, ..ComputedStyle::default() };
+        set_font_measurer(Some(Rc::new(Wide)));
+        assert_eq!(measure_text_width("abc", &style), 30.0);
+        set_font_measurer(None);
+        assert_eq!(measure_text_width("abc", &style), 18.0);
+    }
+
     #[test]
     fn test_empty_text() {
         let style = ComputedStyle::default();
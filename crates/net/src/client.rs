@@ -6,14 +6,16 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use log::{debug, info};
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, USER_AGENT};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, COOKIE, SET_COOKIE, USER_AGENT};
 use url::Url;
 
+use crate::cookie::{new_cookie_jar, CookieSource, SharedCookieJar};
 use crate::error::{NetError, NetResult};
-use crate::response::Response;
+use crate::multipart::Multipart;
+use crate::response::{Response, StreamingResponse};
 
-/// Default user agent string
-const DEFAULT_USER_AGENT: &str = concat!("Gugalanna/", env!("CARGO_PKG_VERSION"));
+/// User agent string sent with every request
+pub const DEFAULT_USER_AGENT: &str = concat!("Gugalanna/", env!("CARGO_PKG_VERSION"));
 
 /// Default timeout in seconds
 const DEFAULT_TIMEOUT_SECS: u64 = 30;
@@ -60,6 +62,8 @@ pub struct HttpClient {
     requests: Option<NetworkRequests>,
     /// Counter for request IDs
     next_id: Arc<AtomicUsize>,
+    /// Cookies sent with requests and set by responses
+    cookies: SharedCookieJar,
 }
 
 impl HttpClient {
@@ -71,7 +75,14 @@ impl HttpClient {
     /// Create a new HTTP client with custom configuration
     pub fn with_config(config: ClientConfig) -> NetResult<Self> {
         let mut headers = HeaderMap::new();
-        headers.insert(USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
+        let user_agent = config.user_agent.as_deref().and_then(|agent| match HeaderValue::from_str(agent) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                log::warn!("Invalid User-Agent {:?}, sending the default", agent);
+                None
+            }
+        });
+        headers.insert(USER_AGENT, user_agent.unwrap_or(HeaderValue::from_static(DEFAULT_USER_AGENT)));
         headers.insert(
             ACCEPT,
             HeaderValue::from_static("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
@@ -89,9 +100,39 @@ impl HttpClient {
             client,
             requests: None,
             next_id: Arc::new(AtomicUsize::new(0)),
+            cookies: new_cookie_jar(),
         })
     }
 
+    /// The cookie jar of the client, shared by its clones
+    pub fn cookie_jar(&self) -> SharedCookieJar {
+        self.cookies.clone()
+    }
+
+    /// A clone sharing the connection pool and request tracking of the
+    /// client, but with a cookie jar of its own, as private tabs use
+    pub fn with_isolated_state(&self) -> Self {
+        Self { cookies: new_cookie_jar(), ..self.clone() }
+    }
+
+    /// The `Cookie` header of a request to `url`, None without cookies
+    fn cookie_header(&self, url: &Url) -> Option<HeaderValue> {
+        let cookies = self.cookies.lock().ok()?.cookie_string(url, CookieSource::Http);
+        if cookies.is_empty() {
+            return None;
+        }
+        HeaderValue::try_from(cookies).ok()
+    }
+
+    /// Store the cookies a response from `url` sets
+    fn store_cookies(&self, url: &Url, headers: &HeaderMap) {
+        if let Ok(mut jar) = self.cookies.lock() {
+            for header in headers.get_all(SET_COOKIE).iter().filter_map(|v| v.to_str().ok()) {
+                jar.set_cookie(url, header, CookieSource::Http);
+            }
+        }
+    }
+
     /// Create a new HTTP client with request tracking for DevTools
     pub fn with_tracking(requests: NetworkRequests) -> NetResult<Self> {
         let mut client = Self::new()?;
@@ -158,16 +199,36 @@ impl HttpClient {
         url: &Url,
         extra_headers: HashMap<String, String>,
     ) -> NetResult<Response> {
-        info!("Fetching: {}", url);
+        self.request("GET", url, extra_headers, None).await
+    }
+
+    /// Send a request with any method, custom headers and an optional body
+    pub async fn request(
+        &self,
+        method: &str,
+        url: &Url,
+        extra_headers: HashMap<String, String>,
+        body: Option<String>,
+    ) -> NetResult<Response> {
+        info!("Fetching: {} {}", method, url);
+
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|_| NetError::RequestFailed(format!("Invalid method: {}", method)))?;
 
         // Track request start
         let req_headers: Vec<(String, String)> = extra_headers
             .iter()
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect();
-        let request_id = self.track_request_start("GET", url.as_str(), &req_headers);
+        let request_id = self.track_request_start(method.as_str(), url.as_str(), &req_headers);
 
-        let mut request = self.client.get(url.clone());
+        let mut request = self.client.request(method, url.clone());
+        if let Some(cookies) = self.cookie_header(url) {
+            request = request.header(COOKIE, cookies);
+        }
+        if let Some(body) = body {
+            request = request.body(body);
+        }
 
         // Add extra headers
         for (key, value) in extra_headers {
@@ -183,19 +244,11 @@ impl HttpClient {
 
         let final_url = response.url().clone();
         let status = response.status().as_u16();
+        self.store_cookies(&final_url, response.headers());
 
         debug!("Response status: {}", status);
 
-        // Convert headers
-        let headers: HashMap<String, String> = response
-            .headers()
-            .iter()
-            .filter_map(|(k, v)| {
-                v.to_str()
-                    .ok()
-                    .map(|val| (k.as_str().to_lowercase(), val.to_string()))
-            })
-            .collect();
+        let headers = response_headers(response.headers());
 
         let body = response.bytes().await?.to_vec();
 
@@ -213,39 +266,75 @@ impl HttpClient {
         Ok(Response::new(final_url, status, headers, body))
     }
 
+    /// Fetch a URL using GET, returning once the response headers arrive
+    /// so its body can be read as it comes
+    pub async fn get_streaming(&self, url: &Url) -> NetResult<StreamingResponse> {
+        info!("Fetching: GET {} (streaming)", url);
+
+        let request_id = self.track_request_start("GET", url.as_str(), &[]);
+
+        let mut request = self.client.get(url.clone());
+        if let Some(cookies) = self.cookie_header(url) {
+            request = request.header(COOKIE, cookies);
+        }
+        let response = request.send().await?;
+
+        let final_url = response.url().clone();
+        let status = response.status().as_u16();
+        self.store_cookies(&final_url, response.headers());
+
+        debug!("Response status: {}", status);
+
+        let headers = response_headers(response.headers());
+
+        // The body is not read yet, its size is the one the server sent
+        if let Some(id) = request_id {
+            let resp_headers: Vec<(String, String)> = headers
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            let size = response.content_length().unwrap_or(0) as usize;
+            self.track_request_complete(id, status, size, resp_headers);
+        }
+
+        Ok(StreamingResponse::new(final_url, status, headers, response))
+    }
+
     /// Send a POST request with form data
     pub async fn post_form(&self, url: &Url, form_data: &str) -> NetResult<Response> {
         info!("POST to: {} with data: {}", url, form_data);
+        self.post(url, "application/x-www-form-urlencoded", form_data.as_bytes().to_vec()).await
+    }
+
+    /// Send a POST request with a `multipart/form-data` body
+    pub async fn post_multipart(&self, url: &Url, form: &Multipart) -> NetResult<Response> {
+        let body = form.to_bytes();
+        info!("POST to: {} with {} bytes of multipart data", url, body.len());
+        self.post(url, &form.content_type(), body).await
+    }
 
+    async fn post(&self, url: &Url, content_type: &str, body: Vec<u8>) -> NetResult<Response> {
         // Track request start
-        let req_headers = vec![
-            ("Content-Type".to_string(), "application/x-www-form-urlencoded".to_string()),
-        ];
+        let req_headers = vec![("Content-Type".to_string(), content_type.to_string())];
         let request_id = self.track_request_start("POST", url.as_str(), &req_headers);
 
-        let response = self
+        let mut request = self
             .client
             .post(url.clone())
-            .header("Content-Type", "application/x-www-form-urlencoded")
-            .body(form_data.to_string())
-            .send()
-            .await?;
+            .header("Content-Type", content_type)
+            .body(body);
+        if let Some(cookies) = self.cookie_header(url) {
+            request = request.header(COOKIE, cookies);
+        }
+        let response = request.send().await?;
 
         let final_url = response.url().clone();
         let status = response.status().as_u16();
+        self.store_cookies(&final_url, response.headers());
 
         debug!("Response status: {}", status);
 
-        // Convert headers
-        let headers: HashMap<String, String> = response
-            .headers()
-            .iter()
-            .filter_map(|(k, v)| {
-                v.to_str()
-                    .ok()
-                    .map(|val| (k.as_str().to_lowercase(), val.to_string()))
-            })
-            .collect();
+        let headers = response_headers(response.headers());
 
         let body = response.bytes().await?.to_vec();
 
@@ -270,16 +359,31 @@ impl Default for HttpClient {
     }
 }
 
+/// Response headers by lowercase name
+fn response_headers(headers: &HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(k, v)| {
+            v.to_str()
+                .ok()
+                .map(|val| (k.as_str().to_lowercase(), val.to_string()))
+        })
+        .collect()
+}
+
 /// HTTP client configuration
 pub struct ClientConfig {
     /// Request timeout in seconds
     pub timeout_secs: u64,
+    /// User-Agent sent in place of `DEFAULT_USER_AGENT`
+    pub user_agent: Option<String>,
 }
 
 impl Default for ClientConfig {
     fn default() -> Self {
         Self {
             timeout_secs: DEFAULT_TIMEOUT_SECS,
+            user_agent: None,
         }
     }
 }
@@ -287,6 +391,133 @@ impl Default for ClientConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_request_sends_method_headers_and_body() {
+        // A server answering one request with what it received
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/echo", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0; 1024];
+            while !String::from_utf8_lossy(&received).ends_with("name=value") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..n]);
+            }
+            let body = String::from_utf8_lossy(&received).to_lowercase();
+            write!(
+                stream,
+                "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        let config = ClientConfig { user_agent: Some("Tester/2.0".to_string()), ..ClientConfig::default() };
+        let client = HttpClient::with_config(config).unwrap();
+        let headers = HashMap::from([("X-Token".to_string(), "abc".to_string())]);
+        let response = client.request("PUT", &url, headers, Some("name=value".to_string())).await.unwrap();
+        server.join().unwrap();
+
+        assert_eq!(response.status, 201);
+        assert_eq!(response.content_type(), Some("text/plain"));
+        let echoed = response.text_lossy();
+        assert!(echoed.starts_with("put /echo http/1.1"));
+        assert!(echoed.contains("x-token: abc"));
+        assert!(echoed.contains("user-agent: tester/2.0"));
+        assert!(echoed.ends_with("name=value"));
+    }
+
+    #[tokio::test]
+    async fn test_cookies_sent_back() {
+        // A server setting a cookie, then answering with the cookies sent
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            for set_cookie in ["Set-Cookie: id=1; HttpOnly\r\n", ""] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut received = Vec::new();
+                let mut buf = [0; 1024];
+                while !String::from_utf8_lossy(&received).ends_with("\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    received.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&received).to_lowercase();
+                let cookie = request.lines().find_map(|l| l.strip_prefix("cookie: ")).unwrap_or("").to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    set_cookie,
+                    cookie.len(),
+                    cookie
+                )
+                .unwrap();
+            }
+        });
+
+        let client = HttpClient::new().unwrap();
+        client.cookie_jar().lock().unwrap().set_cookie(&url, "theme=dark", CookieSource::Script);
+        let first = client.get(&url).await.unwrap();
+        let second = client.clone().get(&url).await.unwrap();
+        server.join().unwrap();
+
+        assert_eq!(first.text_lossy(), "theme=dark");
+        assert_eq!(second.text_lossy(), "theme=dark; id=1");
+    }
+
+    #[test]
+    fn test_isolated_state_has_own_cookies() {
+        let url = Url::parse("https://a.test/").unwrap();
+        let client = HttpClient::new().unwrap();
+        client.cookie_jar().lock().unwrap().set_cookie(&url, "theme=dark", CookieSource::Script);
+
+        let isolated = client.with_isolated_state();
+        assert_eq!(isolated.cookie_jar().lock().unwrap().cookie_string(&url, CookieSource::Http), "");
+        isolated.cookie_jar().lock().unwrap().set_cookie(&url, "id=2", CookieSource::Http);
+        assert_eq!(client.cookie_jar().lock().unwrap().cookie_string(&url, CookieSource::Http), "theme=dark");
+
+        // Its clones share its jar
+        let jar = isolated.clone().cookie_jar();
+        assert_eq!(jar.lock().unwrap().cookie_string(&url, CookieSource::Http), "id=2");
+    }
+
+    #[tokio::test]
+    async fn test_streaming_body_read_in_chunks() {
+        // A server sending its body in two parts
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/file.bin", listener.local_addr().unwrap())).unwrap();
+        let (sent_tx, sent_rx) = std::sync::mpsc::channel::<()>();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).unwrap();
+            assert!(String::from_utf8_lossy(&buf[..n]).starts_with("GET /file.bin"));
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\nhello").unwrap();
+            stream.flush().unwrap();
+            sent_rx.recv().unwrap();
+            write!(stream, "world").unwrap();
+        });
+
+        let client = HttpClient::new().unwrap();
+        let mut response = client.get_streaming(&url).await.unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.content_length(), Some(10));
+        assert_eq!(response.chunk().await.unwrap().as_deref(), Some(&b"hello"[..]));
+        sent_tx.send(()).unwrap();
+        assert_eq!(response.chunk().await.unwrap().as_deref(), Some(&b"world"[..]));
+        assert_eq!(response.chunk().await.unwrap(), None);
+        server.join().unwrap();
+    }
 
     #[tokio::test]
     async fn test_fetch_example() {
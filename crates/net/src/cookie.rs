@@ -0,0 +1,378 @@
+//! Cookie jar
+//!
+//! Stores the cookies servers set with `Set-Cookie` and scripts set with
+//! `document.cookie`, and picks those a request to a URL sends. Cookies
+//! are kept in memory for the session; those past their expiry are
+//! dropped. Only http and https URLs have cookies.
+
+use std::cmp::Reverse;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use url::{Host, Url};
+
+/// When a cookie is sent along with requests from other sites
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// Who set a cookie or reads the cookies of a URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieSource {
+    /// A server, through the headers of a response
+    Http,
+    /// A page's scripts, through `document.cookie`, which never see or
+    /// set HttpOnly cookies
+    Script,
+}
+
+/// A stored cookie
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase host the cookie belongs to
+    pub domain: String,
+    /// Sent to the domain only, not to its subdomains, as when set
+    /// without a Domain attribute
+    pub host_only: bool,
+    pub path: String,
+    /// None for a session cookie
+    pub expires: Option<SystemTime>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Check if the cookie is sent to `host`
+    fn matches_domain(&self, host: &str) -> bool {
+        if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(host, &self.domain)
+        }
+    }
+}
+
+/// Check if `host` is `domain` or one of its subdomains
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// Suffixes of more than one label under which anyone can register a
+/// name, the most used of the Public Suffix List
+const PUBLIC_SUFFIXES: &[&str] = &[
+    "co.uk", "org.uk", "me.uk", "ac.uk", "gov.uk", "ltd.uk", "plc.uk", "net.uk",
+    "com.au", "net.au", "org.au", "edu.au", "gov.au",
+    "co.nz", "org.nz", "net.nz",
+    "co.jp", "ne.jp", "or.jp", "ac.jp", "go.jp",
+    "co.kr", "or.kr",
+    "co.in", "net.in", "org.in",
+    "co.za", "org.za",
+    "com.br", "net.br", "org.br",
+    "com.cn", "net.cn", "org.cn",
+    "com.mx", "com.ar", "com.tr", "com.tw", "com.hk", "com.sg",
+    "github.io", "gitlab.io", "herokuapp.com", "blogspot.com", "appspot.com", "netlify.app", "vercel.app",
+];
+
+/// Check if anyone can register a name under `domain`, so no site may
+/// set cookies for all of it; every top-level domain is
+fn is_public_suffix(domain: &str) -> bool {
+    !domain.contains('.') || PUBLIC_SUFFIXES.contains(&domain)
+}
+
+/// Check if a request to `request_path` sends a cookie of `cookie_path`
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// The path a cookie set without a Path attribute gets, the directory of
+/// the URL's path
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => path[..end].to_string(),
+    }
+}
+
+/// The lowercase host of an http or https URL
+fn cookie_host(url: &Url) -> Option<String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    url.host_str().map(str::to_ascii_lowercase)
+}
+
+/// Parse a `Set-Cookie` header for a response from `url`, None when it is
+/// malformed or sets a cookie for another domain
+pub fn parse_set_cookie(header: &str, url: &Url, now: SystemTime) -> Option<Cookie> {
+    let host = cookie_host(url)?;
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().trim_matches('"').to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(url),
+        expires: None,
+        secure: false,
+        http_only: false,
+        same_site: None,
+    };
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, value) = match attribute.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (attribute.trim(), ""),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                // IP addresses have no subdomains, and a public suffix is
+                // only a host's own domain when it is the host
+                let is_ip = !matches!(url.host(), Some(Host::Domain(_)));
+                let too_wide = is_ip || is_public_suffix(&domain);
+                if !domain_matches(&host, &domain) || (too_wide && domain != host) {
+                    return None;
+                }
+                cookie.host_only = domain == host;
+                cookie.domain = domain;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "expires" => {
+                if let Ok(expires) = httpdate::parse_http_date(value) {
+                    cookie.expires = Some(expires);
+                }
+            }
+            "max-age" => max_age = value.parse::<i64>().ok(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "samesite" => {
+                cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                    "strict" => Some(SameSite::Strict),
+                    "lax" => Some(SameSite::Lax),
+                    "none" => Some(SameSite::None),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Max-Age wins over Expires, zero or less expires the cookie at once
+    if let Some(seconds) = max_age {
+        cookie.expires = Some(match u64::try_from(seconds) {
+            Ok(seconds) if seconds > 0 => now.checked_add(Duration::from_secs(seconds)).unwrap_or(now),
+            _ => SystemTime::UNIX_EPOCH,
+        });
+    }
+    Some(cookie)
+}
+
+/// The cookies of a browsing session
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    /// Cookies in the order they were first set
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a cookie set for `url` by a `Set-Cookie` header or a script,
+    /// returns whether it was taken
+    ///
+    /// Scripts cannot set HttpOnly cookies nor replace them. A cookie
+    /// that already expired deletes the one it would replace.
+    pub fn set_cookie(&mut self, url: &Url, header: &str, source: CookieSource) -> bool {
+        let now = SystemTime::now();
+        let Some(cookie) = parse_set_cookie(header, url, now) else {
+            return false;
+        };
+        if cookie.secure && url.scheme() != "https" {
+            return false;
+        }
+        let existing = self.cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        });
+        if source == CookieSource::Script
+            && (cookie.http_only || existing.is_some_and(|i| self.cookies[i].http_only))
+        {
+            return false;
+        }
+
+        match existing {
+            Some(i) if cookie.is_expired(now) => {
+                self.cookies.remove(i);
+            }
+            Some(i) => self.cookies[i] = cookie,
+            None if cookie.is_expired(now) => {}
+            None => self.cookies.push(cookie),
+        }
+        true
+    }
+
+    /// The cookies a request to `url` sends, those with longer paths first
+    pub fn cookies_for(&self, url: &Url, source: CookieSource) -> Vec<&Cookie> {
+        let Some(host) = cookie_host(url) else {
+            return Vec::new();
+        };
+        let now = SystemTime::now();
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| !c.is_expired(now) && c.matches_domain(&host) && path_matches(url.path(), &c.path))
+            .filter(|c| !c.secure || url.scheme() == "https")
+            .filter(|c| source == CookieSource::Http || !c.http_only)
+            .collect();
+        cookies.sort_by_key(|c| Reverse(c.path.len()));
+        cookies
+    }
+
+    /// The cookies of `url` as `name=value` pairs joined by `; `, the
+    /// value of a `Cookie` header and of `document.cookie`
+    pub fn cookie_string(&self, url: &Url, source: CookieSource) -> String {
+        self.cookies_for(url, source)
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Drop the cookies past their expiry
+    pub fn remove_expired(&mut self) {
+        let now = SystemTime::now();
+        self.cookies.retain(|c| !c.is_expired(now));
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+}
+
+/// Cookie jar shared by the clients and pages of a session
+pub type SharedCookieJar = Arc<Mutex<CookieJar>>;
+
+/// Create an empty shared cookie jar
+pub fn new_cookie_jar() -> SharedCookieJar {
+    Arc::new(Mutex::new(CookieJar::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let page = url("https://example.com/docs/page.html");
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let cookie = parse_set_cookie("id=42; Max-Age=60; Secure; HttpOnly; SameSite=Lax", &page, now).unwrap();
+        assert_eq!((cookie.name.as_str(), cookie.value.as_str()), ("id", "42"));
+        assert_eq!(cookie.path, "/docs");
+        assert!(cookie.host_only && cookie.secure && cookie.http_only);
+        assert_eq!(cookie.same_site, Some(SameSite::Lax));
+        assert_eq!(cookie.expires, Some(now + Duration::from_secs(60)));
+
+        let cookie = parse_set_cookie("a=b; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Path=/", &page, now).unwrap();
+        assert_eq!(cookie.expires, Some(httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap()));
+        assert_eq!(cookie.path, "/");
+    }
+
+    #[test]
+    fn test_foreign_domains_rejected() {
+        let page = url("http://www.example.com/");
+        let now = SystemTime::now();
+        let cookie = parse_set_cookie("a=1; Domain=.example.com", &page, now).unwrap();
+        assert!(!cookie.host_only);
+        assert_eq!(cookie.domain, "example.com");
+        assert!(parse_set_cookie("a=1; Domain=other.com", &page, now).is_none());
+        assert!(parse_set_cookie("a=1; Domain=ample.com", &page, now).is_none());
+        assert!(parse_set_cookie("a=1", &url("file:///tmp/page.html"), now).is_none());
+    }
+
+    #[test]
+    fn test_public_suffix_domains_rejected() {
+        let now = SystemTime::now();
+        assert!(parse_set_cookie("a=1; Domain=com", &url("https://example.com/"), now).is_none());
+        assert!(parse_set_cookie("a=1; Domain=.co.uk", &url("https://shop.example.co.uk/"), now).is_none());
+        let cookie = parse_set_cookie("a=1; Domain=example.co.uk", &url("https://shop.example.co.uk/"), now).unwrap();
+        assert_eq!(cookie.domain, "example.co.uk");
+
+        // A host that is itself a suffix only gets its own cookies
+        let cookie = parse_set_cookie("a=1; Domain=localhost", &url("http://localhost:8080/"), now).unwrap();
+        assert!(cookie.host_only);
+    }
+
+    #[test]
+    fn test_cookies_for_url() {
+        let mut jar = CookieJar::new();
+        let page = url("http://www.example.com/app/index.html");
+        assert!(jar.set_cookie(&page, "site=1; Domain=example.com; Path=/", CookieSource::Http));
+        assert!(jar.set_cookie(&page, "app=2", CookieSource::Http));
+        assert!(jar.set_cookie(&page, "token=3; HttpOnly", CookieSource::Http));
+
+        assert_eq!(jar.cookie_string(&page, CookieSource::Http), "app=2; token=3; site=1");
+        assert_eq!(jar.cookie_string(&page, CookieSource::Script), "app=2; site=1");
+        assert_eq!(jar.cookie_string(&url("http://api.example.com/"), CookieSource::Http), "site=1");
+        assert_eq!(jar.cookie_string(&url("http://www.example.com/application"), CookieSource::Http), "site=1");
+        assert_eq!(jar.cookie_string(&url("http://other.com/app/"), CookieSource::Http), "");
+    }
+
+    #[test]
+    fn test_scripts_cannot_touch_http_only_cookies() {
+        let mut jar = CookieJar::new();
+        let page = url("http://example.com/");
+        assert!(!jar.set_cookie(&page, "session=x; HttpOnly", CookieSource::Script));
+        assert!(jar.is_empty());
+
+        jar.set_cookie(&page, "session=x; HttpOnly", CookieSource::Http);
+        assert!(!jar.set_cookie(&page, "session=y", CookieSource::Script));
+        assert_eq!(jar.cookie_string(&page, CookieSource::Http), "session=x");
+    }
+
+    #[test]
+    fn test_replace_and_expire() {
+        let mut jar = CookieJar::new();
+        let page = url("https://example.com/");
+        jar.set_cookie(&page, "theme=dark", CookieSource::Script);
+        jar.set_cookie(&page, "theme=light", CookieSource::Script);
+        assert_eq!(jar.len(), 1);
+        assert_eq!(jar.cookie_string(&page, CookieSource::Script), "theme=light");
+
+        jar.set_cookie(&page, "theme=; Max-Age=0", CookieSource::Script);
+        assert!(jar.is_empty());
+
+        // Secure cookies only go over https
+        jar.set_cookie(&page, "s=1; Secure", CookieSource::Http);
+        assert_eq!(jar.cookie_string(&url("http://example.com/"), CookieSource::Http), "");
+        assert!(!jar.set_cookie(&url("http://example.com/"), "t=1; Secure", CookieSource::Http));
+    }
+}
@@ -33,6 +33,22 @@ pub enum NetError {
     Io(#[from] std::io::Error),
 }
 
+impl NetError {
+    /// Name of the kind of error, without its details
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NetError::InvalidUrl(_) => "Invalid URL",
+            NetError::RequestFailed(_) => "Request failed",
+            NetError::ConnectionError(_) => "Connection error",
+            NetError::Timeout => "Timeout",
+            NetError::TlsError(_) => "TLS error",
+            NetError::TooManyRedirects => "Too many redirects",
+            NetError::HttpError { .. } => "HTTP error",
+            NetError::Io(_) => "IO error",
+        }
+    }
+}
+
 impl From<reqwest::Error> for NetError {
     fn from(err: reqwest::Error) -> Self {
         if err.is_timeout() {
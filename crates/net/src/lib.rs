@@ -3,11 +3,15 @@
 //! Provides HTTP/HTTPS fetching capabilities for the browser.
 
 mod client;
+mod cookie;
 mod error;
 mod loader;
+mod multipart;
 mod response;
 
-pub use client::{HttpClient, NetworkRequest, NetworkRequests, new_network_requests};
+pub use client::{ClientConfig, HttpClient, DEFAULT_USER_AGENT, NetworkRequest, NetworkRequests, new_network_requests};
+pub use cookie::{new_cookie_jar, parse_set_cookie, Cookie, CookieJar, CookieSource, SameSite, SharedCookieJar};
 pub use error::{NetError, NetResult};
 pub use loader::{ResourceLoader, ResourceType};
-pub use response::Response;
+pub use multipart::{guess_content_type, Multipart, DEFAULT_FILE_TYPE};
+pub use response::{Response, StreamingResponse};
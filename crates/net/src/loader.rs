@@ -89,6 +89,15 @@ impl ResourceLoader {
         })
     }
 
+    /// Create a resource loader fetching with an existing client, its
+    /// cookies and headers
+    pub fn with_client(client: HttpClient) -> Self {
+        Self {
+            client,
+            cache: HashMap::new(),
+        }
+    }
+
     /// Load a resource from a URL
     pub async fn load(&mut self, url: &Url) -> NetResult<&Response> {
         let key = url.to_string();
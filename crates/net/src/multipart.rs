@@ -0,0 +1,212 @@
+//! Multipart form bodies
+//!
+//! The `multipart/form-data` body a form with that enctype submits: one
+//! part per field, text fields as their value and file fields as the
+//! file's name, type and contents, each part set off by a boundary that
+//! appears nowhere in the data.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Content type of files of an unknown kind
+pub const DEFAULT_FILE_TYPE: &str = "application/octet-stream";
+
+/// One field of a multipart body
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text { name: String, value: String },
+    File { name: String, filename: String, content_type: String, data: Vec<u8> },
+}
+
+impl Part {
+    /// Whether `needle` appears in the part's headers or contents
+    fn contains(&self, needle: &str) -> bool {
+        match self {
+            Part::Text { name, value } => name.contains(needle) || value.contains(needle),
+            Part::File { name, filename, content_type, data } => {
+                [name, filename, content_type].iter().any(|header| header.contains(needle))
+                    || data.windows(needle.len()).any(|window| window == needle.as_bytes())
+            }
+        }
+    }
+}
+
+/// A `multipart/form-data` body being built
+#[derive(Debug, Clone)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+impl Multipart {
+    /// An empty body with a boundary of its own
+    pub fn new() -> Self {
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        Self::with_boundary(&format!("----GugalannaFormBoundary{:x}{:x}{:x}", nanos, std::process::id(), count))
+    }
+
+    /// An empty body set off by `boundary`
+    pub fn with_boundary(boundary: &str) -> Self {
+        Self { boundary: boundary.to_string(), parts: Vec::new() }
+    }
+
+    /// The boundary the body is set off by: the one it was made with, or
+    /// when a part contains that, the first variant of it none contains
+    pub fn boundary(&self) -> String {
+        let mut boundary = self.boundary.clone();
+        let mut attempt = 0u32;
+        while self.parts.iter().any(|part| part.contains(&boundary)) {
+            attempt += 1;
+            boundary = format!("{}-{:x}", self.boundary, attempt);
+        }
+        boundary
+    }
+
+    /// Add a text field
+    pub fn text(&mut self, name: &str, value: &str) -> &mut Self {
+        self.parts.push(Part::Text { name: name.to_string(), value: value.to_string() });
+        self
+    }
+
+    /// Add a file field
+    pub fn file(&mut self, name: &str, filename: &str, content_type: &str, data: Vec<u8>) -> &mut Self {
+        self.parts.push(Part::File {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            data,
+        });
+        self
+    }
+
+    /// Add the file at `path` as a file field, its type guessed from its
+    /// extension
+    pub fn file_from_path(&mut self, name: &str, path: &Path) -> std::io::Result<&mut Self> {
+        let data = std::fs::read(path)?;
+        let filename = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(self.file(name, &filename, guess_content_type(path), data))
+    }
+
+    /// Value of the Content-Type header the body is sent with
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary())
+    }
+
+    /// The body's bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let boundary = self.boundary();
+        let mut body = Vec::new();
+        for part in &self.parts {
+            body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            match part {
+                Part::Text { name, value } => {
+                    let header = format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", escape(name));
+                    body.extend_from_slice(header.as_bytes());
+                    body.extend_from_slice(normalize_newlines(value).as_bytes());
+                }
+                Part::File { name, filename, content_type, data } => {
+                    let header = format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                        escape(name),
+                        escape(filename),
+                        content_type
+                    );
+                    body.extend_from_slice(header.as_bytes());
+                    body.extend_from_slice(data);
+                }
+            }
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        body
+    }
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Content type of a file, guessed from its extension
+pub fn guess_content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match extension.as_str() {
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => DEFAULT_FILE_TYPE,
+    }
+}
+
+/// Escape the quotes and line breaks of a name or filename, as browsers do
+fn escape(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Line breaks as CRLF
+fn normalize_newlines(value: &str) -> String {
+    value.replace("\r\n", "\n").replace('\r', "\n").replace('\n', "\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multipart_body() {
+        let mut form = Multipart::with_boundary("XYZ");
+        form.text("title", "Line one\nLine \"two\"").file("upload", "a.txt", "text/plain", b"hello".to_vec());
+        let body = String::from_utf8(form.to_bytes()).unwrap();
+        assert_eq!(
+            body,
+            "--XYZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nLine one\r\nLine \"two\"\r\n\
+             --XYZ\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\nhello\r\n--XYZ--\r\n"
+        );
+        assert_eq!(form.content_type(), "multipart/form-data; boundary=XYZ");
+        assert_ne!(Multipart::new().boundary(), Multipart::new().boundary());
+        assert_eq!(guess_content_type(Path::new("photo.JPG")), "image/jpeg");
+        assert_eq!(guess_content_type(Path::new("notes")), DEFAULT_FILE_TYPE);
+    }
+
+    #[test]
+    fn test_boundary_in_file_is_replaced() {
+        let mut form = Multipart::new();
+        let first = form.boundary();
+        let data = format!("not the end\r\n--{}--\r\n", first).into_bytes();
+        form.file("upload", "a.txt", "text/plain", data.clone());
+
+        let boundary = form.boundary();
+        assert_ne!(boundary, first);
+        assert!(!String::from_utf8(data.clone()).unwrap().contains(&boundary));
+        assert_eq!(form.content_type(), format!("multipart/form-data; boundary={}", boundary));
+        let header = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n",
+            boundary
+        );
+        let expected = [header.as_bytes(), &data, format!("\r\n--{}--\r\n", boundary).as_bytes()].concat();
+        assert_eq!(form.to_bytes(), expected);
+    }
+}
@@ -3,6 +3,8 @@
 use std::collections::HashMap;
 use url::Url;
 
+use crate::error::NetResult;
+
 /// HTTP response
 #[derive(Debug)]
 pub struct Response {
@@ -50,3 +52,48 @@ impl Response {
         String::from_utf8_lossy(&self.body).into_owned()
     }
 }
+
+/// HTTP response whose body is read as it arrives
+#[derive(Debug)]
+pub struct StreamingResponse {
+    /// Final URL after redirects
+    pub url: Url,
+    /// HTTP status code
+    pub status: u16,
+    /// Response headers
+    pub headers: HashMap<String, String>,
+    /// Response body, not read yet
+    body: reqwest::Response,
+}
+
+impl StreamingResponse {
+    pub(crate) fn new(url: Url, status: u16, headers: HashMap<String, String>, body: reqwest::Response) -> Self {
+        Self {
+            url,
+            status,
+            headers,
+            body,
+        }
+    }
+
+    /// Check if the response was successful (2xx)
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Size of the body, when the server sent it
+    pub fn content_length(&self) -> Option<u64> {
+        self.body.content_length()
+    }
+
+    /// Read the next chunk of the body, None once it has all been read
+    pub async fn chunk(&mut self) -> NetResult<Option<Vec<u8>>> {
+        Ok(self.body.chunk().await?.map(|chunk| chunk.to_vec()))
+    }
+
+    /// Read the rest of the body
+    pub async fn into_response(self) -> NetResult<Response> {
+        let body = self.body.bytes().await?.to_vec();
+        Ok(Response::new(self.url, self.status, self.headers, body))
+    }
+}
@@ -0,0 +1,110 @@
+//! Display List Benchmark
+//!
+//! Frame time and allocations of handing a 10k-command page to 300 frames,
+//! cloning the list each frame as the shell used to and sharing it while
+//! repainting what changed between frames.
+//!
+//! Run with `cargo bench -p gugalanna-render`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use gugalanna_layout::Rect;
+use gugalanna_render::{commands_in_rect, damaged_rect, DisplayList, FontFace, PaintCommand, RenderColor};
+
+const COMMANDS: usize = 10_000;
+const FRAMES: usize = 300;
+
+/// Allocator counting allocations
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// A page of text lines on backgrounds
+fn page() -> DisplayList {
+    let commands = (0..COMMANDS)
+        .map(|i| {
+            let y = (i / 2) as f32 * 20.0;
+            if i % 2 == 0 {
+                PaintCommand::FillRect { rect: Rect::new(0.0, y, 800.0, 20.0), color: RenderColor::white() }
+            } else {
+                PaintCommand::DrawText {
+                    text: format!("Line {}", i / 2),
+                    x: 8.0,
+                    y,
+                    color: RenderColor::black(),
+                    font_size: 16.0,
+                    face: FontFace::REGULAR,
+                    family: None,
+                }
+            }
+        })
+        .collect();
+    DisplayList { commands }
+}
+
+/// Run `frame` for each frame, printing its time and allocations
+fn bench(name: &str, mut frame: impl FnMut(usize)) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for i in 0..FRAMES {
+        frame(i);
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{:<8} {:>9.3} ms/frame {:>9} allocations/frame",
+        name,
+        elapsed.as_secs_f64() * 1000.0 / FRAMES as f64,
+        allocations / FRAMES
+    );
+}
+
+fn main() {
+    let mut page = Rc::new(page());
+    let caret = |x| PaintCommand::FillRect { rect: Rect::new(x, 0.0, 1.0, 16.0), color: RenderColor::black() };
+
+    // Every frame deep-cloned the page's list and painted all of it
+    bench("cloned", |_| {
+        let list = DisplayList::clone(&page);
+        list.commands.iter().for_each(|command| {
+            black_box(command);
+        });
+    });
+
+    // Frames share the list and repaint what changed since the last one,
+    // the page being rebuilt with a moved caret every 10 frames
+    let mut painted = Rc::clone(&page);
+    bench("shared", |i| {
+        if i % 10 == 0 {
+            let mut rebuilt = DisplayList::clone(&page);
+            rebuilt.commands[1] = caret(8.0 + i as f32);
+            page = Rc::new(rebuilt);
+        }
+
+        let list = Rc::clone(&page);
+        if let Some(damage) = damaged_rect(&painted, &list) {
+            commands_in_rect(&list, &damage).commands.iter().for_each(|command| {
+                black_box(command);
+            });
+        }
+        painted = list;
+    });
+}
@@ -0,0 +1,57 @@
+//! Text Benchmark
+//!
+//! Frame time of painting a page of paragraphs in a few sizes and faces
+//! with the software backend, so glyph rasterizing and compositing are
+//! measured without a window.
+//!
+//! Run with `cargo bench -p gugalanna-render --bench text`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use gugalanna_render::{DisplayList, FontFace, PaintCommand, RenderBackend, RenderColor, SoftwareBackend};
+
+const LINES: usize = 80;
+const FRAMES: usize = 30;
+
+const PARAGRAPH: &str = "The quick brown fox jumps over the lazy dog. AVA Wave To, Yo. \
+                         Sphinx of black quartz, judge my vow!";
+
+/// A page of text lines in sizes from 11px to 20px
+fn page() -> DisplayList {
+    let mut y = 0.0;
+    let commands = (0..LINES)
+        .map(|i| {
+            let font_size = 11.0 + (i % 10) as f32;
+            let command = PaintCommand::DrawText {
+                text: PARAGRAPH.to_string(),
+                x: 8.0,
+                y,
+                color: RenderColor::rgb(30, 30, 30),
+                font_size,
+                face: if i % 4 == 0 { FontFace::BOLD } else { FontFace::REGULAR },
+                family: None,
+            };
+            y += font_size * 1.3;
+            command
+        })
+        .collect();
+    DisplayList { commands }
+}
+
+fn main() {
+    let page = page();
+    let mut backend = SoftwareBackend::new(1000, 1400);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        backend.clear(RenderColor::white());
+        backend.render(black_box(&page));
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{} lines {:>9.3} ms/frame",
+        LINES,
+        elapsed.as_secs_f64() * 1000.0 / FRAMES as f64
+    );
+}
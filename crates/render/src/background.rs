@@ -0,0 +1,127 @@
+//! Background Images
+//!
+//! Sizes and places background images within their boxes, and lays out
+//! the tiles they repeat in.
+
+use gugalanna_layout::Rect;
+use gugalanna_style::{BackgroundRepeat, BackgroundSize, ComputedStyle};
+
+/// The tile of a background image at the background position, drawn at
+/// the background size, within the positioning area `area`
+pub(crate) fn background_tile(style: &ComputedStyle, area: Rect, natural_width: f32, natural_height: f32) -> Rect {
+    let (width, height) = match style.background_size {
+        BackgroundSize::Cover | BackgroundSize::Contain if natural_width <= 0.0 || natural_height <= 0.0 => {
+            (area.width, area.height)
+        }
+        BackgroundSize::Cover => {
+            let scale = (area.width / natural_width).max(area.height / natural_height);
+            (natural_width * scale, natural_height * scale)
+        }
+        BackgroundSize::Contain => {
+            let scale = (area.width / natural_width).min(area.height / natural_height);
+            (natural_width * scale, natural_height * scale)
+        }
+        BackgroundSize::Size(width, height) => {
+            let width = width.map(|w| w.resolve(area.width));
+            let height = height.map(|h| h.resolve(area.height));
+            // An auto side keeps the aspect ratio of the image
+            let ratio = if natural_height > 0.0 { natural_width / natural_height } else { 1.0 };
+            match (width, height) {
+                (Some(width), Some(height)) => (width, height),
+                (Some(width), None) => (width, if ratio > 0.0 { width / ratio } else { natural_height }),
+                (None, Some(height)) => (height * ratio, height),
+                (None, None) => (natural_width, natural_height),
+            }
+        }
+    };
+
+    let position = style.background_position;
+    Rect::new(
+        area.x + position.x.resolve(area.width - width),
+        area.y + position.y.resolve(area.height - height),
+        width,
+        height,
+    )
+}
+
+/// Tiles laid out from `tile` along the axes it repeats on that overlap
+/// `visible`
+pub(crate) fn tile_rects(tile: &Rect, repeat: BackgroundRepeat, visible: &Rect) -> Vec<Rect> {
+    if tile.width <= 0.0 || tile.height <= 0.0 || visible.is_empty() {
+        return Vec::new();
+    }
+
+    // Steps from the tile to the first and past the last one in view
+    let steps = |repeats: bool, start: f32, size: f32, from: f32, to: f32| {
+        if repeats {
+            ((from - start) / size).floor() as i64..((to - start) / size).ceil() as i64
+        } else {
+            0..1
+        }
+    };
+    let columns = steps(repeat.repeats_x(), tile.x, tile.width, visible.x, visible.right());
+    let rows = steps(repeat.repeats_y(), tile.y, tile.height, visible.y, visible.bottom());
+
+    rows.flat_map(|row| {
+        columns.clone().map(move |column| {
+            Rect::new(
+                tile.x + column as f32 * tile.width,
+                tile.y + row as f32 * tile.height,
+                tile.width,
+                tile.height,
+            )
+        })
+    })
+    .filter(|rect| !rect.intersect(visible).is_empty())
+    .collect()
+}
+
+/// Area the tiles of a background image cover within the box they are
+/// clipped to
+pub(crate) fn tiled_bounds(tile: &Rect, repeat: BackgroundRepeat, clip: &Rect) -> Rect {
+    let (x, width) = if repeat.repeats_x() { (clip.x, clip.width) } else { (tile.x, tile.width) };
+    let (y, height) = if repeat.repeats_y() { (clip.y, clip.height) } else { (tile.y, tile.height) };
+    Rect::new(x, y, width, height).intersect(clip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_style::{LengthPercentage, ObjectPosition};
+
+    #[test]
+    fn test_background_tile_size_and_position() {
+        let area = Rect::new(10.0, 20.0, 100.0, 50.0);
+        let mut style = ComputedStyle::default();
+
+        // Natural size at the top left by default
+        assert_eq!(background_tile(&style, area, 8.0, 4.0), Rect::new(10.0, 20.0, 8.0, 4.0));
+
+        // Cover fills the area, centered
+        style.background_size = BackgroundSize::Cover;
+        style.background_position = ObjectPosition {
+            x: LengthPercentage::Percent(0.5),
+            y: LengthPercentage::Percent(0.5),
+        };
+        assert_eq!(background_tile(&style, area, 8.0, 4.0), Rect::new(10.0, 20.0, 100.0, 50.0));
+
+        // An auto height keeps the aspect ratio
+        style.background_size = BackgroundSize::Size(Some(LengthPercentage::Px(20.0)), None);
+        assert_eq!(background_tile(&style, area, 8.0, 4.0), Rect::new(50.0, 40.0, 20.0, 10.0));
+    }
+
+    #[test]
+    fn test_tile_rects_along_repeated_axes() {
+        let visible = Rect::new(0.0, 0.0, 20.0, 10.0);
+        let tile = Rect::new(4.0, 2.0, 8.0, 8.0);
+
+        // Tiles reach back before the positioned one
+        let xs: Vec<f32> = tile_rects(&tile, BackgroundRepeat::RepeatX, &visible).iter().map(|r| r.x).collect();
+        assert_eq!(xs, [-4.0, 4.0, 12.0]);
+        assert_eq!(tile_rects(&tile, BackgroundRepeat::Repeat, &visible).len(), 6);
+        assert_eq!(tile_rects(&tile, BackgroundRepeat::NoRepeat, &visible), [tile]);
+        assert!(tile_rects(&Rect::new(30.0, 0.0, 8.0, 8.0), BackgroundRepeat::NoRepeat, &visible).is_empty());
+
+        assert_eq!(tiled_bounds(&tile, BackgroundRepeat::RepeatY, &visible), Rect::new(4.0, 0.0, 8.0, 10.0));
+    }
+}
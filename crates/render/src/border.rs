@@ -0,0 +1,419 @@
+//! Border Styles
+//!
+//! Splits the sides of a border into the rectangles and dots they are
+//! drawn with. Top and bottom sides span the corners, left and right sides
+//! fit between them, so no pixel is drawn twice where sides meet. Corners
+//! between solid sides of different colors are split diagonally instead.
+
+use gugalanna_layout::Rect;
+use gugalanna_style::BorderStyle;
+
+use crate::display_list::{BorderColors, BorderStyles, BorderWidths};
+use crate::paint::RenderColor;
+
+/// Length of a dash, in border widths
+const DASH_LENGTH: f32 = 3.0;
+
+/// Gap between dashes, in border widths
+const DASH_GAP: f32 = 2.0;
+
+/// A shape a border side is drawn with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BorderPiece {
+    Rect(Rect),
+    /// A filled circle
+    Dot { x: f32, y: f32, diameter: f32 },
+    /// A side's half of a corner split between two colors
+    Triangle([(f32, f32); 3]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl Side {
+    fn is_horizontal(self) -> bool {
+        matches!(self, Side::Top | Side::Bottom)
+    }
+}
+
+/// The shapes of the border around `rect` and the colors of their sides
+pub(crate) fn border_pieces(
+    rect: &Rect,
+    widths: &BorderWidths,
+    styles: &BorderStyles,
+    colors: &BorderColors,
+) -> Vec<(RenderColor, BorderPiece)> {
+    let border = Border { rect: *rect, widths: *widths, styles: *styles, colors: *colors };
+    let mut pieces = Vec::new();
+    for side in [Side::Top, Side::Right, Side::Bottom, Side::Left] {
+        let mut side_pieces = Vec::new();
+        border.side_pieces(side, &mut side_pieces);
+        pieces.extend(side_pieces.into_iter().map(|piece| (border.color(side), piece)));
+    }
+    pieces
+}
+
+/// Width of each of the two lines of a double border, None when it is too
+/// thin to leave a gap and is drawn solid
+fn double_stroke(width: f32) -> Option<f32> {
+    (width >= 3.0).then(|| (width / 3.0).round())
+}
+
+struct Border {
+    rect: Rect,
+    widths: BorderWidths,
+    styles: BorderStyles,
+    colors: BorderColors,
+}
+
+impl Border {
+    fn width(&self, side: Side) -> f32 {
+        match side {
+            Side::Top => self.widths.top,
+            Side::Right => self.widths.right,
+            Side::Bottom => self.widths.bottom,
+            Side::Left => self.widths.left,
+        }
+    }
+
+    fn style(&self, side: Side) -> BorderStyle {
+        match side {
+            Side::Top => self.styles.top,
+            Side::Right => self.styles.right,
+            Side::Bottom => self.styles.bottom,
+            Side::Left => self.styles.left,
+        }
+    }
+
+    fn color(&self, side: Side) -> RenderColor {
+        match side {
+            Side::Top => self.colors.top,
+            Side::Right => self.colors.right,
+            Side::Bottom => self.colors.bottom,
+            Side::Left => self.colors.left,
+        }
+    }
+
+    /// Whether the corner between two sides is split diagonally between
+    /// their colors, only done for solid sides
+    fn mitered(&self, side: Side, neighbor: Side) -> bool {
+        let solid = |side| self.style(side) == BorderStyle::Solid && self.width(side) > 0.0;
+        solid(side) && solid(neighbor) && self.color(side) != self.color(neighbor)
+    }
+
+    /// Line width of a side drawn as a double border
+    fn double_stroke(&self, side: Side) -> Option<f32> {
+        match self.style(side) {
+            BorderStyle::Double => double_stroke(self.width(side)),
+            _ => None,
+        }
+    }
+
+    /// The sides at the start and end of a side
+    fn neighbors(side: Side) -> (Side, Side) {
+        match side {
+            Side::Top | Side::Bottom => (Side::Left, Side::Right),
+            Side::Left | Side::Right => (Side::Top, Side::Bottom),
+        }
+    }
+
+    /// Start and end of the whole edge of a side
+    fn edge(&self, side: Side) -> (f32, f32) {
+        if side.is_horizontal() {
+            (self.rect.x, self.rect.x + self.rect.width)
+        } else {
+            (self.rect.y, self.rect.y + self.rect.height)
+        }
+    }
+
+    /// Part of the edge a side draws, the corners belong to the top and
+    /// bottom sides unless they are mitered
+    fn owned(&self, side: Side) -> (f32, f32) {
+        let (start, end) = self.edge(side);
+        let (before, after) = Self::neighbors(side);
+        let horizontal = side.is_horizontal();
+        // Mitered corners are drawn as triangles, the others are left out
+        // of the left and right sides
+        let excluded = |neighbor: Side| {
+            if horizontal && !self.mitered(side, neighbor) {
+                0.0
+            } else {
+                self.width(neighbor)
+            }
+        };
+        (start + excluded(before), end - excluded(after))
+    }
+
+    /// Position `along` the edge of a side and `across` from its outer
+    /// edge inwards
+    fn point(&self, side: Side, along: f32, across: f32) -> (f32, f32) {
+        let r = &self.rect;
+        match side {
+            Side::Top => (along, r.y + across),
+            Side::Bottom => (along, r.y + r.height - across),
+            Side::Left => (r.x + across, along),
+            Side::Right => (r.x + r.width - across, along),
+        }
+    }
+
+    /// A side's half of its mitered corners, between the outer corner of
+    /// the border and its inner corner
+    fn miters(&self, side: Side, width: f32, pieces: &mut Vec<BorderPiece>) {
+        let (e0, e1) = self.edge(side);
+        let (before, after) = Self::neighbors(side);
+        if self.mitered(side, before) {
+            let inner = e0 + self.width(before);
+            pieces.push(BorderPiece::Triangle([
+                self.point(side, e0, 0.0),
+                self.point(side, inner, 0.0),
+                self.point(side, inner, width),
+            ]));
+        }
+        if self.mitered(side, after) {
+            let inner = e1 - self.width(after);
+            pieces.push(BorderPiece::Triangle([
+                self.point(side, e1, 0.0),
+                self.point(side, inner, 0.0),
+                self.point(side, inner, width),
+            ]));
+        }
+    }
+
+    /// Rectangle spanning `along` the edge of a side, `across` from its
+    /// outer edge inwards
+    fn piece(&self, side: Side, (a0, a1): (f32, f32), (c0, c1): (f32, f32)) -> Option<BorderPiece> {
+        if a1 <= a0 || c1 <= c0 {
+            return None;
+        }
+        let r = &self.rect;
+        let rect = match side {
+            Side::Top => Rect::new(a0, r.y + c0, a1 - a0, c1 - c0),
+            Side::Bottom => Rect::new(a0, r.y + r.height - c1, a1 - a0, c1 - c0),
+            Side::Left => Rect::new(r.x + c0, a0, c1 - c0, a1 - a0),
+            Side::Right => Rect::new(r.x + r.width - c1, a0, c1 - c0, a1 - a0),
+        };
+        Some(BorderPiece::Rect(rect))
+    }
+
+    fn side_pieces(&self, side: Side, pieces: &mut Vec<BorderPiece>) {
+        let width = self.width(side);
+        if width <= 0.0 {
+            return;
+        }
+        let owned = self.owned(side);
+        match self.style(side) {
+            BorderStyle::None => {}
+            BorderStyle::Dashed => self.dashes(side, width, owned, pieces),
+            BorderStyle::Dotted => self.dots(side, width, owned, pieces),
+            BorderStyle::Double if double_stroke(width).is_some() => self.double(side, width, pieces),
+            BorderStyle::Solid => {
+                pieces.extend(self.piece(side, owned, (0.0, width)));
+                self.miters(side, width, pieces);
+            }
+            BorderStyle::Double => pieces.extend(self.piece(side, owned, (0.0, width))),
+        }
+    }
+
+    /// Dashes spread evenly along the edge, starting and ending at its
+    /// corners, the parts in the corners left to the sides owning them
+    fn dashes(&self, side: Side, width: f32, (start, end): (f32, f32), pieces: &mut Vec<BorderPiece>) {
+        let (e0, e1) = self.edge(side);
+        let (dash, gap) = (DASH_LENGTH * width, DASH_GAP * width);
+        let count = ((e1 - e0 + gap) / (dash + gap)).round().max(1.0);
+        let scale = (e1 - e0) / (count * dash + (count - 1.0) * gap);
+        for i in 0..count as usize {
+            let a0 = e0 + i as f32 * (dash + gap) * scale;
+            let along = (a0.max(start), (a0 + dash * scale).min(end));
+            pieces.extend(self.piece(side, along, (0.0, width)));
+        }
+    }
+
+    /// Dots as wide as the border, about two widths apart from center to
+    /// center, with one in each corner
+    fn dots(&self, side: Side, width: f32, (start, end): (f32, f32), pieces: &mut Vec<BorderPiece>) {
+        let (e0, e1) = self.edge(side);
+        let length = (e1 - e0 - width).max(0.0);
+        let count = (length / (2.0 * width)).round() as usize + 1;
+        let spacing = if count > 1 { length / (count - 1) as f32 } else { 0.0 };
+        let first = if count > 1 { e0 + width / 2.0 } else { (e0 + e1) / 2.0 };
+        for i in 0..count {
+            let center = first + i as f32 * spacing;
+            if center < start || center > end {
+                continue;
+            }
+            let (x, y) = match side {
+                Side::Top => (center, self.rect.y + width / 2.0),
+                Side::Bottom => (center, self.rect.y + self.rect.height - width / 2.0),
+                Side::Left => (self.rect.x + width / 2.0, center),
+                Side::Right => (self.rect.x + self.rect.width - width / 2.0, center),
+            };
+            pieces.push(BorderPiece::Dot { x, y, diameter: width });
+        }
+    }
+
+    /// Two lines along the outer and inner edges, joined at corners with
+    /// double neighbors and filled in at the others
+    fn double(&self, side: Side, width: f32, pieces: &mut Vec<BorderPiece>) {
+        let Some(stroke) = double_stroke(width) else {
+            return;
+        };
+        let (e0, e1) = self.edge(side);
+        let (before, after) = Self::neighbors(side);
+        let (before_width, after_width) = (self.width(before), self.width(after));
+        let outer = (0.0, stroke);
+        let inner = (width - stroke, width);
+
+        if side.is_horizontal() {
+            // The inner line turns the corner where the neighbor's inner
+            // line starts
+            let inset = |neighbor: Side, neighbor_width: f32| match self.double_stroke(neighbor) {
+                Some(neighbor_stroke) => neighbor_width - neighbor_stroke,
+                None => 0.0,
+            };
+            pieces.extend(self.piece(side, (e0, e1), outer));
+            pieces.extend(self.piece(side, (e0 + inset(before, before_width), e1 - inset(after, after_width)), inner));
+
+            // Corners next to other styles are filled between the lines
+            let gap = (stroke, width - stroke);
+            if self.double_stroke(before).is_none() {
+                pieces.extend(self.piece(side, (e0, e0 + before_width), gap));
+            }
+            if self.double_stroke(after).is_none() {
+                pieces.extend(self.piece(side, (e1 - after_width, e1), gap));
+            }
+        } else {
+            // The outer line runs into the corners up to the outer line of
+            // double neighbors, which don't fill them
+            let reach = |neighbor: Side, neighbor_width: f32| self.double_stroke(neighbor).unwrap_or(neighbor_width);
+            pieces.extend(self.piece(side, (e0 + reach(before, before_width), e1 - reach(after, after_width)), outer));
+            pieces.extend(self.piece(side, (e0 + before_width, e1 - after_width), inner));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(width: f32, height: f32, border: f32, style: BorderStyle) -> Vec<BorderPiece> {
+        let widths = BorderWidths { top: border, right: border, bottom: border, left: border };
+        let styles = BorderStyles { top: style, right: style, bottom: style, left: style };
+        let colors = BorderColors::all(RenderColor::black());
+        let pieces = border_pieces(&Rect::new(10.0, 10.0, width, height), &widths, &styles, &colors);
+        pieces.into_iter().map(|(_, piece)| piece).collect()
+    }
+
+    fn rects(pieces: &[BorderPiece]) -> Vec<Rect> {
+        pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                BorderPiece::Rect(rect) => Some(*rect),
+                BorderPiece::Dot { .. } | BorderPiece::Triangle(_) => None,
+            })
+            .collect()
+    }
+
+    fn assert_no_overlap(rects: &[Rect]) {
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                assert!(a.intersect(b).is_empty(), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_solid_sides_meet_without_overlap() {
+        let rects = rects(&pieces(100.0, 50.0, 4.0, BorderStyle::Solid));
+        assert_eq!(rects.len(), 4);
+        assert_no_overlap(&rects);
+        let area: f32 = rects.iter().map(|r| r.width * r.height).sum();
+        assert_eq!(area, 100.0 * 50.0 - 92.0 * 42.0);
+    }
+
+    #[test]
+    fn test_dashed_border_segments() {
+        // Dashes of 6 and gaps of 4, ten along 100px and five along 50px,
+        // scaled to end at the corners
+        let rects = rects(&pieces(100.0, 50.0, 2.0, BorderStyle::Dashed));
+        assert_eq!(rects.len(), 30);
+        assert_no_overlap(&rects);
+        assert_eq!(rects[0].x, 10.0);
+        assert!(rects.iter().all(|r| r.width.min(r.height) == 2.0));
+        let top: Vec<_> = rects.iter().filter(|r| r.y == 10.0).collect();
+        assert_eq!(top.len(), 10);
+        assert!((top[9].right() - 110.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_dotted_border_dots() {
+        // Dots 4px apart, 21 along the 82px sides, 11 along the 42px sides
+        // of which the corner ones belong to the top and bottom
+        let dots = pieces(82.0, 42.0, 2.0, BorderStyle::Dotted);
+        assert_eq!(dots.len(), 21 * 2 + 9 * 2);
+        assert!(matches!(dots[0], BorderPiece::Dot { x, y, diameter } if (x, y, diameter) == (11.0, 11.0, 2.0)));
+        assert!(matches!(dots[20], BorderPiece::Dot { x, .. } if x == 91.0));
+        assert!(matches!(dots[21], BorderPiece::Dot { x, y, .. } if (x, y) == (91.0, 15.0)));
+    }
+
+    #[test]
+    fn test_double_border_lines() {
+        // A 6px double border is two 2px frames with a 2px gap
+        let lines = rects(&pieces(60.0, 40.0, 6.0, BorderStyle::Double));
+        assert_eq!(lines.len(), 8);
+        assert_no_overlap(&lines);
+        let area: f32 = lines.iter().map(|r| r.width * r.height).sum();
+        assert_eq!(area, (60.0 * 40.0 - 56.0 * 36.0) + (52.0 * 32.0 - 48.0 * 28.0));
+
+        // Too thin for a gap, drawn solid
+        assert_eq!(rects(&pieces(60.0, 40.0, 2.0, BorderStyle::Double)).len(), 4);
+    }
+
+    #[test]
+    fn test_mixed_styles_fill_corners() {
+        let widths = BorderWidths { top: 6.0, right: 6.0, bottom: 0.0, left: 6.0 };
+        let styles = BorderStyles {
+            top: BorderStyle::Double,
+            right: BorderStyle::Solid,
+            bottom: BorderStyle::None,
+            left: BorderStyle::Dashed,
+        };
+        let colors = BorderColors::all(RenderColor::black());
+        let pieces: Vec<_> = border_pieces(&Rect::new(0.0, 0.0, 60.0, 40.0), &widths, &styles, &colors)
+            .into_iter()
+            .map(|(_, piece)| piece)
+            .collect();
+        assert_no_overlap(&rects(&pieces));
+
+        // Nothing is drawn for a side without width
+        assert!(rects(&pieces).iter().all(|r| r.bottom() <= 40.0));
+    }
+
+    #[test]
+    fn test_corners_mitered_between_colors() {
+        let (red, blue) = (RenderColor::rgb(255, 0, 0), RenderColor::rgb(0, 0, 255));
+        let widths = BorderWidths { top: 4.0, right: 4.0, bottom: 4.0, left: 8.0 };
+        let colors = BorderColors { left: blue, ..BorderColors::all(red) };
+        let pieces = border_pieces(&Rect::new(0.0, 0.0, 60.0, 40.0), &widths, &BorderStyles::default(), &colors);
+
+        // The left side's corners are split, the others belong to the top
+        // and bottom as before
+        let triangles: Vec<_> = pieces
+            .iter()
+            .filter_map(|(color, piece)| match piece {
+                BorderPiece::Triangle(points) => Some((*color, *points)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(triangles.len(), 4);
+        assert!(triangles.contains(&(red, [(0.0, 0.0), (8.0, 0.0), (8.0, 4.0)])));
+        assert!(triangles.contains(&(blue, [(0.0, 0.0), (0.0, 4.0), (8.0, 4.0)])));
+        assert!(pieces.contains(&(red, BorderPiece::Rect(Rect::new(8.0, 0.0, 52.0, 4.0)))));
+        assert!(pieces.contains(&(blue, BorderPiece::Rect(Rect::new(0.0, 4.0, 8.0, 32.0)))));
+        assert!(pieces.contains(&(red, BorderPiece::Rect(Rect::new(56.0, 4.0, 4.0, 32.0)))));
+    }
+}
@@ -0,0 +1,300 @@
+//! Damage Tracking
+//!
+//! Finds the part of the window that changed between two frames, so only
+//! the paint commands over it are replayed onto the previous frame.
+
+use gugalanna_layout::Rect;
+use gugalanna_style::Transform;
+
+use crate::background::tiled_bounds;
+use crate::display_list::{DisplayList, PaintCommand, FILE_BUTTON_WIDTH};
+use crate::shadow::shadow_bounds;
+use crate::stacking::transform_rect;
+
+/// Runs of commands two display lists start and end with in common
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListDiff {
+    /// Commands both lists start with
+    pub prefix: usize,
+    /// Commands both lists end with, after the prefix
+    pub suffix: usize,
+}
+
+impl ListDiff {
+    /// Number of commands of a list of `len` that are between the
+    /// unchanged runs
+    pub fn changed(&self, len: usize) -> usize {
+        len - self.prefix - self.suffix
+    }
+}
+
+/// Find the unchanged runs at the start and end of a rebuilt display list
+pub fn diff_display_lists(previous: &DisplayList, current: &DisplayList) -> ListDiff {
+    let (old, new) = (&previous.commands, &current.commands);
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    ListDiff { prefix, suffix }
+}
+
+/// Area the changes from `previous` to `current` paint over, None when the
+/// frames are the same
+///
+/// Only the commands between the unchanged runs are damaged, unless the
+/// clip or opacity differs after them: the commands that follow are then
+/// painted differently too.
+pub fn damaged_rect(previous: &DisplayList, current: &DisplayList) -> Option<Rect> {
+    let diff = diff_display_lists(previous, current);
+    let (old_end, new_end) = (previous.commands.len() - diff.suffix, current.commands.len() - diff.suffix);
+    let old_changed = &previous.commands[diff.prefix..old_end];
+    let new_changed = &current.commands[diff.prefix..new_end];
+    let (old_bounds, new_bounds) = (page_bounds(&previous.commands), page_bounds(&current.commands));
+
+    let is_state = |command: &PaintCommand| command_bounds(command).is_none();
+    let changed: Vec<Option<Rect>> = if old_changed.len() == new_changed.len()
+        && old_changed.iter().zip(new_changed).all(|(a, b)| a == b || !(is_state(a) || is_state(b)))
+    {
+        // Only drawing commands were replaced, each one damages where it draws
+        (diff.prefix..old_end)
+            .filter(|&i| previous.commands[i] != current.commands[i])
+            .flat_map(|i| [old_bounds[i], new_bounds[i]])
+            .collect()
+    } else {
+        let start = PaintState::after(&previous.commands[..diff.prefix], PaintState::default());
+        let end = if PaintState::after(old_changed, start.clone()) == PaintState::after(new_changed, start) {
+            (old_end, new_end)
+        } else {
+            (old_bounds.len(), new_bounds.len())
+        };
+        old_bounds[diff.prefix..end.0].iter().chain(&new_bounds[diff.prefix..end.1]).copied().collect()
+    };
+
+    changed
+        .into_iter()
+        .flatten()
+        .filter(|bounds| !bounds.is_empty())
+        .reduce(|damage, bounds| damage.union(&bounds))
+}
+
+/// Area each command of a list paints over in page coordinates, moved by
+/// the transforms it is inside of
+fn page_bounds(commands: &[PaintCommand]) -> Vec<Option<Rect>> {
+    let mut transforms: Vec<Transform> = Vec::new();
+    commands
+        .iter()
+        .map(|command| {
+            match command {
+                PaintCommand::PushTransform(transform) => {
+                    transforms.push(transforms.last().map_or(*transform, |outer| transform.then(outer)));
+                }
+                PaintCommand::PopTransform => {
+                    transforms.pop();
+                }
+                _ => {}
+            }
+            let bounds = command_bounds(command)?;
+            Some(transforms.last().map_or(bounds, |transform| transform_rect(transform, &bounds)))
+        })
+        .collect()
+}
+
+/// Clip and groups in effect at a point of a display list
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PaintState<'a> {
+    /// Clip rect in effect inside each open clip
+    clips: Vec<Rect>,
+    /// Opacity, transform, fixed and scroll commands not yet ended
+    groups: Vec<&'a PaintCommand>,
+}
+
+impl<'a> PaintState<'a> {
+    /// State after painting `commands` from `state`
+    fn after(commands: &'a [PaintCommand], mut state: Self) -> Self {
+        for command in commands {
+            match command {
+                PaintCommand::PushClip(rect) => {
+                    let clip = state.clips.last().map_or(*rect, |outer| outer.intersect(rect));
+                    state.clips.push(clip);
+                }
+                PaintCommand::PopClip => {
+                    state.clips.pop();
+                }
+                PaintCommand::PushOpacity(_)
+                | PaintCommand::PushTransform(_)
+                | PaintCommand::BeginFixed
+                | PaintCommand::BeginScroll(_) => state.groups.push(command),
+                PaintCommand::PopOpacity
+                | PaintCommand::PopTransform
+                | PaintCommand::EndFixed
+                | PaintCommand::EndScroll => {
+                    state.groups.pop();
+                }
+                _ => {}
+            }
+        }
+        state
+    }
+}
+
+/// The commands of a display list that paint inside `rect`
+///
+/// Clip, opacity and transform commands are all kept, they apply to the
+/// commands after them.
+pub fn commands_in_rect(display_list: &DisplayList, rect: &Rect) -> DisplayList {
+    let commands = display_list
+        .commands
+        .iter()
+        .zip(page_bounds(&display_list.commands))
+        .filter(|(_, bounds)| bounds.is_none_or(|bounds| !bounds.intersect(rect).is_empty()))
+        .map(|(command, _)| command.clone())
+        .collect();
+    DisplayList { commands }
+}
+
+/// Area a command paints over, None for commands that only change state
+///
+/// Text isn't measured: glyphs are taken to be at most one em wide, and
+/// to reach half an em below the font size.
+pub fn command_bounds(command: &PaintCommand) -> Option<Rect> {
+    let bounds = match command {
+        PaintCommand::FillRect { rect, .. }
+        | PaintCommand::DrawBorder { rect, .. }
+        | PaintCommand::DrawCheckbox { rect, .. }
+        | PaintCommand::DrawRadio { rect, .. }
+        | PaintCommand::DrawTextArea { rect, .. }
+        | PaintCommand::DrawSelect { rect, .. }
+        | PaintCommand::FillRoundedRect { rect, .. }
+        | PaintCommand::DrawRoundedBorder { rect, .. }
+        | PaintCommand::FillLinearGradient { rect, .. }
+        | PaintCommand::FillRadialGradient { rect, .. } => *rect,
+        PaintCommand::DrawText { text, x, y, font_size, .. } => text_bounds(text, *x, *y, *font_size),
+        // Form controls draw their text at 14px, it may run past the field
+        PaintCommand::DrawTextInput { rect, text, .. } => rect.union(&text_bounds(text, rect.x, rect.y, 14.0)),
+        PaintCommand::DrawFileInput { rect, label, .. } => {
+            rect.union(&text_bounds(label, rect.x + FILE_BUTTON_WIDTH, rect.y, 14.0))
+        }
+        PaintCommand::DrawButton { rect, text, .. } => {
+            let text = text_bounds(text, rect.x, rect.y, 14.0);
+            rect.union(&Rect { x: rect.x + (rect.width - text.width) / 2.0, ..text })
+        }
+        PaintCommand::DrawImage { rect, clip, .. } => match clip {
+            Some(clip) => rect.intersect(clip),
+            None => *rect,
+        },
+        PaintCommand::DrawTiledImage { tile, repeat, clip, .. } => tiled_bounds(tile, *repeat, clip),
+        PaintCommand::DrawBoxShadow { rect, shadow, .. } => shadow_bounds(rect, shadow),
+        PaintCommand::FillSelection { rects, .. } => return rects.iter().copied().reduce(|a, b| a.union(&b)),
+        PaintCommand::PushClip(_)
+        | PaintCommand::PopClip
+        | PaintCommand::PushOpacity(_)
+        | PaintCommand::PopOpacity
+        | PaintCommand::PushTransform(_)
+        | PaintCommand::PopTransform
+        | PaintCommand::BeginFixed
+        | PaintCommand::EndFixed
+        | PaintCommand::BeginScroll(_)
+        | PaintCommand::EndScroll => return None,
+    };
+    Some(bounds)
+}
+
+/// Area taken by a run of text drawn with its top at `y`
+fn text_bounds(text: &str, x: f32, y: f32, font_size: f32) -> Rect {
+    Rect::new(x, y, text.chars().count() as f32 * font_size, font_size * 1.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::FontFace;
+    use crate::paint::RenderColor;
+
+    fn fill(x: f32, color: RenderColor) -> PaintCommand {
+        PaintCommand::FillRect { rect: Rect::new(x, 0.0, 10.0, 10.0), color }
+    }
+
+    fn caret(x: f32) -> PaintCommand {
+        PaintCommand::DrawText {
+            text: "|".to_string(),
+            x,
+            y: 0.0,
+            color: RenderColor::black(),
+            font_size: 10.0,
+            face: FontFace::REGULAR,
+            family: None,
+        }
+    }
+
+    #[test]
+    fn test_unchanged_frame_has_no_damage() {
+        let frame = DisplayList { commands: vec![fill(0.0, RenderColor::white()), caret(50.0)] };
+        assert_eq!(damaged_rect(&frame, &frame.clone()), None);
+    }
+
+    #[test]
+    fn test_damage_covers_changed_commands() {
+        let frame = |caret_x| DisplayList {
+            commands: vec![fill(0.0, RenderColor::white()), caret(caret_x), fill(100.0, RenderColor::white())],
+        };
+        let (previous, current) = (frame(50.0), frame(60.0));
+
+        // The caret moved: where it was and where it is now
+        assert_eq!(damaged_rect(&previous, &current), Some(Rect::new(50.0, 0.0, 20.0, 15.0)));
+
+        let repainted = commands_in_rect(&current, &Rect::new(50.0, 0.0, 20.0, 15.0));
+        assert_eq!(repainted.commands, vec![caret(60.0)]);
+    }
+
+    #[test]
+    fn test_changed_opacity_damages_what_follows() {
+        let previous = DisplayList {
+            commands: vec![
+                fill(0.0, RenderColor::white()),
+                PaintCommand::PushOpacity(1.0),
+                fill(100.0, RenderColor::black()),
+            ],
+        };
+        let mut current = previous.clone();
+        current.commands[1] = PaintCommand::PushOpacity(0.5);
+
+        assert_eq!(damaged_rect(&previous, &current), Some(Rect::new(100.0, 0.0, 10.0, 10.0)));
+    }
+
+    #[test]
+    fn test_inserted_commands_only_damage_themselves() {
+        let previous = DisplayList {
+            commands: vec![fill(0.0, RenderColor::white()), fill(100.0, RenderColor::white())],
+        };
+        let mut current = previous.clone();
+        current.commands.insert(1, caret(50.0));
+        current.commands.insert(1, PaintCommand::PushOpacity(0.5));
+        current.commands.insert(3, PaintCommand::PopOpacity);
+
+        let diff = diff_display_lists(&previous, &current);
+        assert_eq!(diff, ListDiff { prefix: 1, suffix: 1 });
+        assert_eq!(diff.changed(current.commands.len()), 3);
+
+        // The opacity is popped before the last rect
+        assert_eq!(damaged_rect(&previous, &current), Some(Rect::new(50.0, 0.0, 10.0, 15.0)));
+    }
+
+    #[test]
+    fn test_transformed_commands_damage_where_they_are_painted() {
+        let frame = |color| DisplayList {
+            commands: vec![
+                PaintCommand::PushTransform(Transform::translate(100.0, 20.0)),
+                fill(0.0, color),
+                PaintCommand::PopTransform,
+            ],
+        };
+        let (previous, current) = (frame(RenderColor::white()), frame(RenderColor::black()));
+        let moved = Rect::new(100.0, 20.0, 10.0, 10.0);
+        assert_eq!(damaged_rect(&previous, &current), Some(moved));
+        assert_eq!(commands_in_rect(&current, &moved), current);
+        assert_eq!(commands_in_rect(&current, &Rect::new(0.0, 0.0, 10.0, 10.0)).len(), 2);
+    }
+}
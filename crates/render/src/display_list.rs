@@ -2,20 +2,33 @@
 //!
 //! Converts layout tree to paint commands.
 
+use std::path::Path;
+
 use gugalanna_dom::NodeId;
-use gugalanna_layout::{LayoutBox, BoxType, InputType, ImagePixels, Rect};
-use gugalanna_style::{Background, BorderRadius, BoxShadow, ColorStop, Gradient, GradientDirection, Overflow, RadialShape, RadialSize};
+use gugalanna_layout::{measure_text, LayoutBox, BoxType, InputType, ImagePixels, Rect};
+use gugalanna_style::{
+    Background, BackgroundRepeat, BorderRadius, BorderStyle, BoxShadow, ColorStop, ComputedStyle, Gradient, GradientDirection, ImageRendering, ObjectFit, RadialShape,
+    RadialSize, TextDecorationLine, Transform,
+};
+
+use serde::{Deserialize, Serialize};
 
+use crate::background::background_tile;
+use crate::font::FontFace;
 use crate::paint::RenderColor;
+use crate::stacking::{box_transform, is_layer, overflow_clip_rect, scroll_container_id, Layer};
+
+/// Width of the button of a file input
+pub(crate) const FILE_BUTTON_WIDTH: f32 = 80.0;
 
 /// A display list of paint commands
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DisplayList {
     pub commands: Vec<PaintCommand>,
 }
 
 /// A paint command
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PaintCommand {
     /// Fill a rectangle with a solid color
     FillRect {
@@ -26,15 +39,22 @@ pub enum PaintCommand {
     DrawText {
         text: String,
         x: f32,
+        /// Top of the glyphs, one ascent above the baseline
         y: f32,
         color: RenderColor,
         font_size: f32,
+        /// Weight and slant of the glyphs
+        face: FontFace,
+        /// Font families in order of preference, drawn in the default font
+        /// when none of them is a loaded web font
+        family: Option<String>,
     },
     /// Draw a border (outline of rectangle)
     DrawBorder {
         rect: Rect,
         widths: BorderWidths,
-        color: RenderColor,
+        styles: BorderStyles,
+        colors: BorderColors,
     },
     /// Draw a text input field
     DrawTextInput {
@@ -66,6 +86,32 @@ pub enum PaintCommand {
         text: String,
         is_pressed: bool,
     },
+    /// Draw a file input: a button to choose files, and `label` naming
+    /// those chosen
+    DrawFileInput {
+        node_id: NodeId,
+        rect: Rect,
+        label: String,
+    },
+    /// Draw a multi-line text area, its long lines wrapped and those
+    /// above `first_line` scrolled out of view
+    DrawTextArea {
+        node_id: NodeId,
+        rect: Rect,
+        text: String,
+        cursor_pos: Option<usize>,
+        font_size: f32,
+        line_height: f32,
+        first_line: usize,
+        is_focused: bool,
+    },
+    /// Draw a dropdown list showing its selected option
+    DrawSelect {
+        node_id: NodeId,
+        rect: Rect,
+        text: String,
+        font_size: f32,
+    },
     /// Draw an image
     DrawImage {
         rect: Rect,
@@ -73,18 +119,53 @@ pub enum PaintCommand {
         pixels: Option<ImagePixels>,
         /// Alt text for placeholder display
         alt: String,
+        /// Content box the image is clipped to when it is painted larger
+        clip: Option<Rect>,
+        /// Filter the image is scaled with
+        rendering: ImageRendering,
+    },
+    /// Tile a background image over a box
+    DrawTiledImage {
+        /// Tile at the background position, drawn at the background size
+        tile: Rect,
+        pixels: ImagePixels,
+        /// Axes the tile repeats along
+        repeat: BackgroundRepeat,
+        /// Border box the tiles are clipped to
+        clip: Rect,
+        /// Filter the image is scaled with
+        rendering: ImageRendering,
     },
-    /// Set clipping rectangle (for overflow: hidden)
-    SetClipRect(Rect),
-    /// Clear clipping rectangle
-    ClearClipRect,
+    /// Clip the commands until PopClip to a rectangle, within the clip
+    /// already in effect (for overflow: hidden)
+    PushClip(Rect),
+    /// Restore the clip in effect before the last PushClip
+    PopClip,
     /// Push an opacity modifier (affects all subsequent commands until PopOpacity)
     PushOpacity(f32),
     /// Pop the current opacity modifier
     PopOpacity,
+    /// Paint the commands until PopTransform moved by a transform, from
+    /// page coordinates to page coordinates
+    PushTransform(Transform),
+    /// End the commands of the current transform
+    PopTransform,
+    /// Start commands anchored to the viewport (position: fixed), which
+    /// are not moved by scrolling
+    BeginFixed,
+    /// End commands anchored to the viewport
+    EndFixed,
+    /// Start the content of a scroll container, moved up by the container's
+    /// scroll offset
+    BeginScroll(NodeId),
+    /// End the content of a scroll container
+    EndScroll,
     /// Draw a box shadow
     DrawBoxShadow {
+        /// Border box of an outset shadow, padding box of an inset one
         rect: Rect,
+        /// Corner radii of the box
+        radius: BorderRadius,
         shadow: BoxShadow,
     },
     /// Fill a rounded rectangle
@@ -93,12 +174,13 @@ pub enum PaintCommand {
         radius: BorderRadius,
         color: RenderColor,
     },
-    /// Draw a rounded border
+    /// Draw a rounded border, solid whatever its styles
     DrawRoundedBorder {
         rect: Rect,
         radius: BorderRadius,
         widths: BorderWidths,
-        color: RenderColor,
+        styles: BorderStyles,
+        colors: BorderColors,
     },
     /// Fill a rectangle with a linear gradient
     FillLinearGradient {
@@ -107,6 +189,12 @@ pub enum PaintCommand {
         stops: Vec<ColorStop>,
         radius: Option<BorderRadius>,
     },
+    /// Highlight selected text, drawn translucent over the backgrounds and
+    /// under the text it covers
+    FillSelection {
+        rects: Vec<Rect>,
+        color: RenderColor,
+    },
     /// Fill a rectangle with a radial gradient
     FillRadialGradient {
         rect: Rect,
@@ -120,7 +208,7 @@ pub enum PaintCommand {
 }
 
 /// Border widths for all four sides
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct BorderWidths {
     pub top: f32,
     pub right: f32,
@@ -128,6 +216,51 @@ pub struct BorderWidths {
     pub left: f32,
 }
 
+/// Border styles for all four sides, solid by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BorderStyles {
+    pub top: BorderStyle,
+    pub right: BorderStyle,
+    pub bottom: BorderStyle,
+    pub left: BorderStyle,
+}
+
+/// Border colors for all four sides
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BorderColors {
+    pub top: RenderColor,
+    pub right: RenderColor,
+    pub bottom: RenderColor,
+    pub left: RenderColor,
+}
+
+impl BorderColors {
+    /// The same color on every side
+    pub fn all(color: RenderColor) -> Self {
+        Self { top: color, right: color, bottom: color, left: color }
+    }
+}
+
+/// Selected text to highlight, as rectangles in page coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub rects: Vec<Rect>,
+    pub color: RenderColor,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self { rects: Vec::new(), color: RenderColor::new(51, 144, 255, 96) }
+    }
+}
+
+impl Selection {
+    /// Parts of the selection inside the area of a run of text
+    fn within(&self, run: &Rect) -> Vec<Rect> {
+        self.rects.iter().map(|rect| rect.intersect(run)).filter(|rect| !rect.is_empty()).collect()
+    }
+}
+
 impl DisplayList {
     pub fn new() -> Self {
         Self::default()
@@ -144,28 +277,79 @@ impl DisplayList {
     pub fn is_empty(&self) -> bool {
         self.commands.is_empty()
     }
+
+    /// The commands as JSON, a field per line so dumps diff readably
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    /// Read commands written by `to_json`
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    /// Write the commands to a JSON file, to be replayed without the page
+    /// they were painted from
+    pub fn dump(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_json()?).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Read commands dumped to a JSON file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_json(&json)
+    }
 }
 
 /// Build a display list from a layout box tree
 pub fn build_display_list(layout_root: &LayoutBox) -> DisplayList {
+    build_display_list_with_selection(layout_root, &Selection::default())
+}
+
+/// Build a display list from a layout box tree, highlighting selected text
+/// under each run of text it covers
+pub fn build_display_list_with_selection(layout_root: &LayoutBox, selection: &Selection) -> DisplayList {
     let mut list = DisplayList::new();
-    render_layout_box(&mut list, layout_root, 0.0, 0.0);
+    render_layer(&mut list, &Layer::new(layout_root, 0.0, 0.0), selection);
     list
 }
 
-/// Recursively render a layout box and its children
-/// offset_x and offset_y are the absolute position of the parent's content area
-fn render_layout_box(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f32, offset_y: f32) {
-    let d = &layout_box.dimensions;
+/// Render a layer: its box, then the layers and normal flow inside it in
+/// stacking order
+///
+/// Below the flow go layers with negative z-index, then the flow's
+/// backgrounds, floats, the flow's content, and finally positioned layers
+/// by increasing z-index.
+fn render_layer(list: &mut DisplayList, layer: &Layer, selection: &Selection) {
+    let layout_box = layer.layout_box;
+    let (offset_x, offset_y) = (layer.offset_x, layer.offset_y);
+    let (abs_x, abs_y) = layer.content_origin();
+
+    // Fixed boxes stay put while the page scrolls
+    let is_fixed = layout_box.is_fixed_positioned();
+    if is_fixed {
+        list.push(PaintCommand::BeginFixed);
+    }
+
+    // Layers inside an overflow clipping box stay clipped to it, and move
+    // with the scroll containers among those boxes
+    if let Some(clip) = layer.clip {
+        list.push(PaintCommand::PushClip(clip));
+    }
+    for &scroller in &layer.scrollers {
+        list.push(PaintCommand::BeginScroll(scroller));
+    }
 
-    // Calculate absolute position of this box's content area
-    let abs_x = offset_x + d.content.x;
-    let abs_y = offset_y + d.content.y;
+    // Transformed boxes are painted as laid out and moved as a whole
+    let transform = box_transform(layout_box, offset_x, offset_y);
+    if let Some(transform) = transform {
+        list.push(PaintCommand::PushTransform(transform));
+    }
 
     // Check if we need to apply opacity
-    let needs_opacity = layout_box.style().map_or(false, |s| s.opacity < 1.0);
+    let opacity = layout_box.style().map_or(1.0, |s| s.opacity);
+    let needs_opacity = opacity < 1.0;
     if needs_opacity {
-        let opacity = layout_box.style().map(|s| s.opacity).unwrap_or(1.0);
         list.push(PaintCommand::PushOpacity(opacity));
     }
 
@@ -174,45 +358,144 @@ fn render_layout_box(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f
 
     // Render this box's background and borders
     render_background(list, layout_box, offset_x, offset_y);
+    render_inset_box_shadow(list, layout_box, offset_x, offset_y);
     render_borders(list, layout_box, offset_x, offset_y);
 
-    // Render content (text)
-    render_content(list, layout_box, abs_x, abs_y);
+    // Clip descendants to the padding box of this box
+    let needs_clip = layout_box.clips_overflow();
+    if needs_clip {
+        list.push(PaintCommand::PushClip(overflow_clip_rect(layout_box, abs_x, abs_y)));
+    }
+    let scroller = scroll_container_id(layout_box);
+    if let Some(scroller) = scroller {
+        list.push(PaintCommand::BeginScroll(scroller));
+    }
 
-    // Check if we need to clip overflow
-    let needs_clip = layout_box.style().map_or(false, |s| {
-        s.overflow != Overflow::Visible ||
-        s.overflow_x != Overflow::Visible ||
-        s.overflow_y != Overflow::Visible
-    });
+    for child in layer.below() {
+        render_layer(list, child, selection);
+    }
 
-    if needs_clip {
-        // Set clip rect to the content area of this box
-        let clip_rect = Rect::new(abs_x, abs_y, d.content.width, d.content.height);
-        list.push(PaintCommand::SetClipRect(clip_rect));
+    render_flow_backgrounds(list, layout_box, abs_x, abs_y);
+
+    for child in layer.floats() {
+        render_layer(list, child, selection);
     }
 
-    // Sort children by z-index before rendering
-    let mut children_sorted: Vec<_> = layout_box.children.iter().collect();
-    children_sorted.sort_by_key(|child| {
-        child.style().map(|s| s.z_index).unwrap_or(0)
-    });
+    let decoration = own_decoration(layout_box);
+    render_content(list, layout_box, abs_x, abs_y, decoration, selection);
+    render_flow_content(list, layout_box, abs_x, abs_y, decoration, selection);
 
-    // Render children - they are positioned relative to this box's content area
-    for child in children_sorted {
-        render_layout_box(list, child, abs_x, abs_y);
+    for child in layer.above() {
+        render_layer(list, child, selection);
     }
 
+    if scroller.is_some() {
+        list.push(PaintCommand::EndScroll);
+    }
     if needs_clip {
-        list.push(PaintCommand::ClearClipRect);
+        list.push(PaintCommand::PopClip);
     }
 
     if needs_opacity {
         list.push(PaintCommand::PopOpacity);
     }
+    if transform.is_some() {
+        list.push(PaintCommand::PopTransform);
+    }
+
+    for _ in &layer.scrollers {
+        list.push(PaintCommand::EndScroll);
+    }
+    if layer.clip.is_some() {
+        list.push(PaintCommand::PopClip);
+    }
+
+    if is_fixed {
+        list.push(PaintCommand::EndFixed);
+    }
 }
 
-/// Render box shadow for a layout box
+/// Render the backgrounds and borders of the normal flow descendants of a box
+/// offset_x and offset_y are the absolute position of the box's content area
+fn render_flow_backgrounds(list: &mut DisplayList, parent: &LayoutBox, offset_x: f32, offset_y: f32) {
+    for child in parent.children.iter().filter(|c| !is_layer(c)) {
+        render_box_shadow(list, child, offset_x, offset_y);
+        render_background(list, child, offset_x, offset_y);
+        render_inset_box_shadow(list, child, offset_x, offset_y);
+        render_borders(list, child, offset_x, offset_y);
+
+        let d = &child.dimensions;
+        let (abs_x, abs_y) = (offset_x + d.content.x, offset_y + d.content.y);
+        with_overflow_clip(list, child, abs_x, abs_y, |list| {
+            render_flow_backgrounds(list, child, abs_x, abs_y);
+        });
+    }
+}
+
+/// Render the text and replaced content of the normal flow descendants of a box
+/// offset_x and offset_y are the absolute position of the box's content area
+///
+/// `decoration` holds the text decoration lines of the box and its
+/// ancestors, which are drawn across the text of its descendants.
+fn render_flow_content(
+    list: &mut DisplayList,
+    parent: &LayoutBox,
+    offset_x: f32,
+    offset_y: f32,
+    decoration: TextDecorationLine,
+    selection: &Selection,
+) {
+    for child in parent.children.iter().filter(|c| !is_layer(c)) {
+        let d = &child.dimensions;
+        let (abs_x, abs_y) = (offset_x + d.content.x, offset_y + d.content.y);
+
+        // Inline blocks don't take the decorations of their ancestors
+        let decoration = match child.box_type {
+            BoxType::InlineBlock(..) => own_decoration(child),
+            _ => decoration.union(own_decoration(child)),
+        };
+        render_content(list, child, abs_x, abs_y, decoration, selection);
+
+        with_overflow_clip(list, child, abs_x, abs_y, |list| {
+            render_flow_content(list, child, abs_x, abs_y, decoration, selection);
+        });
+    }
+}
+
+/// Text decoration lines set on a box itself
+fn own_decoration(layout_box: &LayoutBox) -> TextDecorationLine {
+    layout_box.style().map_or(TextDecorationLine::default(), |s| s.text_decoration_line)
+}
+
+/// Run `render` with the box's padding box as clip rect if it clips overflow,
+/// moved by the box's scroll offset if it is a scroll container
+fn with_overflow_clip(
+    list: &mut DisplayList,
+    layout_box: &LayoutBox,
+    abs_x: f32,
+    abs_y: f32,
+    render: impl FnOnce(&mut DisplayList),
+) {
+    let needs_clip = layout_box.clips_overflow();
+    if needs_clip {
+        list.push(PaintCommand::PushClip(overflow_clip_rect(layout_box, abs_x, abs_y)));
+    }
+    let scroller = scroll_container_id(layout_box);
+    if let Some(scroller) = scroller {
+        list.push(PaintCommand::BeginScroll(scroller));
+    }
+
+    render(list);
+
+    if scroller.is_some() {
+        list.push(PaintCommand::EndScroll);
+    }
+    if needs_clip {
+        list.push(PaintCommand::PopClip);
+    }
+}
+
+/// Render the outset box shadow of a layout box, cast around its border box
 fn render_box_shadow(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f32, offset_y: f32) {
     let style = match layout_box.style() {
         Some(s) => s,
@@ -220,15 +503,10 @@ fn render_box_shadow(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f
     };
 
     let shadow = match &style.box_shadow {
-        Some(s) => s,
-        None => return,
+        Some(s) if !s.inset => s,
+        _ => return,
     };
 
-    // Skip inset shadows for now (more complex to render)
-    if shadow.inset {
-        return;
-    }
-
     let d = &layout_box.dimensions;
     let border_box = d.border_box();
 
@@ -241,6 +519,47 @@ fn render_box_shadow(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f
 
     list.push(PaintCommand::DrawBoxShadow {
         rect,
+        radius: style.border_radius,
+        shadow: shadow.clone(),
+    });
+}
+
+/// Render the inset box shadow of a layout box, over its background and
+/// inside its padding box
+fn render_inset_box_shadow(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f32, offset_y: f32) {
+    let style = match layout_box.style() {
+        Some(s) => s,
+        None => return,
+    };
+
+    let shadow = match &style.box_shadow {
+        Some(s) if s.inset => s,
+        _ => return,
+    };
+
+    let d = &layout_box.dimensions;
+    let padding_box = d.padding_box();
+    let rect = Rect::new(
+        offset_x + padding_box.x,
+        offset_y + padding_box.y,
+        padding_box.width,
+        padding_box.height,
+    );
+
+    // The inner corners are rounded less by the width of the borders
+    // beside them
+    let b = &d.border;
+    let r = &style.border_radius;
+    let radius = BorderRadius {
+        top_left: (r.top_left - b.top.max(b.left)).max(0.0),
+        top_right: (r.top_right - b.top.max(b.right)).max(0.0),
+        bottom_right: (r.bottom_right - b.bottom.max(b.right)).max(0.0),
+        bottom_left: (r.bottom_left - b.bottom.max(b.left)).max(0.0),
+    };
+
+    list.push(PaintCommand::DrawBoxShadow {
+        rect,
+        radius,
         shadow: shadow.clone(),
     });
 }
@@ -275,19 +594,17 @@ fn render_background(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f
             let render_color: RenderColor = (*color).into();
 
             // Skip transparent backgrounds
-            if render_color.is_transparent() {
-                return;
-            }
-
-            // Check if we have border-radius
-            if has_radius {
-                list.push(PaintCommand::FillRoundedRect {
-                    rect,
-                    radius: style.border_radius,
-                    color: render_color,
-                });
-            } else {
-                list.push(PaintCommand::FillRect { rect, color: render_color });
+            if !render_color.is_transparent() {
+                // Check if we have border-radius
+                if has_radius {
+                    list.push(PaintCommand::FillRoundedRect {
+                        rect,
+                        radius: style.border_radius,
+                        color: render_color,
+                    });
+                } else {
+                    list.push(PaintCommand::FillRect { rect, color: render_color });
+                }
             }
         }
         Background::Gradient(gradient) => {
@@ -320,6 +637,19 @@ fn render_background(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f
             }
         }
     }
+
+    // The image is painted over the color, positioned in the padding box
+    if let (Some(_), Some(pixels)) = (&style.background_image, &layout_box.background_pixels) {
+        let padding_box = d.padding_box();
+        let area = Rect::new(offset_x + padding_box.x, offset_y + padding_box.y, padding_box.width, padding_box.height);
+        list.push(PaintCommand::DrawTiledImage {
+            tile: background_tile(style, area, pixels.width as f32, pixels.height as f32),
+            pixels: pixels.clone(),
+            repeat: style.background_repeat,
+            clip: rect,
+            rendering: style.image_rendering,
+        });
+    }
 }
 
 /// Render the borders of a layout box
@@ -340,7 +670,6 @@ fn render_borders(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f32,
         return;
     }
 
-    let color: RenderColor = style.border_color.into();
     let border_box = d.border_box();
 
     // Adjust to absolute position
@@ -357,6 +686,18 @@ fn render_borders(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f32,
         bottom: d.border.bottom,
         left: d.border.left,
     };
+    let styles = BorderStyles {
+        top: style.border_top_style,
+        right: style.border_right_style,
+        bottom: style.border_bottom_style,
+        left: style.border_left_style,
+    };
+    let colors = BorderColors {
+        top: style.border_top_color.into(),
+        right: style.border_right_color.into(),
+        bottom: style.border_bottom_color.into(),
+        left: style.border_left_color.into(),
+    };
 
     // Check if we have border-radius
     if style.border_radius.has_radius() {
@@ -364,30 +705,72 @@ fn render_borders(list: &mut DisplayList, layout_box: &LayoutBox, offset_x: f32,
             rect,
             radius: style.border_radius,
             widths,
-            color,
+            styles,
+            colors,
         });
     } else {
         list.push(PaintCommand::DrawBorder {
             rect,
             widths,
-            color,
+            styles,
+            colors,
         });
     }
 }
 
 /// Render text content and form elements
-fn render_content(list: &mut DisplayList, layout_box: &LayoutBox, abs_x: f32, abs_y: f32) {
+///
+/// `decoration` holds the lines drawn with text, of the box and the boxes
+/// it is inside.
+fn render_content(
+    list: &mut DisplayList,
+    layout_box: &LayoutBox,
+    abs_x: f32,
+    abs_y: f32,
+    decoration: TextDecorationLine,
+    selection: &Selection,
+) {
     match &layout_box.box_type {
         BoxType::Text(_, text, style) => {
             let color: RenderColor = style.color.into();
+            let face = FontFace::for_style(style);
+            let metrics = measure_text("", style);
 
-            list.push(PaintCommand::DrawText {
-                text: text.clone(),
-                x: abs_x,
-                y: abs_y,
-                color,
-                font_size: style.font_size,
-            });
+            if layout_box.fragments.is_empty() {
+                // A single line, the leading split above and below the glyphs
+                let half_leading = (metrics.height - metrics.ascent - metrics.descent) / 2.0;
+                let y = abs_y + half_leading;
+                let width = measure_text(text, style).width;
+                render_selection(list, selection, Rect::new(abs_x, abs_y, width, layout_box.dimensions.content.height));
+                list.push(PaintCommand::DrawText {
+                    text: text.clone(),
+                    x: abs_x,
+                    y,
+                    color,
+                    font_size: style.font_size,
+                    face,
+                    family: Some(style.font_family.clone()),
+                });
+                render_text_decoration(list, decoration, style, Rect::new(abs_x, y, width, 0.0));
+            }
+
+            // One run of text per line the box was broken into, placed by
+            // its baseline so different font sizes line up
+            for fragment in &layout_box.fragments {
+                let x = abs_x + fragment.rect.x;
+                let y = abs_y + fragment.rect.y + fragment.baseline - metrics.ascent;
+                render_selection(list, selection, Rect { x, y: abs_y + fragment.rect.y, ..fragment.rect });
+                list.push(PaintCommand::DrawText {
+                    text: fragment.text.clone().unwrap_or_default(),
+                    x,
+                    y,
+                    color,
+                    font_size: style.font_size,
+                    face,
+                    family: Some(style.font_family.clone()),
+                });
+                render_text_decoration(list, decoration, style, Rect::new(x, y, fragment.rect.width, 0.0));
+            }
         }
         BoxType::Input(node_id, input_type, _) => {
             let d = &layout_box.dimensions;
@@ -431,6 +814,13 @@ fn render_content(list: &mut DisplayList, layout_box: &LayoutBox, abs_x: f32, ab
                         is_pressed: false,
                     });
                 }
+                InputType::File => {
+                    list.push(PaintCommand::DrawFileInput {
+                        node_id,
+                        rect,
+                        label: "No file chosen".to_string(),
+                    });
+                }
                 InputType::Hidden => {
                     // Hidden inputs don't render anything
                 }
@@ -447,23 +837,118 @@ fn render_content(list: &mut DisplayList, layout_box: &LayoutBox, abs_x: f32, ab
                 is_pressed: false,
             });
         }
-        BoxType::Image(_, ref image_data, _) => {
+        BoxType::TextArea(node_id, data, style) => {
             let d = &layout_box.dimensions;
-            let rect = Rect::new(abs_x, abs_y, d.content.width, d.content.height);
+            list.push(PaintCommand::DrawTextArea {
+                node_id: *node_id,
+                rect: Rect::new(abs_x, abs_y, d.content.width, d.content.height),
+                text: data.text.clone(),
+                cursor_pos: None,
+                font_size: style.font_size,
+                line_height: style.line_height,
+                first_line: 0,
+                is_focused: false,
+            });
+        }
+        BoxType::Select(node_id, data, style) => {
+            let d = &layout_box.dimensions;
+            list.push(PaintCommand::DrawSelect {
+                node_id: *node_id,
+                rect: Rect::new(abs_x, abs_y, d.content.width, d.content.height),
+                text: data.options.get(data.selected).cloned().unwrap_or_default(),
+                font_size: style.font_size,
+            });
+        }
+        BoxType::Image(_, ref image_data, style) => {
+            let d = &layout_box.dimensions;
+            let content = Rect::new(abs_x, abs_y, d.content.width, d.content.height);
+
+            // Placeholders fill the content box
+            let rect = match &image_data.pixels {
+                Some(pixels) => object_fit_rect(style, content, pixels.width as f32, pixels.height as f32),
+                None => content,
+            };
+            let overflows = rect.x < content.x
+                || rect.y < content.y
+                || rect.right() > content.right()
+                || rect.bottom() > content.bottom();
 
             list.push(PaintCommand::DrawImage {
                 rect,
                 pixels: image_data.pixels.clone(),
                 alt: image_data.alt.clone(),
+                clip: overflows.then_some(content),
+                rendering: style.image_rendering,
             });
         }
         _ => {}
     }
 }
 
+/// Highlight the selected part of a run of text, before the text is drawn
+fn render_selection(list: &mut DisplayList, selection: &Selection, run: Rect) {
+    let rects = selection.within(&run);
+    if !rects.is_empty() {
+        list.push(PaintCommand::FillSelection { rects, color: selection.color });
+    }
+}
+
+/// Draw the decoration lines across a run of text in the text color
+///
+/// `run` spans the width of the text from the top of its glyphs. Underlines
+/// sit half a descent below the baseline, line-throughs at half the
+/// x-height, taken as 60% of the ascent.
+fn render_text_decoration(list: &mut DisplayList, lines: TextDecorationLine, style: &ComputedStyle, run: Rect) {
+    if lines.is_none() || run.width <= 0.0 {
+        return;
+    }
+    let metrics = measure_text("", style);
+    let thickness = (style.font_size / 16.0).round().max(1.0);
+    let baseline = run.y + metrics.ascent;
+
+    let positions = [
+        (lines.overline, run.y),
+        (lines.underline, baseline + metrics.descent / 2.0),
+        (lines.line_through, baseline - metrics.ascent * 0.3 - thickness / 2.0),
+    ];
+    for (_, y) in positions.into_iter().filter(|(drawn, _)| *drawn) {
+        list.push(PaintCommand::FillRect {
+            rect: Rect::new(run.x, y, run.width, thickness),
+            color: style.color.into(),
+        });
+    }
+}
+
+/// Rect replaced content of the given natural size is painted into, sized
+/// by object-fit and placed by object-position within the content box
+fn object_fit_rect(style: &ComputedStyle, content: Rect, natural_width: f32, natural_height: f32) -> Rect {
+    if natural_width <= 0.0 || natural_height <= 0.0 {
+        return content;
+    }
+
+    let contain = (content.width / natural_width).min(content.height / natural_height);
+    let scale = match style.object_fit {
+        ObjectFit::Fill => return content,
+        ObjectFit::Contain => contain,
+        ObjectFit::Cover => (content.width / natural_width).max(content.height / natural_height),
+        ObjectFit::None => 1.0,
+        ObjectFit::ScaleDown => contain.min(1.0),
+    };
+
+    let (width, height) = (natural_width * scale, natural_height * scale);
+    let position = style.object_position;
+    Rect::new(
+        content.x + position.x.resolve(content.width - width),
+        content.y + position.y.resolve(content.height - height),
+        width,
+        height,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stacking::boxes_in_paint_order;
 
     #[test]
     fn test_display_list_new() {
@@ -481,6 +966,450 @@ mod tests {
         assert_eq!(list.len(), 1);
     }
 
+    #[test]
+    fn test_fixed_box_commands_marked() {
+        use gugalanna_css::Color;
+        use gugalanna_style::{ComputedStyle, Position};
+
+        let page_style = ComputedStyle::default();
+        let banner_style = ComputedStyle {
+            position: Position::Fixed,
+            background: Background::Color(Color::rgb(255, 0, 0)),
+            ..ComputedStyle::default()
+        };
+
+        let mut banner = LayoutBox::new_block(NodeId(2), &banner_style);
+        banner.dimensions.content = Rect::new(0.0, 0.0, 100.0, 20.0);
+        let mut page = LayoutBox::new_block(NodeId(1), &page_style);
+        page.children.push(banner);
+
+        let list = build_display_list(&page);
+        assert!(matches!(
+            list.commands.as_slice(),
+            [PaintCommand::BeginFixed, PaintCommand::FillRect { .. }, PaintCommand::EndFixed]
+        ));
+    }
+
+    #[test]
+    fn test_select_paints_selected_option() {
+        use gugalanna_layout::SelectData;
+        use gugalanna_style::ComputedStyle;
+
+        let style = ComputedStyle::default();
+        let data = SelectData { options: vec!["one".to_string(), "two".to_string()], selected: 1 };
+        let mut select = LayoutBox::new_select(NodeId(2), data, &style);
+        select.dimensions.content = Rect::new(10.0, 20.0, 80.0, 28.0);
+        let mut page = LayoutBox::new_block(NodeId(1), &style);
+        page.children.push(select);
+
+        // The whole control is painted with the selected option's text
+        let list = build_display_list(&page);
+        assert!(matches!(
+            list.commands.as_slice(),
+            [PaintCommand::DrawSelect { rect, text, .. }] if *rect == Rect::new(10.0, 20.0, 80.0, 28.0) && text == "two"
+        ));
+    }
+
+    /// Colors of the FillRect commands, in paint order
+    fn fill_colors(list: &DisplayList) -> Vec<RenderColor> {
+        list.commands
+            .iter()
+            .filter_map(|c| match c {
+                PaintCommand::FillRect { color, .. } => Some(*color),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_z_index_orders_overlapping_boxes() {
+        use gugalanna_css::Color;
+        use gugalanna_style::{ComputedStyle, Position};
+
+        let boxed = |z_index, color| ComputedStyle {
+            position: Position::Absolute,
+            z_index,
+            background: Background::Color(color),
+            ..ComputedStyle::default()
+        };
+        let page_style = ComputedStyle::default();
+        let red = boxed(2, Color::rgb(255, 0, 0));
+        let blue = boxed(1, Color::rgb(0, 0, 255));
+
+        let mut page = LayoutBox::new_block(NodeId(1), &page_style);
+        for (id, style) in [(2, &red), (3, &blue)] {
+            let mut b = LayoutBox::new_block(NodeId(id), style);
+            b.dimensions.content = Rect::new(0.0, 0.0, 50.0, 50.0);
+            page.children.push(b);
+        }
+
+        // Red comes first in the tree but has the higher z-index
+        let colors = fill_colors(&build_display_list(&page));
+        assert_eq!(colors, vec![RenderColor::from(Color::rgb(0, 0, 255)), RenderColor::from(Color::rgb(255, 0, 0))]);
+    }
+
+    #[test]
+    fn test_transformed_box_painted_as_layer() {
+        use gugalanna_css::Color;
+        use gugalanna_style::{ComputedStyle, TransformFunction};
+
+        let page_style = ComputedStyle::default();
+        let turned = ComputedStyle {
+            transform: vec![TransformFunction::Rotate(90.0)],
+            background: Background::Color(Color::rgb(255, 0, 0)),
+            ..ComputedStyle::default()
+        };
+        let flow = ComputedStyle { background: Background::Color(Color::rgb(0, 0, 255)), ..ComputedStyle::default() };
+
+        let mut page = LayoutBox::new_block(NodeId(1), &page_style);
+        let mut b = LayoutBox::new_block(NodeId(2), &turned);
+        b.dimensions.content = Rect::new(10.0, 0.0, 20.0, 10.0);
+        let mut inner = LayoutBox::new_block(NodeId(3), &page_style);
+        inner.dimensions.content = Rect::new(0.0, 0.0, 20.0, 10.0);
+        b.children.push(inner);
+        page.children.push(b);
+        let mut after = LayoutBox::new_block(NodeId(4), &flow);
+        after.dimensions.content = Rect::new(0.0, 10.0, 40.0, 10.0);
+        page.children.push(after);
+
+        // The transformed box is stacked over the flow that follows it
+        let list = build_display_list(&page);
+        assert_eq!(fill_colors(&list), vec![RenderColor::from(Color::rgb(0, 0, 255)), RenderColor::from(Color::rgb(255, 0, 0))]);
+        let turn = Transform::of_box(&turned.transform, 10.0, 0.0, 20.0, 10.0);
+        let push = list.commands.iter().position(|c| *c == PaintCommand::PushTransform(turn)).unwrap();
+        assert!(matches!(list.commands[push + 1], PaintCommand::FillRect { .. }));
+        assert_eq!(list.commands.last(), Some(&PaintCommand::PopTransform));
+
+        // Its descendants are painted with it
+        let boxes = boxes_in_paint_order(&page);
+        let transforms: Vec<_> = boxes.iter().map(|b| (b.layout_box.node_id(), b.transform)).collect();
+        assert_eq!(
+            transforms,
+            [(Some(NodeId(1)), None), (Some(NodeId(4)), None), (Some(NodeId(2)), Some(turn)), (Some(NodeId(3)), Some(turn))]
+        );
+    }
+
+    #[test]
+    fn test_negative_z_index_below_flow() {
+        use gugalanna_css::Color;
+        use gugalanna_style::{ComputedStyle, Position};
+
+        let parent_style = ComputedStyle {
+            position: Position::Relative,
+            background: Background::Color(Color::rgb(255, 255, 255)),
+            ..ComputedStyle::default()
+        };
+        let below_style = ComputedStyle {
+            position: Position::Absolute,
+            z_index: -1,
+            background: Background::Color(Color::rgb(255, 0, 0)),
+            ..ComputedStyle::default()
+        };
+        let flow_style = ComputedStyle {
+            background: Background::Color(Color::rgb(0, 0, 255)),
+            ..ComputedStyle::default()
+        };
+
+        let mut parent = LayoutBox::new_block(NodeId(1), &parent_style);
+        parent.dimensions.content = Rect::new(0.0, 0.0, 50.0, 50.0);
+        let mut flow = LayoutBox::new_block(NodeId(2), &flow_style);
+        flow.dimensions.content = Rect::new(0.0, 0.0, 50.0, 50.0);
+        let mut below = LayoutBox::new_block(NodeId(3), &below_style);
+        below.dimensions.content = Rect::new(0.0, 0.0, 50.0, 50.0);
+        parent.children.push(flow);
+        parent.children.push(below);
+
+        // Above the parent's background, below its in-flow children
+        let colors = fill_colors(&build_display_list(&parent));
+        let expected: Vec<RenderColor> = [Color::rgb(255, 255, 255), Color::rgb(255, 0, 0), Color::rgb(0, 0, 255)]
+            .into_iter()
+            .map(RenderColor::from)
+            .collect();
+        assert_eq!(colors, expected);
+    }
+
+    #[test]
+    fn test_overflow_hidden_clips_children() {
+        use gugalanna_css::Color;
+        use gugalanna_style::{ComputedStyle, Overflow};
+
+        let clip_style = ComputedStyle {
+            overflow: Overflow::Hidden,
+            ..ComputedStyle::default()
+        };
+        let child_style = ComputedStyle {
+            background: Background::Color(Color::rgb(255, 0, 0)),
+            ..ComputedStyle::default()
+        };
+
+        let mut clipper = LayoutBox::new_block(NodeId(1), &clip_style);
+        clipper.dimensions.content = Rect::new(10.0, 10.0, 100.0, 50.0);
+        clipper.dimensions.padding.top = 5.0;
+        let mut child = LayoutBox::new_block(NodeId(2), &child_style);
+        child.dimensions.content = Rect::new(0.0, 0.0, 100.0, 200.0);
+        clipper.children.push(child);
+
+        let list = build_display_list(&clipper);
+        let clip = list.commands.iter().position(|c| matches!(c, PaintCommand::PushClip(_)));
+        let fill = list.commands.iter().position(|c| matches!(c, PaintCommand::FillRect { .. }));
+        let pop = list.commands.iter().position(|c| matches!(c, PaintCommand::PopClip));
+
+        // The child is painted inside the clipper's padding box
+        assert!(clip < fill && fill < pop);
+        assert!(matches!(
+            list.commands[clip.unwrap()],
+            PaintCommand::PushClip(rect) if rect == Rect::new(10.0, 5.0, 100.0, 55.0)
+        ));
+    }
+
+    #[test]
+    fn test_scroll_container_content_marked() {
+        use gugalanna_css::Color;
+        use gugalanna_style::{ComputedStyle, Overflow, Position};
+
+        let page_style = ComputedStyle::default();
+        let scroll_style = ComputedStyle {
+            overflow: Overflow::Auto,
+            ..ComputedStyle::default()
+        };
+        let flow_style = ComputedStyle {
+            background: Background::Color(Color::rgb(255, 0, 0)),
+            ..ComputedStyle::default()
+        };
+        let positioned_style = ComputedStyle {
+            position: Position::Absolute,
+            background: Background::Color(Color::rgb(0, 0, 255)),
+            ..ComputedStyle::default()
+        };
+
+        let mut scroller = LayoutBox::new_block(NodeId(2), &scroll_style);
+        scroller.dimensions.content = Rect::new(0.0, 0.0, 100.0, 50.0);
+        for (id, style) in [(3, &flow_style), (4, &positioned_style)] {
+            let mut child = LayoutBox::new_block(NodeId(id), style);
+            child.dimensions.content = Rect::new(0.0, 0.0, 100.0, 200.0);
+            scroller.children.push(child);
+        }
+        let mut page = LayoutBox::new_block(NodeId(1), &page_style);
+        page.children.push(scroller);
+
+        // Both the flow content and the positioned layer move with the
+        // scroller, inside its clip, in every paint pass
+        let list = build_display_list(&page);
+        let kinds: Vec<_> = list
+            .commands
+            .iter()
+            .map(|c| match c {
+                PaintCommand::PushClip(_) => "clip",
+                PaintCommand::PopClip => "pop",
+                PaintCommand::BeginScroll(NodeId(2)) => "scroll",
+                PaintCommand::EndScroll => "end",
+                PaintCommand::FillRect { .. } => "fill",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "clip", "scroll", "fill", "end", "pop",
+                "clip", "scroll", "end", "pop",
+                "clip", "scroll", "fill", "end", "pop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_drawn_from_baseline() {
+        use gugalanna_layout::InlineBox;
+        use gugalanna_style::ComputedStyle;
+
+        let style = ComputedStyle {
+            font_size: 20.0,
+            line_height: 40.0,
+            ..ComputedStyle::default()
+        };
+        let mut text = LayoutBox::new_text(NodeId(1), "text".to_string(), &style);
+        text.dimensions.content = Rect::new(0.0, 10.0, 48.0, 40.0);
+        text.fragments.push(InlineBox::new(Rect::new(0.0, 0.0, 48.0, 40.0), Some("text".to_string()), 26.0));
+
+        let list = build_display_list(&text);
+
+        // The glyphs start one ascent (16px) above the baseline at 36px
+        assert!(matches!(
+            list.commands.as_slice(),
+            [PaintCommand::DrawText { y, .. }] if *y == 20.0
+        ));
+    }
+
+    #[test]
+    fn test_link_underlined_on_each_line() {
+        use gugalanna_layout::InlineBox;
+        use gugalanna_style::TextDecorationLine;
+
+        let page_style = ComputedStyle::default();
+        let link_style = ComputedStyle {
+            font_size: 10.0,
+            line_height: 20.0,
+            text_decoration_line: TextDecorationLine { underline: true, ..TextDecorationLine::default() },
+            ..ComputedStyle::default()
+        };
+
+        // A link broken over two lines, 30px and 24px of text
+        let mut text = LayoutBox::new_text(NodeId(3), "aaaaa bbbb".to_string(), &link_style);
+        text.fragments.push(InlineBox::new(Rect::new(0.0, 0.0, 30.0, 20.0), Some("aaaaa".to_string()), 14.0));
+        text.fragments.push(InlineBox::new(Rect::new(0.0, 20.0, 24.0, 20.0), Some("bbbb".to_string()), 14.0));
+        let mut link = LayoutBox::new_inline(NodeId(2), &link_style);
+        link.children.push(text);
+        let mut page = LayoutBox::new_block(NodeId(1), &page_style);
+        page.dimensions.content = Rect::new(10.0, 0.0, 100.0, 40.0);
+        page.children.push(link);
+
+        // Each run is underlined 1px below its baseline, at 14px and 34px
+        let underlines: Vec<_> = build_display_list(&page)
+            .commands
+            .iter()
+            .filter_map(|c| match c {
+                PaintCommand::FillRect { rect, .. } => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(underlines, vec![Rect::new(10.0, 15.0, 30.0, 1.0), Rect::new(10.0, 35.0, 24.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_selection_painted_between_backgrounds_and_text() {
+        use gugalanna_css::Color;
+        use gugalanna_layout::InlineBox;
+
+        let page_style = ComputedStyle { background: Background::Color(Color::rgb(255, 255, 255)), ..ComputedStyle::default() };
+        let text_style = ComputedStyle { font_size: 10.0, line_height: 20.0, ..ComputedStyle::default() };
+
+        // Text over two lines, 30px and 24px wide
+        let mut text = LayoutBox::new_text(NodeId(2), "aaaaa bbbb".to_string(), &text_style);
+        text.fragments.push(InlineBox::new(Rect::new(0.0, 0.0, 30.0, 20.0), Some("aaaaa".to_string()), 14.0));
+        text.fragments.push(InlineBox::new(Rect::new(0.0, 20.0, 24.0, 20.0), Some("bbbb".to_string()), 14.0));
+        let mut page = LayoutBox::new_block(NodeId(1), &page_style);
+        page.dimensions.content = Rect::new(10.0, 0.0, 100.0, 40.0);
+        page.children.push(text);
+
+        // Selected from the middle of the first line into the second
+        let selection = Selection {
+            rects: vec![Rect::new(20.0, 0.0, 90.0, 20.0), Rect::new(10.0, 20.0, 15.0, 20.0)],
+            ..Selection::default()
+        };
+        let list = build_display_list_with_selection(&page, &selection);
+
+        // Each line's highlight is clipped to its text, after the page
+        // background and right before the text it covers
+        let color = selection.color;
+        assert!(matches!(
+            list.commands.as_slice(),
+            [
+                PaintCommand::FillRect { .. },
+                PaintCommand::FillSelection { rects: first, .. },
+                PaintCommand::DrawText { text: a, .. },
+                PaintCommand::FillSelection { rects: second, color: second_color },
+                PaintCommand::DrawText { text: b, .. },
+            ] if *first == [Rect::new(20.0, 0.0, 20.0, 20.0)]
+                && *second == [Rect::new(10.0, 20.0, 15.0, 20.0)]
+                && *second_color == color
+                && a == "aaaaa"
+                && b == "bbbb"
+        ));
+
+        // Without a selection nothing is highlighted
+        let plain = build_display_list(&page);
+        assert!(!plain.commands.iter().any(|c| matches!(c, PaintCommand::FillSelection { .. })));
+    }
+
+    #[test]
+    fn test_bold_text_selects_the_bold_face() {
+        use gugalanna_style::FontStyle;
+
+        let draw = |font_weight, font_style| {
+            let style = ComputedStyle { font_weight, font_style, ..ComputedStyle::default() };
+            let text = LayoutBox::new_text(NodeId(1), "text".to_string(), &style);
+            match build_display_list(&text).commands.as_slice() {
+                [PaintCommand::DrawText { face, .. }] => *face,
+                commands => panic!("unexpected commands {:?}", commands),
+            }
+        };
+
+        assert_eq!(draw(400, FontStyle::Normal), FontFace::REGULAR);
+        assert_eq!(draw(500, FontStyle::Normal), FontFace::REGULAR);
+        assert_eq!(draw(600, FontStyle::Normal), FontFace::BOLD);
+        assert_eq!(draw(700, FontStyle::Italic), FontFace::BOLD_ITALIC);
+        assert_eq!(draw(400, FontStyle::Oblique), FontFace::ITALIC);
+    }
+
+    #[test]
+    fn test_empty_box_paints_border_and_background() {
+        use gugalanna_css::Color;
+        use gugalanna_style::ComputedStyle;
+
+        let style = ComputedStyle {
+            background: Background::Color(Color::rgb(128, 128, 128)),
+            ..ComputedStyle::default()
+        };
+        let mut rule = LayoutBox::new_block(NodeId(1), &style);
+        rule.dimensions.content = Rect::new(1.0, 1.0, 100.0, 0.0);
+        rule.dimensions.border.top = 1.0;
+        rule.dimensions.border.bottom = 1.0;
+        rule.dimensions.border.left = 1.0;
+        rule.dimensions.border.right = 1.0;
+
+        let list = build_display_list(&rule);
+        let expected = Rect::new(0.0, 0.0, 102.0, 2.0);
+        assert!(matches!(
+            list.commands.as_slice(),
+            [PaintCommand::FillRect { rect: fill, .. }, PaintCommand::DrawBorder { rect: border, .. }]
+                if *fill == expected && *border == expected
+        ));
+    }
+
+    #[test]
+    fn test_box_shadow_commands() {
+        use gugalanna_css::Color;
+        use gugalanna_style::ComputedStyle;
+
+        let shadowed = |inset| {
+            let style = ComputedStyle {
+                background: Background::Color(Color::rgb(255, 255, 255)),
+                border_radius: BorderRadius { top_left: 12.0, top_right: 12.0, bottom_right: 12.0, bottom_left: 12.0 },
+                box_shadow: Some(BoxShadow {
+                    offset_x: 2.0,
+                    offset_y: 2.0,
+                    blur_radius: 6.0,
+                    spread_radius: 0.0,
+                    color: Color::rgba(0, 0, 0, 128),
+                    inset,
+                }),
+                ..ComputedStyle::default()
+            };
+            let mut block = LayoutBox::new_block(NodeId(1), &style);
+            block.dimensions.content = Rect::new(4.0, 4.0, 50.0, 30.0);
+            block.dimensions.border = gugalanna_layout::EdgeSizes { top: 4.0, right: 4.0, bottom: 4.0, left: 4.0 };
+            build_display_list(&block)
+        };
+
+        // Outset shadows are cast by the rounded border box, under the
+        // background
+        let outset = shadowed(false);
+        assert!(matches!(
+            outset.commands.as_slice(),
+            [PaintCommand::DrawBoxShadow { rect, radius, .. }, PaintCommand::FillRoundedRect { .. }, ..]
+                if *rect == Rect::new(0.0, 0.0, 58.0, 38.0) && radius.top_left == 12.0
+        ));
+
+        // Inset shadows are drawn over the background inside the padding
+        // box, whose corners are rounded less by the border
+        let inset = shadowed(true);
+        assert!(matches!(
+            inset.commands.as_slice(),
+            [PaintCommand::FillRoundedRect { .. }, PaintCommand::DrawBoxShadow { rect, radius, .. }, PaintCommand::DrawRoundedBorder { .. }]
+                if *rect == Rect::new(4.0, 4.0, 50.0, 30.0) && radius.bottom_right == 8.0
+        ));
+    }
+
     #[test]
     fn test_border_widths() {
         let bw = BorderWidths {
@@ -492,4 +1421,168 @@ mod tests {
         assert_eq!(bw.top, 1.0);
         assert_eq!(bw.right, 2.0);
     }
+
+    #[test]
+    fn test_border_colors_per_side() {
+        use gugalanna_css::Color;
+        use gugalanna_style::ComputedStyle;
+
+        let style = ComputedStyle {
+            border_top_color: Color::rgb(255, 0, 0),
+            border_right_color: Color::rgb(0, 255, 0),
+            border_bottom_color: Color::rgb(0, 0, 255),
+            border_left_color: Color::rgb(0, 170, 255),
+            ..ComputedStyle::default()
+        };
+        let mut block = LayoutBox::new_block(NodeId(1), &style);
+        block.dimensions.content = Rect::new(4.0, 4.0, 50.0, 30.0);
+        block.dimensions.border = gugalanna_layout::EdgeSizes { top: 4.0, right: 4.0, bottom: 4.0, left: 4.0 };
+
+        let list = build_display_list(&block);
+        let colors = list.commands.iter().find_map(|command| match command {
+            PaintCommand::DrawBorder { colors, .. } => Some(*colors),
+            _ => None,
+        });
+        assert_eq!(
+            colors,
+            Some(BorderColors {
+                top: RenderColor::rgb(255, 0, 0),
+                right: RenderColor::rgb(0, 255, 0),
+                bottom: RenderColor::rgb(0, 0, 255),
+                left: RenderColor::rgb(0, 170, 255),
+            })
+        );
+    }
+
+    #[test]
+    fn test_object_fit_modes() {
+        use gugalanna_layout::ImageData;
+
+        // A 400x200 image in a 100x100 box at (10, 10)
+        let draw = |object_fit| {
+            let style = ComputedStyle { object_fit, ..ComputedStyle::default() };
+            let image_data = ImageData {
+                src: "wide.png".to_string(),
+                intrinsic_width: Some(400.0),
+                intrinsic_height: Some(200.0),
+                alt: String::new(),
+                pixels: Some(ImagePixels { width: 400, height: 200, data: vec![0; 400 * 200 * 4].into() }),
+            };
+            let mut image = LayoutBox::new_image(NodeId(1), image_data, &style);
+            image.dimensions.content = Rect::new(10.0, 10.0, 100.0, 100.0);
+
+            match build_display_list(&image).commands.as_slice() {
+                [PaintCommand::DrawImage { rect, clip, .. }] => (*rect, *clip),
+                commands => panic!("unexpected commands {:?}", commands),
+            }
+        };
+        let content = Rect::new(10.0, 10.0, 100.0, 100.0);
+
+        assert_eq!(draw(ObjectFit::Fill), (content, None));
+        // Letterboxed, centered vertically
+        assert_eq!(draw(ObjectFit::Contain), (Rect::new(10.0, 35.0, 100.0, 50.0), None));
+        assert_eq!(draw(ObjectFit::ScaleDown), (Rect::new(10.0, 35.0, 100.0, 50.0), None));
+        // Cropped on both sides
+        assert_eq!(draw(ObjectFit::Cover), (Rect::new(-40.0, 10.0, 200.0, 100.0), Some(content)));
+        assert_eq!(draw(ObjectFit::None), (Rect::new(-140.0, -40.0, 400.0, 200.0), Some(content)));
+    }
+
+    #[test]
+    fn test_background_image_tiles_border_box() {
+        use crate::background::tile_rects;
+        use gugalanna_style::BackgroundSize;
+
+        // An 8x8 tile repeated over a 50x30 box from its padding box
+        let style = ComputedStyle {
+            background_image: Some("tile.png".to_string()),
+            ..ComputedStyle::default()
+        };
+        let pixels = ImagePixels { width: 8, height: 8, data: vec![255; 8 * 8 * 4].into() };
+        let mut b = LayoutBox::new_block(NodeId(1), &style);
+        b.dimensions.content = Rect::new(2.0, 2.0, 46.0, 26.0);
+        b.dimensions.border = gugalanna_layout::EdgeSizes { top: 2.0, right: 2.0, bottom: 2.0, left: 2.0 };
+        b.background_pixels = Some(pixels.clone());
+
+        let commands = build_display_list(&b).commands;
+        let Some(PaintCommand::DrawTiledImage { tile, pixels: drawn, repeat, clip, .. }) = commands.first() else {
+            panic!("unexpected commands {:?}", commands);
+        };
+        assert_eq!(*tile, Rect::new(2.0, 2.0, 8.0, 8.0));
+        assert_eq!(*drawn, pixels);
+        assert_eq!(*repeat, BackgroundRepeat::Repeat);
+        assert_eq!(*clip, Rect::new(0.0, 0.0, 50.0, 30.0));
+
+        // Tiles reach under the border on every side
+        let tiles = tile_rects(tile, *repeat, clip);
+        assert_eq!(tiles.len(), 7 * 5);
+        assert_eq!(tiles[0], Rect::new(-6.0, -6.0, 8.0, 8.0));
+
+        // Scaled and placed like the positioned tile, shown once
+        let style = ComputedStyle {
+            background_repeat: BackgroundRepeat::NoRepeat,
+            background_size: BackgroundSize::Contain,
+            ..style
+        };
+        let mut b = LayoutBox::new_block(NodeId(1), &style);
+        b.dimensions.content = Rect::new(0.0, 0.0, 50.0, 30.0);
+        b.background_pixels = Some(pixels);
+        match build_display_list(&b).commands.as_slice() {
+            [PaintCommand::DrawTiledImage { tile, repeat, .. }] => {
+                assert_eq!(*tile, Rect::new(0.0, 0.0, 30.0, 30.0));
+                assert_eq!(tile_rects(tile, *repeat, &Rect::new(0.0, 0.0, 50.0, 30.0)), [*tile]);
+            }
+            commands => panic!("unexpected commands {:?}", commands),
+        }
+    }
+
+    /// Compare a display list with the golden file of a name, rewriting
+    /// the file instead when GUGALANNA_UPDATE_GOLDEN is set
+    fn assert_golden(list: &DisplayList, name: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("golden").join(name);
+        let actual = list.to_json().unwrap() + "\n";
+        if std::env::var_os("GUGALANNA_UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, &actual).unwrap();
+            return;
+        }
+
+        let expected = std::fs::read_to_string(&path).unwrap_or_default();
+        let (expected_lines, actual_lines): (Vec<_>, Vec<_>) = (expected.lines().collect(), actual.lines().collect());
+        let differs = (0..expected_lines.len().max(actual_lines.len()))
+            .find(|&i| expected_lines.get(i) != actual_lines.get(i));
+        if let Some(line) = differs {
+            let context = |lines: &[&str]| lines[line.saturating_sub(3)..(line + 4).min(lines.len())].join("\n");
+            panic!(
+                "{} differs from line {}\n--- expected\n{}\n--- actual\n{}\n\
+                 rerun with GUGALANNA_UPDATE_GOLDEN=1 to accept the new list",
+                path.display(),
+                line + 1,
+                context(&expected_lines),
+                context(&actual_lines),
+            );
+        }
+    }
+
+    #[test]
+    fn test_demo_page_golden() {
+        use gugalanna_css::Stylesheet;
+        use gugalanna_dom::Queryable;
+        use gugalanna_html::HtmlParser;
+        use gugalanna_layout::{build_layout_tree, layout_document, ContainingBlock};
+        use gugalanna_style::{Cascade, StyleTree};
+
+        // The page of --demo in its 800x600 window
+        let dom = HtmlParser::new().parse(include_str!("../../../test-pages/demo.html")).unwrap();
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(Stylesheet::parse(include_str!("../../../test-pages/demo.css")).unwrap());
+        let style_tree = StyleTree::build(&dom, &cascade, 800.0, 600.0);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(800.0, 600.0));
+
+        let list = build_display_list(&layout);
+        assert_golden(&list, "demo.json");
+
+        // Dumps read back as the same commands
+        assert_eq!(DisplayList::from_json(&list.to_json().unwrap()).unwrap(), list);
+    }
 }
@@ -3,23 +3,99 @@
 //! Text rendering using fontdue.
 
 use fontdue::{Font, FontSettings};
+use gugalanna_style::{ComputedStyle, FontStyle};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::font_fallback::{FallbackFonts, FALLBACK_FONT_PATHS};
+use crate::font_registry::FontRegistry;
 
 /// Default embedded font (DejaVu Sans Mono subset or similar)
 /// For now, we'll use a built-in font from the system or embed one.
 const DEFAULT_FONT_DATA: &[u8] = include_bytes!("fonts/DejaVuSans.ttf");
 
+/// Where the bold and oblique faces of the default font are usually installed
+const SYSTEM_FACES: &[(FontFace, &str)] = &[
+    (FontFace::BOLD, "/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf"),
+    (FontFace::ITALIC, "/usr/share/fonts/truetype/dejavu/DejaVuSans-Oblique.ttf"),
+    (FontFace::BOLD_ITALIC, "/usr/share/fonts/truetype/dejavu/DejaVuSans-BoldOblique.ttf"),
+];
+
+/// Horizontal shift per pixel above the baseline of synthetic oblique glyphs
+pub const OBLIQUE_SLANT: f32 = 0.2;
+
+/// Bytes of glyph bitmaps kept cached, least recently used glyphs are
+/// evicted past it
+const GLYPH_CACHE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Weight and slant of the face text is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct FontFace {
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl FontFace {
+    pub const REGULAR: FontFace = FontFace { bold: false, italic: false };
+    pub const BOLD: FontFace = FontFace { bold: true, italic: false };
+    pub const ITALIC: FontFace = FontFace { bold: false, italic: true };
+    pub const BOLD_ITALIC: FontFace = FontFace { bold: true, italic: true };
+
+    /// Face of a weight and slant, bold from a weight of 600
+    pub fn new(weight: u16, style: FontStyle) -> Self {
+        FontFace {
+            bold: weight >= 600,
+            italic: style != FontStyle::Normal,
+        }
+    }
+
+    /// Face for text in a style
+    pub fn for_style(style: &ComputedStyle) -> Self {
+        Self::new(style.font_weight, style.font_style)
+    }
+
+    /// Faces text in this face can be drawn with, closest first: weight is
+    /// kept over slant
+    pub fn fallbacks(self) -> [FontFace; 4] {
+        [self, FontFace { italic: false, ..self }, FontFace { bold: false, ..self }, FontFace::REGULAR]
+    }
+}
+
 /// Cache for rendered glyphs
 pub struct FontCache {
-    font: Font,
-    glyph_cache: HashMap<GlyphKey, GlyphData>,
+    /// Loaded faces, the regular face is always among them
+    faces: HashMap<FontFace, Rc<Font>>,
+    /// Web fonts, drawn for text in their families
+    registry: FontRegistry,
+    /// Fonts drawing the characters the font of a run lacks
+    fallbacks: FallbackFonts,
+    glyph_cache: HashMap<GlyphKey, CachedGlyph>,
+    /// Bytes of the cached glyph bitmaps
+    cache_bytes: usize,
+    /// Bytes of glyph bitmaps to keep at most
+    cache_limit: usize,
+    /// Count of glyph lookups, which orders them by use
+    clock: u64,
 }
 
 /// Key for cached glyphs
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct GlyphKey {
-    character: char,
+    /// Index of the glyph in its font
+    glyph: u16,
     size_tenths: u32, // Font size * 10 to avoid float hashing
+    /// Web font family the glyph is from, lowercase
+    family: Option<String>,
+    face: FontFace,
+    /// Fallback font the glyph is from, instead of the family and face
+    fallback: Option<usize>,
+}
+
+/// A cached glyph with the lookup it was last used by
+struct CachedGlyph {
+    glyph: Rc<GlyphData>,
+    last_used: u64,
 }
 
 /// Cached glyph bitmap data
@@ -40,8 +116,13 @@ impl FontCache {
             .expect("Failed to load default font");
 
         Self {
-            font,
+            faces: HashMap::from([(FontFace::REGULAR, Rc::new(font))]),
+            registry: FontRegistry::new(),
+            fallbacks: FallbackFonts::new(),
             glyph_cache: HashMap::new(),
+            cache_bytes: 0,
+            cache_limit: GLYPH_CACHE_BYTES,
+            clock: 0,
         }
     }
 
@@ -51,46 +132,212 @@ impl FontCache {
             .map_err(|_| "Failed to parse font data")?;
 
         Ok(Self {
-            font,
+            faces: HashMap::from([(FontFace::REGULAR, Rc::new(font))]),
+            registry: FontRegistry::new(),
+            fallbacks: FallbackFonts::new(),
             glyph_cache: HashMap::new(),
+            cache_bytes: 0,
+            cache_limit: GLYPH_CACHE_BYTES,
+            clock: 0,
         })
     }
 
+    /// Load the bold or italic face of the font from font data
+    pub fn load_face(&mut self, face: FontFace, data: &[u8]) -> Result<(), &'static str> {
+        let font = Font::from_bytes(data, FontSettings::default())
+            .map_err(|_| "Failed to parse font data")?;
+        self.faces.insert(face, Rc::new(font));
+        self.registry.set_default_fonts(self.faces.clone(), self.fallbacks.clone());
+        self.glyph_cache.clear();
+        self.cache_bytes = 0;
+        Ok(())
+    }
+
+    /// Draw text in the families of a registry with its fonts, text in
+    /// other families is measured with the faces of this cache
+    pub fn set_registry(&mut self, registry: FontRegistry) {
+        registry.set_default_fonts(self.faces.clone(), self.fallbacks.clone());
+        self.registry = registry;
+    }
+
+    /// Load the faces of the default font installed on the system, faces
+    /// that aren't found are synthesized when drawn, and fall back to the
+    /// system's CJK and emoji fonts
+    pub fn load_system_faces(&mut self) {
+        for &(face, path) in SYSTEM_FACES {
+            if let Ok(data) = std::fs::read(path) {
+                let _ = self.load_face(face, &data);
+            }
+        }
+        self.fallbacks.add_paths(FALLBACK_FONT_PATHS.iter().copied());
+    }
+
+    /// Fonts searched for characters the font of a run lacks, shared with
+    /// the registry's measurer
+    pub fn fallback_fonts(&self) -> &FallbackFonts {
+        &self.fallbacks
+    }
+
+    /// Closest loaded face to the one asked for, keeping its weight over
+    /// its slant
+    pub fn loaded_face(&self, face: FontFace) -> FontFace {
+        face.fallbacks().into_iter().find(|f| self.faces.contains_key(f)).unwrap_or(FontFace::REGULAR)
+    }
+
+    /// Parts of a face that have no loaded face and are drawn synthetically:
+    /// bold by drawing glyphs twice, italic by shearing them
+    pub fn synthesized(&self, face: FontFace) -> FontFace {
+        self.synthesized_in(None, face)
+    }
+
+    /// Parts of a face synthesized for text in the given families
+    pub fn synthesized_in(&self, families: Option<&str>, face: FontFace) -> FontFace {
+        let loaded = match families.and_then(|f| self.registry.find(f, face)) {
+            Some(web) => web.face,
+            None => self.loaded_face(face),
+        };
+        FontFace {
+            bold: face.bold && !loaded.bold,
+            italic: face.italic && !loaded.italic,
+        }
+    }
+
     /// Rasterize a character at a given size
-    pub fn rasterize(&mut self, c: char, size: f32) -> &GlyphData {
+    pub fn rasterize(&mut self, c: char, size: f32) -> Rc<GlyphData> {
+        self.rasterize_face(c, size, FontFace::REGULAR)
+    }
+
+    /// Rasterize a character at a given size in the closest loaded face
+    pub fn rasterize_face(&mut self, c: char, size: f32, face: FontFace) -> Rc<GlyphData> {
+        self.rasterize_in(c, size, None, face)
+    }
+
+    /// Rasterize a character in the first web font family among `families`
+    /// that is loaded, or in the default font
+    pub fn rasterize_in(&mut self, c: char, size: f32, families: Option<&str>, face: FontFace) -> Rc<GlyphData> {
+        let (glyphs, _) = self.shape_in(c.encode_utf8(&mut [0; 4]), size, families, face);
+        glyphs.into_iter().next().map(|(_, glyph)| glyph).expect("One glyph per character")
+    }
+
+    /// Glyphs of a run of text at their pen positions from the start of
+    /// the run, and the width of the run
+    ///
+    /// Laid out as [`layout_glyphs`] measures them, so text is drawn as
+    /// wide as it was measured.
+    pub fn shape_in(
+        &mut self,
+        text: &str,
+        size: f32,
+        families: Option<&str>,
+        face: FontFace,
+    ) -> (Vec<(f32, Rc<GlyphData>)>, f32) {
+        let (family, face, font) = self.font_in(families, face);
+        let (placed, width) = layout_glyphs(&font, &self.fallbacks, text, size);
+        let glyphs = placed
+            .into_iter()
+            .map(|placed| {
+                let glyph = match placed.fallback.and_then(|slot| self.fallbacks.font(slot)) {
+                    Some(fallback) => {
+                        self.glyph(placed.index, size, (None, FontFace::REGULAR, placed.fallback), &fallback)
+                    }
+                    None => self.glyph(placed.index, size, (family.clone(), face, None), &font),
+                };
+                (placed.x, glyph)
+            })
+            .collect();
+        (glyphs, width)
+    }
+
+    /// Family, face and font text in the given families is drawn with
+    fn font_in(&self, families: Option<&str>, face: FontFace) -> (Option<String>, FontFace, Rc<Font>) {
+        match families.and_then(|f| self.registry.find(f, face)) {
+            Some(web) => (Some(web.family), web.face, web.font),
+            None => {
+                let face = self.loaded_face(face);
+                (None, face, self.faces[&face].clone())
+            }
+        }
+    }
+
+    /// A glyph of a font from the cache, rasterized on first use
+    ///
+    /// The font is told apart by its family, face and fallback.
+    fn glyph(
+        &mut self,
+        index: u16,
+        size: f32,
+        (family, face, fallback): (Option<String>, FontFace, Option<usize>),
+        font: &Font,
+    ) -> Rc<GlyphData> {
+        self.clock += 1;
         let key = GlyphKey {
-            character: c,
+            glyph: index,
             size_tenths: (size * 10.0) as u32,
+            family,
+            face,
+            fallback,
         };
+        if let Some(cached) = self.glyph_cache.get_mut(&key) {
+            cached.last_used = self.clock;
+            return cached.glyph.clone();
+        }
 
-        if !self.glyph_cache.contains_key(&key) {
-            let (metrics, bitmap) = self.font.rasterize(c, size);
-
-            let glyph = GlyphData {
-                width: metrics.width as u32,
-                height: metrics.height as u32,
-                bitmap,
-                advance_width: metrics.advance_width,
-                offset_x: metrics.xmin,
-                offset_y: metrics.ymin,
-            };
-
-            self.glyph_cache.insert(key, glyph);
+        let (metrics, bitmap) = font.rasterize_indexed(index, size);
+        let glyph = Rc::new(GlyphData {
+            width: metrics.width as u32,
+            height: metrics.height as u32,
+            bitmap,
+            advance_width: metrics.advance_width,
+            offset_x: metrics.xmin,
+            offset_y: metrics.ymin,
+        });
+        self.cache_bytes += glyph.bitmap.len();
+        self.glyph_cache.insert(key, CachedGlyph { glyph: glyph.clone(), last_used: self.clock });
+        if self.cache_bytes > self.cache_limit {
+            self.evict_glyphs();
         }
+        glyph
+    }
 
-        self.glyph_cache.get(&key).unwrap()
+    /// Drop the least recently used glyphs until the cache is down to
+    /// three quarters of its limit, so it isn't done on every new glyph
+    fn evict_glyphs(&mut self) {
+        let mut by_use: Vec<_> = self.glyph_cache.iter().map(|(key, cached)| (cached.last_used, key.clone())).collect();
+        by_use.sort_unstable_by_key(|(last_used, _)| *last_used);
+        for (_, key) in by_use {
+            if self.cache_bytes <= self.cache_limit * 3 / 4 {
+                break;
+            }
+            if let Some(cached) = self.glyph_cache.remove(&key) {
+                self.cache_bytes -= cached.glyph.bitmap.len();
+            }
+        }
     }
 
     /// Measure the width of a string
     pub fn measure_text(&mut self, text: &str, size: f32) -> f32 {
-        text.chars()
-            .map(|c| self.rasterize(c, size).advance_width)
-            .sum()
+        self.measure_text_face(text, size, FontFace::REGULAR)
+    }
+
+    /// Measure the width of a string drawn in a face, synthetic faces
+    /// advance like the face they are drawn from
+    pub fn measure_text_face(&mut self, text: &str, size: f32, face: FontFace) -> f32 {
+        let (_, _, font) = self.font_in(None, face);
+        layout_glyphs(&font, &self.fallbacks, text, size).1
+    }
+
+    /// Get the ascent of the font text in the given families is drawn with
+    pub fn ascent_in(&self, families: Option<&str>, size: f32) -> f32 {
+        let web = families.and_then(|f| self.registry.find(f, FontFace::REGULAR));
+        match web.and_then(|web| web.font.horizontal_line_metrics(size)) {
+            Some(m) => m.ascent,
+            None => self.ascent(size),
+        }
     }
 
     /// Get line metrics for a font size
     pub fn line_height(&self, size: f32) -> f32 {
-        let metrics = self.font.horizontal_line_metrics(size);
+        let metrics = self.faces[&FontFace::REGULAR].horizontal_line_metrics(size);
         match metrics {
             Some(m) => m.new_line_size,
             None => size * 1.2,
@@ -99,7 +346,7 @@ impl FontCache {
 
     /// Get the ascent for a font size
     pub fn ascent(&self, size: f32) -> f32 {
-        let metrics = self.font.horizontal_line_metrics(size);
+        let metrics = self.faces[&FontFace::REGULAR].horizontal_line_metrics(size);
         match metrics {
             Some(m) => m.ascent,
             None => size * 0.8,
@@ -107,6 +354,70 @@ impl FontCache {
     }
 }
 
+/// A glyph of a run of text laid out by [`layout_glyphs`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlacedGlyph {
+    /// Fallback font the glyph is from, None for the font of the run
+    pub fallback: Option<usize>,
+    /// Index of the glyph in its font
+    pub index: u16,
+    /// Pen position from the start of the run
+    pub x: f32,
+}
+
+/// Lay out a run of text in a font: the glyph of each character with its
+/// pen position from the start of the run, and the width of the run
+///
+/// Characters the font lacks are taken from the first fallback font that
+/// has them, splitting the run into segments per font. Advances are
+/// adjusted by the kerning between pairs of glyphs of a segment. Text is
+/// measured and drawn from this same layout.
+pub fn layout_glyphs(font: &Rc<Font>, fallbacks: &FallbackFonts, text: &str, size: f32) -> (Vec<PlacedGlyph>, f32) {
+    let mut glyphs = Vec::with_capacity(text.len());
+    let mut pen = 0.0;
+    let mut previous: Option<PlacedGlyph> = None;
+    for c in text.chars() {
+        let mut index = font.lookup_glyph_index(c);
+        let mut glyph_font = font.clone();
+        let mut fallback = None;
+        if index == 0 && !c.is_control() {
+            if let Some((slot, font)) = fallbacks.font_for(c) {
+                index = font.lookup_glyph_index(c);
+                glyph_font = font;
+                fallback = Some(slot);
+            }
+        }
+
+        if let Some(previous) = previous.filter(|p| p.fallback == fallback) {
+            pen += glyph_font.horizontal_kern_indexed(previous.index, index, size).unwrap_or(0.0);
+        }
+        let placed = PlacedGlyph { fallback, index, x: pen };
+        glyphs.push(placed);
+        pen += glyph_font.metrics_indexed(index, size).advance_width;
+        previous = Some(placed);
+    }
+    (glyphs, pen)
+}
+
+/// Shear a glyph bitmap to the right above its baseline, for oblique text
+/// without an oblique face
+///
+/// `baseline` is the number of rows of the glyph above its baseline.
+/// Returns the sheared bitmap, its width and how far its left edge moved.
+pub fn shear_bitmap(bitmap: &[u8], width: u32, height: u32, baseline: i32) -> (Vec<u8>, u32, i32) {
+    let shift = |row: u32| ((baseline - row as i32) as f32 * OBLIQUE_SLANT).round() as i32;
+    let (left, right) = (shift(height.saturating_sub(1)).min(0), shift(0).max(0));
+    let sheared_width = width + (right - left) as u32;
+
+    let mut sheared = vec![0; (sheared_width * height) as usize];
+    for row in 0..height {
+        let start = (row * sheared_width) as i32 + shift(row) - left;
+        let source = &bitmap[(row * width) as usize..((row + 1) * width) as usize];
+        sheared[start as usize..start as usize + width as usize].copy_from_slice(source);
+    }
+    (sheared, sheared_width, left)
+}
+
 impl Default for FontCache {
     fn default() -> Self {
         Self::new()
@@ -116,6 +427,7 @@ impl Default for FontCache {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::font_fallback::has_glyph;
 
     #[test]
     fn test_font_cache_creation() {
@@ -139,6 +451,30 @@ mod tests {
         assert!(width > 0.0);
     }
 
+    #[test]
+    fn test_missing_faces_are_synthesized() {
+        let mut cache = FontCache::new();
+        assert_eq!(cache.loaded_face(FontFace::BOLD_ITALIC), FontFace::REGULAR);
+        assert_eq!(cache.synthesized(FontFace::BOLD_ITALIC), FontFace::BOLD_ITALIC);
+
+        // With a bold face, only the slant is synthesized
+        cache.load_face(FontFace::BOLD, DEFAULT_FONT_DATA).unwrap();
+        assert_eq!(cache.loaded_face(FontFace::BOLD_ITALIC), FontFace::BOLD);
+        assert_eq!(cache.synthesized(FontFace::BOLD_ITALIC), FontFace::ITALIC);
+        assert_eq!(cache.synthesized(FontFace::BOLD), FontFace::REGULAR);
+    }
+
+    #[test]
+    fn test_shear_bitmap() {
+        // A 1px wide bar, 10 rows above the baseline and 5 below
+        let (sheared, width, left) = shear_bitmap(&[255; 15], 1, 15, 10);
+        assert_eq!((width, left), (4, -1));
+
+        // The top row moves right by 2px, the bottom row left by 1px
+        assert_eq!(sheared[..4], [0, 0, 0, 255]);
+        assert_eq!(sheared[14 * 4..], [255, 0, 0, 0]);
+    }
+
     #[test]
     fn test_glyph_caching() {
         let mut cache = FontCache::new();
@@ -155,4 +491,141 @@ mod tests {
         cache.rasterize('X', 24.0);
         assert_eq!(cache.glyph_cache.len(), 2);
     }
+
+    #[test]
+    fn test_kerning_is_measured_and_drawn() {
+        let mut cache = FontCache::new();
+        let font = cache.faces[&FontFace::REGULAR].clone();
+        let unkerned = font.metrics('A', 16.0).advance_width + font.metrics('V', 16.0).advance_width;
+
+        // "AV" is kerned tighter than its advances
+        let (glyphs, width) = layout_glyphs(&font, &FallbackFonts::new(), "AV", 16.0);
+        assert!(width < unkerned, "{} >= {}", width, unkerned);
+        assert_eq!(cache.measure_text("AV", 16.0), width);
+
+        let (drawn, drawn_width) = cache.shape_in("AV", 16.0, None, FontFace::REGULAR);
+        assert_eq!(drawn_width, width);
+        assert_eq!(drawn[1].0, glyphs[1].x);
+    }
+
+    #[test]
+    fn test_glyph_cache_evicts_least_recently_used() {
+        let mut cache = FontCache::new();
+        let a = cache.rasterize('A', 40.0).bitmap.len();
+        cache.cache_limit = a * 3;
+
+        cache.rasterize('B', 40.0);
+        cache.rasterize('A', 40.0);
+        cache.rasterize('C', 40.0);
+        cache.rasterize('D', 40.0);
+
+        // Past the limit, glyphs unused the longest go first
+        assert!(cache.cache_bytes <= cache.cache_limit);
+        let cached: Vec<u16> = cache.glyph_cache.keys().map(|key| key.glyph).collect();
+        let index = |c| cache.faces[&FontFace::REGULAR].lookup_glyph_index(c);
+        assert!(!cached.contains(&index('B')));
+        assert!(cached.contains(&index('D')));
+    }
+
+    /// TrueType font whose glyphs for `chars` are squares, 1000 units per em
+    fn square_font(chars: &[char]) -> Vec<u8> {
+        let be16 = |v: i32| (v as u16).to_be_bytes();
+        let glyph_count = chars.len() as i32 + 1;
+
+        // Glyph 0 is an empty .notdef, each square is a contour of 4 points
+        let mut glyf = Vec::new();
+        let mut loca = vec![be16(0), be16(0)];
+        for _ in chars {
+            for v in [1, 100, 0, 900, 800, 3, 0] {
+                glyf.extend(be16(v)); // contours, bounds, end point, no instructions
+            }
+            glyf.extend([1u8; 4]); // on-curve points with 16-bit coordinates
+            for v in [100, 800, 0, -800, 0, 800, 0, -800] {
+                glyf.extend(be16(v)); // x deltas then y deltas
+            }
+            loca.push(be16(glyf.len() as i32 / 2));
+        }
+
+        // One Windows Unicode subtable of format 12, a group per character
+        let mut cmap: Vec<u8> = [0, 1, 3, 10].into_iter().flat_map(be16).collect();
+        cmap.extend(12u32.to_be_bytes());
+        let groups: Vec<u8> = chars
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &c)| [c as u32, c as u32, i as u32 + 1].into_iter().flat_map(u32::to_be_bytes))
+            .collect();
+        cmap.extend(be16(12).into_iter().chain(be16(0)));
+        cmap.extend((16 + groups.len() as u32).to_be_bytes());
+        cmap.extend(0u32.to_be_bytes());
+        cmap.extend((chars.len() as u32).to_be_bytes());
+        cmap.extend(groups);
+
+        let mut head: Vec<u8> = Vec::new();
+        head.extend(0x0001_0000u32.to_be_bytes());
+        head.extend([0; 8]); // revision and checksum adjustment
+        head.extend(0x5F0F_3CF5u32.to_be_bytes());
+        head.extend([0, 0].into_iter().chain(be16(1000)));
+        head.extend([0; 16]); // created and modified
+        for v in [0, -200, 1000, 800, 0, 8, 2, 0, 0] {
+            head.extend(be16(v)); // bounds, style, smallest size, direction, short loca, glyph format
+        }
+        let mut hhea: Vec<u8> = 0x0001_0000u32.to_be_bytes().to_vec();
+        for v in [800, -200, 0, 1000, 0, 0, 900, 1, 0, 0, 0, 0, 0, 0, 0, glyph_count] {
+            hhea.extend(be16(v));
+        }
+        let mut maxp = 0x0000_5000u32.to_be_bytes().to_vec();
+        maxp.extend(be16(glyph_count));
+        let hmtx: Vec<u8> = (0..glyph_count).flat_map(|_| [be16(1000), be16(100)].concat()).collect();
+        let loca: Vec<u8> = loca.concat();
+
+        // Tables in tag order, each at an offset that is a multiple of 4
+        let tables = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ];
+        let mut font = 0x0001_0000u32.to_be_bytes().to_vec();
+        font.extend([tables.len() as i32, 64, 2, 48].into_iter().flat_map(be16));
+        let mut offset = 12 + 16 * tables.len();
+        let mut data = Vec::new();
+        for (tag, table) in &tables {
+            font.extend(*tag);
+            font.extend([0; 4]);
+            font.extend((offset as u32).to_be_bytes());
+            font.extend((table.len() as u32).to_be_bytes());
+            let padded = table.len().div_ceil(4) * 4;
+            data.extend(table);
+            data.resize(data.len() + padded - table.len(), 0);
+            offset += padded;
+        }
+        font.extend(data);
+        font
+    }
+
+    #[test]
+    fn test_missing_characters_fall_back() {
+        let mut cache = FontCache::new();
+        let text = "h\u{e9}llo \u{4e16}\u{754c} \u{1f389}";
+        let font = cache.faces[&FontFace::REGULAR].clone();
+        assert!(!has_glyph(&font, '\u{4e16}'));
+
+        cache.fallback_fonts().add_paths(["/nonexistent/font.ttf"]);
+        cache.fallback_fonts().add_font(&square_font(&['\u{4e16}', '\u{754c}', '\u{1f389}'])).unwrap();
+
+        // Every character is drawn from a font that has it, none as .notdef
+        let (placed, width) = layout_glyphs(&font, cache.fallback_fonts(), text, 20.0);
+        let fallbacks: Vec<_> = placed.iter().map(|glyph| glyph.fallback).collect();
+        assert_eq!(fallbacks, [None, None, None, None, None, None, Some(1), Some(1), None, Some(1)]);
+        assert!(placed.iter().all(|glyph| glyph.index != 0));
+
+        // The squares are drawn an em apart, and measured as drawn
+        assert_eq!(placed[7].x - placed[6].x, 20.0);
+        let (glyphs, drawn_width) = cache.shape_in(text, 20.0, None, FontFace::REGULAR);
+        assert_eq!(drawn_width, width);
+        assert!(glyphs[6].1.width > 0);
+    }
 }
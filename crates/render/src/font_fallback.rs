@@ -0,0 +1,113 @@
+//! Fallback Fonts
+//!
+//! Fonts searched, in order, for characters the font of a run lacks, such
+//! as CJK or emoji in Latin text. Fonts given by path are only read when a
+//! character first needs them. The list is shared by the font cache, which
+//! draws with it, and by the registry, which measures with it. Fonts are
+//! only ever added, so a font keeps its position in the list.
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use fontdue::{Font, FontSettings};
+
+/// CJK, symbol and emoji fonts commonly installed on Linux, macOS and
+/// Windows
+///
+/// Only fonts with glyph outlines are listed: color emoji fonts made of
+/// bitmaps (CBDT, sbix) can't be rasterized, emoji are drawn in one color.
+pub const FALLBACK_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/truetype/noto/NotoSansSymbols2-Regular.ttf",
+    "/usr/share/fonts/truetype/noto/NotoEmoji-Regular.ttf",
+    "/usr/share/fonts/noto/NotoEmoji-Regular.ttf",
+    "/usr/share/fonts/truetype/ancient-scripts/Symbola_hint.ttf",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Apple Symbols.ttf",
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\seguisym.ttf",
+    "C:\\Windows\\Fonts\\seguiemj.ttf",
+];
+
+/// A fallback font, read on first use
+enum Fallback {
+    Path(PathBuf),
+    Loaded(Rc<Font>),
+    /// The file is missing or isn't a font
+    Unusable,
+}
+
+/// Shared, ordered list of fallback fonts
+#[derive(Clone, Default)]
+pub struct FallbackFonts {
+    fonts: Rc<RefCell<Vec<Fallback>>>,
+}
+
+impl FallbackFonts {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add fonts to search by path, after the ones already listed
+    pub fn add_paths<P: Into<PathBuf>>(&self, paths: impl IntoIterator<Item = P>) {
+        let mut fonts = self.fonts.borrow_mut();
+        fonts.extend(paths.into_iter().map(|path| Fallback::Path(path.into())));
+    }
+
+    /// Parse font data and add it after the fonts already listed
+    pub fn add_font(&self, data: &[u8]) -> Result<(), &'static str> {
+        let font = Font::from_bytes(data, FontSettings::default()).map_err(|_| "Failed to parse font data")?;
+        self.fonts.borrow_mut().push(Fallback::Loaded(Rc::new(font)));
+        Ok(())
+    }
+
+    /// First font of the list that can draw a character, with its position
+    /// in the list
+    pub fn font_for(&self, c: char) -> Option<(usize, Rc<Font>)> {
+        let mut fonts = self.fonts.borrow_mut();
+        for (slot, fallback) in fonts.iter_mut().enumerate() {
+            if let Fallback::Path(path) = fallback {
+                *fallback = match std::fs::read(&*path) {
+                    Ok(data) => match Font::from_bytes(data, FontSettings::default()) {
+                        Ok(font) => {
+                            log::debug!("Loaded fallback font {}", path.display());
+                            Fallback::Loaded(Rc::new(font))
+                        }
+                        Err(e) => {
+                            log::warn!("Failed to parse fallback font {}: {}", path.display(), e);
+                            Fallback::Unusable
+                        }
+                    },
+                    Err(_) => Fallback::Unusable,
+                };
+            }
+            if let Fallback::Loaded(font) = fallback {
+                if has_glyph(font, c) {
+                    return Some((slot, font.clone()));
+                }
+            }
+        }
+        None
+    }
+
+    /// Font at a position of the list, if it is loaded
+    pub fn font(&self, slot: usize) -> Option<Rc<Font>> {
+        match self.fonts.borrow().get(slot) {
+            Some(Fallback::Loaded(font)) => Some(font.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Check if a font can draw a character: it maps it to a glyph with an
+/// outline, or to any glyph for spaces
+pub fn has_glyph(font: &Font, c: char) -> bool {
+    let index = font.lookup_glyph_index(c);
+    index != 0 && (c.is_whitespace() || font.metrics_indexed(index, 16.0).width > 0)
+}
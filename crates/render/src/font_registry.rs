@@ -0,0 +1,147 @@
+//! Web Font Registry
+//!
+//! Fonts downloaded for @font-face rules, by family and face. The registry
+//! is shared by the backend, which draws text with them, and by layout,
+//! which measures text with the same fonts. Text in other families is
+//! measured with the backend's default faces.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use fontdue::{Font, FontSettings};
+use gugalanna_layout::{SimpleTextMeasurer, TextMeasurer, TextMetrics};
+use gugalanna_style::ComputedStyle;
+
+use crate::font::{layout_glyphs, FontFace};
+use crate::font_fallback::FallbackFonts;
+
+/// Fonts by lowercase family name and face
+type FontMap = HashMap<(String, FontFace), Rc<Font>>;
+
+/// Shared handle to the registered web fonts
+#[derive(Clone, Default)]
+pub struct FontRegistry {
+    fonts: Rc<RefCell<FontMap>>,
+    /// Faces of the font text in other families is drawn with
+    default_faces: Rc<RefCell<HashMap<FontFace, Rc<Font>>>>,
+    /// Fonts characters missing from a font are drawn with
+    fallbacks: Rc<RefCell<FallbackFonts>>,
+}
+
+/// A registered font picked for a run of text
+pub struct RegisteredFont {
+    /// Family name, lowercase
+    pub family: String,
+    /// Face of the family that was loaded, closest to the one asked for
+    pub face: FontFace,
+    pub font: Rc<Font>,
+}
+
+impl FontRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse TrueType or OpenType font data and register it as a face of
+    /// a family
+    pub fn register(&self, family: &str, face: FontFace, data: &[u8]) -> Result<(), &'static str> {
+        let font = Font::from_bytes(data, FontSettings::default()).map_err(|_| "Unsupported font data")?;
+        self.fonts.borrow_mut().insert((family.to_lowercase(), face), Rc::new(font));
+        Ok(())
+    }
+
+    /// Set the faces text outside of the registered families is drawn
+    /// with, and the fonts characters missing from a font are drawn with
+    pub(crate) fn set_default_fonts(&self, faces: HashMap<FontFace, Rc<Font>>, fallbacks: FallbackFonts) {
+        *self.default_faces.borrow_mut() = faces;
+        *self.fallbacks.borrow_mut() = fallbacks;
+    }
+
+    /// Check if a face of a family has been registered
+    pub fn contains(&self, family: &str, face: FontFace) -> bool {
+        self.fonts.borrow().contains_key(&(family.to_lowercase(), face))
+    }
+
+    /// Font of the first registered family among `families`, separated by
+    /// commas, in its face closest to `face`
+    pub fn find(&self, families: &str, face: FontFace) -> Option<RegisteredFont> {
+        let fonts = self.fonts.borrow();
+        families.split(',').map(|f| f.trim().to_lowercase()).find_map(|family| {
+            face.fallbacks().into_iter().find_map(|candidate| {
+                let font = fonts.get(&(family.clone(), candidate))?;
+                Some(RegisteredFont { family: family.clone(), face: candidate, font: font.clone() })
+            })
+        })
+    }
+}
+
+impl TextMeasurer for FontRegistry {
+    /// Measure text with the font it will be drawn with, estimated when no
+    /// font is known for it
+    fn measure(&self, text: &str, style: &ComputedStyle) -> TextMetrics {
+        let estimate = SimpleTextMeasurer.measure(text, style);
+        let face = FontFace::for_style(style);
+        let font = match self.find(&style.font_family, face) {
+            Some(registered) => registered.font,
+            None => {
+                let faces = self.default_faces.borrow();
+                match face.fallbacks().into_iter().find_map(|f| faces.get(&f).cloned()) {
+                    Some(font) => font,
+                    None => return estimate,
+                }
+            }
+        };
+
+        let size = style.font_size;
+        let width = layout_glyphs(&font, &self.fallbacks.borrow(), text, size).1;
+        match font.horizontal_line_metrics(size) {
+            Some(line) => TextMetrics::new(width, style.line_height, line.ascent, -line.descent),
+            None => TextMetrics { width, ..estimate },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT_DATA: &[u8] = include_bytes!("fonts/DejaVuSans.ttf");
+
+    #[test]
+    fn test_registered_family_is_measured() {
+        let registry = FontRegistry::new();
+        let style = ComputedStyle {
+            font_family: "Web Sans, sans-serif".to_string(),
+            font_weight: 700,
+            ..ComputedStyle::default()
+        };
+        let estimate = SimpleTextMeasurer.measure("Hello", &style).width;
+        assert_eq!(registry.measure("Hello", &style).width, estimate);
+
+        // The regular face stands in for the bold one
+        registry.register("web sans", FontFace::REGULAR, FONT_DATA).unwrap();
+        let found = registry.find(&style.font_family, FontFace::BOLD).unwrap();
+        assert_eq!((found.family.as_str(), found.face), ("web sans", FontFace::REGULAR));
+        assert_ne!(registry.measure("Hello", &style).width, estimate);
+    }
+
+    #[test]
+    fn test_other_families_are_measured_with_default_faces() {
+        let registry = FontRegistry::new();
+        let mut cache = crate::font::FontCache::new();
+        cache.set_registry(registry.clone());
+
+        // Measured as wide as the default font draws it
+        let style = ComputedStyle { font_size: 16.0, ..ComputedStyle::default() };
+        assert_eq!(registry.measure("Wave", &style).width, cache.measure_text("Wave", 16.0));
+    }
+
+    #[test]
+    fn test_unsupported_font_data() {
+        let registry = FontRegistry::new();
+        assert!(registry.register("Web", FontFace::REGULAR, b"wOF2 compressed").is_err());
+        assert!(!registry.contains("Web", FontFace::REGULAR));
+    }
+}
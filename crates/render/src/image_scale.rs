@@ -0,0 +1,299 @@
+//! Image Scaling
+//!
+//! Resamples decoded images to the size they are drawn at. Smooth images
+//! are first halved with a box filter while they are at least twice the
+//! target size, so every source pixel counts in large downscales, then
+//! interpolated bilinearly. Pixelated and crisp images take the nearest
+//! pixel. Colors are filtered premultiplied by their alpha, so transparent
+//! pixels don't darken the edges next to them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use gugalanna_layout::ImagePixels;
+use gugalanna_style::ImageRendering;
+
+/// Bytes of scaled images kept cached, least recently drawn images are
+/// evicted past it
+const SCALED_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Scale an image to a size with the filter for a rendering mode
+pub fn scale_image(img: &ImagePixels, width: u32, height: u32, rendering: ImageRendering) -> ImagePixels {
+    if (img.width, img.height) == (width, height) {
+        return img.clone();
+    }
+    if width == 0 || height == 0 || img.width == 0 || img.height == 0 {
+        return ImagePixels { width, height, data: vec![0; (width * height * 4) as usize].into() };
+    }
+    if img.data.len() < (img.width * img.height * 4) as usize {
+        return img.clone();
+    }
+
+    let data = match rendering {
+        ImageRendering::Auto => {
+            let mut source = Premultiplied::from_image(img);
+            while source.width >= width * 2 && source.height >= height * 2 {
+                source = source.halve();
+            }
+            source.bilinear(width, height).into_rgba()
+        }
+        ImageRendering::CrispEdges | ImageRendering::Pixelated => nearest(img, width, height),
+    };
+    ImagePixels { width, height, data: data.into() }
+}
+
+/// Scale by taking the source pixel under the center of each pixel
+fn nearest(img: &ImagePixels, width: u32, height: u32) -> Vec<u8> {
+    let columns: Vec<usize> = (0..width)
+        .map(|x| ((2 * x as u64 + 1) * img.width as u64 / (2 * width as u64)) as usize)
+        .collect();
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        let sy = ((2 * y as u64 + 1) * img.height as u64 / (2 * height as u64)) as usize;
+        let row = &img.data[sy * img.width as usize * 4..];
+        for &sx in &columns {
+            data.extend_from_slice(&row[sx * 4..sx * 4 + 4]);
+        }
+    }
+    data
+}
+
+/// RGBA pixels with colors multiplied by their alpha
+struct Premultiplied {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Premultiplied {
+    fn from_image(img: &ImagePixels) -> Self {
+        let mut data = img.data[..(img.width * img.height * 4) as usize].to_vec();
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u32;
+            for channel in &mut pixel[..3] {
+                *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+            }
+        }
+        Self { width: img.width, height: img.height, data }
+    }
+
+    fn into_rgba(mut self) -> Vec<u8> {
+        for pixel in self.data.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u32;
+            for channel in &mut pixel[..3] {
+                if let Some(value) = (*channel as u32 * 255 + alpha / 2).checked_div(alpha) {
+                    *channel = value.min(255) as u8;
+                }
+            }
+        }
+        self.data
+    }
+
+    /// Average each 2x2 block into a pixel, an odd last row or column is
+    /// dropped
+    fn halve(&self) -> Self {
+        let (width, height) = (self.width / 2, self.height / 2);
+        let stride = self.width as usize * 4;
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height as usize {
+            let top = &self.data[2 * y * stride..];
+            let bottom = &self.data[(2 * y + 1) * stride..];
+            for x in 0..width as usize {
+                let i = x * 8;
+                for c in 0..4 {
+                    let sum = top[i + c] as u32 + top[i + 4 + c] as u32 + bottom[i + c] as u32 + bottom[i + 4 + c] as u32;
+                    data.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        Self { width, height, data }
+    }
+
+    /// Interpolate between the four source pixels around the center of
+    /// each pixel, clamped at the edges
+    fn bilinear(&self, width: u32, height: u32) -> Self {
+        let columns: Vec<(usize, usize, f32)> = (0..width).map(|x| sample_position(x, width, self.width)).collect();
+        let stride = self.width as usize * 4;
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let (y0, y1, fy) = sample_position(y, height, self.height);
+            let (top, bottom) = (&self.data[y0 * stride..], &self.data[y1 * stride..]);
+            for &(x0, x1, fx) in &columns {
+                for c in 0..4 {
+                    let lerp = |row: &[u8]| row[x0 * 4 + c] as f32 * (1.0 - fx) + row[x1 * 4 + c] as f32 * fx;
+                    let value = lerp(top) * (1.0 - fy) + lerp(bottom) * fy;
+                    data.push(value.round() as u8);
+                }
+            }
+        }
+        Self { width, height, data }
+    }
+}
+
+/// Source pixels either side of the center of pixel `i` of `size`, and how
+/// far it is between them
+fn sample_position(i: u32, size: u32, source_size: u32) -> (usize, usize, f32) {
+    let position = ((i as f32 + 0.5) * source_size as f32 / size as f32 - 0.5).clamp(0.0, (source_size - 1) as f32);
+    let first = position.floor() as usize;
+    let second = (first + 1).min(source_size as usize - 1);
+    (first, second, position - first as f32)
+}
+
+/// Identity of a scaled image: the source pixels, which an image shares
+/// with its copies, and the scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ScaleKey {
+    source: usize,
+    width: u32,
+    height: u32,
+    rendering: ImageRendering,
+}
+
+struct ScaledImage {
+    /// Kept so the source pixels aren't freed and their address reused by
+    /// another image
+    _source: Arc<[u8]>,
+    image: ImagePixels,
+    last_used: u64,
+}
+
+/// Cache of images scaled to the size they are drawn at, so scrolling or
+/// repainting a page doesn't filter its images again
+pub struct ScaledImages {
+    entries: HashMap<ScaleKey, ScaledImage>,
+    bytes: usize,
+    limit: usize,
+    clock: u64,
+}
+
+impl Default for ScaledImages {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScaledImages {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self { entries: HashMap::new(), bytes: 0, limit: SCALED_IMAGE_BYTES, clock: 0 }
+    }
+
+    /// An image scaled to a size, from the cache when it was scaled before
+    pub fn get(&mut self, img: &ImagePixels, width: u32, height: u32, rendering: ImageRendering) -> ImagePixels {
+        if (img.width, img.height) == (width, height) {
+            return img.clone();
+        }
+        self.clock += 1;
+        let key = ScaleKey { source: img.data.as_ptr() as usize, width, height, rendering };
+        if let Some(cached) = self.entries.get_mut(&key) {
+            cached.last_used = self.clock;
+            return cached.image.clone();
+        }
+
+        let image = scale_image(img, width, height, rendering);
+        self.bytes += image.data.len();
+        self.entries.insert(
+            key,
+            ScaledImage { _source: img.data.clone(), image: image.clone(), last_used: self.clock },
+        );
+        if self.bytes > self.limit {
+            self.evict();
+        }
+        image
+    }
+
+    /// Drop the least recently drawn images until the cache is down to
+    /// three quarters of its limit
+    fn evict(&mut self) {
+        let mut by_use: Vec<_> = self.entries.iter().map(|(key, cached)| (cached.last_used, *key)).collect();
+        by_use.sort_unstable_by_key(|(last_used, _)| *last_used);
+        for (_, key) in by_use {
+            if self.bytes <= self.limit * 3 / 4 {
+                break;
+            }
+            if let Some(cached) = self.entries.remove(&key) {
+                self.bytes -= cached.image.data.len();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Black and white checkerboard of squares `cell` pixels wide
+    fn checkerboard(size: u32, cell: u32) -> ImagePixels {
+        let mut data = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let value = if (x / cell + y / cell).is_multiple_of(2) { 0 } else { 255 };
+                data.extend_from_slice(&[value, value, value, 255]);
+            }
+        }
+        ImagePixels { width: size, height: size, data: data.into() }
+    }
+
+    fn gray(img: &ImagePixels, x: u32, y: u32) -> u8 {
+        img.data[((y * img.width + x) * 4) as usize]
+    }
+
+    #[test]
+    fn test_pixelated_upscale_keeps_blocks() {
+        let scaled = scale_image(&checkerboard(2, 1), 8, 8, ImageRendering::Pixelated);
+
+        // Each source pixel becomes a 4x4 block of its color
+        let values: Vec<u8> = (0..8).map(|x| gray(&scaled, x, 0)).collect();
+        assert_eq!(values, [0, 0, 0, 0, 255, 255, 255, 255]);
+        assert_eq!(gray(&scaled, 3, 4), 255);
+    }
+
+    #[test]
+    fn test_smooth_upscale_blends() {
+        let scaled = scale_image(&checkerboard(2, 1), 8, 8, ImageRendering::Auto);
+
+        // Edges are clamped to the source pixels, the middle is in between
+        assert_eq!(gray(&scaled, 0, 0), 0);
+        assert_eq!(gray(&scaled, 7, 0), 255);
+        let middle = gray(&scaled, 3, 0);
+        assert!(middle > 0 && middle < 255, "{}", middle);
+    }
+
+    #[test]
+    fn test_smooth_downscale_averages() {
+        // A one pixel checkerboard averages to gray instead of aliasing to
+        // one of its colors
+        let scaled = scale_image(&checkerboard(64, 1), 8, 8, ImageRendering::Auto);
+        for (x, y) in [(0, 0), (3, 5), (7, 7)] {
+            assert!(gray(&scaled, x, y).abs_diff(128) <= 1, "{}", gray(&scaled, x, y));
+        }
+
+        let scaled = scale_image(&checkerboard(64, 1), 8, 8, ImageRendering::Pixelated);
+        assert!([0, 255].contains(&gray(&scaled, 3, 5)));
+    }
+
+    #[test]
+    fn test_smooth_scaling_ignores_transparent_colors() {
+        // Red next to transparent black stays red where it is visible
+        let img = ImagePixels { width: 2, height: 1, data: vec![255, 0, 0, 255, 0, 0, 0, 0].into() };
+        let scaled = scale_image(&img, 4, 1, ImageRendering::Auto);
+        let pixel = &scaled.data[4..8];
+        assert_eq!(&pixel[..3], [255, 0, 0]);
+        assert!(pixel[3] > 0 && pixel[3] < 255);
+    }
+
+    #[test]
+    fn test_scaled_images_cached_by_source_and_size() {
+        let mut cache = ScaledImages::new();
+        let img = checkerboard(16, 2);
+        let first = cache.get(&img, 8, 8, ImageRendering::Auto);
+        let again = cache.get(&img.clone(), 8, 8, ImageRendering::Auto);
+        assert!(Arc::ptr_eq(&first.data, &again.data));
+
+        let pixelated = cache.get(&img, 8, 8, ImageRendering::Pixelated);
+        assert!(!Arc::ptr_eq(&first.data, &pixelated.data));
+
+        // Drawn at its size, the image is used as is
+        assert!(Arc::ptr_eq(&cache.get(&img, 16, 16, ImageRendering::Auto).data, &img.data));
+    }
+}
@@ -2,15 +2,38 @@
 //!
 //! Painting and display list generation.
 
+mod background;
+mod border;
+mod damage;
 mod display_list;
 mod paint;
+mod painter;
+#[cfg(feature = "sdl")]
 mod sdl_backend;
+mod software_backend;
 mod font;
+mod font_fallback;
+mod font_registry;
+mod image_scale;
+mod shadow;
+mod stacking;
 
-pub use display_list::{DisplayList, PaintCommand, BorderWidths, build_display_list};
+pub use damage::{command_bounds, commands_in_rect, damaged_rect, diff_display_lists, ListDiff};
+pub use display_list::{
+    build_display_list, build_display_list_with_selection, BorderColors, BorderStyles, BorderWidths, DisplayList, PaintCommand,
+    Selection,
+};
 pub use paint::RenderColor;
-pub use sdl_backend::{SdlBackend, CursorType};
-pub use font::{FontCache, GlyphData};
+#[cfg(feature = "sdl")]
+pub use sdl_backend::SdlBackend;
+pub use software_backend::SoftwareBackend;
+pub use font::{layout_glyphs, shear_bitmap, FontCache, FontFace, GlyphData, PlacedGlyph, OBLIQUE_SLANT};
+pub use font_fallback::{has_glyph, FallbackFonts, FALLBACK_FONT_PATHS};
+pub use font_registry::{FontRegistry, RegisteredFont};
+pub use image_scale::{scale_image, ScaledImages};
+pub use stacking::{box_transform, boxes_in_paint_order, scroll_container_id, transform_rect, PaintedBox};
+
+use gugalanna_layout::Rect;
 
 /// Trait for render backends
 pub trait RenderBackend {
@@ -28,4 +51,21 @@ pub trait RenderBackend {
 
     /// Get the window height
     fn height(&self) -> u32;
+
+    /// Paint the commands of a frame over the part of the previous frame
+    /// inside `damage`, clearing it to `background` first
+    fn repaint(&mut self, display_list: &DisplayList, damage: Rect, background: RenderColor);
+
+    /// Set the mouse cursor type, backends without a pointer ignore it
+    fn set_cursor(&mut self, _cursor_type: CursorType) {}
+
+    /// Get mutable access to font cache
+    fn font_cache_mut(&mut self) -> &mut FontCache;
+}
+
+/// Cursor type for link hover
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorType {
+    Arrow,
+    Hand,
 }
@@ -3,9 +3,10 @@
 //! Basic types for rendering.
 
 use gugalanna_css::Color;
+use serde::{Deserialize, Serialize};
 
 /// Color for rendering (RGBA)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RenderColor {
     pub r: u8,
     pub g: u8,
@@ -0,0 +1,1408 @@
+//! Painter
+//!
+//! Draws paint commands with a few primitives that each backend provides,
+//! so the window and offscreen backends paint alike.
+
+use gugalanna_layout::{text_area_lines, ImagePixels, Rect};
+use gugalanna_style::{
+    BackgroundRepeat, BorderRadius, BoxShadow, ColorStop, GradientDirection, ImageRendering, RadialShape, RadialSize, Transform,
+};
+
+use crate::background::tile_rects;
+use crate::damage::{command_bounds, commands_in_rect};
+use crate::border::{border_pieces, BorderPiece};
+use crate::display_list::{BorderColors, BorderStyles, BorderWidths, DisplayList, PaintCommand, FILE_BUTTON_WIDTH};
+use crate::font::{shear_bitmap, FontCache, FontFace};
+use crate::image_scale::scale_image;
+use crate::shadow::ShadowCache;
+use crate::paint::RenderColor;
+
+/// A rectangle of whole pixels, as clip rects are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PixelRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelRect {
+    /// Clip rect nothing is painted in
+    pub const EMPTY: PixelRect = PixelRect { x: 0, y: 0, width: 0, height: 0 };
+
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Smallest pixel rectangle covering a rectangle, so partly covered
+    /// pixels are in it
+    pub fn enclosing(rect: &Rect) -> Self {
+        let (x, y) = (rect.x.floor() as i32, rect.y.floor() as i32);
+        PixelRect::new(
+            x,
+            y,
+            ((rect.x + rect.width).ceil() as i32 - x).max(1) as u32,
+            ((rect.y + rect.height).ceil() as i32 - y).max(1) as u32,
+        )
+    }
+
+    /// Check if the rectangle has no area
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Area common to both rectangles, None when they don't overlap
+    pub fn intersection(&self, other: PixelRect) -> Option<PixelRect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width as i32).min(other.x + other.width as i32);
+        let bottom = (self.y + self.height as i32).min(other.y + other.height as i32);
+        let rect = PixelRect::new(x, y, (right - x).max(0) as u32, (bottom - y).max(0) as u32);
+        (!rect.is_empty()).then_some(rect)
+    }
+}
+
+/// Drawing on a surface of pixels
+///
+/// Backends provide the primitives, the provided methods draw every paint
+/// command with them.
+pub(crate) trait Painter {
+    /// Fill a rectangle with a color
+    fn draw_rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: RenderColor);
+
+    /// Draw a glyph's coverage bitmap at a position in a color
+    fn draw_glyph_bitmap(&mut self, bitmap: &[u8], width: u32, height: u32, x: i32, y: i32, color: RenderColor);
+
+    /// Draw image pixels scaled to a rectangle, returns true on success
+    ///
+    /// Images are scaled to the rectangle before they are drawn, backends
+    /// only stretch them by the rounding of its size.
+    fn draw_image_pixels(&mut self, img: &ImagePixels, x: i32, y: i32, w: u32, h: u32) -> bool;
+
+    /// Draw copies of image pixels at their size at each of a list of
+    /// positions
+    fn draw_image_tiles(&mut self, img: &ImagePixels, positions: &[(i32, i32)]) {
+        for &(x, y) in positions {
+            self.draw_image_pixels(img, x, y, img.width, img.height);
+        }
+    }
+
+    /// Rectangle drawing is clipped to, None when nothing is clipped
+    fn clip_rect(&self) -> Option<PixelRect>;
+
+    /// Clip drawing to a rectangle, or stop clipping
+    fn set_clip_rect(&mut self, clip: Option<PixelRect>);
+
+    /// Rectangle the clip rects of paint commands are narrowed to
+    fn base_clip(&self) -> Option<PixelRect>;
+
+    /// Narrow the clip rects of paint commands to a rectangle, or stop
+    fn set_base_clip(&mut self, clip: Option<PixelRect>);
+
+    /// Fonts text is drawn with
+    fn font_cache(&mut self) -> &mut FontCache;
+
+    /// Stack of opacity modifiers (multiplied together)
+    fn opacity_stack(&mut self) -> &mut Vec<f32>;
+
+    /// Masks of the box shadows drawn last
+    fn shadow_cache(&mut self) -> &mut ShadowCache;
+
+    /// Draw into a transparent layer the size of the surface until it is
+    /// popped, returns false when no layer could be made
+    fn push_layer(&mut self) -> bool;
+
+    /// Composite the layer drawn into last over what is under it, moved
+    /// by a transform and inside the clip rect
+    fn pop_layer(&mut self, opacity: f32, transform: &Transform);
+
+    /// Paint the commands of a frame that are inside `damage` over what
+    /// is already drawn, clearing it to `background` first
+    fn paint_damage(&mut self, display_list: &DisplayList, damage: &Rect, background: RenderColor) {
+        let clip = PixelRect::enclosing(damage);
+        self.set_base_clip(Some(clip));
+        self.set_clip_rect(Some(clip));
+        // Clearing would ignore the clip rect
+        self.draw_rect(clip.x, clip.y, clip.width, clip.height, background);
+        self.paint(&commands_in_rect(display_list, damage));
+        self.set_base_clip(None);
+        self.set_clip_rect(None);
+    }
+
+    /// Paint the commands of a display list
+    ///
+    /// Translucent groups drawing more than once are drawn into a layer
+    /// composited at their opacity, so what they draw over themselves
+    /// doesn't show through. Transformed groups are drawn into a layer
+    /// composited through their transform. Clips nest, each one inside
+    /// the clips already in effect.
+    fn paint(&mut self, display_list: &DisplayList) {
+        // Layer of each open group, None for groups drawn directly
+        let mut groups: Vec<Option<OpenLayer>> = Vec::new();
+        // Clip rect each open clip restores
+        let mut clips: Vec<Option<PixelRect>> = Vec::new();
+        for (i, command) in display_list.commands.iter().enumerate() {
+            match command {
+                PaintCommand::FillRect { rect, color } => {
+                    self.draw_rect(
+                        rect.x as i32,
+                        rect.y as i32,
+                        rect.width as u32,
+                        rect.height as u32,
+                        *color,
+                    );
+                }
+                PaintCommand::DrawText { text, x, y, color, font_size, face, family } => {
+                    self.draw_text_face(text, *x, *y, *color, *font_size, (*face, family.as_deref()));
+                }
+                PaintCommand::DrawBorder { rect, widths, styles, colors } => {
+                    self.draw_styled_border(rect, widths, styles, colors);
+                }
+                PaintCommand::DrawTextInput { rect, text, cursor_pos, is_password, is_focused, .. } => {
+                    self.draw_text_input(rect, text, *cursor_pos, *is_password, *is_focused);
+                }
+                PaintCommand::DrawCheckbox { rect, checked, is_focused, .. } => {
+                    self.draw_checkbox(rect, *checked, *is_focused);
+                }
+                PaintCommand::DrawRadio { rect, checked, is_focused, .. } => {
+                    self.draw_radio(rect, *checked, *is_focused);
+                }
+                PaintCommand::DrawButton { rect, text, is_pressed, .. } => {
+                    self.draw_button(rect, text, *is_pressed);
+                }
+                PaintCommand::DrawFileInput { rect, label, .. } => {
+                    self.draw_file_input(rect, label);
+                }
+                PaintCommand::DrawTextArea {
+                    rect, text, cursor_pos, font_size, line_height, first_line, is_focused, ..
+                } => {
+                    self.draw_text_area(rect, text, *cursor_pos, *font_size, *line_height, *first_line, *is_focused);
+                }
+                PaintCommand::DrawSelect { rect, text, font_size, .. } => {
+                    self.draw_select(rect, text, *font_size);
+                }
+                PaintCommand::DrawImage { rect, pixels, alt, clip, rendering } => {
+                    match clip {
+                        Some(clip) => self.draw_clipped_image(rect, pixels.as_ref(), alt, clip, *rendering),
+                        None => self.draw_image(rect, pixels.as_ref(), alt, *rendering),
+                    }
+                }
+                PaintCommand::DrawTiledImage { tile, pixels, repeat, clip, rendering } => {
+                    self.draw_tiled_image(tile, pixels, *repeat, clip, *rendering);
+                }
+                PaintCommand::PushClip(rect) => {
+                    let rect = PixelRect::new(rect.x as i32, rect.y as i32, rect.width as u32, rect.height as u32);
+                    // Nothing is painted outside of the clip in effect, or
+                    // the base clip when there is none
+                    let outer = self.clip_rect().or(self.base_clip());
+                    clips.push(self.clip_rect());
+                    let clip = match outer {
+                        Some(outer) => outer.intersection(rect).unwrap_or(PixelRect::EMPTY),
+                        None => rect,
+                    };
+                    self.set_clip_rect(Some(clip));
+                }
+                PaintCommand::PopClip => {
+                    if let Some(clip) = clips.pop() {
+                        self.set_clip_rect(clip);
+                    }
+                }
+                PaintCommand::PushOpacity(opacity) => {
+                    let layered = *opacity < 1.0
+                        && draws_more_than_once(&display_list.commands[i + 1..])
+                        && self.push_layer();
+                    // The layer takes the opacity, not what is drawn into it
+                    self.opacity_stack().push(if layered { 1.0 } else { *opacity });
+                    groups.push(layered.then(|| OpenLayer::new(self, *opacity, Transform::IDENTITY)));
+                }
+                PaintCommand::PopOpacity => {
+                    self.opacity_stack().pop();
+                    if let Some(Some(layer)) = groups.pop() {
+                        layer.composite(self);
+                    }
+                }
+                PaintCommand::PushTransform(transform) => {
+                    // Without a layer the group is drawn where it is laid out
+                    let layer = self.push_layer().then(|| OpenLayer::new(self, 1.0, *transform));
+                    if layer.is_some() {
+                        // What is drawn isn't where it ends up, the clips
+                        // apply once the layer is composited
+                        self.set_base_clip(None);
+                        self.set_clip_rect(None);
+                    }
+                    groups.push(layer);
+                }
+                PaintCommand::PopTransform => {
+                    if let Some(Some(layer)) = groups.pop() {
+                        layer.composite(self);
+                    }
+                }
+                PaintCommand::BeginFixed
+                | PaintCommand::EndFixed
+                | PaintCommand::BeginScroll(_)
+                | PaintCommand::EndScroll => {
+                    // Scrolling is applied before commands reach the backend
+                }
+                PaintCommand::DrawBoxShadow { rect, radius, shadow } => {
+                    self.draw_box_shadow(rect, radius, shadow);
+                }
+                PaintCommand::FillSelection { rects, color } => {
+                    let color = self.apply_opacity(*color);
+                    for rect in rects {
+                        self.draw_rect(rect.x as i32, rect.y as i32, rect.width.ceil() as u32, rect.height.ceil() as u32, color);
+                    }
+                }
+                PaintCommand::FillRoundedRect { rect, radius, color } => {
+                    self.draw_rounded_rect(rect, radius, *color);
+                }
+                PaintCommand::DrawRoundedBorder { rect, radius, widths, colors, .. } => {
+                    self.draw_rounded_border(rect, radius, widths, colors);
+                }
+                PaintCommand::FillLinearGradient { rect, direction, stops, radius } => {
+                    self.draw_linear_gradient(rect, direction, stops, radius.as_ref());
+                }
+                PaintCommand::FillRadialGradient { rect, shape, size, center_x, center_y, stops, radius } => {
+                    self.draw_radial_gradient(rect, shape, size, *center_x, *center_y, stops, radius.as_ref());
+                }
+            }
+        }
+
+        // Groups left open still show
+        while let Some(group) = groups.pop() {
+            if let Some(layer) = group {
+                layer.composite(self);
+            }
+        }
+        self.opacity_stack().clear();
+    }
+
+    /// Draw text at a position
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, color: RenderColor, font_size: f32) {
+        self.draw_text_face(text, x, y, color, font_size, (FontFace::REGULAR, None));
+    }
+
+    /// Draw text in a face of the first loaded family among the font's
+    /// families, synthesizing the bold or italic parts of it that have no
+    /// loaded face
+    fn draw_text_face(
+        &mut self,
+        text: &str,
+        x: f32,
+        y: f32,
+        color: RenderColor,
+        font_size: f32,
+        (face, families): (FontFace, Option<&str>),
+    ) {
+        let baseline_y = (y as i32).saturating_add(self.font_cache().ascent_in(families, font_size) as i32);
+        let synthesized = self.font_cache().synthesized_in(families, face);
+        let (glyphs, _) = self.font_cache().shape_in(text, font_size, families, face);
+
+        for (pen_x, glyph) in glyphs {
+            let (width, height) = (glyph.width, glyph.height);
+            if width == 0 || height == 0 {
+                continue;
+            }
+            // Glyphs start on whole pixels from the unrounded pen position,
+            // so spacing doesn't drift along the run
+            let mut glyph_x = ((x + pen_x).round() as i32).saturating_add(glyph.offset_x);
+            let glyph_y = baseline_y.saturating_sub(glyph.offset_y).saturating_sub(height as i32);
+
+            let sheared;
+            let (bitmap, width) = if synthesized.italic {
+                let (bitmap, sheared_width, left) =
+                    shear_bitmap(&glyph.bitmap, width, height, height as i32 + glyph.offset_y);
+                glyph_x = glyph_x.saturating_add(left);
+                sheared = bitmap;
+                (sheared.as_slice(), sheared_width)
+            } else {
+                (glyph.bitmap.as_slice(), width)
+            };
+
+            self.draw_glyph_bitmap(bitmap, width, height, glyph_x, glyph_y, color);
+            if synthesized.bold {
+                // Drawn again a pixel to the right to thicken the stems
+                self.draw_glyph_bitmap(bitmap, width, height, glyph_x + 1, glyph_y, color);
+            }
+        }
+    }
+
+    /// Draw a border with the style and color of each side
+    fn draw_styled_border(&mut self, rect: &Rect, widths: &BorderWidths, styles: &BorderStyles, colors: &BorderColors) {
+        for (color, piece) in border_pieces(rect, widths, styles, colors) {
+            let color = self.apply_opacity(color);
+            match piece {
+                BorderPiece::Rect(rect) => {
+                    // Rounded to whole pixels so neighboring pieces don't
+                    // overlap or leave a gap
+                    let (x, y) = (rect.x.round(), rect.y.round());
+                    let width = (rect.x + rect.width).round() - x;
+                    let height = (rect.y + rect.height).round() - y;
+                    if width > 0.0 && height > 0.0 {
+                        self.draw_rect(x as i32, y as i32, width as u32, height as u32, color);
+                    }
+                }
+                BorderPiece::Dot { x, y, diameter } => self.draw_dot(x, y, diameter, color),
+                BorderPiece::Triangle(points) => self.draw_triangle(points, color),
+            }
+        }
+    }
+
+    /// Fill a triangle with smooth edges, sampling each pixel 4x4 times
+    fn draw_triangle(&mut self, points: [(f32, f32); 3], color: RenderColor) {
+        let xs = points.map(|(x, _)| x);
+        let ys = points.map(|(_, y)| y);
+        let (left, top) = (xs.iter().copied().fold(f32::MAX, f32::min).floor(), ys.iter().copied().fold(f32::MAX, f32::min).floor());
+        let width = (xs.iter().copied().fold(f32::MIN, f32::max).ceil() - left) as u32;
+        let height = (ys.iter().copied().fold(f32::MIN, f32::max).ceil() - top) as u32;
+
+        // Points are inside when on the same side of every edge as the
+        // triangle's winding
+        let [a, b, c] = points;
+        let cross = |(x0, y0): (f32, f32), (x1, y1): (f32, f32), (x, y): (f32, f32)| (x1 - x0) * (y - y0) - (y1 - y0) * (x - x0);
+        let winding = cross(a, b, c).signum();
+        let inside = |p| [(a, b), (b, c), (c, a)].iter().all(|&(p0, p1)| cross(p0, p1, p) * winding >= 0.0);
+
+        let mut coverage = Vec::with_capacity((width * height) as usize);
+        for py in 0..height {
+            for px in 0..width {
+                let samples = (0..16)
+                    .filter(|i| {
+                        let sx = left + px as f32 + ((i % 4) as f32 + 0.5) / 4.0;
+                        let sy = top + py as f32 + ((i / 4) as f32 + 0.5) / 4.0;
+                        inside((sx, sy))
+                    })
+                    .count();
+                coverage.push((samples * 255 / 16) as u8);
+            }
+        }
+        self.draw_glyph_bitmap(&coverage, width, height, left as i32, top as i32, color);
+    }
+
+    /// Fill a circle with smooth edges
+    fn draw_dot(&mut self, cx: f32, cy: f32, diameter: f32, color: RenderColor) {
+        let radius = diameter / 2.0;
+        let (left, top) = ((cx - radius).floor(), (cy - radius).floor());
+        let size = ((cx + radius).ceil() - left).max((cy + radius).ceil() - top) as u32;
+        let mut coverage = Vec::with_capacity((size * size) as usize);
+        for py in 0..size {
+            for px in 0..size {
+                let distance = (left + px as f32 + 0.5 - cx).hypot(top + py as f32 + 0.5 - cy);
+                coverage.push(((radius - distance + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        self.draw_glyph_bitmap(&coverage, size, size, left as i32, top as i32, color);
+    }
+
+    /// Draw a border (four rectangles)
+    fn draw_border(
+        &mut self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        top: f32,
+        right: f32,
+        bottom: f32,
+        left: f32,
+        color: RenderColor,
+    ) {
+        let x = x as i32;
+        let y = y as i32;
+        let w = w as u32;
+        let h = h as u32;
+
+        // Top border
+        if top > 0.0 {
+            self.draw_rect(x, y, w, top as u32, color);
+        }
+
+        // Bottom border
+        if bottom > 0.0 {
+            self.draw_rect(x, y + h as i32 - bottom as i32, w, bottom as u32, color);
+        }
+
+        // Left border
+        if left > 0.0 {
+            self.draw_rect(x, y, left as u32, h, color);
+        }
+
+        // Right border
+        if right > 0.0 {
+            self.draw_rect(x + w as i32 - right as i32, y, right as u32, h, color);
+        }
+    }
+
+    /// Draw a text input field
+    fn draw_text_input(
+        &mut self,
+        rect: &gugalanna_layout::Rect,
+        text: &str,
+        cursor_pos: Option<usize>,
+        is_password: bool,
+        is_focused: bool,
+    ) {
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let w = rect.width as u32;
+        let h = rect.height as u32;
+
+        // Background
+        let bg_color = if is_focused {
+            RenderColor::rgb(255, 255, 255)
+        } else {
+            RenderColor::rgb(250, 250, 250)
+        };
+        self.draw_rect(x, y, w, h, bg_color);
+
+        // Border
+        let border_color = if is_focused {
+            RenderColor::rgb(0, 120, 212)
+        } else {
+            RenderColor::rgb(180, 180, 180)
+        };
+        self.draw_border(rect.x, rect.y, rect.width, rect.height, 1.0, 1.0, 1.0, 1.0, border_color);
+
+        // Text (or dots for password)
+        if !text.is_empty() {
+            let display_text = if is_password {
+                "\u{2022}".repeat(text.chars().count())
+            } else {
+                text.to_string()
+            };
+            self.draw_text(&display_text, rect.x + 4.0, rect.y + 4.0, RenderColor::black(), 14.0);
+        }
+
+        // Cursor
+        if let Some(pos) = cursor_pos {
+            let cursor_x = rect.x + 4.0 + (pos as f32 * 8.0);
+            self.draw_rect(
+                cursor_x as i32,
+                y + 2,
+                1,
+                h.saturating_sub(4),
+                RenderColor::black(),
+            );
+        }
+    }
+
+    /// Draw a checkbox
+    fn draw_checkbox(&mut self, rect: &gugalanna_layout::Rect, checked: bool, is_focused: bool) {
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let size = rect.width.min(rect.height) as u32;
+
+        // Background
+        self.draw_rect(x, y, size, size, RenderColor::rgb(255, 255, 255));
+
+        // Border
+        let border_color = if is_focused {
+            RenderColor::rgb(0, 120, 212)
+        } else {
+            RenderColor::rgb(128, 128, 128)
+        };
+        self.draw_border(rect.x, rect.y, size as f32, size as f32, 1.0, 1.0, 1.0, 1.0, border_color);
+
+        // Checkmark
+        if checked {
+            // Draw a simple checkmark using two diagonal lines
+            let inset = 3;
+            let inner_size = size.saturating_sub(inset * 2);
+            let check_color = RenderColor::rgb(0, 120, 212);
+
+            // Simple checkmark: draw a small filled rectangle in center
+            self.draw_rect(
+                x + inset as i32 + 2,
+                y + inset as i32 + 2,
+                inner_size.saturating_sub(4),
+                inner_size.saturating_sub(4),
+                check_color,
+            );
+        }
+    }
+
+    /// Draw a radio button
+    fn draw_radio(&mut self, rect: &gugalanna_layout::Rect, checked: bool, is_focused: bool) {
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let size = rect.width.min(rect.height) as u32;
+
+        // Background (circular approximated with filled rect)
+        self.draw_rect(x, y, size, size, RenderColor::rgb(255, 255, 255));
+
+        // Border
+        let border_color = if is_focused {
+            RenderColor::rgb(0, 120, 212)
+        } else {
+            RenderColor::rgb(128, 128, 128)
+        };
+        self.draw_border(rect.x, rect.y, size as f32, size as f32, 1.0, 1.0, 1.0, 1.0, border_color);
+
+        // Inner dot when checked
+        if checked {
+            let inset = 4;
+            let inner_size = size.saturating_sub(inset * 2);
+            self.draw_rect(
+                x + inset as i32,
+                y + inset as i32,
+                inner_size,
+                inner_size,
+                RenderColor::rgb(0, 120, 212),
+            );
+        }
+    }
+
+    /// Draw a button
+    fn draw_button(&mut self, rect: &gugalanna_layout::Rect, text: &str, is_pressed: bool) {
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let w = rect.width as u32;
+        let h = rect.height as u32;
+
+        // Background
+        let bg_color = if is_pressed {
+            RenderColor::rgb(200, 200, 200)
+        } else {
+            RenderColor::rgb(240, 240, 240)
+        };
+        self.draw_rect(x, y, w, h, bg_color);
+
+        // Border
+        self.draw_border(rect.x, rect.y, rect.width, rect.height, 1.0, 1.0, 1.0, 1.0, RenderColor::rgb(128, 128, 128));
+
+        // Centered text
+        // Calculate approximate text width (8px per character at 14px font)
+        let text_width = text.len() as f32 * 8.0;
+        let text_x = rect.x + (rect.width - text_width) / 2.0;
+        let text_y = rect.y + (rect.height - 14.0) / 2.0;
+        self.draw_text(text, text_x, text_y, RenderColor::black(), 14.0);
+    }
+
+    /// Draw a file input, its button at the left and the label of the
+    /// files chosen after it
+    fn draw_file_input(&mut self, rect: &gugalanna_layout::Rect, label: &str) {
+        let button = gugalanna_layout::Rect { width: rect.width.min(FILE_BUTTON_WIDTH), ..*rect };
+        self.draw_button(&button, "Browse…", false);
+        let text_y = rect.y + (rect.height - 14.0) / 2.0;
+        self.draw_text(label, button.x + button.width + 6.0, text_y, RenderColor::black(), 14.0);
+    }
+
+    /// Draw a multi-line text area
+    ///
+    /// Lines wider than the field wrap, those above `first_line` are
+    /// scrolled out of view, and everything is clipped to the field.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_text_area(
+        &mut self,
+        rect: &gugalanna_layout::Rect,
+        text: &str,
+        cursor_pos: Option<usize>,
+        font_size: f32,
+        line_height: f32,
+        first_line: usize,
+        is_focused: bool,
+    ) {
+        let (bg_color, border_color) = if is_focused {
+            (RenderColor::rgb(255, 255, 255), RenderColor::rgb(0, 120, 212))
+        } else {
+            (RenderColor::rgb(250, 250, 250), RenderColor::rgb(180, 180, 180))
+        };
+        self.draw_rect(rect.x as i32, rect.y as i32, rect.width as u32, rect.height as u32, bg_color);
+        self.draw_border(rect.x, rect.y, rect.width, rect.height, 1.0, 1.0, 1.0, 1.0, border_color);
+
+        let padding = gugalanna_layout::CONTROL_PADDING;
+        let inner = PixelRect::new(
+            (rect.x + padding) as i32,
+            (rect.y + padding) as i32,
+            (rect.width - 2.0 * padding).max(0.0) as u32,
+            (rect.height - 2.0 * padding).max(0.0) as u32,
+        );
+        let previous = self.clip_rect();
+        let Some(clip) = previous.map_or(Some(inner), |previous| previous.intersection(inner)) else {
+            return;
+        };
+
+        let fonts = self.font_cache();
+        let lines = text_area_lines(text, rect.width - 2.0 * padding, |line| fonts.measure_text(line, font_size));
+
+        // Line of the cursor and the text before it there, the cursor being
+        // a byte offset; at a wrap it starts the next line
+        let cursor = cursor_pos.map(|pos| {
+            let pos = pos.min(text.len());
+            let line = lines.iter().rposition(|line| line.start <= pos).unwrap_or(0);
+            (line, &text[lines[line].start..pos])
+        });
+        let visible_lines = ((rect.height - 2.0 * padding) / line_height).floor().max(1.0) as usize;
+        let top = rect.y + padding - first_line as f32 * line_height;
+
+        self.set_clip_rect(Some(clip));
+        for (i, line) in lines.iter().enumerate().skip(first_line).take(visible_lines) {
+            let y = top + i as f32 * line_height;
+            self.draw_text(&text[line.clone()], rect.x + padding, y, RenderColor::black(), font_size);
+        }
+        if let Some((line, column)) = cursor {
+            let x = rect.x + padding + self.font_cache().measure_text(column, font_size);
+            let y = top + line as f32 * line_height;
+            self.draw_rect(x as i32, y as i32, 1, line_height as u32, RenderColor::black());
+        }
+        self.set_clip_rect(previous);
+    }
+
+    /// Draw a dropdown list with its selected option and an arrow
+    fn draw_select(&mut self, rect: &gugalanna_layout::Rect, text: &str, font_size: f32) {
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        self.draw_rect(x, y, rect.width as u32, rect.height as u32, RenderColor::rgb(250, 250, 250));
+        self.draw_border(rect.x, rect.y, rect.width, rect.height, 1.0, 1.0, 1.0, 1.0, RenderColor::rgb(180, 180, 180));
+
+        let padding = gugalanna_layout::CONTROL_PADDING;
+        let text_y = rect.y + (rect.height - self.font_cache().line_height(font_size)) / 2.0;
+        self.draw_text(text, rect.x + padding, text_y, RenderColor::black(), font_size);
+
+        // Downward pointing triangle, one row narrower per pixel down
+        let arrow_width = gugalanna_layout::SELECT_ARROW_WIDTH;
+        let center_x = rect.x + rect.width - padding - arrow_width / 2.0;
+        let center_y = rect.y + rect.height / 2.0;
+        for row in 0..4 {
+            let half = 4 - row;
+            self.draw_rect(
+                center_x as i32 - half,
+                center_y as i32 - 2 + row,
+                (2 * half) as u32,
+                1,
+                RenderColor::rgb(80, 80, 80),
+            );
+        }
+    }
+
+    /// Draw an image
+    fn draw_image(
+        &mut self,
+        rect: &gugalanna_layout::Rect,
+        pixels: Option<&gugalanna_layout::ImagePixels>,
+        alt: &str,
+        rendering: ImageRendering,
+    ) {
+        // Check if we have valid image data first
+        let img = match pixels {
+            Some(img) if img.width > 0 && img.height > 0 && !img.data.is_empty() => img,
+            _ => {
+                // No valid image data - draw placeholder
+                self.draw_image_placeholder(rect, alt);
+                return;
+            }
+        };
+
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let w = rect.width as u32;
+        let h = rect.height as u32;
+
+        // Images not already scaled to the rectangle are filtered here,
+        // backends draw them pixel for pixel
+        let scaled = scale_image(img, w, h, rendering);
+
+        // Try to render the image, track if we need to show placeholder
+        let render_success = self.draw_image_pixels(&scaled, x, y, w, h);
+
+        if !render_success {
+            self.draw_image_placeholder(rect, alt);
+        }
+    }
+
+    /// Draw an image within the clip rect already in effect, narrowed to `clip`
+    fn draw_clipped_image(
+        &mut self,
+        rect: &gugalanna_layout::Rect,
+        pixels: Option<&gugalanna_layout::ImagePixels>,
+        alt: &str,
+        clip: &gugalanna_layout::Rect,
+        rendering: ImageRendering,
+    ) {
+        let previous = self.clip_rect();
+        let clip = PixelRect::new(clip.x as i32, clip.y as i32, clip.width as u32, clip.height as u32);
+        let narrowed = match previous {
+            Some(previous) => previous.intersection(clip),
+            None => Some(clip),
+        };
+
+        // Nothing of the image is visible
+        let Some(narrowed) = narrowed else {
+            return;
+        };
+
+        self.set_clip_rect(Some(narrowed));
+        self.draw_image(rect, pixels, alt, rendering);
+        self.set_clip_rect(previous);
+    }
+
+    /// Draw the tiles of an image laid out from `tile` that are inside
+    /// `clip`
+    ///
+    /// Tiles are snapped to whole pixels so they meet without gaps.
+    fn draw_tiled_image(
+        &mut self,
+        tile: &Rect,
+        pixels: &ImagePixels,
+        repeat: BackgroundRepeat,
+        clip: &Rect,
+        rendering: ImageRendering,
+    ) {
+        let (w, h) = (tile.width.round() as u32, tile.height.round() as u32);
+        if w == 0 || h == 0 || pixels.width == 0 || pixels.height == 0 {
+            return;
+        }
+
+        let previous = self.clip_rect();
+        let clip = PixelRect::new(clip.x as i32, clip.y as i32, clip.width as u32, clip.height as u32);
+        let narrowed = match previous {
+            Some(previous) => previous.intersection(clip),
+            None => Some(clip),
+        };
+        let Some(narrowed) = narrowed else {
+            return;
+        };
+
+        let tile = Rect::new(tile.x.round(), tile.y.round(), w as f32, h as f32);
+        let visible = Rect::new(narrowed.x as f32, narrowed.y as f32, narrowed.width as f32, narrowed.height as f32);
+        let positions: Vec<_> =
+            tile_rects(&tile, repeat, &visible).iter().map(|rect| (rect.x as i32, rect.y as i32)).collect();
+
+        self.set_clip_rect(Some(narrowed));
+        self.draw_image_tiles(&scale_image(pixels, w, h, rendering), &positions);
+        self.set_clip_rect(previous);
+    }
+
+    /// Draw a placeholder for failed/loading images
+    fn draw_image_placeholder(&mut self, rect: &gugalanna_layout::Rect, alt: &str) {
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let w = rect.width as u32;
+        let h = rect.height as u32;
+
+        // Light gray background
+        self.draw_rect(x, y, w, h, RenderColor::rgb(240, 240, 240));
+
+        // Border
+        self.draw_border(
+            rect.x, rect.y, rect.width, rect.height,
+            1.0, 1.0, 1.0, 1.0,
+            RenderColor::rgb(200, 200, 200),
+        );
+
+        // Alt text (truncated if too long)
+        if !alt.is_empty() {
+            let text = if alt.len() > 30 {
+                format!("{}...", &alt[..27])
+            } else {
+                alt.to_string()
+            };
+
+            // Center the text
+            let text_width = text.len() as f32 * 7.0;
+            let text_x = rect.x + (rect.width - text_width).max(0.0) / 2.0;
+            let text_y = rect.y + (rect.height - 14.0).max(0.0) / 2.0;
+
+            self.draw_text(&text, text_x.max(rect.x + 4.0), text_y.max(rect.y + 4.0), RenderColor::rgb(128, 128, 128), 14.0);
+        }
+    }
+
+    /// Get the current opacity (product of all stacked opacities)
+    fn current_opacity(&mut self) -> f32 {
+        self.opacity_stack().iter().fold(1.0, |acc, &o| acc * o)
+    }
+
+    /// Apply current opacity to a color
+    fn apply_opacity(&mut self, color: RenderColor) -> RenderColor {
+        let opacity = self.current_opacity();
+        if opacity >= 1.0 {
+            return color;
+        }
+        RenderColor {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+            a: (color.a as f32 * opacity) as u8,
+        }
+    }
+
+    /// Draw a box shadow from its blurred mask
+    fn draw_box_shadow(&mut self, rect: &gugalanna_layout::Rect, radius: &BorderRadius, shadow: &BoxShadow) {
+        let Some(mask) = self.shadow_cache().mask(rect, radius, shadow) else {
+            return;
+        };
+        let color = self.apply_opacity(RenderColor {
+            r: shadow.color.r,
+            g: shadow.color.g,
+            b: shadow.color.b,
+            a: shadow.color.a,
+        });
+        self.draw_glyph_bitmap(&mask.coverage, mask.width, mask.height, mask.x, mask.y, color);
+    }
+
+    /// Draw a filled rounded rectangle
+    fn draw_rounded_rect(
+        &mut self,
+        rect: &gugalanna_layout::Rect,
+        radius: &BorderRadius,
+        color: RenderColor,
+    ) {
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let w = rect.width;
+        let h = rect.height;
+
+        // Clamp radii to half the dimensions
+        let max_radius = (w / 2.0).min(h / 2.0);
+        let tl = radius.top_left.min(max_radius);
+        let tr = radius.top_right.min(max_radius);
+        let br = radius.bottom_right.min(max_radius);
+        let bl = radius.bottom_left.min(max_radius);
+
+        let color = self.apply_opacity(color);
+
+        // If all radii are 0, just draw a regular rect
+        if tl <= 0.0 && tr <= 0.0 && br <= 0.0 && bl <= 0.0 {
+            self.draw_rect(x, y, w as u32, h as u32, color);
+            return;
+        }
+
+        // Draw the main rectangle body (center region)
+        let max_top = tl.max(tr);
+        let max_bottom = bl.max(br);
+        let max_left = tl.max(bl);
+        let max_right = tr.max(br);
+
+        // Center horizontal bar
+        self.draw_rect(
+            x + max_left as i32,
+            y,
+            (w - max_left - max_right) as u32,
+            h as u32,
+            color,
+        );
+
+        // Left bar
+        self.draw_rect(
+            x,
+            y + max_top as i32,
+            max_left as u32,
+            (h - max_top - max_bottom) as u32,
+            color,
+        );
+
+        // Right bar
+        self.draw_rect(
+            x + (w - max_right) as i32,
+            y + max_top as i32,
+            max_right as u32,
+            (h - max_top - max_bottom) as u32,
+            color,
+        );
+
+        // Draw corner quarters using filled circles (scanlines)
+        // Top-left corner
+        if tl > 0.0 {
+            self.fill_quarter_circle(x + tl as i32, y + tl as i32, tl, 0, color);
+        }
+        // Top-right corner
+        if tr > 0.0 {
+            self.fill_quarter_circle(x + (w - tr) as i32, y + tr as i32, tr, 1, color);
+        }
+        // Bottom-right corner
+        if br > 0.0 {
+            self.fill_quarter_circle(x + (w - br) as i32, y + (h - br) as i32, br, 2, color);
+        }
+        // Bottom-left corner
+        if bl > 0.0 {
+            self.fill_quarter_circle(x + bl as i32, y + (h - bl) as i32, bl, 3, color);
+        }
+    }
+
+    /// Fill a quarter circle using horizontal scanlines
+    /// quadrant: 0=top-left, 1=top-right, 2=bottom-right, 3=bottom-left
+    fn fill_quarter_circle(&mut self, cx: i32, cy: i32, r: f32, quadrant: u8, color: RenderColor) {
+        let r_int = r as i32;
+        let r_sq = r * r;
+
+        for dy in 0..=r_int {
+            let dx = ((r_sq - (dy as f32 * dy as f32)).sqrt()) as i32;
+
+            let (line_x, line_y, line_w) = match quadrant {
+                0 => (cx - dx, cy - dy, dx as u32),           // top-left
+                1 => (cx, cy - dy, dx as u32),                 // top-right
+                2 => (cx, cy + dy, dx as u32),                 // bottom-right
+                3 => (cx - dx, cy + dy, dx as u32),           // bottom-left
+                _ => continue,
+            };
+
+            if line_w > 0 {
+                self.draw_rect(line_x, line_y, line_w, 1, color);
+            }
+        }
+    }
+
+    /// Draw a rounded border
+    fn draw_rounded_border(
+        &mut self,
+        rect: &gugalanna_layout::Rect,
+        radius: &BorderRadius,
+        widths: &BorderWidths,
+        colors: &BorderColors,
+    ) {
+        // For now, draw outer rounded rect minus inner rounded rect
+        // This is a simplified approach - proper rounded borders are complex
+
+        let (top, right) = (self.apply_opacity(colors.top), self.apply_opacity(colors.right));
+        let (bottom, left) = (self.apply_opacity(colors.bottom), self.apply_opacity(colors.left));
+
+        // Draw the border sides (simplified - not truly rounded at corners)
+        // Top border
+        if widths.top > 0.0 {
+            self.draw_rect(
+                rect.x as i32 + radius.top_left as i32,
+                rect.y as i32,
+                (rect.width - radius.top_left - radius.top_right) as u32,
+                widths.top as u32,
+                top,
+            );
+        }
+
+        // Bottom border
+        if widths.bottom > 0.0 {
+            self.draw_rect(
+                rect.x as i32 + radius.bottom_left as i32,
+                (rect.y + rect.height - widths.bottom) as i32,
+                (rect.width - radius.bottom_left - radius.bottom_right) as u32,
+                widths.bottom as u32,
+                bottom,
+            );
+        }
+
+        // Left border
+        if widths.left > 0.0 {
+            self.draw_rect(
+                rect.x as i32,
+                rect.y as i32 + radius.top_left as i32,
+                widths.left as u32,
+                (rect.height - radius.top_left - radius.bottom_left) as u32,
+                left,
+            );
+        }
+
+        // Right border
+        if widths.right > 0.0 {
+            self.draw_rect(
+                (rect.x + rect.width - widths.right) as i32,
+                rect.y as i32 + radius.top_right as i32,
+                widths.right as u32,
+                (rect.height - radius.top_right - radius.bottom_right) as u32,
+                right,
+            );
+        }
+
+        // Draw corner arcs (simplified as quarter rings using multiple circles)
+        let border_width = widths.top.max(widths.right).max(widths.bottom).max(widths.left);
+        if border_width > 0.0 {
+            // Top-left arc
+            if radius.top_left > 0.0 {
+                self.draw_quarter_arc(
+                    rect.x as i32 + radius.top_left as i32,
+                    rect.y as i32 + radius.top_left as i32,
+                    radius.top_left,
+                    radius.top_left - border_width,
+                    0,
+                    CornerColors { horizontal: top, vertical: left, widths: (widths.top, widths.left) },
+                );
+            }
+            // Top-right arc
+            if radius.top_right > 0.0 {
+                self.draw_quarter_arc(
+                    (rect.x + rect.width - radius.top_right) as i32,
+                    rect.y as i32 + radius.top_right as i32,
+                    radius.top_right,
+                    radius.top_right - border_width,
+                    1,
+                    CornerColors { horizontal: top, vertical: right, widths: (widths.top, widths.right) },
+                );
+            }
+            // Bottom-right arc
+            if radius.bottom_right > 0.0 {
+                self.draw_quarter_arc(
+                    (rect.x + rect.width - radius.bottom_right) as i32,
+                    (rect.y + rect.height - radius.bottom_right) as i32,
+                    radius.bottom_right,
+                    radius.bottom_right - border_width,
+                    2,
+                    CornerColors { horizontal: bottom, vertical: right, widths: (widths.bottom, widths.right) },
+                );
+            }
+            // Bottom-left arc
+            if radius.bottom_left > 0.0 {
+                self.draw_quarter_arc(
+                    rect.x as i32 + radius.bottom_left as i32,
+                    (rect.y + rect.height - radius.bottom_left) as i32,
+                    radius.bottom_left,
+                    radius.bottom_left - border_width,
+                    3,
+                    CornerColors { horizontal: bottom, vertical: left, widths: (widths.bottom, widths.left) },
+                );
+            }
+        }
+    }
+
+    /// Draw a quarter arc (ring segment) using horizontal scanlines, split
+    /// between the colors of its sides on the line from the outer corner
+    /// of the border to its inner corner
+    fn draw_quarter_arc(
+        &mut self,
+        cx: i32,
+        cy: i32,
+        outer_r: f32,
+        inner_r: f32,
+        quadrant: u8,
+        colors: CornerColors,
+    ) {
+        let outer_r_int = outer_r as i32;
+        let outer_r_sq = outer_r * outer_r;
+        let inner_r_sq = inner_r.max(0.0) * inner_r.max(0.0);
+
+        for dy in 0..=outer_r_int {
+            let dy_sq = (dy as f32) * (dy as f32);
+            let outer_dx = ((outer_r_sq - dy_sq).max(0.0).sqrt()) as i32;
+            let inner_dx = if inner_r > 0.0 {
+                ((inner_r_sq - dy_sq).max(0.0).sqrt()) as i32
+            } else {
+                0
+            };
+
+            let line_width = (outer_dx - inner_dx) as u32;
+            if line_width == 0 {
+                continue;
+            }
+
+            let (line_x, line_y) = match quadrant {
+                0 => (cx - outer_dx, cy - dy),       // top-left
+                1 => (cx + inner_dx, cy - dy),        // top-right
+                2 => (cx + inner_dx, cy + dy),        // bottom-right
+                3 => (cx - outer_dx, cy + dy),       // bottom-left
+                _ => continue,
+            };
+
+            // Where the miter crosses the row, moving away from the outer
+            // corner by the ratio of the side widths
+            let (horizontal_width, vertical_width) = colors.widths;
+            let from_edge = outer_r - dy as f32;
+            let run = if horizontal_width > 0.0 {
+                (from_edge * vertical_width / horizontal_width).min(outer_r * 2.0)
+            } else {
+                outer_r * 2.0
+            };
+            let line_end = line_x + line_width as i32;
+            let (outer_part, inner_part) = match quadrant {
+                0 | 3 => {
+                    let split = ((cx as f32 - outer_r + run).round() as i32).clamp(line_x, line_end);
+                    ((line_x, split), (split, line_end))
+                }
+                _ => {
+                    let split = ((cx as f32 + outer_r - run).round() as i32).clamp(line_x, line_end);
+                    ((split, line_end), (line_x, split))
+                }
+            };
+
+            if outer_part.1 > outer_part.0 {
+                self.draw_rect(outer_part.0, line_y, (outer_part.1 - outer_part.0) as u32, 1, colors.vertical);
+            }
+            if inner_part.1 > inner_part.0 {
+                self.draw_rect(inner_part.0, line_y, (inner_part.1 - inner_part.0) as u32, 1, colors.horizontal);
+            }
+        }
+    }
+
+    /// Draw a linear gradient
+    fn draw_linear_gradient(
+        &mut self,
+        rect: &Rect,
+        direction: &GradientDirection,
+        stops: &[ColorStop],
+        _radius: Option<&BorderRadius>,
+    ) {
+        if stops.len() < 2 {
+            return;
+        }
+
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let w = rect.width as i32;
+        let h = rect.height as i32;
+
+        // Normalize color stops (distribute auto positions)
+        let normalized = normalize_color_stops(stops);
+
+        // Calculate gradient direction vector
+        let (is_vertical, is_horizontal) = match direction {
+            GradientDirection::ToBottom | GradientDirection::ToTop => (true, false),
+            GradientDirection::ToRight | GradientDirection::ToLeft => (false, true),
+            GradientDirection::Angle(deg) => {
+                // For angled gradients, we'll approximate with scanlines
+                let rad = deg.to_radians();
+                let dy = -rad.cos();
+                let dx = rad.sin();
+                // If mostly vertical or horizontal, use that
+                if dy.abs() > dx.abs() {
+                    (true, false)
+                } else {
+                    (false, true)
+                }
+            }
+            _ => (true, false), // Default to vertical for diagonal directions
+        };
+
+        let reverse = matches!(direction, GradientDirection::ToTop | GradientDirection::ToLeft);
+
+        if is_vertical {
+            // Vertical gradient - draw horizontal lines
+            for row in 0..h {
+                let t = if h > 1 {
+                    row as f32 / (h - 1) as f32
+                } else {
+                    0.5
+                };
+                let t = if reverse { 1.0 - t } else { t };
+                let color = interpolate_color(&normalized, t);
+                let final_color = self.apply_opacity(color);
+                self.draw_rect(x, y + row, w as u32, 1, final_color);
+            }
+        } else if is_horizontal {
+            // Horizontal gradient - draw vertical lines
+            for col in 0..w {
+                let t = if w > 1 {
+                    col as f32 / (w - 1) as f32
+                } else {
+                    0.5
+                };
+                let t = if reverse { 1.0 - t } else { t };
+                let color = interpolate_color(&normalized, t);
+                let final_color = self.apply_opacity(color);
+                self.draw_rect(x + col, y, 1, h as u32, final_color);
+            }
+        }
+
+        // Note: border-radius not applied for gradients in this basic implementation
+    }
+
+    /// Draw a radial gradient
+    fn draw_radial_gradient(
+        &mut self,
+        rect: &Rect,
+        _shape: &RadialShape,
+        _size: &RadialSize,
+        center_x: f32,
+        center_y: f32,
+        stops: &[ColorStop],
+        _radius: Option<&BorderRadius>,
+    ) {
+        if stops.len() < 2 {
+            return;
+        }
+
+        let x = rect.x as i32;
+        let y = rect.y as i32;
+        let w = rect.width as i32;
+        let h = rect.height as i32;
+
+        // Center point in absolute pixels
+        let cx = rect.x + rect.width * center_x;
+        let cy = rect.y + rect.height * center_y;
+
+        // Maximum radius (distance to farthest corner)
+        let corners = [
+            (rect.x, rect.y),
+            (rect.x + rect.width, rect.y),
+            (rect.x, rect.y + rect.height),
+            (rect.x + rect.width, rect.y + rect.height),
+        ];
+        let max_radius = corners.iter()
+            .map(|(px, py)| {
+                let dx = px - cx;
+                let dy = py - cy;
+                (dx * dx + dy * dy).sqrt()
+            })
+            .fold(0.0_f32, f32::max);
+
+        // Normalize color stops
+        let normalized = normalize_color_stops(stops);
+
+        // Draw pixel by pixel (simple but slow approach)
+        for row in 0..h {
+            for col in 0..w {
+                let px = x + col;
+                let py = y + row;
+
+                // Distance from center
+                let dx = px as f32 - cx;
+                let dy = py as f32 - cy;
+                let distance = (dx * dx + dy * dy).sqrt();
+
+                // Normalize to 0..1
+                let t = if max_radius > 0.0 {
+                    (distance / max_radius).min(1.0)
+                } else {
+                    0.0
+                };
+
+                let color = interpolate_color(&normalized, t);
+                let final_color = self.apply_opacity(color);
+                self.draw_rect(px, py, 1, 1, final_color);
+            }
+        }
+    }
+}
+
+/// Colors of the two sides meeting at a rounded corner
+pub(crate) struct CornerColors {
+    /// Color of the top or bottom side
+    horizontal: RenderColor,
+    /// Color of the left or right side
+    vertical: RenderColor,
+    /// Widths of the horizontal and vertical sides, the slope of the line
+    /// between their colors
+    widths: (f32, f32),
+}
+
+/// A layer being drawn into, and how it is composited once popped
+struct OpenLayer {
+    opacity: f32,
+    transform: Transform,
+    /// Clip rects where the layer was pushed, which it is composited in
+    clip: Option<PixelRect>,
+    base_clip: Option<PixelRect>,
+}
+
+impl OpenLayer {
+    fn new<P: Painter + ?Sized>(painter: &P, opacity: f32, transform: Transform) -> Self {
+        Self { opacity, transform, clip: painter.clip_rect(), base_clip: painter.base_clip() }
+    }
+
+    /// Composite the layer over what is under it
+    fn composite<P: Painter + ?Sized>(self, painter: &mut P) {
+        painter.set_base_clip(self.base_clip);
+        painter.set_clip_rect(self.clip);
+        painter.pop_layer(self.opacity, &self.transform);
+    }
+}
+
+/// Check if the group the commands start in draws more than one thing,
+/// which may overlap
+fn draws_more_than_once(commands: &[PaintCommand]) -> bool {
+    let mut depth = 0;
+    let mut draws = 0;
+    for command in commands {
+        match command {
+            PaintCommand::PushOpacity(_) => depth += 1,
+            PaintCommand::PopOpacity if depth == 0 => break,
+            PaintCommand::PopOpacity => depth -= 1,
+            command if command_bounds(command).is_some() => {
+                draws += 1;
+                if draws > 1 {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Normalize color stops by distributing auto positions
+fn normalize_color_stops(stops: &[ColorStop]) -> Vec<(f32, RenderColor)> {
+    let mut result = Vec::with_capacity(stops.len());
+
+    // First pass: collect known positions
+    let mut positions: Vec<Option<f32>> = stops.iter()
+        .map(|s| s.position)
+        .collect();
+
+    // Ensure first and last have positions
+    if positions.first().map(|p| p.is_none()).unwrap_or(true) {
+        positions[0] = Some(0.0);
+    }
+    if positions.last().map(|p| p.is_none()).unwrap_or(true) {
+        let last = positions.len() - 1;
+        positions[last] = Some(1.0);
+    }
+
+    // Interpolate missing positions
+    let mut i = 0;
+    while i < positions.len() {
+        if positions[i].is_none() {
+            // Find next known position
+            let start_idx = i - 1;
+            let start_pos = positions[start_idx].unwrap();
+
+            let mut end_idx = i + 1;
+            while end_idx < positions.len() && positions[end_idx].is_none() {
+                end_idx += 1;
+            }
+            let end_pos = positions[end_idx].unwrap();
+
+            // Distribute positions evenly
+            let count = end_idx - start_idx;
+            for j in i..end_idx {
+                let frac = (j - start_idx) as f32 / count as f32;
+                positions[j] = Some(start_pos + (end_pos - start_pos) * frac);
+            }
+            i = end_idx;
+        } else {
+            i += 1;
+        }
+    }
+
+    // Build result
+    for (stop, pos) in stops.iter().zip(positions.iter()) {
+        let color: RenderColor = stop.color.into();
+        result.push((pos.unwrap_or(0.0), color));
+    }
+
+    result
+}
+
+/// Interpolate between color stops at position t (0.0 to 1.0)
+fn interpolate_color(stops: &[(f32, RenderColor)], t: f32) -> RenderColor {
+    if stops.is_empty() {
+        return RenderColor::black();
+    }
+    if stops.len() == 1 {
+        return stops[0].1;
+    }
+
+    let t = t.clamp(0.0, 1.0);
+
+    // Find surrounding stops
+    let mut prev = &stops[0];
+    let mut next = &stops[stops.len() - 1];
+
+    for i in 0..stops.len() - 1 {
+        if stops[i].0 <= t && t <= stops[i + 1].0 {
+            prev = &stops[i];
+            next = &stops[i + 1];
+            break;
+        }
+    }
+
+    // Interpolate between stops
+    let range = next.0 - prev.0;
+    let local_t = if range > 0.0 { (t - prev.0) / range } else { 0.0 };
+
+    RenderColor {
+        r: lerp_u8(prev.1.r, next.1.r, local_t),
+        g: lerp_u8(prev.1.g, next.1.g, local_t),
+        b: lerp_u8(prev.1.b, next.1.b, local_t),
+        a: lerp_u8(prev.1.a, next.1.a, local_t),
+    }
+}
+
+/// Linear interpolation for u8 values
+fn lerp_u8(a: u8, b: u8, t: f32) -> u8 {
+    let result = a as f32 + (b as f32 - a as f32) * t;
+    result.round().clamp(0.0, 255.0) as u8
+}
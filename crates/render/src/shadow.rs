@@ -0,0 +1,430 @@
+//! Box Shadows
+//!
+//! Shadows are drawn as coverage masks: the rounded shape casting the
+//! shadow is rasterized, blurred with three box blurs approximating a
+//! Gaussian, and cut to where the shadow shows, outside the border box for
+//! outset shadows and inside the padding box for inset ones. Shapes with a
+//! large blur are blurred at a lower resolution and scaled back up.
+
+use std::rc::Rc;
+
+use gugalanna_layout::Rect;
+use gugalanna_style::{BorderRadius, BoxShadow};
+
+/// Blur standard deviation past which masks are blurred downsampled
+const MAX_FULL_SIZE_SIGMA: f32 = 8.0;
+
+/// Number of masks kept for reuse
+const SHADOW_CACHE_SIZE: usize = 16;
+
+/// How far a blur spreads a shadow past its shape
+///
+/// The blur radius is twice the standard deviation of the Gaussian, which
+/// is cut off at three deviations.
+pub(crate) fn blur_extent(blur_radius: f32) -> f32 {
+    (blur_radius.max(0.0) * 1.5).ceil()
+}
+
+/// Area a shadow can paint over
+///
+/// `rect` is the border box of outset shadows and the padding box of inset
+/// ones, which is as far as they go.
+pub(crate) fn shadow_bounds(rect: &Rect, shadow: &BoxShadow) -> Rect {
+    if shadow.inset {
+        return *rect;
+    }
+    let extent = blur_extent(shadow.blur_radius) + shadow.spread_radius.max(0.0);
+    Rect::new(
+        rect.x + shadow.offset_x - extent,
+        rect.y + shadow.offset_y - extent,
+        rect.width + 2.0 * extent,
+        rect.height + 2.0 * extent,
+    )
+}
+
+/// Rectangle with a radius at each corner, in mask pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RoundedRect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    /// Top-left, top-right, bottom-right and bottom-left
+    radii: [f32; 4],
+}
+
+impl RoundedRect {
+    /// A rectangle with its radii clamped to half its size, as they are
+    /// drawn
+    fn new(rect: &Rect, radius: &BorderRadius) -> Self {
+        let max = (rect.width / 2.0).min(rect.height / 2.0).max(0.0);
+        let radii = [radius.top_left, radius.top_right, radius.bottom_right, radius.bottom_left];
+        RoundedRect {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width.max(0.0),
+            height: rect.height.max(0.0),
+            radii: radii.map(|r| r.clamp(0.0, max)),
+        }
+    }
+
+    /// The rectangle grown by `spread` on every side, rounded corners
+    /// growing with it
+    fn spread(&self, spread: f32) -> Self {
+        let width = (self.width + 2.0 * spread).max(0.0);
+        let height = (self.height + 2.0 * spread).max(0.0);
+        let max = (width / 2.0).min(height / 2.0);
+        RoundedRect {
+            x: self.x - spread,
+            y: self.y - spread,
+            width,
+            height,
+            radii: self.radii.map(|r| if r > 0.0 { (r + spread).clamp(0.0, max) } else { 0.0 }),
+        }
+    }
+
+    fn translate(&self, dx: f32, dy: f32) -> Self {
+        RoundedRect { x: self.x + dx, y: self.y + dy, ..*self }
+    }
+
+    fn scale(&self, factor: f32) -> Self {
+        RoundedRect {
+            x: self.x * factor,
+            y: self.y * factor,
+            width: self.width * factor,
+            height: self.height * factor,
+            radii: self.radii.map(|r| r * factor),
+        }
+    }
+
+    /// Fraction of the pixel at (px, py) inside the shape, from the distance
+    /// of its center to the edge
+    fn coverage(&self, px: u32, py: u32) -> f32 {
+        let (cx, cy) = (self.x + self.width / 2.0, self.y + self.height / 2.0);
+        let (x, y) = (px as f32 + 0.5 - cx, py as f32 + 0.5 - cy);
+        let radius = match (x < 0.0, y < 0.0) {
+            (true, true) => self.radii[0],
+            (false, true) => self.radii[1],
+            (false, false) => self.radii[2],
+            (true, false) => self.radii[3],
+        };
+        let qx = x.abs() - self.width / 2.0 + radius;
+        let qy = y.abs() - self.height / 2.0 + radius;
+        let distance = qx.max(qy).min(0.0) + qx.max(0.0).hypot(qy.max(0.0)) - radius;
+        (0.5 - distance).clamp(0.0, 1.0)
+    }
+
+    /// Rasterize into a mask of a size, 255 where it is covered
+    fn rasterize(&self, width: u32, height: u32) -> Vec<u8> {
+        let mut mask = Vec::with_capacity((width * height) as usize);
+        for py in 0..height {
+            for px in 0..width {
+                mask.push((self.coverage(px, py) * 255.0).round() as u8);
+            }
+        }
+        mask
+    }
+
+    /// Positions and radii in tenths of a pixel, to tell masks apart
+    fn key(&self) -> [i32; 8] {
+        let [a, b, c, d] = self.radii.map(tenths);
+        [tenths(self.x), tenths(self.y), tenths(self.width), tenths(self.height), a, b, c, d]
+    }
+}
+
+fn tenths(value: f32) -> i32 {
+    (value * 10.0).round() as i32
+}
+
+/// Everything a mask is made from, relative to its top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MaskParams {
+    width: u32,
+    height: u32,
+    /// Shape casting the shadow
+    shape: RoundedRect,
+    /// Box the shadow is drawn outside of (outset) or inside of (inset)
+    clip: RoundedRect,
+    sigma: f32,
+    inset: bool,
+}
+
+impl MaskParams {
+    fn key(&self) -> MaskKey {
+        MaskKey {
+            size: (self.width, self.height),
+            shape: self.shape.key(),
+            clip: self.clip.key(),
+            sigma: tenths(self.sigma),
+            inset: self.inset,
+        }
+    }
+
+    /// Rasterize the shape, blur it and cut it to the clip box
+    fn build(&self) -> Vec<u8> {
+        // Inset shadows are cast from past the edges of the mask, which are
+        // blurred in
+        let margin = if self.inset { blur_extent(self.sigma * 2.0) as u32 } else { 0 };
+        let (full_width, full_height) = (self.width + 2 * margin, self.height + 2 * margin);
+
+        // Blur at 1/scale of the size, the blurred mask is smooth enough to
+        // be scaled back up
+        let scale = if self.sigma > MAX_FULL_SIZE_SIGMA { (self.sigma / (MAX_FULL_SIZE_SIGMA / 2.0)).ceil() } else { 1.0 };
+        let width = (full_width as f32 / scale).ceil() as u32;
+        let height = (full_height as f32 / scale).ceil() as u32;
+
+        let shape = self.shape.translate(margin as f32, margin as f32);
+        let mut mask = shape.scale(1.0 / scale).rasterize(width, height);
+        if self.inset {
+            // The shadow is cast by everything around the shape
+            mask.iter_mut().for_each(|value| *value = 255 - *value);
+        }
+        let radius = (self.sigma / scale).round() as usize;
+        if radius > 0 {
+            blur(&mut mask, width as usize, height as usize, radius);
+        }
+        if scale > 1.0 {
+            mask = upscale(&mask, width, height, full_width, full_height);
+        }
+        if margin > 0 {
+            let (margin, full_width) = (margin as usize, full_width as usize);
+            mask = (0..self.height as usize)
+                .flat_map(|y| {
+                    let start = (y + margin) * full_width + margin;
+                    mask[start..start + self.width as usize].to_vec()
+                })
+                .collect();
+        }
+
+        let clip = self.clip.rasterize(self.width, self.height);
+        for (value, clip) in mask.iter_mut().zip(clip) {
+            let visible = if self.inset { clip } else { 255 - clip };
+            *value = ((*value as u32 * visible as u32 + 127) / 255) as u8;
+        }
+        mask
+    }
+}
+
+/// Three box blurs of `radius` across then down the mask, edges extended
+///
+/// Three boxes of radius r have about the variance of a Gaussian with a
+/// standard deviation of r.
+fn blur(mask: &mut [u8], width: usize, height: usize, radius: usize) {
+    let mut line = Vec::new();
+    for _ in 0..3 {
+        for y in 0..height {
+            box_blur_line(mask, y * width, 1, width, radius, &mut line);
+        }
+        for x in 0..width {
+            box_blur_line(mask, x, width, height, radius, &mut line);
+        }
+    }
+}
+
+/// Blur `len` values `stride` apart from `start` with a sliding window
+fn box_blur_line(mask: &mut [u8], start: usize, stride: usize, len: usize, radius: usize, line: &mut Vec<u8>) {
+    line.clear();
+    line.extend((0..len).map(|i| mask[start + i * stride]));
+    let at = |i: isize| line[i.clamp(0, len as isize - 1) as usize] as u32;
+
+    let diameter = 2 * radius as u32 + 1;
+    let mut sum: u32 = (-(radius as isize)..=radius as isize).map(at).sum();
+    for i in 0..len {
+        mask[start + i * stride] = ((sum + diameter / 2) / diameter) as u8;
+        sum = sum + at(i as isize + radius as isize + 1) - at(i as isize - radius as isize);
+    }
+}
+
+/// Scale a mask up bilinearly
+fn upscale(mask: &[u8], width: u32, height: u32, to_width: u32, to_height: u32) -> Vec<u8> {
+    let sample = |i: u32, size: u32, to: u32| {
+        let position = ((i as f32 + 0.5) * size as f32 / to as f32 - 0.5).clamp(0.0, (size - 1) as f32);
+        let first = position.floor() as usize;
+        (first, (first + 1).min(size as usize - 1), position - first as f32)
+    };
+    let columns: Vec<_> = (0..to_width).map(|x| sample(x, width, to_width)).collect();
+    let mut scaled = Vec::with_capacity((to_width * to_height) as usize);
+    for y in 0..to_height {
+        let (y0, y1, fy) = sample(y, height, to_height);
+        let (top, bottom) = (&mask[y0 * width as usize..], &mask[y1 * width as usize..]);
+        for &(x0, x1, fx) in &columns {
+            let lerp = |row: &[u8]| row[x0] as f32 * (1.0 - fx) + row[x1] as f32 * fx;
+            scaled.push((lerp(top) * (1.0 - fy) + lerp(bottom) * fy).round() as u8);
+        }
+    }
+    scaled
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MaskKey {
+    size: (u32, u32),
+    shape: [i32; 8],
+    clip: [i32; 8],
+    sigma: i32,
+    inset: bool,
+}
+
+/// Coverage of a shadow at a pixel position
+pub(crate) struct ShadowMask {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub coverage: Rc<Vec<u8>>,
+}
+
+/// The most recently drawn shadow masks, so boxes with the same shadow,
+/// or a shadow drawn again, aren't blurred again
+#[derive(Default)]
+pub(crate) struct ShadowCache {
+    /// Most recently used last
+    masks: Vec<(MaskKey, Rc<Vec<u8>>)>,
+}
+
+impl ShadowCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mask of a shadow cast by a box
+    ///
+    /// `rect` and `radius` are the border box of outset shadows and the
+    /// padding box of inset ones. None when nothing of the shadow shows.
+    pub fn mask(&mut self, rect: &Rect, radius: &BorderRadius, shadow: &BoxShadow) -> Option<ShadowMask> {
+        let bounds = shadow_bounds(rect, shadow);
+        let (x, y) = (bounds.x.floor(), bounds.y.floor());
+        let width = ((bounds.x + bounds.width).ceil() - x) as u32;
+        let height = ((bounds.y + bounds.height).ceil() - y) as u32;
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let clip = RoundedRect::new(rect, radius).translate(-x, -y);
+        let spread = if shadow.inset { -shadow.spread_radius } else { shadow.spread_radius };
+        let shape = clip.translate(shadow.offset_x, shadow.offset_y).spread(spread);
+        let params = MaskParams { width, height, shape, clip, sigma: shadow.blur_radius.max(0.0) / 2.0, inset: shadow.inset };
+
+        let key = params.key();
+        let coverage = match self.masks.iter().position(|(cached, _)| *cached == key) {
+            Some(i) => {
+                let entry = self.masks.remove(i);
+                self.masks.push(entry);
+                self.masks[self.masks.len() - 1].1.clone()
+            }
+            None => {
+                let coverage = Rc::new(params.build());
+                if self.masks.len() == SHADOW_CACHE_SIZE {
+                    self.masks.remove(0);
+                }
+                self.masks.push((key, coverage.clone()));
+                coverage
+            }
+        };
+        Some(ShadowMask { x: x as i32, y: y as i32, width, height, coverage })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_css::Color;
+
+    fn shadow(blur_radius: f32, spread_radius: f32, inset: bool) -> BoxShadow {
+        BoxShadow { offset_x: 0.0, offset_y: 0.0, blur_radius, spread_radius, color: Color::rgb(0, 0, 0), inset }
+    }
+
+    fn at(mask: &ShadowMask, x: i32, y: i32) -> u8 {
+        mask.coverage[((y - mask.y) as u32 * mask.width + (x - mask.x) as u32) as usize]
+    }
+
+    #[test]
+    fn test_blur_keeps_total_coverage() {
+        let mut mask = vec![0; 41];
+        mask[20] = 255;
+        blur(&mut mask, 41, 1, 3);
+
+        // Spread out into a bell, without losing much to rounding
+        let total: u32 = mask.iter().map(|&v| v as u32).sum();
+        assert!(total.abs_diff(255) <= 20, "{}", total);
+        assert!(mask[20] > mask[23] && mask[23] > mask[26] && mask[26] > 0);
+        assert_eq!(mask[20 - 3], mask[20 + 3]);
+    }
+
+    #[test]
+    fn test_outset_shadow_fades_outside_box() {
+        let mut cache = ShadowCache::new();
+        let rect = Rect::new(20.0, 20.0, 40.0, 40.0);
+        let mask = cache.mask(&rect, &BorderRadius::default(), &shadow(10.0, 0.0, false)).unwrap();
+
+        // Nothing under the box, half the shadow at its edge, fading away
+        assert_eq!((mask.x, mask.y, mask.width), (5, 5, 70));
+        assert_eq!(at(&mask, 40, 40), 0);
+        assert!(at(&mask, 19, 40).abs_diff(128) < 20, "{}", at(&mask, 19, 40));
+        assert!(at(&mask, 19, 40) > at(&mask, 14, 40) && at(&mask, 14, 40) > at(&mask, 9, 40));
+        assert!(at(&mask, 5, 40) < 8);
+    }
+
+    #[test]
+    fn test_rounded_outset_shadow_follows_corners() {
+        let mut cache = ShadowCache::new();
+        let rect = Rect::new(0.0, 0.0, 40.0, 40.0);
+        let round = BorderRadius { top_left: 20.0, top_right: 20.0, bottom_right: 20.0, bottom_left: 20.0 };
+        let mask = cache.mask(&rect, &round, &shadow(0.0, 4.0, false)).unwrap();
+
+        // The spread ring is solid beside the circle and empty in the corner
+        // of its bounding box
+        assert_eq!(at(&mask, -2, 20), 255);
+        assert_eq!(at(&mask, -3, -3), 0);
+        assert_eq!(at(&mask, 4, 4), 255);
+        assert_eq!(at(&mask, 20, 20), 0);
+    }
+
+    #[test]
+    fn test_inset_shadow_inside_padding_box() {
+        let mut cache = ShadowCache::new();
+        let rect = Rect::new(10.0, 10.0, 60.0, 60.0);
+        let round = BorderRadius { top_left: 10.0, top_right: 10.0, bottom_right: 10.0, bottom_left: 10.0 };
+        let mask = cache.mask(&rect, &round, &shadow(8.0, 2.0, true)).unwrap();
+
+        // Dark along the inner edge, clear in the middle, nothing in the
+        // rounded off corner
+        assert_eq!((mask.x, mask.y, mask.width, mask.height), (10, 10, 60, 60));
+        assert!(at(&mask, 10, 40) > 150, "{}", at(&mask, 10, 40));
+        assert_eq!(at(&mask, 40, 40), 0);
+        assert_eq!(at(&mask, 10, 10), 0);
+        assert!(at(&mask, 11, 40) > at(&mask, 16, 40) && at(&mask, 16, 40) > at(&mask, 22, 40));
+    }
+
+    #[test]
+    fn test_large_blur_downsampled_like_full_size() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 60.0);
+        let large = shadow(40.0, 0.0, false);
+        let downsampled = ShadowCache::new().mask(&rect, &BorderRadius::default(), &large).unwrap();
+
+        // Compare with the mask blurred at full resolution
+        let bounds = shadow_bounds(&rect, &large);
+        let clip = RoundedRect::new(&rect, &BorderRadius::default()).translate(-bounds.x, -bounds.y);
+        let shape = clip;
+        let full_size = shape.rasterize(bounds.width as u32, bounds.height as u32);
+        let mut full_size = full_size;
+        blur(&mut full_size, bounds.width as usize, bounds.height as usize, 20);
+        let y = (30.0 - bounds.y) as usize * bounds.width as usize;
+        for x in [0, 30, 55, 59] {
+            let (a, b) = (downsampled.coverage[y + x], full_size[y + x]);
+            assert!(a.abs_diff(b) <= 12, "{} vs {} at {}", a, b, x);
+        }
+    }
+
+    #[test]
+    fn test_same_shadow_reuses_mask() {
+        let mut cache = ShadowCache::new();
+        let radius = BorderRadius::default();
+        let first = cache.mask(&Rect::new(0.0, 0.0, 30.0, 20.0), &radius, &shadow(6.0, 0.0, false)).unwrap();
+        let moved = cache.mask(&Rect::new(100.0, 50.0, 30.0, 20.0), &radius, &shadow(6.0, 0.0, false)).unwrap();
+        assert!(Rc::ptr_eq(&first.coverage, &moved.coverage));
+        assert_eq!((moved.x, moved.y), (91, 41));
+
+        let wider = cache.mask(&Rect::new(0.0, 0.0, 31.0, 20.0), &radius, &shadow(6.0, 0.0, false)).unwrap();
+        assert!(!Rc::ptr_eq(&first.coverage, &wider.coverage));
+    }
+}
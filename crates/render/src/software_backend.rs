@@ -0,0 +1,579 @@
+//! Offscreen Render Backend
+//!
+//! Rasterizes display lists into an RGBA pixel buffer without opening a
+//! window, for screenshots and tests.
+
+use std::path::Path;
+
+use gugalanna_layout::{ImagePixels, Rect};
+use gugalanna_style::Transform;
+
+use crate::display_list::DisplayList;
+use crate::font::FontCache;
+use crate::paint::RenderColor;
+use crate::painter::{Painter, PixelRect};
+use crate::shadow::ShadowCache;
+use crate::stacking::transform_rect;
+use crate::RenderBackend;
+
+/// Render backend drawing into memory
+pub struct SoftwareBackend {
+    width: u32,
+    height: u32,
+    /// RGBA pixel data, 4 bytes per pixel, row by row
+    pixels: Vec<u8>,
+    font_cache: FontCache,
+    clip: Option<PixelRect>,
+    /// Area being repainted, which clip rects are narrowed to
+    damage_clip: Option<PixelRect>,
+    /// Stack of opacity modifiers (multiplied together)
+    opacity_stack: Vec<f32>,
+    shadow_cache: ShadowCache,
+    /// Pixels of the surface and layers under the one drawn into
+    layers: Vec<Vec<u8>>,
+    /// Buffers of popped layers, reused for the next ones
+    layer_pool: Vec<Vec<u8>>,
+}
+
+/// Most layer buffers kept for reuse
+const LAYER_POOL_SIZE: usize = 4;
+
+impl SoftwareBackend {
+    /// Create a transparent surface of a size
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut font_cache = FontCache::new();
+        font_cache.load_system_faces();
+
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+            font_cache,
+            clip: None,
+            damage_clip: None,
+            opacity_stack: Vec::new(),
+            shadow_cache: ShadowCache::new(),
+            layers: Vec::new(),
+            layer_pool: Vec::new(),
+        }
+    }
+
+    /// RGBA pixel data, 4 bytes per pixel, row by row
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Color of a pixel, None outside of the surface
+    pub fn pixel(&self, x: u32, y: u32) -> Option<RenderColor> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = ((y * self.width + x) * 4) as usize;
+        let [r, g, b, a] = self.pixels[i..i + 4] else {
+            return None;
+        };
+        Some(RenderColor::new(r, g, b, a))
+    }
+
+    /// Encode the pixels as a PNG file
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        let image = image::RgbaImage::from_raw(self.width, self.height, self.pixels.clone())
+            .ok_or("Pixel data doesn't match the surface size")?;
+        image
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Part of a rectangle on the surface and inside the clip rect
+    fn visible(&self, rect: PixelRect) -> Option<PixelRect> {
+        let surface = PixelRect::new(0, 0, self.width, self.height);
+        let rect = rect.intersection(surface)?;
+        match self.clip {
+            Some(clip) => rect.intersection(clip),
+            None => Some(rect),
+        }
+    }
+
+    /// Pixel of a layer, transparent outside of the surface
+    fn layer_pixel(&self, layer: &[u8], x: i32, y: i32) -> [u8; 4] {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return [0; 4];
+        }
+        let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+        [layer[i], layer[i + 1], layer[i + 2], layer[i + 3]]
+    }
+
+    /// Color of a layer at a point, blended between the four pixels
+    /// around it
+    fn sample_layer(&self, layer: &[u8], x: f32, y: f32) -> [u8; 4] {
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (left, top) = (x.floor(), y.floor());
+        let (fx, fy) = (x - left, y - top);
+        let (left, top) = (left as i32, top as i32);
+        let mut color = [0.0f32; 4];
+        for (dx, dy, weight) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+            let pixel = self.layer_pixel(layer, left + dx, top + dy);
+            for (channel, value) in color.iter_mut().zip(pixel) {
+                *channel += value as f32 * weight;
+            }
+        }
+        color.map(|channel| channel.round() as u8)
+    }
+
+    /// Blend a color over a pixel known to be on the surface
+    fn blend(&mut self, x: i32, y: i32, color: RenderColor) {
+        let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+        let alpha = color.a as u32;
+        let pixel = &mut self.pixels[i..i + 4];
+        for (channel, source) in pixel.iter_mut().zip([color.r, color.g, color.b]) {
+            *channel = ((source as u32 * alpha + *channel as u32 * (255 - alpha)) / 255) as u8;
+        }
+        pixel[3] = (alpha + pixel[3] as u32 * (255 - alpha) / 255) as u8;
+    }
+}
+
+impl Painter for SoftwareBackend {
+    fn draw_rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: RenderColor) {
+        let Some(rect) = self.visible(PixelRect::new(x, y, w, h)) else {
+            return;
+        };
+        for py in rect.y..rect.y + rect.height as i32 {
+            for px in rect.x..rect.x + rect.width as i32 {
+                self.blend(px, py, color);
+            }
+        }
+    }
+
+    fn draw_glyph_bitmap(&mut self, bitmap: &[u8], width: u32, height: u32, x: i32, y: i32, color: RenderColor) {
+        let Some(rect) = self.visible(PixelRect::new(x, y, width, height)) else {
+            return;
+        };
+        for py in rect.y..rect.y + rect.height as i32 {
+            for px in rect.x..rect.x + rect.width as i32 {
+                let coverage = bitmap.get(((py - y) as u32 * width + (px - x) as u32) as usize).copied().unwrap_or(0);
+                let alpha = (coverage as u32 * color.a as u32 / 255) as u8;
+                if alpha > 0 {
+                    self.blend(px, py, RenderColor { a: alpha, ..color });
+                }
+            }
+        }
+    }
+
+    /// Stretch the image to the rectangle by nearest neighbor
+    fn draw_image_pixels(&mut self, img: &ImagePixels, x: i32, y: i32, w: u32, h: u32) -> bool {
+        if img.data.len() < (img.width * img.height * 4) as usize {
+            return false;
+        }
+        let Some(rect) = self.visible(PixelRect::new(x, y, w, h)) else {
+            return true;
+        };
+        for py in rect.y..rect.y + rect.height as i32 {
+            let sy = ((py - y) as u64 * img.height as u64 / h as u64) as u32;
+            for px in rect.x..rect.x + rect.width as i32 {
+                let sx = ((px - x) as u64 * img.width as u64 / w as u64) as u32;
+                let i = ((sy * img.width + sx) * 4) as usize;
+                let [r, g, b, a] = img.data[i..i + 4] else {
+                    continue;
+                };
+                self.blend(px, py, RenderColor::new(r, g, b, a));
+            }
+        }
+        true
+    }
+
+    fn clip_rect(&self) -> Option<PixelRect> {
+        self.clip
+    }
+
+    fn set_clip_rect(&mut self, clip: Option<PixelRect>) {
+        self.clip = clip;
+    }
+
+    fn base_clip(&self) -> Option<PixelRect> {
+        self.damage_clip
+    }
+
+    fn set_base_clip(&mut self, clip: Option<PixelRect>) {
+        self.damage_clip = clip;
+    }
+
+    fn font_cache(&mut self) -> &mut FontCache {
+        &mut self.font_cache
+    }
+
+    fn opacity_stack(&mut self) -> &mut Vec<f32> {
+        &mut self.opacity_stack
+    }
+
+    fn shadow_cache(&mut self) -> &mut ShadowCache {
+        &mut self.shadow_cache
+    }
+
+    fn push_layer(&mut self) -> bool {
+        let mut layer = self.layer_pool.pop().unwrap_or_default();
+        layer.clear();
+        layer.resize(self.pixels.len(), 0);
+        self.layers.push(std::mem::replace(&mut self.pixels, layer));
+        true
+    }
+
+    /// Blend the layer's pixels, which start out transparent and so hold
+    /// premultiplied colors, over the ones under it
+    ///
+    /// Transformed layers are sampled where each pixel they cover comes
+    /// from.
+    fn pop_layer(&mut self, opacity: f32, transform: &Transform) {
+        let Some(below) = self.layers.pop() else {
+            return;
+        };
+        let layer = std::mem::replace(&mut self.pixels, below);
+        let opacity = (opacity.clamp(0.0, 1.0) * 255.0).round() as u32;
+        let surface = Rect::new(0.0, 0.0, self.width as f32, self.height as f32);
+        let covered = PixelRect::enclosing(&transform_rect(transform, &surface));
+        if let (Some(rect), Some(inverse)) = (self.visible(covered), transform.inverse()) {
+            for y in rect.y..rect.y + rect.height as i32 {
+                for x in rect.x..rect.x + rect.width as i32 {
+                    let source = if transform.is_identity() {
+                        self.layer_pixel(&layer, x, y)
+                    } else {
+                        let (lx, ly) = inverse.apply(x as f32 + 0.5, y as f32 + 0.5);
+                        self.sample_layer(&layer, lx, ly)
+                    };
+                    if source[3] == 0 {
+                        continue;
+                    }
+                    let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+                    let alpha = source[3] as u32 * opacity / 255;
+                    let pixel = &mut self.pixels[i..i + 4];
+                    for (channel, &source) in pixel.iter_mut().zip(&source[..3]) {
+                        *channel = ((source as u32 * opacity + *channel as u32 * (255 - alpha)) / 255) as u8;
+                    }
+                    pixel[3] = (alpha + pixel[3] as u32 * (255 - alpha) / 255) as u8;
+                }
+            }
+        }
+        if self.layer_pool.len() < LAYER_POOL_SIZE {
+            self.layer_pool.push(layer);
+        }
+    }
+}
+
+impl RenderBackend for SoftwareBackend {
+    fn clear(&mut self, color: RenderColor) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    fn render(&mut self, display_list: &DisplayList) {
+        self.paint(display_list);
+    }
+
+    fn present(&mut self) {
+        // Nothing is shown, the pixels are read back instead
+    }
+
+    /// Paint over the part of the surface that changed, the rest of the
+    /// previous frame is still in the buffer
+    fn repaint(&mut self, display_list: &DisplayList, damage: Rect, background: RenderColor) {
+        let surface = Rect::new(0.0, 0.0, self.width as f32, self.height as f32);
+        let damage = damage.intersect(&surface);
+        if !damage.is_empty() {
+            self.paint_damage(display_list, &damage, background);
+        }
+    }
+
+    fn font_cache_mut(&mut self) -> &mut FontCache {
+        &mut self.font_cache
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_list::{build_display_list, BorderColors, BorderStyles, BorderWidths, PaintCommand};
+    use gugalanna_css::Stylesheet;
+    use gugalanna_dom::Queryable;
+    use gugalanna_html::HtmlParser;
+    use gugalanna_layout::{build_layout_tree, layout_document, ContainingBlock};
+    use gugalanna_css::Color;
+    use gugalanna_style::{BackgroundRepeat, BorderRadius, BoxShadow, Cascade, ImageRendering, StyleTree};
+
+    #[test]
+    fn test_render_page_offscreen() {
+        let dom = HtmlParser::new()
+            .parse("<body><div id='box'></div><p>Hello</p></body>")
+            .unwrap();
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse(
+                "body { margin: 0; } \
+                 #box { width: 40px; height: 30px; margin: 10px; background-color: #ff0000; } \
+                 p { margin: 0 10px; font-size: 20px; line-height: 24px; color: #0000ff; }",
+            )
+            .unwrap(),
+        );
+        let style_tree = StyleTree::build(&dom, &cascade, 100.0, 100.0);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(100.0, 100.0));
+
+        let mut backend = SoftwareBackend::new(100, 100);
+        backend.clear(RenderColor::white());
+        backend.render(&build_display_list(&layout));
+
+        // The box is filled, its margin isn't
+        assert_eq!(backend.pixel(10, 10), Some(RenderColor::rgb(255, 0, 0)));
+        assert_eq!(backend.pixel(49, 39), Some(RenderColor::rgb(255, 0, 0)));
+        assert_eq!(backend.pixel(5, 5), Some(RenderColor::white()));
+        assert_eq!(backend.pixel(50, 20), Some(RenderColor::white()));
+
+        // The text under it is drawn in blue glyphs
+        let text_pixels = (50..74)
+            .flat_map(|y| (10..70).map(move |x| (x, y)))
+            .filter_map(|(x, y)| backend.pixel(x, y))
+            .filter(|pixel| pixel.b == 255 && pixel.r < 128)
+            .count();
+        assert!(text_pixels > 20, "{} text pixels", text_pixels);
+    }
+
+    #[test]
+    fn test_repaint_keeps_undamaged_pixels() {
+        let fill = |color| DisplayList {
+            commands: vec![PaintCommand::FillRect { rect: Rect::new(0.0, 0.0, 10.0, 10.0), color }],
+        };
+        let mut backend = SoftwareBackend::new(10, 10);
+        backend.repaint(&fill(RenderColor::black()), Rect::new(0.0, 0.0, 10.0, 10.0), RenderColor::white());
+
+        // Only the damaged half takes the new color
+        backend.repaint(&fill(RenderColor::rgb(255, 0, 0)), Rect::new(0.0, 0.0, 4.5, 10.0), RenderColor::white());
+        assert_eq!(backend.pixel(4, 5), Some(RenderColor::rgb(255, 0, 0)));
+        assert_eq!(backend.pixel(5, 5), Some(RenderColor::black()));
+    }
+
+    #[test]
+    fn test_draw_image_filter() {
+        let draw = |rendering| {
+            let checkerboard = ImagePixels {
+                width: 2,
+                height: 2,
+                data: vec![0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255, 0, 0, 0, 255].into(),
+            };
+            let mut backend = SoftwareBackend::new(8, 8);
+            backend.render(&DisplayList {
+                commands: vec![PaintCommand::DrawImage {
+                    rect: Rect::new(0.0, 0.0, 8.0, 8.0),
+                    pixels: Some(checkerboard),
+                    alt: String::new(),
+                    clip: None,
+                    rendering,
+                }],
+            });
+            (0..8).map(|x| backend.pixel(x, 1).unwrap().r).collect::<Vec<_>>()
+        };
+
+        // Pixelated squares stay sharp, smooth ones blend across the middle
+        assert_eq!(draw(ImageRendering::Pixelated), [0, 0, 0, 0, 255, 255, 255, 255]);
+        let smooth = draw(ImageRendering::Auto);
+        assert_eq!((smooth[0], smooth[7]), (0, 255));
+        assert!(smooth[3] > 0 && smooth[4] < 255, "{:?}", smooth);
+    }
+
+    #[test]
+    fn test_tiled_image_clipped_to_box() {
+        // An 8x8 tile, red in its first column, repeated across a 50x30 box
+        let data: Vec<u8> = (0..64)
+            .flat_map(|i| if i % 8 == 0 { [255, 0, 0, 255] } else { [255, 255, 255, 255] })
+            .collect();
+        let mut backend = SoftwareBackend::new(60, 40);
+        backend.render(&DisplayList {
+            commands: vec![PaintCommand::DrawTiledImage {
+                tile: Rect::new(2.0, 0.0, 8.0, 8.0),
+                pixels: ImagePixels { width: 8, height: 8, data: data.into() },
+                repeat: BackgroundRepeat::RepeatX,
+                clip: Rect::new(0.0, 0.0, 50.0, 30.0),
+                rendering: ImageRendering::Auto,
+            }],
+        });
+
+        let red: Vec<u32> = (0..60).filter(|&x| backend.pixel(x, 4) == Some(RenderColor::rgb(255, 0, 0))).collect();
+        assert_eq!(red, [2, 10, 18, 26, 34, 42]);
+        // The tile before the positioned one reaches into the box
+        assert_eq!(backend.pixel(0, 4), Some(RenderColor::white()));
+        // Nothing past the box or below the row
+        assert_eq!(backend.pixel(55, 4), Some(RenderColor::transparent()));
+        assert_eq!(backend.pixel(4, 12), Some(RenderColor::transparent()));
+    }
+
+    #[test]
+    fn test_box_shadows_on_rounded_box() {
+        let radius = BorderRadius { top_left: 15.0, top_right: 15.0, bottom_right: 15.0, bottom_left: 15.0 };
+        let draw = |inset| {
+            let shadow = BoxShadow {
+                offset_x: 0.0,
+                offset_y: 0.0,
+                blur_radius: 8.0,
+                spread_radius: 0.0,
+                color: Color::rgb(0, 0, 0),
+                inset,
+            };
+            let mut backend = SoftwareBackend::new(100, 100);
+            backend.clear(RenderColor::white());
+            backend.render(&DisplayList {
+                commands: vec![PaintCommand::DrawBoxShadow { rect: Rect::new(20.0, 20.0, 60.0, 60.0), radius, shadow }],
+            });
+            move |x, y| backend.pixel(x, y).unwrap().r
+        };
+
+        // Outset: darkest at the box, fading out, lighter off the rounded
+        // corner than off a side, and nothing under the box
+        let outset = draw(false);
+        assert!(outset(19, 50) < outset(14, 50) && outset(14, 50) < outset(8, 50));
+        assert!(outset(16, 16) > outset(16, 50));
+        assert_eq!(outset(50, 50), 255);
+        assert_eq!(outset(2, 50), 255);
+
+        // Inset: darkest along the inside of the edge, clear in the middle
+        // and outside of the box
+        let inset = draw(true);
+        assert!(inset(20, 50) < inset(25, 50) && inset(25, 50) < inset(30, 50));
+        assert_eq!(inset(50, 50), 255);
+        assert_eq!(inset(19, 50), 255);
+        assert_eq!(inset(20, 20), 255);
+    }
+
+    #[test]
+    fn test_border_triangle() {
+        // A box without size whose only visible border is the bottom one
+        // is a triangle pointing up
+        let red = RenderColor::rgb(255, 0, 0);
+        let mut backend = SoftwareBackend::new(100, 100);
+        backend.clear(RenderColor::white());
+        backend.render(&DisplayList {
+            commands: vec![PaintCommand::DrawBorder {
+                rect: Rect::new(0.0, 0.0, 100.0, 50.0),
+                widths: BorderWidths { top: 0.0, right: 50.0, bottom: 50.0, left: 50.0 },
+                styles: BorderStyles::default(),
+                colors: BorderColors { bottom: red, ..BorderColors::all(RenderColor::transparent()) },
+            }],
+        });
+
+        let pixel = |x, y| backend.pixel(x, y).unwrap();
+        assert_eq!(pixel(50, 45), red);
+        assert_eq!(pixel(50, 2), red);
+        assert_eq!(pixel(10, 45), red);
+        assert_eq!(pixel(10, 10), RenderColor::white());
+        assert_eq!(pixel(90, 10), RenderColor::white());
+    }
+
+    #[test]
+    fn test_clip_and_blend() {
+        let mut backend = SoftwareBackend::new(10, 10);
+        backend.clear(RenderColor::white());
+        backend.set_clip_rect(Some(PixelRect::new(0, 0, 5, 10)));
+        backend.draw_rect(0, 0, 10, 10, RenderColor::new(0, 0, 0, 128));
+
+        // Half covered black over white, and nothing past the clip
+        assert_eq!(backend.pixel(2, 2), Some(RenderColor::new(127, 127, 127, 255)));
+        assert_eq!(backend.pixel(7, 2), Some(RenderColor::white()));
+        assert_eq!(backend.pixel(10, 2), None);
+    }
+
+    #[test]
+    fn test_nested_clips_intersect() {
+        let black = RenderColor::rgb(0, 0, 0);
+        let fill = |x, y| PaintCommand::FillRect { rect: Rect::new(x, y, 2.0, 2.0), color: black };
+        let mut backend = SoftwareBackend::new(20, 20);
+        backend.clear(RenderColor::white());
+        backend.render(&DisplayList {
+            commands: vec![
+                PaintCommand::PushClip(Rect::new(0.0, 0.0, 10.0, 10.0)),
+                // The inner clip reaches past the outer one on the right
+                PaintCommand::PushClip(Rect::new(5.0, 5.0, 10.0, 10.0)),
+                fill(2.0, 6.0),
+                fill(6.0, 6.0),
+                fill(11.0, 6.0),
+                PaintCommand::PopClip,
+                fill(2.0, 2.0),
+                fill(12.0, 2.0),
+                PaintCommand::PopClip,
+                fill(16.0, 16.0),
+            ],
+        });
+
+        // Only the intersection is painted inside the inner clip
+        assert_eq!(backend.pixel(2, 6), Some(RenderColor::white()));
+        assert_eq!(backend.pixel(6, 6), Some(black));
+        assert_eq!(backend.pixel(11, 6), Some(RenderColor::white()));
+        // Popping it restores the outer clip, then no clip at all
+        assert_eq!(backend.pixel(2, 2), Some(black));
+        assert_eq!(backend.pixel(12, 2), Some(RenderColor::white()));
+        assert_eq!(backend.pixel(16, 16), Some(black));
+    }
+
+    #[test]
+    fn test_opacity_group_composited_once() {
+        let red = RenderColor::rgb(255, 0, 0);
+        let group = |opacity| {
+            vec![
+                PaintCommand::PushOpacity(opacity),
+                PaintCommand::FillRect { rect: Rect::new(0.0, 0.0, 6.0, 10.0), color: red },
+                PaintCommand::FillRect { rect: Rect::new(4.0, 0.0, 6.0, 10.0), color: red },
+                PaintCommand::PopOpacity,
+            ]
+        };
+        let mut backend = SoftwareBackend::new(10, 10);
+        backend.clear(RenderColor::white());
+        backend.render(&DisplayList { commands: group(0.5) });
+
+        // Where the children overlap is as translucent as where they don't
+        let pixel = |backend: &SoftwareBackend, x| backend.pixel(x, 5).unwrap();
+        assert_eq!(pixel(&backend, 2), RenderColor::new(255, 127, 127, 255));
+        assert_eq!(pixel(&backend, 5), pixel(&backend, 2));
+        assert_eq!(pixel(&backend, 8), pixel(&backend, 2));
+
+        // Nested groups multiply
+        let mut nested = group(0.5);
+        nested.insert(0, PaintCommand::PushOpacity(0.5));
+        nested.insert(1, PaintCommand::FillRect { rect: Rect::new(0.0, 0.0, 1.0, 10.0), color: red });
+        nested.push(PaintCommand::PopOpacity);
+        let mut backend = SoftwareBackend::new(10, 10);
+        backend.clear(RenderColor::white());
+        backend.render(&DisplayList { commands: nested });
+        assert_eq!(pixel(&backend, 5), pixel(&backend, 2));
+        assert_eq!(pixel(&backend, 2), RenderColor::new(255, 191, 191, 255));
+        assert_eq!(pixel(&backend, 0), RenderColor::new(255, 127, 127, 255));
+    }
+
+    #[test]
+    fn test_transform_rotates_group() {
+        use gugalanna_style::TransformFunction;
+
+        // A 20x10 box turned a quarter about its center at (20, 5)
+        let red = RenderColor::rgb(255, 0, 0);
+        let turn = Transform::of_box(&[TransformFunction::Rotate(90.0)], 10.0, 0.0, 20.0, 10.0);
+        let mut backend = SoftwareBackend::new(40, 20);
+        backend.clear(RenderColor::white());
+        backend.render(&DisplayList {
+            commands: vec![
+                PaintCommand::PushTransform(turn),
+                PaintCommand::FillRect { rect: Rect::new(10.0, 0.0, 20.0, 10.0), color: red },
+                PaintCommand::PopTransform,
+            ],
+        });
+
+        let pixel = |x, y| backend.pixel(x, y).unwrap();
+        assert_eq!(pixel(20, 12), red);
+        assert_eq!(pixel(16, 2), red);
+        assert_eq!(pixel(12, 5), RenderColor::white());
+        assert_eq!(pixel(27, 5), RenderColor::white());
+    }
+}
@@ -0,0 +1,313 @@
+//! Stacking Contexts
+//!
+//! Orders boxes for painting. Positioned boxes, floats, translucent and
+//! transformed boxes are painted as layers on top of the normal flow around them,
+//! positioned ones sorted by z-index. Every positioned box is treated as a
+//! stacking context, so z-index only orders siblings within the nearest
+//! positioned ancestor.
+
+use gugalanna_dom::NodeId;
+use gugalanna_layout::{LayoutBox, Rect};
+use gugalanna_style::Transform;
+
+/// A box painted as a unit, with the layers among its descendants
+#[derive(Debug)]
+pub struct Layer<'b, 'a> {
+    pub layout_box: &'b LayoutBox<'a>,
+    /// Absolute position of the parent's content box
+    pub offset_x: f32,
+    pub offset_y: f32,
+    /// Clip rect of the overflow clipping ancestors between this layer and
+    /// the layer it belongs to
+    pub clip: Option<Rect>,
+    /// Scroll containers between this layer and the layer it belongs to,
+    /// outermost first
+    pub scrollers: Vec<NodeId>,
+    /// Inside a position: fixed box
+    pub fixed: bool,
+    /// Layers among the box's descendants, in tree order
+    pub children: Vec<Layer<'b, 'a>>,
+}
+
+/// A box in paint order, with the absolute position of its parent's content box
+#[derive(Debug, Clone, Copy)]
+pub struct PaintedBox<'b, 'a> {
+    pub layout_box: &'b LayoutBox<'a>,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    /// Inside a position: fixed box
+    pub fixed: bool,
+    /// Area the box is clipped to by its overflow clipping ancestors
+    pub clip: Option<Rect>,
+    /// Innermost scroll container the box moves with when it is scrolled
+    pub scroller: Option<NodeId>,
+    /// Transforms of the box and its transformed ancestors together, from
+    /// where the box is laid out to where it is painted
+    ///
+    /// The clip is where the box is laid out too.
+    pub transform: Option<Transform>,
+}
+
+/// Check if a box is painted as a layer rather than with the normal flow
+pub fn is_layer(layout_box: &LayoutBox) -> bool {
+    layout_box.is_positioned()
+        || layout_box.is_floated()
+        || layout_box.style().is_some_and(|s| s.opacity < 1.0 || !s.transform.is_empty())
+}
+
+/// Transform of a box's `transform` property, None when it has none
+///
+/// `offset_x` and `offset_y` are the absolute position of the parent's
+/// content box.
+pub fn box_transform(layout_box: &LayoutBox, offset_x: f32, offset_y: f32) -> Option<Transform> {
+    let functions = &layout_box.style()?.transform;
+    if functions.is_empty() {
+        return None;
+    }
+    let border = layout_box.dimensions.border_box();
+    let transform = Transform::of_box(functions, offset_x + border.x, offset_y + border.y, border.width, border.height);
+    (!transform.is_identity()).then_some(transform)
+}
+
+/// Smallest rectangle around a transformed rectangle
+pub fn transform_rect(transform: &Transform, rect: &Rect) -> Rect {
+    let corners = [(rect.x, rect.y), (rect.right(), rect.y), (rect.x, rect.bottom()), (rect.right(), rect.bottom())]
+        .map(|(x, y)| transform.apply(x, y));
+    let (mut left, mut top, mut right, mut bottom) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for (x, y) in corners {
+        (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+    }
+    Rect::new(left, top, right - left, bottom - top)
+}
+
+/// Area an overflow clipping box clips its descendants to, its padding box
+///
+/// `abs_x` and `abs_y` are the absolute position of the box's content area.
+pub fn overflow_clip_rect(layout_box: &LayoutBox, abs_x: f32, abs_y: f32) -> Rect {
+    let d = &layout_box.dimensions;
+    Rect::new(
+        abs_x - d.padding.left,
+        abs_y - d.padding.top,
+        d.content.width + d.padding.horizontal(),
+        d.content.height + d.padding.vertical(),
+    )
+}
+
+/// Node of a box whose content can be scrolled
+pub fn scroll_container_id(layout_box: &LayoutBox) -> Option<NodeId> {
+    layout_box.node_id().filter(|_| layout_box.is_scroll_container())
+}
+
+/// Narrow a clip to an overflow clipping box's clip rect
+fn clip_to(clip: Option<Rect>, layout_box: &LayoutBox, abs_x: f32, abs_y: f32) -> Option<Rect> {
+    if !layout_box.clips_overflow() {
+        return clip;
+    }
+    let rect = overflow_clip_rect(layout_box, abs_x, abs_y);
+    Some(clip.map_or(rect, |clip| clip.intersect(&rect)))
+}
+
+impl<'b, 'a> Layer<'b, 'a> {
+    /// Build the layer tree rooted at a box
+    pub fn new(layout_box: &'b LayoutBox<'a>, offset_x: f32, offset_y: f32) -> Self {
+        Self::with_context(layout_box, offset_x, offset_y, None, &[], false)
+    }
+
+    fn with_context(
+        layout_box: &'b LayoutBox<'a>,
+        offset_x: f32,
+        offset_y: f32,
+        clip: Option<Rect>,
+        scrollers: &[NodeId],
+        fixed: bool,
+    ) -> Self {
+        let fixed = fixed || layout_box.is_fixed_positioned();
+        let content = layout_box.dimensions.content;
+
+        let mut children = Vec::new();
+        collect_layers(
+            layout_box,
+            offset_x + content.x,
+            offset_y + content.y,
+            None,
+            &mut Vec::new(),
+            fixed,
+            &mut children,
+        );
+
+        // The viewport is the only clip fixed boxes get, and they don't
+        // move with scrolled ancestors
+        let is_fixed = layout_box.is_fixed_positioned();
+        Self {
+            layout_box,
+            offset_x,
+            offset_y,
+            clip: if is_fixed { None } else { clip },
+            scrollers: if is_fixed { Vec::new() } else { scrollers.to_vec() },
+            fixed,
+            children,
+        }
+    }
+
+    /// The layer's z-index, 0 for boxes that aren't positioned
+    pub fn z_index(&self) -> i32 {
+        if self.layout_box.is_positioned() {
+            self.layout_box.style().map_or(0, |s| s.z_index)
+        } else {
+            0
+        }
+    }
+
+    /// Positioned layers painted below the normal flow, in paint order
+    pub fn below(&self) -> Vec<&Layer<'b, 'a>> {
+        self.sorted(|z| z < 0)
+    }
+
+    /// Floated layers, painted between the flow's backgrounds and its content
+    pub fn floats(&self) -> impl Iterator<Item = &Layer<'b, 'a>> {
+        self.children.iter().filter(|l| !l.is_positioned_layer())
+    }
+
+    /// Positioned layers painted above the normal flow, in paint order
+    pub fn above(&self) -> Vec<&Layer<'b, 'a>> {
+        self.sorted(|z| z >= 0)
+    }
+
+    /// Positioned boxes and translucent boxes are stacked by z-index
+    fn is_positioned_layer(&self) -> bool {
+        !self.layout_box.is_floated()
+    }
+
+    /// Positioned layers with a matching z-index, stable sorted by z-index
+    fn sorted(&self, matches: impl Fn(i32) -> bool) -> Vec<&Layer<'b, 'a>> {
+        let mut layers: Vec<_> = self
+            .children
+            .iter()
+            .filter(|l| l.is_positioned_layer() && matches(l.z_index()))
+            .collect();
+        layers.sort_by_key(|l| l.z_index());
+        layers
+    }
+
+    /// Absolute position of the layer box's content area
+    pub fn content_origin(&self) -> (f32, f32) {
+        let content = self.layout_box.dimensions.content;
+        (self.offset_x + content.x, self.offset_y + content.y)
+    }
+
+    /// Push the boxes of this layer in paint order
+    ///
+    /// `clip`, `scroller` and `transform` are the clip, scroll container
+    /// and transform in effect for the layer this one belongs to.
+    fn push_boxes(
+        &self,
+        clip: Option<Rect>,
+        scroller: Option<NodeId>,
+        transform: Option<Transform>,
+        boxes: &mut Vec<PaintedBox<'b, 'a>>,
+    ) {
+        let (clip, scroller) = match (self.layout_box.is_fixed_positioned(), self.clip) {
+            (true, _) => (None, None),
+            (false, Some(own)) => (Some(clip.map_or(own, |clip| clip.intersect(&own))), scroller),
+            (false, None) => (clip, scroller),
+        };
+        let scroller = self.scrollers.last().copied().or(scroller);
+
+        // Clips from outside a transformed box are moved to where it is
+        // laid out, nothing is left of them when it is flattened
+        let (clip, transform) = match box_transform(self.layout_box, self.offset_x, self.offset_y) {
+            Some(own) => (
+                clip.map(|clip| own.inverse().map_or(Rect::default(), |inverse| transform_rect(&inverse, &clip))),
+                Some(transform.map_or(own, |outer| own.then(&outer))),
+            ),
+            None => (clip, transform),
+        };
+        boxes.push(PaintedBox {
+            layout_box: self.layout_box,
+            offset_x: self.offset_x,
+            offset_y: self.offset_y,
+            fixed: self.fixed,
+            clip,
+            scroller,
+            transform,
+        });
+
+        let (x, y) = self.content_origin();
+        let inner = clip_to(clip, self.layout_box, x, y);
+        let inner_scroller = scroll_container_id(self.layout_box).or(scroller);
+        for layer in self.below() {
+            layer.push_boxes(inner, inner_scroller, transform, boxes);
+        }
+        let context = FlowContext { fixed: self.fixed, clip: inner, scroller: inner_scroller, transform };
+        push_flow_boxes(self.layout_box, x, y, context, boxes);
+        for layer in self.floats() {
+            layer.push_boxes(inner, inner_scroller, transform, boxes);
+        }
+        for layer in self.above() {
+            layer.push_boxes(inner, inner_scroller, transform, boxes);
+        }
+    }
+}
+
+/// Find the layers among a box's descendants, without descending into them
+fn collect_layers<'b, 'a>(
+    parent: &'b LayoutBox<'a>,
+    offset_x: f32,
+    offset_y: f32,
+    clip: Option<Rect>,
+    scrollers: &mut Vec<NodeId>,
+    fixed: bool,
+    layers: &mut Vec<Layer<'b, 'a>>,
+) {
+    for child in &parent.children {
+        if is_layer(child) {
+            layers.push(Layer::with_context(child, offset_x, offset_y, clip, scrollers, fixed));
+            continue;
+        }
+
+        let content = child.dimensions.content;
+        let (x, y) = (offset_x + content.x, offset_y + content.y);
+        let scroller = scroll_container_id(child);
+        scrollers.extend(scroller);
+        collect_layers(child, x, y, clip_to(clip, child, x, y), scrollers, fixed, layers);
+        if scroller.is_some() {
+            scrollers.pop();
+        }
+    }
+}
+
+/// What the normal flow boxes of a layer are painted with
+#[derive(Clone, Copy)]
+struct FlowContext {
+    fixed: bool,
+    clip: Option<Rect>,
+    scroller: Option<NodeId>,
+    transform: Option<Transform>,
+}
+
+/// Push the normal flow descendants of a box in tree order
+fn push_flow_boxes<'b, 'a>(
+    parent: &'b LayoutBox<'a>,
+    offset_x: f32,
+    offset_y: f32,
+    context: FlowContext,
+    boxes: &mut Vec<PaintedBox<'b, 'a>>,
+) {
+    let FlowContext { fixed, clip, scroller, transform } = context;
+    for child in parent.children.iter().filter(|c| !is_layer(c)) {
+        boxes.push(PaintedBox { layout_box: child, offset_x, offset_y, fixed, clip, scroller, transform });
+        let content = child.dimensions.content;
+        let (x, y) = (offset_x + content.x, offset_y + content.y);
+        let inner = FlowContext { clip: clip_to(clip, child, x, y), scroller: scroll_container_id(child).or(scroller), ..context };
+        push_flow_boxes(child, x, y, inner, boxes);
+    }
+}
+
+/// List every box of a layout tree in the order it is painted
+///
+/// Later boxes are painted on top of earlier ones.
+pub fn boxes_in_paint_order<'b, 'a>(root: &'b LayoutBox<'a>) -> Vec<PaintedBox<'b, 'a>> {
+    let mut boxes = Vec::new();
+    Layer::new(root, 0.0, 0.0).push_boxes(None, None, None, &mut boxes);
+    boxes
+}
@@ -0,0 +1,164 @@
+//! Bookmarks
+//!
+//! Pages the user saved for later, kept as JSON in the config directory.
+//! A URL is bookmarked once, bookmarking it again updates its title. The
+//! `about:bookmarks` page lists them, each with a link deleting it.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+/// URL of the page listing the bookmarks
+pub const BOOKMARKS_URL: &str = "about:bookmarks";
+
+/// A saved page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    /// When it was bookmarked, in seconds since the Unix epoch
+    pub added: u64,
+}
+
+/// The user's bookmarks, in the order they were added
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    bookmarks: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// Load the bookmarks saved to a file, none when it does not exist
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { bookmarks: serde_json::from_str(&json)? })
+    }
+
+    /// Save the bookmarks to a file, creating its directory
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&self.bookmarks)?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Bookmark> {
+        self.bookmarks.iter()
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.bookmarks.iter().any(|b| b.url == url)
+    }
+
+    /// Bookmark a page, or update the title of its bookmark
+    pub fn add(&mut self, title: &str, url: &str, added: u64) {
+        match self.bookmarks.iter_mut().find(|b| b.url == url) {
+            Some(bookmark) => bookmark.title = title.to_string(),
+            None => self.bookmarks.push(Bookmark { title: title.to_string(), url: url.to_string(), added }),
+        }
+    }
+
+    /// Delete the bookmark of a page, returns whether it had one
+    pub fn remove(&mut self, url: &str) -> bool {
+        let count = self.bookmarks.len();
+        self.bookmarks.retain(|b| b.url != url);
+        self.bookmarks.len() != count
+    }
+
+    /// Bookmark a page, or delete its bookmark when it has one; returns
+    /// whether it is bookmarked now
+    pub fn toggle(&mut self, title: &str, url: &str, added: u64) -> bool {
+        if self.remove(url) {
+            return false;
+        }
+        self.add(title, url, added);
+        true
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// The `about:bookmarks` page
+pub fn bookmarks_page(bookmarks: &Bookmarks) -> String {
+    let mut items = String::new();
+    for bookmark in bookmarks.iter() {
+        let delete: String = form_urlencoded::byte_serialize(bookmark.url.as_bytes()).collect();
+        let title = if bookmark.title.is_empty() { &bookmark.url } else { &bookmark.title };
+        items.push_str(&format!(
+            "<li><a href=\"{url}\">{title}</a> <span class=\"url\">{url}</span> \
+             <a class=\"delete\" href=\"{page}?delete={delete}\">Delete</a></li>\n",
+            url = escape_html(&bookmark.url),
+            title = escape_html(title),
+            page = BOOKMARKS_URL,
+            delete = delete,
+        ));
+    }
+    let list = if items.is_empty() {
+        "<p class=\"empty\">No bookmarks yet. Press Ctrl+D to bookmark a page.</p>".to_string()
+    } else {
+        format!("<ul>\n{}</ul>", items)
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>Bookmarks</title>
+    <style>
+        body {{ font-family: sans-serif; padding: 24px; }}
+        li {{ margin-bottom: 8px; }}
+        .url {{ color: #666; font-size: 12px; }}
+        .delete {{ color: #d93025; font-size: 12px; }}
+        .empty {{ color: #666; }}
+    </style>
+</head>
+<body>
+    <h1>Bookmarks</h1>
+    {list}
+</body>
+</html>"#,
+        list = list,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks_round_trip() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.add("Example", "https://example.com/", 100);
+        bookmarks.add("Rust", "https://www.rust-lang.org/", 200);
+        // Bookmarking a URL again updates its title
+        bookmarks.add("Example Domain", "https://example.com/", 300);
+        assert!(!bookmarks.toggle("Rust", "https://www.rust-lang.org/", 400));
+        assert!(bookmarks.toggle("Docs", "https://docs.rs/", 500));
+
+        let path = std::env::temp_dir().join(format!("gugalanna-bookmarks-{}.json", std::process::id()));
+        bookmarks.save(&path).unwrap();
+        let loaded = Bookmarks::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let saved: Vec<(&str, &str, u64)> =
+            loaded.iter().map(|b| (b.title.as_str(), b.url.as_str(), b.added)).collect();
+        assert_eq!(saved, [("Example Domain", "https://example.com/", 100), ("Docs", "https://docs.rs/", 500)]);
+        assert!(Bookmarks::load(&path).unwrap().iter().next().is_none());
+    }
+
+    #[test]
+    fn test_bookmarks_page_links_and_deletes() {
+        let mut bookmarks = Bookmarks::default();
+        assert!(bookmarks_page(&bookmarks).contains("No bookmarks yet"));
+
+        bookmarks.add("A <b>&</b>", "https://a.test/?q=1&r=2", 0);
+        let page = bookmarks_page(&bookmarks);
+        assert!(page.contains("<a href=\"https://a.test/?q=1&amp;r=2\">A &lt;b&gt;&amp;&lt;/b&gt;</a>"));
+        assert!(page.contains("href=\"about:bookmarks?delete=https%3A%2F%2Fa.test%2F%3Fq%3D1%26r%3D2\""));
+    }
+}
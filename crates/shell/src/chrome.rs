@@ -2,9 +2,11 @@
 //!
 //! Address bar, navigation buttons, tab bar, and browser chrome rendering.
 
-use gugalanna_layout::Rect;
-use gugalanna_render::{DisplayList, PaintCommand, RenderColor};
+use gugalanna_layout::{ImagePixels, Rect};
+use gugalanna_render::{DisplayList, FontFace, PaintCommand, RenderColor};
+use gugalanna_style::ImageRendering;
 
+use crate::favicon::FAVICON_SIZE;
 use crate::TabId;
 
 /// Tab bar height in pixels
@@ -37,6 +39,36 @@ const TAB_CLOSE_SIZE: f32 = 16.0;
 /// New tab button width
 const NEW_TAB_BUTTON_WIDTH: f32 = 28.0;
 
+/// Height of the script error badge
+const ERROR_BADGE_HEIGHT: f32 = 16.0;
+
+/// Room a favicon takes left of a tab's title and of the address
+const FAVICON_SPACE: f32 = FAVICON_SIZE as f32 + 6.0;
+
+/// Width of the bookmark star at the end of the address bar
+const STAR_WIDTH: f32 = 20.0;
+
+/// Find bar size, and the width of its match counter
+const FIND_BAR_WIDTH: f32 = 320.0;
+const FIND_BAR_HEIGHT: f32 = 40.0;
+const FIND_COUNTER_WIDTH: f32 = 64.0;
+
+/// Toast height
+const TOAST_HEIGHT: f32 = 30.0;
+
+/// Status overlay height, and the width of a character of its text
+const STATUS_HEIGHT: f32 = 22.0;
+const STATUS_CHAR_WIDTH: f32 = 7.0;
+
+/// Loading bar thickness
+const LOADING_BAR_HEIGHT: f32 = 3.0;
+
+/// How long the loading bar takes to fade once filled, in ms
+const LOADING_BAR_FADE_MS: f32 = 200.0;
+
+/// How long a pulse of the loading bar takes to sweep across, in ms
+const LOADING_BAR_PULSE_MS: f32 = 1200.0;
+
 /// Visual tab in tab bar
 #[derive(Debug, Clone)]
 pub struct Tab {
@@ -52,6 +84,20 @@ pub struct Tab {
     pub is_active: bool,
     /// Whether this tab is loading
     pub is_loading: bool,
+    /// Icon of the tab's page, once it is fetched
+    pub favicon: Option<ImagePixels>,
+    /// Whether this tab is private, drawn darker with a stripe along its top
+    pub is_private: bool,
+}
+
+/// What the tab bar shows of a tab
+#[derive(Debug, Clone)]
+pub struct TabInfo {
+    pub id: TabId,
+    pub title: String,
+    pub is_loading: bool,
+    pub is_private: bool,
+    pub favicon: Option<ImagePixels>,
 }
 
 impl Tab {
@@ -91,10 +137,30 @@ pub struct Chrome {
     pub address_bar: AddressBar,
     /// Go button
     pub go_button: Button,
+    /// Reader mode button, enabled when the page has an article
+    pub reader_button: Button,
+    /// Whether the active tab shows its page in reader mode
+    pub is_reader_mode: bool,
     /// Whether a page is currently loading
     pub is_loading: bool,
     /// Loading animation frame counter
     loading_frame: u8,
+    /// Bar along the bottom of the chrome showing how far the page loaded
+    pub loading_bar: LoadingBar,
+    /// Errors the active tab's page logged to its console, shown as a badge
+    pub error_count: usize,
+    /// Whether the active tab's page is bookmarked, shown as a star
+    pub is_bookmarked: bool,
+    /// Icon of the active tab's page, shown before the address
+    pub favicon: Option<ImagePixels>,
+    /// Find in page bar
+    pub find_bar: FindBar,
+    /// Message shown over the top of the page for a moment, as when a
+    /// page was saved
+    pub toast: Option<String>,
+    /// Where the link under the mouse goes, or a passing message, shown
+    /// at the bottom left of the page
+    pub status: Option<String>,
 }
 
 /// A clickable button
@@ -121,6 +187,35 @@ pub struct AddressBar {
     pub is_focused: bool,
 }
 
+/// Find in page bar state, the bar drawn over the top right corner of the
+/// page while open
+#[derive(Debug, Clone, Default)]
+pub struct FindBar {
+    pub open: bool,
+    /// Text searched for
+    pub query: String,
+    pub is_focused: bool,
+    /// Index of the match the search is at
+    pub current: Option<usize>,
+    /// Number of matches in the page
+    pub count: usize,
+}
+
+/// Loading bar state, filling as the active tab's page loads and fading
+/// once it has loaded
+#[derive(Debug, Clone, Default)]
+pub struct LoadingBar {
+    visible: bool,
+    /// How far the load has come, None while that is not known
+    target: Option<f32>,
+    /// Share of the width filled, catching up with the load
+    shown: f32,
+    /// Time into the current pulse, while how far is not known
+    pulse_ms: f32,
+    /// Time the bar has been fading, None until the load finished
+    finished_ms: Option<f32>,
+}
+
 /// Result of hit testing the chrome
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChromeHit {
@@ -136,6 +231,8 @@ pub enum ChromeHit {
     ForwardButton,
     /// Go button clicked
     GoButton,
+    /// Reader mode button clicked
+    ReaderButton,
     /// Address bar clicked
     AddressBar,
 }
@@ -182,9 +279,21 @@ impl Chrome {
             enabled: true,
         };
 
-        // Address bar between forward button and go button
+        // Reader mode button left of the go button
+        let reader_button = Button {
+            rect: Rect {
+                x: go_button.rect.x - PADDING - BUTTON_WIDTH,
+                y: nav_y_center,
+                width: BUTTON_WIDTH,
+                height: BUTTON_HEIGHT,
+            },
+            label: "\u{2261}",
+            enabled: false,
+        };
+
+        // Address bar between forward button and reader button
         let address_bar_x = forward_button.rect.x + forward_button.rect.width + PADDING;
-        let address_bar_width = go_button.rect.x - address_bar_x - PADDING;
+        let address_bar_width = reader_button.rect.x - address_bar_x - PADDING;
 
         let address_bar = AddressBar {
             rect: Rect {
@@ -219,16 +328,24 @@ impl Chrome {
             forward_button,
             address_bar,
             go_button,
+            reader_button,
+            is_reader_mode: false,
             is_loading: false,
             loading_frame: 0,
+            loading_bar: LoadingBar::default(),
+            error_count: 0,
+            is_bookmarked: false,
+            favicon: None,
+            find_bar: FindBar::default(),
+            toast: None,
+            status: None,
         }
     }
 
     /// Update tab layout from tab state
     ///
     /// Call this when tabs are added, removed, or switched.
-    pub fn layout_tabs(&mut self, tab_infos: &[(TabId, String, bool, bool)], active_id: TabId) {
-        // tab_infos: [(id, title, is_loading, is_active)]
+    pub fn layout_tabs(&mut self, tab_infos: &[TabInfo], active_id: TabId) {
         self.tabs.clear();
 
         let tab_count = tab_infos.len();
@@ -247,14 +364,14 @@ impl Chrome {
         let tab_y = 2.0;
 
         let mut x = PADDING;
-        for (id, title, is_loading, _) in tab_infos {
-            let is_active = *id == active_id;
+        for info in tab_infos {
+            let is_active = info.id == active_id;
 
             let close_x = x + tab_width - TAB_CLOSE_SIZE - 4.0;
             let close_y = tab_y + (tab_height - TAB_CLOSE_SIZE) / 2.0;
 
             self.tabs.push(Tab {
-                id: *id,
+                id: info.id,
                 rect: Rect {
                     x,
                     y: tab_y,
@@ -267,9 +384,11 @@ impl Chrome {
                     width: TAB_CLOSE_SIZE,
                     height: TAB_CLOSE_SIZE,
                 },
-                title: truncate_title(title, (tab_width - TAB_CLOSE_SIZE - 16.0) / 7.0),
+                title: truncate_title(&info.title, (tab_width - TAB_CLOSE_SIZE - FAVICON_SPACE - 16.0) / 7.0),
                 is_active,
-                is_loading: *is_loading,
+                is_loading: info.is_loading,
+                favicon: info.favicon.clone(),
+                is_private: info.is_private,
             });
 
             x += tab_width;
@@ -279,13 +398,15 @@ impl Chrome {
         self.new_tab_button.rect.x = x + PADDING / 2.0;
     }
 
-    /// Update loading animation (call each frame when loading)
-    pub fn tick_loading(&mut self) {
+    /// Update loading animation (call each frame), `delta_ms` after the
+    /// last one
+    pub fn tick_loading(&mut self, delta_ms: f32) {
         if self.is_loading {
             self.loading_frame = self.loading_frame.wrapping_add(1);
         } else {
             self.loading_frame = 0;
         }
+        self.loading_bar.tick(delta_ms);
     }
 
     /// Build a display list for the chrome
@@ -344,23 +465,8 @@ impl Chrome {
             color: RenderColor::new(200, 200, 200, 255), // Darker gray
         });
 
-        // Loading indicator (animated progress bar at bottom of chrome)
-        if self.is_loading {
-            // Oscillating progress bar using sine wave
-            let progress = (self.loading_frame as f32 / 30.0 * std::f32::consts::PI).sin();
-            let bar_width = self.width * (0.3 + 0.3 * progress.abs());
-            let bar_x = (self.width - bar_width) * ((progress + 1.0) / 2.0);
-
-            commands.push(PaintCommand::FillRect {
-                rect: Rect {
-                    x: bar_x,
-                    y: self.height - 3.0,
-                    width: bar_width,
-                    height: 3.0,
-                },
-                color: RenderColor::new(66, 133, 244, 255), // Google blue
-            });
-        }
+        // Loading indicator (progress bar at bottom of chrome)
+        self.loading_bar.render(self.width, self.height - LOADING_BAR_HEIGHT, &mut commands);
 
         // Back button
         self.render_button(&self.back_button, &mut commands);
@@ -371,19 +477,205 @@ impl Chrome {
         // Address bar
         self.render_address_bar(&mut commands);
 
+        // Reader mode button, highlighted while the page is shown in it
+        self.render_button(&self.reader_button, &mut commands);
+        if self.is_reader_mode {
+            self.render_reader_mode_highlight(&mut commands);
+        }
+
         // Go button
         self.render_button(&self.go_button, &mut commands);
 
+        // Bookmark star and error badge
+        self.render_bookmark_star(&mut commands);
+        self.render_error_badge(&mut commands);
+
         DisplayList { commands }
     }
 
+    /// Build a display list for the find bar, painted over the page; empty
+    /// while it is closed
+    pub fn build_find_bar_display_list(&self) -> DisplayList {
+        let mut commands = Vec::new();
+        if !self.find_bar.open {
+            return DisplayList { commands };
+        }
+        let bar = self.find_bar_rect();
+        let gray = RenderColor::new(180, 180, 180, 255);
+
+        commands.push(PaintCommand::FillRect { rect: bar, color: RenderColor::new(240, 240, 240, 255) });
+        commands.push(PaintCommand::DrawBorder {
+            rect: bar,
+            widths: gugalanna_render::BorderWidths { top: 0.0, right: 1.0, bottom: 1.0, left: 1.0 },
+            styles: gugalanna_render::BorderStyles::default(),
+            colors: gugalanna_render::BorderColors::all(gray),
+        });
+
+        // Query field, the counter after it
+        let field = Rect {
+            x: bar.x + PADDING,
+            y: bar.y + (FIND_BAR_HEIGHT - BUTTON_HEIGHT + 4.0) / 2.0,
+            width: bar.width - FIND_COUNTER_WIDTH - PADDING * 2.0,
+            height: BUTTON_HEIGHT - 4.0,
+        };
+        let (border_color, border_width) = if self.find_bar.is_focused {
+            (RenderColor::new(66, 133, 244, 255), 2.0)
+        } else {
+            (gray, 1.0)
+        };
+        commands.push(PaintCommand::FillRect { rect: field, color: RenderColor::new(255, 255, 255, 255) });
+        commands.push(PaintCommand::DrawBorder {
+            rect: field,
+            widths: gugalanna_render::BorderWidths {
+                top: border_width,
+                right: border_width,
+                bottom: border_width,
+                left: border_width,
+            },
+            styles: gugalanna_render::BorderStyles::default(),
+            colors: gugalanna_render::BorderColors::all(border_color),
+        });
+
+        let text_x = field.x + 6.0;
+        let text_y = field.y + field.height / 2.0 - 6.0;
+        let max_chars = ((field.width - 12.0) / 8.0) as usize;
+        let skip = self.find_bar.query.chars().count().saturating_sub(max_chars);
+        let shown: String = self.find_bar.query.chars().skip(skip).collect();
+        if self.find_bar.is_focused {
+            let cursor_x = (text_x + shown.chars().count() as f32 * 8.0).min(field.x + field.width - 6.0);
+            commands.push(PaintCommand::FillRect {
+                rect: Rect { x: cursor_x, y: field.y + 5.0, width: 1.0, height: field.height - 10.0 },
+                color: RenderColor::new(0, 0, 0, 255),
+            });
+        }
+        if !shown.is_empty() {
+            commands.push(PaintCommand::DrawText {
+                text: shown,
+                x: text_x,
+                y: text_y,
+                color: RenderColor::new(0, 0, 0, 255),
+                font_size: 14.0,
+                face: FontFace::REGULAR,
+                family: None,
+            });
+        }
+
+        // Red when nothing was found
+        let counter = self.find_bar.counter();
+        if !counter.is_empty() {
+            let color = if self.find_bar.count == 0 {
+                RenderColor::new(217, 48, 37, 255)
+            } else {
+                RenderColor::new(100, 100, 100, 255)
+            };
+            commands.push(PaintCommand::DrawText {
+                text: counter,
+                x: field.x + field.width + PADDING,
+                y: text_y,
+                color,
+                font_size: 13.0,
+                face: FontFace::REGULAR,
+                family: None,
+            });
+        }
+
+        DisplayList { commands }
+    }
+
+    /// Build a display list for the toast, painted centered over the top
+    /// of the page; empty while there is none
+    pub fn build_toast_display_list(&self) -> DisplayList {
+        let mut commands = Vec::new();
+        let Some(toast) = &self.toast else {
+            return DisplayList { commands };
+        };
+        let width = (toast.chars().count() as f32 * 7.0 + PADDING * 4.0).min(self.width);
+        let rect = Rect {
+            x: (self.width - width) / 2.0,
+            y: self.height + PADDING,
+            width,
+            height: TOAST_HEIGHT,
+        };
+        commands.push(PaintCommand::FillRect { rect, color: RenderColor::new(50, 50, 50, 230) });
+        commands.push(PaintCommand::DrawText {
+            text: toast.clone(),
+            x: rect.x + PADDING * 2.0,
+            y: rect.y + TOAST_HEIGHT / 2.0 - 7.0,
+            color: RenderColor::new(255, 255, 255, 255),
+            font_size: 13.0,
+            face: FontFace::REGULAR,
+            family: None,
+        });
+        DisplayList { commands }
+    }
+
+    /// Build a display list for the status overlay, painted over the
+    /// bottom left of the page, which ends at `bottom`; empty while there
+    /// is no status or the find bar covers that corner
+    pub fn build_status_display_list(&self, bottom: f32) -> DisplayList {
+        let mut commands = Vec::new();
+        let Some((rect, text)) = self.status_layout(bottom) else {
+            return DisplayList { commands };
+        };
+        commands.push(PaintCommand::FillRect { rect, color: RenderColor::new(245, 245, 245, 240) });
+        commands.push(PaintCommand::DrawBorder {
+            rect,
+            widths: gugalanna_render::BorderWidths { top: 1.0, right: 1.0, bottom: 0.0, left: 0.0 },
+            styles: gugalanna_render::BorderStyles::default(),
+            colors: gugalanna_render::BorderColors::all(RenderColor::new(190, 190, 190, 255)),
+        });
+        commands.push(PaintCommand::DrawText {
+            text,
+            x: rect.x + PADDING,
+            y: rect.y + STATUS_HEIGHT / 2.0 - 7.0,
+            color: RenderColor::new(60, 60, 60, 255),
+            font_size: 12.0,
+            face: FontFace::REGULAR,
+            family: None,
+        });
+        DisplayList { commands }
+    }
+
+    /// Area of the status overlay and its text, cut short with an ellipsis
+    /// to fit the window
+    fn status_layout(&self, bottom: f32) -> Option<(Rect, String)> {
+        let status = self.status.as_ref()?;
+        let max_chars = ((self.width - PADDING * 2.0) / STATUS_CHAR_WIDTH).max(1.0) as usize;
+        let text = if status.chars().count() > max_chars {
+            let kept: String = status.chars().take(max_chars - 1).collect();
+            format!("{}\u{2026}", kept)
+        } else {
+            status.clone()
+        };
+        let width = (text.chars().count() as f32 * STATUS_CHAR_WIDTH + PADDING * 2.0).min(self.width);
+        let rect = Rect { x: 0.0, y: (bottom - STATUS_HEIGHT).max(self.height), width, height: STATUS_HEIGHT };
+        let find_bar = self.find_bar_rect();
+        let covered = self.find_bar.open
+            && find_bar.x < rect.x + rect.width
+            && rect.x < find_bar.x + find_bar.width
+            && find_bar.y < rect.y + rect.height
+            && rect.y < find_bar.y + find_bar.height;
+        (!covered).then_some((rect, text))
+    }
+
+    /// Area of the find bar, below the chrome at the right of the window
+    pub fn find_bar_rect(&self) -> Rect {
+        Rect {
+            x: (self.width - FIND_BAR_WIDTH - PADDING).max(0.0),
+            y: self.height,
+            width: FIND_BAR_WIDTH.min(self.width),
+            height: FIND_BAR_HEIGHT,
+        }
+    }
+
     /// Render a tab
     fn render_tab(&self, tab: &Tab, commands: &mut Vec<PaintCommand>) {
-        // Tab background
-        let bg_color = if tab.is_active {
-            RenderColor::new(255, 255, 255, 255) // White for active
-        } else {
-            RenderColor::new(220, 220, 220, 255) // Gray for inactive
+        // Tab background, dark for private tabs
+        let bg_color = match (tab.is_private, tab.is_active) {
+            (false, true) => RenderColor::new(255, 255, 255, 255), // White for active
+            (false, false) => RenderColor::new(220, 220, 220, 255), // Gray for inactive
+            (true, true) => RenderColor::new(72, 52, 102, 255),
+            (true, false) => RenderColor::new(52, 38, 74, 255),
         };
 
         commands.push(PaintCommand::FillRect {
@@ -401,7 +693,8 @@ impl Chrome {
                     bottom: 0.0,
                     left: 1.0,
                 },
-                color: RenderColor::new(180, 180, 180, 255),
+                styles: gugalanna_render::BorderStyles::default(),
+                colors: gugalanna_render::BorderColors::all(RenderColor::new(180, 180, 180, 255)),
             });
         } else {
             commands.push(PaintCommand::DrawBorder {
@@ -412,34 +705,39 @@ impl Chrome {
                     bottom: 1.0,
                     left: 1.0,
                 },
-                color: RenderColor::new(200, 200, 200, 255),
+                styles: gugalanna_render::BorderStyles::default(),
+                colors: gugalanna_render::BorderColors::all(RenderColor::new(200, 200, 200, 255)),
             });
         }
 
-        // Tab title
-        let text_x = tab.rect.x + 8.0;
-        let text_y = tab.rect.y + tab.rect.height / 2.0 - 6.0;
-        let text_color = if tab.is_active {
-            RenderColor::new(0, 0, 0, 255)
-        } else {
-            RenderColor::new(80, 80, 80, 255)
-        };
+        // Private tabs are marked by a stripe along their top
+        if tab.is_private {
+            commands.push(PaintCommand::FillRect {
+                rect: Rect { height: 3.0, ..tab.rect },
+                color: RenderColor::new(170, 120, 230, 255),
+            });
+        }
 
-        // Loading indicator or title
-        let display_text = if tab.is_loading {
-            let spinner = ['|', '/', '-', '\\'];
-            let frame = (self.loading_frame / 8) as usize % 4;
-            format!("{} {}", spinner[frame], &tab.title)
-        } else {
-            tab.title.clone()
+        // Favicon, then the title
+        let icon_y = tab.rect.y + (tab.rect.height - FAVICON_SIZE as f32) / 2.0;
+        self.render_favicon(tab.favicon.as_ref(), tab.is_loading, tab.rect.x + 8.0, icon_y, commands);
+        let text_x = tab.rect.x + 8.0 + FAVICON_SPACE;
+        let text_y = tab.rect.y + tab.rect.height / 2.0 - 6.0;
+        let text_color = match (tab.is_private, tab.is_active) {
+            (false, true) => RenderColor::new(0, 0, 0, 255),
+            (false, false) => RenderColor::new(80, 80, 80, 255),
+            (true, true) => RenderColor::new(255, 255, 255, 255),
+            (true, false) => RenderColor::new(200, 190, 215, 255),
         };
 
         commands.push(PaintCommand::DrawText {
-            text: display_text,
+            text: tab.title.clone(),
             x: text_x,
             y: text_y,
             color: text_color,
             font_size: 12.0,
+            face: FontFace::REGULAR,
+            family: None,
         });
 
         // Close button (X)
@@ -449,9 +747,61 @@ impl Chrome {
             y: tab.close_rect.y + 2.0,
             color: RenderColor::new(120, 120, 120, 255),
             font_size: 12.0,
+            face: FontFace::REGULAR,
+            family: None,
         });
     }
 
+    /// Render a favicon, or a blank page while there is none, with the
+    /// loading spinner over it while its page loads
+    fn render_favicon(
+        &self,
+        favicon: Option<&ImagePixels>,
+        is_loading: bool,
+        x: f32,
+        y: f32,
+        commands: &mut Vec<PaintCommand>,
+    ) {
+        let size = FAVICON_SIZE as f32;
+        match favicon {
+            Some(favicon) => commands.push(PaintCommand::DrawImage {
+                rect: Rect::new(x, y, size, size),
+                pixels: Some(favicon.clone()),
+                alt: String::new(),
+                clip: None,
+                rendering: ImageRendering::default(),
+            }),
+            None => {
+                let page = Rect::new(x + 2.0, y + 1.0, size - 4.0, size - 2.0);
+                commands.push(PaintCommand::FillRect { rect: page, color: RenderColor::new(255, 255, 255, 255) });
+                commands.push(PaintCommand::DrawBorder {
+                    rect: page,
+                    widths: gugalanna_render::BorderWidths { top: 1.0, right: 1.0, bottom: 1.0, left: 1.0 },
+                    styles: gugalanna_render::BorderStyles::default(),
+                    colors: gugalanna_render::BorderColors::all(RenderColor::new(150, 150, 150, 255)),
+                });
+            }
+        }
+
+        if is_loading {
+            let spinner = ['|', '/', '-', '\\'];
+            let frame = (self.loading_frame / 8) as usize % 4;
+            commands.push(PaintCommand::FillRect {
+                rect: Rect::new(x, y, size, size),
+                color: RenderColor::new(255, 255, 255, 200),
+            });
+            commands.push(PaintCommand::DrawText {
+                text: spinner[frame].to_string(),
+                x: x + 5.0,
+                y: y + 1.0,
+                color: RenderColor::new(66, 133, 244, 255),
+                font_size: 12.0,
+                face: FontFace::BOLD,
+                family: None,
+            });
+        }
+    }
+
     /// Render the new tab button
     fn render_new_tab_button(&self, commands: &mut Vec<PaintCommand>) {
         // Button background
@@ -469,7 +819,8 @@ impl Chrome {
                 bottom: 1.0,
                 left: 1.0,
             },
-            color: RenderColor::new(180, 180, 180, 255),
+            styles: gugalanna_render::BorderStyles::default(),
+            colors: gugalanna_render::BorderColors::all(RenderColor::new(180, 180, 180, 255)),
         });
 
         // Plus sign
@@ -482,6 +833,8 @@ impl Chrome {
             y: text_y,
             color: RenderColor::new(80, 80, 80, 255),
             font_size: 14.0,
+            face: FontFace::REGULAR,
+            family: None,
         });
     }
 
@@ -514,7 +867,8 @@ impl Chrome {
                 bottom: 1.0,
                 left: 1.0,
             },
-            color: RenderColor::new(180, 180, 180, 255),
+            styles: gugalanna_render::BorderStyles::default(),
+            colors: gugalanna_render::BorderColors::all(RenderColor::new(180, 180, 180, 255)),
         });
 
         // Button label (centered)
@@ -527,6 +881,8 @@ impl Chrome {
             y: text_y,
             color: text_color,
             font_size: 14.0,
+            face: FontFace::REGULAR,
+            family: None,
         });
     }
 
@@ -554,11 +910,14 @@ impl Chrome {
                 bottom: border_width,
                 left: border_width,
             },
-            color: border_color,
+            styles: gugalanna_render::BorderStyles::default(),
+            colors: gugalanna_render::BorderColors::all(border_color),
         });
 
-        // Address bar text (truncated to fit)
-        let text_x = self.address_bar.rect.x + 8.0;
+        // Favicon, then the text (truncated to fit)
+        let icon_y = self.address_bar.rect.y + (self.address_bar.rect.height - FAVICON_SIZE as f32) / 2.0;
+        self.render_favicon(self.favicon.as_ref(), false, self.address_bar.rect.x + 8.0, icon_y, commands);
+        let text_x = self.address_bar.rect.x + 8.0 + FAVICON_SPACE;
         let text_y = self.address_bar.rect.y + self.address_bar.rect.height / 2.0 - 6.0;
 
         if !self.address_bar.text.is_empty() {
@@ -569,6 +928,8 @@ impl Chrome {
                 y: text_y,
                 color: RenderColor::new(0, 0, 0, 255),
                 font_size: 14.0,
+                face: FontFace::REGULAR,
+                family: None,
             });
         }
 
@@ -591,6 +952,70 @@ impl Chrome {
         }
     }
 
+    /// Render the reader mode button as pressed
+    fn render_reader_mode_highlight(&self, commands: &mut Vec<PaintCommand>) {
+        let rect = self.reader_button.rect;
+        commands.push(PaintCommand::FillRect { rect, color: RenderColor::new(66, 133, 244, 255) });
+        commands.push(PaintCommand::DrawText {
+            text: self.reader_button.label.to_string(),
+            x: rect.x + rect.width / 2.0 - 6.0,
+            y: rect.y + rect.height / 2.0 - 6.0,
+            color: RenderColor::new(255, 255, 255, 255),
+            font_size: 14.0,
+            face: FontFace::REGULAR,
+            family: None,
+        });
+    }
+
+    /// Render the star at the end of the address bar, filled when the page
+    /// is bookmarked
+    fn render_bookmark_star(&self, commands: &mut Vec<PaintCommand>) {
+        let (star, color) = if self.is_bookmarked {
+            ("\u{2605}", RenderColor::new(244, 180, 0, 255))
+        } else {
+            ("\u{2606}", RenderColor::new(150, 150, 150, 255))
+        };
+        commands.push(PaintCommand::DrawText {
+            text: star.to_string(),
+            x: self.address_bar.rect.x + self.address_bar.rect.width - STAR_WIDTH,
+            y: self.address_bar.rect.y + self.address_bar.rect.height / 2.0 - 8.0,
+            color,
+            font_size: 16.0,
+            face: FontFace::REGULAR,
+            family: None,
+        });
+    }
+
+    /// Render the count of script errors at the end of the address bar,
+    /// before the bookmark star
+    fn render_error_badge(&self, commands: &mut Vec<PaintCommand>) {
+        if self.error_count == 0 {
+            return;
+        }
+        let text = if self.error_count > 99 { "99+".to_string() } else { self.error_count.to_string() };
+        let width = 12.0 + text.len() as f32 * 7.0;
+        let rect = Rect {
+            x: self.address_bar.rect.x + self.address_bar.rect.width - STAR_WIDTH - width - 6.0,
+            y: self.address_bar.rect.y + (self.address_bar.rect.height - ERROR_BADGE_HEIGHT) / 2.0,
+            width,
+            height: ERROR_BADGE_HEIGHT,
+        };
+
+        commands.push(PaintCommand::FillRect {
+            rect,
+            color: RenderColor::new(217, 48, 37, 255),
+        });
+        commands.push(PaintCommand::DrawText {
+            text,
+            x: rect.x + 6.0,
+            y: rect.y + 2.0,
+            color: RenderColor::new(255, 255, 255, 255),
+            font_size: 11.0,
+            face: FontFace::REGULAR,
+            family: None,
+        });
+    }
+
     /// Hit test the chrome
     ///
     /// Returns which element was hit, if any.
@@ -633,6 +1058,10 @@ impl Chrome {
             return Some(ChromeHit::GoButton);
         }
 
+        if self.reader_button.contains(x, y) {
+            return Some(ChromeHit::ReaderButton);
+        }
+
         if self.address_bar.contains(x, y) {
             return Some(ChromeHit::AddressBar);
         }
@@ -650,13 +1079,14 @@ impl Chrome {
     pub fn update_width(&mut self, width: f32) {
         self.width = width;
 
-        // Recalculate go button position
+        // Recalculate go and reader button positions
         self.go_button.rect.x = width - PADDING - BUTTON_WIDTH;
+        self.reader_button.rect.x = self.go_button.rect.x - PADDING - BUTTON_WIDTH;
 
         // Recalculate address bar width
         let address_bar_x = self.forward_button.rect.x + self.forward_button.rect.width + PADDING;
         self.address_bar.rect.x = address_bar_x;
-        self.address_bar.rect.width = self.go_button.rect.x - address_bar_x - PADDING;
+        self.address_bar.rect.width = self.reader_button.rect.x - address_bar_x - PADDING;
     }
 }
 
@@ -681,7 +1111,7 @@ impl AddressBar {
 
     /// Get truncated display text that fits in the address bar
     pub fn truncated_display_text(&self) -> String {
-        let available_width = self.rect.width - 16.0; // padding on both sides
+        let available_width = self.rect.width - 16.0 - FAVICON_SPACE; // padding on both sides, and the favicon
         let max_chars = (available_width / 8.0) as usize; // approximate char width
 
         if self.text.len() <= max_chars {
@@ -732,6 +1162,117 @@ impl AddressBar {
     }
 }
 
+impl FindBar {
+    /// Where the search is among the matches, as "3/17"; empty before
+    /// anything was typed
+    pub fn counter(&self) -> String {
+        if self.query.is_empty() {
+            return String::new();
+        }
+        format!("{}/{}", self.current.map_or(0, |i| i + 1), self.count)
+    }
+
+    /// Type a character at the end of the query
+    pub fn insert_char(&mut self, c: char) {
+        self.query.push(c);
+    }
+
+    /// Delete the last character of the query
+    pub fn delete_char(&mut self) {
+        self.query.pop();
+    }
+}
+
+impl LoadingBar {
+    /// Show a new load, from an empty bar
+    pub fn start(&mut self) {
+        *self = Self { visible: true, target: Some(0.0), ..Self::default() };
+    }
+
+    /// Show how far the load has come, from 0 to 1; None pulses the bar
+    /// while that is not known
+    pub fn set_progress(&mut self, progress: Option<f32>) {
+        if !self.visible || self.finished_ms.is_some() {
+            self.start();
+        }
+        self.target = progress.map(|progress| progress.clamp(0.0, 1.0));
+    }
+
+    /// Fill the bar, then fade it out
+    pub fn finish(&mut self) {
+        if self.visible && self.finished_ms.is_none() {
+            self.target = Some(1.0);
+            self.finished_ms = Some(0.0);
+        }
+    }
+
+    /// Hide the bar at once
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Move the animation on by `delta_ms`
+    pub fn tick(&mut self, delta_ms: f32) {
+        if !self.visible {
+            return;
+        }
+
+        // Ease towards the progress, faster once finished, never going back
+        let target = self.target.unwrap_or(self.shown);
+        let time_constant = if self.finished_ms.is_some() { 50.0 } else { 100.0 };
+        let step = 1.0 - (-delta_ms / time_constant).exp();
+        self.shown += (target - self.shown).max(0.0) * step;
+        if target - self.shown < 0.005 {
+            self.shown = self.shown.max(target);
+        }
+
+        if self.target.is_none() {
+            self.pulse_ms = (self.pulse_ms + delta_ms) % LOADING_BAR_PULSE_MS;
+        }
+        if let Some(finished_ms) = self.finished_ms {
+            let finished_ms = if self.shown >= 1.0 { finished_ms + delta_ms } else { finished_ms };
+            self.finished_ms = Some(finished_ms);
+            if finished_ms >= LOADING_BAR_FADE_MS {
+                self.clear();
+            }
+        }
+    }
+
+    /// Paint the bar across `width` at `y`
+    fn render(&self, width: f32, y: f32, commands: &mut Vec<PaintCommand>) {
+        if !self.visible {
+            return;
+        }
+        let fade = self.finished_ms.map_or(1.0, |ms| 1.0 - ms / LOADING_BAR_FADE_MS);
+        let color = RenderColor::new(66, 133, 244, (255.0 * fade.clamp(0.0, 1.0)) as u8);
+
+        if self.shown > 0.0 {
+            commands.push(PaintCommand::FillRect {
+                rect: Rect::new(0.0, y, width * self.shown, LOADING_BAR_HEIGHT),
+                color,
+            });
+        }
+
+        // A pulse sweeping across the rest while how far is not known
+        if self.target.is_none() {
+            let start = width * self.shown;
+            let pulse = (width - start) * 0.3;
+            let x = start - pulse + (width - start + pulse) * (self.pulse_ms / LOADING_BAR_PULSE_MS);
+            let (left, right) = (x.max(start), (x + pulse).min(width));
+            if right > left {
+                commands.push(PaintCommand::FillRect {
+                    rect: Rect::new(left, y, right - left, LOADING_BAR_HEIGHT),
+                    color: RenderColor::new(66, 133, 244, 160),
+                });
+            }
+        }
+    }
+}
+
 /// Truncate a title to fit in the available width
 fn truncate_title(title: &str, max_chars: f32) -> String {
     let max_chars = max_chars.max(3.0) as usize;
@@ -755,7 +1296,8 @@ mod tests {
         assert_eq!(chrome.height, CHROME_HEIGHT);
         assert!(chrome.back_button.rect.x < chrome.forward_button.rect.x);
         assert!(chrome.forward_button.rect.x < chrome.address_bar.rect.x);
-        assert!(chrome.address_bar.rect.x + chrome.address_bar.rect.width < chrome.go_button.rect.x);
+        assert!(chrome.address_bar.rect.x + chrome.address_bar.rect.width < chrome.reader_button.rect.x);
+        assert!(chrome.reader_button.rect.x + chrome.reader_button.rect.width < chrome.go_button.rect.x);
     }
 
     #[test]
@@ -767,6 +1309,17 @@ mod tests {
         assert_eq!(chrome.hit_test(center_x, center_y), Some(ChromeHit::BackButton));
     }
 
+    #[test]
+    fn test_hit_test_reader_button() {
+        let mut chrome = Chrome::new(800.0);
+        chrome.update_width(1000.0);
+        let center_x = chrome.reader_button.rect.x + chrome.reader_button.rect.width / 2.0;
+        let center_y = chrome.reader_button.rect.y + chrome.reader_button.rect.height / 2.0;
+
+        assert_eq!(chrome.hit_test(center_x, center_y), Some(ChromeHit::ReaderButton));
+        assert!(chrome.address_bar.rect.x + chrome.address_bar.rect.width < chrome.reader_button.rect.x);
+    }
+
     #[test]
     fn test_hit_test_address_bar() {
         let chrome = Chrome::new(800.0);
@@ -865,16 +1418,52 @@ mod tests {
     fn test_tab_layout() {
         let mut chrome = Chrome::new(800.0);
 
-        let tab_infos = vec![
-            (TabId(0), "Tab 1".to_string(), false, true),
-            (TabId(1), "Tab 2".to_string(), false, false),
-        ];
+        let icon = ImagePixels { width: 16, height: 16, data: vec![0; 16 * 16 * 4].into() };
+        let info = |id, title: &str, is_private, favicon| TabInfo {
+            id: TabId(id),
+            title: title.to_string(),
+            is_loading: false,
+            is_private,
+            favicon,
+        };
+        let tab_infos = vec![info(0, "Tab 1", false, Some(icon.clone())), info(1, "Tab 2", true, None)];
 
         chrome.layout_tabs(&tab_infos, TabId(0));
 
         assert_eq!(chrome.tabs.len(), 2);
         assert!(chrome.tabs[0].is_active);
         assert!(!chrome.tabs[1].is_active);
+        assert!(!chrome.tabs[0].is_private && chrome.tabs[1].is_private);
+
+        // The icon is drawn left of the title, the other tab gets a blank
+        // page in its place
+        let list = chrome.build_display_list();
+        let icons: Vec<Rect> = list
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                PaintCommand::DrawImage { rect, pixels: Some(pixels), .. } if *pixels == icon => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(icons, [Rect::new(chrome.tabs[0].rect.x + 8.0, 8.0, 16.0, 16.0)]);
+        let title_x = list.commands.iter().find_map(|command| match command {
+            PaintCommand::DrawText { text, x, .. } if text == "Tab 2" => Some(*x),
+            _ => None,
+        });
+        assert_eq!(title_x, Some(chrome.tabs[1].rect.x + 8.0 + FAVICON_SPACE));
+
+        // The private tab is darker, with its stripe
+        let fills: Vec<(Rect, RenderColor)> = list
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                PaintCommand::FillRect { rect, color } if rect.x == chrome.tabs[1].rect.x => Some((*rect, *color)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fills[0], (chrome.tabs[1].rect, RenderColor::new(52, 38, 74, 255)));
+        assert_eq!(fills[1].0.height, 3.0);
     }
 
     #[test]
@@ -904,4 +1493,132 @@ mod tests {
 
         assert_eq!(chrome.hit_test(center_x, center_y), Some(ChromeHit::NewTab));
     }
+
+    #[test]
+    fn test_error_badge() {
+        let texts = |chrome: &Chrome| -> Vec<String> {
+            let list = chrome.build_display_list();
+            list.commands
+                .iter()
+                .filter_map(|command| match command {
+                    PaintCommand::DrawText { text, .. } => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let mut chrome = Chrome::new(800.0);
+        assert!(!texts(&chrome).iter().any(|text| text.chars().all(|c| c.is_ascii_digit())));
+
+        chrome.error_count = 3;
+        assert!(texts(&chrome).contains(&"3".to_string()));
+        chrome.error_count = 120;
+        assert!(texts(&chrome).contains(&"99+".to_string()));
+    }
+
+    #[test]
+    fn test_find_bar_counter() {
+        let mut chrome = Chrome::new(800.0);
+        assert!(chrome.build_find_bar_display_list().is_empty());
+
+        chrome.find_bar.open = true;
+        assert_eq!(chrome.find_bar.counter(), "");
+        for c in "foxe".chars() {
+            chrome.find_bar.insert_char(c);
+        }
+        chrome.find_bar.delete_char();
+        chrome.find_bar.current = Some(2);
+        chrome.find_bar.count = 17;
+        assert_eq!(chrome.find_bar.query, "fox");
+        assert_eq!(chrome.find_bar.counter(), "3/17");
+        let list = chrome.build_find_bar_display_list();
+        assert!(list.commands.iter().any(|c| matches!(c, PaintCommand::DrawText { text, .. } if text == "3/17")));
+
+        let bar = chrome.find_bar_rect();
+        assert_eq!((bar.right(), bar.y), (800.0 - PADDING, CHROME_HEIGHT));
+    }
+
+    #[test]
+    fn test_toast_centered_over_page() {
+        let mut chrome = Chrome::new(800.0);
+        assert!(chrome.build_toast_display_list().is_empty());
+
+        chrome.toast = Some("Saved page.png".to_string());
+        let list = chrome.build_toast_display_list();
+        let Some(PaintCommand::FillRect { rect, .. }) = list.commands.first() else {
+            panic!("no toast drawn");
+        };
+        assert_eq!(rect.x + rect.width / 2.0, 400.0);
+        assert_eq!(rect.y, CHROME_HEIGHT + PADDING);
+        let saved = |c: &PaintCommand| matches!(c, PaintCommand::DrawText { text, .. } if text == "Saved page.png");
+        assert!(list.commands.iter().any(saved));
+    }
+
+    #[test]
+    fn test_status_at_bottom_left() {
+        let mut chrome = Chrome::new(300.0);
+        assert!(chrome.build_status_display_list(600.0).is_empty());
+
+        chrome.status = Some("https://a.test/page#top".to_string());
+        let (rect, text) = chrome.status_layout(600.0).unwrap();
+        assert_eq!((rect.x, rect.y + rect.height), (0.0, 600.0));
+        assert_eq!(text, "https://a.test/page#top");
+        assert!(!chrome.build_status_display_list(600.0).is_empty());
+
+        // Long URLs are cut short to the window
+        chrome.status = Some(format!("https://a.test/{}", "x".repeat(100)));
+        let (rect, text) = chrome.status_layout(600.0).unwrap();
+        assert!(rect.width <= 300.0);
+        assert!(text.ends_with('\u{2026}') && text.chars().count() < 45);
+
+        // Hidden while the find bar covers the corner, in a short window
+        chrome.find_bar.open = true;
+        assert!(chrome.status_layout(600.0).is_some());
+        assert!(chrome.status_layout(CHROME_HEIGHT + 30.0).is_none());
+    }
+
+    #[test]
+    fn test_loading_bar_fills_and_fades() {
+        let mut chrome = Chrome::new(800.0);
+        let bar_width = |chrome: &Chrome| {
+            let mut commands = Vec::new();
+            chrome.loading_bar.render(800.0, 0.0, &mut commands);
+            match commands.first() {
+                Some(PaintCommand::FillRect { rect, .. }) => rect.width,
+                _ => 0.0,
+            }
+        };
+
+        // The bar only grows as the load goes on, even when told less
+        chrome.loading_bar.start();
+        let mut last = 0.0;
+        for progress in [0.1, 0.3, 0.5, 0.7, 0.3, 0.85] {
+            chrome.loading_bar.set_progress(Some(progress));
+            for _ in 0..5 {
+                chrome.tick_loading(16.0);
+                assert!(bar_width(&chrome) >= last);
+                last = bar_width(&chrome);
+            }
+        }
+        assert!(last > 400.0 && last <= 680.0);
+
+        // Filled once finished, then gone a little after
+        chrome.loading_bar.finish();
+        let mut frames = 0;
+        while chrome.loading_bar.is_visible() {
+            assert!(bar_width(&chrome) >= last);
+            last = bar_width(&chrome);
+            chrome.tick_loading(16.0);
+            frames += 1;
+            assert!(frames < 40);
+        }
+        assert_eq!((last, bar_width(&chrome)), (800.0, 0.0));
+
+        // Without a length it pulses instead
+        chrome.loading_bar.set_progress(None);
+        chrome.tick_loading(300.0);
+        let mut commands = Vec::new();
+        chrome.loading_bar.render(800.0, 0.0, &mut commands);
+        assert!(matches!(commands[..], [PaintCommand::FillRect { rect, .. }] if rect.x > 0.0 && rect.width > 0.0));
+    }
 }
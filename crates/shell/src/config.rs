@@ -0,0 +1,168 @@
+//! Configuration File
+//!
+//! Settings read from `config.toml` in the config directory at startup,
+//! each of them optional, those left out keeping their defaults. Keys the
+//! browser does not know are ignored with a warning. A file that cannot be
+//! read or parsed is logged and ignored, so a bad config never keeps the
+//! browser from starting.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::BrowserConfig;
+
+/// Name of the configuration file in the config directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// The settings of a configuration file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    /// Size of the window
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Page the first tab opens at
+    pub homepage: Option<String>,
+    /// URL searches typed in the address bar go to, `{}` standing for the
+    /// search terms
+    pub search_engine: Option<String>,
+    /// Scheme of addresses typed without one
+    pub default_scheme: Option<String>,
+    /// User-Agent sent with requests and shown to scripts
+    pub user_agent: Option<String>,
+    pub download_dir: Option<PathBuf>,
+    pub smooth_scrolling: Option<bool>,
+    /// Run the scripts of pages
+    pub javascript: Option<bool>,
+    /// How far an arrow key or a step of the wheel scrolls
+    pub scroll_step: Option<f32>,
+    /// Stylesheet added to every page, in place of `user.css` in the config
+    /// directory
+    pub user_stylesheet: Option<PathBuf>,
+    /// Go back or forward by swiping sideways with two fingers
+    pub swipe_navigation: Option<bool>,
+    /// Keys the browser does not know
+    #[serde(flatten)]
+    pub unknown: toml::Table,
+}
+
+impl ConfigFile {
+    /// Load the settings of a file, none when it is missing or cannot be
+    /// read or parsed
+    pub fn load(path: &Path) -> Self {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                log::warn!("Failed to read the config file {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        match Self::parse(&text) {
+            Ok(config) => {
+                for key in config.unknown.keys() {
+                    log::warn!("Unknown setting `{}` in {} is ignored", key, path.display());
+                }
+                config
+            }
+            Err(e) => {
+                log::warn!("Failed to parse the config file {}, using the defaults: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// The settings of a file's text
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Set the settings the file has on a browser configuration
+    pub fn apply(&self, config: &mut BrowserConfig) {
+        if let Some(width) = self.width.filter(|&width| width > 0) {
+            config.width = width;
+        }
+        if let Some(height) = self.height.filter(|&height| height > 0) {
+            config.height = height;
+        }
+        if let Some(homepage) = &self.homepage {
+            config.homepage = Some(homepage.clone());
+        }
+        if let Some(search_engine) = &self.search_engine {
+            config.search_engine = search_engine.clone();
+        }
+        if let Some(scheme) = &self.default_scheme {
+            config.default_scheme = scheme.clone();
+        }
+        if let Some(user_agent) = &self.user_agent {
+            config.user_agent = Some(user_agent.clone());
+        }
+        if let Some(dir) = &self.download_dir {
+            config.download_dir = Some(dir.clone());
+        }
+        if let Some(smooth) = self.smooth_scrolling {
+            config.smooth_scrolling = smooth;
+        }
+        if let Some(javascript) = self.javascript {
+            config.javascript_enabled = javascript;
+        }
+        if let Some(step) = self.scroll_step.filter(|&step| step > 0.0) {
+            config.scroll_step = step;
+        }
+        if let Some(path) = &self.user_stylesheet {
+            config.user_stylesheet = Some(path.clone());
+        }
+        if let Some(swipe) = self.swipe_navigation {
+            config.swipe_navigation = swipe;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip() {
+        let config = ConfigFile {
+            width: Some(1280),
+            homepage: Some("https://start.test/".to_string()),
+            search_engine: Some("https://find.test/?q={}".to_string()),
+            user_agent: Some("Test/1.0".to_string()),
+            download_dir: Some(PathBuf::from("/tmp/saved")),
+            javascript: Some(false),
+            scroll_step: Some(60.0),
+            ..ConfigFile::default()
+        };
+        let text = toml::to_string(&config).unwrap();
+        assert!(text.contains("homepage = \"https://start.test/\""));
+        assert!(!text.contains("height"));
+        assert_eq!(ConfigFile::parse(&text).unwrap(), config);
+        assert_eq!(ConfigFile::parse("").unwrap(), ConfigFile::default());
+    }
+
+    #[test]
+    fn test_unknown_keys_ignored() {
+        let config = ConfigFile::parse("smooth_scrolling = false\ntheme = \"dark\"\n").unwrap();
+        assert_eq!(config.smooth_scrolling, Some(false));
+        assert_eq!(config.unknown.keys().collect::<Vec<_>>(), ["theme"]);
+
+        // The known settings are applied, the rest left alone
+        let mut browser = BrowserConfig { config_dir: None, ..BrowserConfig::default() };
+        config.apply(&mut browser);
+        assert!(!browser.smooth_scrolling);
+        assert_eq!(browser.width, BrowserConfig::default().width);
+
+        // A file with a bad value is not used, a missing one is empty
+        assert!(ConfigFile::parse("width = \"wide\"").is_err());
+        assert!(ConfigFile::parse("width = ").is_err());
+        let dir = std::env::temp_dir().join(format!("gugalanna-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE_NAME);
+        std::fs::write(&path, "javascript = 1").unwrap();
+        assert_eq!(ConfigFile::load(&path), ConfigFile::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(ConfigFile::load(&dir.join(CONFIG_FILE_NAME)), ConfigFile::default());
+    }
+}
@@ -0,0 +1,245 @@
+//! Context Menu
+//!
+//! The menu a right click opens over the page. Its items depend on what is
+//! under the click: a link, an image, the selected text, or else the page
+//! itself. The menu is drawn by the browser over the page, and goes away
+//! once an item is picked, Escape is pressed or the user clicks elsewhere.
+
+use gugalanna_layout::Rect;
+use gugalanna_render::{BorderColors, BorderStyles, BorderWidths, DisplayList, FontFace, PaintCommand, RenderColor};
+use url::Url;
+
+/// Menu size
+const MENU_WIDTH: f32 = 200.0;
+const ITEM_HEIGHT: f32 = 26.0;
+const MENU_PADDING: f32 = 4.0;
+
+/// Something the menu can do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    OpenInNewTab,
+    CopyLinkAddress,
+    SaveLinkAs,
+    CopyImageUrl,
+    SaveImage,
+    Copy,
+    Back,
+    Forward,
+    Reload,
+    ViewSource,
+    SavePageAsImage,
+    SavePageAsPdf,
+}
+
+impl MenuItem {
+    pub fn label(self) -> &'static str {
+        match self {
+            MenuItem::OpenInNewTab => "Open in New Tab",
+            MenuItem::CopyLinkAddress => "Copy Link Address",
+            MenuItem::SaveLinkAs => "Save Link As",
+            MenuItem::CopyImageUrl => "Copy Image URL",
+            MenuItem::SaveImage => "Save Image",
+            MenuItem::Copy => "Copy",
+            MenuItem::Back => "Back",
+            MenuItem::Forward => "Forward",
+            MenuItem::Reload => "Reload",
+            MenuItem::ViewSource => "View Page Source",
+            MenuItem::SavePageAsImage => "Save Page as Image",
+            MenuItem::SavePageAsPdf => "Save Page as PDF",
+        }
+    }
+}
+
+/// What is under a right click
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MenuContext {
+    /// Target of the link clicked
+    pub link: Option<Url>,
+    /// Source of the image clicked
+    pub image: Option<Url>,
+    /// Selected text, when the click was on it
+    pub selection: Option<String>,
+}
+
+/// Items of the menu for what was clicked: those of a link, then those of
+/// an image, as an image may be a link, then Copy for selected text; with
+/// none of these, those of the page
+pub fn menu_items(context: &MenuContext) -> Vec<MenuItem> {
+    let mut items = Vec::new();
+    if context.link.is_some() {
+        items.extend([MenuItem::OpenInNewTab, MenuItem::CopyLinkAddress, MenuItem::SaveLinkAs]);
+    }
+    if context.image.is_some() {
+        items.extend([MenuItem::CopyImageUrl, MenuItem::SaveImage]);
+    }
+    if context.selection.is_some() {
+        items.push(MenuItem::Copy);
+    }
+    if items.is_empty() {
+        items.extend([
+            MenuItem::Back,
+            MenuItem::Forward,
+            MenuItem::Reload,
+            MenuItem::ViewSource,
+            MenuItem::SavePageAsImage,
+            MenuItem::SavePageAsPdf,
+        ]);
+    }
+    items
+}
+
+/// An open context menu
+#[derive(Debug, Clone)]
+pub struct ContextMenu {
+    /// What was clicked
+    pub context: MenuContext,
+    /// Items with whether each can be picked
+    items: Vec<(MenuItem, bool)>,
+    /// Area of the menu in the window
+    rect: Rect,
+    /// Index of the item under the mouse
+    hovered: Option<usize>,
+}
+
+impl ContextMenu {
+    /// Open a menu at a click, kept inside the window; `enabled` tells
+    /// whether an item can be picked
+    pub fn new(context: MenuContext, x: f32, y: f32, window: (f32, f32), enabled: impl Fn(MenuItem) -> bool) -> Self {
+        let items: Vec<_> = menu_items(&context).into_iter().map(|item| (item, enabled(item))).collect();
+        let height = items.len() as f32 * ITEM_HEIGHT + MENU_PADDING * 2.0;
+        let (window_width, window_height) = window;
+        // Opened to the left or above the click when it does not fit
+        let x = if x + MENU_WIDTH > window_width { (x - MENU_WIDTH).max(0.0) } else { x };
+        let y = if y + height > window_height { (y - height).max(0.0) } else { y };
+        Self { context, items, rect: Rect::new(x, y, MENU_WIDTH, height), hovered: None }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.rect.contains(x, y)
+    }
+
+    fn index_at(&self, x: f32, y: f32) -> Option<usize> {
+        if !self.contains(x, y) || y < self.rect.y + MENU_PADDING {
+            return None;
+        }
+        let index = ((y - self.rect.y - MENU_PADDING) / ITEM_HEIGHT) as usize;
+        (index < self.items.len()).then_some(index)
+    }
+
+    /// The item at a position in the window, if it can be picked
+    pub fn item_at(&self, x: f32, y: f32) -> Option<MenuItem> {
+        self.index_at(x, y).map(|i| self.items[i]).filter(|(_, enabled)| *enabled).map(|(item, _)| item)
+    }
+
+    /// Highlight the item under the mouse, returns whether that changed
+    pub fn hover(&mut self, x: f32, y: f32) -> bool {
+        let hovered = self.index_at(x, y).filter(|&i| self.items[i].1);
+        std::mem::replace(&mut self.hovered, hovered) != hovered
+    }
+
+    /// Build a display list for the menu, painted over the page
+    pub fn build_display_list(&self) -> DisplayList {
+        let mut list = DisplayList::new();
+        let border = RenderColor::new(180, 180, 180, 255);
+
+        // Shadow, then the menu over it
+        list.push(PaintCommand::FillRect {
+            rect: Rect::new(self.rect.x + 2.0, self.rect.y + 2.0, self.rect.width, self.rect.height),
+            color: RenderColor::new(0, 0, 0, 40),
+        });
+        list.push(PaintCommand::FillRect { rect: self.rect, color: RenderColor::new(255, 255, 255, 255) });
+        list.push(PaintCommand::DrawBorder {
+            rect: self.rect,
+            widths: BorderWidths { top: 1.0, right: 1.0, bottom: 1.0, left: 1.0 },
+            styles: BorderStyles::default(),
+            colors: BorderColors::all(border),
+        });
+
+        for (i, (item, enabled)) in self.items.iter().enumerate() {
+            let item_rect = Rect::new(
+                self.rect.x + 1.0,
+                self.rect.y + MENU_PADDING + i as f32 * ITEM_HEIGHT,
+                self.rect.width - 2.0,
+                ITEM_HEIGHT,
+            );
+            if self.hovered == Some(i) {
+                list.push(PaintCommand::FillRect { rect: item_rect, color: RenderColor::new(66, 133, 244, 255) });
+            }
+            let color = match (*enabled, self.hovered == Some(i)) {
+                (false, _) => RenderColor::new(170, 170, 170, 255),
+                (true, true) => RenderColor::new(255, 255, 255, 255),
+                (true, false) => RenderColor::new(0, 0, 0, 255),
+            };
+            list.push(PaintCommand::DrawText {
+                text: item.label().to_string(),
+                x: item_rect.x + 12.0,
+                y: item_rect.y + ITEM_HEIGHT / 2.0 - 7.0,
+                color,
+                font_size: 13.0,
+                face: FontFace::REGULAR,
+                family: None,
+            });
+        }
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items_follow_what_was_clicked() {
+        let url = |s: &str| Some(Url::parse(s).unwrap());
+        let link = MenuContext { link: url("https://a.test/next"), ..MenuContext::default() };
+        assert_eq!(menu_items(&link), [MenuItem::OpenInNewTab, MenuItem::CopyLinkAddress, MenuItem::SaveLinkAs]);
+
+        let image = MenuContext { image: url("https://a.test/cat.png"), ..MenuContext::default() };
+        assert_eq!(menu_items(&image), [MenuItem::CopyImageUrl, MenuItem::SaveImage]);
+
+        let linked_image = MenuContext { link: url("https://a.test/"), image: url("https://a.test/cat.png"), ..image };
+        assert_eq!(
+            menu_items(&linked_image),
+            [
+                MenuItem::OpenInNewTab,
+                MenuItem::CopyLinkAddress,
+                MenuItem::SaveLinkAs,
+                MenuItem::CopyImageUrl,
+                MenuItem::SaveImage,
+            ]
+        );
+
+        let selection = MenuContext { selection: Some("quick fox".to_string()), ..MenuContext::default() };
+        assert_eq!(menu_items(&selection), [MenuItem::Copy]);
+
+        let page = MenuContext::default();
+        assert_eq!(
+            menu_items(&page),
+            [
+                MenuItem::Back,
+                MenuItem::Forward,
+                MenuItem::Reload,
+                MenuItem::ViewSource,
+                MenuItem::SavePageAsImage,
+                MenuItem::SavePageAsPdf,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_menu_picks_enabled_items() {
+        let enabled = |item| item != MenuItem::Back;
+        let menu = ContextMenu::new(MenuContext::default(), 100.0, 100.0, (800.0, 600.0), enabled);
+        let row = |i: f32| 100.0 + MENU_PADDING + ITEM_HEIGHT * i + ITEM_HEIGHT / 2.0;
+        assert_eq!(menu.item_at(150.0, row(0.0)), None);
+        assert_eq!(menu.item_at(150.0, row(2.0)), Some(MenuItem::Reload));
+        assert_eq!(menu.item_at(150.0, row(4.0)), Some(MenuItem::SavePageAsImage));
+        assert_eq!(menu.item_at(150.0, row(6.0)), None);
+        assert!(!menu.contains(50.0, row(1.0)));
+
+        // A click near the corner opens the menu up and to the left
+        let menu = ContextMenu::new(MenuContext::default(), 790.0, 590.0, (800.0, 600.0), |_| true);
+        assert!(menu.contains(700.0, 580.0));
+        assert_eq!(menu.item_at(700.0, 590.0 - MENU_PADDING - ITEM_HEIGHT / 2.0), Some(MenuItem::SavePageAsPdf));
+    }
+}
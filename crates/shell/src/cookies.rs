@@ -0,0 +1,36 @@
+//! Page cookies
+//!
+//! Gives the scripts of pages the cookies of the browser's HTTP client,
+//! so `document.cookie` and the requests of the session share one jar.
+
+use gugalanna_js::CookieAccess;
+use gugalanna_net::{CookieSource, SharedCookieJar};
+use url::Url;
+
+/// The cookie jar as scripts see it, without HttpOnly cookies
+pub struct PageCookies {
+    jar: SharedCookieJar,
+}
+
+impl PageCookies {
+    pub fn new(jar: SharedCookieJar) -> Self {
+        Self { jar }
+    }
+}
+
+impl CookieAccess for PageCookies {
+    fn cookies(&self, url: &Url) -> String {
+        match self.jar.lock() {
+            Ok(jar) => jar.cookie_string(url, CookieSource::Script),
+            Err(_) => String::new(),
+        }
+    }
+
+    fn set_cookie(&self, url: &Url, cookie: &str) {
+        if let Ok(mut jar) = self.jar.lock() {
+            if !jar.set_cookie(url, cookie, CookieSource::Script) {
+                log::debug!("Refused cookie set by a script on {}: {}", url, cookie);
+            }
+        }
+    }
+}
@@ -1,14 +1,26 @@
 //! Developer Tools Panel
 //!
 //! Provides Console, DOM Inspector, Network, and Element Selector panels.
+//!
+//! The Console panel lists what the page's scripts logged, following new
+//! messages, above a line where code is typed and run in the page; its
+//! result is logged after it. Up and Down go through the code run before.
+//!
+//! The panel takes the bottom of the window, and the page is laid out in
+//! what is left. The Elements panel shows the DOM tree beside the
+//! attributes and computed style of the selected element, which is
+//! highlighted on the page with its margin, border, padding and content
+//! boxes.
 
 use std::collections::HashSet;
 
 use gugalanna_dom::{DomTree, NodeId, NodeType};
-use gugalanna_js::{ConsoleMessage, LogLevel};
+use gugalanna_js::{ConsoleMessage, JsError, JsRuntime, JsValue, LogLevel};
 use gugalanna_layout::Rect;
 use gugalanna_net::NetworkRequest;
-use gugalanna_render::{DisplayList, PaintCommand, RenderColor};
+use gugalanna_render::{DisplayList, FontFace, PaintCommand, RenderColor};
+
+use crate::geometry::BoxModel;
 
 /// Height of the DevTools panel when open
 pub const DEVTOOLS_HEIGHT: f32 = 250.0;
@@ -16,6 +28,51 @@ pub const DEVTOOLS_HEIGHT: f32 = 250.0;
 /// Height of the tab bar within DevTools
 const DEVTOOLS_TAB_HEIGHT: f32 = 28.0;
 
+/// Height of a line of the DOM tree
+const DOM_LINE_HEIGHT: f32 = 18.0;
+
+/// Height of a line of the console
+const CONSOLE_LINE_HEIGHT: f32 = 18.0;
+
+/// Height of the line at the bottom of the console code is typed in
+const CONSOLE_INPUT_HEIGHT: f32 = 24.0;
+
+/// Share of the panel's width the DOM tree takes, the selected element's
+/// attributes and style take the rest
+const DOM_TREE_SHARE: f32 = 0.6;
+
+/// Nodes this deep in the DOM tree are expanded until collapsed
+const EXPANDED_DEPTH: usize = 3;
+
+/// Computed style properties shown for the selected element
+pub const INSPECTED_PROPERTIES: &[&str] = &[
+    "display",
+    "position",
+    "width",
+    "height",
+    "margin-top",
+    "margin-right",
+    "margin-bottom",
+    "margin-left",
+    "padding-top",
+    "padding-right",
+    "padding-bottom",
+    "padding-left",
+    "border-top-width",
+    "border-right-width",
+    "border-bottom-width",
+    "border-left-width",
+    "color",
+    "background-color",
+    "font-size",
+    "font-family",
+    "font-weight",
+    "line-height",
+    "overflow",
+    "z-index",
+    "opacity",
+];
+
 /// DevTools tab type
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum DevToolsTab {
@@ -32,12 +89,27 @@ pub enum DevToolsHit {
     Tab(DevToolsTab),
     /// Element selector button was clicked
     ElementSelector,
+    /// The console's Clear button was clicked
+    ClearConsole,
+    /// The console's input line was clicked
+    ConsoleInput,
     /// Content area was clicked
     Content { local_x: f32, local_y: f32 },
     /// DOM tree node was clicked
     DomNode(NodeId),
 }
 
+/// A line of the DOM tree as the Elements panel shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeRow {
+    pub node_id: NodeId,
+    pub depth: usize,
+    pub label: String,
+    /// Whether the node has children to show or hide
+    pub expandable: bool,
+    pub expanded: bool,
+}
+
 /// DevTools state
 #[derive(Debug)]
 pub struct DevTools {
@@ -57,6 +129,20 @@ pub struct DevTools {
     pub network_scroll: f32,
     /// Expanded nodes in DOM tree
     pub expanded_nodes: HashSet<NodeId>,
+    /// Nodes collapsed that are expanded at first
+    pub collapsed_nodes: HashSet<NodeId>,
+    /// Code being typed in the console
+    pub console_input: String,
+    /// Whether keys go to the console's input line
+    pub console_focused: bool,
+    /// Code run in the console, oldest first
+    console_history: Vec<String>,
+    /// Entry of the history in the input line, while going through it
+    history_index: Option<usize>,
+    /// What was typed before going through the history
+    history_draft: String,
+    /// Number of console messages when the panel last followed them
+    console_seen: usize,
     /// Window width
     width: f32,
 }
@@ -79,6 +165,13 @@ impl DevTools {
             dom_scroll: 0.0,
             network_scroll: 0.0,
             expanded_nodes: HashSet::new(),
+            collapsed_nodes: HashSet::new(),
+            console_input: String::new(),
+            console_focused: false,
+            console_history: Vec::new(),
+            history_index: None,
+            history_draft: String::new(),
+            console_seen: 0,
             width: window_width,
         }
     }
@@ -104,6 +197,7 @@ impl DevTools {
         let mut current = node_id;
         while let Some(node) = tree.get(current) {
             self.expanded_nodes.insert(current);
+            self.collapsed_nodes.remove(&current);
             if let Some(parent) = node.parent {
                 current = parent;
             } else {
@@ -112,15 +206,137 @@ impl DevTools {
         }
     }
 
-    /// Toggle expansion of a DOM node
-    pub fn toggle_node_expansion(&mut self, node_id: NodeId) {
-        if self.expanded_nodes.contains(&node_id) {
+    /// Toggle expansion of a DOM node at a depth of the tree
+    pub fn toggle_node_expansion(&mut self, node_id: NodeId, depth: usize) {
+        if self.is_expanded(node_id, depth) {
             self.expanded_nodes.remove(&node_id);
+            self.collapsed_nodes.insert(node_id);
         } else {
+            self.collapsed_nodes.remove(&node_id);
             self.expanded_nodes.insert(node_id);
         }
     }
 
+    fn is_expanded(&self, node_id: NodeId, depth: usize) -> bool {
+        !self.collapsed_nodes.contains(&node_id) && (depth < EXPANDED_DEPTH || self.expanded_nodes.contains(&node_id))
+    }
+
+    /// The lines of the DOM tree, the children of expanded nodes under
+    /// them; text of only whitespace is left out
+    pub fn tree_rows(&self, tree: &DomTree) -> Vec<TreeRow> {
+        let mut rows = Vec::new();
+        self.push_tree_rows(tree, tree.document_id(), 0, &mut rows);
+        rows
+    }
+
+    fn push_tree_rows(&self, tree: &DomTree, node_id: NodeId, depth: usize, rows: &mut Vec<TreeRow>) {
+        let Some(label) = tree.get(node_id).and_then(|node| node_label(&node.node_type)) else {
+            return;
+        };
+        let children = tree.children(node_id);
+        let expandable = children.iter().any(|&child| tree.get(child).and_then(|c| node_label(&c.node_type)).is_some());
+        let expanded = expandable && self.is_expanded(node_id, depth);
+        rows.push(TreeRow { node_id, depth, label, expandable, expanded });
+        if expanded {
+            for child in children {
+                self.push_tree_rows(tree, child, depth + 1, rows);
+            }
+        }
+    }
+
+    /// Handle a click in the Elements panel, at a position in its content
+    /// area: one on a node's arrow expands or collapses it, one elsewhere
+    /// on its line selects it and returns it
+    pub fn click_tree(&mut self, tree: &DomTree, local_x: f32, local_y: f32) -> Option<NodeId> {
+        if local_x >= self.width * DOM_TREE_SHARE {
+            return None;
+        }
+        let line = (local_y + self.dom_scroll - 6.0) / DOM_LINE_HEIGHT;
+        if line < 0.0 {
+            return None;
+        }
+        let row = self.tree_rows(tree).into_iter().nth(line as usize)?;
+        let indent = row_indent(row.depth);
+        if row.expandable && local_x >= indent - 14.0 && local_x < indent {
+            self.toggle_node_expansion(row.node_id, row.depth);
+            return None;
+        }
+        self.selected_element = Some(row.node_id);
+        Some(row.node_id)
+    }
+
+    /// Type text at the end of the console's input line
+    pub fn insert_console_text(&mut self, text: &str) {
+        self.console_input.push_str(text);
+    }
+
+    /// Delete the last character of the console's input line
+    pub fn delete_console_char(&mut self) {
+        self.console_input.pop();
+    }
+
+    /// Show the code run before (`back`) or after the one in the input
+    /// line; past the last one, what was typed is back
+    pub fn step_history(&mut self, back: bool) {
+        let index = match (self.history_index, back) {
+            (None, true) => self.console_history.len().checked_sub(1),
+            (None, false) => return,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => (i + 1 < self.console_history.len()).then_some(i + 1),
+        };
+        if self.history_index.is_none() {
+            if index.is_none() {
+                return;
+            }
+            self.history_draft = std::mem::take(&mut self.console_input);
+        }
+        self.history_index = index;
+        self.console_input = match index {
+            Some(i) => self.console_history[i].clone(),
+            None => std::mem::take(&mut self.history_draft),
+        };
+    }
+
+    /// Run the code in the console's input line in a page's runtime,
+    /// logging the code, then its result or error
+    pub fn run_console_input(&mut self, rt: &JsRuntime) {
+        let code = std::mem::take(&mut self.console_input);
+        self.history_index = None;
+        self.history_draft.clear();
+        if code.trim().is_empty() {
+            return;
+        }
+        if self.console_history.last() != Some(&code) {
+            self.console_history.push(code.clone());
+        }
+
+        let log = |message: ConsoleMessage| {
+            if let Ok(mut messages) = rt.console_messages().lock() {
+                messages.push(message);
+            }
+        };
+        log(ConsoleMessage::text(LogLevel::Log, format!("> {}", code)));
+        match rt.eval(&code) {
+            Ok(value) => log(ConsoleMessage::text(LogLevel::Log, format!("< {}", format_result(&value)))),
+            // Exceptions were logged as uncaught
+            Err(JsError::Runtime { .. }) => {}
+            Err(e) => log(ConsoleMessage::text(LogLevel::Error, e.to_string())),
+        }
+    }
+
+    /// Follow the console's messages, scrolling to the last one when there
+    /// are new ones; returns whether they changed since the last call
+    pub fn follow_console(&mut self, messages: &[ConsoleMessage]) -> bool {
+        if messages.len() == self.console_seen {
+            return false;
+        }
+        self.console_seen = messages.len();
+        let lines: usize = messages.iter().map(|message| message.message.split('\n').count()).sum();
+        let visible = DEVTOOLS_HEIGHT - DEVTOOLS_TAB_HEIGHT - CONSOLE_INPUT_HEIGHT - 8.0;
+        self.console_scroll = (lines as f32 * CONSOLE_LINE_HEIGHT - visible).max(0.0);
+        true
+    }
+
     /// Build display list for DevTools panel
     pub fn build_display_list(
         &self,
@@ -128,6 +344,7 @@ impl DevTools {
         console_messages: &[ConsoleMessage],
         dom_tree: Option<&DomTree>,
         network_requests: &[NetworkRequest],
+        selected_style: &[(&str, String)],
     ) -> DisplayList {
         if !self.open {
             return DisplayList { commands: vec![] };
@@ -171,7 +388,7 @@ impl DevTools {
             }
             DevToolsTab::Elements => {
                 if let Some(tree) = dom_tree {
-                    self.build_elements_panel(&mut commands, content_y, content_height, tree);
+                    self.build_elements_panel(&mut commands, content_y, content_height, tree, selected_style);
                 }
             }
             DevToolsTab::Network => {
@@ -231,11 +448,36 @@ impl DevTools {
                     RenderColor::new(160, 160, 160, 255)
                 },
                 font_size: 12.0,
+                face: FontFace::REGULAR,
+                family: None,
             });
 
             x += tab_width + 4.0;
         }
 
+        // Clear button of the console
+        if self.active_tab == DevToolsTab::Console {
+            let clear_x = self.width - 190.0;
+            commands.push(PaintCommand::FillRect {
+                rect: Rect {
+                    x: clear_x,
+                    y: y + 4.0,
+                    width: 80.0,
+                    height: DEVTOOLS_TAB_HEIGHT - 8.0,
+                },
+                color: RenderColor::new(60, 60, 60, 255),
+            });
+            commands.push(PaintCommand::DrawText {
+                x: clear_x + 8.0,
+                y: y + 8.0,
+                text: "Clear".to_string(),
+                color: RenderColor::new(255, 255, 255, 255),
+                font_size: 12.0,
+                face: FontFace::REGULAR,
+                family: None,
+            });
+        }
+
         // Element selector button
         let selector_x = self.width - 100.0;
         commands.push(PaintCommand::FillRect {
@@ -257,6 +499,8 @@ impl DevTools {
             text: "Select".to_string(),
             color: RenderColor::new(255, 255, 255, 255),
             font_size: 12.0,
+            face: FontFace::REGULAR,
+            family: None,
         });
     }
 
@@ -267,20 +511,23 @@ impl DevTools {
         height: f32,
         messages: &[ConsoleMessage],
     ) {
-        let line_height = 18.0;
+        let line_height = CONSOLE_LINE_HEIGHT;
         let mut line_y = y + 8.0 - self.console_scroll;
+        // Messages are listed above the input line
+        let input_y = y + height - CONSOLE_INPUT_HEIGHT;
+        let height = height - CONSOLE_INPUT_HEIGHT;
 
         for msg in messages {
+            let (indicator, color) = match msg.level {
+                LogLevel::Log => ("", RenderColor::new(200, 200, 200, 255)),
+                LogLevel::Info => ("i", RenderColor::new(100, 180, 255, 255)),
+                LogLevel::Warn => ("!", RenderColor::new(255, 200, 100, 255)),
+                LogLevel::Error => ("X", RenderColor::new(255, 100, 100, 255)),
+                LogLevel::Debug => ("D", RenderColor::new(150, 150, 150, 255)),
+            };
+
             if line_y > y - line_height && line_y < y + height {
                 // Level indicator
-                let (indicator, color) = match msg.level {
-                    LogLevel::Log => ("", RenderColor::new(200, 200, 200, 255)),
-                    LogLevel::Info => ("i", RenderColor::new(100, 180, 255, 255)),
-                    LogLevel::Warn => ("!", RenderColor::new(255, 200, 100, 255)),
-                    LogLevel::Error => ("X", RenderColor::new(255, 100, 100, 255)),
-                    LogLevel::Debug => ("D", RenderColor::new(150, 150, 150, 255)),
-                };
-
                 if !indicator.is_empty() {
                     commands.push(PaintCommand::DrawText {
                         x: 10.0,
@@ -288,18 +535,29 @@ impl DevTools {
                         text: indicator.to_string(),
                         color,
                         font_size: 12.0,
+                        face: FontFace::REGULAR,
+                        family: None,
                     });
                 }
+            }
 
-                commands.push(PaintCommand::DrawText {
-                    x: 30.0,
-                    y: line_y,
-                    text: msg.message.clone(),
-                    color,
-                    font_size: 12.0,
-                });
+            // Messages logged in groups are indented, tables take a line
+            // per row
+            let x = 30.0 + msg.group_depth as f32 * 12.0;
+            for line in msg.message.split('\n') {
+                if line_y > y - line_height && line_y < y + height {
+                    commands.push(PaintCommand::DrawText {
+                        x,
+                        y: line_y,
+                        text: line.to_string(),
+                        color,
+                        font_size: 12.0,
+                        face: FontFace::REGULAR,
+                        family: None,
+                    });
+                }
+                line_y += line_height;
             }
-            line_y += line_height;
         }
 
         // Empty state
@@ -310,6 +568,59 @@ impl DevTools {
                 text: "No console messages".to_string(),
                 color: RenderColor::new(120, 120, 120, 255),
                 font_size: 12.0,
+                face: FontFace::REGULAR,
+                family: None,
+            });
+        }
+
+        // Input line
+        commands.push(PaintCommand::FillRect {
+            rect: Rect {
+                x: 0.0,
+                y: input_y,
+                width: self.width,
+                height: CONSOLE_INPUT_HEIGHT,
+            },
+            color: RenderColor::new(36, 36, 36, 255),
+        });
+        commands.push(PaintCommand::FillRect {
+            rect: Rect {
+                x: 0.0,
+                y: input_y,
+                width: self.width,
+                height: 1.0,
+            },
+            color: RenderColor::new(60, 60, 60, 255),
+        });
+        commands.push(PaintCommand::DrawText {
+            x: 10.0,
+            y: input_y + 6.0,
+            text: ">".to_string(),
+            color: RenderColor::new(100, 180, 255, 255),
+            font_size: 12.0,
+            face: FontFace::REGULAR,
+            family: None,
+        });
+        commands.push(PaintCommand::DrawText {
+            x: 30.0,
+            y: input_y + 6.0,
+            text: self.console_input.clone(),
+            color: RenderColor::new(230, 230, 230, 255),
+            font_size: 12.0,
+            face: FontFace::REGULAR,
+            family: None,
+        });
+        if self.console_focused {
+            // Caret after the text (approximate)
+            let caret_x = 30.0 + self.console_input.chars().count() as f32 * 7.0;
+            commands.push(PaintCommand::FillRect {
+                rect: Rect {
+                    x: caret_x,
+                    y: input_y + 5.0,
+                    width: 1.0,
+                    height: 14.0,
+                },
+                color: RenderColor::new(230, 230, 230, 255),
             });
         }
     }
@@ -320,131 +631,105 @@ impl DevTools {
         y: f32,
         height: f32,
         tree: &DomTree,
+        selected_style: &[(&str, String)],
     ) {
-        let line_height = 18.0;
+        let tree_width = self.width * DOM_TREE_SHARE;
         let mut line_y = y + 8.0 - self.dom_scroll;
 
-        // Render DOM tree starting from document root
-        let root_id = tree.document_id();
-        self.render_dom_node(commands, tree, root_id, 0, &mut line_y, y, height);
-    }
-
-    fn render_dom_node(
-        &self,
-        commands: &mut Vec<PaintCommand>,
-        tree: &DomTree,
-        node_id: NodeId,
-        depth: i32,
-        line_y: &mut f32,
-        panel_y: f32,
-        panel_height: f32,
-    ) {
-        let indent = 10.0 + depth as f32 * 16.0;
-        let line_height = 18.0;
-
-        if let Some(node) = tree.get(node_id) {
-            // Only render if visible
-            if *line_y > panel_y - line_height && *line_y < panel_y + panel_height {
-                let is_selected = self.selected_element == Some(node_id);
-
+        for row in self.tree_rows(tree) {
+            if line_y > y - DOM_LINE_HEIGHT && line_y < y + height {
                 // Selection highlight
-                if is_selected {
+                if self.selected_element == Some(row.node_id) {
                     commands.push(PaintCommand::FillRect {
                         rect: Rect {
                             x: 0.0,
-                            y: *line_y - 2.0,
-                            width: self.width,
-                            height: line_height,
+                            y: line_y - 2.0,
+                            width: tree_width,
+                            height: DOM_LINE_HEIGHT,
                         },
                         color: RenderColor::new(74, 144, 226, 100),
                     });
                 }
 
-                // Node text
-                let text = match &node.node_type {
-                    NodeType::Element(elem) => {
-                        let tag = &elem.tag_name;
-                        let id = elem
-                            .get_attribute("id")
-                            .map(|i| format!("#{}", i))
-                            .unwrap_or_default();
-                        let class = elem
-                            .get_attribute("class")
-                            .map(|c| {
-                                format!(
-                                    ".{}",
-                                    c.split_whitespace().collect::<Vec<_>>().join(".")
-                                )
-                            })
-                            .unwrap_or_default();
-                        format!("<{}{}{}>", tag, id, class)
-                    }
-                    NodeType::Text(text) => {
-                        let trimmed = text.trim();
-                        if trimmed.is_empty() {
-                            // Skip empty text nodes
-                            *line_y += line_height;
-                            return;
-                        }
-                        let truncated = if trimmed.len() > 40 {
-                            format!("\"{}...\"", &trimmed[..40])
-                        } else {
-                            format!("\"{}\"", trimmed)
-                        };
-                        truncated
-                    }
-                    NodeType::Comment(_) => "<!-- comment -->".to_string(),
-                    NodeType::Document => "#document".to_string(),
-                    NodeType::Doctype { .. } => "<!DOCTYPE>".to_string(),
-                };
-
-                let color = match &node.node_type {
-                    NodeType::Element(_) => RenderColor::new(136, 180, 255, 255),
-                    NodeType::Text(_) => RenderColor::new(200, 200, 200, 255),
-                    _ => RenderColor::new(120, 120, 120, 255),
-                };
-
-                // Expand/collapse indicator for elements with children
-                let has_expandable_children = !node.children.is_empty();
-                if matches!(&node.node_type, NodeType::Element(_) | NodeType::Document)
-                    && has_expandable_children
-                {
-                    let expanded = self.expanded_nodes.contains(&node_id) || depth < 2;
+                let indent = row_indent(row.depth);
+                if row.expandable {
                     commands.push(PaintCommand::DrawText {
                         x: indent - 12.0,
-                        y: *line_y,
-                        text: if expanded { "v" } else { ">" }.to_string(),
+                        y: line_y,
+                        text: if row.expanded { "v" } else { ">" }.to_string(),
                         color: RenderColor::new(150, 150, 150, 255),
                         font_size: 10.0,
+                        face: FontFace::REGULAR,
+                        family: None,
                     });
                 }
 
+                let color = match tree.get(row.node_id).map(|node| &node.node_type) {
+                    Some(NodeType::Element(_)) => RenderColor::new(136, 180, 255, 255),
+                    Some(NodeType::Text(_)) => RenderColor::new(200, 200, 200, 255),
+                    _ => RenderColor::new(120, 120, 120, 255),
+                };
                 commands.push(PaintCommand::DrawText {
                     x: indent,
-                    y: *line_y,
-                    text,
+                    y: line_y,
+                    text: row.label,
                     color,
                     font_size: 12.0,
+                    face: FontFace::REGULAR,
+                    family: None,
                 });
             }
+            line_y += DOM_LINE_HEIGHT;
+        }
 
-            *line_y += line_height;
-
-            // Render children if expanded
-            let expanded = self.expanded_nodes.contains(&node_id) || depth < 2;
-            if expanded {
-                for child_id in tree.children(node_id) {
-                    self.render_dom_node(
-                        commands,
-                        tree,
-                        child_id,
-                        depth + 1,
-                        line_y,
-                        panel_y,
-                        panel_height,
-                    );
-                }
+        // The selected element's attributes and computed style, beside
+        // the tree
+        commands.push(PaintCommand::FillRect {
+            rect: Rect {
+                x: tree_width,
+                y,
+                width: self.width - tree_width,
+                height,
+            },
+            color: RenderColor::new(40, 40, 40, 255),
+        });
+        commands.push(PaintCommand::FillRect {
+            rect: Rect {
+                x: tree_width,
+                y,
+                width: 1.0,
+                height,
+            },
+            color: RenderColor::new(60, 60, 60, 255),
+        });
+        let Some(element) = self.selected_element.and_then(|id| tree.get(id)).and_then(|node| node.as_element()) else {
+            return;
+        };
+        let mut lines = vec![("Attributes".to_string(), RenderColor::new(160, 160, 160, 255))];
+        let mut attributes: Vec<_> = element.attributes.iter().collect();
+        attributes.sort();
+        for (name, value) in attributes {
+            lines.push((format!("{}=\"{}\"", name, value), RenderColor::new(230, 180, 120, 255)));
+        }
+        lines.push(("Computed".to_string(), RenderColor::new(160, 160, 160, 255)));
+        for (property, value) in selected_style {
+            lines.push((format!("{}: {}", property, value), RenderColor::new(200, 200, 200, 255)));
+        }
+        let mut line_y = y + 8.0;
+        for (text, color) in lines {
+            if line_y + DOM_LINE_HEIGHT > y + height {
+                break;
             }
+            commands.push(PaintCommand::DrawText {
+                x: tree_width + 10.0,
+                y: line_y,
+                text,
+                color,
+                font_size: 12.0,
+                face: FontFace::REGULAR,
+                family: None,
+            });
+            line_y += DOM_LINE_HEIGHT;
         }
     }
 
@@ -477,6 +762,8 @@ impl DevTools {
                 text: header.to_string(),
                 color: RenderColor::new(160, 160, 160, 255),
                 font_size: 11.0,
+                face: FontFace::REGULAR,
+                family: None,
             });
         }
 
@@ -498,6 +785,8 @@ impl DevTools {
                     text: req.status.map(|s| s.to_string()).unwrap_or("...".to_string()),
                     color: status_color,
                     font_size: 11.0,
+                    face: FontFace::REGULAR,
+                    family: None,
                 });
 
                 // Method
@@ -507,6 +796,8 @@ impl DevTools {
                     text: req.method.clone(),
                     color: RenderColor::new(200, 200, 200, 255),
                     font_size: 11.0,
+                    face: FontFace::REGULAR,
+                    family: None,
                 });
 
                 // URL (truncated)
@@ -521,6 +812,8 @@ impl DevTools {
                     text: url,
                     color: RenderColor::new(180, 180, 180, 255),
                     font_size: 11.0,
+                    face: FontFace::REGULAR,
+                    family: None,
                 });
 
                 // Size
@@ -538,6 +831,8 @@ impl DevTools {
                         text: size_str,
                         color: RenderColor::new(150, 150, 150, 255),
                         font_size: 11.0,
+                        face: FontFace::REGULAR,
+                        family: None,
                     });
                 }
 
@@ -554,6 +849,8 @@ impl DevTools {
                         text: time_str,
                         color: RenderColor::new(150, 150, 150, 255),
                         font_size: 11.0,
+                        face: FontFace::REGULAR,
+                        family: None,
                     });
                 }
             }
@@ -568,6 +865,8 @@ impl DevTools {
                 text: "No network requests".to_string(),
                 color: RenderColor::new(120, 120, 120, 255),
                 font_size: 12.0,
+                face: FontFace::REGULAR,
+                family: None,
             });
         }
     }
@@ -595,6 +894,12 @@ impl DevTools {
                 tab_x += 84.0;
             }
 
+            // Check the console's Clear button
+            let clear_x = self.width - 190.0;
+            if self.active_tab == DevToolsTab::Console && x >= clear_x && x < clear_x + 80.0 {
+                return Some(DevToolsHit::ClearConsole);
+            }
+
             // Check element selector button
             let selector_x = self.width - 100.0;
             if x >= selector_x && x < selector_x + 90.0 {
@@ -602,6 +907,11 @@ impl DevTools {
             }
         }
 
+        // Console input line
+        if self.active_tab == DevToolsTab::Console && y >= viewport_height - CONSOLE_INPUT_HEIGHT {
+            return Some(DevToolsHit::ConsoleInput);
+        }
+
         // Content area hit test
         Some(DevToolsHit::Content {
             local_x: x,
@@ -625,9 +935,84 @@ impl DevTools {
     }
 }
 
+/// Left edge of the label of a node at a depth of the DOM tree
+fn row_indent(depth: usize) -> f32 {
+    10.0 + depth as f32 * 16.0
+}
+
+/// How a node reads in the DOM tree, None for text of only whitespace
+fn node_label(node_type: &NodeType) -> Option<String> {
+    let label = match node_type {
+        NodeType::Element(elem) => {
+            let id = elem.get_attribute("id").map(|i| format!("#{}", i)).unwrap_or_default();
+            let class = elem
+                .get_attribute("class")
+                .map(|c| format!(".{}", c.split_whitespace().collect::<Vec<_>>().join(".")))
+                .unwrap_or_default();
+            format!("<{}{}{}>", elem.tag_name, id, class)
+        }
+        NodeType::Text(text) => {
+            let trimmed = text.trim();
+            if trimmed.is_empty() {
+                return None;
+            }
+            match trimmed.char_indices().nth(40) {
+                Some((end, _)) => format!("\"{}...\"", &trimmed[..end]),
+                None => format!("\"{}\"", trimmed),
+            }
+        }
+        NodeType::Comment(_) => "<!-- comment -->".to_string(),
+        NodeType::Document => "#document".to_string(),
+        NodeType::Doctype { .. } => "<!DOCTYPE>".to_string(),
+        NodeType::DocumentFragment => "#document-fragment".to_string(),
+    };
+    Some(label)
+}
+
+/// A value code run in the console evaluated to, as the console shows it
+fn format_result(value: &JsValue) -> String {
+    match value {
+        JsValue::Undefined => "undefined".to_string(),
+        JsValue::Null => "null".to_string(),
+        JsValue::Boolean(b) => b.to_string(),
+        JsValue::Number(n) if n.is_infinite() => if *n > 0.0 { "Infinity" } else { "-Infinity" }.to_string(),
+        JsValue::Number(n) => n.to_string(),
+        JsValue::String(s) => format!("'{}'", s),
+        JsValue::Array(items) => format!("[{}]", items.iter().map(format_result).collect::<Vec<_>>().join(", ")),
+        JsValue::Object => "{…}".to_string(),
+        JsValue::Function => "[Function]".to_string(),
+    }
+}
+
+/// Translucent rectangles showing a box model, each box drawn where it
+/// reaches past the one inside it: margin, border, padding, then content
+pub fn box_model_overlays(model: &BoxModel) -> Vec<(Rect, RenderColor)> {
+    let ring = |outer: Rect, inner: Rect| {
+        [
+            Rect::new(outer.x, outer.y, outer.width, inner.y - outer.y),
+            Rect::new(outer.x, inner.bottom(), outer.width, outer.bottom() - inner.bottom()),
+            Rect::new(outer.x, inner.y, inner.x - outer.x, inner.height),
+            Rect::new(inner.right(), inner.y, outer.right() - inner.right(), inner.height),
+        ]
+    };
+    let margin = RenderColor::new(246, 178, 107, 110);
+    let border = RenderColor::new(255, 229, 153, 130);
+    let padding = RenderColor::new(147, 196, 125, 130);
+    let content = RenderColor::new(111, 168, 220, 130);
+    ring(model.margin, model.border)
+        .map(|rect| (rect, margin))
+        .into_iter()
+        .chain(ring(model.border, model.padding).map(|rect| (rect, border)))
+        .chain(ring(model.padding, model.content).map(|rect| (rect, padding)))
+        .chain([(model.content, content)])
+        .filter(|(rect, _)| !rect.is_empty())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use gugalanna_html::HtmlParser;
 
     #[test]
     fn test_devtools_toggle() {
@@ -694,4 +1079,107 @@ mod tests {
         devtools.scroll(-100.0); // Should clamp to 0
         assert_eq!(devtools.console_scroll, 0.0);
     }
+
+    #[test]
+    fn test_console_history() {
+        let mut devtools = DevTools::new(800.0);
+        devtools.console_history = vec!["1 + 1".to_string(), "document.title".to_string()];
+        devtools.insert_console_text("wind");
+
+        devtools.step_history(true);
+        assert_eq!(devtools.console_input, "document.title");
+        devtools.step_history(true);
+        devtools.step_history(true);
+        assert_eq!(devtools.console_input, "1 + 1");
+        devtools.step_history(false);
+        assert_eq!(devtools.console_input, "document.title");
+
+        // Past the last entry what was typed is back
+        devtools.step_history(false);
+        assert_eq!(devtools.console_input, "wind");
+        devtools.step_history(false);
+        assert_eq!(devtools.console_input, "wind");
+    }
+
+    #[test]
+    fn test_format_result() {
+        let array = JsValue::Array(vec![JsValue::Number(1.0), JsValue::String("a".to_string()), JsValue::Null]);
+        assert_eq!(format_result(&array), "[1, 'a', null]");
+        assert_eq!(format_result(&JsValue::Number(0.5)), "0.5");
+        assert_eq!(format_result(&JsValue::Number(f64::NEG_INFINITY)), "-Infinity");
+        assert_eq!(format_result(&JsValue::Undefined), "undefined");
+        assert_eq!(format_result(&JsValue::Object), "{…}");
+    }
+
+    #[test]
+    fn test_console_input_hit() {
+        let mut devtools = DevTools::new(800.0);
+        devtools.open = true;
+        assert!(matches!(devtools.hit_test(300.0, 590.0, 600.0), Some(DevToolsHit::ConsoleInput)));
+        let tab_bar_y = 600.0 - DEVTOOLS_HEIGHT + 10.0;
+        assert!(matches!(devtools.hit_test(650.0, tab_bar_y, 600.0), Some(DevToolsHit::ClearConsole)));
+
+        // Other panels have no input line
+        devtools.active_tab = DevToolsTab::Elements;
+        assert!(matches!(devtools.hit_test(300.0, 590.0, 600.0), Some(DevToolsHit::Content { .. })));
+    }
+
+    #[test]
+    fn test_tree_rows_follow_expansion() {
+        let tree = HtmlParser::new()
+            .parse("<div id=\"a\"><p>Hello</p>\n  <p class=\"x y\">World</p></div>")
+            .unwrap();
+        let mut devtools = DevTools::new(800.0);
+        let labels = |devtools: &DevTools| -> Vec<String> {
+            devtools.tree_rows(&tree).iter().map(|row| format!("{}{}", " ".repeat(row.depth), row.label)).collect()
+        };
+        assert_eq!(labels(&devtools), ["#document", " <html>", "  <body>", "   <div#a>"]);
+
+        // The arrow of the div expands it, whitespace between its children
+        // is left out
+        let line = |i: f32| 6.0 + i * DOM_LINE_HEIGHT + DOM_LINE_HEIGHT / 2.0;
+        assert_eq!(devtools.click_tree(&tree, row_indent(3) - 6.0, line(3.0)), None);
+        assert_eq!(labels(&devtools), ["#document", " <html>", "  <body>", "   <div#a>", "    <p>", "    <p.x.y>"]);
+
+        // Elsewhere on its line a node is selected
+        let selected = devtools.click_tree(&tree, 200.0, line(5.0)).unwrap();
+        assert_eq!(tree.get(selected).and_then(|n| n.as_element()).and_then(|e| e.get_attribute("class")), Some("x y"));
+        assert_eq!(devtools.selected_element, Some(selected));
+
+        // Selecting a node on the page expands the nodes down to it
+        let text = tree.children(selected)[0];
+        devtools.expand_path_to_node(&tree, text);
+        assert_eq!(labels(&devtools).last().map(String::as_str), Some("     \"World\""));
+
+        let html = devtools.tree_rows(&tree)[1].node_id;
+        devtools.toggle_node_expansion(html, 1);
+        assert_eq!(labels(&devtools), ["#document", " <html>"]);
+    }
+
+    #[test]
+    fn test_box_model_overlays() {
+        // 10px margins, 2px borders and 8px padding around 60x20 content
+        let model = BoxModel {
+            margin: Rect::new(0.0, 0.0, 100.0, 60.0),
+            border: Rect::new(10.0, 10.0, 80.0, 40.0),
+            padding: Rect::new(12.0, 12.0, 76.0, 36.0),
+            content: Rect::new(20.0, 20.0, 60.0, 20.0),
+            fixed: false,
+        };
+        let overlays = box_model_overlays(&model);
+        let rects: Vec<Rect> = overlays.iter().map(|(rect, _)| *rect).collect();
+        assert_eq!(rects.len(), 13);
+        assert_eq!(rects[0], Rect::new(0.0, 0.0, 100.0, 10.0));
+        assert_eq!(rects[3], Rect::new(90.0, 10.0, 10.0, 40.0));
+        assert_eq!(rects[5], Rect::new(10.0, 48.0, 80.0, 2.0));
+        assert_eq!(rects[10], Rect::new(12.0, 20.0, 8.0, 20.0));
+        assert_eq!(rects[12], model.content);
+        // Each box has its own color
+        assert_ne!(overlays[0].1, overlays[4].1);
+        assert_ne!(overlays[8].1, overlays[12].1);
+
+        // Boxes without margins show no margin
+        let model = BoxModel { margin: model.border, ..model };
+        assert_eq!(box_model_overlays(&model).len(), 9);
+    }
 }
@@ -0,0 +1,350 @@
+//! Page dialogs
+//!
+//! Shows the dialogs of `alert()`, `confirm()` and `prompt()` as a box
+//! over the window. The script that opened one waits for the answer, so
+//! while it is open its own event loop runs and the page gets no input.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+
+use gugalanna_js::{DialogHandler, DialogKind, DialogRequest};
+use gugalanna_layout::Rect;
+use gugalanna_render::{
+    BorderColors, BorderStyles, BorderWidths, DisplayList, FontFace, PaintCommand, RenderBackend, RenderColor,
+};
+
+use crate::chrome::CHROME_HEIGHT;
+use crate::event::{
+    poll_events, start_text_input, stop_text_input, BrowserEvent, MouseButton, SCANCODE_BACKSPACE, SCANCODE_END,
+    SCANCODE_ESCAPE, SCANCODE_HOME, SCANCODE_LEFT, SCANCODE_RETURN, SCANCODE_RIGHT,
+};
+use crate::form::TextInputState;
+
+/// The backend, shared by the browser and the dialogs it paints over
+pub type SharedBackend = Rc<RefCell<Box<dyn RenderBackend>>>;
+
+/// Widest a dialog gets
+const DIALOG_WIDTH: f32 = 440.0;
+
+/// Padding inside the dialog
+const PADDING: f32 = 16.0;
+
+/// Height of a line of the message
+const LINE_HEIGHT: f32 = 20.0;
+
+/// Lines of the message shown, the rest is cut
+const MAX_LINES: usize = 12;
+
+/// Approximate width of a character, as the chrome measures text
+const CHAR_WIDTH: f32 = 8.0;
+
+const FIELD_HEIGHT: f32 = 28.0;
+const BUTTON_WIDTH: f32 = 72.0;
+const BUTTON_HEIGHT: f32 = 28.0;
+
+/// Split a message into lines of at most `max_chars` characters, at
+/// spaces where it can
+fn wrap_message(message: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+    for paragraph in message.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let mut word = word.to_string();
+            let line_chars = line.chars().count();
+            if line_chars > 0 && line_chars + 1 + word.chars().count() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            }
+            // Words too long for a line are broken
+            while word.chars().count() > max_chars {
+                let rest = word.split_off(word.char_indices().nth(max_chars).map_or(word.len(), |(i, _)| i));
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                lines.push(std::mem::replace(&mut word, rest));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        lines.push(line);
+    }
+    if lines.len() > MAX_LINES {
+        lines.truncate(MAX_LINES);
+        lines[MAX_LINES - 1].push('…');
+    }
+    lines
+}
+
+/// A dialog on screen and what was typed into it
+struct ModalDialog {
+    kind: DialogKind,
+    lines: Vec<String>,
+    /// The field of a prompt
+    input: TextInputState,
+    rect: Rect,
+    field_rect: Rect,
+    ok_rect: Rect,
+    /// None for alerts, which can only be acknowledged
+    cancel_rect: Option<Rect>,
+}
+
+impl ModalDialog {
+    /// Lay out a dialog for a window of the given size
+    fn new(request: &DialogRequest, window_width: f32, window_height: f32) -> Self {
+        let width = DIALOG_WIDTH.min(window_width - 2.0 * PADDING).max(BUTTON_WIDTH * 2.0 + 3.0 * PADDING);
+        let lines = wrap_message(&request.message, ((width - 2.0 * PADDING) / CHAR_WIDTH) as usize);
+        let field_space = if request.kind == DialogKind::Prompt { PADDING / 2.0 + FIELD_HEIGHT } else { 0.0 };
+        let height = PADDING + lines.len() as f32 * LINE_HEIGHT + field_space + PADDING + BUTTON_HEIGHT + PADDING;
+
+        let x = ((window_width - width) / 2.0).max(0.0);
+        let y = (CHROME_HEIGHT + 24.0).min((window_height - height).max(0.0));
+        let rect = Rect::new(x, y, width, height);
+
+        let field_y = y + PADDING + lines.len() as f32 * LINE_HEIGHT + PADDING / 2.0;
+        let field_rect = Rect::new(x + PADDING, field_y, width - 2.0 * PADDING, FIELD_HEIGHT);
+        let button_y = y + height - PADDING - BUTTON_HEIGHT;
+        let ok_rect = Rect::new(x + width - PADDING - BUTTON_WIDTH, button_y, BUTTON_WIDTH, BUTTON_HEIGHT);
+        let cancel_rect = (request.kind != DialogKind::Alert)
+            .then(|| Rect::new(ok_rect.x - PADDING / 2.0 - BUTTON_WIDTH, button_y, BUTTON_WIDTH, BUTTON_HEIGHT));
+
+        Self {
+            kind: request.kind,
+            lines,
+            input: TextInputState::new(request.default_value.clone()),
+            rect,
+            field_rect,
+            ok_rect,
+            cancel_rect,
+        }
+    }
+
+    /// Handle an event, returning the answer once the dialog is closed
+    fn handle_event(&mut self, event: &BrowserEvent) -> Option<Option<String>> {
+        let prompt = self.kind == DialogKind::Prompt;
+        match *event {
+            BrowserEvent::MouseDown { x, y, button: MouseButton::Left } => {
+                if contains(&self.ok_rect, x, y) {
+                    return Some(self.accepted());
+                }
+                if self.cancel_rect.is_some_and(|rect| contains(&rect, x, y)) {
+                    return Some(None);
+                }
+            }
+            BrowserEvent::KeyDown { scancode, .. } => match scancode {
+                SCANCODE_RETURN => return Some(self.accepted()),
+                SCANCODE_ESCAPE => return Some(None),
+                SCANCODE_BACKSPACE if prompt => self.input.delete_char_before(),
+                SCANCODE_LEFT if prompt => self.input.move_cursor_left(),
+                SCANCODE_RIGHT if prompt => self.input.move_cursor_right(),
+                SCANCODE_HOME if prompt => self.input.move_cursor_to_start(),
+                SCANCODE_END if prompt => self.input.move_cursor_to_end(),
+                _ => {}
+            },
+            BrowserEvent::TextInput { ref text } if prompt => self.input.insert_text(text),
+            // The window cannot close under a waiting script
+            BrowserEvent::Quit => return Some(None),
+            _ => {}
+        }
+        None
+    }
+
+    /// The answer of accepting the dialog
+    fn accepted(&self) -> Option<String> {
+        match self.kind {
+            DialogKind::Prompt => Some(self.input.value.clone()),
+            DialogKind::Alert | DialogKind::Confirm => Some(String::new()),
+        }
+    }
+
+    fn build_display_list(&self) -> DisplayList {
+        let mut commands = vec![
+            PaintCommand::FillRect { rect: self.rect, color: RenderColor::new(250, 250, 250, 255) },
+            border(self.rect, 1.0, RenderColor::new(150, 150, 150, 255)),
+        ];
+
+        for (i, line) in self.lines.iter().enumerate() {
+            commands.push(text(line, self.rect.x + PADDING, self.rect.y + PADDING + i as f32 * LINE_HEIGHT + 3.0));
+        }
+
+        if self.kind == DialogKind::Prompt {
+            let field = self.field_rect;
+            commands.push(PaintCommand::FillRect { rect: field, color: RenderColor::new(255, 255, 255, 255) });
+            commands.push(border(field, 2.0, RenderColor::new(66, 133, 244, 255)));
+            commands.push(text(&self.input.value, field.x + 8.0, field.y + field.height / 2.0 - 6.0));
+            let cursor_x = field.x + 8.0 + self.input.value[..self.input.cursor_pos].chars().count() as f32 * CHAR_WIDTH;
+            commands.push(PaintCommand::FillRect {
+                rect: Rect::new(cursor_x, field.y + 6.0, 1.0, field.height - 12.0),
+                color: RenderColor::new(0, 0, 0, 255),
+            });
+        }
+
+        button(&mut commands, self.ok_rect, "OK");
+        if let Some(rect) = self.cancel_rect {
+            button(&mut commands, rect, "Cancel");
+        }
+
+        DisplayList { commands }
+    }
+}
+
+fn contains(rect: &Rect, x: f32, y: f32) -> bool {
+    x >= rect.x && x <= rect.x + rect.width && y >= rect.y && y <= rect.y + rect.height
+}
+
+fn text(text: &str, x: f32, y: f32) -> PaintCommand {
+    PaintCommand::DrawText {
+        text: text.to_string(),
+        x,
+        y,
+        color: RenderColor::new(0, 0, 0, 255),
+        font_size: 14.0,
+        face: FontFace::REGULAR,
+        family: None,
+    }
+}
+
+fn border(rect: Rect, width: f32, color: RenderColor) -> PaintCommand {
+    PaintCommand::DrawBorder {
+        rect,
+        widths: BorderWidths { top: width, right: width, bottom: width, left: width },
+        styles: BorderStyles::default(),
+        colors: BorderColors::all(color),
+    }
+}
+
+fn button(commands: &mut Vec<PaintCommand>, rect: Rect, label: &str) {
+    commands.push(PaintCommand::FillRect { rect, color: RenderColor::new(255, 255, 255, 255) });
+    commands.push(border(rect, 1.0, RenderColor::new(180, 180, 180, 255)));
+    let label_width = label.chars().count() as f32 * CHAR_WIDTH;
+    commands.push(text(label, rect.x + (rect.width - label_width) / 2.0, rect.y + rect.height / 2.0 - 6.0));
+}
+
+/// Shows the dialogs of the pages of a browser
+pub struct Dialogs {
+    backend: SharedBackend,
+    /// The backend has a window the user can answer dialogs in
+    interactive: bool,
+    /// Answers for the next dialogs, given before they open
+    answers: RefCell<VecDeque<Option<String>>>,
+    /// A dialog was painted over the window since the last `take_shown`
+    shown: Cell<bool>,
+}
+
+impl Dialogs {
+    pub fn new(backend: SharedBackend, interactive: bool) -> Self {
+        Self { backend, interactive, answers: RefCell::default(), shown: Cell::new(false) }
+    }
+
+    /// Answer the next dialog without showing it
+    pub fn answer_next(&self, answer: Option<String>) {
+        self.answers.borrow_mut().push_back(answer);
+    }
+
+    /// Check if a dialog was painted over the window since the last call,
+    /// so the frame under it is painted again
+    pub fn take_shown(&self) -> bool {
+        self.shown.replace(false)
+    }
+}
+
+impl DialogHandler for Dialogs {
+    fn show(&self, request: &DialogRequest) -> Option<String> {
+        if let Some(answer) = self.answers.borrow_mut().pop_front() {
+            return answer;
+        }
+        if !self.interactive {
+            log::info!("No window to show a {:?} dialog in, dismissing it", request.kind);
+            return None;
+        }
+
+        let (width, height) = {
+            let backend = self.backend.borrow();
+            (backend.width() as f32, backend.height() as f32)
+        };
+        let mut dialog = ModalDialog::new(request, width, height);
+        if request.kind == DialogKind::Prompt {
+            start_text_input();
+        }
+        self.shown.set(true);
+
+        let mut changed = true;
+        let answer = loop {
+            if std::mem::take(&mut changed) {
+                let mut backend = self.backend.borrow_mut();
+                backend.repaint(&dialog.build_display_list(), dialog.rect, RenderColor::white());
+                backend.present();
+            }
+            let events = poll_events();
+            if let Some(answer) = events.iter().find_map(|event| dialog.handle_event(event)) {
+                break answer;
+            }
+            changed = !events.is_empty();
+            std::thread::sleep(Duration::from_millis(16));
+        };
+
+        if request.kind == DialogKind::Prompt {
+            stop_text_input();
+        }
+        answer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Modifiers;
+
+    fn request(kind: DialogKind, message: &str) -> DialogRequest {
+        DialogRequest { kind, message: message.to_string(), default_value: String::new() }
+    }
+
+    #[test]
+    fn test_wrap_message() {
+        assert_eq!(wrap_message("Delete the file?", 40), vec!["Delete the file?"]);
+        assert_eq!(wrap_message("one two three", 8), vec!["one two", "three"]);
+        assert_eq!(wrap_message("Saved\n\nDone", 40), vec!["Saved", "", "Done"]);
+        assert_eq!(wrap_message("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+
+        let lines = wrap_message(&"line\n".repeat(20), 40);
+        assert_eq!(lines.len(), MAX_LINES);
+        assert_eq!(lines[MAX_LINES - 1], "line…");
+    }
+
+    #[test]
+    fn test_modal_dialog_answers() {
+        let key = |scancode| BrowserEvent::KeyDown { scancode, modifiers: Modifiers::default() };
+
+        // Typing into a prompt, then Enter
+        let mut prompt = ModalDialog::new(
+            &DialogRequest { default_value: "Ad".to_string(), ..request(DialogKind::Prompt, "Name?") },
+            1024.0,
+            768.0,
+        );
+        assert_eq!(prompt.handle_event(&BrowserEvent::TextInput { text: "x".to_string() }), None);
+        assert_eq!(prompt.handle_event(&key(SCANCODE_BACKSPACE)), None);
+        assert_eq!(prompt.handle_event(&BrowserEvent::TextInput { text: "a".to_string() }), None);
+        assert_eq!(prompt.handle_event(&key(SCANCODE_RETURN)), Some(Some("Ada".to_string())));
+
+        // Clicking the buttons of a confirm
+        let mut confirm = ModalDialog::new(&request(DialogKind::Confirm, "Delete?"), 1024.0, 768.0);
+        let click = |rect: Rect| BrowserEvent::MouseDown {
+            x: rect.x + 1.0,
+            y: rect.y + 1.0,
+            button: MouseButton::Left,
+        };
+        assert_eq!(confirm.handle_event(&click(confirm.cancel_rect.unwrap())), Some(None));
+        assert_eq!(confirm.handle_event(&click(confirm.ok_rect)), Some(Some(String::new())));
+        assert_eq!(confirm.handle_event(&key(SCANCODE_ESCAPE)), Some(None));
+
+        // Alerts have only OK, and take no text
+        let mut alert = ModalDialog::new(&request(DialogKind::Alert, "Saved"), 1024.0, 768.0);
+        assert!(alert.cancel_rect.is_none());
+        assert_eq!(alert.handle_event(&BrowserEvent::TextInput { text: "a".to_string() }), None);
+        assert_eq!(alert.input.value, "");
+        assert!(contains(&alert.rect, alert.ok_rect.x, alert.ok_rect.y));
+    }
+}
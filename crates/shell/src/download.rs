@@ -0,0 +1,542 @@
+//! Downloads
+//!
+//! Files the user saves from pages, and responses to navigations that are
+//! not pages, such as a PDF or one sent as an attachment. They are written
+//! to the download directory as their body arrives, under the name the
+//! server gave or else the one their URL ends with. A name already taken
+//! gets a number, as `cat (1).png`, so no file is overwritten.
+//!
+//! The `about:downloads` page lists them with their progress, and a link
+//! cancelling those still in progress.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use gugalanna_net::{HttpClient, NetResult, Response, StreamingResponse};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+use crate::bookmarks::escape_html;
+
+/// URL of the page listing the downloads
+pub const DOWNLOADS_URL: &str = "about:downloads";
+
+/// Name of a file saved from a URL whose path names none
+const DEFAULT_FILE_NAME: &str = "download";
+
+/// `Downloads` in the user's home directory, or XDG_DOWNLOAD_DIR
+pub fn default_download_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_DOWNLOAD_DIR") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => Some(PathBuf::from(std::env::var_os("HOME")?).join("Downloads")),
+    }
+}
+
+/// Whether the response to a navigation is saved rather than shown: it is
+/// sent as an attachment, or its content is not a page
+pub fn is_download(headers: &HashMap<String, String>) -> bool {
+    let attachment = headers
+        .get("content-disposition")
+        .is_some_and(|disposition| disposition.trim_start().to_ascii_lowercase().starts_with("attachment"));
+    let page = headers.get("content-type").is_none_or(|content_type| {
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        mime.is_empty() || mime.starts_with("text/") || mime == "application/xhtml+xml"
+    });
+    attachment || !page
+}
+
+/// Name to save a URL under, the last segment of its path
+fn file_name(url: &Url) -> String {
+    let segment = url.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or_default();
+    safe_file_name(&percent_decode(segment))
+}
+
+/// Name to save a capture of a page under: that of the page's file, or
+/// of its site when its path names none, with the capture's extension
+pub fn capture_file_name(url: &Url, extension: &str) -> String {
+    let name = file_name(url);
+    let stem = match Path::new(&name).file_stem().and_then(|s| s.to_str()) {
+        Some(stem) if name != DEFAULT_FILE_NAME => stem.to_string(),
+        _ => url.host_str().unwrap_or("page").to_string(),
+    };
+    format!("{}.{}", stem, extension)
+}
+
+/// Name to save a response under, the one its `Content-Disposition`
+/// header gives or else the one its URL ends with
+fn response_file_name(url: &Url, headers: &HashMap<String, String>) -> String {
+    headers
+        .get("content-disposition")
+        .and_then(|disposition| {
+            disposition.split(';').find_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                name.trim().eq_ignore_ascii_case("filename").then(|| value.trim().trim_matches('"').to_string())
+            })
+        })
+        .map(|name| safe_file_name(&name))
+        .unwrap_or_else(|| file_name(url))
+}
+
+/// A name that stays in the directory a file is saved to
+fn safe_file_name(name: &str) -> String {
+    let name = name.replace(['/', '\\'], "_");
+    if name.is_empty() || name == "." || name == ".." {
+        DEFAULT_FILE_NAME.to_string()
+    } else {
+        name
+    }
+}
+
+/// A path segment with its percent escapes decoded
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Path in `dir` to save a file named `name` at, not taken by any file yet
+pub fn download_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let stem = Path::new(name).file_stem().and_then(|s| s.to_str()).unwrap_or(name).to_string();
+    let extension = Path::new(name).extension().and_then(|e| e.to_str()).map(|e| format!(".{}", e));
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension.as_deref().unwrap_or_default())))
+        .find(|path| !path.exists())
+        .expect("some number is free")
+}
+
+/// ID of a download, its place among those of the session
+pub type DownloadId = usize;
+
+/// Where a download is at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadState {
+    InProgress,
+    Complete,
+    Failed(String),
+    Cancelled,
+}
+
+/// A file being saved, or saved, this session
+#[derive(Debug)]
+pub struct Download {
+    pub id: DownloadId,
+    pub url: Url,
+    /// File it is written to
+    pub path: PathBuf,
+    /// Bytes written so far
+    pub received: u64,
+    /// Size of the file, when the server sent it
+    pub total: Option<u64>,
+    pub state: DownloadState,
+    cancel: CancellationToken,
+}
+
+/// What happened to a download, sent by the task writing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadEvent {
+    Progress { id: DownloadId, received: u64, total: Option<u64> },
+    Complete { id: DownloadId },
+    Failed { id: DownloadId, error: String },
+    Cancelled { id: DownloadId },
+}
+
+/// The downloads of the session
+///
+/// Each is written in the background, and the frame loop applies what
+/// happened to them with `poll`.
+pub struct DownloadManager {
+    /// Directory files are saved to, None when there is none to save to
+    dir: Option<PathBuf>,
+    downloads: Vec<Download>,
+    sender: UnboundedSender<DownloadEvent>,
+    receiver: UnboundedReceiver<DownloadEvent>,
+}
+
+impl DownloadManager {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Self { dir, downloads: Vec::new(), sender, receiver }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Download> {
+        self.downloads.iter()
+    }
+
+    pub fn get(&self, id: DownloadId) -> Option<&Download> {
+        self.downloads.get(id)
+    }
+
+    /// Create the file of a new download, under a name not taken yet
+    fn begin(&mut self, url: &Url, name: &str) -> Result<(DownloadId, File), String> {
+        let dir = self.dir.as_ref().ok_or("No download directory")?;
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let path = download_path(dir, name);
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let id = self.downloads.len();
+        log::info!("Downloading {} to {}", url, path.display());
+        self.downloads.push(Download {
+            id,
+            url: url.clone(),
+            path,
+            received: 0,
+            total: None,
+            state: DownloadState::InProgress,
+            cancel: CancellationToken::new(),
+        });
+        Ok((id, file))
+    }
+
+    /// Start saving what a URL points to, as when the user saves a link
+    pub fn start(&mut self, client: &HttpClient, url: Url) -> Result<DownloadId, String> {
+        let (id, mut file) = self.begin(&url, &file_name(&url))?;
+        if url.scheme() == "file" {
+            let copied = url
+                .to_file_path()
+                .map_err(|_| format!("Invalid file URL: {}", url))
+                .and_then(|path| File::open(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e)))
+                .and_then(|mut source| std::io::copy(&mut source, &mut file).map_err(|e| e.to_string()));
+            let event = match copied {
+                Ok(size) => {
+                    let _ = self.sender.send(DownloadEvent::Progress { id, received: size, total: Some(size) });
+                    DownloadEvent::Complete { id }
+                }
+                Err(error) => DownloadEvent::Failed { id, error },
+            };
+            let _ = self.sender.send(event);
+            return Ok(id);
+        }
+        let client = client.clone();
+        self.spawn(id, file, async move { client.get_streaming(&url).await });
+        Ok(id)
+    }
+
+    /// Start saving the body of a navigation's response as it arrives
+    pub fn start_response(&mut self, response: StreamingResponse) -> Result<DownloadId, String> {
+        let name = response_file_name(&response.url, &response.headers);
+        let (id, file) = self.begin(&response.url, &name)?;
+        self.spawn(id, file, std::future::ready(Ok(response)));
+        Ok(id)
+    }
+
+    /// Save the body of a navigation's response received whole
+    pub fn save_response(&mut self, response: &Response) -> Result<DownloadId, String> {
+        let name = response_file_name(&response.url, &response.headers);
+        let (id, mut file) = self.begin(&response.url, &name)?;
+        let size = response.body.len() as u64;
+        let event = match file.write_all(&response.body) {
+            Ok(()) => {
+                let _ = self.sender.send(DownloadEvent::Progress { id, received: size, total: Some(size) });
+                DownloadEvent::Complete { id }
+            }
+            Err(e) => DownloadEvent::Failed { id, error: e.to_string() },
+        };
+        let _ = self.sender.send(event);
+        Ok(id)
+    }
+
+    fn spawn(
+        &self,
+        id: DownloadId,
+        file: File,
+        response: impl Future<Output = NetResult<StreamingResponse>> + Send + 'static,
+    ) {
+        let download = &self.downloads[id];
+        tokio::spawn(write_download(
+            id,
+            response,
+            file,
+            download.path.clone(),
+            download.cancel.clone(),
+            self.sender.clone(),
+        ));
+    }
+
+    /// Stop a download in progress, its file is deleted
+    pub fn cancel(&mut self, id: DownloadId) {
+        if let Some(download) = self.get(id).filter(|d| d.state == DownloadState::InProgress) {
+            download.cancel.cancel();
+        }
+    }
+
+    /// Apply what happened to the downloads since the last call, and
+    /// return it
+    pub fn poll(&mut self) -> Vec<DownloadEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.receiver.try_recv() {
+            match &event {
+                DownloadEvent::Progress { id, received, total } => {
+                    let download = &mut self.downloads[*id];
+                    download.received = *received;
+                    download.total = *total;
+                }
+                DownloadEvent::Complete { id } => {
+                    log::info!("Downloaded {}", self.downloads[*id].path.display());
+                    self.downloads[*id].state = DownloadState::Complete;
+                }
+                DownloadEvent::Failed { id, error } => {
+                    log::error!("Downloading {} failed: {}", self.downloads[*id].url, error);
+                    self.downloads[*id].state = DownloadState::Failed(error.clone());
+                }
+                DownloadEvent::Cancelled { id } => self.downloads[*id].state = DownloadState::Cancelled,
+            }
+            events.push(event);
+        }
+        events
+    }
+}
+
+impl Drop for DownloadManager {
+    fn drop(&mut self) {
+        for download in &self.downloads {
+            download.cancel.cancel();
+        }
+    }
+}
+
+/// Write the body of a response to the file of a download as it arrives,
+/// reporting progress, and delete the file unless it was all written
+async fn write_download(
+    id: DownloadId,
+    response: impl Future<Output = NetResult<StreamingResponse>>,
+    mut file: File,
+    path: PathBuf,
+    cancel: CancellationToken,
+    events: UnboundedSender<DownloadEvent>,
+) {
+    let written = async {
+        let mut response = response.await.map_err(|e| e.to_string())?;
+        if !response.is_success() {
+            return Err(format!("HTTP error: {}", response.status));
+        }
+        let total = response.content_length();
+        let mut received = 0;
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+            file.write_all(&chunk).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            received += chunk.len() as u64;
+            let _ = events.send(DownloadEvent::Progress { id, received, total });
+        }
+        Ok(())
+    };
+    let event = tokio::select! {
+        _ = cancel.cancelled() => DownloadEvent::Cancelled { id },
+        written = written => match written {
+            Ok(()) => DownloadEvent::Complete { id },
+            Err(error) => DownloadEvent::Failed { id, error },
+        },
+    };
+    if event != (DownloadEvent::Complete { id }) {
+        let _ = std::fs::remove_file(&path);
+    }
+    let _ = events.send(event);
+}
+
+/// A size in bytes for people to read
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1_000 => format!("{} B", bytes),
+        1_000..1_000_000 => format!("{:.1} kB", bytes as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1} MB", bytes as f64 / 1e6),
+        _ => format!("{:.1} GB", bytes as f64 / 1e9),
+    }
+}
+
+/// The `about:downloads` page, newest downloads first
+///
+/// Those in progress have a link cancelling them, which comes back to the
+/// browser as `about:downloads?cancel=<id>`.
+pub fn downloads_page(downloads: &DownloadManager) -> String {
+    let mut items = String::new();
+    for download in downloads.iter().collect::<Vec<_>>().into_iter().rev() {
+        let name = download.path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let status = match (&download.state, download.total) {
+            (DownloadState::InProgress, Some(total)) => {
+                format!("{} of {}", format_size(download.received), format_size(total))
+            }
+            (DownloadState::InProgress, None) => format_size(download.received),
+            (DownloadState::Complete, _) => format!("Complete, {}", format_size(download.received)),
+            (DownloadState::Failed(error), _) => format!("Failed: {}", escape_html(error)),
+            (DownloadState::Cancelled, _) => "Cancelled".to_string(),
+        };
+        let cancel = if download.state == DownloadState::InProgress {
+            format!(" <a class=\"cancel\" href=\"{}?cancel={}\">Cancel</a>", DOWNLOADS_URL, download.id)
+        } else {
+            String::new()
+        };
+        items.push_str(&format!(
+            "<li><span class=\"name\">{name}</span> <span class=\"status\">{status}</span>{cancel}<br>\
+             <span class=\"url\">{url}</span></li>\n",
+            name = escape_html(&name),
+            status = status,
+            cancel = cancel,
+            url = escape_html(download.url.as_str()),
+        ));
+    }
+    let list = if items.is_empty() {
+        "<p class=\"empty\">No downloads yet.</p>".to_string()
+    } else {
+        format!("<ul>\n{}</ul>", items)
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>Downloads</title>
+    <style>
+        body {{ font-family: sans-serif; padding: 24px; }}
+        li {{ margin-bottom: 12px; }}
+        .name {{ font-weight: bold; }}
+        .status, .url {{ color: #666; font-size: 12px; }}
+        .cancel {{ color: #d93025; font-size: 12px; }}
+        .empty {{ color: #666; }}
+    </style>
+</head>
+<body>
+    <h1>Downloads</h1>
+    {list}
+</body>
+</html>"#,
+        list = list,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_download_paths_do_not_overwrite() {
+        let dir = std::env::temp_dir().join(format!("gugalanna-downloads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |s: &str| download_path(&dir, &file_name(&Url::parse(s).unwrap()));
+
+        let cat = path("https://a.test/img/black%20cat.png?size=2");
+        assert_eq!(cat, dir.join("black cat.png"));
+        std::fs::write(&cat, b"").unwrap();
+        assert_eq!(path("https://b.test/black%20cat.png"), dir.join("black cat (1).png"));
+        assert_eq!(path("https://a.test/"), dir.join("download"));
+        assert_eq!(path("https://a.test/a%2F..%2Fb"), dir.join("a_.._b"));
+
+        // Captures of pages are named after them
+        let capture = |s: &str| capture_file_name(&Url::parse(s).unwrap(), "png");
+        assert_eq!(capture("https://a.test/news/library.html?x=1"), "library.png");
+        assert_eq!(capture("https://a.test/news/"), "a.test.png");
+        assert_eq!(capture("about:blank"), "page.png");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_responses_that_are_not_pages_are_downloaded() {
+        let headers = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+        };
+        assert!(!is_download(&headers(&[("content-type", "text/html; charset=utf-8")])));
+        assert!(!is_download(&headers(&[])));
+        assert!(is_download(&headers(&[("content-type", "application/pdf")])));
+        assert!(is_download(&headers(&[("content-type", "text/csv"), ("content-disposition", "attachment")])));
+
+        let url = Url::parse("https://a.test/export?id=3").unwrap();
+        let sent = headers(&[("content-disposition", "attachment; filename=\"../report.csv\"")]);
+        assert_eq!(response_file_name(&url, &sent), ".._report.csv");
+        assert_eq!(response_file_name(&url, &headers(&[])), "export");
+    }
+
+    #[tokio::test]
+    async fn test_download_written_as_it_arrives() {
+        // A server sending a file in two parts, twice
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/files/report.pdf", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 1024];
+                let n = stream.read(&mut buf).unwrap();
+                assert!(String::from_utf8_lossy(&buf[..n]).starts_with("GET /files/report.pdf"));
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/pdf\r\nContent-Length: 8\r\n\
+                     Connection: close\r\n\r\n%PDF"
+                )
+                .unwrap();
+                stream.flush().unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                write!(stream, "-1.7").unwrap();
+            }
+        });
+
+        let dir = std::env::temp_dir().join(format!("gugalanna-download-manager-{}", std::process::id()));
+        let mut downloads = DownloadManager::new(Some(dir.clone()));
+        let client = HttpClient::new().unwrap();
+
+        // A navigation to it, then the user saving it again
+        let response = client.get_streaming(&url).await.unwrap();
+        assert!(is_download(&response.headers));
+        let first = downloads.start_response(response).unwrap();
+        let mut events = Vec::new();
+        while downloads.get(first).unwrap().state == DownloadState::InProgress {
+            tokio::task::yield_now().await;
+            events.extend(downloads.poll());
+        }
+        let second = downloads.start(&client, url).unwrap();
+        while downloads.get(second).unwrap().state == DownloadState::InProgress {
+            tokio::task::yield_now().await;
+            events.extend(downloads.poll());
+        }
+        server.join().unwrap();
+
+        let progress = |id| DownloadEvent::Progress { id, received: 4, total: Some(8) };
+        let done = |id| DownloadEvent::Progress { id, received: 8, total: Some(8) };
+        assert_eq!(
+            events,
+            [
+                progress(first),
+                done(first),
+                DownloadEvent::Complete { id: first },
+                progress(second),
+                done(second),
+                DownloadEvent::Complete { id: second },
+            ]
+        );
+        for (id, name) in [(first, "report.pdf"), (second, "report (1).pdf")] {
+            let download = downloads.get(id).unwrap();
+            assert_eq!(download.state, DownloadState::Complete);
+            assert_eq!(download.path, dir.join(name));
+            assert_eq!(std::fs::read(&download.path).unwrap(), b"%PDF-1.7");
+        }
+        assert!(downloads_page(&downloads).contains("report (1).pdf</span> <span class=\"status\">Complete, 8 B"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
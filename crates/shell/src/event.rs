@@ -1,6 +1,9 @@
 //! SDL event handling
 //!
-//! Polls SDL events and converts them to browser events.
+//! Polls SDL events and converts them to browser events. Built without
+//! the `sdl` feature there is no window, and no events.
+
+use std::time::{Duration, Instant};
 
 /// Browser event types
 #[derive(Debug, Clone)]
@@ -9,12 +12,16 @@ pub enum BrowserEvent {
     Quit,
     /// Mouse button pressed
     MouseDown { x: f32, y: f32, button: MouseButton },
+    /// Mouse button released
+    MouseUp { button: MouseButton },
     /// Mouse moved
     MouseMove { x: f32, y: f32 },
     /// Mouse wheel scrolled
     MouseWheel { x: i32, y: i32 },
     /// Key pressed
     KeyDown { scancode: u32, modifiers: Modifiers },
+    /// Key released
+    KeyUp { scancode: u32, modifiers: Modifiers },
     /// Text input (for address bar)
     TextInput { text: String },
     /// Window resize
@@ -38,17 +45,65 @@ pub enum MouseButton {
     Left,
     Middle,
     Right,
+    /// Side button going back in history (X1)
+    Back,
+    /// Side button going forward in history (X2)
+    Forward,
     Other(u8),
 }
 
-// SDL event type constants
-const SDL_QUIT: u32 = 0x100;
-const SDL_KEYDOWN: u32 = 0x300;
-const SDL_TEXTINPUT: u32 = 0x303;
-const SDL_MOUSEMOTION: u32 = 0x400;
-const SDL_MOUSEBUTTONDOWN: u32 = 0x401;
-const SDL_MOUSEWHEEL: u32 = 0x403;
-const SDL_WINDOWEVENT: u32 = 0x200;
+/// Convert an SDL mouse button index
+pub fn mouse_button(button: u8) -> MouseButton {
+    match button {
+        1 => MouseButton::Left,
+        2 => MouseButton::Middle,
+        3 => MouseButton::Right,
+        4 => MouseButton::Back,
+        5 => MouseButton::Forward,
+        b => MouseButton::Other(b),
+    }
+}
+
+/// Horizontal wheel ticks in one direction that make a swipe
+const SWIPE_TICKS: i32 = 6;
+
+/// Pause between wheel ticks that ends a swipe
+const SWIPE_GAP: Duration = Duration::from_millis(300);
+
+/// Horizontal wheel ticks, as two fingers swiping across a touchpad make,
+/// added up into going back or forward
+#[derive(Debug, Default)]
+pub struct Swipe {
+    /// Ticks since the swipe began, negative to the left
+    ticks: i32,
+    last_tick: Option<Instant>,
+    /// Whether the swipe went back or forward already
+    done: bool,
+}
+
+impl Swipe {
+    /// Add a horizontal wheel tick at `now`, the side button the swipe
+    /// acts as once it is long enough: back to the left, forward to the
+    /// right
+    ///
+    /// A swipe acts once, until a pause or a turn starts another.
+    pub fn add(&mut self, x: i32, now: Instant) -> Option<MouseButton> {
+        let paused = self.last_tick.is_none_or(|last| now.duration_since(last) > SWIPE_GAP);
+        if paused || x.signum() == -self.ticks.signum() {
+            *self = Self::default();
+        }
+        self.last_tick = Some(now);
+        if self.done {
+            return None;
+        }
+        self.ticks += x;
+        if self.ticks.abs() < SWIPE_TICKS {
+            return None;
+        }
+        self.done = true;
+        Some(if self.ticks < 0 { MouseButton::Back } else { MouseButton::Forward })
+    }
+}
 
 // SDL scancode constants
 pub const SCANCODE_ESCAPE: u32 = 41;
@@ -69,11 +124,22 @@ pub const SCANCODE_LEFT: u32 = 80;
 pub const SCANCODE_RIGHT: u32 = 79;
 
 // Letter keys
+pub const SCANCODE_D: u32 = 7;
+pub const SCANCODE_F: u32 = 9;
+pub const SCANCODE_J: u32 = 13;
 pub const SCANCODE_L: u32 = 15;
+pub const SCANCODE_N: u32 = 17;
+pub const SCANCODE_P: u32 = 19;
 pub const SCANCODE_R: u32 = 21;
+pub const SCANCODE_S: u32 = 22;
 pub const SCANCODE_T: u32 = 23;
 pub const SCANCODE_W: u32 = 26;
 
+// Number and symbol keys
+pub const SCANCODE_0: u32 = 39;
+pub const SCANCODE_MINUS: u32 = 45;
+pub const SCANCODE_EQUALS: u32 = 46;
+
 // Function keys
 pub const SCANCODE_F5: u32 = 62;
 pub const SCANCODE_F12: u32 = 69;
@@ -81,129 +147,324 @@ pub const SCANCODE_F12: u32 = 69;
 // Tab key
 pub const SCANCODE_TAB: u32 = 43;
 
-// SDL keyboard modifier masks
-const KMOD_CTRL: u16 = 0x00C0;
-const KMOD_ALT: u16 = 0x0300;
-const KMOD_SHIFT: u16 = 0x0003;
+// Space bar
+pub const SCANCODE_SPACE: u32 = 44;
+
+/// The `key` and `code` a key has in DOM keyboard events
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyNames {
+    /// What the key means, the character it types for printable keys
+    pub key: String,
+    /// Which physical key it is, regardless of layout and modifiers
+    pub code: String,
+}
+
+impl KeyNames {
+    /// Check if the key types a character
+    pub fn is_printable(&self) -> bool {
+        self.key.chars().count() == 1
+    }
+}
+
+/// Keys other than letters and digits: scancode, code, key, key with shift
+const KEY_TABLE: &[(u32, &str, &str, &str)] = &[
+    (40, "Enter", "Enter", "Enter"),
+    (41, "Escape", "Escape", "Escape"),
+    (42, "Backspace", "Backspace", "Backspace"),
+    (43, "Tab", "Tab", "Tab"),
+    (44, "Space", " ", " "),
+    (45, "Minus", "-", "_"),
+    (46, "Equal", "=", "+"),
+    (47, "BracketLeft", "[", "{"),
+    (48, "BracketRight", "]", "}"),
+    (49, "Backslash", "\\", "|"),
+    (51, "Semicolon", ";", ":"),
+    (52, "Quote", "'", "\""),
+    (53, "Backquote", "`", "~"),
+    (54, "Comma", ",", "<"),
+    (55, "Period", ".", ">"),
+    (56, "Slash", "/", "?"),
+    (57, "CapsLock", "CapsLock", "CapsLock"),
+    (73, "Insert", "Insert", "Insert"),
+    (74, "Home", "Home", "Home"),
+    (75, "PageUp", "PageUp", "PageUp"),
+    (76, "Delete", "Delete", "Delete"),
+    (77, "End", "End", "End"),
+    (78, "PageDown", "PageDown", "PageDown"),
+    (79, "ArrowRight", "ArrowRight", "ArrowRight"),
+    (80, "ArrowLeft", "ArrowLeft", "ArrowLeft"),
+    (81, "ArrowDown", "ArrowDown", "ArrowDown"),
+    (82, "ArrowUp", "ArrowUp", "ArrowUp"),
+    (224, "ControlLeft", "Control", "Control"),
+    (225, "ShiftLeft", "Shift", "Shift"),
+    (226, "AltLeft", "Alt", "Alt"),
+    (227, "MetaLeft", "Meta", "Meta"),
+    (228, "ControlRight", "Control", "Control"),
+    (229, "ShiftRight", "Shift", "Shift"),
+    (230, "AltRight", "Alt", "Alt"),
+    (231, "MetaRight", "Meta", "Meta"),
+];
+
+/// Characters the digit keys type with shift, from 1 to 0
+const SHIFTED_DIGITS: [char; 10] = ['!', '@', '#', '$', '%', '^', '&', '*', '(', ')'];
+
+/// The DOM names of a key by its SDL scancode, on a US layout
+pub fn key_names(scancode: u32, shift: bool) -> Option<KeyNames> {
+    let names = |key: String, code: String| Some(KeyNames { key, code });
+    match scancode {
+        // A to Z
+        4..=29 => {
+            let letter = (b'a' + (scancode - 4) as u8) as char;
+            let key = if shift { letter.to_ascii_uppercase() } else { letter };
+            names(key.to_string(), format!("Key{}", letter.to_ascii_uppercase()))
+        }
+        // 1 to 9, then 0
+        30..=39 => {
+            let index = (scancode - 30) as usize;
+            let digit = char::from_digit((index as u32 + 1) % 10, 10)?;
+            let key = if shift { SHIFTED_DIGITS[index] } else { digit };
+            names(key.to_string(), format!("Digit{}", digit))
+        }
+        // F1 to F12
+        58..=69 => {
+            let name = format!("F{}", scancode - 57);
+            names(name.clone(), name)
+        }
+        _ => {
+            let &(_, code, key, shifted) = KEY_TABLE.iter().find(|entry| entry.0 == scancode)?;
+            names(if shift { shifted } else { key }.to_string(), code.to_string())
+        }
+    }
+}
 
-// SDL window event subtypes
-const SDL_WINDOWEVENT_CLOSE: u8 = 14;
-const SDL_WINDOWEVENT_SIZE_CHANGED: u8 = 6;
+#[cfg(feature = "sdl")]
+pub use sdl_events::{poll_events, set_clipboard_text, start_text_input, stop_text_input};
 
-/// Poll all pending SDL events
-///
-/// # Safety
-/// This function uses raw SDL2 calls.
+/// Without a window there are no events to poll
+#[cfg(not(feature = "sdl"))]
 pub fn poll_events() -> Vec<BrowserEvent> {
-    let mut events = Vec::new();
+    Vec::new()
+}
 
-    unsafe {
-        let mut raw_event: sdl2::sys::SDL_Event = std::mem::zeroed();
+/// Text input needs no enabling without a window
+#[cfg(not(feature = "sdl"))]
+pub fn start_text_input() {}
 
-        while sdl2::sys::SDL_PollEvent(&mut raw_event) != 0 {
-            let event_type = raw_event.type_;
+/// Text input needs no disabling without a window
+#[cfg(not(feature = "sdl"))]
+pub fn stop_text_input() {}
 
-            match event_type {
-                SDL_QUIT => {
-                    events.push(BrowserEvent::Quit);
-                }
+/// Without a window there is no clipboard to copy to
+#[cfg(not(feature = "sdl"))]
+pub fn set_clipboard_text(_text: &str) {}
 
-                SDL_KEYDOWN => {
-                    let key_event = raw_event.key;
-                    let scancode = key_event.keysym.scancode as u32;
-                    let mod_state = key_event.keysym.mod_;
-                    let modifiers = Modifiers {
-                        ctrl: (mod_state & KMOD_CTRL) != 0,
-                        alt: (mod_state & KMOD_ALT) != 0,
-                        shift: (mod_state & KMOD_SHIFT) != 0,
-                    };
-                    events.push(BrowserEvent::KeyDown { scancode, modifiers });
-                }
+#[cfg(feature = "sdl")]
+mod sdl_events {
+    use super::*;
+
+    // SDL event type constants
+    const SDL_QUIT: u32 = 0x100;
+    const SDL_KEYDOWN: u32 = 0x300;
+    const SDL_KEYUP: u32 = 0x301;
+    const SDL_TEXTINPUT: u32 = 0x303;
+    const SDL_MOUSEMOTION: u32 = 0x400;
+    const SDL_MOUSEBUTTONDOWN: u32 = 0x401;
+    const SDL_MOUSEBUTTONUP: u32 = 0x402;
+    const SDL_MOUSEWHEEL: u32 = 0x403;
+    const SDL_WINDOWEVENT: u32 = 0x200;
+
+    // SDL keyboard modifier masks
+    const KMOD_CTRL: u16 = 0x00C0;
+    const KMOD_ALT: u16 = 0x0300;
+    const KMOD_SHIFT: u16 = 0x0003;
+
+    // SDL window event subtypes
+    const SDL_WINDOWEVENT_CLOSE: u8 = 14;
+    const SDL_WINDOWEVENT_SIZE_CHANGED: u8 = 6;
+
+    /// Poll all pending SDL events
+    ///
+    /// # Safety
+    /// This function uses raw SDL2 calls.
+    pub fn poll_events() -> Vec<BrowserEvent> {
+        let mut events = Vec::new();
+
+        unsafe {
+            let mut raw_event: sdl2::sys::SDL_Event = std::mem::zeroed();
 
-                SDL_TEXTINPUT => {
-                    let text_event = raw_event.text;
-                    // Convert C string to Rust string
-                    let c_str = std::ffi::CStr::from_ptr(text_event.text.as_ptr());
-                    if let Ok(text) = c_str.to_str() {
-                        if !text.is_empty() {
-                            events.push(BrowserEvent::TextInput {
-                                text: text.to_string(),
-                            });
+            while sdl2::sys::SDL_PollEvent(&mut raw_event) != 0 {
+                let event_type = raw_event.type_;
+
+                match event_type {
+                    SDL_QUIT => {
+                        events.push(BrowserEvent::Quit);
+                    }
+
+                    SDL_KEYDOWN | SDL_KEYUP => {
+                        let key_event = raw_event.key;
+                        let scancode = key_event.keysym.scancode as u32;
+                        let mod_state = key_event.keysym.mod_;
+                        let modifiers = Modifiers {
+                            ctrl: (mod_state & KMOD_CTRL) != 0,
+                            alt: (mod_state & KMOD_ALT) != 0,
+                            shift: (mod_state & KMOD_SHIFT) != 0,
+                        };
+                        if event_type == SDL_KEYDOWN {
+                            events.push(BrowserEvent::KeyDown { scancode, modifiers });
+                        } else {
+                            events.push(BrowserEvent::KeyUp { scancode, modifiers });
                         }
                     }
-                }
 
-                SDL_MOUSEMOTION => {
-                    let motion_event = raw_event.motion;
-                    events.push(BrowserEvent::MouseMove {
-                        x: motion_event.x as f32,
-                        y: motion_event.y as f32,
-                    });
-                }
+                    SDL_TEXTINPUT => {
+                        let text_event = raw_event.text;
+                        // Convert C string to Rust string
+                        let c_str = std::ffi::CStr::from_ptr(text_event.text.as_ptr());
+                        if let Ok(text) = c_str.to_str() {
+                            if !text.is_empty() {
+                                events.push(BrowserEvent::TextInput {
+                                    text: text.to_string(),
+                                });
+                            }
+                        }
+                    }
 
-                SDL_MOUSEBUTTONDOWN => {
-                    let button_event = raw_event.button;
-                    let button = match button_event.button {
-                        1 => MouseButton::Left,
-                        2 => MouseButton::Middle,
-                        3 => MouseButton::Right,
-                        b => MouseButton::Other(b),
-                    };
-                    events.push(BrowserEvent::MouseDown {
-                        x: button_event.x as f32,
-                        y: button_event.y as f32,
-                        button,
-                    });
-                }
+                    SDL_MOUSEMOTION => {
+                        let motion_event = raw_event.motion;
+                        events.push(BrowserEvent::MouseMove {
+                            x: motion_event.x as f32,
+                            y: motion_event.y as f32,
+                        });
+                    }
 
-                SDL_MOUSEWHEEL => {
-                    let wheel_event = raw_event.wheel;
-                    events.push(BrowserEvent::MouseWheel {
-                        x: wheel_event.x,
-                        y: wheel_event.y,
-                    });
-                }
+                    SDL_MOUSEBUTTONDOWN => {
+                        let button_event = raw_event.button;
+                        events.push(BrowserEvent::MouseDown {
+                            x: button_event.x as f32,
+                            y: button_event.y as f32,
+                            button: mouse_button(button_event.button),
+                        });
+                    }
 
-                SDL_WINDOWEVENT => {
-                    let window_event = raw_event.window;
-                    match window_event.event {
-                        SDL_WINDOWEVENT_CLOSE => {
-                            events.push(BrowserEvent::Quit);
-                        }
-                        SDL_WINDOWEVENT_SIZE_CHANGED => {
-                            events.push(BrowserEvent::WindowResize {
-                                width: window_event.data1 as u32,
-                                height: window_event.data2 as u32,
-                            });
+                    SDL_MOUSEBUTTONUP => {
+                        let button_event = raw_event.button;
+                        events.push(BrowserEvent::MouseUp { button: mouse_button(button_event.button) });
+                    }
+
+                    SDL_MOUSEWHEEL => {
+                        let wheel_event = raw_event.wheel;
+                        events.push(BrowserEvent::MouseWheel {
+                            x: wheel_event.x,
+                            y: wheel_event.y,
+                        });
+                    }
+
+                    SDL_WINDOWEVENT => {
+                        let window_event = raw_event.window;
+                        match window_event.event {
+                            SDL_WINDOWEVENT_CLOSE => {
+                                events.push(BrowserEvent::Quit);
+                            }
+                            SDL_WINDOWEVENT_SIZE_CHANGED => {
+                                events.push(BrowserEvent::WindowResize {
+                                    width: window_event.data1 as u32,
+                                    height: window_event.data2 as u32,
+                                });
+                            }
+                            _ => {}
                         }
-                        _ => {}
                     }
-                }
 
-                _ => {
-                    // Ignore unknown events
+                    _ => {
+                        // Ignore unknown events
+                    }
                 }
             }
         }
+
+        events
     }
 
-    events
-}
+    /// Enable SDL text input mode
+    ///
+    /// Must be called when the address bar gains focus.
+    pub fn start_text_input() {
+        unsafe {
+            sdl2::sys::SDL_StartTextInput();
+        }
+    }
 
-/// Enable SDL text input mode
-///
-/// Must be called when the address bar gains focus.
-pub fn start_text_input() {
-    unsafe {
-        sdl2::sys::SDL_StartTextInput();
+    /// Disable SDL text input mode
+    ///
+    /// Must be called when the address bar loses focus.
+    pub fn stop_text_input() {
+        unsafe {
+            sdl2::sys::SDL_StopTextInput();
+        }
+    }
+
+    /// Put text on the system clipboard; text holding a NUL byte is not
+    /// copied
+    pub fn set_clipboard_text(text: &str) {
+        let Ok(text) = std::ffi::CString::new(text) else {
+            return;
+        };
+        unsafe {
+            sdl2::sys::SDL_SetClipboardText(text.as_ptr());
+        }
     }
 }
 
-/// Disable SDL text input mode
-///
-/// Must be called when the address bar loses focus.
-pub fn stop_text_input() {
-    unsafe {
-        sdl2::sys::SDL_StopTextInput();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names() {
+        let names = |scancode, shift| key_names(scancode, shift).map(|k| (k.key, k.code));
+        assert_eq!(names(4, false), Some(("a".to_string(), "KeyA".to_string())));
+        assert_eq!(names(29, true), Some(("Z".to_string(), "KeyZ".to_string())));
+        assert_eq!(names(39, false), Some(("0".to_string(), "Digit0".to_string())));
+        assert_eq!(names(30, true), Some(("!".to_string(), "Digit1".to_string())));
+        assert_eq!(names(SCANCODE_DOWN, false), Some(("ArrowDown".to_string(), "ArrowDown".to_string())));
+        assert_eq!(names(SCANCODE_F12, false), Some(("F12".to_string(), "F12".to_string())));
+        assert!(key_names(44, false).unwrap().is_printable());
+        assert!(!key_names(SCANCODE_RETURN, false).unwrap().is_printable());
+        assert_eq!(key_names(0, false), None);
+    }
+
+    #[test]
+    fn test_mouse_buttons() {
+        assert_eq!(mouse_button(1), MouseButton::Left);
+        assert_eq!(mouse_button(2), MouseButton::Middle);
+        assert_eq!(mouse_button(3), MouseButton::Right);
+        assert_eq!(mouse_button(4), MouseButton::Back);
+        assert_eq!(mouse_button(5), MouseButton::Forward);
+        assert_eq!(mouse_button(6), MouseButton::Other(6));
+    }
+
+    #[test]
+    fn test_swipe_past_threshold() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut swipe = Swipe::default();
+
+        // Going back once the ticks add up, then not again in the swipe
+        for tick in 0..5 {
+            assert_eq!(swipe.add(-1, at(tick * 20)), None);
+        }
+        assert_eq!(swipe.add(-1, at(100)), Some(MouseButton::Back));
+        assert_eq!(swipe.add(-3, at(120)), None);
+
+        // A pause starts another swipe, as does a turn
+        assert_eq!(swipe.add(4, at(500)), None);
+        assert_eq!(swipe.add(-2, at(520)), None);
+        assert_eq!(swipe.add(3, at(540)), None);
+        assert_eq!(swipe.add(3, at(560)), Some(MouseButton::Forward));
+
+        // Scattered ticks never make a swipe
+        for tick in 0..10 {
+            assert_eq!(swipe.add(1, at(1000 + tick * 400)), None);
+        }
     }
 }
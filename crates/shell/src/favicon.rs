@@ -0,0 +1,167 @@
+//! Favicons
+//!
+//! The icon of a page, shown left of its tab's title and in the address
+//! bar. It is the first `<link rel="icon">` of the page, else its first
+//! `<link rel="shortcut icon">`, else `/favicon.ico` of its site. Icons are
+//! fetched in the background once the page has loaded, scaled to
+//! `FAVICON_SIZE` and kept by origin, so other pages of the site show
+//! theirs at once.
+
+use std::collections::HashMap;
+
+use gugalanna_dom::{DomTree, Queryable};
+use gugalanna_layout::ImagePixels;
+use gugalanna_net::HttpClient;
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use url::Url;
+
+use crate::image_loader::decode_image;
+use crate::script_loader::base_url;
+
+/// Width and height favicons are shown at
+pub const FAVICON_SIZE: u32 = 16;
+
+/// URL of a page's icon, None for pages that have none, as `about:` pages
+pub fn favicon_url(dom: &DomTree, page_url: &Url) -> Option<Url> {
+    let base = base_url(dom, page_url);
+    let links: Vec<(Vec<String>, &str)> = dom
+        .get_elements_by_tag_name("link")
+        .into_iter()
+        .filter_map(|id| {
+            let element = dom.get(id)?.as_element()?;
+            let rel = element.get_attribute("rel")?.split_ascii_whitespace().map(str::to_ascii_lowercase).collect();
+            let href = element.get_attribute("href").filter(|href| !href.trim().is_empty())?;
+            Some((rel, href))
+        })
+        .collect();
+
+    // `icon` on its own wins over the older `shortcut icon`
+    let declared = links
+        .iter()
+        .find(|(rel, _)| rel.len() == 1 && rel[0] == "icon")
+        .or_else(|| links.iter().find(|(rel, _)| rel.iter().any(|token| token == "icon")))
+        .and_then(|(_, href)| base.join(href.trim()).ok());
+    if declared.is_some() {
+        return declared;
+    }
+    matches!(page_url.scheme(), "http" | "https").then(|| page_url.join("/favicon.ico").ok()).flatten()
+}
+
+/// Key a page's icon is cached under, None for pages without a site, as
+/// `file:` and `about:` pages
+pub fn cache_key(page_url: &Url) -> Option<String> {
+    let origin = page_url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+/// Icons of the sites visited, by origin
+#[derive(Debug, Default)]
+pub struct FaviconCache {
+    icons: HashMap<String, ImagePixels>,
+}
+
+impl FaviconCache {
+    /// Icon of a page's site, if it was fetched before
+    pub fn get(&self, page_url: &Url) -> Option<ImagePixels> {
+        self.icons.get(&cache_key(page_url)?).cloned()
+    }
+
+    pub fn insert(&mut self, page_url: &Url, icon: ImagePixels) {
+        if let Some(key) = cache_key(page_url) {
+            self.icons.insert(key, icon);
+        }
+    }
+}
+
+/// Fetch an icon with the client of the page's tab and scale it to
+/// `FAVICON_SIZE`
+pub async fn fetch_favicon(client: &HttpClient, url: Url) -> Result<ImagePixels, String> {
+    let bytes = if url.scheme() == "file" {
+        let path = url.to_file_path().map_err(|_| "Invalid file path".to_string())?;
+        std::fs::read(path).map_err(|e| e.to_string())?
+    } else {
+        let response = client.get(&url).await.map_err(|e| e.to_string())?;
+        if !response.is_success() {
+            return Err(format!("HTTP error: {}", response.status));
+        }
+        response.body
+    };
+    scale_favicon(&bytes)
+}
+
+/// Decode an icon and scale it to `FAVICON_SIZE`
+fn scale_favicon(bytes: &[u8]) -> Result<ImagePixels, String> {
+    let decoded = decode_image(bytes).map_err(|e| e.to_string())?;
+    let image = RgbaImage::from_raw(decoded.width, decoded.height, decoded.data)
+        .ok_or_else(|| "Truncated image data".to_string())?;
+    let scaled = imageops::resize(&image, FAVICON_SIZE, FAVICON_SIZE, FilterType::Triangle);
+    Ok(ImagePixels { width: FAVICON_SIZE, height: FAVICON_SIZE, data: scaled.into_raw().into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_html::HtmlParser;
+
+    fn icon_of(html: &str, page: &str) -> Option<String> {
+        let dom = HtmlParser::new().parse(html).unwrap();
+        favicon_url(&dom, &Url::parse(page).unwrap()).map(|url| url.to_string())
+    }
+
+    #[test]
+    fn test_favicon_url_precedence() {
+        let page = "https://a.test/docs/page.html";
+        let both = r#"<link rel="shortcut icon" href="/old.ico"><link rel="stylesheet" href="s.css">
+            <link rel="Icon" href="new.png">"#;
+        assert_eq!(icon_of(both, page).as_deref(), Some("https://a.test/docs/new.png"));
+        let shortcut = r#"<link rel="apple-touch-icon" href="/touch.png"><link rel="shortcut icon" href="/old.ico">"#;
+        assert_eq!(icon_of(shortcut, page).as_deref(), Some("https://a.test/old.ico"));
+        let based = r#"<base href="https://cdn.test/assets/"><link rel="icon" href="i.png">"#;
+        assert_eq!(icon_of(based, page).as_deref(), Some("https://cdn.test/assets/i.png"));
+
+        // Without a usable link the site's /favicon.ico is tried, for
+        // pages that have a site
+        let empty = r#"<link rel="icon" href=""><p>Hi</p>"#;
+        assert_eq!(icon_of(empty, page).as_deref(), Some("https://a.test/favicon.ico"));
+        assert_eq!(icon_of("<p>Hi</p>", "about:blank"), None);
+        assert_eq!(icon_of("<p>Hi</p>", "file:///tmp/page.html"), None);
+        assert_eq!(
+            icon_of(r#"<link rel="icon" href="i.png">"#, "file:///tmp/page.html").as_deref(),
+            Some("file:///tmp/i.png")
+        );
+    }
+
+    #[test]
+    fn test_cache_keyed_by_origin() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        let icon = ImagePixels { width: 1, height: 1, data: vec![255, 0, 0, 255].into() };
+        let mut cache = FaviconCache::default();
+        cache.insert(&url("https://a.test/one"), icon.clone());
+
+        assert_eq!(cache.get(&url("https://a.test/two?q=1")), Some(icon.clone()));
+        assert_eq!(cache.get(&url("http://a.test/one")), None);
+        assert_eq!(cache.get(&url("https://a.test:8443/one")), None);
+        assert_eq!(cache.get(&url("https://b.a.test/one")), None);
+
+        // Pages without a site share nothing
+        cache.insert(&url("file:///tmp/a.html"), icon);
+        assert_eq!(cache.get(&url("file:///tmp/a.html")), None);
+        assert_eq!(cache_key(&url("about:blank")), None);
+    }
+
+    #[test]
+    fn test_icons_are_scaled() {
+        let mut png = Vec::new();
+        let image = RgbaImage::from_pixel(64, 32, image::Rgba([0, 128, 255, 255]));
+        image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        let icon = scale_favicon(&png).unwrap();
+        assert_eq!((icon.width, icon.height, icon.data.len()), (16, 16, 16 * 16 * 4));
+        assert_eq!(&icon.data[..4], &[0, 128, 255, 255]);
+
+        let mut ico = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut ico), image::ImageFormat::Ico).unwrap();
+        assert_eq!(scale_favicon(&ico).unwrap(), icon);
+        assert!(scale_favicon(b"not an image").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Script Fetches
+//!
+//! Sends the requests a page's scripts make with fetch() in the background.
+//! Responses are handed back to the page's runtime from the frame loop,
+//! since it lives on the main thread.
+
+use gugalanna_js::{FetchRequest, FetchResponse};
+use gugalanna_net::HttpClient;
+use log::debug;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// The outcome of a request, under the ID the script knows it by
+pub type FetchResult = (u32, Result<FetchResponse, String>);
+
+/// Requests of a page in flight
+///
+/// Dropping it, as when the page is navigated away from or its tab closed,
+/// cancels them.
+pub struct PageFetches {
+    sender: UnboundedSender<FetchResult>,
+    receiver: UnboundedReceiver<FetchResult>,
+    cancel: CancellationToken,
+}
+
+impl PageFetches {
+    pub fn new() -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Self { sender, receiver, cancel: CancellationToken::new() }
+    }
+
+    /// Start sending a request made by a page at `page_url`
+    pub fn start(&self, client: &HttpClient, page_url: &Url, request: FetchRequest) {
+        let url = match page_url.join(&request.url) {
+            Ok(url) => url,
+            Err(e) => {
+                let _ = self.sender.send((request.id, Err(format!("Invalid URL '{}': {}", request.url, e))));
+                return;
+            }
+        };
+        tokio::spawn(send_fetch(
+            client.clone(),
+            url,
+            page_url.clone(),
+            request,
+            self.cancel.clone(),
+            self.sender.clone(),
+        ));
+    }
+
+    /// Results of the requests that finished since the last call
+    pub fn finished(&mut self) -> Vec<FetchResult> {
+        let mut results = Vec::new();
+        while let Ok(result) = self.receiver.try_recv() {
+            results.push(result);
+        }
+        results
+    }
+}
+
+impl Drop for PageFetches {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// Send a request to `url`, naming the page that made it as the referrer,
+/// and report how it went unless cancelled first
+pub async fn send_fetch(
+    client: HttpClient,
+    url: Url,
+    referrer: Url,
+    request: FetchRequest,
+    cancel: CancellationToken,
+    results: UnboundedSender<FetchResult>,
+) {
+    let mut headers = request.headers;
+    headers.insert("referer".to_string(), referrer.to_string());
+
+    let response = tokio::select! {
+        _ = cancel.cancelled() => return,
+        response = client.request(&request.method, &url, headers, request.body) => response,
+    };
+    debug!("Fetch {} {} finished", request.method, url);
+
+    let result = response
+        .map(|response| FetchResponse {
+            url: response.url.to_string(),
+            status: response.status,
+            body: response.text_lossy(),
+            headers: response.headers,
+        })
+        .map_err(|e| e.to_string());
+    // The page may be gone
+    let _ = results.send((request.id, result));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_html::HtmlParser;
+    use gugalanna_js::JsRuntime;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_fetched_json_changes_dom() {
+        // A server answering one request with JSON
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let page_url = Url::parse(&format!("http://{}/index.html", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).unwrap();
+            assert!(String::from_utf8_lossy(&buf[..n]).starts_with("GET /api/data HTTP/1.1"));
+            let body = r#"{"greeting": "hello"}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        let dom = HtmlParser::new().parse(r#"<p id="out"></p>"#).unwrap();
+        let runtime = JsRuntime::with_dom(dom).unwrap();
+        runtime
+            .exec(
+                r#"
+                fetch('/api/data')
+                    .then(function(r) { return r.json(); })
+                    .then(function(data) {
+                        document.getElementById('out').setAttribute('data-greeting', data.greeting);
+                    });
+                "#,
+            )
+            .unwrap();
+
+        // Requests go out and come back as the frame loop would pass them on
+        let mut fetches = PageFetches::new();
+        let client = HttpClient::new().unwrap();
+        for request in runtime.take_fetch_requests() {
+            fetches.start(&client, &page_url, request);
+        }
+        let (id, result) = fetches.receiver.recv().await.unwrap();
+        server.join().unwrap();
+        runtime.complete_fetch(id, result).unwrap();
+
+        let greeting = runtime.eval("document.getElementById('out').getAttribute('data-greeting')").unwrap();
+        assert_eq!(greeting.as_str(), Some("hello"));
+    }
+}
@@ -0,0 +1,117 @@
+//! File Chooser
+//!
+//! The dialog a file input opens to choose the files it submits, the
+//! desktop's own through zenity, or kdialog where zenity is missing. The
+//! dialog runs on a thread of its own so the window keeps drawing while it
+//! is open; what was chosen is picked up by `FilePicker::poll`.
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+use gugalanna_dom::NodeId;
+
+/// Files chosen in a dialog, None when it was canceled
+pub type Choice = Option<Vec<PathBuf>>;
+
+/// The file chooser open for a file input, if any
+#[derive(Default)]
+pub struct FilePicker {
+    open: Option<(NodeId, Receiver<Choice>)>,
+}
+
+impl FilePicker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a dialog is open
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    /// Open the desktop's file chooser for a file input, several files
+    /// chosen at once with `multiple`
+    pub fn open(&mut self, node_id: NodeId, multiple: bool) {
+        self.open_with(node_id, move || choose_files(multiple));
+    }
+
+    /// Choose the files of a file input with `choose`, run on a thread of
+    /// its own; nothing happens while a dialog is already open
+    pub fn open_with(&mut self, node_id: NodeId, choose: impl FnOnce() -> Choice + Send + 'static) {
+        if self.is_open() {
+            return;
+        }
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(choose());
+        });
+        self.open = Some((node_id, rx));
+    }
+
+    /// The file input and what was chosen for it, once the dialog closed
+    pub fn poll(&mut self) -> Option<(NodeId, Choice)> {
+        let (node_id, rx) = self.open.as_ref()?;
+        let choice = match rx.try_recv() {
+            Ok(choice) => choice,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => None,
+        };
+        let node_id = *node_id;
+        self.open = None;
+        Some((node_id, choice))
+    }
+}
+
+/// Ask for files with the first dialog program found, None when it was
+/// canceled or there is none
+fn choose_files(multiple: bool) -> Choice {
+    let mut zenity = Command::new("zenity");
+    zenity.args(["--file-selection", "--separator=\n"]);
+    let mut kdialog = Command::new("kdialog");
+    kdialog.args(["--getopenfilename", ".", "--separate-output"]);
+    if multiple {
+        zenity.arg("--multiple");
+        kdialog.arg("--multiple");
+    }
+    for mut command in [zenity, kdialog] {
+        match command.output() {
+            // Canceling the dialog exits with an error
+            Ok(output) if output.status.success() => {
+                return Some(chosen_files(&String::from_utf8_lossy(&output.stdout)));
+            }
+            Ok(_) => return None,
+            Err(e) => log::debug!("No file chooser {:?}: {}", command.get_program(), e),
+        }
+    }
+    log::warn!("No file chooser found, install zenity or kdialog to choose files");
+    None
+}
+
+/// The files a dialog printed, one per line
+fn chosen_files(output: &str) -> Vec<PathBuf> {
+    output.lines().filter(|line| !line.is_empty()).map(PathBuf::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picker_reports_choice_once() {
+        let mut picker = FilePicker::new();
+        assert!(picker.poll().is_none());
+        picker.open_with(NodeId::new(4), || Some(chosen_files("/tmp/a.txt\n/tmp/b.png\n")));
+        assert!(picker.is_open());
+
+        let (node_id, choice) = loop {
+            if let Some(closed) = picker.poll() {
+                break closed;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(node_id, NodeId::new(4));
+        assert_eq!(choice, Some(vec![PathBuf::from("/tmp/a.txt"), PathBuf::from("/tmp/b.png")]));
+        assert!(!picker.is_open() && picker.poll().is_none());
+    }
+}
@@ -0,0 +1,375 @@
+//! Find in Page
+//!
+//! Searches the text of a page for what the user typed in the find bar,
+//! ignoring case. Adjacent text nodes are searched together, so a match
+//! may start in one and end in the next, and a run of whitespace matches a
+//! single space, as the page shows it. Matches are highlighted over the
+//! runs of text layout broke their text nodes into.
+
+use std::collections::HashMap;
+
+use gugalanna_dom::{DomTree, NodeId};
+use gugalanna_layout::{measure_text, BoxType, LayoutBox, Rect};
+use gugalanna_render::{boxes_in_paint_order, RenderColor, Selection};
+use gugalanna_style::{ComputedStyle, WhiteSpace};
+
+/// Elements whose text is not shown
+const HIDDEN_ELEMENTS: &[&str] = &["head", "title", "script", "style", "template", "noscript"];
+
+/// The part of a match in one text node, a byte range of its text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchPart {
+    pub node: NodeId,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Where the query was found, in one text node or several adjacent ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FindMatch {
+    pub parts: Vec<MatchPart>,
+}
+
+/// A character of the searched text, with the bytes of the text node it
+/// stands for; a run of whitespace is a single space
+struct SearchChar {
+    c: char,
+    node: NodeId,
+    start: usize,
+    end: usize,
+}
+
+fn fold(c: char) -> char {
+    if c.is_whitespace() {
+        ' '
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+/// The characters of a query as they are compared
+fn fold_query(query: &str) -> Vec<char> {
+    let mut folded: Vec<char> = Vec::new();
+    for c in query.chars().map(fold) {
+        if !(c == ' ' && folded.last() == Some(&' ')) {
+            folded.push(c);
+        }
+    }
+    folded
+}
+
+fn is_hidden(dom: &DomTree, id: NodeId) -> bool {
+    let mut current = dom.parent(id);
+    while let Some(parent) = current {
+        if dom.get(parent).and_then(|n| n.tag_name()).is_some_and(|tag| HIDDEN_ELEMENTS.contains(&tag)) {
+            return true;
+        }
+        current = dom.parent(parent);
+    }
+    false
+}
+
+/// Find the query in the text of a document, in document order
+pub fn find_matches(dom: &DomTree, query: &str) -> Vec<FindMatch> {
+    let query = fold_query(query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    // Text nodes next to each other are searched as one text
+    let mut runs: Vec<Vec<SearchChar>> = Vec::new();
+    let mut last_text: Option<NodeId> = None;
+    for id in dom.descendants(dom.document_id()) {
+        let Some(node) = dom.get(id) else {
+            continue;
+        };
+        let Some(text) = node.as_text() else {
+            continue;
+        };
+        if is_hidden(dom, id) {
+            continue;
+        }
+        if last_text.is_none() || node.prev_sibling != last_text {
+            runs.push(Vec::new());
+        }
+        last_text = Some(id);
+
+        let run = runs.last_mut().expect("a run was started");
+        for (start, c) in text.char_indices() {
+            let end = start + c.len_utf8();
+            let c = fold(c);
+            match run.last_mut() {
+                Some(last) if c == ' ' && last.c == ' ' && last.node == id => last.end = end,
+                Some(last) if c == ' ' && last.c == ' ' => {}
+                _ => run.push(SearchChar { c, node: id, start, end }),
+            }
+        }
+    }
+
+    let mut matches = Vec::new();
+    for run in &runs {
+        let mut i = 0;
+        while i + query.len() <= run.len() {
+            if run[i..i + query.len()].iter().zip(&query).all(|(sc, c)| sc.c == *c) {
+                matches.push(match_parts(&run[i..i + query.len()]));
+                i += query.len();
+            } else {
+                i += 1;
+            }
+        }
+    }
+    matches
+}
+
+fn match_parts(chars: &[SearchChar]) -> FindMatch {
+    let mut parts: Vec<MatchPart> = Vec::new();
+    for sc in chars {
+        match parts.last_mut() {
+            Some(part) if part.node == sc.node => part.end = sc.end,
+            _ => parts.push(MatchPart { node: sc.node, start: sc.start, end: sc.end }),
+        }
+    }
+    FindMatch { parts }
+}
+
+/// Byte offset in the text a text node is laid out with of a byte offset
+/// in its DOM text, whitespace collapsing unless it is preserved
+fn laid_out_offset(text: &str, offset: usize, style: &ComputedStyle) -> usize {
+    let prefix = &text[..offset];
+    match style.white_space {
+        WhiteSpace::Pre | WhiteSpace::PreWrap => prefix.replace("\r\n", "\n").len(),
+        _ => {
+            let mut len = 0;
+            let mut after_space = false;
+            for c in prefix.chars() {
+                if c.is_whitespace() && !(style.white_space == WhiteSpace::PreLine && c == '\n') {
+                    if !after_space {
+                        len += 1;
+                    }
+                    after_space = true;
+                } else {
+                    len += c.len_utf8();
+                    after_space = false;
+                }
+            }
+            len
+        }
+    }
+}
+
+/// Highlight rectangles of the part of a match in a text box, in document
+/// coordinates
+fn part_rects(
+    layout_box: &LayoutBox,
+    text: &str,
+    range: (usize, usize),
+    style: &ComputedStyle,
+    origin: (f32, f32),
+) -> Vec<Rect> {
+    let width = |s: &str| measure_text(s, style).width;
+    let (abs_x, abs_y) = origin;
+
+    if layout_box.fragments.is_empty() {
+        let (start, end) = (range.0.min(text.len()), range.1.min(text.len()));
+        if start >= end || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            return Vec::new();
+        }
+        let x = abs_x + width(&text[..start]);
+        return vec![Rect::new(x, abs_y, width(&text[start..end]), layout_box.dimensions.content.height)];
+    }
+
+    // Each line's run of text is a piece of the laid out text, without the
+    // spaces the lines broke at
+    let mut rects = Vec::new();
+    let mut cursor = 0;
+    for fragment in &layout_box.fragments {
+        let run = fragment.text.as_deref().unwrap_or_default();
+        let Some(found) = text.get(cursor..).and_then(|rest| rest.find(run)) else {
+            continue;
+        };
+        let run_start = cursor + found;
+        cursor = run_start + run.len();
+
+        let (start, end) = (range.0.max(run_start), range.1.min(cursor));
+        if start >= end || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            continue;
+        }
+        let x = abs_x + fragment.rect.x + width(&text[run_start..start]);
+        let y = abs_y + fragment.rect.y;
+        rects.push(Rect::new(x, y, width(&text[start..end]), fragment.rect.height));
+    }
+    rects
+}
+
+/// Highlight rectangles of each match, in document coordinates
+pub fn match_rects(dom: &DomTree, layout: &LayoutBox, matches: &[FindMatch]) -> Vec<Vec<Rect>> {
+    let mut parts_by_node: HashMap<NodeId, Vec<(usize, MatchPart)>> = HashMap::new();
+    for (i, found) in matches.iter().enumerate() {
+        for part in &found.parts {
+            parts_by_node.entry(part.node).or_default().push((i, *part));
+        }
+    }
+
+    let mut rects = vec![Vec::new(); matches.len()];
+    for painted in boxes_in_paint_order(layout) {
+        let BoxType::Text(node, text, style) = &painted.layout_box.box_type else {
+            continue;
+        };
+        let (Some(parts), Some(dom_text)) = (parts_by_node.get(node), dom.get(*node).and_then(|n| n.as_text())) else {
+            continue;
+        };
+        let d = &painted.layout_box.dimensions;
+        let origin = (painted.offset_x + d.content.x, painted.offset_y + d.content.y);
+        for (i, part) in parts {
+            let range = (laid_out_offset(dom_text, part.start, style), laid_out_offset(dom_text, part.end, style));
+            rects[*i].extend(part_rects(painted.layout_box, text, range, style, origin));
+        }
+    }
+    rects
+}
+
+/// Scroll offset that brings a match into view: the current one when the
+/// match is visible, else one centering it in the viewport
+pub fn scroll_target(rects: &[Rect], scroll_y: f32, viewport_height: f32) -> Option<f32> {
+    let area = rects.iter().skip(1).fold(*rects.first()?, |area, rect| area.union(rect));
+    if area.y >= scroll_y && area.bottom() <= scroll_y + viewport_height {
+        return Some(scroll_y);
+    }
+    Some((area.y + area.height / 2.0 - viewport_height / 2.0).max(0.0))
+}
+
+/// The search of a page, with the match the find bar is at
+#[derive(Debug, Default)]
+pub struct PageFind {
+    query: String,
+    matches: Vec<FindMatch>,
+    /// Highlight rectangles of each match, from the last layout
+    rects: Vec<Vec<Rect>>,
+    current: Option<usize>,
+}
+
+impl PageFind {
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn count(&self) -> usize {
+        self.matches.len()
+    }
+
+    /// Index of the match the find bar is at
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Search for a new query, starting at its first match
+    pub fn search(&mut self, dom: &DomTree, query: &str) {
+        self.query = query.to_string();
+        self.matches = find_matches(dom, query);
+        self.rects.clear();
+        self.current = if self.matches.is_empty() { None } else { Some(0) };
+    }
+
+    /// Search again in a new layout of the page, whose text scripts may
+    /// have changed, and place the highlights
+    pub fn update_layout(&mut self, dom: &DomTree, layout: &LayoutBox) {
+        if self.query.is_empty() {
+            return;
+        }
+        self.matches = find_matches(dom, &self.query);
+        self.current = match self.matches.len() {
+            0 => None,
+            count => Some(self.current.unwrap_or(0).min(count - 1)),
+        };
+        self.rects = match_rects(dom, layout, &self.matches);
+    }
+
+    /// Move to the next match, or the previous one, wrapping around
+    pub fn step(&mut self, backwards: bool) {
+        let count = self.matches.len();
+        self.current = self.current.map(|current| {
+            if backwards {
+                (current + count - 1) % count
+            } else {
+                (current + 1) % count
+            }
+        });
+    }
+
+    /// Text of the match the find bar is at
+    pub fn current_text(&self, dom: &DomTree) -> Option<String> {
+        let found = self.matches.get(self.current?)?;
+        let parts = found.parts.iter().filter_map(|part| dom.get(part.node)?.as_text()?.get(part.start..part.end));
+        Some(parts.collect())
+    }
+
+    /// Highlight rectangles of the match the find bar is at
+    pub fn current_rects(&self) -> &[Rect] {
+        self.current.and_then(|i| self.rects.get(i)).map_or(&[], Vec::as_slice)
+    }
+
+    /// Highlights of every match, to paint under the text
+    pub fn selection(&self) -> Selection {
+        Selection { rects: self.rects.concat(), color: RenderColor::new(255, 200, 0, 128) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_dom::Queryable;
+    use gugalanna_html::HtmlParser;
+    use gugalanna_layout::{build_layout_tree, layout_document, ContainingBlock};
+    use gugalanna_style::StyleTree;
+
+    use crate::{page_cascade, DEFAULT_CSS};
+
+    #[test]
+    fn test_matches_found_across_text_nodes() {
+        let html = r#"<html><head><style>.spacer { height: 2000px; }</style></head><body>
+            <p>The QUICK brown fox</p>
+            <script>var quick = 1;</script>
+            <div class="spacer"></div>
+            <p id="last">qui</p>
+        </body></html>"#;
+        let mut dom = HtmlParser::new().parse(html).unwrap();
+        // Text appended by a script sits in a text node of its own
+        let last = dom.get_element_by_id("last").unwrap();
+        let appended = dom.create_text("ck  and    quiet");
+        dom.append_child(last, appended).unwrap();
+
+        let matches = find_matches(&dom, "quick and");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].parts.len(), 2);
+        assert_eq!(matches[0].parts[1], MatchPart { node: appended, start: 0, end: 7 });
+
+        let cascade = page_cascade(&dom, DEFAULT_CSS, None);
+        let style_tree = StyleTree::build(&dom, &cascade, 800.0, 600.0);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(800.0, 600.0));
+
+        let mut find = PageFind::default();
+        find.search(&dom, "Quick");
+        find.update_layout(&dom, &layout);
+        assert_eq!(find.count(), 2);
+        assert!(find.current_rects()[0].y < 100.0);
+        // The first match is in view, the second is centered once stepped to
+        assert_eq!(scroll_target(find.current_rects(), 0.0, 600.0), Some(0.0));
+
+        find.step(false);
+        assert_eq!(find.current(), Some(1));
+        let rects = find.current_rects();
+        assert_eq!(rects.len(), 2);
+        assert_eq!(rects[0].y, rects[1].y);
+        assert!((rects[0].right() - rects[1].x).abs() < 0.01);
+        let target = scroll_target(rects, 0.0, 600.0).unwrap();
+        assert!(rects[0].y > 2000.0);
+        assert!((target - (rects[0].y + rects[0].height / 2.0 - 300.0)).abs() < 0.01);
+
+        find.step(false);
+        assert_eq!(find.current(), Some(0));
+        find.step(true);
+        assert_eq!(find.current(), Some(1));
+    }
+}
@@ -0,0 +1,71 @@
+//! Focus Order
+//!
+//! The order Tab moves focus through the elements of a page: those with a
+//! positive `tabindex` first, lowest first, then the rest in document
+//! order. Links with an `href` and form controls are focusable by nature,
+//! other elements by having a `tabindex`; a negative one leaves an element
+//! out of the order.
+
+use gugalanna_dom::{DomTree, ElementData, NodeId};
+
+/// The elements Tab moves focus through, in order, leaving out those
+/// `usable` says are not shown or are disabled
+pub fn focus_order(dom: &DomTree, usable: impl Fn(NodeId) -> bool) -> Vec<NodeId> {
+    let mut order: Vec<(i32, NodeId)> = dom
+        .descendants(dom.document_id())
+        .into_iter()
+        .filter_map(|id| {
+            let index = tab_index(dom.get(id)?.as_element()?)?;
+            Some((index, id))
+        })
+        .filter(|&(_, id)| usable(id))
+        .collect();
+    // Stable, so equal indices keep document order
+    order.sort_by_key(|&(index, _)| if index > 0 { index } else { i32::MAX });
+    order.into_iter().map(|(_, id)| id).collect()
+}
+
+/// Where an element goes in the focus order, 0 for document order; None
+/// when it is not in the order
+fn tab_index(element: &ElementData) -> Option<i32> {
+    let focusable = match element.tag_name.as_str() {
+        "a" => element.get_attribute("href").is_some(),
+        "input" => !element.get_attribute("type").is_some_and(|t| t.eq_ignore_ascii_case("hidden")),
+        "button" | "select" | "textarea" => true,
+        _ => false,
+    };
+    match element.get_attribute("tabindex").and_then(|index| index.trim().parse::<i32>().ok()) {
+        Some(index) if index < 0 => None,
+        Some(index) => Some(index),
+        None => focusable.then_some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_dom::Queryable;
+    use gugalanna_html::HtmlParser;
+
+    #[test]
+    fn test_focus_order() {
+        let html = r##"<a id="plain">No href</a>
+            <a id="home" href="/">Home</a>
+            <input id="name">
+            <input id="secret" type="hidden">
+            <div id="card" tabindex="0">Card</div>
+            <button id="later" tabindex="2">Later</button>
+            <button id="skipped" tabindex="-1">Skipped</button>
+            <textarea id="first" tabindex="1"></textarea>
+            <select id="size"></select>
+            <a id="gone" href="#">Gone</a>
+            <input id="also-first" tabindex="1">"##;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let gone = dom.get_element_by_id("gone").unwrap();
+        let ids: Vec<_> = focus_order(&dom, |id| id != gone)
+            .into_iter()
+            .map(|id| dom.get(id).unwrap().as_element().unwrap().id().unwrap().to_string())
+            .collect();
+        assert_eq!(ids, ["first", "also-first", "later", "home", "name", "card", "size"]);
+    }
+}
@@ -0,0 +1,127 @@
+//! Web Font Loading
+//!
+//! Downloads the fonts of @font-face rules in the background. Text is drawn
+//! in the fallback font until its font arrives, then the page is laid out
+//! again with it.
+
+use gugalanna_net::{HttpClient, ResourceLoader};
+use gugalanna_render::FontFace;
+use gugalanna_style::WebFontFace;
+use log::{debug, warn};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// A downloaded font, ready to register under its family
+#[derive(Debug)]
+pub struct LoadedFont {
+    pub family: String,
+    pub face: FontFace,
+    pub data: Vec<u8>,
+}
+
+/// Download each face from the first of its sources that loads, sending
+/// the fonts as they arrive
+///
+/// Sources in unsupported formats are skipped. Fonts are fetched with
+/// `client`, the tab's. Stops when `cancel` is cancelled, as when the tab
+/// navigates away.
+pub async fn fetch_web_fonts(
+    client: HttpClient,
+    faces: Vec<WebFontFace>,
+    base_url: Url,
+    cancel: CancellationToken,
+    fonts: UnboundedSender<LoadedFont>,
+) {
+    let mut loader = ResourceLoader::with_client(client);
+
+    for face in faces {
+        for source in face.sources.iter().filter(|s| s.is_supported()) {
+            let url = match base_url.join(&source.url) {
+                Ok(url) => url,
+                Err(e) => {
+                    warn!("Invalid font URL '{}': {}", source.url, e);
+                    continue;
+                }
+            };
+
+            let data = tokio::select! {
+                _ = cancel.cancelled() => return,
+                data = fetch_font(&mut loader, &url) => data,
+            };
+            match data {
+                Ok(data) => {
+                    debug!("Loaded font '{}' from {}", face.family, url);
+                    let font = LoadedFont {
+                        family: face.family.clone(),
+                        face: FontFace::new(face.weight, face.style),
+                        data,
+                    };
+                    if fonts.send(font).is_err() {
+                        // The tab is gone
+                        return;
+                    }
+                    break;
+                }
+                Err(e) => warn!("Failed to load font {}: {}", url, e),
+            }
+        }
+    }
+}
+
+/// Fetch the data of a font, checking it is TrueType or OpenType
+async fn fetch_font(loader: &mut ResourceLoader, url: &Url) -> Result<Vec<u8>, String> {
+    let data = if url.scheme() == "file" {
+        let path = url.to_file_path().map_err(|_| "Invalid file path".to_string())?;
+        std::fs::read(path).map_err(|e| e.to_string())?
+    } else {
+        let response = loader.load(url).await.map_err(|e| e.to_string())?;
+        if !response.is_success() {
+            return Err(format!("HTTP error: {}", response.status));
+        }
+        response.body.clone()
+    };
+
+    if !is_font_data(&data) {
+        return Err("Unsupported font format".to_string());
+    }
+    Ok(data)
+}
+
+/// Check if data starts like a TrueType or OpenType font or collection
+fn is_font_data(data: &[u8]) -> bool {
+    matches!(data.get(..4), Some([0, 1, 0, 0] | b"true" | b"OTTO" | b"ttcf"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_style::{FontStyle, WebFontSource};
+
+    #[tokio::test]
+    async fn test_fetch_web_fonts_skips_unusable_sources() {
+        let fonts_dir = Url::from_directory_path(concat!(env!("CARGO_MANIFEST_DIR"), "/../render/src/fonts/")).unwrap();
+        let source = |url: &str, format: Option<&str>| WebFontSource {
+            url: url.to_string(),
+            format: format.map(str::to_string),
+        };
+        let face = WebFontFace {
+            family: "Web Sans".to_string(),
+            weight: 700,
+            style: FontStyle::Normal,
+            sources: vec![
+                source("DejaVuSans.woff2", Some("woff2")),
+                source("Missing.ttf", None),
+                source("DejaVuSans.ttf", Some("truetype")),
+            ],
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        fetch_web_fonts(HttpClient::new().unwrap(), vec![face], fonts_dir, CancellationToken::new(), tx).await;
+
+        let font = rx.recv().await.unwrap();
+        assert_eq!((font.family.as_str(), font.face), ("Web Sans", FontFace::BOLD));
+        assert!(is_font_data(&font.data));
+        assert!(rx.recv().await.is_none());
+    }
+}
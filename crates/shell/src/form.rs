@@ -3,8 +3,16 @@
 //! Tracks runtime state for form elements separate from the DOM.
 //! This allows user input to be tracked without constantly modifying DOM attributes.
 
-use gugalanna_dom::NodeId;
-use rustc_hash::FxHashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use gugalanna_dom::{DomTree, NodeId};
+use gugalanna_js::FormControls;
+use gugalanna_layout::{measure_text, text_area_lines, Rect, CONTROL_PADDING};
+use gugalanna_style::ComputedStyle;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::validation::PatternCache;
 
 /// Tracks runtime state for form elements
 #[derive(Debug, Default, Clone)]
@@ -13,6 +21,17 @@ pub struct FormState {
     text_values: FxHashMap<NodeId, TextInputState>,
     /// Checked state for checkboxes/radios (keyed by node ID)
     checked: FxHashMap<NodeId, bool>,
+    /// Disabled state set by scripts, overriding the `disabled` attribute
+    disabled: FxHashMap<NodeId, bool>,
+    /// Selected option index set by scripts for selects, -1 for none
+    selected: FxHashMap<NodeId, i32>,
+    /// Files chosen for file inputs
+    files: FxHashMap<NodeId, Vec<PathBuf>>,
+    /// Controls found invalid when their form was sent, shown as invalid
+    /// for as long as they stay so
+    user_invalid: FxHashSet<NodeId>,
+    /// Patterns the controls were checked against, kept from page to page
+    patterns: PatternCache,
 }
 
 /// State for a text input element
@@ -22,13 +41,48 @@ pub struct TextInputState {
     pub value: String,
     /// Cursor position (byte offset)
     pub cursor_pos: usize,
+    /// First line a text area shows, scrolled to keep the cursor in view
+    pub scroll_line: usize,
+}
+
+/// The box a text area shows its text in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextAreaBox {
+    /// Width its lines wrap at
+    pub width: f32,
+    pub font_size: f32,
+    pub line_height: f32,
+    /// How many lines fit in it
+    pub visible_lines: usize,
+}
+
+impl TextAreaBox {
+    /// The box of a text area painted in `rect`
+    pub fn new(rect: &Rect, font_size: f32, line_height: f32) -> Self {
+        Self {
+            width: rect.width - 2.0 * CONTROL_PADDING,
+            font_size,
+            line_height,
+            visible_lines: ((rect.height - 2.0 * CONTROL_PADDING) / line_height).floor().max(1.0) as usize,
+        }
+    }
+
+    /// Width of text in the text area's font
+    fn measure(&self, text: &str) -> f32 {
+        let style = ComputedStyle {
+            font_size: self.font_size,
+            line_height: self.line_height,
+            ..ComputedStyle::default()
+        };
+        measure_text(text, &style).width
+    }
 }
 
 impl TextInputState {
     /// Create a new text input state with the given initial value
     pub fn new(value: String) -> Self {
         let cursor_pos = value.len();
-        Self { value, cursor_pos }
+        Self { value, cursor_pos, scroll_line: 0 }
     }
 
     /// Insert text at the current cursor position
@@ -100,13 +154,119 @@ impl TextInputState {
     pub fn set_value(&mut self, value: String) {
         self.value = value;
         self.cursor_pos = self.value.len();
+        self.scroll_line = 0;
     }
 
     /// Clear the input
     pub fn clear(&mut self) {
         self.value.clear();
         self.cursor_pos = 0;
+        self.scroll_line = 0;
+    }
+
+    /// Lines a text area shows the value in
+    pub fn lines(&self, area: &TextAreaBox) -> Vec<Range<usize>> {
+        text_area_lines(&self.value, area.width, |text| area.measure(text))
+    }
+
+    /// Line and column of the cursor in a text area, the column a byte
+    /// offset into the line
+    pub fn line_and_column(&self, area: &TextAreaBox) -> (usize, usize) {
+        let lines = self.lines(area);
+        let line = self.cursor_line(&lines);
+        (line, self.cursor_pos - lines[line].start)
+    }
+
+    /// Move the cursor of a text area to the line above, as near as it
+    /// gets to where it was across, or to the start from the first line
+    pub fn move_cursor_up(&mut self, area: &TextAreaBox) {
+        let lines = self.lines(area);
+        match self.cursor_line(&lines).checked_sub(1) {
+            Some(line) => self.cursor_pos = self.offset_nearest(&lines, line, self.cursor_x(&lines, area), area),
+            None => self.cursor_pos = 0,
+        }
+    }
+
+    /// Move the cursor of a text area to the line below, as near as it
+    /// gets to where it was across, or to the end from the last line
+    pub fn move_cursor_down(&mut self, area: &TextAreaBox) {
+        let lines = self.lines(area);
+        let line = self.cursor_line(&lines) + 1;
+        if line < lines.len() {
+            self.cursor_pos = self.offset_nearest(&lines, line, self.cursor_x(&lines, area), area);
+        } else {
+            self.cursor_pos = self.value.len();
+        }
+    }
+
+    /// Move the cursor to the start of its line in a text area
+    pub fn move_cursor_to_line_start(&mut self, area: &TextAreaBox) {
+        let lines = self.lines(area);
+        self.cursor_pos = lines[self.cursor_line(&lines)].start;
+    }
+
+    /// Move the cursor to the end of its line in a text area, before the
+    /// last character of a wrapped line as its end starts the next one
+    pub fn move_cursor_to_line_end(&mut self, area: &TextAreaBox) {
+        let lines = self.lines(area);
+        let line = self.cursor_line(&lines);
+        let end = lines[line].end;
+        self.cursor_pos = if is_wrapped(&lines, line) {
+            self.value[..end].char_indices().next_back().map_or(end, |(i, _)| i)
+        } else {
+            end
+        };
+    }
+
+    /// Move the cursor to the character boundary nearest a point of a text
+    /// area, from the top left of the text it shows
+    pub fn move_cursor_to_point(&mut self, area: &TextAreaBox, x: f32, y: f32) {
+        let lines = self.lines(area);
+        let line = ((y / area.line_height).max(0.0) as usize + self.scroll_line).min(lines.len() - 1);
+        self.cursor_pos = self.offset_nearest(&lines, line, x, area);
     }
+
+    /// Scroll a text area as little as it takes to show the cursor's line
+    pub fn scroll_to_cursor(&mut self, area: &TextAreaBox) {
+        let lines = self.lines(area);
+        let line = self.cursor_line(&lines);
+        let visible = area.visible_lines.max(1);
+        self.scroll_line = self.scroll_line.min(lines.len().saturating_sub(visible));
+        if line < self.scroll_line {
+            self.scroll_line = line;
+        } else if line >= self.scroll_line + visible {
+            self.scroll_line = line + 1 - visible;
+        }
+    }
+
+    /// Line the cursor is on, at a wrap the one it starts
+    fn cursor_line(&self, lines: &[Range<usize>]) -> usize {
+        lines.iter().rposition(|line| line.start <= self.cursor_pos).unwrap_or(0)
+    }
+
+    /// How far across its line the cursor is
+    fn cursor_x(&self, lines: &[Range<usize>], area: &TextAreaBox) -> f32 {
+        let start = lines[self.cursor_line(lines)].start;
+        area.measure(&self.value[start..self.cursor_pos])
+    }
+
+    /// Byte offset of the character boundary of a line nearest to `x`
+    fn offset_nearest(&self, lines: &[Range<usize>], line: usize, x: f32, area: &TextAreaBox) -> usize {
+        let range = lines[line].clone();
+        let text = &self.value[range.clone()];
+        let distance = |i: usize| (area.measure(&text[..i]) - x).abs();
+        text.char_indices()
+            .map(|(i, _)| i)
+            .chain((!is_wrapped(lines, line)).then_some(text.len()))
+            .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+            .map_or(range.start, |i| range.start + i)
+    }
+}
+
+/// Check if a line of a text area wraps, its end being where the next
+/// line starts
+fn is_wrapped(lines: &[Range<usize>], line: usize) -> bool {
+    lines.get(line + 1).is_some_and(|next| next.start == lines[line].end)
 }
 
 impl FormState {
@@ -153,10 +313,78 @@ impl FormState {
         self.checked.insert(node_id, !current);
     }
 
+    /// Get the disabled state set for a control, if any
+    pub fn get_disabled(&self, node_id: NodeId) -> Option<bool> {
+        self.disabled.get(&node_id).copied()
+    }
+
+    /// Set the disabled state of a control
+    pub fn set_disabled(&mut self, node_id: NodeId, disabled: bool) {
+        self.disabled.insert(node_id, disabled);
+    }
+
+    /// Get the selected option index set for a select, if any
+    pub fn get_selected_index(&self, node_id: NodeId) -> Option<i32> {
+        self.selected.get(&node_id).copied()
+    }
+
+    /// Set the selected option index of a select
+    pub fn set_selected_index(&mut self, node_id: NodeId, index: i32) {
+        self.selected.insert(node_id, index);
+    }
+
+    /// Get the files chosen for a file input, none when nothing was chosen
+    pub fn get_files(&self, node_id: NodeId) -> &[PathBuf] {
+        self.files.get(&node_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Set the files chosen for a file input
+    pub fn set_files(&mut self, node_id: NodeId, files: Vec<PathBuf>) {
+        self.files.insert(node_id, files);
+    }
+
+    /// What a file input shows: the name of the file chosen, or how many
+    /// were chosen
+    pub fn file_label(&self, node_id: NodeId) -> String {
+        match self.get_files(node_id) {
+            [] => "No file chosen".to_string(),
+            [file] => file.file_name().map_or_else(|| file.display().to_string(), |n| n.to_string_lossy().into_owned()),
+            files => format!("{} files", files.len()),
+        }
+    }
+
+    /// Show a control as invalid while it stays so
+    pub fn mark_user_invalid(&mut self, node_id: NodeId) {
+        self.user_invalid.insert(node_id);
+    }
+
+    /// Controls shown as invalid while they stay so
+    pub fn user_invalid(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.user_invalid.iter().copied()
+    }
+
+    /// Forget the edits of a control, back to its initial state
+    pub fn reset(&mut self, node_id: NodeId) {
+        self.text_values.remove(&node_id);
+        self.checked.remove(&node_id);
+        self.selected.remove(&node_id);
+        self.files.remove(&node_id);
+        self.user_invalid.remove(&node_id);
+    }
+
     /// Clear all form state
     pub fn clear(&mut self) {
         self.text_values.clear();
         self.checked.clear();
+        self.disabled.clear();
+        self.selected.clear();
+        self.files.clear();
+        self.user_invalid.clear();
+    }
+
+    /// Compiled `pattern` attributes of the controls validated
+    pub fn patterns(&self) -> &PatternCache {
+        &self.patterns
     }
 
     /// Get the text value for an input (convenience method)
@@ -165,6 +393,49 @@ impl FormState {
     }
 }
 
+/// Scripts read and write the same state the user edits
+impl FormControls for FormState {
+    fn value(&self, node_id: NodeId) -> Option<String> {
+        self.get_value(node_id).map(str::to_string)
+    }
+
+    fn set_value(&mut self, node_id: NodeId, value: &str) {
+        self.ensure_text(node_id).set_value(value.to_string());
+    }
+
+    fn checked(&self, node_id: NodeId) -> bool {
+        self.is_checked(node_id)
+    }
+
+    fn set_checked(&mut self, node_id: NodeId, checked: bool) {
+        FormState::set_checked(self, node_id, checked);
+    }
+
+    fn disabled(&self, node_id: NodeId) -> Option<bool> {
+        self.get_disabled(node_id)
+    }
+
+    fn set_disabled(&mut self, node_id: NodeId, disabled: bool) {
+        FormState::set_disabled(self, node_id, disabled);
+    }
+
+    fn selected_index(&self, node_id: NodeId) -> Option<i32> {
+        self.get_selected_index(node_id)
+    }
+
+    fn set_selected_index(&mut self, node_id: NodeId, index: i32) {
+        FormState::set_selected_index(self, node_id, index);
+    }
+
+    fn reset(&mut self, node_id: NodeId) {
+        FormState::reset(self, node_id);
+    }
+
+    fn validation_message(&self, dom: &DomTree, node_id: NodeId) -> Option<String> {
+        crate::validation::validation_message(dom, self, node_id)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -204,6 +475,74 @@ mod tests {
         assert_eq!(state.cursor_pos, 1);
     }
 
+    /// Ten characters a line, six pixels each, three lines in view
+    const AREA: TextAreaBox = TextAreaBox { width: 60.0, font_size: 10.0, line_height: 12.0, visible_lines: 3 };
+
+    #[test]
+    fn test_text_area_editing_across_lines() {
+        let mut state = TextInputState::new("ab\ncd".to_string());
+        assert_eq!(state.line_and_column(&AREA), (1, 2));
+
+        // Deleting the newline joins the lines, Enter splits them again
+        for _ in 0..3 {
+            state.delete_char_before();
+        }
+        assert_eq!((state.value.as_str(), state.line_and_column(&AREA)), ("ab", (0, 2)));
+        state.insert_char('\n');
+        assert_eq!(state.line_and_column(&AREA), (1, 0));
+        state.insert_text("xy");
+        assert_eq!(state.value, "ab\nxy");
+
+        // Up and down keep to the same place across, past the first and
+        // last lines they go to the start and end
+        state.move_cursor_up(&AREA);
+        assert_eq!(state.cursor_pos, 2);
+        state.move_cursor_down(&AREA);
+        assert_eq!(state.cursor_pos, 5);
+        state.move_cursor_to_line_start(&AREA);
+        assert_eq!(state.cursor_pos, 3);
+        state.move_cursor_down(&AREA);
+        assert_eq!(state.cursor_pos, 5);
+        state.move_cursor_up(&AREA);
+        state.move_cursor_up(&AREA);
+        assert_eq!(state.cursor_pos, 0);
+        state.move_cursor_to_line_end(&AREA);
+        assert_eq!(state.cursor_pos, 2);
+    }
+
+    #[test]
+    fn test_text_area_wrapping_and_scrolling() {
+        let mut state = TextInputState::new("one two three four five six".to_string());
+        let lines: Vec<_> = state.lines(&AREA).into_iter().map(|line| &state.value[line]).collect();
+        assert_eq!(lines, ["one two ", "three four ", "five six"]);
+        assert_eq!(state.line_and_column(&AREA), (2, 8));
+
+        // The start of a wrapped line is on it, its end before its space
+        state.move_cursor_to_line_start(&AREA);
+        state.move_cursor_up(&AREA);
+        assert_eq!((state.cursor_pos, state.line_and_column(&AREA)), (8, (1, 0)));
+        state.move_cursor_to_line_end(&AREA);
+        assert_eq!(state.line_and_column(&AREA), (1, 10));
+
+        // A click goes to the nearest character of the line under it
+        state.move_cursor_to_point(&AREA, 13.0, 14.0);
+        assert_eq!(state.cursor_pos, 10);
+        state.move_cursor_to_point(&AREA, 500.0, 100.0);
+        assert_eq!(state.cursor_pos, 27);
+
+        // A box one line high scrolls to the cursor's line
+        let short = TextAreaBox { visible_lines: 1, ..AREA };
+        state.scroll_to_cursor(&short);
+        assert_eq!(state.scroll_line, 2);
+        state.move_cursor_to_point(&short, 0.0, 0.0);
+        assert_eq!(state.cursor_pos, 19);
+        state.move_cursor_up(&short);
+        state.scroll_to_cursor(&short);
+        assert_eq!(state.scroll_line, 1);
+        state.clear();
+        assert_eq!(state.scroll_line, 0);
+    }
+
     #[test]
     fn test_form_state_text() {
         let mut form = FormState::new();
@@ -224,4 +563,21 @@ mod tests {
         form.toggle_checked(node_id);
         assert!(!form.is_checked(node_id));
     }
+
+    #[test]
+    fn test_form_state_files() {
+        let mut form = FormState::new();
+        let node_id = NodeId::new(1);
+
+        assert!(form.get_files(node_id).is_empty());
+        assert_eq!(form.file_label(node_id), "No file chosen");
+        form.set_files(node_id, vec![PathBuf::from("/home/user/photo.png")]);
+        assert_eq!(form.get_files(node_id), [PathBuf::from("/home/user/photo.png")]);
+        assert_eq!(form.file_label(node_id), "photo.png");
+        form.set_files(node_id, vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")]);
+        assert_eq!(form.file_label(node_id), "2 files");
+
+        form.reset(node_id);
+        assert!(form.get_files(node_id).is_empty());
+    }
 }
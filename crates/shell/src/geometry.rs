@@ -0,0 +1,110 @@
+//! Element Geometry
+//!
+//! Measures the boxes of a page's elements for its scripts and for the
+//! DevTools overlay, and lays the page out again when scripts measure it
+//! after changing the DOM.
+
+use std::collections::HashMap;
+
+use gugalanna_css::Stylesheet;
+use gugalanna_dom::{DomTree, NodeId};
+use gugalanna_js::{BoxRect, ElementBox, ElementBoxes, LayoutProvider};
+use gugalanna_layout::{build_layout_tree, layout_document, ContainingBlock, LayoutBox, Rect};
+use gugalanna_render::boxes_in_paint_order;
+use gugalanna_style::StyleTree;
+
+use crate::page_cascade;
+
+fn box_rect(rect: Rect, offset_x: f32, offset_y: f32) -> BoxRect {
+    BoxRect { x: rect.x + offset_x, y: rect.y + offset_y, width: rect.width, height: rect.height }
+}
+
+/// The border and padding boxes of the elements of a layout, in document
+/// coordinates
+///
+/// An element laid out in several boxes measures the area around them.
+pub fn measure_elements(layout: &LayoutBox) -> ElementBoxes {
+    let mut boxes = ElementBoxes::new();
+    for painted in boxes_in_paint_order(layout) {
+        let Some(node_id) = painted.layout_box.node_id() else {
+            continue;
+        };
+        let d = &painted.layout_box.dimensions;
+        let measured = ElementBox {
+            border_box: box_rect(d.border_box(), painted.offset_x, painted.offset_y),
+            padding_box: box_rect(d.padding_box(), painted.offset_x, painted.offset_y),
+            fixed: painted.fixed || painted.layout_box.is_fixed_positioned(),
+        };
+        boxes
+            .entry(node_id)
+            .and_modify(|b| {
+                b.border_box = b.border_box.union(&measured.border_box);
+                b.padding_box = b.padding_box.union(&measured.padding_box);
+            })
+            .or_insert(measured);
+    }
+    boxes
+}
+
+/// The margin, border, padding and content boxes of a node, in document
+/// coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoxModel {
+    pub margin: Rect,
+    pub border: Rect,
+    pub padding: Rect,
+    pub content: Rect,
+    /// Inside a position: fixed box, so in viewport rather than page
+    /// coordinates
+    pub fixed: bool,
+}
+
+/// The box model of each node of a layout, the first box of those laid
+/// out in several
+pub fn measure_box_models(layout: &LayoutBox) -> HashMap<NodeId, BoxModel> {
+    let mut models = HashMap::new();
+    for painted in boxes_in_paint_order(layout) {
+        let Some(node_id) = painted.layout_box.node_id() else {
+            continue;
+        };
+        let d = &painted.layout_box.dimensions;
+        let at = |rect: Rect| Rect::new(rect.x + painted.offset_x, rect.y + painted.offset_y, rect.width, rect.height);
+        models.entry(node_id).or_insert(BoxModel {
+            margin: at(d.margin_box()),
+            border: at(d.border_box()),
+            padding: at(d.padding_box()),
+            content: at(d.content),
+            fixed: painted.fixed || painted.layout_box.is_fixed_positioned(),
+        });
+    }
+    models
+}
+
+/// Lays out a page with its stylesheets, the browser's, the user's and
+/// those of its `<style>` elements as they are when scripts measure it
+pub struct PageLayout {
+    /// The browser's stylesheet for the page
+    css: String,
+    user: Option<Stylesheet>,
+}
+
+impl PageLayout {
+    pub fn new(css: &str, user: Option<&Stylesheet>) -> Self {
+        Self { css: css.to_string(), user: user.cloned() }
+    }
+}
+
+impl LayoutProvider for PageLayout {
+    fn layout(&self, dom: &DomTree, width: f32, height: f32) -> ElementBoxes {
+        let cascade = page_cascade(dom, &self.css, self.user.as_ref());
+        let style_tree = StyleTree::build(dom, &cascade, width, height);
+        let root_id = crate::layout_root(dom);
+        match build_layout_tree(dom, &style_tree, root_id) {
+            Some(mut layout_tree) => {
+                layout_document(&mut layout_tree, ContainingBlock::new(width, height));
+                measure_elements(&layout_tree)
+            }
+            None => ElementBoxes::new(),
+        }
+    }
+}
@@ -6,6 +6,7 @@ use gugalanna_layout::{ImagePixels, LayoutBox, BoxType};
 use gugalanna_net::HttpClient;
 use image::GenericImageView;
 use log::{debug, warn};
+use std::collections::HashMap;
 use std::fs;
 use url::Url;
 
@@ -120,7 +121,7 @@ fn fetch_image_bytes(client: &HttpClient, url: &Url) -> Result<Vec<u8>, ImageLoa
 }
 
 /// Decode image bytes to RGBA pixel data
-fn decode_image(bytes: &[u8]) -> Result<DecodedImage, ImageLoadError> {
+pub fn decode_image(bytes: &[u8]) -> Result<DecodedImage, ImageLoadError> {
     let img = image::load_from_memory(bytes)
         .map_err(|e| ImageLoadError::DecodeFailed(e.to_string()))?;
 
@@ -140,51 +141,76 @@ pub struct DecodedImage {
     pub data: Vec<u8>,
 }
 
-/// Load images in a layout tree (modifies ImageData in-place)
-pub fn load_images_in_tree(
-    layout_box: &mut LayoutBox,
-    client: &HttpClient,
-    base_url: &Url,
-) {
-    load_images_recursive(layout_box, client, base_url);
+/// Decoded images of a page by source, so laying the page out again
+/// doesn't fetch them again
+#[derive(Default)]
+pub struct ImageCache {
+    /// Pixels of each source, None if the image failed to load
+    images: HashMap<String, Option<ImagePixels>>,
 }
 
-fn load_images_recursive(
+impl ImageCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decoded image of a source, loaded once however many boxes and
+    /// layouts show it (None if it failed)
+    fn load(&mut self, client: &HttpClient, base_url: &Url, src: &str) -> Option<ImagePixels> {
+        self.images
+            .entry(src.to_string())
+            .or_insert_with(|| match load_image(client, base_url, src) {
+                Ok(decoded) => {
+                    debug!("Loaded image: {} ({}x{})", src, decoded.width, decoded.height);
+                    Some(ImagePixels {
+                        width: decoded.width,
+                        height: decoded.height,
+                        data: decoded.data.into(),
+                    })
+                }
+                Err(e) => {
+                    warn!("Failed to load image '{}': {}", src, e);
+                    None
+                }
+            })
+            .clone()
+    }
+}
+
+/// Load images in a layout tree (modifies ImageData in-place), and the
+/// background images of the boxes' styles, from `images` when the page
+/// showed them before
+pub fn load_images_in_tree(
     layout_box: &mut LayoutBox,
     client: &HttpClient,
     base_url: &Url,
+    images: &mut ImageCache,
 ) {
+    // Background images are only painted, they don't change the layout
+    if layout_box.background_pixels.is_none() {
+        if let Some(src) = layout_box.style().and_then(|style| style.background_image.as_deref()) {
+            if !src.is_empty() {
+                layout_box.background_pixels = images.load(client, base_url, src);
+            }
+        }
+    }
+
     // Check if this is an image box
     if let BoxType::Image(_, ref mut image_data, _) = layout_box.box_type {
         // Only load if we don't have pixel data yet
         if image_data.pixels.is_none() && !image_data.src.is_empty() {
-            match load_image(client, base_url, &image_data.src) {
-                Ok(decoded) => {
-                    // Update intrinsic dimensions from decoded image
-                    image_data.intrinsic_width = Some(decoded.width as f32);
-                    image_data.intrinsic_height = Some(decoded.height as f32);
-
-                    // Store pixel data
-                    image_data.pixels = Some(ImagePixels {
-                        width: decoded.width,
-                        height: decoded.height,
-                        data: decoded.data,
-                    });
-
-                    debug!(
-                        "Loaded image: {} ({}x{})",
-                        image_data.src, decoded.width, decoded.height
-                    );
-                }
-                Err(e) => {
-                    warn!("Failed to load image '{}': {}", image_data.src, e);
-                }
+            if let Some(pixels) = images.load(client, base_url, &image_data.src) {
+                // Update intrinsic dimensions from decoded image
+                image_data.intrinsic_width = Some(pixels.width as f32);
+                image_data.intrinsic_height = Some(pixels.height as f32);
+                image_data.pixels = Some(pixels);
             }
         }
     }
 
     // Recurse into children
     for child in &mut layout_box.children {
-        load_images_recursive(child, client, base_url);
+        load_images_in_tree(child, client, base_url, images);
     }
 }
@@ -2,39 +2,114 @@
 //!
 //! Browser window, event handling, and UI.
 
+mod bookmarks;
 mod chrome;
+mod config;
+mod context_menu;
+mod cookies;
 mod devtools;
+mod dialog;
+mod download;
 mod event;
+mod favicon;
+mod fetch;
+mod file_picker;
+mod find;
+mod focus;
+mod font_loader;
 mod form;
+mod geometry;
 mod image_loader;
 mod loading;
 mod navigation;
+mod reader;
+mod replay;
+mod retained_layout;
+mod screenshot;
+mod script_loader;
+mod session;
+mod scrollbar;
+mod smooth_scroll;
 mod transition;
+mod user_style;
+mod validation;
+mod view_source;
 
-pub use chrome::{Chrome, ChromeHit, CHROME_HEIGHT};
+pub use chrome::{Chrome, ChromeHit, TabInfo, CHROME_HEIGHT};
 pub use devtools::{DevTools, DevToolsHit, DevToolsTab, DEVTOOLS_HEIGHT};
-pub use loading::{LoadingState, NavigationError, NavigationResult};
+pub use loading::{LoadMilestone, LoadingState, NavigationError, NavigationResult};
 pub use navigation::NavigationState;
+#[cfg(feature = "sdl")]
+pub use replay::replay_in_window;
+pub use replay::replay_to_png;
+pub use screenshot::{dump_display_list, render_to_png, screenshot_url, CaptureFormat};
 
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use url::Url;
 
 use crate::transition::TransitionManager;
 
 use gugalanna_css::Stylesheet;
-use gugalanna_dom::{DomTree, NodeId, Queryable};
+use gugalanna_dom::{DomTree, ElementData, NodeId, Queryable};
 use gugalanna_html::HtmlParser;
-use gugalanna_js::JsRuntime;
-use gugalanna_layout::{build_layout_tree, layout_block, BoxType, ContainingBlock, LayoutBox};
-use gugalanna_net::HttpClient;
-use gugalanna_render::{build_display_list, CursorType, DisplayList, RenderBackend, RenderColor, SdlBackend};
-use gugalanna_style::{Cascade, MatchingContext, StyleTree};
-
-use crate::event::{poll_events, start_text_input, stop_text_input, BrowserEvent, Modifiers, MouseButton};
-use crate::form::FormState;
+use gugalanna_js::{HistoryChange, JsRuntime, KeyEvent, LogLevel, StorageStore};
+use gugalanna_layout::{
+    build_layout_tree, layout_document, select_data, set_font_measurer, BoxType, ContainingBlock, ImagePixels, LayoutBox,
+    Rect, CONTROL_PADDING,
+};
+use gugalanna_net::{ClientConfig, HttpClient, Multipart};
+use gugalanna_render::{
+    boxes_in_paint_order, build_display_list, build_display_list_with_selection, command_bounds, damaged_rect,
+    diff_display_lists, scroll_container_id, CursorType, DisplayList, FontFace, FontRegistry, PaintedBox,
+    RenderBackend, RenderColor, ScaledImages, SoftwareBackend,
+};
+#[cfg(feature = "sdl")]
+use gugalanna_render::SdlBackend;
+use gugalanna_style::{
+    interpolate_transform, Cascade, LengthPercentage, MatchingContext, StyleTree, Transform, TransformFunction, WebFontFace,
+};
+
+use crate::event::{
+    key_names, poll_events, set_clipboard_text, start_text_input, stop_text_input, BrowserEvent, KeyNames, Modifiers,
+    MouseButton, Swipe,
+};
+use crate::bookmarks::{bookmarks_page, escape_html, Bookmarks, BOOKMARKS_URL};
+use crate::config::{ConfigFile, CONFIG_FILE_NAME};
+use crate::context_menu::{ContextMenu, MenuContext, MenuItem};
+use crate::cookies::PageCookies;
+use crate::devtools::{box_model_overlays, INSPECTED_PROPERTIES};
+use crate::dialog::{Dialogs, SharedBackend};
+use crate::download::{
+    capture_file_name, default_download_dir, download_path, downloads_page, is_download, DownloadManager,
+    DOWNLOADS_URL,
+};
+use crate::favicon::{favicon_url, fetch_favicon, FaviconCache};
+use crate::fetch::PageFetches;
+use crate::file_picker::FilePicker;
+use crate::find::PageFind;
+use crate::focus::focus_order;
+use crate::font_loader::{fetch_web_fonts, LoadedFont};
+use crate::form::{FormState, TextAreaBox, TextInputState};
+use crate::geometry::{measure_box_models, measure_elements, BoxModel, PageLayout};
+use crate::image_loader::ImageCache;
+use crate::reader::{
+    extract_article, reader_css, reader_page, Article, MAX_READER_FONT_SIZE, MIN_READER_FONT_SIZE, READER_FONT_SIZE,
+};
+use crate::retained_layout::RetainedLayout;
+use crate::script_loader::{run_fetched, run_page_scripts, AsyncScripts};
+use crate::screenshot::{paint_display_list, save_capture};
+use crate::session::{Session, SessionTab};
+use crate::scrollbar::Scrollbar;
+use crate::smooth_scroll::SmoothScroll;
+use crate::user_style::{UserStylesheet, USER_STYLESHEET_NAME};
+use crate::validation::{validated_controls, validation_message};
+use crate::view_source::{read_file_source, source_page, source_target, source_url, VIEW_SOURCE_SCHEME};
 
 /// Browser configuration
 #[derive(Debug, Clone)]
@@ -42,6 +117,36 @@ pub struct BrowserConfig {
     pub width: u32,
     pub height: u32,
     pub title: String,
+    pub backend: BackendKind,
+    /// Directory data kept between runs is saved in, None to keep it in
+    /// memory only
+    pub config_dir: Option<PathBuf>,
+    /// Directory files saved from pages are written to
+    pub download_dir: Option<PathBuf>,
+    /// Reopen the tabs of the last session saved in `config_dir`
+    pub restore_session: bool,
+    /// Animate scrolling by the wheel and keys, off to have the page jump
+    /// at once. Pages drawn without a window always jump.
+    pub smooth_scrolling: bool,
+    /// Page the first tab opens at, when no other page is asked for
+    pub homepage: Option<String>,
+    /// URL searches typed in the address bar go to, `{}` standing for the
+    /// search terms
+    pub search_engine: String,
+    /// Scheme of addresses typed without one
+    pub default_scheme: String,
+    /// User-Agent sent in place of the default one
+    pub user_agent: Option<String>,
+    /// Run the scripts of pages
+    pub javascript_enabled: bool,
+    /// How far an arrow key or a step of the wheel scrolls
+    pub scroll_step: f32,
+    /// Stylesheet added to every page at the user origin, `user.css` in
+    /// `config_dir` when None
+    pub user_stylesheet: Option<PathBuf>,
+    /// Go back or forward by swiping sideways with two fingers on a
+    /// touchpad
+    pub swipe_navigation: bool,
 }
 
 impl Default for BrowserConfig {
@@ -50,6 +155,63 @@ impl Default for BrowserConfig {
             width: 1024,
             height: 768,
             title: String::from("Gugalanna"),
+            backend: BackendKind::from_env(),
+            config_dir: default_config_dir(),
+            download_dir: default_download_dir(),
+            restore_session: false,
+            smooth_scrolling: true,
+            homepage: None,
+            search_engine: DEFAULT_SEARCH_ENGINE.to_string(),
+            default_scheme: String::from("https"),
+            user_agent: None,
+            javascript_enabled: true,
+            scroll_step: DEFAULT_SCROLL_STEP,
+            user_stylesheet: None,
+            swipe_navigation: false,
+        }
+    }
+}
+
+impl BrowserConfig {
+    /// The defaults with the settings of the config file in the config
+    /// directory set on them
+    pub fn load() -> Self {
+        let mut config = Self::default();
+        if let Some(dir) = config.config_dir.clone() {
+            ConfigFile::load(&dir.join(CONFIG_FILE_NAME)).apply(&mut config);
+        }
+        config
+    }
+}
+
+/// `gugalanna` in the user's configuration directory, from XDG_CONFIG_HOME
+/// or else HOME
+fn default_config_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("gugalanna"))
+}
+
+/// Backend the browser draws with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// SDL2 window, needs the `sdl` feature
+    Sdl,
+    /// In-memory framebuffer, without a window or events
+    Software,
+}
+
+impl BackendKind {
+    /// Backend named by the GUGALANNA_BACKEND environment variable ("sdl"
+    /// or "software"), else the window when built with it
+    pub fn from_env() -> Self {
+        match std::env::var("GUGALANNA_BACKEND").as_deref() {
+            Ok("software") => BackendKind::Software,
+            Ok("sdl") => BackendKind::Sdl,
+            _ if cfg!(feature = "sdl") => BackendKind::Sdl,
+            _ => BackendKind::Software,
         }
     }
 }
@@ -59,28 +221,96 @@ impl Default for BrowserConfig {
 enum FocusTarget {
     None,
     AddressBar,
+    /// The find bar's query is being typed
+    FindBar,
     Page,
     /// A form input element is focused
     FormInput(NodeId),
+    /// A link or another control Tab moved focus to, shown with a ring
+    Element(NodeId),
+    /// Code is being typed in the DevTools console
+    DevToolsConsole,
 }
 
+/// Width of the ring around the element Tab moved focus to
+const FOCUS_RING_WIDTH: f32 = 2.0;
+
 /// Scroll constants
-const SCROLL_LINE_HEIGHT: f32 = 40.0; // Arrow keys scroll amount
+const DEFAULT_SCROLL_STEP: f32 = 40.0; // Arrow keys and mouse wheel scroll amount
 const SCROLL_PAGE_FACTOR: f32 = 0.9; // Page Up/Down scrolls 90% of viewport
-const SCROLL_WHEEL_MULTIPLIER: f32 = 40.0; // Mouse wheel multiplier
+
+/// Where searches typed in the address bar go, `{}` standing for the terms
+const DEFAULT_SEARCH_ENGINE: &str = "https://duckduckgo.com/html/?q={}";
+
+/// How often the timers of background tabs run
+const BACKGROUND_TIMER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long localStorage goes unchanged before it is saved
+const LOCAL_STORAGE_SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// How long the tabs go unchanged before the session is saved
+const SESSION_SAVE_DELAY: Duration = Duration::from_secs(5);
+
+/// How many closed tabs can be reopened
+const MAX_CLOSED_TABS: usize = 10;
+
+/// How long a toast stays up
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+/// How long the message of an invalid control stays up
+const VALIDATION_BUBBLE_DURATION: Duration = Duration::from_secs(5);
+
+/// Border painted around controls shown as invalid
+const INVALID_BORDER_WIDTH: f32 = 2.0;
+
+/// How long a message stays in the status overlay, over the link hovered
+const STATUS_MESSAGE_DURATION: Duration = Duration::from_millis(1500);
+
+/// How long a navigation that failed for want of a connection waits
+/// before it is tried again by itself
+const ERROR_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Captures of longer pages are cut off there, to keep them in memory
+const MAX_CAPTURE_HEIGHT: f32 = 32_768.0;
+
+/// Styles for pages loaded without their own
+const DEFAULT_CSS: &str = r#"
+    body { background-color: white; color: black; font-size: 16px; }
+    h1, h2, h3, h4, h5, h6, p, div { display: block; }
+    h1 { font-size: 32px; margin-top: 20px; margin-bottom: 10px; }
+    h2 { font-size: 24px; margin-top: 18px; margin-bottom: 8px; }
+    h3 { font-size: 18px; margin-top: 16px; margin-bottom: 6px; }
+    p { margin-top: 10px; margin-bottom: 10px; }
+"#;
+
+/// Styles for pages loaded from history without their own
+const HISTORY_CSS: &str = "body { background-color: white; color: black; font-size: 16px; }";
 
 /// Page state (rendered content)
-struct PageState {
+pub struct PageState {
     /// Current URL
     url: Url,
-    /// Display list for rendering
-    display_list: DisplayList,
+    /// Display list for rendering, shared with each frame painted from it
+    display_list: Rc<DisplayList>,
     /// JavaScript runtime (for event handling)
     js_runtime: Option<JsRuntime>,
+    /// Requests made by the page's scripts that are in flight
+    fetches: PageFetches,
+    /// Async scripts of the page still being fetched
+    async_scripts: AsyncScripts,
     /// Layout tree for hit testing (stored as display list node IDs)
     hit_regions: Vec<HitRegion>,
+    /// Boxes of each node, for the DevTools overlay
+    box_models: HashMap<NodeId, BoxModel>,
     /// Current vertical scroll offset (0 = top)
     scroll_y: f32,
+    /// Smooth scroll of the page a script started, if under way
+    smooth_scroll: Option<SmoothScroll>,
+    /// The page scrolled since its scripts were last sent `scroll`
+    scrolled: bool,
+    /// Vertical scroll offsets of the scroll containers inside the page,
+    /// by node ID
+    scroll_offsets: HashMap<u32, f32>,
     /// Total content height
     content_height: f32,
     /// Visible viewport height (window height - chrome height)
@@ -89,6 +319,140 @@ struct PageState {
     dom: Rc<RefCell<DomTree>>,
     /// CSS cascade (for re-layout on resize)
     cascade: Cascade,
+    /// Tree the page was last laid out as, None when it has no box
+    layout: Option<RetainedLayout>,
+    /// Nodes scripts changed since the page was last laid out
+    dirty_nodes: Vec<NodeId>,
+    /// Blocks the last layout laid out, the others kept their layout
+    blocks_laid_out: usize,
+    /// Images the page loaded, shown again without loading them again
+    /// when it is laid out anew
+    images: ImageCache,
+    /// Images of the page scaled to the size they were last drawn at
+    scaled_images: ScaledImages,
+    /// Search of the find bar in the page, highlighted as it is laid out
+    find: PageFind,
+    /// Dialog choosing the files of one of the page's file inputs
+    file_picker: FilePicker,
+    /// HTML the page was loaded from, for viewing its source
+    source: String,
+    /// Article of the page for reader mode, found when it loaded
+    article: Option<Article>,
+}
+
+impl PageState {
+    /// Replace the images of a frame with copies scaled to the size they
+    /// are drawn at, filtered once and reused while the size holds
+    fn scale_images(&mut self, frame: &mut DisplayList) {
+        use gugalanna_render::PaintCommand;
+
+        for cmd in &mut frame.commands {
+            match cmd {
+                PaintCommand::DrawImage { rect, pixels: Some(pixels), rendering, .. } => {
+                    *pixels = self.scaled_images.get(pixels, rect.width as u32, rect.height as u32, *rendering);
+                }
+                // Tiles are drawn at their size rounded to whole pixels
+                PaintCommand::DrawTiledImage { tile, pixels, rendering, .. } => {
+                    let (width, height) = (tile.width.round() as u32, tile.height.round() as u32);
+                    *pixels = self.scaled_images.get(pixels, width, height, *rendering);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Scrollbars of the scroll containers that overflow and of the page,
+    /// in window coordinates, with the container each one scrolls (None for
+    /// the page)
+    fn scrollbars(&self, window_width: f32) -> Vec<(Option<u32>, Scrollbar)> {
+        let mut bars: Vec<_> = self
+            .hit_regions
+            .iter()
+            .filter_map(|region| {
+                let range = region.scroll_range?;
+                let offset = self.scroll_offsets.get(&region.node_id).copied().unwrap_or(0.0);
+                let page_offset = if region.fixed { CHROME_HEIGHT } else { CHROME_HEIGHT - self.scroll_y };
+                let area = Rect::new(region.x, region.y + page_offset, region.width, region.height);
+                Some((Some(region.node_id), Scrollbar::new(area, region.height + range, offset)?))
+            })
+            .collect();
+
+        let viewport = Rect::new(0.0, CHROME_HEIGHT, window_width, self.viewport_height);
+        bars.extend(Scrollbar::new(viewport, self.content_height, self.scroll_y).map(|bar| (None, bar)));
+        bars
+    }
+
+    /// Scroll the page, or a scroll container inside it, to an offset
+    fn scroll_to(&mut self, scroller: Option<u32>, offset: f32) {
+        match scroller {
+            None => {
+                self.smooth_scroll = None;
+                self.scroll_page(offset);
+            }
+            Some(scroller) => {
+                let current = self.scroll_offsets.entry(scroller).or_insert(0.0);
+                let moved = offset - *current;
+                *current = offset;
+                scroll_hit_regions(&mut self.hit_regions, scroller, moved);
+            }
+        }
+    }
+
+    /// Move the page to an offset clamped to its content, telling its
+    /// scripts
+    fn scroll_page(&mut self, offset: f32) {
+        let max_scroll = (self.content_height - self.viewport_height).max(0.0);
+        let scroll_y = offset.clamp(0.0, max_scroll);
+        self.scrolled |= scroll_y != self.scroll_y;
+        self.scroll_y = scroll_y;
+        if let Some(rt) = &self.js_runtime {
+            rt.set_scroll_y(self.scroll_y);
+        }
+    }
+
+    /// Scroll the page toward an offset clamped to its content, over the
+    /// next frames when `smooth`, retargeting a smooth scroll under way
+    fn scroll_toward(&mut self, offset: f32, smooth: bool) {
+        if !smooth {
+            self.scroll_to(None, offset);
+            return;
+        }
+        let max_scroll = (self.content_height - self.viewport_height).max(0.0);
+        let target = offset.clamp(0.0, max_scroll);
+        match &mut self.smooth_scroll {
+            Some(scroll) => scroll.retarget(self.scroll_y, target),
+            None if target != self.scroll_y => self.smooth_scroll = Some(SmoothScroll::new(self.scroll_y, target)),
+            None => {}
+        }
+    }
+
+    /// Offset the page is headed to, where a smooth scroll under way ends
+    fn scroll_target(&self) -> f32 {
+        self.smooth_scroll.map_or(self.scroll_y, |scroll| scroll.target())
+    }
+
+    /// Move a smooth scroll on by `delta_ms`, returns whether the page moved
+    fn step_smooth_scroll(&mut self, delta_ms: f32) -> bool {
+        let Some(scroll) = self.smooth_scroll.as_mut() else {
+            return false;
+        };
+        let (offset, done) = scroll.advance(delta_ms);
+        if done {
+            self.smooth_scroll = None;
+        }
+        let before = self.scroll_y;
+        self.scroll_page(offset);
+        self.scroll_y != before
+    }
+}
+
+/// A scrollbar thumb held with the mouse
+#[derive(Debug, Clone, Copy)]
+struct ScrollbarDrag {
+    /// Scroll container being scrolled, None for the page
+    scroller: Option<u32>,
+    /// Distance from the top of the thumb to where it was grabbed
+    grab_y: f32,
 }
 
 /// Hit region for click handling
@@ -98,6 +462,17 @@ struct HitRegion {
     width: f32,
     height: f32,
     node_id: u32,
+    /// Inside a position: fixed box, so in viewport rather than page coordinates
+    fixed: bool,
+    /// Area the region is clipped to by its overflow clipping ancestors
+    clip: Option<Rect>,
+    /// Scroll containers the region moves with, innermost first
+    scrollers: Vec<u32>,
+    /// How far the box can be scrolled down, if it is a scroll container
+    scroll_range: Option<f32>,
+    /// Undoes the transforms the region is painted with, taking points
+    /// where it is painted to where it is laid out
+    inverse_transform: Option<Transform>,
 }
 
 /// Unique tab identifier
@@ -116,10 +491,35 @@ pub struct TabState {
     pub loading_state: LoadingState,
     /// Receiver for navigation results from async task
     pub nav_receiver: Option<tokio::sync::mpsc::Receiver<NavigationResult>>,
+    /// Receiver for the milestones of the current navigation as it loads
+    pub nav_progress: Option<tokio::sync::mpsc::UnboundedReceiver<LoadMilestone>>,
     /// Cancellation token for current navigation
     pub nav_cancel: Option<tokio_util::sync::CancellationToken>,
-    /// Form state for this tab
-    pub form_state: FormState,
+    /// Receiver for the web fonts of the current page as they download
+    pub font_receiver: Option<tokio::sync::mpsc::UnboundedReceiver<LoadedFont>>,
+    /// Cancellation token for the current page's font downloads
+    pub font_cancel: Option<tokio_util::sync::CancellationToken>,
+    /// Fonts arrived while the tab was in the background, so its page was
+    /// laid out with the fallback font
+    pub fonts_changed: bool,
+    /// Scripts changed the DOM while the tab was in the background, so its
+    /// page was laid out before the changes
+    pub dom_changed: bool,
+    /// Form state for this tab, shared with its page's scripts
+    pub form_state: Rc<RefCell<FormState>>,
+    /// sessionStorage of the origins the tab visited, kept while it is open
+    pub session_storage: StorageStore,
+    /// Icon of the tab's page, and the receiver for it while it is fetched
+    pub favicon: Option<ImagePixels>,
+    pub favicon_receiver: Option<tokio::sync::oneshot::Receiver<Result<ImagePixels, String>>>,
+    /// Set on a restored tab until its page is first loaded, with how far
+    /// the page was scrolled
+    pub restore_scroll: Option<f32>,
+    /// The page shown before reader mode, while `page` is its reader page
+    pub reader_original: Option<PageState>,
+    /// Whether the tab is private, its cookies and localStorage kept apart
+    /// and nothing of it saved
+    pub private: bool,
 }
 
 impl TabState {
@@ -131,9 +531,38 @@ impl TabState {
             page: None,
             loading_state: LoadingState::default(),
             nav_receiver: None,
+            nav_progress: None,
             nav_cancel: None,
-            form_state: FormState::new(),
+            font_receiver: None,
+            font_cancel: None,
+            fonts_changed: false,
+            dom_changed: false,
+            form_state: Rc::default(),
+            session_storage: StorageStore::new(),
+            favicon: None,
+            favicon_receiver: None,
+            restore_scroll: None,
+            reader_original: None,
+            private: false,
+        }
+    }
+
+    /// A tab with the history of a saved one, its page not loaded yet
+    fn restored(id: TabId, saved: &SessionTab) -> Option<Self> {
+        let mut tab = Self::new(id);
+        tab.navigation = saved.navigation()?;
+        tab.restore_scroll = Some(saved.scroll_y);
+        Some(tab)
+    }
+
+    /// The tab as it is saved in the session, None for private tabs
+    fn session_tab(&self) -> Option<SessionTab> {
+        if self.private {
+            return None;
         }
+        let page = self.reader_original.as_ref().or(self.page.as_ref());
+        let scroll_y = page.map(|page| page.scroll_y).or(self.restore_scroll);
+        SessionTab::new(&self.navigation, scroll_y.unwrap_or(0.0))
     }
 
     /// Get the tab's title (URL host or "New Tab")
@@ -167,12 +596,59 @@ impl TabState {
             .map(|u| u.as_str().to_string())
             .unwrap_or_default()
     }
+
+    /// Address the address bar shows: the URL that failed to load while
+    /// its error page is shown, so it can be tried again
+    pub fn address(&self) -> String {
+        match &self.loading_state {
+            LoadingState::Failed { url, .. } => url.to_string(),
+            _ => self.current_url_string(),
+        }
+    }
+
+    /// Forget a navigation that failed, once the tab shows another page
+    fn clear_failure(&mut self) {
+        if matches!(self.loading_state, LoadingState::Failed { .. }) {
+            self.loading_state = LoadingState::Idle;
+        }
+    }
+}
+
+/// The retry of a navigation that failed for want of a connection, made
+/// by itself after a countdown on the error page
+struct ErrorRetry {
+    tab_id: TabId,
+    at: Instant,
+    /// Seconds left shown by the error page
+    seconds_left: u64,
+}
+
+/// The message of an invalid control, shown in a bubble under it
+struct ValidationBubble {
+    node_id: NodeId,
+    message: String,
+    expires: Instant,
+}
+
+/// What the private tabs share, apart from the other tabs: the HTTP
+/// client with its own cookie jar, and localStorage kept in memory
+struct PrivateState {
+    client: HttpClient,
+    local_storage: StorageStore,
+}
+
+impl PrivateState {
+    fn new(client: &HttpClient) -> Self {
+        Self { client: client.with_isolated_state(), local_storage: StorageStore::new() }
+    }
 }
 
 /// Browser window state
 pub struct Browser {
     pub config: BrowserConfig,
-    backend: SdlBackend,
+    backend: SharedBackend,
+    /// Shows the dialogs of pages over the window
+    dialogs: Rc<Dialogs>,
     chrome: Chrome,
     /// Developer tools panel
     devtools: DevTools,
@@ -184,41 +660,160 @@ pub struct Browser {
     next_tab_id: u32,
     /// Input focus target
     focus: FocusTarget,
-    /// HTTP client (shared across all tabs)
+    /// HTTP client (shared across all tabs but private ones)
     http_client: HttpClient,
+    /// State of the private tabs, while any is open
+    private_state: Option<PrivateState>,
     /// Current cursor type
     current_cursor: CursorType,
     /// Transition manager for CSS transitions
     transition_manager: TransitionManager,
+    /// Transforms each element's transform transition runs between, its
+    /// value in the manager is how far along it is
+    transform_transitions: HashMap<usize, (Vec<TransformFunction>, Vec<TransformFunction>)>,
     /// Last frame timestamp for delta time calculation
     last_frame: Instant,
+    /// When the timers of background tabs last ran
+    background_timers_run: Instant,
+    /// When the animation frames of background tabs last ran
+    background_frames_run: Instant,
+    /// Something changed since the last frame was rendered
+    needs_redraw: bool,
+    /// Commands of the last frame painted to the window, compared with the
+    /// next one to find what to repaint
+    painted_frame: Option<DisplayList>,
     /// Currently hovered element (for :hover pseudo-class)
     hovered_element: Option<NodeId>,
+    /// Last known mouse position, where wheel events are routed
+    mouse_position: (f32, f32),
+    /// Horizontal wheel ticks adding up to going back or forward
+    swipe: Swipe,
+    /// The page canceled the key the next text input is typed by
+    suppress_text_input: bool,
+    /// Value of the focused form input when it got focus, `change` is
+    /// dispatched on blur if it differs
+    value_at_focus: Option<String>,
+    /// Scrollbar thumb being dragged, until the button is released
+    scrollbar_drag: Option<ScrollbarDrag>,
+    /// Web fonts loaded by pages, shared with the backend and layout
+    font_registry: FontRegistry,
+    /// localStorage of every origin, and the file it is saved to
+    local_storage: StorageStore,
+    local_storage_path: Option<PathBuf>,
+    /// When localStorage changed after it was last saved
+    local_storage_changed: Option<Instant>,
+    /// Pages the user bookmarked, and the file they are saved to
+    bookmarks: Bookmarks,
+    bookmarks_path: Option<PathBuf>,
+    /// Menu a right click opened over the page, until it is dismissed
+    context_menu: Option<ContextMenu>,
+    /// Files saved from pages this session
+    downloads: DownloadManager,
+    /// Icons of the sites visited this session
+    favicons: FaviconCache,
+    /// The tabs as last seen, the file they are saved to and when they
+    /// changed after they were last saved
+    session: Session,
+    session_path: Option<PathBuf>,
+    session_changed: Option<Instant>,
+    /// Tabs closed this session, the last closed last
+    closed_tabs: Vec<SessionTab>,
+    /// Size of the text of reader pages
+    reader_font_size: f32,
+    /// When the toast shown in the chrome goes away
+    toast_expires: Option<Instant>,
+    /// Message of the invalid control that kept a form from being sent
+    validation_bubble: Option<ValidationBubble>,
+    /// When the message in the status overlay gives way to the link
+    /// hovered
+    status_expires: Option<Instant>,
+    /// Retry of the failed navigation the active tab's error page counts
+    /// down to
+    error_retry: Option<ErrorRetry>,
+    /// Navigation that was retried by itself, not retried by itself again
+    /// when it fails once more
+    auto_retried: Option<(TabId, Url)>,
+    /// The user's stylesheet, added to the cascade of every page
+    user_style: UserStylesheet,
 }
 
 impl Browser {
     /// Create a new browser with the given configuration
     pub fn new(config: BrowserConfig) -> Result<Self, String> {
-        let backend =
-            SdlBackend::new(&config.title, config.width, config.height).map_err(|e| e.to_string())?;
+        let mut backend: Box<dyn RenderBackend> = match config.backend {
+            #[cfg(feature = "sdl")]
+            BackendKind::Sdl => Box::new(SdlBackend::new(&config.title, config.width, config.height)?),
+            #[cfg(not(feature = "sdl"))]
+            BackendKind::Sdl => return Err("Built without the sdl feature".into()),
+            BackendKind::Software => Box::new(SoftwareBackend::new(config.width, config.height)),
+        };
+
+        // Text is drawn and measured with the same web fonts
+        let font_registry = FontRegistry::new();
+        backend.font_cache_mut().set_registry(font_registry.clone());
+        set_font_measurer(Some(Rc::new(font_registry.clone())));
+
+        let backend = Rc::new(RefCell::new(backend));
+        let dialogs = Rc::new(Dialogs::new(backend.clone(), config.backend == BackendKind::Sdl));
 
         let mut chrome = Chrome::new(config.width as f32);
 
-        let http_client = HttpClient::new().map_err(|e| e.to_string())?;
+        let client_config = ClientConfig { user_agent: config.user_agent.clone(), ..ClientConfig::default() };
+        let http_client = HttpClient::with_config(client_config).map_err(|e| e.to_string())?;
 
         // Create initial tab
         let initial_tab_id = TabId(0);
         let initial_tab = TabState::new(initial_tab_id);
 
         // Initialize tab bar with first tab
-        let tab_infos = vec![(initial_tab_id, initial_tab.title(), false, true)];
+        let tab_infos = vec![TabInfo {
+            id: initial_tab_id,
+            title: initial_tab.title(),
+            is_loading: false,
+            is_private: false,
+            favicon: None,
+        }];
         chrome.layout_tabs(&tab_infos, initial_tab_id);
 
         let devtools = DevTools::new(config.width as f32);
 
-        Ok(Self {
+        let local_storage_path = config.config_dir.as_ref().map(|dir| dir.join("local-storage.json"));
+        let local_storage = match &local_storage_path {
+            Some(path) => StorageStore::load(path).unwrap_or_else(|e| {
+                log::warn!("Failed to load localStorage from {}: {}", path.display(), e);
+                StorageStore::new()
+            }),
+            None => StorageStore::new(),
+        };
+
+        let bookmarks_path = config.config_dir.as_ref().map(|dir| dir.join("bookmarks.json"));
+        let bookmarks = match &bookmarks_path {
+            Some(path) => Bookmarks::load(path).unwrap_or_else(|e| {
+                log::warn!("Failed to load bookmarks from {}: {}", path.display(), e);
+                Bookmarks::default()
+            }),
+            None => Bookmarks::default(),
+        };
+        let downloads = DownloadManager::new(config.download_dir.clone());
+
+        let user_style_path = config.user_stylesheet.clone();
+        let user_style = UserStylesheet::load(
+            user_style_path.or_else(|| config.config_dir.as_ref().map(|dir| dir.join(USER_STYLESHEET_NAME))),
+        );
+
+        let session_path = config.config_dir.as_ref().map(|dir| dir.join("session.json"));
+        let session = match &session_path {
+            Some(path) if config.restore_session => Session::load(path).unwrap_or_else(|e| {
+                log::warn!("Failed to load the session from {}: {}", path.display(), e);
+                Session::default()
+            }),
+            _ => Session::default(),
+        };
+
+        let mut browser = Self {
             config,
             backend,
+            dialogs,
             chrome,
             devtools,
             tabs: vec![initial_tab],
@@ -226,15 +821,55 @@ impl Browser {
             next_tab_id: 1,
             focus: FocusTarget::None,
             http_client,
+            private_state: None,
             current_cursor: CursorType::Arrow,
             transition_manager: TransitionManager::new(),
+            transform_transitions: HashMap::new(),
             last_frame: Instant::now(),
+            background_timers_run: Instant::now(),
+            background_frames_run: Instant::now(),
+            needs_redraw: true,
+            painted_frame: None,
             hovered_element: None,
-        })
+            mouse_position: (0.0, 0.0),
+            swipe: Swipe::default(),
+            suppress_text_input: false,
+            value_at_focus: None,
+            scrollbar_drag: None,
+            font_registry,
+            local_storage,
+            local_storage_path,
+            local_storage_changed: None,
+            bookmarks,
+            bookmarks_path,
+            context_menu: None,
+            downloads,
+            favicons: FaviconCache::default(),
+            session: Session::default(),
+            session_path,
+            session_changed: None,
+            closed_tabs: Vec::new(),
+            reader_font_size: READER_FONT_SIZE,
+            toast_expires: None,
+            validation_bubble: None,
+            status_expires: None,
+            error_retry: None,
+            auto_retried: None,
+            user_style,
+        };
+        browser.restore_session(session);
+        Ok(browser)
     }
 
     // ==================== Tab Helper Methods ====================
 
+    /// Height of the page's viewport, the window below the chrome and
+    /// above DevTools when they are open
+    fn page_viewport_height(&self) -> f32 {
+        let devtools = if self.devtools.open { DEVTOOLS_HEIGHT } else { 0.0 };
+        self.config.height as f32 - CHROME_HEIGHT - devtools
+    }
+
     /// Get the active tab (immutable)
     fn active_tab(&self) -> Option<&TabState> {
         self.tabs.iter().find(|t| t.id == self.active_tab_id)
@@ -259,16 +894,15 @@ impl Browser {
     /// Sync Chrome UI with current tab state
     fn sync_chrome_with_tabs(&mut self) {
         // Collect tab info for layout
-        let tab_infos: Vec<(TabId, String, bool, bool)> = self
+        let tab_infos: Vec<TabInfo> = self
             .tabs
             .iter()
-            .map(|tab| {
-                (
-                    tab.id,
-                    tab.title(),
-                    tab.is_loading(),
-                    tab.id == self.active_tab_id,
-                )
+            .map(|tab| TabInfo {
+                id: tab.id,
+                title: tab.title(),
+                is_loading: tab.is_loading(),
+                is_private: tab.private,
+                favicon: tab.favicon.clone(),
             })
             .collect();
 
@@ -280,37 +914,101 @@ impl Browser {
         // Extract values for active tab
         let tab_data = self.active_tab().map(|tab| {
             (
-                tab.current_url_string(),
+                tab.address(),
                 tab.navigation.can_go_back(),
                 tab.navigation.can_go_forward(),
                 tab.is_loading(),
+                tab.favicon.clone(),
+                tab.reader_original.is_some(),
+                tab.page.as_ref().is_some_and(|page| page.article.is_some()),
             )
         });
 
         // Update chrome UI
-        if let Some((url, can_back, can_forward, is_loading)) = tab_data {
+        if let Some((url, can_back, can_forward, is_loading, favicon, reader_mode, has_article)) = tab_data {
             self.chrome.address_bar.set_text(&url);
+            self.chrome.favicon = favicon;
+            self.chrome.is_reader_mode = reader_mode;
+            self.chrome.reader_button.enabled = reader_mode || has_article;
             self.chrome.update_navigation_state(can_back, can_forward);
             self.chrome.is_loading = is_loading;
+            self.chrome.is_bookmarked = self.bookmarks.contains(&url);
         }
+        self.update_error_badge();
+        self.sync_find_bar();
+    }
+
+    /// Count the errors the active tab's page logged, for the badge in the
+    /// chrome
+    fn update_error_badge(&mut self) {
+        let count = self
+            .active_tab()
+            .and_then(|tab| tab.page.as_ref())
+            .and_then(|page| page.js_runtime.as_ref())
+            .and_then(|rt| rt.console_messages().lock().ok().map(|messages| {
+                messages.iter().filter(|message| message.level == LogLevel::Error).count()
+            }))
+            .unwrap_or(0);
+        if count != self.chrome.error_count {
+            self.chrome.error_count = count;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Redraw the DevTools console when the active page logged, scrolled
+    /// to the last message
+    fn follow_console(&mut self) {
+        let tab = self.tabs.iter().find(|t| t.id == self.active_tab_id);
+        let Some(rt) = tab.and_then(|t| t.page.as_ref()).and_then(|p| p.js_runtime.as_ref()) else {
+            return;
+        };
+        let changed = rt.console_messages().lock().is_ok_and(|messages| self.devtools.follow_console(&messages));
+        self.needs_redraw |= changed && self.devtools.open;
     }
 
     // ==================== Tab Management ====================
 
     /// Create a new tab and switch to it
     pub fn new_tab(&mut self) -> TabId {
+        self.open_tab(false)
+    }
+
+    /// Create a new private tab and switch to it
+    pub fn new_private_tab(&mut self) -> TabId {
+        self.open_tab(true)
+    }
+
+    /// Create a tab, private or not, and switch to it
+    fn open_tab(&mut self, private: bool) -> TabId {
         let id = TabId(self.next_tab_id);
         self.next_tab_id += 1;
 
-        let tab = TabState::new(id);
+        let mut tab = TabState::new(id);
+        tab.private = private;
+        if private && self.private_state.is_none() {
+            self.private_state = Some(PrivateState::new(&self.http_client));
+        }
         self.tabs.push(tab);
 
         self.switch_to_tab(id);
 
-        log::info!("Created new tab {}", id.0);
+        log::info!("Created new {}tab {}", if private { "private " } else { "" }, id.0);
         id
     }
 
+    /// Whether the active tab is private, so tabs opened from it are too
+    fn active_tab_private(&self) -> bool {
+        self.active_tab().is_some_and(|tab| tab.private)
+    }
+
+    /// The HTTP client of a tab, the private one for private tabs
+    fn client_for(&self, tab_id: TabId) -> &HttpClient {
+        match &self.private_state {
+            Some(state) if self.tabs.iter().any(|tab| tab.id == tab_id && tab.private) => &state.client,
+            _ => &self.http_client,
+        }
+    }
+
     /// Close a tab by ID
     /// Returns true if the browser should quit (last tab closed)
     pub fn close_tab(&mut self, id: TabId) -> bool {
@@ -325,15 +1023,29 @@ impl Browser {
             None => return false,
         };
 
-        // Cancel any in-progress navigation for this tab
+        // Cancel any in-progress navigation and font downloads for this tab
         if let Some(tab) = self.tab_mut(id) {
             if let Some(cancel) = tab.nav_cancel.take() {
                 cancel.cancel();
             }
+            if let Some(cancel) = tab.font_cancel.take() {
+                cancel.cancel();
+            }
         }
 
-        // Remove the tab
+        // Kept to be reopened
+        if let Some(saved) = self.tabs[index].session_tab() {
+            if self.closed_tabs.len() == MAX_CLOSED_TABS {
+                self.closed_tabs.remove(0);
+            }
+            self.closed_tabs.push(saved);
+        }
+
+        // Remove the tab, and the private state with the last private tab
         self.tabs.remove(index);
+        if !self.tabs.iter().any(|tab| tab.private) {
+            self.private_state = None;
+        }
 
         // If we closed the active tab, switch to an adjacent one
         if id == self.active_tab_id {
@@ -348,15 +1060,63 @@ impl Browser {
         false
     }
 
+    /// Reopen the tab closed last, with its history, and switch to it
+    pub fn reopen_closed_tab(&mut self) -> Option<TabId> {
+        let saved = self.closed_tabs.pop()?;
+        let id = TabId(self.next_tab_id);
+        let tab = TabState::restored(id, &saved)?;
+        self.next_tab_id += 1;
+        self.tabs.push(tab);
+
+        self.switch_to_tab(id);
+
+        log::info!("Reopened tab {}", id.0);
+        Some(id)
+    }
+
     /// Switch to a tab by ID
     pub fn switch_to_tab(&mut self, id: TabId) {
         if self.tabs.iter().any(|t| t.id == id) {
             self.active_tab_id = id;
+            self.drop_page_focus();
             self.sync_chrome_with_tabs();
+            self.chrome.loading_bar.clear();
+            if let Some(tab) = self.active_tab().filter(|tab| tab.is_loading()) {
+                let progress = tab.loading_state.progress();
+                self.chrome.loading_bar.set_progress(progress);
+            }
+
+            // Lay the page out with the fonts that arrived and the DOM
+            // changes made in the background
+            let changed = self.tab_mut(id).is_some_and(|tab| {
+                std::mem::take(&mut tab.fonts_changed) | std::mem::take(&mut tab.dom_changed)
+            });
+            if changed {
+                self.relayout_page();
+            }
+
+            // A restored tab loads its page when it is first shown
+            if let Some(scroll_y) = self.tab_mut(id).and_then(|tab| tab.restore_scroll.take()) {
+                self.load_restored_page(scroll_y);
+            }
             log::debug!("Switched to tab {}", id.0);
         }
     }
 
+    /// Load the page of a tab restored from a session, scrolled as it was
+    fn load_restored_page(&mut self, scroll_y: f32) {
+        let Some(url) = self.active_tab().and_then(|tab| tab.navigation.current_url().cloned()) else {
+            return;
+        };
+        if let Err(e) = self.reload_url(url.clone()) {
+            log::warn!("Failed to restore {}: {}", url, e);
+            return;
+        }
+        if let Some(page) = self.active_tab_mut().and_then(|tab| tab.page.as_mut()) {
+            page.scroll_to(None, scroll_y);
+        }
+    }
+
     /// Switch to next tab (wraps around)
     pub fn next_tab(&mut self) {
         if let Some(current_index) = self.tab_index(self.active_tab_id) {
@@ -381,20 +1141,40 @@ impl Browser {
 
     // ==================== Navigation ====================
 
+    /// Open the homepage in the active tab when it has no page yet, as
+    /// when no session was restored
+    pub fn open_homepage(&mut self) {
+        let Some(homepage) = self.config.homepage.clone() else {
+            return;
+        };
+        if self.active_tab().is_some_and(|tab| tab.navigation.is_empty()) {
+            if let Err(e) = self.navigate(&homepage) {
+                log::error!("Failed to open the homepage {}: {}", homepage, e);
+            }
+        }
+    }
+
     /// Navigate to a URL
     pub fn navigate(&mut self, url_str: &str) -> Result<(), String> {
         // Parse URL
-        let url = if url_str.contains("://") {
-            Url::parse(url_str).map_err(|e| e.to_string())?
-        } else {
-            Url::parse(&format!("https://{}", url_str)).map_err(|e| e.to_string())?
-        };
+        let url = parse_address(url_str, &self.config)?;
+
+        // The browser's own pages are made, not fetched
+        if url.scheme() == "about" {
+            return self.open_about_page(url);
+        }
 
         log::info!("Navigating to: {}", url);
 
         // Update address bar
         self.chrome.address_bar.set_text(url.as_str());
 
+        // The source of a page is shown instead of the page
+        if url.scheme() == VIEW_SOURCE_SCHEME {
+            let html = self.fetch_source_page(&url)?;
+            return self.load_page(url, &html);
+        }
+
         // Fetch the page - use block_in_place to allow blocking in async context
         let response = self.fetch_url(&url)?;
 
@@ -402,6 +1182,14 @@ impl Browser {
             return Err(format!("HTTP error: {}", response.status));
         }
 
+        // What is not a page is saved instead
+        if is_download(&response.headers) {
+            self.sync_chrome_with_tabs();
+            self.downloads.save_response(&response)?;
+            self.show_downloads();
+            return Ok(());
+        }
+
         let html = response.text_lossy();
         log::info!("Received {} bytes", html.len());
 
@@ -414,17 +1202,29 @@ impl Browser {
     /// Navigate via POST form submission
     pub fn navigate_post(&mut self, url: &Url, form_data: &str) -> Result<(), String> {
         log::info!("POST navigating to: {} with data: {}", url, form_data);
+        self.post_and_load(url, &PostBody::UrlEncoded(form_data.to_string()))
+    }
 
+    /// POST a form's body and show the page it answers with
+    fn post_and_load(&mut self, url: &Url, body: &PostBody) -> Result<(), String> {
         // Update address bar
         self.chrome.address_bar.set_text(url.as_str());
 
         // POST the form data
-        let response = self.fetch_url_post(url, form_data)?;
+        let response = self.fetch_url_post(url, body)?;
 
         if !response.is_success() {
             return Err(format!("HTTP error: {}", response.status));
         }
 
+        // What is not a page is saved instead
+        if is_download(&response.headers) {
+            self.sync_chrome_with_tabs();
+            self.downloads.save_response(&response)?;
+            self.show_downloads();
+            return Ok(());
+        }
+
         let html = response.text_lossy();
         log::info!("Received {} bytes", html.len());
 
@@ -446,34 +1246,40 @@ impl Browser {
                 cancel.cancel();
             }
             tab.nav_receiver = None;
+            tab.nav_progress = None;
         }
+        self.chrome.loading_bar.clear();
 
         // Parse URL
-        let url = if url_str.contains("://") {
-            Url::parse(url_str).map_err(|e| e.to_string())?
-        } else {
-            Url::parse(&format!("https://{}", url_str)).map_err(|e| e.to_string())?
-        };
+        let url = parse_address(url_str, &self.config)?;
+
+        // The browser's own pages are made, not fetched
+        if url.scheme() == "about" {
+            return self.open_about_page(url);
+        }
 
         log::info!("Starting async navigation to: {}", url);
 
         // Update UI immediately
         self.chrome.address_bar.set_text(url.as_str());
         self.chrome.is_loading = true;
+        self.chrome.loading_bar.start();
 
-        // Create channel and cancellation token
+        // Create channels and cancellation token
         let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let (progress_tx, progress_rx) = tokio::sync::mpsc::unbounded_channel();
         let cancel_token = tokio_util::sync::CancellationToken::new();
 
         // Store in active tab
         if let Some(tab) = self.tab_mut(active_id) {
-            tab.loading_state = LoadingState::Loading { url: url.clone() };
+            tab.loading_state = LoadingState::loading(url.clone());
             tab.nav_receiver = Some(rx);
+            tab.nav_progress = Some(progress_rx);
             tab.nav_cancel = Some(cancel_token.clone());
         }
 
         // Clone what we need for the async task
-        let client = self.http_client.clone();
+        let client = self.client_for(active_id).clone();
         let url_clone = url.clone();
 
         // Spawn async fetch task
@@ -485,34 +1291,7 @@ impl Browser {
                         error: NavigationError::Cancelled,
                     }
                 }
-                fetch_result = client.get(&url_clone) => {
-                    match fetch_result {
-                        Ok(response) if response.is_success() => {
-                            let html = response.text_lossy();
-                            NavigationResult::Success {
-                                url: response.url,
-                                html,
-                            }
-                        }
-                        Ok(response) => {
-                            NavigationResult::Failed {
-                                url: url_clone,
-                                error: NavigationError::HttpError { status: response.status },
-                            }
-                        }
-                        Err(e) => {
-                            let error = if e.to_string().contains("timed out") {
-                                NavigationError::Timeout
-                            } else {
-                                NavigationError::NetworkError(e.to_string())
-                            };
-                            NavigationResult::Failed {
-                                url: url_clone,
-                                error,
-                            }
-                        }
-                    }
-                }
+                result = fetch_navigation(&client, &url_clone, &progress_tx) => result,
             };
 
             let _ = tx.send(result).await;
@@ -521,6 +1300,207 @@ impl Browser {
         Ok(())
     }
 
+    /// Open a page the browser makes, such as `about:bookmarks`
+    fn open_about_page(&mut self, url: Url) -> Result<(), String> {
+        let (url, html) = self.about_page(url)?;
+        self.chrome.address_bar.set_text(url.as_str());
+        self.load_page(url, &html)
+    }
+
+    /// The URL and HTML of a page the browser makes
+    ///
+    /// The delete links of `about:bookmarks` come back here as
+    /// `about:bookmarks?delete=<url>`, which deletes the bookmark and shows
+    /// the page again, as do the cancel links of `about:downloads`.
+    fn about_page(&mut self, mut url: Url) -> Result<(Url, String), String> {
+        let html = match url.path() {
+            "blank" => "<body></body>".to_string(),
+            "bookmarks" => {
+                let deleted = url.query_pairs().find(|(name, _)| name == "delete").map(|(_, url)| url.into_owned());
+                if let Some(deleted) = deleted {
+                    self.bookmarks.remove(&deleted);
+                    self.save_bookmarks();
+                }
+                url = Url::parse(BOOKMARKS_URL).map_err(|e| e.to_string())?;
+                bookmarks_page(&self.bookmarks)
+            }
+            "downloads" => {
+                let cancelled = url.query_pairs().find(|(name, _)| name == "cancel").map(|(_, id)| id.into_owned());
+                if let Some(id) = cancelled.and_then(|id| id.parse().ok()) {
+                    self.downloads.cancel(id);
+                }
+                url = Url::parse(DOWNLOADS_URL).map_err(|e| e.to_string())?;
+                downloads_page(&self.downloads)
+            }
+            page => return Err(format!("Unknown page about:{}", page)),
+        };
+        Ok((url, html))
+    }
+
+    /// Show `about:downloads`, in the tab already showing it or else a new
+    /// one
+    fn show_downloads(&mut self) {
+        let shown = self.tabs.iter().find(|tab| tab.current_url_string() == DOWNLOADS_URL).map(|tab| tab.id);
+        match shown {
+            Some(id) => {
+                self.switch_to_tab(id);
+                self.refresh_downloads_page();
+            }
+            None => {
+                self.new_tab();
+                if let Err(e) = self.navigate(DOWNLOADS_URL) {
+                    log::error!("Failed to open {}: {}", DOWNLOADS_URL, e);
+                }
+            }
+        }
+    }
+
+    /// Show `about:downloads` again in the active tab when it is on it
+    fn refresh_downloads_page(&mut self) {
+        if self.active_tab().is_none_or(|tab| tab.current_url_string() != DOWNLOADS_URL) {
+            return;
+        }
+        let html = downloads_page(&self.downloads);
+        let shown = Url::parse(DOWNLOADS_URL)
+            .map_err(|e| e.to_string())
+            .and_then(|url| self.load_page_without_history(url, &html));
+        if let Err(e) = shown {
+            log::error!("Failed to show {}: {}", DOWNLOADS_URL, e);
+        }
+    }
+
+    /// Apply what happened to the downloads, showing their progress
+    fn poll_downloads(&mut self) {
+        if !self.downloads.poll().is_empty() {
+            self.refresh_downloads_page();
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Bookmark the active tab's page, or delete its bookmark
+    pub fn toggle_bookmark(&mut self) {
+        let Some(tab) = self.active_tab() else {
+            return;
+        };
+        let url = tab.current_url_string();
+        // The browser's own pages are not bookmarked
+        if url.is_empty() || url.starts_with("about:") {
+            return;
+        }
+        let title = tab
+            .page
+            .as_ref()
+            .and_then(|page| {
+                let dom = page.dom.borrow();
+                let title = dom.get_elements_by_tag_name("title").first().map(|&id| dom.text_content(id))?;
+                Some(title.split_whitespace().collect::<Vec<_>>().join(" ")).filter(|t| !t.is_empty())
+            })
+            .unwrap_or_else(|| tab.title());
+        let added = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let bookmarked = self.bookmarks.toggle(&title, &url, added);
+        log::info!("{} {}", if bookmarked { "Bookmarked" } else { "Deleted the bookmark of" }, url);
+        self.save_bookmarks();
+        self.chrome.is_bookmarked = bookmarked;
+        self.needs_redraw = true;
+    }
+
+    fn save_bookmarks(&self) {
+        if let Some(path) = &self.bookmarks_path {
+            if let Err(e) = self.bookmarks.save(path) {
+                log::warn!("Failed to save bookmarks to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Whether the active tab shows its page in reader mode
+    pub fn is_reader_mode(&self) -> bool {
+        self.active_tab().is_some_and(|tab| tab.reader_original.is_some())
+    }
+
+    /// Show the article of the active tab's page in reader mode, or the
+    /// page again when it is in reader mode
+    pub fn toggle_reader_mode(&mut self) {
+        let active_id = self.active_tab_id;
+        let Some(tab) = self.tab_mut(active_id) else {
+            return;
+        };
+        match tab.reader_original.take() {
+            // The page comes back as it was left, laid out again in case
+            // the window changed meanwhile
+            Some(original) => {
+                tab.page = Some(original);
+                self.relayout_page();
+                self.sync_chrome_with_tabs();
+                self.needs_redraw = true;
+            }
+            None => self.load_reader_page(),
+        }
+    }
+
+    /// Change the size of reader text, going back to the default for None;
+    /// a reader page shown is made again at the new size
+    fn set_reader_font_size(&mut self, size: Option<f32>) {
+        let size = size.unwrap_or(READER_FONT_SIZE).clamp(MIN_READER_FONT_SIZE, MAX_READER_FONT_SIZE);
+        if size != self.reader_font_size {
+            self.reader_font_size = size;
+            self.reload_reader_page();
+        }
+    }
+
+    /// Make the reader page of the active tab again, as its text size or
+    /// the window changed, keeping how far through the article it is
+    fn reload_reader_page(&mut self) {
+        if !self.is_reader_mode() {
+            return;
+        }
+        let progress = self
+            .active_tab()
+            .and_then(|tab| tab.page.as_ref())
+            .filter(|page| page.content_height > 0.0)
+            .map_or(0.0, |page| page.scroll_y / page.content_height);
+        self.load_reader_page();
+        if let Some(page) = self.active_tab_mut().and_then(|tab| tab.page.as_mut()) {
+            let offset = progress * page.content_height;
+            page.scroll_to(None, offset);
+        }
+    }
+
+    /// Load the reader page of the active tab's article in place of its
+    /// page, which is kept to be shown again as it was
+    fn load_reader_page(&mut self) {
+        let active_id = self.active_tab_id;
+        let Some(page) = self.active_tab().and_then(|tab| tab.reader_original.as_ref().or(tab.page.as_ref())) else {
+            return;
+        };
+        let Some(article) = page.article.as_ref() else {
+            return;
+        };
+        let html = reader_page(article, favicon_url(&page.dom.borrow(), &page.url).as_ref());
+        let url = page.url.clone();
+        let css = reader_css(self.reader_font_size, self.config.width as f32);
+
+        // The reader page takes the page's place without an entry in the
+        // tab's history
+        let Some(tab) = self.tab_mut(active_id) else {
+            return;
+        };
+        let navigation = std::mem::take(&mut tab.navigation);
+        let original = tab.reader_original.take().or_else(|| tab.page.take());
+        let result = self.load_page_with_css(url, &html, &css);
+        if let Some(tab) = self.tab_mut(active_id) {
+            tab.navigation = navigation;
+            match result {
+                Ok(()) => tab.reader_original = original,
+                Err(e) => {
+                    log::error!("Showing the reader page failed: {}", e);
+                    tab.page = original;
+                }
+            }
+        }
+        self.sync_chrome_with_tabs();
+        self.needs_redraw = true;
+    }
+
     /// Load HTML content directly (for demos and local content)
     pub fn load_html(&mut self, html: &str, css: &str) -> Result<(), String> {
         // Use about:blank as the URL
@@ -549,24 +1529,33 @@ impl Browser {
 
     /// Load HTML content into a page
     fn load_page(&mut self, url: Url, html: &str) -> Result<(), String> {
-        let default_css = r#"
-            body { background-color: white; color: black; font-size: 16px; }
-            h1, h2, h3, h4, h5, h6, p, div { display: block; }
-            h1 { font-size: 32px; margin-top: 20px; margin-bottom: 10px; }
-            h2 { font-size: 24px; margin-top: 18px; margin-bottom: 8px; }
-            h3 { font-size: 18px; margin-top: 16px; margin-bottom: 6px; }
-            p { margin-top: 10px; margin-bottom: 10px; }
-        "#;
-        self.load_page_with_css(url, html, default_css)
+        self.load_page_with_css(url, html, DEFAULT_CSS)
     }
 
-    /// Load HTML content with custom CSS
+    /// User-Agent of the browser, the configured one if set
+    fn user_agent(&self) -> &str {
+        self.config.user_agent.as_deref().unwrap_or(gugalanna_net::DEFAULT_USER_AGENT)
+    }
+
+    /// Script runtime for a page's DOM, None when scripts are turned off
+    fn page_runtime(&self, dom: DomTree) -> Option<JsRuntime> {
+        if !self.config.javascript_enabled {
+            return None;
+        }
+        JsRuntime::with_dom(dom).ok()
+    }
+
+    /// Load HTML content with custom CSS
     fn load_page_with_css(&mut self, url: Url, html: &str, css: &str) -> Result<(), String> {
         // Parse HTML
         let dom = HtmlParser::new().parse(html).map_err(|e| e.to_string())?;
 
+        // The user stylesheet is read again if it changed, so edits show
+        // on reload
+        self.user_style.refresh();
+
         // Create JS runtime with DOM bindings
-        let js_runtime = JsRuntime::with_dom(dom).ok();
+        let js_runtime = self.page_runtime(dom);
 
         // Get DOM reference
         let shared_dom = match js_runtime.as_ref().and_then(|rt| rt.dom()) {
@@ -578,93 +1567,127 @@ impl Browser {
             }
         };
 
-        // Execute scripts
-        if let Some(ref rt) = js_runtime {
-            if let Err(e) = rt.execute_scripts() {
-                log::warn!("Script execution error: {}", e);
+        // Update navigation, scripts see the new entry in `history`
+        let active_id = self.active_tab_id;
+        if let Some(tab) = self.tab_mut(active_id) {
+            tab.navigation.navigate_to(url.clone());
+            // What was typed into the last page's controls stays with it
+            tab.form_state.borrow_mut().clear();
+            if let Some(ref rt) = js_runtime {
+                attach_history(&tab.navigation, rt);
+                rt.set_form_controls(tab.form_state.clone());
             }
         }
 
-        // Parse CSS and build cascade
-        let mut cascade = Cascade::new();
-        if let Ok(stylesheet) = Stylesheet::parse(css) {
-            cascade.add_author_stylesheet(stylesheet);
-        }
-
-        // Extract and add CSS from <style> tags in the document
-        {
-            let dom_ref = shared_dom.borrow();
-            let style_elements = dom_ref.get_elements_by_tag_name("style");
-            for style_id in style_elements {
-                // Get the text content of the style element
-                if let Some(style_css) = extract_style_content(&dom_ref, style_id) {
-                    if let Ok(stylesheet) = Stylesheet::parse(&style_css) {
-                        cascade.add_author_stylesheet(stylesheet);
-                    }
+        // Execute scripts
+        let async_scripts = AsyncScripts::new(self.client_for(active_id).clone());
+        if let Some(ref rt) = js_runtime {
+            self.attach_storage(active_id, &url, rt);
+            rt.set_viewport(self.config.width as f32, self.page_viewport_height());
+            rt.set_user_agent(self.user_agent());
+            rt.set_dialog_handler(self.dialogs.clone());
+            rt.set_cookie_access(Rc::new(PageCookies::new(self.client_for(active_id).cookie_jar())));
+            rt.set_module_fetcher(Arc::new(async_scripts.module_fetch()));
+            rt.set_layout_provider(Rc::new(PageLayout::new(css, self.user_style.stylesheet())));
+            for result in run_page_scripts(rt, &url, &async_scripts) {
+                if let Some(e) = result.error {
+                    log::warn!("Script execution error: {}", e);
                 }
             }
+            if let Err(e) = rt.dispatch_dom_content_loaded() {
+                log::warn!("DOMContentLoaded dispatch failed: {}", e);
+            }
+            // The first layout below already has the changes scripts made
+            rt.take_style_dirty();
         }
 
+        // The browser's, the user's and those of <style> elements
+        let cascade = page_cascade(&shared_dom.borrow(), css, self.user_style.stylesheet());
+
         // Calculate viewport (below chrome)
         let viewport_width = self.config.width as f32;
-        let viewport_height = self.config.height as f32 - CHROME_HEIGHT;
+        let viewport_height = self.page_viewport_height();
 
         // Build style and layout trees
         let dom_ref = shared_dom.borrow();
-        let style_tree = StyleTree::build(&*dom_ref, &cascade, viewport_width, viewport_height);
+        let style_tree = Rc::new(StyleTree::build(&dom_ref, &cascade, viewport_width, viewport_height));
 
-        let body_ids = dom_ref.get_elements_by_tag_name("body");
-        let root_id = if !body_ids.is_empty() {
-            body_ids[0]
-        } else {
-            dom_ref.document_id()
-        };
+        let root_id = layout_root(&dom_ref);
 
-        let mut layout_tree = match build_layout_tree(&*dom_ref, &style_tree, root_id) {
-            Some(tree) => tree,
+        let mut layout = match RetainedLayout::build(&dom_ref, style_tree.clone(), root_id) {
+            Some(layout) => layout,
             None => return Err("Failed to build layout tree".into()),
         };
 
         // Load images (before layout so intrinsic dimensions are available)
-        image_loader::load_images_in_tree(&mut layout_tree, &self.http_client, &url);
+        let client = self.client_for(active_id);
+        let mut images = ImageCache::new();
+        layout.with_dependent_mut(|_, tree| image_loader::load_images_in_tree(tree, client, &url, &mut images));
 
         // Perform layout
-        layout_block(
-            &mut layout_tree,
-            ContainingBlock::new(viewport_width, viewport_height),
-        );
+        let blocks_laid_out = layout.lay_out(None, &[], ContainingBlock::new(viewport_width, viewport_height));
+        let layout_tree = layout.tree();
 
         // Get content height for scrolling
         let content_height = layout_tree.dimensions.margin_box_height();
 
         // Build display list
-        let display_list = build_display_list(&layout_tree);
+        let display_list = build_display_list(layout_tree);
 
         // Build hit regions
-        let hit_regions = build_hit_regions(&layout_tree);
+        let hit_regions = build_hit_regions(layout_tree);
+        let box_models = measure_box_models(layout_tree);
+        let element_boxes = measure_elements(layout_tree);
 
         // Drop DOM borrow
         drop(dom_ref);
+        if let Some(ref rt) = js_runtime {
+            rt.set_element_boxes(element_boxes);
+            rt.set_content_height(content_height);
+            rt.set_computed_styles(style_tree);
+            // Images load before layout and nothing loads after it yet, so
+            // the page is complete; the frame loop lays out what load
+            // listeners change
+            if let Err(e) = rt.dispatch_load() {
+                log::warn!("Load dispatch failed: {}", e);
+            }
+        }
 
         // Store page state in active tab
-        let active_id = self.active_tab_id;
         let paint_commands = display_list.commands.len();
+        self.load_web_fonts(active_id, cascade.web_font_faces(), &url);
+        self.load_favicon(active_id, &url, &shared_dom.borrow());
+        let article = extract_article(&shared_dom.borrow(), &url);
 
+        self.drop_page_focus();
         if let Some(tab) = self.tab_mut(active_id) {
-            // Update navigation
-            tab.navigation.navigate_to(url.clone());
-
-            // Store page state
+            tab.reader_original = None;
+            tab.clear_failure();
             tab.page = Some(PageState {
                 url,
-                display_list,
+                display_list: Rc::new(display_list),
                 js_runtime,
+                fetches: PageFetches::new(),
+                async_scripts,
                 hit_regions,
+                box_models,
                 scroll_y: 0.0,
+                smooth_scroll: None,
+                scrolled: false,
+                scroll_offsets: HashMap::new(),
                 content_height,
                 viewport_height,
                 dom: shared_dom.clone(),
                 cascade,
+                layout: Some(layout),
+                dirty_nodes: Vec::new(),
+                blocks_laid_out,
+                images,
+                scaled_images: ScaledImages::new(),
+                find: PageFind::default(),
+                file_picker: FilePicker::new(),
+                source: html.to_string(),
+                article,
             });
         }
 
@@ -678,33 +1701,44 @@ impl Browser {
 
     /// Go back in history
     pub fn go_back(&mut self) -> Result<(), String> {
-        let active_id = self.active_tab_id;
-        let url = if let Some(tab) = self.tab_mut(active_id) {
-            tab.navigation.go_back().cloned()
-        } else {
-            None
-        };
-
-        if let Some(url) = url {
-            self.chrome.address_bar.set_text(url.as_str());
-            self.reload_url(url)?;
-        }
-        Ok(())
+        self.traverse_history(-1)
     }
 
     /// Go forward in history
     pub fn go_forward(&mut self) -> Result<(), String> {
+        self.traverse_history(1)
+    }
+
+    /// Move `delta` entries through the active tab's history
+    ///
+    /// An entry of the document shown is not loaded again, its page gets
+    /// a `popstate` event with the entry's state instead.
+    fn traverse_history(&mut self, delta: i32) -> Result<(), String> {
         let active_id = self.active_tab_id;
-        let url = if let Some(tab) = self.tab_mut(active_id) {
-            tab.navigation.go_forward().cloned()
-        } else {
-            None
+        let Some(traversal) = self.tab_mut(active_id).and_then(|tab| tab.navigation.traverse(delta)) else {
+            return Ok(());
         };
+        if let Some(tab) = self.tab_mut(active_id) {
+            tab.clear_failure();
+        }
+        self.chrome.address_bar.set_text(traversal.url.as_str());
+        if !traversal.same_document {
+            self.drop_page_focus();
+            return self.reload_url(traversal.url);
+        }
 
-        if let Some(url) = url {
-            self.chrome.address_bar.set_text(url.as_str());
-            self.reload_url(url)?;
+        if let Some(tab) = self.tab_mut(active_id) {
+            if let Some(page) = tab.page.as_mut() {
+                page.url = traversal.url;
+                if let Some(rt) = page.js_runtime.as_ref() {
+                    attach_history(&tab.navigation, rt);
+                    if let Err(e) = rt.dispatch_popstate(traversal.state.as_deref()) {
+                        log::warn!("Popstate dispatch failed: {}", e);
+                    }
+                }
+            }
         }
+        self.sync_chrome_with_tabs();
         Ok(())
     }
 
@@ -713,8 +1747,8 @@ impl Browser {
         // Get the current URL from active tab's navigation history or address bar
         let url = self
             .active_tab()
-            .and_then(|tab| tab.navigation.current_url())
-            .map(|u| u.as_str().to_string())
+            .map(|tab| tab.address())
+            .filter(|url| !url.is_empty())
             .or_else(|| {
                 let text = &self.chrome.address_bar.text;
                 if !text.is_empty() {
@@ -742,12 +1776,32 @@ impl Browser {
             }
             tab.loading_state = LoadingState::Idle;
             tab.nav_receiver = None;
+            tab.nav_progress = None;
+            // And the page's scripts, such as one waiting on a dialog
+            if let Some(rt) = tab.page.as_ref().and_then(|page| page.js_runtime.as_ref()) {
+                rt.request_interrupt();
+            }
         }
         self.chrome.is_loading = false;
+        self.chrome.loading_bar.clear();
+    }
+
+    /// Answer the next dialog a page opens without showing it: Some to
+    /// accept it, with the text prompt() returns, None to dismiss it
+    pub fn answer_next_dialog(&self, answer: Option<String>) {
+        self.dialogs.answer_next(answer);
     }
 
     /// Reload a URL (for back/forward)
     fn reload_url(&mut self, url: Url) -> Result<(), String> {
+        if url.scheme() == "about" {
+            let (url, html) = self.about_page(url)?;
+            return self.load_page_without_history(url, &html);
+        }
+        if url.scheme() == VIEW_SOURCE_SCHEME {
+            let html = self.fetch_source_page(&url)?;
+            return self.load_page_without_history(url, &html);
+        }
         let response = self.fetch_url(&url)?;
 
         if !response.is_success() {
@@ -766,33 +1820,53 @@ impl Browser {
         if let Ok(handle) = Handle::try_current() {
             // We're in an async context - use block_in_place
             tokio::task::block_in_place(|| {
-                handle.block_on(self.http_client.get(url))
+                handle.block_on(self.client_for(self.active_tab_id).get(url))
             })
             .map_err(|e| e.to_string())
         } else {
             // No runtime - create one
             let rt = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
-            rt.block_on(self.http_client.get(url))
+            rt.block_on(self.client_for(self.active_tab_id).get(url))
                 .map_err(|e| e.to_string())
         }
     }
 
+    /// Fetch the page a `view-source:` URL shows, the source of its target
+    fn fetch_source_page(&self, url: &Url) -> Result<String, String> {
+        let target = source_target(url).ok_or_else(|| format!("No source to view at {}", url))?;
+        let source = match target.scheme() {
+            "file" => read_file_source(&target)?,
+            _ => {
+                let response = self.fetch_url(&target)?;
+                if !response.is_success() {
+                    return Err(format!("HTTP error: {}", response.status));
+                }
+                response.text_lossy()
+            }
+        };
+        Ok(source_page(&target, &source))
+    }
+
     /// POST form data to a URL
-    fn fetch_url_post(&self, url: &Url, form_data: &str) -> Result<gugalanna_net::Response, String> {
+    fn fetch_url_post(&self, url: &Url, body: &PostBody) -> Result<gugalanna_net::Response, String> {
         use tokio::runtime::Handle;
 
+        let client = self.client_for(self.active_tab_id);
+        let post = async {
+            match body {
+                PostBody::UrlEncoded(form_data) => client.post_form(url, form_data).await,
+                PostBody::Multipart(form) => client.post_multipart(url, form).await,
+            }
+        };
+
         // Check if we're already in a tokio runtime
         if let Ok(handle) = Handle::try_current() {
             // We're in an async context - use block_in_place
-            tokio::task::block_in_place(|| {
-                handle.block_on(self.http_client.post_form(url, form_data))
-            })
-            .map_err(|e| e.to_string())
+            tokio::task::block_in_place(|| handle.block_on(post)).map_err(|e| e.to_string())
         } else {
             // No runtime - create one
             let rt = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
-            rt.block_on(self.http_client.post_form(url, form_data))
-                .map_err(|e| e.to_string())
+            rt.block_on(post).map_err(|e| e.to_string())
         }
     }
 
@@ -800,7 +1874,8 @@ impl Browser {
     fn load_page_without_history(&mut self, url: Url, html: &str) -> Result<(), String> {
         // Similar to load_page but doesn't update navigation
         let dom = HtmlParser::new().parse(html).map_err(|e| e.to_string())?;
-        let js_runtime = JsRuntime::with_dom(dom).ok();
+        self.user_style.refresh();
+        let js_runtime = self.page_runtime(dom);
 
         let shared_dom = match js_runtime.as_ref().and_then(|rt| rt.dom()) {
             Some(dom) => dom.clone(),
@@ -810,73 +1885,96 @@ impl Browser {
             }
         };
 
-        if let Some(ref rt) = js_runtime {
-            let _ = rt.execute_scripts();
-        }
-
-        let mut cascade = Cascade::new();
-        let css = "body { background-color: white; color: black; font-size: 16px; }";
-        if let Ok(stylesheet) = Stylesheet::parse(css) {
-            cascade.add_author_stylesheet(stylesheet);
+        if let Some(tab) = self.active_tab() {
+            tab.form_state.borrow_mut().clear();
         }
-
-        // Extract and add CSS from <style> tags in the document
-        {
-            let dom_ref = shared_dom.borrow();
-            let style_elements = dom_ref.get_elements_by_tag_name("style");
-            for style_id in style_elements {
-                if let Some(style_css) = extract_style_content(&dom_ref, style_id) {
-                    if let Ok(stylesheet) = Stylesheet::parse(&style_css) {
-                        cascade.add_author_stylesheet(stylesheet);
-                    }
-                }
+        let async_scripts = AsyncScripts::new(self.client_for(self.active_tab_id).clone());
+        if let Some(ref rt) = js_runtime {
+            if let Some(tab) = self.active_tab() {
+                attach_history(&tab.navigation, rt);
+                rt.set_form_controls(tab.form_state.clone());
             }
+            self.attach_storage(self.active_tab_id, &url, rt);
+            rt.set_viewport(self.config.width as f32, self.page_viewport_height());
+            rt.set_user_agent(self.user_agent());
+            rt.set_dialog_handler(self.dialogs.clone());
+            rt.set_cookie_access(Rc::new(PageCookies::new(self.client_for(self.active_tab_id).cookie_jar())));
+            rt.set_module_fetcher(Arc::new(async_scripts.module_fetch()));
+            rt.set_layout_provider(Rc::new(PageLayout::new(HISTORY_CSS, self.user_style.stylesheet())));
+            run_page_scripts(rt, &url, &async_scripts);
+            let _ = rt.dispatch_dom_content_loaded();
+            rt.take_style_dirty();
         }
 
+        // The browser's stylesheet and those of <style> elements
+        let cascade = page_cascade(&shared_dom.borrow(), HISTORY_CSS, self.user_style.stylesheet());
+
         // Calculate viewport (below chrome)
         let viewport_width = self.config.width as f32;
-        let viewport_height = self.config.height as f32 - CHROME_HEIGHT;
+        let viewport_height = self.page_viewport_height();
 
         let dom_ref = shared_dom.borrow();
-        let style_tree = StyleTree::build(&*dom_ref, &cascade, viewport_width, viewport_height);
+        let style_tree = Rc::new(StyleTree::build(&dom_ref, &cascade, viewport_width, viewport_height));
 
-        let body_ids = dom_ref.get_elements_by_tag_name("body");
-        let root_id = if !body_ids.is_empty() {
-            body_ids[0]
-        } else {
-            dom_ref.document_id()
-        };
+        let root_id = layout_root(&dom_ref);
 
-        let mut layout_tree = match build_layout_tree(&*dom_ref, &style_tree, root_id) {
-            Some(tree) => tree,
+        let mut layout = match RetainedLayout::build(&dom_ref, style_tree.clone(), root_id) {
+            Some(layout) => layout,
             None => return Err("Failed to build layout tree".into()),
         };
 
-        layout_block(
-            &mut layout_tree,
-            ContainingBlock::new(viewport_width, viewport_height),
-        );
+        let blocks_laid_out = layout.lay_out(None, &[], ContainingBlock::new(viewport_width, viewport_height));
+        let layout_tree = layout.tree();
 
         // Get content height for scrolling
         let content_height = layout_tree.dimensions.margin_box_height();
 
-        let display_list = build_display_list(&layout_tree);
-        let hit_regions = build_hit_regions(&layout_tree);
+        let display_list = build_display_list(layout_tree);
+        let hit_regions = build_hit_regions(layout_tree);
+        let box_models = measure_box_models(layout_tree);
+        let element_boxes = measure_elements(layout_tree);
         drop(dom_ref);
+        if let Some(ref rt) = js_runtime {
+            rt.set_element_boxes(element_boxes);
+            rt.set_content_height(content_height);
+            rt.set_computed_styles(style_tree);
+            if let Err(e) = rt.dispatch_load() {
+                log::warn!("Load dispatch failed: {}", e);
+            }
+        }
 
         // Store page state in active tab (without updating navigation history)
         let active_id = self.active_tab_id;
+        self.load_web_fonts(active_id, cascade.web_font_faces(), &url);
+        self.load_favicon(active_id, &url, &shared_dom.borrow());
+        let article = extract_article(&shared_dom.borrow(), &url);
         if let Some(tab) = self.tab_mut(active_id) {
+            tab.reader_original = None;
             tab.page = Some(PageState {
                 url,
-                display_list,
+                display_list: Rc::new(display_list),
                 js_runtime,
+                fetches: PageFetches::new(),
+                async_scripts,
                 hit_regions,
+                box_models,
                 scroll_y: 0.0,
+                smooth_scroll: None,
+                scrolled: false,
+                scroll_offsets: HashMap::new(),
                 content_height,
                 viewport_height,
                 dom: shared_dom.clone(),
                 cascade,
+                layout: Some(layout),
+                dirty_nodes: Vec::new(),
+                blocks_laid_out,
+                images: ImageCache::new(),
+                scaled_images: ScaledImages::new(),
+                find: PageFind::default(),
+                file_picker: FilePicker::new(),
+                source: html.to_string(),
+                article,
             });
         }
 
@@ -888,6 +1986,12 @@ impl Browser {
 
     /// Run the browser event loop
     pub fn run(&mut self) -> Result<(), String> {
+        if self.config.backend == BackendKind::Software {
+            // Without a window no events arrive, draw the page once
+            self.render();
+            self.save_local_storage(true);
+            return Ok(());
+        }
         self.last_frame = Instant::now();
 
         'running: loop {
@@ -896,11 +2000,27 @@ impl Browser {
             let delta_ms = now.duration_since(self.last_frame).as_secs_f32() * 1000.0;
             self.last_frame = now;
 
-            // Poll for navigation completion
+            // Poll for navigation completion and arriving fonts
             self.poll_navigation();
-
-            // Poll events
+            self.poll_fonts();
+            self.poll_favicons();
+            self.poll_downloads();
+            self.run_timers();
+            self.poll_fetches();
+            self.poll_scripts();
+            self.poll_history();
+            self.poll_form_submissions();
+            self.poll_file_choices();
+            self.save_local_storage(false);
+            self.save_session(false);
+            self.expire_toast(now);
+            self.expire_validation_bubble(now);
+            self.expire_status(now);
+            self.tick_error_retry(now);
+
+            // Poll events, any of them may change what is shown
             let events = poll_events();
+            self.needs_redraw |= !events.is_empty();
 
             for event in events {
                 match event {
@@ -914,6 +2034,10 @@ impl Browser {
                         }
                     }
 
+                    BrowserEvent::KeyUp { scancode, modifiers } => {
+                        self.handle_key_up(scancode, modifiers);
+                    }
+
                     BrowserEvent::TextInput { text } => {
                         self.handle_text_input(&text);
                     }
@@ -923,29 +2047,67 @@ impl Browser {
                             if self.handle_click(x, y) {
                                 break 'running;
                             }
+                        } else if button == MouseButton::Right {
+                            self.handle_right_click(x, y);
+                        } else if button == MouseButton::Middle {
+                            self.handle_middle_click(x, y);
+                        } else {
+                            self.handle_history_button(button);
+                        }
+                    }
+
+                    BrowserEvent::MouseUp { button } => {
+                        if button == MouseButton::Left {
+                            self.scrollbar_drag = None;
                         }
                     }
 
-                    BrowserEvent::MouseWheel { y, .. } => {
-                        // Scroll page (y > 0 = scroll up, y < 0 = scroll down)
-                        let delta = y as f32 * SCROLL_WHEEL_MULTIPLIER;
-                        self.handle_scroll(delta);
+                    BrowserEvent::MouseWheel { x, y } => {
+                        // Scroll what is under the cursor (y > 0 = scroll up, y < 0 = scroll down)
+                        let delta = y as f32 * self.config.scroll_step;
+                        let (mouse_x, mouse_y) = self.mouse_position;
+                        self.handle_wheel(mouse_x, mouse_y, delta);
+                        if x != 0 {
+                            self.handle_swipe(x, Instant::now());
+                        }
                     }
 
                     BrowserEvent::MouseMove { x, y } => {
                         log::trace!("MouseMove: x={}, y={}", x, y);
+                        self.mouse_position = (x, y);
                         self.handle_mouse_move(x, y);
                     }
 
                     BrowserEvent::WindowResize { width, height } => {
                         self.config.width = width;
                         self.config.height = height;
+                        // The whole window is painted at its new size
+                        self.painted_frame = None;
                         self.chrome.update_width(width as f32);
-                        self.relayout_page();
+                        // A reader page is made again for its column to fit
+                        if self.is_reader_mode() {
+                            self.reload_reader_page();
+                        } else {
+                            self.relayout_page();
+                        }
+                        self.dispatch_resize();
                     }
                 }
             }
 
+            // Scrolls scripts asked for and smooth scrolls move the page,
+            // then its scripts hear of it before the frame renders
+            self.poll_scrolls();
+            self.step_smooth_scroll(delta_ms);
+            self.run_scroll_events();
+
+            // Animation frame callbacks run right before the frame renders
+            self.run_animation_frames();
+
+            // Lay out what timers, fetches, event listeners and animation
+            // frames changed
+            self.restyle_pages();
+
             // Tick CSS transitions
             let transitions_active = self.transition_manager.tick(delta_ms);
 
@@ -954,8 +2116,13 @@ impl Browser {
                 self.relayout_page_with_animations(true);
             }
 
+            // Errors scripts logged since the last frame
+            self.update_error_badge();
+            self.follow_console();
+
             // Update loading animation
-            self.chrome.tick_loading();
+            self.chrome.tick_loading(delta_ms);
+            self.needs_redraw |= transitions_active || self.chrome.is_loading || self.chrome.loading_bar.is_visible();
 
             // Render
             self.render();
@@ -964,6 +2131,8 @@ impl Browser {
             std::thread::sleep(std::time::Duration::from_millis(16));
         }
 
+        self.save_local_storage(true);
+        self.save_session(true);
         Ok(())
     }
 
@@ -972,12 +2141,18 @@ impl Browser {
     /// Returns true if the browser should quit.
     fn handle_key(&mut self, scancode: u32, modifiers: Modifiers) -> bool {
         use crate::event::{
-            SCANCODE_BACKSPACE, SCANCODE_DOWN, SCANCODE_END, SCANCODE_ESCAPE, SCANCODE_F5,
-            SCANCODE_F12, SCANCODE_HOME, SCANCODE_L, SCANCODE_LEFT, SCANCODE_PAGEDOWN, SCANCODE_PAGEUP,
-            SCANCODE_Q, SCANCODE_R, SCANCODE_RETURN, SCANCODE_RIGHT, SCANCODE_T, SCANCODE_TAB,
-            SCANCODE_UP, SCANCODE_W,
+            SCANCODE_0, SCANCODE_BACKSPACE, SCANCODE_D, SCANCODE_DOWN, SCANCODE_END, SCANCODE_EQUALS, SCANCODE_ESCAPE,
+            SCANCODE_F, SCANCODE_F5, SCANCODE_F12, SCANCODE_HOME, SCANCODE_J, SCANCODE_L, SCANCODE_LEFT, SCANCODE_MINUS,
+            SCANCODE_N, SCANCODE_P, SCANCODE_PAGEDOWN, SCANCODE_PAGEUP, SCANCODE_Q, SCANCODE_R, SCANCODE_RETURN,
+            SCANCODE_RIGHT, SCANCODE_S, SCANCODE_SPACE, SCANCODE_T, SCANCODE_TAB, SCANCODE_UP, SCANCODE_W,
         };
 
+        // Escape dismisses the context menu before anything else
+        if scancode == SCANCODE_ESCAPE && self.context_menu.take().is_some() {
+            self.needs_redraw = true;
+            return false;
+        }
+
         // Handle keyboard shortcuts with modifiers first
         match (scancode, modifiers.ctrl, modifiers.alt, modifiers.shift) {
             // Ctrl+Q: Quit browser
@@ -993,6 +2168,19 @@ impl Browser {
                 return false;
             }
 
+            // Ctrl+Shift+N: New private tab
+            (SCANCODE_N, true, false, true) => {
+                self.new_private_tab();
+                self.focus_address_bar();
+                return false;
+            }
+
+            // Ctrl+Shift+T: Reopen the tab closed last
+            (SCANCODE_T, true, false, true) => {
+                self.reopen_closed_tab();
+                return false;
+            }
+
             // Ctrl+W: Close current tab
             (SCANCODE_W, true, false, false) => {
                 let active_id = self.active_tab_id;
@@ -1029,6 +2217,56 @@ impl Browser {
                 return false;
             }
 
+            // Ctrl+Alt+R: Enter or leave reader mode
+            (SCANCODE_R, true, true, false) => {
+                if self.chrome.reader_button.enabled {
+                    self.toggle_reader_mode();
+                }
+                return false;
+            }
+
+            // Ctrl+= and Ctrl+-: Larger and smaller reader text, Ctrl+0
+            // back to its default size
+            (SCANCODE_EQUALS | SCANCODE_MINUS | SCANCODE_0, true, false, _) if self.is_reader_mode() => {
+                let size = match scancode {
+                    SCANCODE_EQUALS => Some(self.reader_font_size + 2.0),
+                    SCANCODE_MINUS => Some(self.reader_font_size - 2.0),
+                    _ => None,
+                };
+                self.set_reader_font_size(size);
+                return false;
+            }
+
+            // Ctrl+D: Bookmark the page, or delete its bookmark
+            (SCANCODE_D, true, false, false) => {
+                self.toggle_bookmark();
+                return false;
+            }
+
+            // Ctrl+Shift+S and Ctrl+Shift+P: Save the page as an image or
+            // as a PDF
+            (SCANCODE_S | SCANCODE_P, true, false, true) => {
+                let format = if scancode == SCANCODE_S { CaptureFormat::Png } else { CaptureFormat::Pdf };
+                self.save_page_capture(format);
+                return false;
+            }
+
+            // Ctrl+J: Show the downloads
+            (SCANCODE_J, true, false, false) => {
+                self.show_downloads();
+                return false;
+            }
+
+            // Ctrl+F: Open or close the find bar
+            (SCANCODE_F, true, false, false) => {
+                if self.chrome.find_bar.open {
+                    self.close_find_bar();
+                } else {
+                    self.open_find_bar();
+                }
+                return false;
+            }
+
             // Alt+Left: Go back
             (SCANCODE_LEFT, false, true, _) => {
                 if self.chrome.back_button.enabled {
@@ -1052,6 +2290,21 @@ impl Browser {
             _ => {}
         }
 
+        // The page hears the other keys first, canceling one skips what it
+        // does in the browser, the character it types too
+        if let Some(key) = key_names(scancode, modifiers.shift) {
+            let printable = key.is_printable() && !modifiers.ctrl && !modifiers.alt;
+            let mut allowed = self.dispatch_key_to_page("keydown", &key, modifiers);
+            if allowed && printable {
+                allowed = self.dispatch_key_to_page("keypress", &key, modifiers);
+            }
+            // The text input for the key arrives after it
+            self.suppress_text_input = printable && !allowed;
+            if !allowed {
+                return false;
+            }
+        }
+
         // Handle non-modifier keys
         match scancode {
             // F5: Reload page
@@ -1061,7 +2314,13 @@ impl Browser {
 
             // F12: Toggle DevTools
             SCANCODE_F12 => {
+                if self.focus == FocusTarget::DevToolsConsole {
+                    self.blur_console();
+                }
                 self.devtools.toggle();
+                // The page gives DevTools the bottom of the window
+                self.relayout_page();
+                self.dispatch_resize();
             }
 
             // Escape: Stop loading or blur address bar (no longer quits)
@@ -1070,6 +2329,10 @@ impl Browser {
                     self.stop_loading();
                 } else if self.focus == FocusTarget::AddressBar {
                     self.blur_address_bar();
+                } else if self.focus == FocusTarget::FindBar {
+                    self.close_find_bar();
+                } else if self.focus == FocusTarget::DevToolsConsole {
+                    self.blur_console();
                 }
                 // No longer quits - use Ctrl+Q to quit
             }
@@ -1078,6 +2341,31 @@ impl Browser {
                 self.chrome.address_bar.delete_char();
             }
 
+            SCANCODE_BACKSPACE if self.focus == FocusTarget::FindBar => {
+                self.chrome.find_bar.delete_char();
+                self.update_find();
+            }
+
+            SCANCODE_BACKSPACE if self.focus == FocusTarget::DevToolsConsole => {
+                self.devtools.delete_console_char();
+                self.needs_redraw = true;
+            }
+
+            SCANCODE_RETURN if self.focus == FocusTarget::DevToolsConsole => {
+                self.run_console_input();
+            }
+
+            // Up and Down go through the code run in the console
+            SCANCODE_UP | SCANCODE_DOWN if self.focus == FocusTarget::DevToolsConsole => {
+                self.devtools.step_history(scancode == SCANCODE_UP);
+                self.needs_redraw = true;
+            }
+
+            // Enter: Next match, Shift+Enter: previous one
+            SCANCODE_RETURN if self.focus == FocusTarget::FindBar => {
+                self.step_find(modifiers.shift);
+            }
+
             SCANCODE_RETURN if self.focus == FocusTarget::AddressBar => {
                 // Navigate to URL in address bar
                 let url = self.chrome.address_bar.text.clone();
@@ -1092,46 +2380,94 @@ impl Browser {
             // Form input keyboard handling
             SCANCODE_BACKSPACE if matches!(self.focus, FocusTarget::FormInput(_)) => {
                 if let FocusTarget::FormInput(node_id) = self.focus {
-                    if let Some(tab) = self.tab_mut(self.active_tab_id) {
-                        if let Some(state) = tab.form_state.get_text_mut(node_id) {
-                            state.delete_char_before();
-                        }
-                    }
+                    self.edit_form_input(node_id, TextInputState::delete_char_before);
                 }
             }
 
             SCANCODE_LEFT if matches!(self.focus, FocusTarget::FormInput(_)) => {
                 if let FocusTarget::FormInput(node_id) = self.focus {
                     if let Some(tab) = self.tab_mut(self.active_tab_id) {
-                        if let Some(state) = tab.form_state.get_text_mut(node_id) {
+                        if let Some(state) = tab.form_state.borrow_mut().get_text_mut(node_id) {
                             state.move_cursor_left();
                         }
                     }
+                    self.scroll_text_area_to_cursor(node_id);
                 }
             }
 
             SCANCODE_RIGHT if matches!(self.focus, FocusTarget::FormInput(_)) => {
                 if let FocusTarget::FormInput(node_id) = self.focus {
                     if let Some(tab) = self.tab_mut(self.active_tab_id) {
-                        if let Some(state) = tab.form_state.get_text_mut(node_id) {
+                        if let Some(state) = tab.form_state.borrow_mut().get_text_mut(node_id) {
                             state.move_cursor_right();
                         }
                     }
+                    self.scroll_text_area_to_cursor(node_id);
                 }
             }
 
             SCANCODE_RETURN if matches!(self.focus, FocusTarget::FormInput(_)) => {
-                // TODO: Submit form or move to next input
-                self.blur_form_input();
+                if let FocusTarget::FormInput(node_id) = self.focus {
+                    // Text areas take the newline, other inputs lose focus
+                    let is_textarea = self.active_tab().and_then(|t| t.page.as_ref()).is_some_and(|page| {
+                        let dom = page.dom.borrow();
+                        dom.get(node_id)
+                            .and_then(|n| n.as_element())
+                            .is_some_and(|e| e.tag_name == "textarea")
+                    });
+                    if !is_textarea {
+                        // TODO: Submit form or move to next input
+                        self.blur_form_input();
+                    } else {
+                        self.edit_form_input(node_id, |state| state.insert_char('\n'));
+                    }
+                }
+            }
+
+            // In a text area Up and Down move between its lines, Home and
+            // End to the ends of one
+            SCANCODE_UP | SCANCODE_DOWN | SCANCODE_HOME | SCANCODE_END if self.focused_text_area().is_some() => {
+                if let Some(node_id) = self.focused_text_area() {
+                    self.move_text_area_cursor(node_id, |state, area| match scancode {
+                        SCANCODE_UP => state.move_cursor_up(area),
+                        SCANCODE_DOWN => state.move_cursor_down(area),
+                        SCANCODE_HOME => state.move_cursor_to_line_start(area),
+                        _ => state.move_cursor_to_line_end(area),
+                    });
+                }
+            }
+
+            // Tab: Focus the page's next link or control, Shift+Tab: the
+            // previous one
+            SCANCODE_TAB if !modifiers.ctrl && !modifiers.alt && self.page_has_focus() => {
+                self.move_focus(modifiers.shift);
+            }
+
+            // Enter follows the focused link or presses the focused button
+            SCANCODE_RETURN if matches!(self.focus, FocusTarget::Element(_)) => {
+                if let FocusTarget::Element(node_id) = self.focus {
+                    self.activate_element(node_id);
+                }
+            }
+
+            // Space checks the focused checkbox or radio button
+            SCANCODE_SPACE if matches!(self.focus, FocusTarget::Element(_)) => {
+                if let FocusTarget::Element(node_id) = self.focus {
+                    match self.form_control(node_id) {
+                        Some(FormElementInfo::Checkbox { node_id }) => self.toggle_checkbox(node_id),
+                        Some(FormElementInfo::Radio { node_id, name }) => self.select_radio(node_id, &name),
+                        _ => {}
+                    }
+                }
             }
 
             // Scroll keys (only when not editing address bar or form input)
             SCANCODE_UP if self.focus != FocusTarget::AddressBar => {
-                self.handle_scroll(SCROLL_LINE_HEIGHT);
+                self.handle_scroll(self.config.scroll_step);
             }
 
             SCANCODE_DOWN if self.focus != FocusTarget::AddressBar => {
-                self.handle_scroll(-SCROLL_LINE_HEIGHT);
+                self.handle_scroll(-self.config.scroll_step);
             }
 
             SCANCODE_PAGEUP if self.focus != FocusTarget::AddressBar => {
@@ -1168,55 +2504,247 @@ impl Browser {
         false
     }
 
+    /// Handle a released key, which only the page hears
+    fn handle_key_up(&mut self, scancode: u32, modifiers: Modifiers) {
+        if let Some(key) = key_names(scancode, modifiers.shift) {
+            self.dispatch_key_to_page("keyup", &key, modifiers);
+        }
+    }
+
+    /// Dispatch a keyboard event on the focused element, or else the
+    /// document, of the active page
+    ///
+    /// Returns false when a listener canceled it. Keys typed into the
+    /// address bar, find bar or DevTools console are not the page's.
+    fn dispatch_key_to_page(&self, event_type: &str, key: &KeyNames, modifiers: Modifiers) -> bool {
+        if !self.page_has_focus() {
+            return true;
+        }
+        let Some(page) = self.active_tab().and_then(|tab| tab.page.as_ref()) else {
+            return true;
+        };
+        let Some(rt) = page.js_runtime.as_ref() else {
+            return true;
+        };
+        let target = match self.focused_node() {
+            Some(node_id) => node_id,
+            None => page.dom.borrow().document_id(),
+        };
+        let event = KeyEvent {
+            key: key.key.clone(),
+            code: key.code.clone(),
+            ctrl_key: modifiers.ctrl,
+            shift_key: modifiers.shift,
+            alt_key: modifiers.alt,
+        };
+        rt.dispatch_key_event(target.0, event_type, &event).unwrap_or_else(|e| {
+            log::warn!("Key dispatch failed: {}", e);
+            true
+        })
+    }
+
+    /// Edit the text of a form input, dispatching `input` to the page when
+    /// the edit changed it
+    fn edit_form_input(&mut self, node_id: NodeId, edit: impl FnOnce(&mut TextInputState)) {
+        let edited = self.tab_mut(self.active_tab_id).is_some_and(|tab| {
+            let mut form_state = tab.form_state.borrow_mut();
+            form_state.get_text_mut(node_id).is_some_and(|state| {
+                let before = state.value.clone();
+                edit(state);
+                state.value != before
+            })
+        });
+        self.scroll_text_area_to_cursor(node_id);
+        if edited {
+            self.dispatch_form_event(node_id, "input");
+        }
+    }
+
+    /// Where a text area of the active page is painted, and the box it
+    /// shows its text in
+    fn text_area_box(&self, node_id: NodeId) -> Option<(Rect, TextAreaBox)> {
+        use gugalanna_render::PaintCommand;
+
+        let page = self.active_tab()?.page.as_ref()?;
+        page.display_list.commands.iter().find_map(|command| match command {
+            PaintCommand::DrawTextArea { node_id: id, rect, font_size, line_height, .. } if *id == node_id => {
+                Some((*rect, TextAreaBox::new(rect, *font_size, *line_height)))
+            }
+            _ => None,
+        })
+    }
+
+    /// The text area being edited, if one is
+    fn focused_text_area(&self) -> Option<NodeId> {
+        match self.focus {
+            FocusTarget::FormInput(node_id) if self.text_area_box(node_id).is_some() => Some(node_id),
+            _ => None,
+        }
+    }
+
+    /// Move the cursor of a text area by its lines, scrolling it into view
+    fn move_text_area_cursor(&mut self, node_id: NodeId, step: impl FnOnce(&mut TextInputState, &TextAreaBox)) {
+        let Some((_, area)) = self.text_area_box(node_id) else {
+            return;
+        };
+        if let Some(tab) = self.tab_mut(self.active_tab_id) {
+            if let Some(state) = tab.form_state.borrow_mut().get_text_mut(node_id) {
+                step(state, &area);
+                state.scroll_to_cursor(&area);
+            }
+        }
+    }
+
+    /// Scroll a text area so the line its cursor is on shows
+    fn scroll_text_area_to_cursor(&mut self, node_id: NodeId) {
+        self.move_text_area_cursor(node_id, |_, _| {});
+    }
+
+    /// Dispatch an event a form or its controls fire, as `input` or
+    /// `submit`, to the page's scripts
+    ///
+    /// Returns false when a listener canceled it.
+    fn dispatch_form_event(&self, node_id: NodeId, event_type: &str) -> bool {
+        let Some(rt) = self.active_tab().and_then(|t| t.page.as_ref()).and_then(|p| p.js_runtime.as_ref()) else {
+            return true;
+        };
+        rt.dispatch_event(node_id.0, event_type).unwrap_or_else(|e| {
+            log::warn!("Form event dispatch failed: {}", e);
+            true
+        })
+    }
+
     /// Handle text input (for address bar)
     fn handle_text_input(&mut self, text: &str) {
+        if std::mem::take(&mut self.suppress_text_input) {
+            return;
+        }
         match self.focus {
             FocusTarget::AddressBar => {
                 for c in text.chars() {
                     self.chrome.address_bar.insert_char(c);
                 }
             }
-            FocusTarget::FormInput(node_id) => {
-                // Insert text into the form input
-                if let Some(tab) = self.tab_mut(self.active_tab_id) {
-                    if let Some(state) = tab.form_state.get_text_mut(node_id) {
-                        state.insert_text(text);
-                    }
+            FocusTarget::FindBar => {
+                for c in text.chars() {
+                    self.chrome.find_bar.insert_char(c);
                 }
+                self.update_find();
+            }
+            FocusTarget::FormInput(node_id) => {
+                self.edit_form_input(node_id, |state| state.insert_text(text));
+            }
+            FocusTarget::DevToolsConsole => {
+                self.devtools.insert_console_text(text);
+                self.needs_redraw = true;
             }
             _ => {}
         }
     }
 
-    /// Handle scroll by delta (positive = scroll up/show content above, negative = scroll down)
-    fn handle_scroll(&mut self, delta: f32) {
+    /// Handle a wheel scroll at a window position
+    ///
+    /// The innermost scroll container under the cursor that can still move
+    /// that way is scrolled; once it is at its limit the page scrolls.
+    fn handle_wheel(&mut self, x: f32, y: f32, delta: f32) {
+        // DevTools scroll their own panel
+        if self.devtools.open && y >= self.config.height as f32 - DEVTOOLS_HEIGHT {
+            self.devtools.scroll(-delta);
+            self.needs_redraw = true;
+            return;
+        }
         let active_id = self.active_tab_id;
-        if let Some(tab) = self.tab_mut(active_id) {
-            if let Some(ref mut page) = tab.page {
-                let max_scroll = (page.content_height - page.viewport_height).max(0.0);
-                page.scroll_y = (page.scroll_y - delta).clamp(0.0, max_scroll);
+        if y >= CHROME_HEIGHT {
+            if let Some(page) = self.tab_mut(active_id).and_then(|t| t.page.as_mut()) {
+                let content_y = (y - CHROME_HEIGHT) + page.scroll_y;
+                let target = scroll_target(&page.hit_regions, &page.scroll_offsets, x, content_y, page.scroll_y, delta);
+                if let Some((scroller, range)) = target {
+                    let offset = page.scroll_offsets.entry(scroller).or_insert(0.0);
+                    let new_offset = (*offset - delta).clamp(0.0, range);
+                    let moved = new_offset - *offset;
+                    *offset = new_offset;
+                    scroll_hit_regions(&mut page.hit_regions, scroller, moved);
+                    return;
+                }
             }
         }
+        self.handle_scroll(delta);
     }
 
-    /// Scroll to the top of the page
-    fn scroll_to_top(&mut self) {
-        let active_id = self.active_tab_id;
-        if let Some(tab) = self.tab_mut(active_id) {
-            if let Some(ref mut page) = tab.page {
-                page.scroll_y = 0.0;
-            }
+    /// Grab the scrollbar under a click, the page's first as it is drawn on
+    /// top. A click on the track moves the thumb's middle there first.
+    fn handle_scrollbar_press(&mut self, x: f32, y: f32) -> bool {
+        let width = self.config.width as f32;
+        let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) else {
+            return false;
+        };
+        if y < CHROME_HEIGHT {
+            return false;
         }
+        let Some((scroller, bar)) = page.scrollbars(width).into_iter().rev().find(|(_, bar)| bar.track.contains(x, y)) else {
+            return false;
+        };
+
+        let grab_y = if bar.thumb.contains(x, y) {
+            y - bar.thumb.y
+        } else {
+            let grab_y = bar.thumb.height / 2.0;
+            page.scroll_to(scroller, bar.scroll_for_thumb(y - grab_y));
+            grab_y
+        };
+        self.scrollbar_drag = Some(ScrollbarDrag { scroller, grab_y });
+        true
     }
 
-    /// Scroll to the bottom of the page
-    fn scroll_to_bottom(&mut self) {
-        let active_id = self.active_tab_id;
-        if let Some(tab) = self.tab_mut(active_id) {
-            if let Some(ref mut page) = tab.page {
-                let max_scroll = (page.content_height - page.viewport_height).max(0.0);
-                page.scroll_y = max_scroll;
-            }
+    /// Scroll with a dragged scrollbar thumb as the mouse moves to `y`
+    fn drag_scrollbar(&mut self, drag: ScrollbarDrag, y: f32) {
+        let width = self.config.width as f32;
+        let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) else {
+            return;
+        };
+        let bar = page.scrollbars(width).into_iter().find(|(scroller, _)| *scroller == drag.scroller);
+        match bar {
+            Some((_, bar)) => page.scroll_to(drag.scroller, bar.scroll_for_thumb(y - drag.grab_y)),
+            // The content shrank to fit while dragging
+            None => self.scrollbar_drag = None,
+        }
+    }
+
+    /// Handle scroll by delta (positive = scroll up/show content above, negative = scroll down)
+    ///
+    /// A smooth scroll under way goes `delta` further.
+    fn handle_scroll(&mut self, delta: f32) {
+        let smooth = self.smooth_scrolling();
+        if let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) {
+            page.scroll_toward(page.scroll_target() - delta, smooth);
+        }
+    }
+
+    /// Scroll to the top of the page
+    fn scroll_to_top(&mut self) {
+        let smooth = self.smooth_scrolling();
+        if let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) {
+            page.scroll_toward(0.0, smooth);
+        }
+    }
+
+    /// Scroll to the bottom of the page
+    fn scroll_to_bottom(&mut self) {
+        let smooth = self.smooth_scrolling();
+        if let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) {
+            page.scroll_toward(page.content_height, smooth);
+        }
+    }
+
+    /// Whether scrolling is animated, which takes the frame loop of a window
+    fn smooth_scrolling(&self) -> bool {
+        self.config.smooth_scrolling && self.config.backend == BackendKind::Sdl
+    }
+
+    /// Move the smooth scroll of the active page on by `delta_ms`
+    fn step_smooth_scroll(&mut self, delta_ms: f32) {
+        if let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) {
+            self.needs_redraw |= page.step_smooth_scroll(delta_ms);
         }
     }
 
@@ -1229,19 +2757,15 @@ impl Browser {
         let active_id = self.active_tab_id;
         if let Some(tab) = self.tab_mut(active_id) {
             if let Some(ref mut page) = tab.page {
-                let dom_ref = page.dom.borrow();
-
                 // Find element by ID
-                if let Some(element_id) = dom_ref.get_element_by_id(fragment) {
+                let element_id = page.dom.borrow().get_element_by_id(fragment);
+                if let Some(element_id) = element_id {
                     // Find hit region for this element to get Y position
-                    for region in &page.hit_regions {
-                        if region.node_id == element_id.0 {
-                            // Scroll to put element at top of viewport
-                            let max_scroll = (page.content_height - page.viewport_height).max(0.0);
-                            page.scroll_y = region.y.clamp(0.0, max_scroll);
-                            log::debug!("Scrolling to fragment '{}' at y={}", fragment, region.y);
-                            break;
-                        }
+                    let region_y = page.hit_regions.iter().find(|r| r.node_id == element_id.0).map(|r| r.y);
+                    if let Some(y) = region_y {
+                        // Scroll to put element at top of viewport
+                        page.scroll_to(None, y);
+                        log::debug!("Scrolling to fragment '{}' at y={}", fragment, y);
                     }
                 } else {
                     log::debug!("Fragment '{}' not found in document", fragment);
@@ -1257,6 +2781,11 @@ impl Browser {
         let mut results: Vec<(TabId, NavigationResult)> = Vec::new();
 
         for tab in &mut self.tabs {
+            if let Some(rx) = &mut tab.nav_progress {
+                while let Ok(milestone) = rx.try_recv() {
+                    tab.loading_state.reach(milestone);
+                }
+            }
             if let Some(ref mut rx) = tab.nav_receiver {
                 if let Ok(result) = rx.try_recv() {
                     results.push((tab.id, result));
@@ -1264,11 +2793,20 @@ impl Browser {
             }
         }
 
+        // Show how far the active tab's page has come
+        if let Some(tab) = self.active_tab().filter(|tab| tab.is_loading()) {
+            let progress = tab.loading_state.progress();
+            self.chrome.loading_bar.set_progress(progress);
+        }
+
         // Process results
         for (tab_id, result) in results {
+            self.needs_redraw = true;
+
             // Clear loading state for this tab
             if let Some(tab) = self.tab_mut(tab_id) {
                 tab.nav_receiver = None;
+                tab.nav_progress = None;
                 tab.nav_cancel = None;
                 tab.loading_state = LoadingState::Idle;
             }
@@ -1281,13 +2819,19 @@ impl Browser {
             match result {
                 NavigationResult::Success { url, html } => {
                     log::info!("Navigation complete for tab {}: {}", tab_id.0, url);
+                    if self.auto_retried.as_ref().is_some_and(|(id, _)| *id == tab_id) {
+                        self.auto_retried = None;
+                    }
 
                     // Load the page into the specific tab
                     if tab_id == self.active_tab_id {
                         // Active tab - use normal load
+                        self.chrome.loading_bar.set_progress(LoadMilestone::Parsed.progress());
                         if let Err(e) = self.load_page(url, &html) {
                             log::error!("Failed to load page: {}", e);
                         }
+                        // Painted with the next frame
+                        self.chrome.loading_bar.finish();
                     } else {
                         // Background tab - load directly into tab
                         if let Err(e) = self.load_page_into_tab(tab_id, url, &html) {
@@ -1295,6 +2839,18 @@ impl Browser {
                         }
                     }
                 }
+                NavigationResult::Download { response } => {
+                    // The tab keeps its page
+                    if tab_id == self.active_tab_id {
+                        self.sync_chrome_with_tabs();
+                        self.chrome.loading_bar.clear();
+                    }
+                    let url = response.url.clone();
+                    match self.downloads.start_response(response) {
+                        Ok(_) => self.show_downloads(),
+                        Err(e) => log::error!("Downloading {} failed: {}", url, e),
+                    }
+                }
                 NavigationResult::Failed { url, error } => {
                     log::error!("Navigation failed for tab {} to {}: {:?}", tab_id.0, url, error);
 
@@ -1305,19 +2861,523 @@ impl Browser {
                         };
                     }
 
-                    // Show error page if this is the active tab
+                    // Show error page if this is the active tab, counting
+                    // down to a retry when the network may be back soon
                     if tab_id == self.active_tab_id {
+                        let retried = self.auto_retried.as_ref() == Some(&(tab_id, url.clone()));
+                        self.error_retry = (error.retries_automatically() && !retried).then(|| ErrorRetry {
+                            tab_id,
+                            at: Instant::now() + ERROR_RETRY_DELAY,
+                            seconds_left: ERROR_RETRY_DELAY.as_secs(),
+                        });
                         self.show_error_page(&url, &error);
+                        self.chrome.loading_bar.finish();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Start downloading the web fonts a tab's page declares, replacing
+    /// the downloads of its previous page
+    ///
+    /// Faces already registered by an earlier page are reused.
+    fn load_web_fonts(&mut self, tab_id: TabId, faces: Vec<WebFontFace>, base_url: &Url) {
+        let registry = self.font_registry.clone();
+        let client = self.client_for(tab_id).clone();
+        let tab = match self.tab_mut(tab_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        if let Some(cancel) = tab.font_cancel.take() {
+            cancel.cancel();
+        }
+        tab.font_receiver = None;
+
+        let faces: Vec<_> = faces
+            .into_iter()
+            .filter(|f| !registry.contains(&f.family, FontFace::new(f.weight, f.style)))
+            .collect();
+        if faces.is_empty() {
+            return;
+        }
+        log::info!("Loading {} web fonts", faces.len());
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let cancel_token = tokio_util::sync::CancellationToken::new();
+        tab.font_receiver = Some(rx);
+        tab.font_cancel = Some(cancel_token.clone());
+        tokio::spawn(fetch_web_fonts(client, faces, base_url.clone(), cancel_token, tx));
+    }
+
+    /// Show the icon of a tab's page, from the cache or else fetched in the
+    /// background, replacing the fetch for its previous page
+    ///
+    /// Without a runtime to fetch on, as when a page is drawn once, pages
+    /// get the icons already cached only.
+    fn load_favicon(&mut self, tab_id: TabId, url: &Url, dom: &DomTree) {
+        let cached = self.favicons.get(url);
+        let client = self.client_for(tab_id).clone();
+        let Some(tab) = self.tab_mut(tab_id) else {
+            return;
+        };
+        tab.favicon_receiver = None;
+        tab.favicon = cached;
+        if tab.favicon.is_some() || tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+        let Some(icon_url) = favicon_url(dom, url) else {
+            return;
+        };
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tab.favicon_receiver = Some(rx);
+        tokio::spawn(async move {
+            // The tab may be gone, or on another page
+            let _ = tx.send(fetch_favicon(&client, icon_url).await);
+        });
+    }
+
+    /// Show the icons that arrived, and cache them for their sites
+    fn poll_favicons(&mut self) {
+        let mut changed = false;
+        for tab in &mut self.tabs {
+            let Some(rx) = tab.favicon_receiver.as_mut() else {
+                continue;
+            };
+            let result = match rx.try_recv() {
+                Ok(result) => result,
+                Err(tokio::sync::oneshot::error::TryRecvError::Empty) => continue,
+                Err(tokio::sync::oneshot::error::TryRecvError::Closed) => Err("Fetch abandoned".to_string()),
+            };
+            tab.favicon_receiver = None;
+            let Some(url) = tab.page.as_ref().map(|page| page.url.clone()) else {
+                continue;
+            };
+            match result {
+                Ok(icon) => {
+                    // Private tabs leave nothing behind for other tabs to see
+                    if !tab.private {
+                        self.favicons.insert(&url, icon.clone());
+                    }
+                    tab.favicon = Some(icon);
+                    changed = true;
+                }
+                Err(e) => log::debug!("No favicon for {}: {}", url, e),
+            }
+        }
+        if changed {
+            self.sync_chrome_with_tabs();
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Register the web fonts that arrived and lay out the pages using them
+    /// again
+    fn poll_fonts(&mut self) {
+        let mut loaded: Vec<(TabId, LoadedFont)> = Vec::new();
+        for tab in &mut self.tabs {
+            if let Some(ref mut rx) = tab.font_receiver {
+                while let Ok(font) = rx.try_recv() {
+                    loaded.push((tab.id, font));
+                }
+            }
+        }
+
+        let mut relayout = false;
+        for (tab_id, font) in loaded {
+            if let Err(e) = self.font_registry.register(&font.family, font.face, &font.data) {
+                log::warn!("Failed to register font '{}': {}", font.family, e);
+                continue;
+            }
+            log::info!("Font '{}' loaded for tab {}", font.family, tab_id.0);
+
+            if tab_id == self.active_tab_id {
+                relayout = true;
+            } else if let Some(tab) = self.tab_mut(tab_id) {
+                tab.fonts_changed = true;
+            }
+        }
+
+        if relayout {
+            self.relayout_page();
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Save localStorage once it has gone unchanged for
+    /// `LOCAL_STORAGE_SAVE_DELAY`, or right away with `now` when it changed
+    fn save_local_storage(&mut self, now: bool) {
+        if self.local_storage.take_changed() {
+            self.local_storage_changed = Some(Instant::now());
+        }
+        let due = self
+            .local_storage_changed
+            .is_some_and(|changed| now || changed.elapsed() >= LOCAL_STORAGE_SAVE_DELAY);
+        if !due {
+            return;
+        }
+        self.local_storage_changed = None;
+        if let Some(path) = &self.local_storage_path {
+            if let Err(e) = self.local_storage.save(path) {
+                log::warn!("Failed to save localStorage to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Reopen the tabs of a saved session in place of the first, empty one,
+    /// loading the page of the tab shown
+    fn restore_session(&mut self, session: Session) {
+        let mut tabs: Vec<TabState> = Vec::new();
+        let mut active = None;
+        for (i, saved) in session.tabs.iter().enumerate() {
+            let Some(tab) = TabState::restored(TabId(self.next_tab_id), saved) else {
+                continue;
+            };
+            self.next_tab_id += 1;
+            if i == session.active || active.is_none() {
+                active = Some(tab.id);
+            }
+            tabs.push(tab);
+        }
+        let Some(active) = active else {
+            return;
+        };
+        log::info!("Restoring {} tabs", tabs.len());
+        self.tabs = tabs;
+        self.session = session;
+        self.switch_to_tab(active);
+    }
+
+    /// The open tabs as they are saved in the session
+    fn current_session(&self) -> Session {
+        let mut session = Session::default();
+        for tab in &self.tabs {
+            if let Some(saved) = tab.session_tab() {
+                if tab.id == self.active_tab_id {
+                    session.active = session.tabs.len();
+                }
+                session.tabs.push(saved);
+            }
+        }
+        session
+    }
+
+    /// Save the open tabs once they have gone unchanged for
+    /// `SESSION_SAVE_DELAY`, or right away with `now` when they changed
+    fn save_session(&mut self, now: bool) {
+        if self.session_path.is_none() {
+            return;
+        }
+        let session = self.current_session();
+        if session != self.session {
+            self.session = session;
+            self.session_changed = Some(Instant::now());
+        }
+        let due = self.session_changed.is_some_and(|changed| now || changed.elapsed() >= SESSION_SAVE_DELAY);
+        if !due {
+            return;
+        }
+        self.session_changed = None;
+        if let Some(path) = &self.session_path {
+            if let Err(e) = self.session.save(path) {
+                log::warn!("Failed to save the session to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Give a page's scripts the storage of its origin, kept in memory for
+    /// the page alone when its origin is opaque, as for file: and about:
+    fn attach_storage(&mut self, tab_id: TabId, url: &Url, rt: &JsRuntime) {
+        let origin = url.origin();
+        let (local, session) = if origin.is_tuple() {
+            let origin = origin.ascii_serialization();
+            let session = match self.tab_mut(tab_id) {
+                Some(tab) => tab.session_storage.area(&origin),
+                None => Default::default(),
+            };
+            let local = match &mut self.private_state {
+                Some(state) if self.tabs.iter().any(|tab| tab.id == tab_id && tab.private) => {
+                    state.local_storage.area(&origin)
+                }
+                _ => self.local_storage.area(&origin),
+            };
+            (local, session)
+        } else {
+            (Default::default(), Default::default())
+        };
+        if let Err(e) = rt.set_storage(local, session) {
+            log::warn!("Failed to set up storage: {}", e);
+        }
+    }
+
+    /// Run the page timers that are due, those of background tabs only
+    /// every `BACKGROUND_TIMER_INTERVAL`
+    fn run_timers(&mut self) {
+        let background = self.background_timers_run.elapsed() >= BACKGROUND_TIMER_INTERVAL;
+        if background {
+            self.background_timers_run = Instant::now();
+        }
+
+        let active_id = self.active_tab_id;
+        for tab in &mut self.tabs {
+            if tab.id != active_id && !background {
+                continue;
+            }
+            let Some(rt) = tab.page.as_ref().and_then(|page| page.js_runtime.as_ref()) else {
+                continue;
+            };
+            if let Err(e) = rt.run_due_timers(rt.elapsed()) {
+                log::warn!("Timers failed in tab {}: {}", tab.id.0, e);
+            }
+        }
+    }
+
+    /// Run the requestAnimationFrame callbacks of the frame being
+    /// rendered, those of background tabs only every
+    /// `BACKGROUND_TIMER_INTERVAL`
+    fn run_animation_frames(&mut self) {
+        let background = self.background_frames_run.elapsed() >= BACKGROUND_TIMER_INTERVAL;
+        if background {
+            self.background_frames_run = Instant::now();
+        }
+
+        let active_id = self.active_tab_id;
+        for tab in &mut self.tabs {
+            if tab.id != active_id && !background {
+                continue;
+            }
+            let Some(rt) = tab.page.as_ref().and_then(|page| page.js_runtime.as_ref()) else {
+                continue;
+            };
+            if let Err(e) = rt.run_animation_frames(rt.elapsed()) {
+                log::warn!("Animation frames failed in tab {}: {}", tab.id.0, e);
+            }
+        }
+    }
+
+    /// Send the requests page scripts made and hand the responses that
+    /// arrived to the scripts waiting on them
+    fn poll_fetches(&mut self) {
+        for tab in &mut self.tabs {
+            let client = match &self.private_state {
+                Some(state) if tab.private => &state.client,
+                _ => &self.http_client,
+            };
+            let Some(page) = tab.page.as_mut() else {
+                continue;
+            };
+            let Some(rt) = page.js_runtime.as_ref() else {
+                continue;
+            };
+            for request in rt.take_fetch_requests() {
+                page.fetches.start(client, &page.url, request);
+            }
+
+            for (id, result) in page.fetches.finished() {
+                if let Err(e) = rt.complete_fetch(id, result) {
+                    log::warn!("Fetch completion failed in tab {}: {}", tab.id.0, e);
+                }
+            }
+        }
+    }
+
+    /// Run the async scripts of pages that arrived
+    fn poll_scripts(&mut self) {
+        for tab in &mut self.tabs {
+            let Some(page) = tab.page.as_mut() else {
+                continue;
+            };
+            let Some(rt) = page.js_runtime.as_ref() else {
+                continue;
+            };
+            for script in page.async_scripts.arrived() {
+                if let Some(e) = run_fetched(rt, script).error {
+                    log::warn!("Script error in tab {}: {}", tab.id.0, e);
+                }
+            }
+        }
+    }
+
+    /// Send the forms scripts of the active page submitted with submit(),
+    /// the last one as each navigates away, and show the message of the
+    /// last control they reported invalid with reportValidity()
+    ///
+    /// Submissions and reports from background tabs are dropped.
+    fn poll_form_submissions(&mut self) {
+        let active_id = self.active_tab_id;
+        let mut submitted = None;
+        let mut reported = None;
+        for tab in &self.tabs {
+            let Some(rt) = tab.page.as_ref().and_then(|page| page.js_runtime.as_ref()) else {
+                continue;
+            };
+            let forms = rt.take_form_submissions();
+            let controls = rt.take_validity_reports();
+            if tab.id == active_id {
+                submitted = forms.last().copied();
+                reported = controls.last().copied();
+            }
+        }
+        let invalid = reported.and_then(|node_id| {
+            let tab = self.active_tab()?;
+            let dom = tab.page.as_ref()?.dom.borrow();
+            let message = validation_message(&dom, &tab.form_state.borrow(), node_id)?;
+            tab.form_state.borrow_mut().mark_user_invalid(node_id);
+            Some((node_id, message))
+        });
+        if let Some((node_id, message)) = invalid {
+            self.report_invalid(node_id, message);
+        }
+        if let Some(form_id) = submitted {
+            self.send_form(form_id);
+        }
+    }
+
+    /// Apply the changes page scripts made to their tab's history
+    ///
+    /// Pushed and replaced entries change the URL shown without loading
+    /// it. Only the active tab moves through its history.
+    fn poll_history(&mut self) {
+        let active_id = self.active_tab_id;
+        let mut active_changed = false;
+        let mut moves = Vec::new();
+        for tab in &mut self.tabs {
+            let Some(page) = tab.page.as_mut() else {
+                continue;
+            };
+            let Some(rt) = page.js_runtime.as_ref() else {
+                continue;
+            };
+            for change in rt.take_history_changes() {
+                active_changed |= tab.id == active_id;
+                match change {
+                    HistoryChange::Push { url, state } => {
+                        page.url = url.clone();
+                        tab.navigation.push_state(url, state);
+                    }
+                    HistoryChange::Replace { url, state } => {
+                        page.url = url.clone();
+                        tab.navigation.replace_state(url, state);
                     }
+                    HistoryChange::Go(delta) if tab.id == active_id => moves.push(delta),
+                    HistoryChange::Go(_) => {}
                 }
             }
         }
+
+        if active_changed {
+            self.sync_chrome_with_tabs();
+        }
+        for delta in moves {
+            // go(0) reloads the page
+            let result = if delta == 0 {
+                self.reload_page();
+                Ok(())
+            } else {
+                self.traverse_history(delta)
+            };
+            if let Err(e) = result {
+                log::warn!("History traversal failed: {}", e);
+            }
+        }
+    }
+
+    /// Compute the style of pages again when scripts changed their DOM,
+    /// laying out the active page now and the others when switched to
+    ///
+    /// There is no incremental restyle yet, a page with any node in its
+    /// mutation journal has its whole style tree rebuilt, but only the
+    /// boxes that changed are laid out again. Scripts that only changed
+    /// form controls get the active page painted again.
+    fn restyle_pages(&mut self) {
+        let active_id = self.active_tab_id;
+        let mut relayout = false;
+        for tab in &mut self.tabs {
+            let Some(page) = tab.page.as_mut() else {
+                continue;
+            };
+            let Some(rt) = page.js_runtime.as_ref() else {
+                continue;
+            };
+            if rt.take_paint_dirty() && tab.id == active_id {
+                self.needs_redraw = true;
+            }
+            let dirty = rt.take_style_dirty();
+            if dirty.is_empty() {
+                continue;
+            }
+            page.dirty_nodes.extend(dirty);
+            if tab.id == active_id {
+                relayout = true;
+            } else {
+                tab.dom_changed = true;
+            }
+        }
+
+        if relayout {
+            self.relayout_page();
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Apply the scrolls page scripts asked for
+    ///
+    /// Background pages jump to where a smooth scroll would end.
+    fn poll_scrolls(&mut self) {
+        let active_id = self.active_tab_id;
+        for tab in &mut self.tabs {
+            let Some(page) = tab.page.as_mut() else {
+                continue;
+            };
+            let requests = page.js_runtime.as_ref().map(|rt| rt.take_scroll_requests()).unwrap_or_default();
+            for request in requests {
+                page.scroll_toward(request.top, request.smooth && tab.id == active_id);
+            }
+            if tab.id == active_id {
+                self.needs_redraw |= page.scrolled;
+            }
+        }
+    }
+
+    /// Dispatch `scroll` on the window of every page that scrolled since
+    /// the last frame
+    fn run_scroll_events(&mut self) {
+        for tab in &mut self.tabs {
+            let Some(page) = tab.page.as_mut() else {
+                continue;
+            };
+            if !std::mem::take(&mut page.scrolled) {
+                continue;
+            }
+            let Some(rt) = page.js_runtime.as_ref() else {
+                continue;
+            };
+            if let Err(e) = rt.dispatch_window_event("scroll") {
+                log::warn!("Scroll dispatch failed in tab {}: {}", tab.id.0, e);
+            }
+        }
+    }
+
+    /// Tell the scripts of every page the window was resized
+    fn dispatch_resize(&mut self) {
+        let (width, height) = (self.config.width as f32, self.page_viewport_height());
+        for tab in &self.tabs {
+            let Some(rt) = tab.page.as_ref().and_then(|page| page.js_runtime.as_ref()) else {
+                continue;
+            };
+            rt.set_viewport(width, height);
+            if let Err(e) = rt.dispatch_window_event("resize") {
+                log::warn!("Resize dispatch failed in tab {}: {}", tab.id.0, e);
+            }
+        }
     }
 
     /// Load a page into a specific tab (for background tab loading)
     fn load_page_into_tab(&mut self, tab_id: TabId, url: Url, html: &str) -> Result<(), String> {
         let dom = HtmlParser::new().parse(html).map_err(|e| e.to_string())?;
-        let js_runtime = JsRuntime::with_dom(dom).ok();
+        self.user_style.refresh();
+        let js_runtime = self.page_runtime(dom);
 
         let shared_dom = match js_runtime.as_ref().and_then(|rt| rt.dom()) {
             Some(dom) => dom.clone(),
@@ -1327,77 +3387,94 @@ impl Browser {
             }
         };
 
-        if let Some(ref rt) = js_runtime {
-            let _ = rt.execute_scripts();
+        if let Some(tab) = self.tab_mut(tab_id) {
+            tab.navigation.navigate_to(url.clone());
+            tab.form_state.borrow_mut().clear();
+            if let Some(ref rt) = js_runtime {
+                attach_history(&tab.navigation, rt);
+                rt.set_form_controls(tab.form_state.clone());
+            }
         }
 
-        let mut cascade = Cascade::new();
-        let css = r#"
-            body { background-color: white; color: black; font-size: 16px; }
-            h1, h2, h3, h4, h5, h6, p, div { display: block; }
-            h1 { font-size: 32px; margin-top: 20px; margin-bottom: 10px; }
-            h2 { font-size: 24px; margin-top: 18px; margin-bottom: 8px; }
-            h3 { font-size: 18px; margin-top: 16px; margin-bottom: 6px; }
-            p { margin-top: 10px; margin-bottom: 10px; }
-        "#;
-        if let Ok(stylesheet) = Stylesheet::parse(css) {
-            cascade.add_author_stylesheet(stylesheet);
+        let async_scripts = AsyncScripts::new(self.client_for(tab_id).clone());
+        if let Some(ref rt) = js_runtime {
+            self.attach_storage(tab_id, &url, rt);
+            rt.set_viewport(self.config.width as f32, self.page_viewport_height());
+            rt.set_user_agent(self.user_agent());
+            rt.set_dialog_handler(self.dialogs.clone());
+            rt.set_cookie_access(Rc::new(PageCookies::new(self.client_for(tab_id).cookie_jar())));
+            rt.set_module_fetcher(Arc::new(async_scripts.module_fetch()));
+            rt.set_layout_provider(Rc::new(PageLayout::new(DEFAULT_CSS, self.user_style.stylesheet())));
+            run_page_scripts(rt, &url, &async_scripts);
+            let _ = rt.dispatch_dom_content_loaded();
+            rt.take_style_dirty();
         }
 
-        // Extract and add CSS from <style> tags
-        {
-            let dom_ref = shared_dom.borrow();
-            let style_elements = dom_ref.get_elements_by_tag_name("style");
-            for style_id in style_elements {
-                if let Some(style_css) = extract_style_content(&dom_ref, style_id) {
-                    if let Ok(stylesheet) = Stylesheet::parse(&style_css) {
-                        cascade.add_author_stylesheet(stylesheet);
-                    }
-                }
-            }
-        }
+        // The browser's stylesheet and those of <style> elements
+        let cascade = page_cascade(&shared_dom.borrow(), DEFAULT_CSS, self.user_style.stylesheet());
 
         let viewport_width = self.config.width as f32;
-        let viewport_height = self.config.height as f32 - CHROME_HEIGHT;
+        let viewport_height = self.page_viewport_height();
 
         let dom_ref = shared_dom.borrow();
-        let style_tree = StyleTree::build(&*dom_ref, &cascade, viewport_width, viewport_height);
+        let style_tree = Rc::new(StyleTree::build(&dom_ref, &cascade, viewport_width, viewport_height));
 
-        let body_ids = dom_ref.get_elements_by_tag_name("body");
-        let root_id = if !body_ids.is_empty() {
-            body_ids[0]
-        } else {
-            dom_ref.document_id()
-        };
+        let root_id = layout_root(&dom_ref);
 
-        let mut layout_tree = match build_layout_tree(&*dom_ref, &style_tree, root_id) {
-            Some(tree) => tree,
+        let mut layout = match RetainedLayout::build(&dom_ref, style_tree.clone(), root_id) {
+            Some(layout) => layout,
             None => return Err("Failed to build layout tree".into()),
         };
 
-        layout_block(
-            &mut layout_tree,
-            ContainingBlock::new(viewport_width, viewport_height),
-        );
+        let blocks_laid_out = layout.lay_out(None, &[], ContainingBlock::new(viewport_width, viewport_height));
+        let layout_tree = layout.tree();
 
         let content_height = layout_tree.dimensions.margin_box_height();
-        let display_list = build_display_list(&layout_tree);
-        let hit_regions = build_hit_regions(&layout_tree);
+        let display_list = build_display_list(layout_tree);
+        let hit_regions = build_hit_regions(layout_tree);
+        let box_models = measure_box_models(layout_tree);
+        let element_boxes = measure_elements(layout_tree);
         drop(dom_ref);
+        if let Some(ref rt) = js_runtime {
+            rt.set_element_boxes(element_boxes);
+            rt.set_content_height(content_height);
+            rt.set_computed_styles(style_tree);
+            if let Err(e) = rt.dispatch_load() {
+                log::warn!("Load dispatch failed: {}", e);
+            }
+        }
 
         // Store in the specific tab
+        self.load_web_fonts(tab_id, cascade.web_font_faces(), &url);
+        self.load_favicon(tab_id, &url, &shared_dom.borrow());
+        let article = extract_article(&shared_dom.borrow(), &url);
         if let Some(tab) = self.tab_mut(tab_id) {
-            tab.navigation.navigate_to(url.clone());
+            tab.reader_original = None;
             tab.page = Some(PageState {
                 url,
-                display_list,
+                display_list: Rc::new(display_list),
                 js_runtime,
+                fetches: PageFetches::new(),
+                async_scripts,
                 hit_regions,
+                box_models,
                 scroll_y: 0.0,
+                smooth_scroll: None,
+                scrolled: false,
+                scroll_offsets: HashMap::new(),
                 content_height,
                 viewport_height,
                 dom: shared_dom.clone(),
                 cascade,
+                layout: Some(layout),
+                dirty_nodes: Vec::new(),
+                blocks_laid_out,
+                images: ImageCache::new(),
+                scaled_images: ScaledImages::new(),
+                find: PageFind::default(),
+                file_picker: FilePicker::new(),
+                source: html.to_string(),
+                article,
             });
         }
 
@@ -1405,7 +3482,18 @@ impl Browser {
     }
 
     /// Display an error page for navigation failures
+    ///
+    /// The page has a button to try again, and counts down to the retry
+    /// made by itself when there is one. The URL stays in the address bar.
     fn show_error_page(&mut self, url: &Url, error: &NavigationError) {
+        let countdown = match &self.error_retry {
+            Some(retry) if retry.tab_id == self.active_tab_id => format!(
+                "<p class=\"countdown\">Trying again in {} second{}\u{2026}</p>",
+                retry.seconds_left,
+                if retry.seconds_left == 1 { "" } else { "s" }
+            ),
+            _ => String::new(),
+        };
         let html = format!(
             r#"<!DOCTYPE html>
 <html>
@@ -1433,17 +3521,31 @@ impl Browser {
             color: #888;
             font-size: 14px;
         }}
+        .kind {{
+            color: #aaa;
+            font-size: 12px;
+        }}
+        button {{
+            margin-top: 20px;
+            padding: 8px 16px;
+            font-size: 16px;
+        }}
     </style>
 </head>
 <body>
     <h1>{title}</h1>
     <p class="url">{url}</p>
     <p class="details">{details}</p>
+    <p class="kind">Error: {kind}</p>
+    <button type="button" data-gugalanna-retry>Try again</button>
+    {countdown}
 </body>
 </html>"#,
             title = error.title(),
-            url = url.as_str(),
-            details = error.details(),
+            url = escape_html(url.as_str()),
+            details = escape_html(&error.details()),
+            kind = error.kind(),
+            countdown = countdown,
         );
 
         // Load as error page (don't add to history)
@@ -1459,7 +3561,8 @@ impl Browser {
     fn relayout_page_with_animations(&mut self, apply_animations: bool) {
         let active_id = self.active_tab_id;
         let viewport_width = self.config.width as f32;
-        let viewport_height = self.config.height as f32 - CHROME_HEIGHT;
+        let viewport_height = self.page_viewport_height();
+        let client = self.client_for(active_id).clone();
 
         // Collect animated values if needed
         let animated_values: Vec<(usize, String, f32)> = if apply_animations {
@@ -1474,6 +3577,14 @@ impl Browser {
         } else {
             Vec::new()
         };
+        let animated_transforms: HashMap<usize, Vec<TransformFunction>> = animated_values
+            .iter()
+            .filter(|(_, property, _)| property == "transform")
+            .filter_map(|(element_id, _, progress)| {
+                let (from, to) = self.transform_transitions.get(element_id)?;
+                Some((*element_id, interpolate_transform(from, to, *progress)))
+            })
+            .collect();
 
         if let Some(tab) = self.tab_mut(active_id) {
             if let Some(ref mut page) = tab.page {
@@ -1489,51 +3600,95 @@ impl Browser {
                         match property.as_str() {
                             "opacity" => style.opacity = *value,
                             "width" => style.width = Some(*value),
-                            "height" => style.height = Some(*value),
+                            "height" => style.height = Some(LengthPercentage::Px(*value)),
+                            "transform" => {
+                                if let Some(transform) = animated_transforms.get(element_id) {
+                                    style.transform = transform.clone();
+                                }
+                            }
                             _ => {}
                         }
                     }
                 }
 
                 // Get root element
-                let body_ids = dom_ref.get_elements_by_tag_name("body");
-                let root_id = if !body_ids.is_empty() {
-                    body_ids[0]
-                } else {
-                    dom_ref.document_id()
-                };
-
-                // Build and perform layout
-                if let Some(mut layout_tree) = build_layout_tree(&*dom_ref, &style_tree, root_id) {
-                    layout_block(
-                        &mut layout_tree,
-                        ContainingBlock::new(viewport_width, viewport_height),
-                    );
+                let root_id = layout_root(&dom_ref);
+                let style_tree = Rc::new(style_tree);
+
+                // Build the layout tree and lay out what changed since the
+                // last layout
+                let mut previous = page.layout.take();
+                page.layout = RetainedLayout::build(&dom_ref, style_tree.clone(), root_id);
+                let dirty = std::mem::take(&mut page.dirty_nodes);
+                if let Some(layout) = page.layout.as_mut() {
+                    // The rebuilt boxes have no images yet, show those the
+                    // page loaded before laying them out at their size
+                    let (url, images) = (&page.url, &mut page.images);
+                    layout.with_dependent_mut(|_, tree| image_loader::load_images_in_tree(tree, &client, url, images));
+                    let viewport = ContainingBlock::new(viewport_width, viewport_height);
+                    page.blocks_laid_out = layout.lay_out(previous.as_mut(), &dirty, viewport);
+                    log::debug!("Laid out {} blocks", page.blocks_laid_out);
+                }
 
+                if let Some(layout_tree) = page.layout.as_ref().map(RetainedLayout::tree) {
                     // Update content height
                     let content_height = layout_tree.dimensions.margin_box_height();
 
-                    // Rebuild display list and hit regions
-                    let display_list = build_display_list(&layout_tree);
-                    let hit_regions = build_hit_regions(&layout_tree);
+                    // Rebuild display list and hit regions, highlighting what
+                    // the find bar found
+                    page.find.update_layout(&dom_ref, layout_tree);
+                    let display_list = build_display_list_with_selection(layout_tree, &page.find.selection());
+                    let hit_regions = build_hit_regions(layout_tree);
+
+                    // Keep the retained list when the page paints the same
+                    let diff = diff_display_lists(&page.display_list, &display_list);
+                    let len = display_list.commands.len();
+                    if diff.changed(len) > 0 || len != page.display_list.commands.len() {
+                        log::debug!("Display list rebuilt: {} of {} commands changed", diff.changed(len), len);
+                        page.display_list = Rc::new(display_list);
+                    }
 
-                    // Update page state
-                    page.display_list = display_list;
+                    // Update page state, keeping inner scroll positions within
+                    // the new scroll ranges
                     page.hit_regions = hit_regions;
+                    restore_scroll_offsets(&mut page.hit_regions, &mut page.scroll_offsets);
+                    page.box_models = measure_box_models(layout_tree);
                     page.content_height = content_height;
                     page.viewport_height = viewport_height;
 
                     // Clamp scroll position to new content bounds
                     let max_scroll = (content_height - viewport_height).max(0.0);
                     page.scroll_y = page.scroll_y.clamp(0.0, max_scroll);
+
+                    if let Some(ref rt) = page.js_runtime {
+                        rt.set_element_boxes(measure_elements(layout_tree));
+                        rt.set_content_height(content_height);
+                        rt.set_scroll_y(page.scroll_y);
+                    }
+                }
+
+                if let Some(ref rt) = page.js_runtime {
+                    rt.set_viewport(viewport_width, viewport_height);
+                    rt.set_computed_styles(style_tree);
                 }
             }
         }
+        // Scripts may have changed what the search finds
+        self.sync_find_bar();
     }
 
     /// Handle a mouse click
     /// Returns true if the browser should quit (last tab closed)
     fn handle_click(&mut self, x: f32, y: f32) -> bool {
+        // An open context menu takes the click, one outside dismisses it
+        if let Some(menu) = self.context_menu.take() {
+            self.needs_redraw = true;
+            if let Some(item) = menu.item_at(x, y) {
+                self.run_menu_item(item, menu.context);
+            }
+            return false;
+        }
+
         // Check chrome first
         if let Some(hit) = self.chrome.hit_test(x, y) {
             match hit {
@@ -1572,6 +3727,11 @@ impl Browser {
                         }
                     }
                 }
+                ChromeHit::ReaderButton => {
+                    if self.chrome.reader_button.enabled {
+                        self.toggle_reader_mode();
+                    }
+                }
                 ChromeHit::AddressBar => {
                     self.focus_address_bar();
                 }
@@ -1589,13 +3749,24 @@ impl Browser {
                     DevToolsHit::ElementSelector => {
                         self.devtools.toggle_element_selector();
                     }
-                    DevToolsHit::Content { local_y, .. } => {
-                        // Handle content clicks (e.g., DOM tree node selection)
+                    DevToolsHit::ClearConsole => {
+                        let page = self.active_tab().and_then(|t| t.page.as_ref());
+                        if let Some(rt) = page.and_then(|p| p.js_runtime.as_ref()) {
+                            rt.clear_console_messages();
+                        }
+                    }
+                    DevToolsHit::ConsoleInput => {
+                        self.focus_console();
+                    }
+                    DevToolsHit::Content { local_x, local_y } => {
+                        // A click on the DOM tree selects or expands a node
                         if self.devtools.active_tab == DevToolsTab::Elements {
-                            // Calculate which node was clicked based on scroll position
-                            let line_height = 18.0;
-                            let line_index = ((local_y + self.devtools.dom_scroll) / line_height) as usize;
-                            log::debug!("DevTools Elements click at line {}", line_index);
+                            let dom = self.active_tab().and_then(|t| t.page.as_ref()).map(|p| p.dom.clone());
+                            let selected =
+                                dom.and_then(|dom| self.devtools.click_tree(&dom.borrow(), local_x, local_y));
+                            if let Some(node_id) = selected {
+                                self.scroll_node_into_view(node_id);
+                            }
                         }
                     }
                     DevToolsHit::DomNode(node_id) => {
@@ -1606,15 +3777,33 @@ impl Browser {
             }
         }
 
-        // Blur address bar if clicking outside
+        // Scrollbars are over the page, clicking one doesn't move focus
+        if self.handle_scrollbar_press(x, y) {
+            return false;
+        }
+
+        // The find bar is over the page
+        if self.chrome.find_bar.open && self.chrome.find_bar_rect().contains(x, y) {
+            self.focus_find_bar();
+            return false;
+        }
+
+        // Blur address bar or find bar if clicking outside
         if self.focus == FocusTarget::AddressBar {
             self.blur_address_bar();
         }
+        if self.focus == FocusTarget::FindBar {
+            self.blur_find_bar();
+        }
+        if self.focus == FocusTarget::DevToolsConsole {
+            self.blur_console();
+        }
 
         // Blur form input if clicking outside chrome
         if let FocusTarget::FormInput(_) = self.focus {
             self.blur_form_input();
         }
+        self.blur_element();
 
         // Check page content
         let page_y = y - CHROME_HEIGHT;
@@ -1627,7 +3816,7 @@ impl Browser {
                 if let Some(tab) = self.tabs.iter().find(|t| t.id == active_id) {
                     if let Some(ref page) = tab.page {
                         let content_y = page_y + page.scroll_y;
-                        if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y) {
+                        if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y, page.scroll_y) {
                             let dom_ref = page.dom.borrow();
                             // Expand path to the selected node
                             self.devtools.expand_path_to_node(&dom_ref, NodeId(node_id));
@@ -1642,14 +3831,48 @@ impl Browser {
                 }
             }
 
+            // Scripts see the click first, preventDefault() cancels what
+            // clicking the element does, as following a link
+            let default_prevented = self.active_tab().and_then(|tab| tab.page.as_ref()).is_some_and(|page| {
+                let content_y = page_y + page.scroll_y;
+                let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y, page.scroll_y) else {
+                    return false;
+                };
+                let Some(rt) = page.js_runtime.as_ref() else {
+                    return false;
+                };
+                // Clicks target elements, one on text goes to its parent
+                let target = match page.dom.borrow().get(NodeId(node_id)) {
+                    Some(node) if node.as_element().is_none() => node.parent.map_or(node_id, |parent| parent.0),
+                    _ => node_id,
+                };
+                match rt.dispatch_click(target) {
+                    Ok(not_canceled) => !not_canceled,
+                    Err(e) => {
+                        log::warn!("Click dispatch failed: {}", e);
+                        false
+                    }
+                }
+            });
+            if default_prevented {
+                return false;
+            }
+
             // First check for form elements without mutable borrow
             let form_info = if let Some(tab) = self.tabs.iter().find(|t| t.id == active_id) {
                 if let Some(ref page) = tab.page {
                     let content_y = page_y + page.scroll_y;
 
-                    if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y) {
+                    if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y, page.scroll_y) {
                         let dom_ref = page.dom.borrow();
+                        // Disabled controls don't react to clicks
                         find_form_element(&dom_ref, NodeId(node_id))
+                            .filter(|info| !is_disabled(&dom_ref, &tab.form_state.borrow(), info.node_id()))
+                            .map(|info| {
+                                // The click was on a label of the control
+                                let labelled = !dom_ref.contains(info.node_id(), NodeId(node_id));
+                                (info, labelled)
+                            })
                     } else {
                         None
                     }
@@ -1660,14 +3883,47 @@ impl Browser {
                 None
             };
 
+            // A label passes its click on to its control, which scripts
+            // hear clicked too and may cancel
+            if let Some((ref form_elem, true)) = form_info {
+                let default_prevented = self.active_tab().and_then(|tab| tab.page.as_ref()).is_some_and(|page| {
+                    let Some(rt) = page.js_runtime.as_ref() else {
+                        return false;
+                    };
+                    match rt.dispatch_click(form_elem.node_id().0) {
+                        Ok(not_canceled) => !not_canceled,
+                        Err(e) => {
+                            log::warn!("Click dispatch failed: {}", e);
+                            false
+                        }
+                    }
+                });
+                if default_prevented {
+                    return false;
+                }
+            }
+
             // Handle form element click
-            if let Some(ref form_elem) = form_info {
+            if let Some((ref form_elem, labelled)) = form_info {
                 log::info!("Form element clicked: {:?}", form_elem);
                 match form_elem {
                     FormElementInfo::TextInput { node_id, .. } => {
                         self.focus_form_input(*node_id);
                         return false;
                     }
+                    FormElementInfo::TextArea { node_id, text } => {
+                        self.focus_text_area(*node_id, text);
+                        // The cursor goes to the character clicked
+                        let scroll_y = self.active_tab().and_then(|t| t.page.as_ref()).map_or(0.0, |p| p.scroll_y);
+                        if let Some((rect, _)) = self.text_area_box(*node_id).filter(|_| !labelled) {
+                            let text_x = x - rect.x - CONTROL_PADDING;
+                            let text_y = page_y + scroll_y - rect.y - CONTROL_PADDING;
+                            self.move_text_area_cursor(*node_id, |state, area| {
+                                state.move_cursor_to_point(area, text_x, text_y);
+                            });
+                        }
+                        return false;
+                    }
                     FormElementInfo::Checkbox { node_id } => {
                         self.toggle_checkbox(*node_id);
                         return false;
@@ -1683,7 +3939,11 @@ impl Browser {
                     }
                     FormElementInfo::Button { node_id } => {
                         log::info!("Button clicked (node {})", node_id.0);
-                        // Dispatch to JS if available
+                        self.press_button(*node_id);
+                        return false;
+                    }
+                    FormElementInfo::File { node_id, multiple } => {
+                        self.choose_files(*node_id, *multiple);
                         return false;
                     }
                 }
@@ -1695,7 +3955,7 @@ impl Browser {
                     let content_y = page_y + page.scroll_y;
                     log::debug!("Content y={}, hit_regions count={}", content_y, page.hit_regions.len());
 
-                    if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y) {
+                    if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y, page.scroll_y) {
                         log::debug!("Page click on node {}", node_id);
                         let dom_ref = page.dom.borrow();
                         find_anchor_href(&dom_ref, gugalanna_dom::NodeId(node_id))
@@ -1712,48 +3972,394 @@ impl Browser {
 
             if let Some((href, base_url, _node_id)) = link_info {
                 log::info!("Link clicked: {}", href);
+                self.follow_link(&href, &base_url);
+                return false;
+            }
+        }
+        false
+    }
 
-                // Handle fragment-only links (same page scroll)
-                if href.starts_with('#') {
-                    self.scroll_to_fragment(&href[1..]);
-                    return false;
-                }
+    /// Go where a link of the page at `base_url` points
+    fn follow_link(&mut self, href: &str, base_url: &Url) {
+        // Handle fragment-only links (same page scroll)
+        if let Some(fragment) = href.strip_prefix('#') {
+            self.scroll_to_fragment(fragment);
+            return;
+        }
 
-                // Resolve the URL and navigate
-                match resolve_link_url(&base_url, &href) {
-                    Ok(target_url) => {
-                        if let Err(e) = self.navigate_async(target_url.as_str()) {
-                            log::error!("Link navigation failed: {}", e);
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("Failed to resolve URL '{}': {}", href, e);
-                    }
+        // Resolve the URL and navigate
+        match resolve_link_url(base_url, href) {
+            Ok(target_url) => {
+                if let Err(e) = self.navigate_async(target_url.as_str()) {
+                    log::error!("Link navigation failed: {}", e);
                 }
-                return false;
             }
+            Err(e) => {
+                log::error!("Failed to resolve URL '{}': {}", href, e);
+            }
+        }
+    }
 
-            // Not a link - dispatch click to JS
-            if let Some(tab) = self.tab_mut(active_id) {
-                if let Some(ref mut page) = tab.page {
-                    let content_y = page_y + page.scroll_y;
-                    if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y) {
-                        if let Some(ref rt) = page.js_runtime {
-                            if let Err(e) = rt.dispatch_click(node_id) {
-                                log::warn!("Click dispatch failed: {}", e);
-                            }
-                        }
+    /// Press a button that submits nothing
+    fn press_button(&mut self, node_id: NodeId) {
+        // The error page's button tries the navigation again
+        let retry = self.active_tab().and_then(|tab| tab.page.as_ref()).is_some_and(|page| {
+            let dom = page.dom.borrow();
+            let element = dom.get(node_id).and_then(|node| node.as_element());
+            element.is_some_and(|element| element.get_attribute("data-gugalanna-retry").is_some())
+        });
+        if retry {
+            self.retry_navigation();
+        }
+    }
+
+    /// Open the context menu for what is under a right click on the page
+    fn handle_right_click(&mut self, x: f32, y: f32) {
+        self.context_menu = None;
+        self.needs_redraw = true;
+        if y < CHROME_HEIGHT {
+            return;
+        }
+        let context = self.menu_context(x, y);
+        let can_back = self.chrome.back_button.enabled;
+        let can_forward = self.chrome.forward_button.enabled;
+        let page = self.active_tab().and_then(|t| t.page.as_ref());
+        let has_page = page.is_some();
+        // A page's source has no source of its own to view
+        let has_source = page.is_some_and(|p| p.url.scheme() != VIEW_SOURCE_SCHEME);
+        let window = (self.config.width as f32, self.config.height as f32);
+        self.context_menu = Some(ContextMenu::new(context, x, y, window, |item| match item {
+            MenuItem::Back => can_back,
+            MenuItem::Forward => can_forward,
+            MenuItem::Reload => has_page,
+            MenuItem::ViewSource => has_source,
+            MenuItem::SavePageAsImage | MenuItem::SavePageAsPdf => has_page,
+            _ => true,
+        }));
+    }
+
+    /// What is at a point of the window on the active page: the link and
+    /// image there, and the current find match, which is the selection
+    fn menu_context(&self, x: f32, y: f32) -> MenuContext {
+        let mut context = MenuContext::default();
+        let Some(page) = self.active_tab().and_then(|t| t.page.as_ref()) else {
+            return context;
+        };
+        let content_y = y - CHROME_HEIGHT + page.scroll_y;
+        let dom = page.dom.borrow();
+        if page.find.current_rects().iter().any(|rect| rect.contains(x, content_y)) {
+            context.selection = page.find.current_text(&dom);
+        }
+        let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y, page.scroll_y).map(NodeId) else {
+            return context;
+        };
+        context.link = self.link_at(x, y);
+        context.image = dom
+            .get(node_id)
+            .and_then(|node| node.as_element())
+            .filter(|element| element.tag_name == "img")
+            .and_then(|element| element.get_attribute("src"))
+            .and_then(|src| page.url.join(src).ok());
+        context
+    }
+
+    /// Do what a context menu item stands for
+    fn run_menu_item(&mut self, item: MenuItem, context: MenuContext) {
+        match item {
+            MenuItem::OpenInNewTab => {
+                if let Some(url) = context.link {
+                    self.open_tab(self.active_tab_private());
+                    if let Err(e) = self.navigate_async(url.as_str()) {
+                        log::error!("Navigation failed: {}", e);
                     }
                 }
             }
+            MenuItem::CopyLinkAddress => {
+                if let Some(url) = context.link {
+                    set_clipboard_text(url.as_str());
+                }
+            }
+            MenuItem::CopyImageUrl => {
+                if let Some(url) = context.image {
+                    set_clipboard_text(url.as_str());
+                }
+            }
+            MenuItem::SaveLinkAs => {
+                if let Some(url) = context.link {
+                    self.save_url(url);
+                }
+            }
+            MenuItem::SaveImage => {
+                if let Some(url) = context.image {
+                    self.save_url(url);
+                }
+            }
+            MenuItem::Copy => {
+                if let Some(text) = context.selection {
+                    set_clipboard_text(&text);
+                }
+            }
+            MenuItem::Back => {
+                if let Err(e) = self.go_back() {
+                    log::error!("Go back failed: {}", e);
+                }
+            }
+            MenuItem::Forward => {
+                if let Err(e) = self.go_forward() {
+                    log::error!("Go forward failed: {}", e);
+                }
+            }
+            MenuItem::Reload => self.reload_page(),
+            MenuItem::ViewSource => self.view_source(),
+            MenuItem::SavePageAsImage => {
+                self.save_page_capture(CaptureFormat::Png);
+            }
+            MenuItem::SavePageAsPdf => {
+                self.save_page_capture(CaptureFormat::Pdf);
+            }
+        }
+    }
+
+    /// Save what a URL points to in the download directory
+    fn save_url(&mut self, url: Url) {
+        let client = self.client_for(self.active_tab_id).clone();
+        match self.downloads.start(&client, url.clone()) {
+            Ok(_) => self.show_downloads(),
+            Err(e) => log::error!("Saving {} failed: {}", url, e),
+        }
+    }
+
+    /// Save the whole of the active page as an image or PDF in the download
+    /// directory, telling how that went in a toast
+    ///
+    /// The page is laid out again at the window's width in a viewport as
+    /// tall as its content, so what is below the fold is in the capture,
+    /// and painted without the chrome. Returns where it was saved.
+    pub fn save_page_capture(&mut self, format: CaptureFormat) -> Option<PathBuf> {
+        match self.capture_page(format) {
+            Ok(path) => {
+                log::info!("Saved the page to {}", path.display());
+                let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                self.show_toast(format!("Saved the page as {}", name));
+                Some(path)
+            }
+            Err(e) => {
+                log::error!("Saving the page failed: {}", e);
+                self.show_toast(format!("Saving the page failed: {}", e));
+                None
+            }
+        }
+    }
+
+    fn capture_page(&self, format: CaptureFormat) -> Result<PathBuf, String> {
+        let page = self.active_tab().and_then(|t| t.page.as_ref()).ok_or("No page to save")?;
+        let dir = self.config.download_dir.as_ref().ok_or("No download directory")?;
+        let width = self.config.width as f32;
+        let height = page.content_height.clamp(1.0, MAX_CAPTURE_HEIGHT);
+
+        let dom = page.dom.borrow();
+        let style_tree = StyleTree::build(&dom, &page.cascade, width, height);
+        let root_id = layout_root(&dom);
+        let mut layout_tree = build_layout_tree(&dom, &style_tree, root_id).ok_or("Failed to build layout tree")?;
+        let client = self.client_for(self.active_tab_id);
+        image_loader::load_images_in_tree(&mut layout_tree, client, &page.url, &mut ImageCache::new());
+        layout_document(&mut layout_tree, ContainingBlock::new(width, height));
+
+        // The content may be another height in the taller viewport
+        let height = layout_tree.dimensions.margin_box_height().clamp(1.0, MAX_CAPTURE_HEIGHT).ceil() as u32;
+        let backend = paint_display_list(&build_display_list(&layout_tree), self.config.width, height);
+
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let path = download_path(dir, &capture_file_name(&page.url, format.extension()));
+        save_capture(&backend, self.config.width, height, format, &path)?;
+        Ok(path)
+    }
+
+    /// Show a message over the top of the page for a moment
+    fn show_toast(&mut self, message: String) {
+        self.chrome.toast = Some(message);
+        self.toast_expires = Some(Instant::now() + TOAST_DURATION);
+        self.needs_redraw = true;
+    }
+
+    /// Take the toast down once its time is up
+    fn expire_toast(&mut self, now: Instant) {
+        if self.toast_expires.is_some_and(|expires| now >= expires) {
+            self.toast_expires = None;
+            self.chrome.toast = None;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Show a message in the status overlay for a moment
+    fn show_status(&mut self, message: &str) {
+        self.chrome.status = Some(message.to_string());
+        self.status_expires = Some(Instant::now() + STATUS_MESSAGE_DURATION);
+        self.needs_redraw = true;
+    }
+
+    /// Count down on the error page to the retry of the navigation that
+    /// failed, and retry it once the time is up
+    ///
+    /// The retry is dropped when its tab is no longer shown or no longer
+    /// shows the error.
+    fn tick_error_retry(&mut self, now: Instant) {
+        let Some(retry) = &self.error_retry else {
+            return;
+        };
+        let failure = match self.active_tab().filter(|tab| tab.id == retry.tab_id).map(|tab| &tab.loading_state) {
+            Some(LoadingState::Failed { url, error }) => (url.clone(), error.clone()),
+            _ => {
+                self.error_retry = None;
+                return;
+            }
+        };
+        let left = retry.at.saturating_duration_since(now);
+        if left.is_zero() {
+            self.error_retry = None;
+            self.auto_retried = Some((self.active_tab_id, failure.0));
+            self.retry_navigation();
+            return;
+        }
+        let seconds_left = left.as_secs_f32().ceil() as u64;
+        if seconds_left != retry.seconds_left {
+            if let Some(retry) = self.error_retry.as_mut() {
+                retry.seconds_left = seconds_left;
+            }
+            self.show_error_page(&failure.0, &failure.1);
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Try the navigation the active tab's error page shows again
+    fn retry_navigation(&mut self) {
+        let Some(LoadingState::Failed { url, .. }) = self.active_tab().map(|tab| &tab.loading_state) else {
+            return;
+        };
+        let url = url.to_string();
+        log::info!("Trying again: {}", url);
+        self.error_retry = None;
+        if let Err(e) = self.navigate_async(&url) {
+            log::error!("Retry failed: {}", e);
+        }
+    }
+
+    /// Take the message of an invalid control down once its time is up
+    fn expire_validation_bubble(&mut self, now: Instant) {
+        if self.validation_bubble.as_ref().is_some_and(|bubble| now >= bubble.expires) {
+            self.validation_bubble = None;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Give the status overlay back to the link under the mouse once the
+    /// message's time is up
+    fn expire_status(&mut self, now: Instant) {
+        if self.status_expires.is_some_and(|expires| now >= expires) {
+            self.status_expires = None;
+            let (x, y) = self.mouse_position;
+            self.chrome.status = None;
+            self.update_link_status(x, y);
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Show where the link under the mouse goes in the status overlay,
+    /// unless a message is shown there
+    fn update_link_status(&mut self, x: f32, y: f32) {
+        if self.status_expires.is_some() {
+            return;
+        }
+        let status = self.link_at(x, y).map(|url| url.to_string());
+        if status != self.chrome.status {
+            self.chrome.status = status;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Where the link at a point of the window goes, resolved against the
+    /// page's URL
+    fn link_at(&self, x: f32, y: f32) -> Option<Url> {
+        if y < CHROME_HEIGHT {
+            return None;
+        }
+        let page = self.active_tab()?.page.as_ref()?;
+        let content_y = y - CHROME_HEIGHT + page.scroll_y;
+        let node_id = hit_test_regions(&page.hit_regions, x, content_y, page.scroll_y).map(NodeId)?;
+        let (href, _) = find_anchor_href(&page.dom.borrow(), node_id)?;
+        resolve_link_url(&page.url, &href).ok()
+    }
+
+    /// Open the link under a middle-click in a new tab behind the active
+    /// one
+    fn handle_middle_click(&mut self, x: f32, y: f32) {
+        let Some(url) = self.link_at(x, y) else {
+            return;
+        };
+        let active_id = self.active_tab_id;
+        self.open_tab(self.active_tab_private());
+        if let Err(e) = self.navigate_async(url.as_str()) {
+            log::error!("Navigation failed: {}", e);
+        }
+        self.switch_to_tab(active_id);
+        self.show_status("Opening new tab\u{2026}");
+    }
+
+    /// Go back or forward for a mouse's side buttons, when the chrome's
+    /// buttons would
+    ///
+    /// Ignored while the context menu is open or the devtools console has
+    /// focus.
+    fn handle_history_button(&mut self, button: MouseButton) {
+        if self.context_menu.is_some() || self.devtools.console_focused {
+            return;
+        }
+        let result = match button {
+            MouseButton::Back if self.chrome.back_button.enabled => self.go_back(),
+            MouseButton::Forward if self.chrome.forward_button.enabled => self.go_forward(),
+            _ => return,
+        };
+        if let Err(e) = result {
+            log::error!("History navigation failed: {}", e);
+        }
+    }
+
+    /// Go back or forward once the horizontal wheel ticks of a touchpad
+    /// swipe add up, when swipe navigation is on
+    fn handle_swipe(&mut self, x: i32, now: Instant) {
+        if !self.config.swipe_navigation {
+            return;
+        }
+        if let Some(button) = self.swipe.add(x, now) {
+            self.handle_history_button(button);
+        }
+    }
+
+    /// Open the source of the active page in a new tab, from the HTML it
+    /// was loaded from
+    fn view_source(&mut self) {
+        // In reader mode, the source of the page the article came from
+        let Some(page) = self.active_tab().and_then(|t| t.reader_original.as_ref().or(t.page.as_ref())) else {
+            return;
+        };
+        let Some(url) = source_url(&page.url) else {
+            return;
+        };
+        let html = source_page(&page.url, &page.source);
+        self.open_tab(self.active_tab_private());
+        self.chrome.address_bar.set_text(url.as_str());
+        if let Err(e) = self.load_page(url, &html) {
+            log::error!("Viewing the source failed: {}", e);
         }
-        false
     }
 
     /// Focus the address bar
     fn focus_address_bar(&mut self) {
+        self.blur_element();
         self.focus = FocusTarget::AddressBar;
         self.chrome.address_bar.is_focused = true;
+        self.chrome.find_bar.is_focused = false;
+        self.devtools.console_focused = false;
         self.chrome.address_bar.move_cursor_to_end();
         start_text_input();
     }
@@ -1765,27 +4371,332 @@ impl Browser {
         stop_text_input();
     }
 
+    /// Open the find bar, its query to be typed
+    fn open_find_bar(&mut self) {
+        self.chrome.find_bar.open = true;
+        self.focus_find_bar();
+    }
+
+    /// Close the find bar, clearing its search
+    fn close_find_bar(&mut self) {
+        if self.focus == FocusTarget::FindBar {
+            self.blur_find_bar();
+        }
+        self.chrome.find_bar.open = false;
+        self.chrome.find_bar.query.clear();
+        self.update_find();
+    }
+
+    fn focus_find_bar(&mut self) {
+        if self.focus == FocusTarget::AddressBar {
+            self.blur_address_bar();
+        }
+        self.blur_element();
+        self.devtools.console_focused = false;
+        self.focus = FocusTarget::FindBar;
+        self.chrome.find_bar.is_focused = true;
+        self.needs_redraw = true;
+        start_text_input();
+    }
+
+    fn blur_find_bar(&mut self) {
+        self.focus = FocusTarget::None;
+        self.chrome.find_bar.is_focused = false;
+        self.needs_redraw = true;
+        stop_text_input();
+    }
+
+    /// Focus the DevTools console's input line, code to be typed in it
+    fn focus_console(&mut self) {
+        match self.focus {
+            FocusTarget::AddressBar => self.blur_address_bar(),
+            FocusTarget::FindBar => self.blur_find_bar(),
+            FocusTarget::FormInput(_) => self.blur_form_input(),
+            FocusTarget::Element(_) => self.blur_element(),
+            _ => {}
+        }
+        self.focus = FocusTarget::DevToolsConsole;
+        self.devtools.console_focused = true;
+        self.needs_redraw = true;
+        start_text_input();
+    }
+
+    fn blur_console(&mut self) {
+        self.focus = FocusTarget::None;
+        self.devtools.console_focused = false;
+        self.needs_redraw = true;
+        stop_text_input();
+    }
+
+    /// Run the code typed in the DevTools console in the active page
+    fn run_console_input(&mut self) {
+        let tab = self.tabs.iter().find(|t| t.id == self.active_tab_id);
+        if let Some(rt) = tab.and_then(|t| t.page.as_ref()).and_then(|p| p.js_runtime.as_ref()) {
+            self.devtools.run_console_input(rt);
+        }
+        // The code may have changed the page
+        self.restyle_pages();
+        self.follow_console();
+        self.needs_redraw = true;
+    }
+
+    /// Search the active page for the find bar's query, highlighting the
+    /// matches and scrolling to the first
+    fn update_find(&mut self) {
+        let query = self.chrome.find_bar.query.clone();
+        let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) else {
+            return;
+        };
+        if query.is_empty() && page.find.query().is_empty() {
+            return;
+        }
+        let dom = page.dom.clone();
+        page.find.search(&dom.borrow(), &query);
+        self.relayout_page();
+        self.scroll_to_find_match();
+    }
+
+    /// Move the search to the next match of the active page, or the
+    /// previous one
+    fn step_find(&mut self, backwards: bool) {
+        if let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) {
+            page.find.step(backwards);
+        }
+        self.scroll_to_find_match();
+    }
+
+    /// Scroll the match the search is at into view
+    fn scroll_to_find_match(&mut self) {
+        let smooth = self.smooth_scrolling();
+        if let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) {
+            let target = find::scroll_target(page.find.current_rects(), page.scroll_target(), page.viewport_height);
+            if let Some(target) = target {
+                page.scroll_toward(target, smooth);
+            }
+        }
+        self.sync_find_bar();
+        self.needs_redraw = true;
+    }
+
+    /// Scroll the page so a node selected in DevTools is in view
+    fn scroll_node_into_view(&mut self, node_id: NodeId) {
+        let smooth = self.smooth_scrolling();
+        if let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) {
+            let Some(model) = page.box_models.get(&node_id).filter(|model| !model.fixed) else {
+                return;
+            };
+            if let Some(target) = find::scroll_target(&[model.border], page.scroll_target(), page.viewport_height) {
+                page.scroll_toward(target, smooth);
+            }
+        }
+        self.needs_redraw = true;
+    }
+
+    /// Show the search of the active page in the find bar, which a page
+    /// navigated to has not started
+    fn sync_find_bar(&mut self) {
+        let search = self.active_tab().and_then(|t| t.page.as_ref()).map(|p| &p.find);
+        let (query, current, count) =
+            search.map_or((String::new(), None, 0), |s| (s.query().to_string(), s.current(), s.count()));
+        let find_bar = &mut self.chrome.find_bar;
+        find_bar.query = query;
+        find_bar.current = current;
+        find_bar.count = count;
+    }
+
     /// Focus a form text input
     fn focus_form_input(&mut self, node_id: NodeId) {
+        let old_focused = self.focused_node();
         self.focus = FocusTarget::FormInput(node_id);
         start_text_input();
+        self.handle_focus_change(old_focused, Some(node_id));
 
         // Ensure the input has state
         if let Some(tab) = self.tab_mut(self.active_tab_id) {
-            tab.form_state.ensure_text(node_id);
+            let value = tab.form_state.borrow_mut().ensure_text(node_id).value.clone();
+            self.value_at_focus = Some(value);
         }
     }
 
-    /// Blur a form text input
+    /// Blur a form text input, dispatching `change` if its value was
+    /// modified while it had focus
     fn blur_form_input(&mut self) {
-        self.focus = FocusTarget::None;
+        let focus = std::mem::replace(&mut self.focus, FocusTarget::None);
         stop_text_input();
+        if let FocusTarget::FormInput(node_id) = focus {
+            self.handle_focus_change(Some(node_id), None);
+        }
+
+        let (FocusTarget::FormInput(node_id), Some(before)) = (focus, self.value_at_focus.take()) else {
+            return;
+        };
+        let changed = self
+            .active_tab()
+            .is_some_and(|tab| tab.form_state.borrow().get_value(node_id).is_some_and(|value| value != before));
+        if changed {
+            self.dispatch_form_event(node_id, "change");
+        }
+    }
+
+    /// Focus a text area, editing starting from its initial text
+    fn focus_text_area(&mut self, node_id: NodeId, text: &str) {
+        if let Some(tab) = self.tab_mut(self.active_tab_id) {
+            let mut form_state = tab.form_state.borrow_mut();
+            if form_state.get_text(node_id).is_none() {
+                form_state.set_text(node_id, text.to_string());
+            }
+        }
+        self.focus_form_input(node_id);
+    }
+
+    /// Check if keys go to the page, rather than to the chrome or DevTools
+    fn page_has_focus(&self) -> bool {
+        matches!(
+            self.focus,
+            FocusTarget::None | FocusTarget::Page | FocusTarget::FormInput(_) | FocusTarget::Element(_)
+        )
+    }
+
+    /// The element of the page that has focus
+    fn focused_node(&self) -> Option<NodeId> {
+        match self.focus {
+            FocusTarget::FormInput(node_id) | FocusTarget::Element(node_id) => Some(node_id),
+            _ => None,
+        }
+    }
+
+    /// Move focus to the page's next link or control in the focus order,
+    /// or to its previous one, going around at the ends
+    fn move_focus(&mut self, backward: bool) {
+        let Some(tab) = self.active_tab() else {
+            return;
+        };
+        let Some(page) = tab.page.as_ref() else {
+            return;
+        };
+        let order = {
+            let dom = page.dom.borrow();
+            let form_state = tab.form_state.borrow();
+            // Elements without a box are not displayed
+            focus_order(&dom, |id| page.box_models.contains_key(&id) && !is_disabled(&dom, &form_state, id))
+        };
+        let current = self.focused_node().and_then(|node_id| order.iter().position(|&id| id == node_id));
+        let next = match current {
+            Some(i) if backward => order.get((i + order.len() - 1) % order.len()),
+            Some(i) => order.get((i + 1) % order.len()),
+            None if backward => order.last(),
+            None => order.first(),
+        };
+        if let Some(&node_id) = next {
+            self.focus_element(node_id);
+        }
+    }
+
+    /// Give an element of the page focus: text fields take what is typed,
+    /// other elements are shown focused with a ring
+    fn focus_element(&mut self, node_id: NodeId) {
+        if let FocusTarget::FormInput(_) = self.focus {
+            self.blur_form_input();
+        }
+        self.blur_element();
+        match self.form_control(node_id) {
+            Some(FormElementInfo::TextInput { .. }) => self.focus_form_input(node_id),
+            Some(FormElementInfo::TextArea { text, .. }) => self.focus_text_area(node_id, &text),
+            _ => {
+                self.focus = FocusTarget::Element(node_id);
+                self.handle_focus_change(None, Some(node_id));
+            }
+        }
+        self.scroll_into_view(node_id);
+        self.needs_redraw = true;
+    }
+
+    /// Take focus from the link or control Tab moved it to
+    fn blur_element(&mut self) {
+        if let FocusTarget::Element(node_id) = self.focus {
+            self.focus = FocusTarget::None;
+            self.handle_focus_change(Some(node_id), None);
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Forget the element that had focus on the page shown before, and
+    /// the message shown under it
+    fn drop_page_focus(&mut self) {
+        self.validation_bubble = None;
+        if matches!(self.focus, FocusTarget::FormInput(_) | FocusTarget::Element(_)) {
+            self.focus = FocusTarget::None;
+            self.value_at_focus = None;
+            stop_text_input();
+        }
+    }
+
+    /// The form control an element of the active page is, if it is one
+    fn form_control(&self, node_id: NodeId) -> Option<FormElementInfo> {
+        let page = self.active_tab()?.page.as_ref()?;
+        let dom = page.dom.borrow();
+        find_form_element(&dom, node_id).filter(|info| info.node_id() == node_id)
+    }
+
+    /// Do what clicking a focused element does: follow the link, press
+    /// the button
+    fn activate_element(&mut self, node_id: NodeId) {
+        // Scripts hear a click, preventDefault() cancels it
+        let default_prevented = self.active_tab().and_then(|tab| tab.page.as_ref()).is_some_and(|page| {
+            let Some(rt) = page.js_runtime.as_ref() else {
+                return false;
+            };
+            match rt.dispatch_click(node_id.0) {
+                Ok(not_canceled) => !not_canceled,
+                Err(e) => {
+                    log::warn!("Click dispatch failed: {}", e);
+                    false
+                }
+            }
+        });
+        if default_prevented {
+            return;
+        }
+        match self.form_control(node_id) {
+            Some(FormElementInfo::Submit { node_id }) => self.submit_form(node_id),
+            Some(FormElementInfo::Button { node_id }) => self.press_button(node_id),
+            Some(FormElementInfo::File { node_id, multiple }) => self.choose_files(node_id, multiple),
+            Some(_) => {}
+            None => {
+                let link = self.active_tab().and_then(|tab| tab.page.as_ref()).and_then(|page| {
+                    let dom = page.dom.borrow();
+                    find_anchor_href(&dom, node_id).map(|(href, _)| (href, page.url.clone()))
+                });
+                if let Some((href, base_url)) = link {
+                    self.follow_link(&href, &base_url);
+                }
+            }
+        }
+    }
+
+    /// Scroll the page as little as it takes to show an element
+    fn scroll_into_view(&mut self, node_id: NodeId) {
+        let Some(page) = self.active_tab_mut().and_then(|tab| tab.page.as_mut()) else {
+            return;
+        };
+        // Fixed boxes are always in view
+        let Some(border) = page.box_models.get(&node_id).filter(|model| !model.fixed).map(|model| model.border) else {
+            return;
+        };
+        let bottom = border.y + border.height;
+        if border.y < page.scroll_y {
+            page.scroll_to(None, border.y);
+        } else if bottom > page.scroll_y + page.viewport_height {
+            // An element taller than the viewport shows its top
+            page.scroll_to(None, (bottom - page.viewport_height).min(border.y));
+        }
     }
 
     /// Toggle a checkbox
     fn toggle_checkbox(&mut self, node_id: NodeId) {
         if let Some(tab) = self.tab_mut(self.active_tab_id) {
-            tab.form_state.toggle_checked(node_id);
+            tab.form_state.borrow_mut().toggle_checked(node_id);
+            self.dispatch_form_event(node_id, "change");
         }
     }
 
@@ -1808,17 +4719,131 @@ impl Browser {
             vec![]
         };
 
-        // Now update the form state
-        if let Some(tab) = self.tab_mut(active_id) {
-            for id in radios_to_deselect {
-                tab.form_state.set_checked(id, false);
+        // Now update the form state, the radio changes unless it was
+        // already selected
+        if let Some(tab) = self.tab_mut(active_id) {
+            let mut form_state = tab.form_state.borrow_mut();
+            let was_checked = form_state.is_checked(node_id);
+            for id in radios_to_deselect {
+                form_state.set_checked(id, false);
+            }
+            form_state.set_checked(node_id, true);
+            drop(form_state);
+            if !was_checked {
+                self.dispatch_form_event(node_id, "change");
+            }
+        }
+    }
+
+    /// Open the dialog choosing the files of a file input, which keeps
+    /// the page going until it closes
+    fn choose_files(&mut self, node_id: NodeId, multiple: bool) {
+        if let Some(page) = self.tab_mut(self.active_tab_id).and_then(|tab| tab.page.as_mut()) {
+            page.file_picker.open(node_id, multiple);
+        }
+    }
+
+    /// Give a file input the files chosen for it once its dialog closed,
+    /// telling the page's scripts
+    fn poll_file_choices(&mut self) {
+        let closed = self
+            .tab_mut(self.active_tab_id)
+            .and_then(|tab| tab.page.as_mut())
+            .and_then(|page| page.file_picker.poll());
+        // Canceling the dialog keeps the files chosen before
+        let Some((node_id, Some(files))) = closed else {
+            return;
+        };
+        if let Some(tab) = self.active_tab() {
+            tab.form_state.borrow_mut().set_files(node_id, files);
+        }
+        self.dispatch_form_event(node_id, "input");
+        self.dispatch_form_event(node_id, "change");
+        self.needs_redraw = true;
+    }
+
+    /// Submit the form of a submit button, unless one of its controls is
+    /// invalid or a `submit` listener cancels it
+    ///
+    /// Controls are not checked when the form has `novalidate`, or the
+    /// button `formnovalidate`.
+    fn submit_form(&mut self, submit_button_id: NodeId) {
+        let found = self.active_tab().and_then(|t| t.page.as_ref()).and_then(|page| {
+            let dom = page.dom.borrow();
+            let form_id = find_parent_form(&dom, submit_button_id)?;
+            let has = |id: NodeId, name: &str| {
+                dom.get(id).and_then(|n| n.as_element()).is_some_and(|e| e.get_attribute(name).is_some())
+            };
+            Some((form_id, has(form_id, "novalidate") || has(submit_button_id, "formnovalidate")))
+        });
+        let Some((form_id, no_validate)) = found else {
+            log::warn!("Submit button has no parent form");
+            return;
+        };
+
+        if !no_validate && !self.validate_form(form_id) {
+            return;
+        }
+        if self.dispatch_form_event(form_id, "submit") {
+            self.send_form(form_id);
+        }
+    }
+
+    /// Check the controls of a form before it is sent, false when one is
+    /// invalid
+    ///
+    /// Invalid controls hear `invalid` and are shown as invalid; the first
+    /// one no listener cancels the event for is focused with its message.
+    fn validate_form(&mut self, form_id: NodeId) -> bool {
+        let invalid: Vec<(NodeId, String)> = match self.active_tab() {
+            Some(tab) => match tab.page.as_ref() {
+                Some(page) => {
+                    let dom = page.dom.borrow();
+                    let form_state = tab.form_state.borrow();
+                    validated_controls(&dom, form_id)
+                        .into_iter()
+                        .filter_map(|id| validation_message(&dom, &form_state, id).map(|message| (id, message)))
+                        .collect()
+                }
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        if invalid.is_empty() {
+            return true;
+        }
+
+        log::info!("Form not sent, {} of its controls are invalid", invalid.len());
+        let mut reported = None;
+        for (node_id, message) in invalid {
+            if let Some(tab) = self.active_tab() {
+                tab.form_state.borrow_mut().mark_user_invalid(node_id);
             }
-            tab.form_state.set_checked(node_id, true);
+            if self.dispatch_form_event(node_id, "invalid") && reported.is_none() {
+                reported = Some((node_id, message));
+            }
+        }
+        if let Some((node_id, message)) = reported {
+            self.report_invalid(node_id, message);
         }
+        self.needs_redraw = true;
+        false
     }
 
-    /// Submit a form
-    fn submit_form(&mut self, submit_button_id: NodeId) {
+    /// Focus an invalid control, scrolled into view, with its message
+    /// shown under it
+    fn report_invalid(&mut self, node_id: NodeId, message: String) {
+        self.focus_element(node_id);
+        self.validation_bubble = Some(ValidationBubble {
+            node_id,
+            message,
+            expires: Instant::now() + VALIDATION_BUBBLE_DURATION,
+        });
+        self.needs_redraw = true;
+    }
+
+    /// Send the data of a form to its action, navigating there
+    fn send_form(&mut self, form_id: NodeId) {
         let active_id = self.active_tab_id;
 
         // Collect form data
@@ -1826,15 +4851,6 @@ impl Browser {
             if let Some(ref page) = tab.page {
                 let dom = page.dom.borrow();
 
-                // Find the parent form
-                let form_id = match find_parent_form(&dom, submit_button_id) {
-                    Some(id) => id,
-                    None => {
-                        log::warn!("Submit button has no parent form");
-                        return;
-                    }
-                };
-
                 // Get form attributes
                 let (action, method) = if let Some(node) = dom.get(form_id) {
                     if let Some(elem) = node.as_element() {
@@ -1848,15 +4864,20 @@ impl Browser {
                 } else {
                     ("".to_string(), "get".to_string())
                 };
+                // Files are only uploaded by POST forms that ask for it
+                let is_multipart = method == "post"
+                    && dom.get(form_id).and_then(|n| n.as_element()).and_then(|e| e.get_attribute("enctype"))
+                        .is_some_and(|enctype| enctype.eq_ignore_ascii_case("multipart/form-data"));
 
                 // Collect form data
-                let fields = collect_form_data(&dom, form_id, &tab.form_state);
+                let fields = collect_form_data(&dom, form_id, &tab.form_state.borrow());
                 let query_string = build_form_data_string(&fields);
+                let multipart = is_multipart.then_some(fields);
 
                 // Get base URL for resolving action
                 let base_url = page.url.clone();
 
-                Some((action, method, query_string, base_url))
+                Some((action, method, query_string, multipart, base_url))
             } else {
                 None
             }
@@ -1865,7 +4886,7 @@ impl Browser {
         };
 
         // Now perform navigation (outside the borrow)
-        if let Some((action, method, query_string, base_url)) = submit_data {
+        if let Some((action, method, query_string, multipart, base_url)) = submit_data {
             log::info!("Form submit: action={}, method={}, data={}", action, method, query_string);
 
             if method == "get" {
@@ -1907,7 +4928,14 @@ impl Browser {
                     }
                 };
 
-                if let Err(e) = self.navigate_post(&target_url, &query_string) {
+                let result = match multipart {
+                    Some(fields) => match build_multipart(&fields) {
+                        Ok(form) => self.post_and_load(&target_url, &PostBody::Multipart(form)),
+                        Err(e) => Err(format!("Failed to read a file to upload: {}", e)),
+                    },
+                    None => self.navigate_post(&target_url, &query_string),
+                };
+                if let Err(e) = result {
                     log::error!("Form POST submission failed: {}", e);
                 }
             }
@@ -1916,6 +4944,14 @@ impl Browser {
 
     /// Handle mouse movement (for cursor changes on link hover and :hover transitions)
     fn handle_mouse_move(&mut self, x: f32, y: f32) {
+        if let Some(drag) = self.scrollbar_drag {
+            self.drag_scrollbar(drag, y);
+            return;
+        }
+        if let Some(menu) = &mut self.context_menu {
+            self.needs_redraw |= menu.hover(x, y);
+        }
+
         // Update cursor for links
         let is_over_link = self.is_over_link(x, y);
 
@@ -1927,9 +4963,12 @@ impl Browser {
 
         if desired_cursor != self.current_cursor {
             self.current_cursor = desired_cursor;
-            self.backend.set_cursor(desired_cursor);
+            self.backend.borrow_mut().set_cursor(desired_cursor);
         }
 
+        // Where the link goes, in the status overlay
+        self.update_link_status(x, y);
+
         // Track hovered element for :hover CSS transitions
         let new_hovered = self.get_element_at(x, y);
 
@@ -1950,7 +4989,7 @@ impl Browser {
         if let Some(tab) = self.active_tab() {
             if let Some(ref page) = tab.page {
                 let content_y = (y - CHROME_HEIGHT) + page.scroll_y;
-                hit_test_regions(&page.hit_regions, x, content_y)
+                hit_test_regions(&page.hit_regions, x, content_y, page.scroll_y)
                     .map(|id| NodeId(id))
             } else {
                 None
@@ -2014,6 +5053,39 @@ impl Browser {
         }
     }
 
+    /// Start the transitions of `:focus` rules as focus moves from one
+    /// element of the page to another
+    fn handle_focus_change(&mut self, old_focused: Option<NodeId>, new_focused: Option<NodeId>) {
+        if old_focused == new_focused {
+            return;
+        }
+        let Some(page) = self.active_tab().and_then(|tab| tab.page.as_ref()) else {
+            return;
+        };
+        let (dom_rc, cascade) = (page.dom.clone(), page.cascade.clone());
+        let dom = dom_rc.borrow();
+
+        // What is hovered stays hovered
+        let hovered = match self.hovered_element {
+            Some(id) => MatchingContext::with_hover(&dom, id),
+            None => MatchingContext::new(),
+        };
+        let old_context = MatchingContext { focused: old_focused, ..hovered.clone() };
+        let new_context = MatchingContext { focused: new_focused, ..hovered };
+
+        for element_id in old_focused.into_iter().chain(new_focused) {
+            self.check_element_transitions(
+                element_id,
+                &dom,
+                &cascade,
+                &old_context,
+                &new_context,
+                self.config.width as f32,
+                self.config.height as f32,
+            );
+        }
+    }
+
     /// Collect all elements affected by a hover change (both old and new hover chains)
     fn collect_affected_hover_elements(
         &self,
@@ -2109,7 +5181,7 @@ impl Browser {
         // Check each transition definition
         for transition_def in transition_defs {
             let properties_to_check = if transition_def.property == "all" {
-                vec!["opacity", "width", "height"]
+                vec!["opacity", "width", "height", "transform"]
             } else {
                 vec![transition_def.property.as_str()]
             };
@@ -2135,6 +5207,18 @@ impl Browser {
                         let new_h = new_value.and_then(|v| StyleResolver::resolve_length(&v, &context)).unwrap_or(0.0);
                         (old_h, new_h)
                     }
+                    "transform" => {
+                        // Runs from 0 to 1 between the two transforms
+                        let resolve = |value: Option<gugalanna_css::CssValue>| {
+                            value.and_then(|v| StyleResolver::resolve_transform(&v, &context)).unwrap_or_default()
+                        };
+                        let (from, to) = (resolve(old_value), resolve(new_value));
+                        if from == to {
+                            continue;
+                        }
+                        self.transform_transitions.insert(element_id.0 as usize, (from, to));
+                        (0.0, 1.0)
+                    }
                     _ => continue,
                 };
 
@@ -2165,7 +5249,7 @@ impl Browser {
             if let Some(ref page) = tab.page {
                 let content_y = (y - CHROME_HEIGHT) + page.scroll_y;
 
-                if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y) {
+                if let Some(node_id) = hit_test_regions(&page.hit_regions, x, content_y, page.scroll_y) {
                     let dom_ref = page.dom.borrow();
                     let result = find_anchor_href(&dom_ref, gugalanna_dom::NodeId(node_id));
                     if result.is_some() {
@@ -2180,22 +5264,34 @@ impl Browser {
 
     /// Render the browser
     fn render(&mut self) {
-        // Clear with white
-        self.backend.clear(RenderColor::white());
+        // A dialog was painted over the last frame, and may have stopped
+        // text input
+        if self.dialogs.take_shown() {
+            self.painted_frame = None;
+            self.needs_redraw = true;
+            if matches!(self.focus, FocusTarget::AddressBar | FocusTarget::FindBar | FocusTarget::FormInput(_)) {
+                start_text_input();
+            }
+        }
+
+        // Nothing changed since the last frame
+        if !std::mem::take(&mut self.needs_redraw) {
+            return;
+        }
 
         // Render chrome
-        let chrome_display_list = self.chrome.build_display_list();
-        self.backend.render(&chrome_display_list);
+        let mut frame = self.chrome.build_display_list();
 
         // Render page content (offset by chrome height and scroll)
-        // Clone the display list, scroll_y, and form state to avoid borrow issues
+        // Share the display list and clone scroll_y and form state to avoid borrow issues
         let page_data = self
             .active_tab()
             .map(|t| {
                 let display_list = t.page.as_ref().map(|p| p.display_list.clone());
                 let scroll_y = t.page.as_ref().map(|p| p.scroll_y).unwrap_or(0.0);
+                let scroll_offsets = t.page.as_ref().map(|p| p.scroll_offsets.clone()).unwrap_or_default();
                 let form_state = t.form_state.clone();
-                (display_list, scroll_y, form_state)
+                (display_list, scroll_y, scroll_offsets, form_state)
             });
 
         // Get the focused form node_id if any
@@ -2204,66 +5300,78 @@ impl Browser {
             _ => None,
         };
 
-        if let Some((Some(display_list), scroll_y, form_state)) = page_data {
-            self.render_page(&display_list, scroll_y, &form_state, focused_form_node);
+        if let Some((Some(display_list), scroll_y, scroll_offsets, form_state)) = page_data {
+            self.render_page(&mut frame, &display_list, scroll_y, &scroll_offsets, &form_state.borrow(), focused_form_node);
+            if let Some(page) = self.active_tab_mut().and_then(|t| t.page.as_mut()) {
+                page.scale_images(&mut frame);
+            }
+            self.render_scrollbars(&mut frame);
+        }
+        frame.commands.extend(self.chrome.build_find_bar_display_list().commands);
+        frame.commands.extend(self.chrome.build_toast_display_list().commands);
+        let page_bottom = CHROME_HEIGHT + self.page_viewport_height();
+        frame.commands.extend(self.chrome.build_status_display_list(page_bottom).commands);
+        if let Some(menu) = &self.context_menu {
+            frame.commands.extend(menu.build_display_list().commands);
         }
 
         // Render element highlighting for DevTools
-        self.render_element_highlight();
+        self.render_element_highlight(&mut frame);
+        self.render_invalid_controls(&mut frame);
+        self.render_focus_ring(&mut frame);
+        self.render_validation_bubble(&mut frame);
 
         // Render DevTools panel (if open)
         if self.devtools.open {
-            // Get console messages from active tab's JS runtime
-            let console_messages = self
-                .active_tab()
-                .and_then(|t| t.page.as_ref())
-                .and_then(|p| p.js_runtime.as_ref())
-                .map(|js| js.get_console_messages())
-                .unwrap_or_default();
-
-            // Network requests (empty for now - will integrate with HttpClient tracking)
-            let network_requests = vec![];
-
-            // Build display list with DOM tree (scope the borrow)
-            let devtools_display_list = {
-                let dom_tree = self
-                    .active_tab()
-                    .and_then(|t| t.page.as_ref())
-                    .map(|p| p.dom.borrow());
-
-                self.devtools.build_display_list(
-                    self.config.height as f32,
-                    &console_messages,
-                    dom_tree.as_deref(),
-                    &network_requests,
-                )
-            };
-            self.backend.render(&devtools_display_list);
+            frame.commands.extend(self.devtools_display_list().commands);
         }
 
-        // Present
-        self.backend.present();
+        // Repaint the part of the window that changed, then present
+        let damage = match &self.painted_frame {
+            Some(previous) => damaged_rect(previous, &frame),
+            None => Some(Rect::new(0.0, 0.0, self.config.width as f32, self.config.height as f32)),
+        };
+        if let Some(damage) = damage {
+            let mut backend = self.backend.borrow_mut();
+            backend.repaint(&frame, damage, RenderColor::white());
+            backend.present();
+        }
+        self.painted_frame = Some(frame);
     }
 
-    /// Render page content with Y offset (chrome height) and scroll offset
+    /// Add page content to a frame with Y offset (chrome height) and scroll offset
     fn render_page(
         &mut self,
+        frame: &mut DisplayList,
         display_list: &DisplayList,
         scroll_y: f32,
+        scroll_offsets: &HashMap<u32, f32>,
         form_state: &crate::form::FormState,
         focused_form_node: Option<NodeId>,
     ) {
-        use gugalanna_layout::Rect;
         use gugalanna_render::PaintCommand;
 
         // Combined offset: chrome pushes content down, scroll moves it up
-        let y_offset = CHROME_HEIGHT - scroll_y;
-        let viewport_bottom = self.config.height as f32;
+        let scrolled_offset = CHROME_HEIGHT - scroll_y;
+        let page_dom = self.active_tab().and_then(|t| t.page.as_ref()).map(|p| p.dom.clone());
+        let viewport_bottom = CHROME_HEIGHT + self.page_viewport_height();
 
         // Offset all commands by combined offset
         let mut offset_commands = Vec::with_capacity(display_list.commands.len());
+        // Offsets of the scroll containers being painted, and where each
+        // fixed box started in that stack
+        let mut inner_scrolls: Vec<f32> = Vec::new();
+        let mut fixed_starts: Vec<usize> = Vec::new();
 
         for cmd in &display_list.commands {
+            // Fixed boxes are only pushed down by the chrome, and only move
+            // with the scroll containers inside them
+            let (page_offset, first_scroll) = match fixed_starts.last() {
+                Some(&start) => (CHROME_HEIGHT, start),
+                None => (scrolled_offset, 0),
+            };
+            let y_offset = page_offset - inner_scrolls[first_scroll..].iter().sum::<f32>();
+
             match cmd {
                 PaintCommand::FillRect { rect, color } => {
                     let mut new_y = rect.y + y_offset;
@@ -2300,6 +5408,8 @@ impl Browser {
                     y,
                     color,
                     font_size,
+                    face,
+                    family,
                 } => {
                     let new_y = *y + y_offset;
                     // Skip if text is off-screen or in chrome area
@@ -2312,12 +5422,15 @@ impl Browser {
                         y: new_y,
                         color: *color,
                         font_size: *font_size,
+                        face: *face,
+                        family: family.clone(),
                     });
                 }
                 PaintCommand::DrawBorder {
                     rect,
                     widths,
-                    color,
+                    styles,
+                    colors,
                 } => {
                     let new_y = rect.y + y_offset;
                     // Skip if off-screen or in chrome area
@@ -2332,7 +5445,8 @@ impl Browser {
                             height: rect.height,
                         },
                         widths: *widths,
-                        color: *color,
+                        styles: *styles,
+                        colors: *colors,
                     });
                 }
                 PaintCommand::DrawTextInput {
@@ -2444,48 +5558,148 @@ impl Browser {
                         is_pressed: *is_pressed,
                     });
                 }
-                PaintCommand::DrawImage { rect, pixels, alt } => {
+                PaintCommand::DrawTextArea {
+                    node_id,
+                    rect,
+                    text,
+                    cursor_pos: _,
+                    font_size,
+                    line_height,
+                    first_line: _,
+                    is_focused: _,
+                } => {
+                    let new_y = rect.y + y_offset;
+                    // Skip if off-screen or in chrome area
+                    if new_y + rect.height < CHROME_HEIGHT || new_y > viewport_bottom || new_y < CHROME_HEIGHT {
+                        continue;
+                    }
+                    // Typed text replaces the initial text once there is some
+                    let is_focused = focused_form_node == Some(*node_id);
+                    let (text, cursor_pos, first_line) = match form_state.get_text(*node_id) {
+                        Some(state) => (state.value.clone(), is_focused.then_some(state.cursor_pos), state.scroll_line),
+                        None => (text.clone(), None, 0),
+                    };
+                    offset_commands.push(PaintCommand::DrawTextArea {
+                        node_id: *node_id,
+                        rect: Rect { y: new_y, ..*rect },
+                        text,
+                        cursor_pos,
+                        font_size: *font_size,
+                        line_height: *line_height,
+                        first_line,
+                        is_focused,
+                    });
+                }
+                PaintCommand::DrawFileInput { node_id, rect, .. } => {
+                    let new_y = rect.y + y_offset;
+                    // Skip if off-screen or in chrome area
+                    if new_y + rect.height < CHROME_HEIGHT || new_y > viewport_bottom || new_y < CHROME_HEIGHT {
+                        continue;
+                    }
+                    offset_commands.push(PaintCommand::DrawFileInput {
+                        node_id: *node_id,
+                        rect: Rect { y: new_y, ..*rect },
+                        label: form_state.file_label(*node_id),
+                    });
+                }
+                PaintCommand::DrawSelect { node_id, rect, text, font_size } => {
+                    let new_y = rect.y + y_offset;
+                    // Skip if off-screen or in chrome area
+                    if new_y + rect.height < CHROME_HEIGHT || new_y > viewport_bottom || new_y < CHROME_HEIGHT {
+                        continue;
+                    }
+                    // An option a script selected replaces the initial one
+                    let text = match (form_state.get_selected_index(*node_id), &page_dom) {
+                        (Some(index), Some(dom)) => usize::try_from(index)
+                            .ok()
+                            .and_then(|i| select_data(&dom.borrow(), *node_id).options.get(i).cloned())
+                            .unwrap_or_default(),
+                        _ => text.clone(),
+                    };
+                    offset_commands.push(PaintCommand::DrawSelect {
+                        node_id: *node_id,
+                        rect: Rect { y: new_y, ..*rect },
+                        text,
+                        font_size: *font_size,
+                    });
+                }
+                PaintCommand::DrawImage { rect, pixels, alt, clip, rendering } => {
                     let new_y = rect.y + y_offset;
                     // Skip if off-screen or in chrome area
                     if new_y + rect.height < CHROME_HEIGHT || new_y > viewport_bottom {
                         continue;
                     }
 
-                    // Clip to chrome area
-                    let mut clipped_y = new_y;
-                    let mut clipped_height = rect.height;
-                    if clipped_y < CHROME_HEIGHT {
-                        let clip_amount = CHROME_HEIGHT - clipped_y;
-                        clipped_y = CHROME_HEIGHT;
-                        clipped_height -= clip_amount;
-                        if clipped_height <= 0.0 {
-                            continue;
-                        }
+                    // Keep the image out of the chrome area
+                    let viewport = Rect::new(rect.x, CHROME_HEIGHT, rect.width, viewport_bottom - CHROME_HEIGHT);
+                    let clip = match clip {
+                        Some(clip) => Rect { y: clip.y + y_offset, ..*clip }.intersect(&viewport),
+                        None => viewport,
+                    };
+                    if clip.is_empty() {
+                        continue;
                     }
 
                     offset_commands.push(PaintCommand::DrawImage {
                         rect: Rect {
                             x: rect.x,
-                            y: clipped_y,
+                            y: new_y,
                             width: rect.width,
-                            height: clipped_height,
+                            height: rect.height,
                         },
                         pixels: pixels.clone(),
                         alt: alt.clone(),
+                        clip: Some(clip),
+                        rendering: *rendering,
+                    });
+                }
+                PaintCommand::DrawTiledImage { tile, pixels, repeat, clip, rendering } => {
+                    // Keep the tiles out of the chrome area
+                    let viewport = Rect::new(clip.x, CHROME_HEIGHT, clip.width, viewport_bottom - CHROME_HEIGHT);
+                    let clip = Rect { y: clip.y + y_offset, ..*clip }.intersect(&viewport);
+                    if clip.is_empty() {
+                        continue;
+                    }
+
+                    offset_commands.push(PaintCommand::DrawTiledImage {
+                        tile: Rect { y: tile.y + y_offset, ..*tile },
+                        pixels: pixels.clone(),
+                        repeat: *repeat,
+                        clip,
+                        rendering: *rendering,
                     });
                 }
-                PaintCommand::SetClipRect(rect) => {
+                PaintCommand::PushClip(rect) => {
                     // Offset the clip rect for scroll position
                     let new_y = rect.y + y_offset;
-                    offset_commands.push(PaintCommand::SetClipRect(Rect {
+                    let rect = Rect {
                         x: rect.x,
                         y: new_y,
                         width: rect.width,
                         height: rect.height,
-                    }));
+                    };
+                    offset_commands.push(PaintCommand::PushClip(rect));
+                }
+                PaintCommand::PopClip => {
+                    offset_commands.push(PaintCommand::PopClip);
                 }
-                PaintCommand::ClearClipRect => {
-                    offset_commands.push(PaintCommand::ClearClipRect);
+                PaintCommand::PushTransform(transform) => {
+                    // Transformed content may be moved over the chrome, it is
+                    // kept below it once composited. Commands inside are
+                    // culled where they are laid out.
+                    let viewport = Rect::new(0.0, CHROME_HEIGHT, self.config.width as f32, viewport_bottom - CHROME_HEIGHT);
+                    offset_commands.push(PaintCommand::PushClip(viewport));
+
+                    // The transform moves page coordinates, which are offset
+                    // by the scroll position here
+                    let moved = Transform::translate(0.0, -y_offset)
+                        .then(transform)
+                        .then(&Transform::translate(0.0, y_offset));
+                    offset_commands.push(PaintCommand::PushTransform(moved));
+                }
+                PaintCommand::PopTransform => {
+                    offset_commands.push(PaintCommand::PopTransform);
+                    offset_commands.push(PaintCommand::PopClip);
                 }
                 PaintCommand::PushOpacity(opacity) => {
                     offset_commands.push(PaintCommand::PushOpacity(*opacity));
@@ -2493,12 +5707,23 @@ impl Browser {
                 PaintCommand::PopOpacity => {
                     offset_commands.push(PaintCommand::PopOpacity);
                 }
-                PaintCommand::DrawBoxShadow { rect, shadow } => {
+                PaintCommand::BeginFixed => {
+                    fixed_starts.push(inner_scrolls.len());
+                }
+                PaintCommand::EndFixed => {
+                    fixed_starts.pop();
+                }
+                PaintCommand::BeginScroll(node_id) => {
+                    inner_scrolls.push(scroll_offsets.get(&node_id.0).copied().unwrap_or(0.0));
+                }
+                PaintCommand::EndScroll => {
+                    inner_scrolls.pop();
+                }
+                PaintCommand::DrawBoxShadow { rect, radius, shadow } => {
                     let new_y = rect.y + y_offset;
-                    // Skip if off-screen
-                    if new_y + rect.height + shadow.blur_radius + shadow.spread_radius < CHROME_HEIGHT
-                        || new_y - shadow.blur_radius - shadow.spread_radius > viewport_bottom
-                    {
+                    // Skip if the blurred shadow is off-screen
+                    let bounds = command_bounds(cmd).unwrap_or(*rect);
+                    if bounds.bottom() + y_offset < CHROME_HEIGHT || bounds.y + y_offset > viewport_bottom {
                         continue;
                     }
                     offset_commands.push(PaintCommand::DrawBoxShadow {
@@ -2508,6 +5733,7 @@ impl Browser {
                             width: rect.width,
                             height: rect.height,
                         },
+                        radius: *radius,
                         shadow: shadow.clone(),
                     });
                 }
@@ -2528,7 +5754,7 @@ impl Browser {
                         color: *color,
                     });
                 }
-                PaintCommand::DrawRoundedBorder { rect, radius, widths, color } => {
+                PaintCommand::DrawRoundedBorder { rect, radius, widths, styles, colors } => {
                     let new_y = rect.y + y_offset;
                     // Skip if off-screen or in chrome area
                     if new_y + rect.height < CHROME_HEIGHT || new_y > viewport_bottom {
@@ -2543,7 +5769,8 @@ impl Browser {
                         },
                         radius: *radius,
                         widths: *widths,
-                        color: *color,
+                        styles: *styles,
+                        colors: *colors,
                     });
                 }
                 PaintCommand::FillLinearGradient { rect, direction, stops, radius } => {
@@ -2585,18 +5812,86 @@ impl Browser {
                         radius: *radius,
                     });
                 }
+                PaintCommand::FillSelection { rects, color } => {
+                    let rects: Vec<Rect> = rects
+                        .iter()
+                        .map(|rect| Rect { y: rect.y + y_offset, ..*rect })
+                        .filter(|rect| rect.y + rect.height >= CHROME_HEIGHT && rect.y <= viewport_bottom)
+                        .collect();
+                    // Skip if every highlighted line is off-screen
+                    if rects.is_empty() {
+                        continue;
+                    }
+                    offset_commands.push(PaintCommand::FillSelection { rects, color: *color });
+                }
+            }
+        }
+
+        frame.commands.extend(offset_commands);
+    }
+
+    /// Add the scrollbars of the page and its scroll containers to a frame,
+    /// over the page content
+    fn render_scrollbars(&mut self, frame: &mut DisplayList) {
+        use gugalanna_render::PaintCommand;
+
+        let width = self.config.width as f32;
+        let Some(page) = self.active_tab().and_then(|t| t.page.as_ref()) else {
+            return;
+        };
+
+        // Bars of scroll containers partly under the chrome stay out of it
+        let viewport = Rect::new(0.0, CHROME_HEIGHT, width, page.viewport_height);
+        frame.commands.push(PaintCommand::PushClip(viewport));
+        for (_, bar) in page.scrollbars(width) {
+            if !bar.track.intersect(&viewport).is_empty() {
+                bar.paint(&mut frame.commands);
             }
         }
+        frame.commands.push(PaintCommand::PopClip);
+    }
 
-        let offset_list = DisplayList {
-            commands: offset_commands,
+    /// Build the DevTools panel for the active page
+    fn devtools_display_list(&self) -> DisplayList {
+        // Get console messages from active tab's JS runtime
+        let console_messages = self
+            .active_tab()
+            .and_then(|t| t.page.as_ref())
+            .and_then(|p| p.js_runtime.as_ref())
+            .map(|js| js.get_console_messages())
+            .unwrap_or_default();
+
+        // Network requests (empty for now - will integrate with HttpClient tracking)
+        let network_requests = vec![];
+
+        // Computed style of the element selected in the Elements panel
+        let selected_style: Vec<(&str, String)> = match (
+            self.devtools.selected_element,
+            self.active_tab().and_then(|t| t.page.as_ref()).and_then(|p| p.js_runtime.as_ref()),
+        ) {
+            (Some(node_id), Some(rt)) => INSPECTED_PROPERTIES
+                .iter()
+                .filter_map(|&property| rt.computed_style(node_id, property).map(|value| (property, value)))
+                .collect(),
+            _ => Vec::new(),
         };
-        self.backend.render(&offset_list);
+
+        // Build display list with DOM tree
+        let dom_tree = self.active_tab().and_then(|t| t.page.as_ref()).map(|p| p.dom.borrow());
+        self.devtools.build_display_list(
+            self.config.height as f32,
+            &console_messages,
+            dom_tree.as_deref(),
+            &network_requests,
+            &selected_style,
+        )
     }
 
-    /// Render element highlighting for DevTools (selected element or hover in selector mode)
-    fn render_element_highlight(&mut self) {
-        use gugalanna_layout::Rect;
+    /// Add element highlighting for DevTools to a frame (selected element or hover in selector mode)
+    ///
+    /// The element's margin, border, padding and content boxes are shaded
+    /// over the page.
+    fn render_element_highlight(&mut self, frame: &mut DisplayList) {
         use gugalanna_render::PaintCommand;
 
         // Determine which element to highlight
@@ -2610,135 +5905,430 @@ impl Browser {
             None
         };
 
-        let highlight_node = match highlight_node {
-            Some(n) => n,
-            None => return,
+        let Some(highlight_node) = highlight_node else {
+            return;
         };
-
-        // Get element bounds from hit regions
-        let bounds = self.active_tab().and_then(|tab| {
-            tab.page.as_ref().and_then(|page| {
-                page.hit_regions
-                    .iter()
-                    .find(|r| r.node_id == highlight_node.0)
-                    .map(|r| {
-                        let y_offset = CHROME_HEIGHT - page.scroll_y;
-                        (r.x, r.y + y_offset, r.width, r.height)
-                    })
-            })
-        });
-
-        let (x, y, width, height) = match bounds {
-            Some(b) => b,
-            None => return,
+        let Some(page) = self.active_tab().and_then(|tab| tab.page.as_ref()) else {
+            return;
         };
-
-        // Don't draw if entirely outside visible area
-        if y + height < CHROME_HEIGHT || y > self.config.height as f32 {
+        let Some(model) = page.box_models.get(&highlight_node) else {
             return;
+        };
+
+        // Fixed boxes don't move with the page
+        let y_offset = if model.fixed { CHROME_HEIGHT } else { CHROME_HEIGHT - page.scroll_y };
+        let viewport = Rect::new(0.0, CHROME_HEIGHT, self.config.width as f32, page.viewport_height);
+        frame.commands.push(PaintCommand::PushClip(viewport));
+        for (rect, color) in box_model_overlays(model) {
+            let rect = Rect::new(rect.x, rect.y + y_offset, rect.width, rect.height);
+            if !rect.intersect(&viewport).is_empty() {
+                frame.commands.push(PaintCommand::FillRect { rect, color });
+            }
         }
+        frame.commands.push(PaintCommand::PopClip);
+    }
 
-        // Build highlight display list
-        let mut commands = Vec::new();
+    /// Add a ring around the link or control Tab moved focus to
+    fn render_focus_ring(&self, frame: &mut DisplayList) {
+        use gugalanna_render::{BorderColors, BorderStyles, BorderWidths, PaintCommand};
 
-        // Semi-transparent overlay
-        let overlay_color = if self.devtools.element_selector_active {
-            RenderColor::new(74, 144, 226, 60) // Blue for selector mode
-        } else {
-            RenderColor::new(74, 144, 226, 40) // Lighter blue for selected
+        let FocusTarget::Element(node_id) = self.focus else {
+            return;
+        };
+        let Some(page) = self.active_tab().and_then(|tab| tab.page.as_ref()) else {
+            return;
+        };
+        let Some(model) = page.box_models.get(&node_id) else {
+            return;
         };
 
-        commands.push(PaintCommand::FillRect {
-            rect: Rect { x, y, width, height },
-            color: overlay_color,
+        let y_offset = if model.fixed { CHROME_HEIGHT } else { CHROME_HEIGHT - page.scroll_y };
+        let border = model.border;
+        let ring = Rect::new(
+            border.x - FOCUS_RING_WIDTH,
+            border.y + y_offset - FOCUS_RING_WIDTH,
+            border.width + FOCUS_RING_WIDTH * 2.0,
+            border.height + FOCUS_RING_WIDTH * 2.0,
+        );
+        let viewport = Rect::new(0.0, CHROME_HEIGHT, self.config.width as f32, page.viewport_height);
+        frame.commands.push(PaintCommand::PushClip(viewport));
+        frame.commands.push(PaintCommand::DrawBorder {
+            rect: ring,
+            widths: BorderWidths {
+                top: FOCUS_RING_WIDTH,
+                right: FOCUS_RING_WIDTH,
+                bottom: FOCUS_RING_WIDTH,
+                left: FOCUS_RING_WIDTH,
+            },
+            styles: BorderStyles::default(),
+            colors: BorderColors::all(RenderColor::rgb(66, 133, 244)),
         });
+        frame.commands.push(PaintCommand::PopClip);
+    }
 
-        // Border
-        let border_color = RenderColor::new(74, 144, 226, 200);
-        let border_width = 2.0;
+    /// Paint a red border around the controls a form was not sent for,
+    /// for as long as they stay invalid
+    fn render_invalid_controls(&self, frame: &mut DisplayList) {
+        use gugalanna_render::{BorderColors, BorderStyles, BorderWidths, PaintCommand};
 
-        // Top border
-        commands.push(PaintCommand::FillRect {
-            rect: Rect { x, y, width, height: border_width },
-            color: border_color,
-        });
-        // Bottom border
-        commands.push(PaintCommand::FillRect {
-            rect: Rect { x, y: y + height - border_width, width, height: border_width },
-            color: border_color,
-        });
-        // Left border
-        commands.push(PaintCommand::FillRect {
-            rect: Rect { x, y, width: border_width, height },
-            color: border_color,
+        let Some(tab) = self.active_tab() else {
+            return;
+        };
+        let Some(page) = tab.page.as_ref() else {
+            return;
+        };
+        let dom = page.dom.borrow();
+        let form_state = tab.form_state.borrow();
+        let viewport = Rect::new(0.0, CHROME_HEIGHT, self.config.width as f32, page.viewport_height);
+        frame.commands.push(PaintCommand::PushClip(viewport));
+        for node_id in form_state.user_invalid() {
+            let Some(model) = page.box_models.get(&node_id) else {
+                continue;
+            };
+            if validation_message(&dom, &form_state, node_id).is_none() {
+                continue;
+            }
+            let y_offset = if model.fixed { CHROME_HEIGHT } else { CHROME_HEIGHT - page.scroll_y };
+            frame.commands.push(PaintCommand::DrawBorder {
+                rect: Rect { y: model.border.y + y_offset, ..model.border },
+                widths: BorderWidths {
+                    top: INVALID_BORDER_WIDTH,
+                    right: INVALID_BORDER_WIDTH,
+                    bottom: INVALID_BORDER_WIDTH,
+                    left: INVALID_BORDER_WIDTH,
+                },
+                styles: BorderStyles::default(),
+                colors: BorderColors::all(RenderColor::rgb(220, 38, 38)),
+            });
+        }
+        frame.commands.push(PaintCommand::PopClip);
+    }
+
+    /// Paint the message of an invalid control in a bubble under it
+    fn render_validation_bubble(&self, frame: &mut DisplayList) {
+        use gugalanna_render::{BorderColors, BorderStyles, BorderWidths, FontFace, PaintCommand};
+
+        let Some(bubble) = &self.validation_bubble else {
+            return;
+        };
+        let Some(page) = self.active_tab().and_then(|tab| tab.page.as_ref()) else {
+            return;
+        };
+        let Some(model) = page.box_models.get(&bubble.node_id) else {
+            return;
+        };
+
+        const PADDING: f32 = 8.0;
+        const HEIGHT: f32 = 30.0;
+        let y_offset = if model.fixed { CHROME_HEIGHT } else { CHROME_HEIGHT - page.scroll_y };
+        let window_width = self.config.width as f32;
+        let width = (bubble.message.chars().count() as f32 * 7.0 + PADDING * 2.0).min(window_width);
+        let rect = Rect::new(
+            model.border.x.min(window_width - width).max(0.0),
+            model.border.y + model.border.height + y_offset + 4.0,
+            width,
+            HEIGHT,
+        );
+        frame.commands.push(PaintCommand::FillRect { rect, color: RenderColor::rgb(255, 255, 255) });
+        frame.commands.push(PaintCommand::DrawBorder {
+            rect,
+            widths: BorderWidths { top: 1.0, right: 1.0, bottom: 1.0, left: 1.0 },
+            styles: BorderStyles::default(),
+            colors: BorderColors::all(RenderColor::rgb(160, 160, 160)),
         });
-        // Right border
-        commands.push(PaintCommand::FillRect {
-            rect: Rect { x: x + width - border_width, y, width: border_width, height },
-            color: border_color,
+        frame.commands.push(PaintCommand::DrawText {
+            text: bubble.message.clone(),
+            x: rect.x + PADDING,
+            y: rect.y + HEIGHT / 2.0 - 7.0,
+            color: RenderColor::rgb(40, 40, 40),
+            font_size: 13.0,
+            face: FontFace::REGULAR,
+            family: None,
         });
-
-        let highlight_list = DisplayList { commands };
-        self.backend.render(&highlight_list);
     }
 }
 
 /// Build hit regions from layout tree
 fn build_hit_regions(layout: &LayoutBox) -> Vec<HitRegion> {
+    // Regions follow paint order so the topmost box is hit first
     let mut regions = Vec::new();
-    build_hit_regions_recursive(layout, &mut regions, 0.0, 0.0);
+    // Scroll container each scroll container moves with; containers are
+    // painted before their content
+    let mut outer_scrollers: HashMap<NodeId, Option<NodeId>> = HashMap::new();
+    for painted in boxes_in_paint_order(layout) {
+        let mut scrollers = Vec::new();
+        let mut scroller = painted.scroller;
+        while let Some(id) = scroller {
+            scrollers.push(id.0);
+            scroller = outer_scrollers.get(&id).copied().flatten();
+        }
+        if let Some(id) = scroll_container_id(painted.layout_box) {
+            outer_scrollers.insert(id, painted.scroller);
+        }
+        push_hit_regions(painted, scrollers, &mut regions);
+    }
     regions
 }
 
-fn build_hit_regions_recursive(layout: &LayoutBox, regions: &mut Vec<HitRegion>, offset_x: f32, offset_y: f32) {
+fn push_hit_regions(painted: PaintedBox, scrollers: Vec<u32>, regions: &mut Vec<HitRegion>) {
+    let layout = painted.layout_box;
     let d = &layout.dimensions;
+    let fixed = painted.fixed || layout.is_fixed_positioned();
 
     // Calculate absolute position of this box's content area
-    let abs_x = offset_x + d.content.x;
-    let abs_y = offset_y + d.content.y;
+    let abs_x = painted.offset_x + d.content.x;
+    let abs_y = painted.offset_y + d.content.y;
 
     // Get node ID from box type
     let node_id = match &layout.box_type {
         BoxType::Block(id, _) => Some(id.0),
         BoxType::Inline(id, _) => Some(id.0),
+        BoxType::InlineBlock(id, _) => Some(id.0),
         BoxType::Text(id, _, _) => Some(id.0),
         BoxType::Input(id, _, _) => Some(id.0),
         BoxType::Button(id, _, _) => Some(id.0),
         BoxType::Image(id, _, _) => Some(id.0),
-        BoxType::AnonymousBlock | BoxType::AnonymousInline => None,
+        BoxType::TextArea(id, _, _) => Some(id.0),
+        BoxType::Select(id, _, _) => Some(id.0),
+        BoxType::TableCell(id, _, _) => Some(id.0),
+        BoxType::LineBreak(id, _) => Some(id.0),
+        BoxType::AnonymousBlock | BoxType::AnonymousInline | BoxType::AnonymousTable => None,
+    };
+
+    let Some(node_id) = node_id else {
+        return;
+    };
+
+    // Boxes flattened onto a line can't be hit
+    let inverse_transform = match painted.transform {
+        Some(transform) => match transform.inverse() {
+            Some(inverse) => Some(inverse),
+            None => return,
+        },
+        None => None,
+    };
+
+    // Scroll containers can move their content by how far it reaches past
+    // the padding box
+    let scroll_range = match (layout.is_scroll_container(), layout.scroll_size) {
+        (true, Some((_, height))) => Some((height - d.padding_box().height).max(0.0)),
+        _ => None,
     };
 
-    if let Some(id) = node_id {
-        if d.content.width > 0.0 && d.content.height > 0.0 {
+    // Content clipped by an overflow box can't be hit outside of it, but
+    // may be scrolled into view
+    let mut push = |rect: Rect| {
+        let visible = match painted.clip {
+            Some(clip) => !rect.intersect(&clip).is_empty() || !scrollers.is_empty(),
+            None => true,
+        };
+        if !rect.is_empty() && visible {
             regions.push(HitRegion {
-                x: abs_x,
-                y: abs_y,
-                width: d.content.width,
-                height: d.content.height,
-                node_id: id,
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                height: rect.height,
+                node_id,
+                fixed,
+                clip: painted.clip,
+                scrollers: scrollers.clone(),
+                scroll_range,
+                inverse_transform,
             });
         }
-    }
+    };
 
-    // Process children - they are positioned relative to this box's content area
-    for child in &layout.children {
-        build_hit_regions_recursive(child, regions, abs_x, abs_y);
+    if let BoxType::Text(_, _, _) = &layout.box_type {
+        // Wrapped text is only hit on the lines it occupies
+        for fragment in &layout.fragments {
+            push(Rect::new(
+                abs_x + fragment.rect.x,
+                abs_y + fragment.rect.y,
+                fragment.rect.width,
+                fragment.rect.height,
+            ));
+        }
+    } else {
+        push(Rect::new(abs_x, abs_y, d.content.width, d.content.height));
     }
 }
 
-/// Hit test hit regions
-fn hit_test_regions(regions: &[HitRegion], x: f32, y: f32) -> Option<u32> {
+/// Check if a region is hit at a point in page coordinates
+fn region_contains(region: &HitRegion, x: f32, y: f32, scroll_y: f32) -> bool {
+    // Fixed regions don't move with the page
+    let y = if region.fixed { y - scroll_y } else { y };
+    let (x, y) = region.inverse_transform.map_or((x, y), |inverse| inverse.apply(x, y));
+    let inside = |r: Rect| x >= r.x && x <= r.right() && y >= r.y && y <= r.bottom();
+    inside(Rect::new(region.x, region.y, region.width, region.height)) && region.clip.is_none_or(inside)
+}
+
+/// Hit test hit regions at a point in page coordinates
+fn hit_test_regions(regions: &[HitRegion], x: f32, y: f32, scroll_y: f32) -> Option<u32> {
     // Test in reverse order (later elements are on top)
-    for region in regions.iter().rev() {
-        if x >= region.x
-            && x <= region.x + region.width
-            && y >= region.y
-            && y <= region.y + region.height
-        {
-            return Some(region.node_id);
+    regions
+        .iter()
+        .rev()
+        .find(|region| region_contains(region, x, y, scroll_y))
+        .map(|region| region.node_id)
+}
+
+/// Innermost scroll container under a point in page coordinates that can
+/// still scroll by `delta` (positive scrolls up), with its scroll range
+///
+/// `None` when nothing under the point can, and the page scrolls instead.
+fn scroll_target(
+    regions: &[HitRegion],
+    offsets: &HashMap<u32, f32>,
+    x: f32,
+    y: f32,
+    scroll_y: f32,
+    delta: f32,
+) -> Option<(u32, f32)> {
+    let hit = regions.iter().rev().find(|region| region_contains(region, x, y, scroll_y))?;
+
+    // The hit box itself if it scrolls, then the containers around it
+    let own = hit.scroll_range.map(|_| hit.node_id);
+    own.into_iter().chain(hit.scrollers.iter().copied()).find_map(|scroller| {
+        let range = regions
+            .iter()
+            .find(|r| r.node_id == scroller && r.scroll_range.is_some())
+            .and_then(|r| r.scroll_range)?;
+        let offset = offsets.get(&scroller).copied().unwrap_or(0.0);
+        let can_move = if delta > 0.0 { offset > 0.0 } else { offset < range };
+        can_move.then_some((scroller, range))
+    })
+}
+
+/// Move the regions inside a scroll container after its scroll offset
+/// changed by `delta`
+fn scroll_hit_regions(regions: &mut [HitRegion], scroller: u32, delta: f32) {
+    for region in regions.iter_mut() {
+        let Some(depth) = region.scrollers.iter().position(|&s| s == scroller) else {
+            continue;
+        };
+        region.y -= delta;
+
+        // The clip comes from the innermost container, which only moves
+        // with the ones further out
+        if depth > 0 {
+            if let Some(clip) = region.clip.as_mut() {
+                clip.y -= delta;
+            }
         }
     }
-    None
+}
+
+/// Apply the scroll offsets of inner scroll containers to freshly built
+/// hit regions, clamping them to the containers' new scroll ranges
+fn restore_scroll_offsets(regions: &mut [HitRegion], offsets: &mut HashMap<u32, f32>) {
+    let ranges: HashMap<u32, f32> = regions
+        .iter()
+        .filter_map(|r| r.scroll_range.map(|range| (r.node_id, range)))
+        .collect();
+
+    // Containers that are gone lose their offset
+    offsets.retain(|id, _| ranges.contains_key(id));
+    for (id, offset) in offsets.iter_mut() {
+        *offset = offset.clamp(0.0, ranges[id]);
+        scroll_hit_regions(regions, *id, *offset);
+    }
+}
+
+/// Hand a page's scripts the current entry of its tab's history
+fn attach_history(navigation: &NavigationState, rt: &JsRuntime) {
+    if let Some(url) = navigation.current_url() {
+        rt.set_history(url, navigation.current_state(), navigation.current_index(), navigation.len());
+    }
+}
+
+/// Fetch the response to a navigation: a page, or else a download whose
+/// body is read as it is saved
+///
+/// The milestones of the page's load are sent to `progress` as they pass.
+async fn fetch_navigation(
+    client: &HttpClient,
+    url: &Url,
+    progress: &tokio::sync::mpsc::UnboundedSender<LoadMilestone>,
+) -> NavigationResult {
+    if url.scheme() == VIEW_SOURCE_SCHEME {
+        return fetch_source_navigation(client, url).await;
+    }
+    let failed = |e: gugalanna_net::NetError| NavigationResult::Failed { url: url.clone(), error: e.into() };
+    let _ = progress.send(LoadMilestone::RequestSent);
+    let mut response = match client.get_streaming(url).await {
+        Ok(response) => response,
+        Err(e) => return failed(e),
+    };
+    if !response.is_success() {
+        return NavigationResult::Failed {
+            url: url.clone(),
+            error: NavigationError::HttpError { status: response.status },
+        };
+    }
+    // What is not a page is saved instead
+    if is_download(&response.headers) {
+        return NavigationResult::Download { response };
+    }
+    let _ = progress.send(LoadMilestone::HeadersReceived);
+
+    // The body chunk by chunk, to tell how far it has come
+    let total = response.content_length();
+    let mut body = Vec::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                body.extend_from_slice(&chunk);
+                let _ = progress.send(LoadMilestone::BodyReceived { received: body.len() as u64, total });
+            }
+            Ok(None) => break,
+            Err(e) => return failed(e),
+        }
+    }
+    let response = gugalanna_net::Response::new(response.url, response.status, response.headers, body);
+    NavigationResult::Success { html: response.text_lossy(), url: response.url }
+}
+
+/// The URL of an address typed or navigated to, with the default scheme
+/// when it has none, as `example.com` or `view-source:example.com`. Text
+/// that is not an address, with spaces or without a dot, is searched for.
+fn parse_address(address: &str, config: &BrowserConfig) -> Result<Url, String> {
+    if let Some(target) = address.strip_prefix("view-source:") {
+        let target = parse_address(target, config)?;
+        return source_url(&target).ok_or_else(|| format!("No source to view at {}", target));
+    }
+    if address.contains("://") || address.starts_with("about:") {
+        return Url::parse(address).map_err(|e| e.to_string());
+    }
+    let is_host = |host: &str| host.contains('.') || host.contains(':') || host == "localhost";
+    let host = address.split(['/', '?', '#']).next().unwrap_or_default();
+    if address.contains(char::is_whitespace) || !is_host(host) {
+        let terms: String = url::form_urlencoded::byte_serialize(address.trim().as_bytes()).collect();
+        return Url::parse(&config.search_engine.replace("{}", &terms)).map_err(|e| e.to_string());
+    }
+    Url::parse(&format!("{}://{}", config.default_scheme, address)).map_err(|e| e.to_string())
+}
+
+/// Fetch the page a `view-source:` URL shows, the source of its target
+/// fetched as the target would be, but never saved as a download
+async fn fetch_source_navigation(client: &HttpClient, url: &Url) -> NavigationResult {
+    let failed = |error| NavigationResult::Failed { url: url.clone(), error };
+    let Some(target) = source_target(url) else {
+        let message = format!("No source to view at {}", url);
+        return failed(NavigationError::NetworkError { kind: "Invalid URL", message });
+    };
+    let source = if target.scheme() == "file" {
+        read_file_source(&target).map_err(|message| NavigationError::NetworkError { kind: "IO error", message })
+    } else {
+        match client.get(&target).await {
+            Ok(response) if response.is_success() => Ok(response.text_lossy()),
+            Ok(response) => Err(NavigationError::HttpError { status: response.status }),
+            Err(e) => Err(e.into()),
+        }
+    };
+    match source {
+        Ok(source) => NavigationResult::Success { url: url.clone(), html: source_page(&target, &source) },
+        Err(error) => failed(error),
+    }
 }
 
 /// Extract text content from a <style> element
@@ -2752,11 +6342,31 @@ fn extract_style_content(dom: &DomTree, style_id: gugalanna_dom::NodeId) -> Opti
             }
         }
     }
-    if css_content.is_empty() {
-        None
-    } else {
-        Some(css_content)
-    }
+    if css_content.is_empty() {
+        None
+    } else {
+        Some(css_content)
+    }
+}
+
+/// Cascade of a page, the browser's stylesheet `css`, the user's and those
+/// of its `<style>` elements
+fn page_cascade(dom: &DomTree, css: &str, user: Option<&Stylesheet>) -> Cascade {
+    let mut cascade = Cascade::new();
+    if let Ok(stylesheet) = Stylesheet::parse(css) {
+        cascade.add_author_stylesheet(stylesheet);
+    }
+    if let Some(stylesheet) = user {
+        cascade.add_user_stylesheet(stylesheet.clone());
+    }
+    for style_id in dom.get_elements_by_tag_name("style") {
+        if let Some(style_css) = extract_style_content(dom, style_id) {
+            if let Ok(stylesheet) = Stylesheet::parse(&style_css) {
+                cascade.add_author_stylesheet(stylesheet);
+            }
+        }
+    }
+    cascade
 }
 
 /// Walk up the DOM tree to find an anchor element with href attribute
@@ -2794,6 +6404,8 @@ fn find_anchor_href(dom: &DomTree, start_id: gugalanna_dom::NodeId) -> Option<(S
 enum FormElementInfo {
     /// Text or password input
     TextInput { node_id: NodeId, is_password: bool },
+    /// Multi-line text field, with its initial text
+    TextArea { node_id: NodeId, text: String },
     /// Checkbox
     Checkbox { node_id: NodeId },
     /// Radio button
@@ -2802,39 +6414,70 @@ enum FormElementInfo {
     Submit { node_id: NodeId },
     /// Regular button
     Button { node_id: NodeId },
+    /// File input, choosing several files with `multiple`
+    File { node_id: NodeId, multiple: bool },
+}
+
+impl FormElementInfo {
+    fn node_id(&self) -> NodeId {
+        match self {
+            FormElementInfo::TextInput { node_id, .. }
+            | FormElementInfo::TextArea { node_id, .. }
+            | FormElementInfo::Checkbox { node_id }
+            | FormElementInfo::Radio { node_id, .. }
+            | FormElementInfo::Submit { node_id }
+            | FormElementInfo::Button { node_id }
+            | FormElementInfo::File { node_id, .. } => *node_id,
+        }
+    }
+}
+
+/// Check if a form control is disabled, by a script or its `disabled`
+/// attribute
+fn is_disabled(dom: &DomTree, form_state: &crate::form::FormState, node_id: NodeId) -> bool {
+    form_state.get_disabled(node_id).unwrap_or_else(|| {
+        dom.get(node_id)
+            .and_then(|n| n.as_element())
+            .is_some_and(|e| e.get_attribute("disabled").is_some())
+    })
 }
 
-/// Find form element info for a clicked node
+/// The element a page is laid out from: its body, or the root element of
+/// a page without one, such as about:blank
+fn layout_root(dom: &DomTree) -> NodeId {
+    let body = dom.get_elements_by_tag_name("body").first().copied();
+    let root_element = || {
+        let document = dom.document_id();
+        dom.children(document).into_iter().find(|&id| dom.get(id).is_some_and(|node| node.as_element().is_some()))
+    };
+    body.or_else(root_element).unwrap_or_else(|| dom.document_id())
+}
+
+/// Find form element info for a clicked node, a click in a label being
+/// one on the label's control
 fn find_form_element(dom: &DomTree, start_id: NodeId) -> Option<FormElementInfo> {
     let mut current_id = Some(start_id);
+    // A link or list in a label takes its clicks for itself
+    let mut in_interactive = false;
 
     while let Some(id) = current_id {
         if let Some(node) = dom.get(id) {
             if let Some(elem) = node.as_element() {
+                if let Some(info) = form_element_info(dom, id, elem) {
+                    return Some(info);
+                }
                 match elem.tag_name.as_str() {
-                    "input" => {
-                        let input_type = elem.get_attribute("type").unwrap_or("text");
-                        match input_type {
-                            "text" => return Some(FormElementInfo::TextInput { node_id: id, is_password: false }),
-                            "password" => return Some(FormElementInfo::TextInput { node_id: id, is_password: true }),
-                            "checkbox" => return Some(FormElementInfo::Checkbox { node_id: id }),
-                            "radio" => {
-                                let name = elem.get_attribute("name").unwrap_or("").to_string();
-                                return Some(FormElementInfo::Radio { node_id: id, name });
-                            }
-                            "submit" => return Some(FormElementInfo::Submit { node_id: id }),
-                            "button" => return Some(FormElementInfo::Button { node_id: id }),
-                            _ => {}
-                        }
-                    }
-                    "button" => {
-                        let btn_type = elem.get_attribute("type").unwrap_or("submit");
-                        if btn_type == "submit" {
-                            return Some(FormElementInfo::Submit { node_id: id });
-                        } else {
-                            return Some(FormElementInfo::Button { node_id: id });
+                    "label" if !in_interactive => {
+                        let control = label_control(dom, id);
+                        let info = control.and_then(|control| {
+                            form_element_info(dom, control, dom.get(control)?.as_element()?)
+                        });
+                        if info.is_some() {
+                            return info;
                         }
                     }
+                    "a" if elem.get_attribute("href").is_some() => in_interactive = true,
+                    "select" => in_interactive = true,
                     _ => {}
                 }
             }
@@ -2846,6 +6489,65 @@ fn find_form_element(dom: &DomTree, start_id: NodeId) -> Option<FormElementInfo>
     None
 }
 
+/// Form element info for an element that is a form control
+fn form_element_info(dom: &DomTree, id: NodeId, elem: &ElementData) -> Option<FormElementInfo> {
+    match elem.tag_name.as_str() {
+        "input" => {
+            let input_type = elem.get_attribute("type").unwrap_or("text");
+            match input_type {
+                "text" | "email" | "url" | "number" | "search" | "tel" => {
+                    Some(FormElementInfo::TextInput { node_id: id, is_password: false })
+                }
+                "password" => Some(FormElementInfo::TextInput { node_id: id, is_password: true }),
+                "checkbox" => Some(FormElementInfo::Checkbox { node_id: id }),
+                "radio" => {
+                    let name = elem.get_attribute("name").unwrap_or("").to_string();
+                    Some(FormElementInfo::Radio { node_id: id, name })
+                }
+                "submit" => Some(FormElementInfo::Submit { node_id: id }),
+                "button" => Some(FormElementInfo::Button { node_id: id }),
+                "file" => {
+                    let multiple = elem.get_attribute("multiple").is_some();
+                    Some(FormElementInfo::File { node_id: id, multiple })
+                }
+                _ => None,
+            }
+        }
+        "textarea" => {
+            let text = dom.text_content(id);
+            Some(FormElementInfo::TextArea { node_id: id, text })
+        }
+        "button" => {
+            let btn_type = elem.get_attribute("type").unwrap_or("submit");
+            if btn_type == "submit" {
+                Some(FormElementInfo::Submit { node_id: id })
+            } else {
+                Some(FormElementInfo::Button { node_id: id })
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The control a label is for: the element its `for` attribute names, or
+/// without one the first labelable element inside it
+fn label_control(dom: &DomTree, label_id: NodeId) -> Option<NodeId> {
+    let label = dom.get(label_id)?.as_element()?;
+    if let Some(for_id) = label.get_attribute("for") {
+        return dom.get_element_by_id(for_id).filter(|&id| is_labelable(dom, id));
+    }
+    dom.descendants(label_id).into_iter().find(|&id| is_labelable(dom, id))
+}
+
+/// Whether an element can be the control of a label
+fn is_labelable(dom: &DomTree, node_id: NodeId) -> bool {
+    dom.get(node_id).and_then(|node| node.as_element()).is_some_and(|elem| match elem.tag_name.as_str() {
+        "input" => !elem.get_attribute("type").is_some_and(|t| t.eq_ignore_ascii_case("hidden")),
+        "button" | "meter" | "output" | "progress" | "select" | "textarea" => true,
+        _ => false,
+    })
+}
+
 /// Find all radio buttons with a given name attribute in the DOM
 fn find_radio_buttons_in_group(dom: &DomTree, group_name: &str) -> Vec<NodeId> {
     let mut result = Vec::new();
@@ -2881,7 +6583,7 @@ fn resolve_link_url(base_url: &Url, href: &str) -> Result<Url, String> {
     }
 
     // If href is an absolute URL, parse it directly
-    if href.contains("://") {
+    if href.contains("://") || href.starts_with("about:") {
         return Url::parse(href).map_err(|e| e.to_string());
     }
 
@@ -2947,6 +6649,16 @@ fn find_parent_form(dom: &DomTree, start_id: NodeId) -> Option<NodeId> {
 struct FormField {
     name: String,
     value: String,
+    /// File a file input uploads, the value being its name; an empty path
+    /// when no file was chosen
+    file: Option<PathBuf>,
+}
+
+/// Body of a form sent with POST
+enum PostBody {
+    /// `application/x-www-form-urlencoded` data
+    UrlEncoded(String),
+    Multipart(Multipart),
 }
 
 /// Collect all form fields from a form element
@@ -2959,8 +6671,9 @@ fn collect_form_data(
 
     // Get all input elements under the form
     for input_id in dom.get_elements_by_tag_name("input") {
-        // Check if this input is a descendant of the form
-        if !is_descendant_of(dom, input_id, form_id) {
+        // Check if this input is a descendant of the form, disabled
+        // inputs are not submitted
+        if !is_descendant_of(dom, input_id, form_id) || is_disabled(dom, form_state, input_id) {
             continue;
         }
 
@@ -2973,25 +6686,39 @@ fn collect_form_data(
 
                 let input_type = elem.get_attribute("type").unwrap_or("text");
                 match input_type {
-                    "text" | "password" | "hidden" => {
+                    "text" | "password" | "hidden" | "email" | "url" | "number" | "search" | "tel" => {
                         // Get value from form state or attribute
                         let value = form_state
                             .get_value(input_id)
                             .map(|v| v.to_string())
                             .or_else(|| elem.get_attribute("value").map(|v| v.to_string()))
                             .unwrap_or_default();
-                        fields.push(FormField { name, value });
+                        fields.push(FormField { name, value, file: None });
                     }
                     "checkbox" => {
                         if form_state.is_checked(input_id) {
                             let value = elem.get_attribute("value").unwrap_or("on").to_string();
-                            fields.push(FormField { name, value });
+                            fields.push(FormField { name, value, file: None });
                         }
                     }
                     "radio" => {
                         if form_state.is_checked(input_id) {
                             let value = elem.get_attribute("value").unwrap_or("on").to_string();
-                            fields.push(FormField { name, value });
+                            fields.push(FormField { name, value, file: None });
+                        }
+                    }
+                    "file" => {
+                        // Each file chosen is a field of its own, sending
+                        // its name unless the form uploads it
+                        let files = form_state.get_files(input_id);
+                        if files.is_empty() {
+                            fields.push(FormField { name, value: String::new(), file: Some(PathBuf::new()) });
+                        } else {
+                            for file in files {
+                                let value = file.file_name().map(|n| n.to_string_lossy().into_owned());
+                                let value = value.unwrap_or_default();
+                                fields.push(FormField { name: name.clone(), value, file: Some(file.clone()) });
+                            }
                         }
                     }
                     "submit" | "button" => {
@@ -3003,6 +6730,22 @@ fn collect_form_data(
         }
     }
 
+    // Text areas submit what was typed, or their initial text
+    for textarea_id in dom.get_elements_by_tag_name("textarea") {
+        if !is_descendant_of(dom, textarea_id, form_id) || is_disabled(dom, form_state, textarea_id) {
+            continue;
+        }
+        let name = match dom.get(textarea_id).and_then(|n| n.as_element()).and_then(|e| e.get_attribute("name")) {
+            Some(n) if !n.is_empty() => n.to_string(),
+            _ => continue,
+        };
+        let value = form_state
+            .get_value(textarea_id)
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| dom.text_content(textarea_id));
+        fields.push(FormField { name, value, file: None });
+    }
+
     fields
 }
 
@@ -3022,8 +6765,9 @@ fn is_descendant_of(dom: &DomTree, node_id: NodeId, ancestor_id: NodeId) -> bool
     false
 }
 
-/// URL-encode a string for form submission
+/// URL-encode a string for form submission, its newlines as CRLF pairs
 fn url_encode(s: &str) -> String {
+    let s = s.replace("\r\n", "\n").replace('\r', "\n").replace('\n', "\r\n");
     let mut encoded = String::new();
     for c in s.chars() {
         match c {
@@ -3049,3 +6793,1667 @@ fn build_form_data_string(fields: &[FormField]) -> String {
         .collect::<Vec<_>>()
         .join("&")
 }
+
+/// Build the multipart body of a form, reading the files it uploads
+fn build_multipart(fields: &[FormField]) -> std::io::Result<Multipart> {
+    let mut form = Multipart::new();
+    for field in fields {
+        match &field.file {
+            // A file input with no file sends an empty one without a name
+            Some(path) if path.as_os_str().is_empty() => {
+                form.file(&field.name, "", gugalanna_net::DEFAULT_FILE_TYPE, Vec::new());
+            }
+            Some(path) => {
+                form.file_from_path(&field.name, path)?;
+            }
+            None => {
+                form.text(&field.name, &field.value);
+            }
+        }
+    }
+    Ok(form)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_layout::Rect;
+    use gugalanna_js::JsValue;
+    use gugalanna_style::{ComputedStyle, Display, Position};
+
+    /// Config for a browser painting in software with nothing on disk
+    fn test_config() -> BrowserConfig {
+        BrowserConfig {
+            backend: BackendKind::Software,
+            config_dir: None,
+            ..BrowserConfig::default()
+        }
+    }
+
+    fn test_browser() -> Browser {
+        Browser::new(test_config()).unwrap()
+    }
+
+    /// Evaluate `code` in the script runtime of the active page
+    fn eval(browser: &Browser, code: &str) -> JsValue {
+        let rt = browser.active_tab().unwrap().page.as_ref().unwrap().js_runtime.as_ref().unwrap();
+        rt.eval(code).unwrap()
+    }
+
+    /// The element of the active page with the id `name`
+    fn element_id(browser: &Browser, name: &str) -> NodeId {
+        browser.active_tab().unwrap().page.as_ref().unwrap().dom.borrow().get_element_by_id(name).unwrap()
+    }
+
+    #[test]
+    fn test_fixed_hit_region_ignores_scroll() {
+        let page_style = ComputedStyle::default();
+        let banner_style = ComputedStyle {
+            position: Position::Fixed,
+            ..ComputedStyle::default()
+        };
+
+        // A long page with a banner fixed to the top of the viewport
+        let mut banner = LayoutBox::new_block(NodeId(2), &banner_style);
+        banner.dimensions.content = Rect::new(0.0, 0.0, 800.0, 40.0);
+        let mut page = LayoutBox::new_block(NodeId(1), &page_style);
+        page.dimensions.content = Rect::new(0.0, 0.0, 800.0, 5000.0);
+        page.children.push(banner);
+
+        let regions = build_hit_regions(&page);
+        assert_eq!(hit_test_regions(&regions, 10.0, 10.0, 0.0), Some(2));
+
+        // Scrolled down, the top of the viewport still hits the banner
+        let scroll_y = 1000.0;
+        assert_eq!(hit_test_regions(&regions, 10.0, 10.0 + scroll_y, scroll_y), Some(2));
+        assert_eq!(hit_test_regions(&regions, 10.0, 10.0, scroll_y), Some(1));
+    }
+
+    #[test]
+    fn test_relative_offset_moves_hit_region_only() {
+        let dom = HtmlParser::new()
+            .parse("<div id='a'>a</div><div id='b'>b</div><div id='c'>c</div>")
+            .unwrap();
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse("div { line-height: 20px; } #b { position: relative; top: 30px; left: 5px; }")
+                .unwrap(),
+        );
+        let style_tree = StyleTree::build(&dom, &cascade, 800.0, 600.0);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(800.0, 600.0));
+
+        let regions = build_hit_regions(&layout);
+        let region = |id: &str| {
+            let node = dom.get_element_by_id(id).unwrap();
+            regions.iter().find(|r| r.node_id == node.0).map(|r| (r.x, r.y)).unwrap()
+        };
+
+        // The offset box moves, its siblings stay where normal flow put them
+        let (ax, ay) = region("a");
+        assert_eq!(region("b"), (ax + 5.0, ay + 50.0));
+        assert_eq!(region("c"), (ax, ay + 40.0));
+    }
+
+    #[test]
+    fn test_script_style_writes_restyle_page() {
+        let dom = HtmlParser::new()
+            .parse("<div id='a' style='height: 20px'>a</div><div id='b'>b</div>")
+            .unwrap();
+        let rt = JsRuntime::with_dom(dom).unwrap();
+        let dom = rt.dom().unwrap().clone();
+        let cascade = Cascade::new();
+        let layout = |dom: &DomTree| {
+            let style_tree = StyleTree::build(dom, &cascade, 800.0, 600.0);
+            let body = dom.get_elements_by_tag_name("body")[0];
+            let regions = {
+                let mut layout = build_layout_tree(dom, &style_tree, body).unwrap();
+                layout_document(&mut layout, ContainingBlock::new(800.0, 600.0));
+                build_hit_regions(&layout)
+            };
+            (style_tree, regions)
+        };
+        let (a, b) = {
+            let dom = dom.borrow();
+            (dom.get_element_by_id("a").unwrap(), dom.get_element_by_id("b").unwrap())
+        };
+        let region_y = |regions: &[HitRegion], id: NodeId| regions.iter().find(|r| r.node_id == id.0).map(|r| r.y);
+
+        let (_, regions) = layout(&dom.borrow());
+        let b_y = region_y(&regions, b).unwrap();
+
+        rt.exec("var a = document.getElementById('a'); a.style.display = 'none'; a.style.color = '#0000ff';")
+            .unwrap();
+        assert_eq!(rt.take_style_dirty(), vec![a]);
+        let (style_tree, regions) = layout(&dom.borrow());
+        assert_eq!(style_tree.get_style(a).unwrap().display, Display::None);
+        assert_eq!(style_tree.get_style(a).unwrap().color, gugalanna_css::Color::rgb(0, 0, 255));
+        assert_eq!(region_y(&regions, a), None);
+        assert_eq!(region_y(&regions, b), Some(b_y - 20.0));
+
+        // Removing the declaration brings the box back
+        rt.exec("document.getElementById('a').style.removeProperty('display')").unwrap();
+        assert_eq!(rt.take_style_dirty(), vec![a]);
+        let (style_tree, regions) = layout(&dom.borrow());
+        assert_eq!(style_tree.get_style(a).unwrap().display, Display::Block);
+        assert_eq!(region_y(&regions, b), Some(b_y));
+    }
+
+    #[test]
+    fn test_hit_test_follows_z_index() {
+        let dom = HtmlParser::new()
+            .parse("<div id='top'>a</div><div id='bottom'>b</div>")
+            .unwrap();
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse(
+                "div { position: absolute; top: 0; left: 0; width: 50px; height: 50px; } \
+                 #top { z-index: 2; } #bottom { z-index: 1; }",
+            )
+            .unwrap(),
+        );
+        let style_tree = StyleTree::build(&dom, &cascade, 800.0, 600.0);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(800.0, 600.0));
+
+        // The later box in tree order is stacked below
+        let regions = build_hit_regions(&layout);
+        let top = dom.get_element_by_id("top").unwrap();
+        assert_eq!(hit_test_regions(&regions, 10.0, 40.0, 0.0), Some(top.0));
+    }
+
+    #[test]
+    fn test_rotated_box_hit_where_painted() {
+        let dom = HtmlParser::new().parse("<div id='bar'></div>").unwrap();
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse("#bar { width: 20px; height: 100px; transform: rotate(90deg); }").unwrap(),
+        );
+        let style_tree = StyleTree::build(&dom, &cascade, 800.0, 600.0);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(800.0, 600.0));
+
+        let regions = build_hit_regions(&layout);
+        let bar = dom.get_element_by_id("bar").unwrap();
+        let region = regions.iter().find(|r| r.node_id == bar.0).unwrap();
+        let (cx, cy) = (region.x + 10.0, region.y + 50.0);
+
+        // Turned about its center, the tall bar lies across
+        assert_eq!(hit_test_regions(&regions, cx + 40.0, cy, 0.0), Some(bar.0));
+        assert_eq!(hit_test_regions(&regions, cx - 40.0, cy + 5.0, 0.0), Some(bar.0));
+        assert_ne!(hit_test_regions(&regions, cx, cy - 40.0, 0.0), Some(bar.0));
+    }
+
+    #[test]
+    fn test_overflow_hidden_clips_hit_regions() {
+        let dom = HtmlParser::new()
+            .parse("<div id='clip'><div id='tall'></div></div>")
+            .unwrap();
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse("#clip { overflow: hidden; height: 50px; } #tall { height: 200px; }").unwrap(),
+        );
+        let style_tree = StyleTree::build(&dom, &cascade, 800.0, 600.0);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(800.0, 600.0));
+
+        let regions = build_hit_regions(&layout);
+        let clip = dom.get_element_by_id("clip").unwrap();
+        let tall = dom.get_element_by_id("tall").unwrap();
+        let (x, y) = regions.iter().find(|r| r.node_id == clip.0).map(|r| (r.x, r.y)).unwrap();
+
+        // Inside the clip the child is hit, below it the child isn't there
+        assert_eq!(hit_test_regions(&regions, x + 10.0, y + 40.0, 0.0), Some(tall.0));
+        assert_ne!(hit_test_regions(&regions, x + 10.0, y + 100.0, 0.0), Some(tall.0));
+    }
+
+    #[test]
+    fn test_wheel_scrolls_container_under_cursor() {
+        let dom = HtmlParser::new()
+            .parse("<div id='box'><div id='tall'></div></div><div id='after'></div>")
+            .unwrap();
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse("#box { overflow: auto; height: 50px; } #tall { height: 200px; } #after { height: 20px; }")
+                .unwrap(),
+        );
+        let style_tree = StyleTree::build(&dom, &cascade, 800.0, 600.0);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(800.0, 600.0));
+
+        let mut regions = build_hit_regions(&layout);
+        let mut offsets = HashMap::new();
+        let scroller = dom.get_element_by_id("box").unwrap().0;
+        let tall = dom.get_element_by_id("tall").unwrap().0;
+        let after = dom.get_element_by_id("after").unwrap();
+        let (x, y) = regions.iter().find(|r| r.node_id == scroller).map(|r| (r.x, r.y)).unwrap();
+        let (_, after_y) = regions.iter().find(|r| r.node_id == after.0).map(|r| (r.x, r.y)).unwrap();
+
+        // Over the container the wheel scrolls it, elsewhere the page
+        assert_eq!(scroll_target(&regions, &offsets, x + 10.0, y + 10.0, 0.0, -40.0), Some((scroller, 150.0)));
+        assert_eq!(scroll_target(&regions, &offsets, x + 10.0, after_y + 10.0, 0.0, -40.0), None);
+
+        // Scrolled content moves its hit regions with it
+        offsets.insert(scroller, 150.0);
+        restore_scroll_offsets(&mut regions, &mut offsets);
+        let tall_y = regions.iter().find(|r| r.node_id == tall).map(|r| r.y).unwrap();
+        assert_eq!(tall_y, y - 150.0);
+        assert_eq!(hit_test_regions(&regions, x + 10.0, y + 10.0, 0.0), Some(tall));
+
+        // At the bottom, scrolling further down falls back to the page
+        assert_eq!(scroll_target(&regions, &offsets, x + 10.0, y + 10.0, 0.0, -40.0), None);
+        assert_eq!(scroll_target(&regions, &offsets, x + 10.0, y + 10.0, 0.0, 40.0), Some((scroller, 150.0)));
+    }
+
+    #[test]
+    fn test_render_page_without_window() {
+        let mut browser = test_browser();
+        browser
+            .load_html("<body><p>Hello</p></body>", "p { background-color: #ff0000; }")
+            .unwrap();
+        browser.run().unwrap();
+
+        // The page was painted below the chrome
+        let frame = browser.painted_frame.as_ref().unwrap();
+        assert!(frame.commands.iter().any(|command| matches!(
+            command,
+            gugalanna_render::PaintCommand::FillRect { rect, color }
+                if *color == RenderColor::rgb(255, 0, 0) && rect.y >= CHROME_HEIGHT
+        )));
+    }
+
+    #[test]
+    fn test_click_toggling_class_repaints_background() {
+        let mut browser = test_browser();
+        let html = "<style>#menu { height: 40px; background-color: #0000ff; } \
+                    #menu.open { background-color: #ff0000; }</style>\
+                    <div id='menu'></div>\
+                    <script>var menu = document.getElementById('menu');\
+                    menu.addEventListener('click', function() { menu.classList.toggle('open'); });</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        browser.run().unwrap();
+        let painted = |browser: &Browser, color: RenderColor| {
+            browser.painted_frame.as_ref().unwrap().commands.iter().any(|command| {
+                matches!(command, gugalanna_render::PaintCommand::FillRect { color: c, .. } if *c == color)
+            })
+        };
+        let (blue, red) = (RenderColor::rgb(0, 0, 255), RenderColor::rgb(255, 0, 0));
+        assert!(painted(&browser, blue) && !painted(&browser, red));
+
+        // The listener only changes the class, the frame loop restyles
+        browser.handle_click(20.0, CHROME_HEIGHT + 10.0);
+        browser.restyle_pages();
+        browser.render();
+        assert!(painted(&browser, red) && !painted(&browser, blue));
+    }
+
+    #[test]
+    fn test_scripts_measure_the_layout() {
+        let mut browser = test_browser();
+        let html = "<div id='box' style='width: 200px; height: 30px; padding-top: 5px; padding-bottom: 5px;\
+                    border: 2px solid black'></div>\
+                    <script>var box = document.getElementById('box'); box.style.height = '40px';\
+                    var measured = box.offsetHeight;</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+
+        // The script changed the box before measuring it, the page was
+        // laid out for it
+        assert_eq!(eval(&browser, "measured").as_number(), Some(54.0));
+
+        let (width, height) = (browser.config.width as f32, page.viewport_height);
+        let dom = page.dom.borrow();
+        let style_tree = StyleTree::build(&dom, &page.cascade, width, height);
+        let body = dom.get_elements_by_tag_name("body")[0];
+        let mut layout = build_layout_tree(&dom, &style_tree, body).unwrap();
+        layout_document(&mut layout, ContainingBlock::new(width, height));
+        let boxes = measure_elements(&layout);
+        let element = boxes[&dom.get_element_by_id("box").unwrap()];
+        drop(dom);
+
+        let read = |code: &str| eval(&browser, code).as_number().unwrap() as f32;
+        eval(&browser, "var rect = box.getBoundingClientRect();");
+        assert_eq!(read("rect.left"), element.border_box.x);
+        assert_eq!(read("rect.top"), element.border_box.y);
+        assert_eq!(read("rect.width"), element.border_box.width);
+        assert_eq!(read("rect.height"), element.border_box.height);
+        assert_eq!(read("box.clientWidth"), element.padding_box.width.round());
+    }
+
+    #[test]
+    fn test_script_cookies_sent_with_requests() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        // A server answering with the cookies a request sent
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0; 1024];
+            while !String::from_utf8_lossy(&received).ends_with("\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..n]);
+            }
+            let request = String::from_utf8_lossy(&received).to_lowercase();
+            let cookie = request.lines().find_map(|l| l.strip_prefix("cookie: ")).unwrap_or("").to_string();
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", cookie.len(), cookie).unwrap();
+        });
+
+        let mut browser = test_browser();
+        let jar = browser.http_client.cookie_jar();
+        jar.lock().unwrap().set_cookie(&url, "session=x; HttpOnly", gugalanna_net::CookieSource::Http);
+        let html = "<script>document.cookie = 'theme=dark; path=/'; document.cookie = 'token=1; HttpOnly';\
+                    var seen = document.cookie;</script>";
+        browser.load_page(url.clone(), html).unwrap();
+
+        // Scripts never see HttpOnly cookies nor set them
+        assert_eq!(eval(&browser, "seen").as_str(), Some("theme=dark"));
+
+        let response = browser.fetch_url(&url).unwrap();
+        server.join().unwrap();
+        assert_eq!(response.text_lossy(), "session=x; theme=dark");
+    }
+
+    #[test]
+    fn test_animation_frame_loop_repaints() {
+        let mut browser = test_browser();
+        let html = "<div id='box' style='height: 40px; background-color: #0000ff'></div>\
+                    <script>var box = document.getElementById('box'); var frames = 0;\
+                    function step() {\
+                        frames++;\
+                        if (frames < 3) requestAnimationFrame(step); else box.style.backgroundColor = '#ff0000';\
+                    }\
+                    requestAnimationFrame(step);</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let painted = |browser: &Browser, color: RenderColor| {
+            browser.painted_frame.as_ref().unwrap().commands.iter().any(|command| {
+                matches!(command, gugalanna_render::PaintCommand::FillRect { color: c, .. } if *c == color)
+            })
+        };
+
+        // The loop takes a frame per step, the last one shows
+        let red = RenderColor::rgb(255, 0, 0);
+        for frame in 1..=3 {
+            browser.run_animation_frames();
+            browser.restyle_pages();
+            browser.needs_redraw = true;
+            browser.render();
+            assert_eq!(painted(&browser, red), frame == 3);
+        }
+    }
+
+    #[test]
+    fn test_timer_appending_items_repaints() {
+        let mut browser = test_browser();
+        let html = "<ul id='list'><li>item 0</li></ul>\
+                    <script>var list = document.getElementById('list'); var count = 0;\
+                    var timer = setInterval(function() {\
+                        var item = document.createElement('li');\
+                        item.textContent = 'item ' + (++count);\
+                        list.appendChild(item);\
+                        if (count === 3) clearInterval(timer);\
+                    }, 0);</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let texts = |browser: &Browser| {
+            let commands = &browser.painted_frame.as_ref().unwrap().commands;
+            commands.iter().filter(|c| matches!(c, gugalanna_render::PaintCommand::DrawText { .. })).count()
+        };
+        browser.needs_redraw = true;
+        browser.render();
+        let before = texts(&browser);
+
+        // Nothing but the frame loop takes the new items to the screen
+        for _ in 0..5 {
+            browser.run_timers();
+            browser.restyle_pages();
+            browser.render();
+        }
+        assert_eq!(texts(&browser), before + 3);
+    }
+
+    #[test]
+    fn test_script_built_list_rendered_in_order() {
+        let mut browser = test_browser();
+        let html = "<ul id='list'></ul><script>var list = document.getElementById('list');\
+                    ['one', 'two', 'three'].forEach(function(text) {\
+                        var item = document.createElement('li'); item.textContent = text; list.appendChild(item);\
+                    });</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let page_texts = |browser: &Browser| {
+            let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+            let mut texts: Vec<(f32, String)> = page
+                .display_list
+                .commands
+                .iter()
+                .filter_map(|c| match c {
+                    gugalanna_render::PaintCommand::DrawText { text, y, .. } => Some((*y, text.clone())),
+                    _ => None,
+                })
+                .collect();
+            texts.sort_by(|a, b| a.0.total_cmp(&b.0));
+            texts.into_iter().map(|(_, text)| text).collect::<Vec<_>>()
+        };
+        assert_eq!(page_texts(&browser), ["one", "two", "three"]);
+
+        eval(&browser, "list.insertBefore(list.children[2], list.firstChild); list.children[1].remove();");
+        browser.restyle_pages();
+        assert_eq!(page_texts(&browser), ["three", "two"]);
+    }
+
+    #[test]
+    fn test_mutation_lays_out_changed_boxes_only() {
+        let mut browser = test_browser();
+        let list = format!("<div id='list'>{}</div>", "<p>Item</p>".repeat(200));
+        let url = Url::parse("https://a.test/").unwrap();
+        browser.load_page(url.clone(), &format!("<p id='status'>Idle</p>{}", list)).unwrap();
+        fn page(browser: &Browser) -> &PageState {
+            browser.active_tab().unwrap().page.as_ref().unwrap()
+        }
+        let full = page(&browser).blocks_laid_out;
+        assert!(full > 200, "{} blocks laid out", full);
+
+        // The status grows to a few lines, the list after it only moves
+        eval(&browser, "document.getElementById('status').textContent = 'Loading more items '.repeat(40)");
+        browser.restyle_pages();
+        let partial = page(&browser).blocks_laid_out;
+        assert!(partial < 10, "{} of {} blocks laid out", partial, full);
+        let list_id = element_id(&browser, "list");
+        let (moved, height) = (page(&browser).box_models[&list_id], page(&browser).content_height);
+
+        // It is where a full layout of the changed page puts it
+        let status = format!("<p id='status'>{}</p>", "Loading more items ".repeat(40));
+        browser.load_page(url, &format!("{}{}", status, list)).unwrap();
+        let list_id = element_id(&browser, "list");
+        assert_eq!(page(&browser).box_models[&list_id], moved);
+        assert_eq!(page(&browser).content_height, height);
+    }
+
+    #[test]
+    fn test_images_kept_when_script_mutates_page() {
+        use gugalanna_render::PaintCommand;
+
+        let dir = std::env::temp_dir().join(format!("gugalanna-page-images-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255])).save(dir.join("dot.png")).unwrap();
+
+        let mut browser = test_browser();
+        let html = "<style>#box { background-image: url(dot.png); height: 20px; }</style>\
+                    <div id='page'><div id='box'></div><img src='dot.png'></div>";
+        browser.load_page(Url::from_file_path(dir.join("page.html")).unwrap(), html).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The boxes in the page are built anew, none is the same as before
+        eval(&browser, "document.getElementById('page').appendChild(document.createElement('p')).textContent = 'Done'");
+        browser.restyle_pages();
+
+        // Both images are drawn from the pixels the page loaded first
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let commands = &page.display_list.commands;
+        let image = commands.iter().find_map(|cmd| match cmd {
+            PaintCommand::DrawImage { pixels, .. } => Some(pixels),
+            _ => None,
+        });
+        assert_eq!(image.unwrap().as_ref().map(|pixels| (pixels.width, pixels.height)), Some((4, 4)));
+        let background = commands.iter().find_map(|cmd| match cmd {
+            PaintCommand::DrawTiledImage { pixels, .. } => Some(pixels),
+            _ => None,
+        });
+        assert_eq!(background.map(|pixels| pixels.data.len()), Some(4 * 4 * 4));
+    }
+
+    #[test]
+    fn test_fragment_children_rendered() {
+        let mut browser = test_browser();
+        let html = "<ul id='list'></ul><script>var fragment = document.createDocumentFragment();\
+                    for (var i = 0; i < 100; i++) {\
+                        var item = document.createElement('li'); item.textContent = 'item ' + i;\
+                        fragment.appendChild(item);\
+                    }\
+                    document.getElementById('list').appendChild(fragment);</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let texts = page
+            .display_list
+            .commands
+            .iter()
+            .filter(|c| matches!(c, gugalanna_render::PaintCommand::DrawText { .. }))
+            .count();
+        assert_eq!(texts, 100);
+    }
+
+    #[test]
+    fn test_script_scrolls_page() {
+        let mut browser = test_browser();
+        let html = "<div style='height: 3000px'></div><script>var seen = [];\
+                    addEventListener('scroll', function() { seen.push(scrollY); });\
+                    scrollTo(0, 100000);</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        browser.poll_scrolls();
+        browser.run_scroll_events();
+
+        // The page stops at the end of its content, where scripts see it
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let max_scroll = page.content_height - page.viewport_height;
+        assert!(max_scroll > 0.0);
+        assert_eq!(page.scroll_y, max_scroll);
+        assert_eq!(eval(&browser, "seen.length === 1 && seen[0]").as_number(), Some(max_scroll as f64));
+
+        // A smooth scroll moves the page over the next frames
+        eval(&browser, "scrollTo({ top: 0, behavior: 'smooth' })");
+        browser.poll_scrolls();
+        let page = browser.active_tab_mut().unwrap().page.as_mut().unwrap();
+        assert!(page.smooth_scroll.is_some());
+        assert!(page.step_smooth_scroll(1000.0));
+        assert_eq!(page.scroll_y, 0.0);
+        assert!(page.smooth_scroll.is_none());
+    }
+
+    #[test]
+    fn test_smooth_scroll_steps_to_clamped_target() {
+        let mut browser = test_browser();
+        browser.load_page(Url::parse("https://a.test/").unwrap(), "<div style='height: 3000px'></div>").unwrap();
+
+        // Without a window the page jumps
+        browser.handle_scroll(-100.0);
+        let page = browser.active_tab_mut().unwrap().page.as_mut().unwrap();
+        assert_eq!((page.scroll_y, page.smooth_scroll), (100.0, None));
+
+        // Steps approach the target, which a second scroll moves further
+        page.scroll_toward(page.scroll_target() + 300.0, true);
+        let mut last = page.scroll_y;
+        for _ in 0..5 {
+            assert!(page.step_smooth_scroll(16.0));
+            assert!(page.scroll_y > last && page.scroll_y < 400.0);
+            last = page.scroll_y;
+        }
+        page.scroll_toward(page.scroll_target() + 100_000.0, true);
+        let max_scroll = page.content_height - page.viewport_height;
+        assert_eq!(page.scroll_target(), max_scroll);
+        assert!(page.step_smooth_scroll(500.0));
+        assert_eq!((page.scroll_y, page.smooth_scroll), (max_scroll, None));
+    }
+
+    #[test]
+    fn test_runaway_timer_is_stopped() {
+        let mut browser = test_browser();
+        let html = "<script>var ticks = 0; setTimeout(function() { for (;;) {} }, 0);\
+                    setTimeout(function() { ticks++; }, 0);</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        page.js_runtime.as_ref().unwrap().set_execution_limit(Duration::from_millis(100));
+
+        let started = Instant::now();
+        browser.run_timers();
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // The browser goes on, the next turn running the timer left
+        browser.run_timers();
+        assert_eq!(eval(&browser, "ticks").as_number(), Some(1.0));
+    }
+
+    #[test]
+    fn test_local_storage_kept_across_loads() {
+        let dir = std::env::temp_dir().join(format!("gugalanna-config-{}", std::process::id()));
+        let config = BrowserConfig {
+            config_dir: Some(dir.clone()),
+            ..test_config()
+        };
+        let mut browser = Browser::new(config).unwrap();
+        let html = "<script>localStorage.visits = Number(localStorage.visits || 0) + 1;</script>";
+        let a = Url::parse("https://a.test/").unwrap();
+        browser.load_page(a.clone(), html).unwrap();
+        browser.load_page(a, html).unwrap();
+        browser.load_page(Url::parse("https://b.test/").unwrap(), html).unwrap();
+        browser.run().unwrap();
+
+        // The reload counted on, the other origin counted apart
+        let mut saved = StorageStore::load(&dir.join("local-storage.json")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(saved.area("https://a.test").borrow().get_item("visits"), Some("2"));
+        assert_eq!(saved.area("https://b.test").borrow().get_item("visits"), Some("1"));
+    }
+
+    #[test]
+    fn test_back_to_pushed_entry_dispatches_popstate() {
+        let mut browser = test_browser();
+        let html = "<script>addEventListener('popstate', function(e) { globalThis.popped = JSON.stringify(e.state); });\
+                    history.pushState({ step: 1 }, '', '/step/1');</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        browser.poll_history();
+        assert_eq!(browser.chrome.address_bar.text, "https://a.test/step/1");
+
+        eval(&browser, "history.pushState({ step: 2 }, '', '/step/2')");
+        browser.poll_history();
+        browser.go_back().unwrap();
+
+        // The same page got the state of the entry, nothing was fetched
+        assert_eq!(browser.chrome.address_bar.text, "https://a.test/step/1");
+        assert_eq!(eval(&browser, "popped").as_str(), Some(r#"{"step":1}"#));
+        assert_eq!(eval(&browser, "history.length + ' ' + history.state.step").as_str(), Some("3 1"));
+        let tab = browser.active_tab().unwrap();
+        assert!(tab.navigation.can_go_forward());
+        assert_eq!(tab.page.as_ref().unwrap().url.as_str(), "https://a.test/step/1");
+    }
+
+    #[test]
+    fn test_prevent_default_cancels_link_navigation() {
+        let mut browser = test_browser();
+        let html = "<a href='https://b.test/' style='display: block; height: 40px'>Elsewhere</a>\
+                    <script>document.addEventListener('click', function(e) {\
+                        if (e.target.tagName === 'A') { e.preventDefault(); globalThis.clicked = e.currentTarget === document; }\
+                    });</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+
+        // A delegated listener on the document cancels the click on the
+        // link, so no navigation starts
+        browser.handle_click(20.0, CHROME_HEIGHT + 10.0);
+        let tab = browser.active_tab().unwrap();
+        assert!(tab.nav_receiver.is_none());
+        assert_eq!(browser.chrome.address_bar.text, "https://a.test/");
+        assert_eq!(eval(&browser, "clicked").as_bool(), Some(true));
+    }
+
+    #[test]
+    fn test_page_keydown_can_cancel_scrolling() {
+        use crate::event::{SCANCODE_DOWN, SCANCODE_PAGEDOWN};
+
+        let mut browser = test_browser();
+        let html = "<div style='height: 3000px'></div>\
+                    <script>document.addEventListener('keydown', function(e) {\
+                        globalThis.seen = e.key + ' ' + e.code + ' ' + e.shiftKey;\
+                        if (e.key === 'ArrowDown') e.preventDefault();\
+                    });</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let scroll_y = |browser: &Browser| browser.active_tab().unwrap().page.as_ref().unwrap().scroll_y;
+
+        // The page took the arrow key for itself
+        browser.handle_key(SCANCODE_DOWN, Modifiers::default());
+        assert_eq!(eval(&browser, "seen").as_str(), Some("ArrowDown ArrowDown false"));
+        assert_eq!(scroll_y(&browser), 0.0);
+
+        browser.handle_key(SCANCODE_PAGEDOWN, Modifiers { shift: true, ..Modifiers::default() });
+        assert_eq!(eval(&browser, "seen").as_str(), Some("PageDown PageDown true"));
+        assert!(scroll_y(&browser) > 0.0);
+    }
+
+    #[test]
+    fn test_input_events_follow_typing() {
+        let mut browser = test_browser();
+        let html = "<input id='name'><div id='mirror'></div>\
+                    <script>var field = document.getElementById('name');\
+                        field.addEventListener('input', function(e) {\
+                            document.getElementById('mirror').setAttribute('title', e.target.value);\
+                        });\
+                        globalThis.changes = 0;\
+                        field.addEventListener('change', function() { changes++; });</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let input = browser.active_tab().unwrap().page.as_ref().unwrap().dom.borrow().get_element_by_id("name").unwrap();
+        let mirror = "document.getElementById('mirror').getAttribute('title')";
+
+        browser.focus_form_input(input);
+        browser.handle_text_input("h");
+        assert_eq!(eval(&browser, mirror).as_str(), Some("h"));
+        browser.handle_text_input("i");
+        assert_eq!(eval(&browser, mirror).as_str(), Some("hi"));
+        browser.handle_key(crate::event::SCANCODE_BACKSPACE, Modifiers::default());
+        assert_eq!(eval(&browser, mirror).as_str(), Some("h"));
+
+        // Losing focus with a modified value fires change once
+        browser.blur_form_input();
+        assert_eq!(eval(&browser, "changes").as_number(), Some(1.0));
+        browser.focus_form_input(input);
+        browser.blur_form_input();
+        assert_eq!(eval(&browser, "changes").as_number(), Some(1.0));
+    }
+
+    #[test]
+    fn test_script_value_painted_and_submitted() {
+        let mut browser = test_browser();
+        let html = "<form id='search'><input id='q' name='q' value='initial'>\
+                    <input name='token' value='secret' disabled><input id='all' type='checkbox' name='all'></form>\
+                    <script>document.getElementById('q').value = 'preset';\
+                        document.getElementById('all').checked = true;</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        browser.run().unwrap();
+
+        // The next frame paints the script's value
+        let frame = browser.painted_frame.as_ref().unwrap();
+        assert!(frame.commands.iter().any(|command| matches!(
+            command,
+            gugalanna_render::PaintCommand::DrawTextInput { text, .. } if text == "preset"
+        )));
+
+        // And the form submits it, leaving out the disabled input
+        let tab = browser.active_tab().unwrap();
+        let dom = tab.page.as_ref().unwrap().dom.borrow();
+        let form = dom.get_element_by_id("search").unwrap();
+        let fields = collect_form_data(&dom, form, &tab.form_state.borrow());
+        assert_eq!(build_form_data_string(&fields), "q=preset&all=on");
+    }
+
+    #[test]
+    fn test_submit_event_and_programmatic_submit() {
+        let mut browser = test_browser();
+        // Nothing listens on port 1, submissions fail right after they start
+        let html = "<form id='search' action='/find'><input type='submit' value='Go'>\
+                    <input name='q' value='kittens'></form>\
+                    <script>globalThis.block = true;\
+                        document.getElementById('search').addEventListener('submit', function(e) {\
+                            if (block) e.preventDefault();\
+                        });</script>";
+        browser.load_page(Url::parse("http://127.0.0.1:1/app").unwrap(), html).unwrap();
+
+        // A listener canceling submit keeps the page
+        browser.handle_click(10.0, CHROME_HEIGHT + 10.0);
+        assert_eq!(browser.chrome.address_bar.text, "http://127.0.0.1:1/app");
+
+        eval(&browser, "block = false;");
+        browser.handle_click(10.0, CHROME_HEIGHT + 10.0);
+        let clicked = browser.chrome.address_bar.text.clone();
+        assert_eq!(clicked, "http://127.0.0.1:1/find?q=kittens");
+
+        // submit() sends the same data, even though the listener would
+        // cancel it
+        browser.chrome.address_bar.set_text("");
+        eval(&browser, "block = true; document.getElementById('search').submit();");
+        browser.poll_form_submissions();
+        assert_eq!(browser.chrome.address_bar.text, clicked);
+    }
+
+    #[test]
+    fn test_invalid_form_not_sent() {
+        use gugalanna_render::PaintCommand;
+
+        let mut browser = test_browser();
+        // Nothing listens on port 1, submissions fail right after they start
+        let url = Url::parse("http://127.0.0.1:1/app").unwrap();
+        let html = "<form action='/join'><input id='email' name='email' type='email' required>\
+                    <input id='age' name='age' type='number' min='18'>\
+                    <button id='go'>Join</button><button id='skip' formnovalidate>Later</button></form>\
+                    <script>globalThis.invalid = 0;\
+                        document.getElementById('email').addEventListener('invalid', function() { invalid++; });\
+                    </script>";
+        browser.load_page(url.clone(), html).unwrap();
+        let type_in = |browser: &Browser, name: &str, value: &str| {
+            let node_id = element_id(browser, name);
+            browser.active_tab().unwrap().form_state.borrow_mut().set_text(node_id, value.to_string());
+        };
+        let message = |browser: &Browser| browser.validation_bubble.as_ref().map(|b| (b.node_id, b.message.clone()));
+        let invalid_borders = |browser: &Browser| {
+            let mut frame = DisplayList::new();
+            browser.render_invalid_controls(&mut frame);
+            frame.commands.iter().filter(|c| matches!(c, PaintCommand::DrawBorder { .. })).count()
+        };
+
+        // The empty required field keeps the form from being sent, and is
+        // focused with its message
+        browser.submit_form(element_id(&browser, "go"));
+        assert_eq!(browser.chrome.address_bar.text, "http://127.0.0.1:1/app");
+        assert_eq!(eval(&browser, "invalid").as_number(), Some(1.0));
+        assert_eq!(browser.focus, FocusTarget::FormInput(element_id(&browser, "email")));
+        assert_eq!(message(&browser), Some((element_id(&browser, "email"), "Please fill out this field.".to_string())));
+        assert_eq!(invalid_borders(&browser), 1);
+
+        type_in(&browser, "email", "ana@example.com");
+        type_in(&browser, "age", "16");
+        assert_eq!(invalid_borders(&browser), 0);
+        browser.submit_form(element_id(&browser, "go"));
+        assert_eq!(browser.chrome.address_bar.text, "http://127.0.0.1:1/app");
+        assert_eq!(message(&browser).unwrap().1, "Value must be greater than or equal to 18.");
+        assert_eq!(browser.focus, FocusTarget::FormInput(element_id(&browser, "age")));
+
+        type_in(&browser, "age", "20");
+        browser.submit_form(element_id(&browser, "go"));
+        assert_eq!(browser.chrome.address_bar.text, "http://127.0.0.1:1/join?email=ana%40example.com&age=20");
+
+        // Scripts report a control invalid, formnovalidate sends the form
+        // as it is
+        browser.load_page(url, html).unwrap();
+        assert!(message(&browser).is_none());
+        assert_eq!(eval(&browser, "document.getElementById('email').reportValidity()").as_bool(), Some(false));
+        browser.poll_form_submissions();
+        assert_eq!(message(&browser).unwrap().0, element_id(&browser, "email"));
+        browser.submit_form(element_id(&browser, "skip"));
+        assert_eq!(browser.chrome.address_bar.text, "http://127.0.0.1:1/join?email=&age=");
+    }
+
+    #[test]
+    fn test_subresources_fetched_with_configured_agent() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        // A server answering a script with the User-Agent it was fetched with
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0; 1024];
+            while !String::from_utf8_lossy(&received).ends_with("\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..n]);
+            }
+            let request = String::from_utf8_lossy(&received).to_lowercase();
+            let agent = request.lines().find_map(|l| l.strip_prefix("user-agent: ")).unwrap_or("").to_string();
+            let body = format!("var agent = '{}';", agent);
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        });
+
+        let config = BrowserConfig { user_agent: Some("Tester/2.0".to_string()), ..test_config() };
+        let mut browser = Browser::new(config).unwrap();
+        browser.load_page(url, "<script src='/agent.js'></script>").unwrap();
+        server.join().unwrap();
+        assert_eq!(eval(&browser, "agent").as_str(), Some("tester/2.0"));
+    }
+
+    #[test]
+    fn test_navigator_reports_network_user_agent() {
+        let mut browser = test_browser();
+        let html = "<p id='ua'></p><script>\
+                        var ua = navigator.userAgent;\
+                        if (ua.includes('Gugalanna') && navigator.onLine) {\
+                            document.getElementById('ua').setAttribute('title', ua);\
+                        }</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let dom = page.dom.borrow();
+        let ua = dom.get_element_by_id("ua").unwrap();
+        let title = dom.get(ua).and_then(|n| n.as_element()).and_then(|e| e.get_attribute("title"));
+        assert_eq!(title, Some(gugalanna_net::DEFAULT_USER_AGENT));
+    }
+
+    #[test]
+    fn test_configured_agent_and_scripts() {
+        let config = BrowserConfig {
+            user_agent: Some("Tester/2.0".to_string()),
+            ..test_config()
+        };
+        let mut browser = Browser::new(config).unwrap();
+        let url = Url::parse("https://a.test/").unwrap();
+        browser.load_page(url.clone(), "<script>var ua = navigator.userAgent;</script>").unwrap();
+        assert_eq!(eval(&browser, "ua").as_str(), Some("Tester/2.0"));
+
+        // Pages load without scripts when they are turned off
+        browser.config.javascript_enabled = false;
+        let html = "<p id='p'>Static</p><script>document.getElementById('p').textContent = 'Run';</script>";
+        browser.load_page(url, html).unwrap();
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        assert!(page.js_runtime.is_none());
+        let dom = page.dom.borrow();
+        assert_eq!(dom.text_content(dom.get_element_by_id("p").unwrap()), "Static");
+    }
+
+    #[test]
+    fn test_addresses_and_searches() {
+        let mut config = BrowserConfig { config_dir: None, ..BrowserConfig::default() };
+        let address = |text: &str, config: &BrowserConfig| parse_address(text, config).unwrap().to_string();
+        assert_eq!(address("example.com/a", &config), "https://example.com/a");
+        assert_eq!(address("localhost:8000", &config), "https://localhost:8000/");
+        assert_eq!(address("http://a.test/", &config), "http://a.test/");
+        assert_eq!(address("rust lifetimes", &config), "https://duckduckgo.com/html/?q=rust+lifetimes");
+        assert_eq!(address("gugalanna", &config), "https://duckduckgo.com/html/?q=gugalanna");
+
+        // Both follow the configuration
+        config.search_engine = "https://find.test/search?q={}&safe=1".to_string();
+        config.default_scheme = "http".to_string();
+        assert_eq!(address("a&b c", &config), "https://find.test/search?q=a%26b+c&safe=1");
+        assert_eq!(address("example.com", &config), "http://example.com/");
+    }
+
+    #[test]
+    fn test_uncaught_errors_counted_in_chrome() {
+        let mut browser = test_browser();
+        let html = "<script>window.onerror = function(message) { return message === 'Error: expected'; };</script>\
+                    <script>throw new Error('expected');</script>\
+                    <script>undefinedFunction();</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+
+        // The error the handler marked handled is not counted
+        assert_eq!(browser.chrome.error_count, 1);
+    }
+
+    #[test]
+    fn test_bookmark_star_follows_active_tab() {
+        let mut browser = test_browser();
+        browser.load_page(Url::parse("https://a.test/").unwrap(), "<title> Page  A </title>").unwrap();
+        browser.toggle_bookmark();
+        assert!(browser.chrome.is_bookmarked);
+        assert_eq!(browser.bookmarks.iter().next().map(|b| b.title.as_str()), Some("Page A"));
+
+        let first = browser.active_tab_id;
+        browser.new_tab();
+        assert!(!browser.chrome.is_bookmarked);
+        browser.switch_to_tab(first);
+        assert!(browser.chrome.is_bookmarked);
+
+        // The manager page lists it, its delete link deletes it
+        browser.navigate("about:bookmarks").unwrap();
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let links = page.dom.borrow().get_elements_by_tag_name("a").len();
+        assert_eq!(links, 2);
+        browser.navigate("about:bookmarks?delete=https%3A%2F%2Fa.test%2F").unwrap();
+        assert_eq!(browser.active_tab().unwrap().current_url_string(), "about:bookmarks");
+        assert!(!browser.bookmarks.contains("https://a.test/"));
+    }
+
+    #[test]
+    fn test_find_bar_cycles_through_matches() {
+        use crate::event::{SCANCODE_ESCAPE, SCANCODE_F, SCANCODE_RETURN};
+
+        let mut browser = test_browser();
+        let html = "<p>One fox</p><div style=\"height: 2000px\"></div><p>Two FOX</p><p>Three fox</p>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let ctrl = Modifiers { ctrl: true, ..Modifiers::default() };
+        let highlights = |browser: &Browser| {
+            let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+            page.display_list.commands.iter().any(|c| matches!(c, gugalanna_render::PaintCommand::FillSelection { .. }))
+        };
+
+        browser.handle_key(SCANCODE_F, ctrl);
+        assert!(browser.chrome.find_bar.open);
+        browser.handle_text_input("Fox");
+        assert_eq!(browser.chrome.find_bar.counter(), "1/3");
+        assert!(highlights(&browser));
+        let scroll_y = |browser: &Browser| browser.active_tab().unwrap().page.as_ref().unwrap().scroll_y;
+        assert_eq!(scroll_y(&browser), 0.0);
+
+        // The second match is scrolled into view, Shift+Enter goes back
+        browser.handle_key(SCANCODE_RETURN, Modifiers::default());
+        assert_eq!(browser.chrome.find_bar.counter(), "2/3");
+        assert!(scroll_y(&browser) > 1000.0);
+        browser.handle_key(SCANCODE_RETURN, Modifiers { shift: true, ..Modifiers::default() });
+        browser.handle_key(SCANCODE_RETURN, Modifiers { shift: true, ..Modifiers::default() });
+        assert_eq!(browser.chrome.find_bar.counter(), "3/3");
+
+        browser.handle_key(SCANCODE_ESCAPE, Modifiers::default());
+        assert!(!browser.chrome.find_bar.open);
+        assert!(!highlights(&browser));
+
+        // A page navigated to starts without a search
+        browser.handle_key(SCANCODE_F, ctrl);
+        browser.handle_text_input("two");
+        assert_eq!(browser.chrome.find_bar.counter(), "1/1");
+        browser.load_page(Url::parse("https://b.test/").unwrap(), "<p>Two</p>").unwrap();
+        assert_eq!(browser.chrome.find_bar.counter(), "");
+        assert!(!highlights(&browser));
+    }
+
+    #[test]
+    fn test_context_menu_follows_what_was_clicked() {
+        use crate::context_menu::menu_items;
+        use crate::event::SCANCODE_ESCAPE;
+
+        let mut browser = test_browser();
+        let html = "<a href=\"/next\" style=\"display: block; height: 100px\">Next</a>\
+                    <p style=\"height: 100px\">Some quick text</p>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+
+        browser.handle_right_click(50.0, CHROME_HEIGHT + 50.0);
+        let menu = browser.context_menu.as_ref().unwrap();
+        assert_eq!(menu.context.link.as_ref().map(Url::as_str), Some("https://a.test/next"));
+        assert_eq!(
+            menu_items(&menu.context),
+            [MenuItem::OpenInNewTab, MenuItem::CopyLinkAddress, MenuItem::SaveLinkAs]
+        );
+        browser.handle_key(SCANCODE_ESCAPE, Modifiers::default());
+        assert!(browser.context_menu.is_none());
+
+        // Elsewhere it has the page's items, Back disabled without history
+        let y = CHROME_HEIGHT + 400.0;
+        browser.handle_right_click(50.0, y);
+        let menu = browser.context_menu.as_ref().unwrap();
+        assert_eq!(menu.context, MenuContext::default());
+        assert_eq!(menu.item_at(60.0, y + 15.0), None);
+        assert_eq!(menu.item_at(60.0, y + 4.0 + 26.0 * 2.0 + 13.0), Some(MenuItem::Reload));
+        // A click outside dismisses it
+        browser.handle_click(900.0, 700.0);
+        assert!(browser.context_menu.is_none());
+
+        // The current find match is the selection
+        browser.handle_key(crate::event::SCANCODE_F, Modifiers { ctrl: true, ..Modifiers::default() });
+        browser.handle_text_input("quick");
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let rect = page.find.current_rects()[0];
+        let (x, y) = (rect.x + rect.width / 2.0, rect.y + rect.height / 2.0 + CHROME_HEIGHT - page.scroll_y);
+        browser.handle_right_click(x, y);
+        let menu = browser.context_menu.as_ref().unwrap();
+        assert_eq!(menu.context.selection.as_deref(), Some("quick"));
+        assert_eq!(menu_items(&menu.context), [MenuItem::Copy]);
+    }
+
+    #[test]
+    fn test_link_status_on_hover() {
+        let mut browser = test_browser();
+        let html = "<a href=\"intro.html#start\" style=\"display: block; height: 100px\">Intro</a>\
+                    <a href=\"about:blank\" style=\"display: block; height: 100px\">Blank</a>\
+                    <p style=\"height: 100px\">Text</p>";
+        browser.load_page(Url::parse("https://a.test/docs/page.html").unwrap(), html).unwrap();
+        let (link, blank, text) = (CHROME_HEIGHT + 50.0, CHROME_HEIGHT + 150.0, CHROME_HEIGHT + 250.0);
+
+        // Relative links show resolved, and the status goes off the link
+        browser.handle_mouse_move(50.0, link);
+        assert_eq!(browser.chrome.status.as_deref(), Some("https://a.test/docs/intro.html#start"));
+        browser.handle_mouse_move(50.0, text);
+        assert_eq!(browser.chrome.status, None);
+        browser.handle_mouse_move(50.0, CHROME_HEIGHT / 2.0);
+        assert_eq!(browser.chrome.status, None);
+
+        // A middle-click opens the link behind, saying so for a moment
+        let first = browser.active_tab_id;
+        browser.handle_middle_click(50.0, blank);
+        assert_eq!((browser.tabs.len(), browser.active_tab_id), (2, first));
+        assert_eq!(browser.chrome.status.as_deref(), Some("Opening new tab\u{2026}"));
+        browser.mouse_position = (50.0, link);
+        browser.handle_mouse_move(50.0, link);
+        assert_eq!(browser.chrome.status.as_deref(), Some("Opening new tab\u{2026}"));
+        browser.expire_status(Instant::now() + STATUS_MESSAGE_DURATION);
+        assert_eq!(browser.chrome.status.as_deref(), Some("https://a.test/docs/intro.html#start"));
+    }
+
+    #[test]
+    fn test_navigation_to_a_file_downloads_it() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        // A server answering with a PDF
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/files/report.pdf", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).unwrap();
+            assert!(String::from_utf8_lossy(&buf[..n]).starts_with("GET /files/report.pdf"));
+            let headers = "Content-Type: application/pdf\r\nContent-Length: 8\r\nConnection: close";
+            write!(stream, "HTTP/1.1 200 OK\r\n{}\r\n\r\n%PDF-1.7", headers).unwrap();
+        });
+
+        let dir = std::env::temp_dir().join(format!("gugalanna-browser-downloads-{}", std::process::id()));
+        let config = BrowserConfig {
+            download_dir: Some(dir.clone()),
+            ..test_config()
+        };
+        let mut browser = Browser::new(config).unwrap();
+        browser.load_page(Url::parse("https://a.test/").unwrap(), "<p>Before</p>").unwrap();
+        browser.navigate(&url).unwrap();
+        server.join().unwrap();
+        browser.poll_downloads();
+
+        // The tab keeps its page, the file is listed in a new one
+        assert_eq!(browser.tabs[0].current_url_string(), "https://a.test/");
+        assert_eq!(browser.chrome.address_bar.text, DOWNLOADS_URL);
+        assert_eq!(std::fs::read(dir.join("report.pdf")).unwrap(), b"%PDF-1.7");
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let dom = page.dom.borrow();
+        let list = dom.text_content(dom.get_elements_by_tag_name("li")[0]);
+        assert!(list.starts_with("report.pdf Complete, 8 B"), "{}", list);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_devtools_inspect_node() {
+        use crate::event::SCANCODE_F12;
+
+        let mut browser = test_browser();
+        let padding = "padding-top: 5px; padding-right: 5px; padding-bottom: 5px; padding-left: 5px";
+        let html = format!("<div style=\"height: 2000px\"></div><p style=\"margin: 10px; {}\">Far</p>", padding);
+        browser.load_page(Url::parse("https://a.test/").unwrap(), &html).unwrap();
+        let viewport_height = |browser: &Browser| browser.active_tab().unwrap().page.as_ref().unwrap().viewport_height;
+        let full_height = viewport_height(&browser);
+
+        // DevTools take the bottom of the window from the page
+        browser.handle_key(SCANCODE_F12, Modifiers::default());
+        assert!(browser.devtools.open);
+        assert_eq!(viewport_height(&browser), full_height - DEVTOOLS_HEIGHT);
+
+        // Selecting a node scrolls it into view and shades its margin,
+        // padding and content boxes; it has no border
+        let p = browser.active_tab().unwrap().page.as_ref().unwrap().dom.borrow().get_elements_by_tag_name("p")[0];
+        browser.devtools.selected_element = Some(p);
+        browser.scroll_node_into_view(p);
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        assert!(page.scroll_y > 0.0);
+        let mut frame = DisplayList::new();
+        browser.render_element_highlight(&mut frame);
+        let fills = frame.commands.iter().filter(|c| matches!(c, gugalanna_render::PaintCommand::FillRect { .. }));
+        assert_eq!(fills.count(), 9);
+
+        browser.handle_key(SCANCODE_F12, Modifiers::default());
+        assert_eq!(viewport_height(&browser), full_height);
+    }
+
+    #[test]
+    fn test_devtools_console_lists_and_runs_code() {
+        use crate::event::{SCANCODE_F12, SCANCODE_RETURN, SCANCODE_UP};
+
+        let mut browser = test_browser();
+        let html = "<script>console.log('early')</script><p>Hi</p>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let texts = |browser: &Browser| -> Vec<String> {
+            let list = browser.devtools_display_list();
+            list.commands
+                .iter()
+                .filter_map(|c| match c {
+                    gugalanna_render::PaintCommand::DrawText { text, .. } => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Messages logged before DevTools opened are listed
+        browser.handle_key(SCANCODE_F12, Modifiers::default());
+        assert!(texts(&browser).iter().any(|text| text == "early"));
+
+        // Code typed in the console runs in the page, its result is logged
+        let code = "document.querySelector('p').textContent + 1";
+        browser.focus_console();
+        browser.handle_text_input(code);
+        browser.handle_key(SCANCODE_RETURN, Modifiers::default());
+        let listed = texts(&browser);
+        assert!(listed.iter().any(|text| *text == format!("> {}", code)), "{:?}", listed);
+        assert!(listed.iter().any(|text| text == "< 'Hi1'"), "{:?}", listed);
+        assert!(browser.devtools.console_input.is_empty());
+
+        // Up brings the code back
+        browser.handle_key(SCANCODE_UP, Modifiers::default());
+        assert_eq!(browser.devtools.console_input, code);
+    }
+
+    #[test]
+    fn test_view_source() {
+        let mut browser = test_browser();
+        let html = "<p>Hi <b>there</b></p>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+
+        // The page's HTML is shown, a.test is never fetched
+        browser.view_source();
+        assert_eq!(browser.tabs.len(), 2);
+        assert_eq!(browser.chrome.address_bar.text, "view-source:https://a.test/");
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let dom = page.dom.borrow();
+        assert!(dom.get_elements_by_tag_name("b").is_empty());
+        assert_eq!(dom.text_content(dom.get_elements_by_tag_name("div")[0]), "1<p>Hi <b>there</b></p>");
+        drop(dom);
+
+        // Typed in the address bar it is fetched, and kept in history
+        let path = std::env::temp_dir().join(format!("gugalanna-view-source-{}.html", std::process::id()));
+        std::fs::write(&path, "<i>file</i>\n").unwrap();
+        let file_url = Url::from_file_path(&path).unwrap();
+        browser.navigate(&format!("view-source:{}", file_url)).unwrap();
+        browser.navigate("about:blank").unwrap();
+        browser.go_back().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(browser.chrome.address_bar.text, format!("view-source:{}", file_url));
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let dom = page.dom.borrow();
+        assert_eq!(dom.text_content(dom.get_elements_by_tag_name("div")[0]), "1<i>file</i>");
+    }
+
+    #[test]
+    fn test_session_restore() {
+        let dir = std::env::temp_dir().join(format!("gugalanna-restore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let page_path = dir.join("long.html");
+        std::fs::write(&page_path, "<p>line</p>\n".repeat(300)).unwrap();
+        let long_page = format!("view-source:{}", Url::from_file_path(&page_path).unwrap());
+
+        let entry = |url: &str| session::SessionEntry { url: url.to_string(), state: None, same_document: false };
+        let saved = Session {
+            tabs: vec![
+                SessionTab { history: vec![entry("about:blank"), entry(&long_page)], current: 1, scroll_y: 400.0 },
+                SessionTab { history: vec![entry("about:blank"), entry("about:bookmarks")], current: 0, scroll_y: 0.0 },
+            ],
+            active: 0,
+        };
+        saved.save(&dir.join("session.json")).unwrap();
+
+        let config = BrowserConfig {
+            config_dir: Some(dir.clone()),
+            restore_session: true,
+            ..test_config()
+        };
+        let mut browser = Browser::new(config).unwrap();
+
+        // The tab shown is loaded and scrolled, the other waits to be shown
+        assert_eq!(browser.tabs.len(), 2);
+        assert_eq!(browser.chrome.address_bar.text, long_page);
+        assert_eq!(browser.tabs[0].page.as_ref().unwrap().scroll_y, 400.0);
+        assert!(browser.tabs[0].navigation.can_go_back());
+        assert!(browser.tabs[1].page.is_none());
+        assert_eq!(browser.current_session(), saved);
+
+        let second = browser.tabs[1].id;
+        browser.switch_to_tab(second);
+        assert_eq!(browser.chrome.address_bar.text, "about:blank");
+        assert!(browser.tabs[1].navigation.can_go_forward());
+
+        // Saved again it round-trips, with the tab now shown
+        browser.save_session(true);
+        let reloaded = Session::load(&dir.join("session.json")).unwrap();
+        assert_eq!(reloaded, Session { active: 1, ..saved.clone() });
+
+        // A closed tab reopens with its history
+        let first = browser.tabs[0].id;
+        browser.close_tab(first);
+        assert_eq!(browser.tabs.len(), 1);
+        browser.reopen_closed_tab().unwrap();
+        assert_eq!(browser.current_session().tabs[1], saved.tabs[0]);
+        assert!(browser.reopen_closed_tab().is_none());
+
+        // A corrupt session starts afresh
+        std::fs::write(dir.join("session.json"), "{\"tabs\": [").unwrap();
+        let config = BrowserConfig {
+            config_dir: Some(dir.clone()),
+            restore_session: true,
+            ..test_config()
+        };
+        let browser = Browser::new(config).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(browser.tabs.len(), 1);
+        assert!(browser.tabs[0].navigation.is_empty());
+    }
+
+    #[test]
+    fn test_cached_favicon_shown_at_once() {
+        let mut browser = test_browser();
+        let icon = ImagePixels { width: 16, height: 16, data: vec![255; 16 * 16 * 4].into() };
+        browser.favicons.insert(&Url::parse("https://a.test/").unwrap(), icon.clone());
+
+        browser.load_page(Url::parse("https://b.test/").unwrap(), "<p>B</p>").unwrap();
+        assert_eq!(browser.chrome.favicon, None);
+
+        // A new tab to the site shows its icon without fetching it
+        browser.new_tab();
+        browser.load_page(Url::parse("https://a.test/other").unwrap(), "<p>A</p>").unwrap();
+        assert_eq!(browser.chrome.favicon.as_ref(), Some(&icon));
+        assert_eq!(browser.chrome.tabs[1].favicon.as_ref(), Some(&icon));
+        assert_eq!(browser.chrome.tabs[0].favicon, None);
+    }
+
+    #[test]
+    fn test_reader_mode_toggles_without_refetch() {
+        let mut browser = test_browser();
+        browser.load_page(Url::parse("https://ledger.test/front").unwrap(), "<p>Front page</p>").unwrap();
+        assert!(!browser.chrome.reader_button.enabled);
+        browser.toggle_reader_mode();
+        assert!(!browser.is_reader_mode());
+
+        let url = Url::parse("https://ledger.test/library").unwrap();
+        let html = include_str!("../../../test-pages/reader/news-article.html");
+        browser.load_page(url.clone(), html).unwrap();
+        assert!(browser.chrome.reader_button.enabled);
+        browser.active_tab_mut().unwrap().page.as_mut().unwrap().scroll_to(None, 40.0);
+
+        // The reader page shows the article at the page's URL, without a
+        // history entry of its own
+        browser.toggle_reader_mode();
+        assert!(browser.is_reader_mode() && browser.chrome.is_reader_mode);
+        let tab = browser.active_tab().unwrap();
+        let page = tab.page.as_ref().unwrap();
+        assert_eq!(page.url, url);
+        assert!(page.source.contains("<h1>City Opens New Riverside Library</h1>"));
+        assert!(!page.dom.borrow().text_content(page.dom.borrow().document_id()).contains("Most read"));
+        assert_eq!(tab.navigation.len(), 2);
+        assert_eq!(browser.chrome.address_bar.text, url.as_str());
+
+        // Larger text lays the article out taller
+        let height = page.content_height;
+        browser.handle_key(crate::event::SCANCODE_EQUALS, Modifiers { ctrl: true, ..Modifiers::default() });
+        assert_eq!(browser.reader_font_size, READER_FONT_SIZE + 2.0);
+        assert!(browser.active_tab().unwrap().page.as_ref().unwrap().content_height > height);
+        assert!(browser.is_reader_mode());
+
+        // Leaving it brings the page back as it was
+        browser.toggle_reader_mode();
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        assert!(!browser.is_reader_mode());
+        assert_eq!(page.source, html);
+        assert_eq!(page.scroll_y, 40.0);
+
+        // A new page leaves reader mode
+        browser.toggle_reader_mode();
+        browser.load_page(Url::parse("https://ledger.test/next").unwrap(), "<p>Next</p>").unwrap();
+        assert!(!browser.is_reader_mode() && !browser.chrome.is_reader_mode);
+    }
+
+    #[test]
+    fn test_page_captured_below_the_fold() {
+        let dir = std::env::temp_dir().join(format!("gugalanna-captures-{}", std::process::id()));
+        let config = BrowserConfig {
+            width: 400,
+            height: 300,
+            download_dir: Some(dir.clone()),
+            ..test_config()
+        };
+        let mut browser = Browser::new(config).unwrap();
+        let html = "<style>body { margin: 0; } .top { height: 200px; background-color: #ff0000; } \
+                    .rest { height: 1000px; background-color: #0000ff; }</style>\
+                    <div class='top'></div><div class='rest'></div>";
+        browser.load_page(Url::parse("https://a.test/long.html").unwrap(), html).unwrap();
+        browser.active_tab_mut().unwrap().page.as_mut().unwrap().scroll_to(None, 500.0);
+
+        // The whole page, not the part scrolled to, without the chrome
+        let path = browser.save_page_capture(CaptureFormat::Png).unwrap();
+        assert_eq!(path, dir.join("long.png"));
+        assert_eq!(browser.chrome.toast.as_deref(), Some("Saved the page as long.png"));
+        let image = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (400, 1200));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(399, 199).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(200, 200).0, [0, 0, 255, 255]);
+        assert_eq!(image.get_pixel(200, 1199).0, [0, 0, 255, 255]);
+
+        let pdf = browser.save_page_capture(CaptureFormat::Pdf).unwrap();
+        assert_eq!(pdf, dir.join("long.pdf"));
+        assert!(std::fs::read(&pdf).unwrap().starts_with(b"%PDF-1.4"));
+        assert_eq!(browser.save_page_capture(CaptureFormat::Png), Some(dir.join("long (1).png")));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The toast goes away in time
+        browser.expire_toast(Instant::now() + TOAST_DURATION);
+        assert_eq!(browser.chrome.toast, None);
+    }
+
+    #[test]
+    fn test_private_tab_state_isolated() {
+        let mut browser = test_browser();
+        let url = Url::parse("https://a.test/").unwrap();
+        let normal = browser.active_tab_id;
+        let private = browser.new_private_tab();
+        let html = "<script>document.cookie = 'id=secret'; localStorage.setItem('seen', 'yes');</script>";
+        browser.load_page(url.clone(), html).unwrap();
+        assert!(browser.chrome.tabs[1].is_private);
+
+        // The cookie is in the private jar alone, and the page in no saved tab
+        let private_jar = browser.client_for(private).cookie_jar();
+        let cookies = |jar: &gugalanna_net::SharedCookieJar| {
+            jar.lock().unwrap().cookie_string(&url, gugalanna_net::CookieSource::Http)
+        };
+        assert_eq!(cookies(&private_jar), "id=secret");
+        assert_eq!(cookies(&browser.client_for(normal).cookie_jar()), "");
+        assert_eq!(browser.current_session().tabs.len(), 0);
+
+        // A normal tab of the same site sees none of it
+        browser.switch_to_tab(normal);
+        let html = "<script>var seen = document.cookie + '|' + localStorage.getItem('seen');</script>";
+        browser.load_page(url.clone(), html).unwrap();
+        assert_eq!(eval(&browser, "seen").as_str(), Some("|null"));
+
+        // Closing the last private tab drops its state, and it cannot be
+        // reopened
+        assert!(!browser.close_tab(private));
+        assert!(browser.private_state.is_none());
+        assert!(browser.closed_tabs.is_empty());
+        browser.new_private_tab();
+        assert_eq!(cookies(&browser.client_for(browser.active_tab_id).cookie_jar()), "");
+    }
+
+    #[test]
+    fn test_confirm_answered_through_browser() {
+        let mut browser = test_browser();
+        browser.answer_next_dialog(Some(String::new()));
+        browser.answer_next_dialog(None);
+        browser.answer_next_dialog(Some("Ada".to_string()));
+        let html = "<script>globalThis.accepted = confirm('Delete the file?');\
+                        globalThis.dismissed = confirm('Really?');\
+                        globalThis.name = prompt('Name?', 'Anonymous');</script>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+
+        assert_eq!(eval(&browser, "accepted").as_bool(), Some(true));
+        assert_eq!(eval(&browser, "dismissed").as_bool(), Some(false));
+        assert_eq!(eval(&browser, "name").as_str(), Some("Ada"));
+
+        // Dialogs nothing answers are dismissed
+        assert_eq!(eval(&browser, "confirm('Again?')").as_bool(), Some(false));
+        assert_eq!(eval(&browser, "prompt('Name?') === null").as_bool(), Some(true));
+    }
+
+    #[test]
+    fn test_error_page_tries_again() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let mut browser = test_browser();
+        let wait = |browser: &mut Browser| {
+            let started = Instant::now();
+            while browser.active_tab().unwrap().is_loading() {
+                assert!(started.elapsed() < Duration::from_secs(10));
+                browser.poll_navigation();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        // Nothing listens yet, the connection is refused
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let url = format!("http://{}/news", addr);
+        browser.navigate_async(&url).unwrap();
+        wait(&mut browser);
+
+        // The error page counts down to a retry, the URL kept to try again
+        assert_eq!(browser.chrome.address_bar.text, url);
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let text = page.dom.borrow().text_content(page.dom.borrow().document_id());
+        assert!(text.contains("Unable to Connect") && text.contains("Error: Connection error"), "{}", text);
+        assert!(text.contains("Trying again in 5 seconds"), "{}", text);
+        browser.tick_error_retry(Instant::now() + Duration::from_millis(1500));
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        assert!(page.dom.borrow().text_content(page.dom.borrow().document_id()).contains("Trying again in 4 seconds"));
+
+        // The server is back, the button loads the page
+        let listener = TcpListener::bind(addr).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).unwrap();
+            let headers = "Content-Length: 15\r\nConnection: close";
+            write!(stream, "HTTP/1.1 200 OK\r\n{}\r\n\r\n<p>Headline</p>", headers).unwrap();
+        });
+        let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+        let button = page.dom.borrow().get_elements_by_tag_name("button")[0];
+        let region = page.hit_regions.iter().find(|r| r.node_id == button.0).unwrap();
+        let (x, y) = (region.x + region.width / 2.0, CHROME_HEIGHT + region.y + region.height / 2.0);
+        browser.handle_click(x, y);
+        wait(&mut browser);
+        server.join().unwrap();
+
+        let tab = browser.active_tab().unwrap();
+        assert_eq!(tab.current_url_string(), url);
+        let page = tab.page.as_ref().unwrap();
+        assert!(page.dom.borrow().text_content(page.dom.borrow().document_id()).contains("Headline"));
+        assert!(browser.error_retry.is_none());
+    }
+
+    #[test]
+    fn test_side_buttons_and_swipes_navigate() {
+        let mut browser = test_browser();
+        browser.navigate("about:blank").unwrap();
+        browser.navigate(BOOKMARKS_URL).unwrap();
+        let current = |browser: &Browser| browser.active_tab().unwrap().current_url_string();
+
+        // As the chrome's buttons, forward does nothing at the last page
+        browser.handle_history_button(MouseButton::Forward);
+        assert_eq!(current(&browser), BOOKMARKS_URL);
+        browser.handle_history_button(MouseButton::Back);
+        assert_eq!(current(&browser), "about:blank");
+
+        // Nor while the devtools console has focus
+        browser.devtools.console_focused = true;
+        browser.handle_history_button(MouseButton::Forward);
+        assert_eq!(current(&browser), "about:blank");
+        browser.devtools.console_focused = false;
+
+        // Swipes only once turned on
+        let now = Instant::now();
+        browser.handle_swipe(6, now);
+        assert_eq!(current(&browser), "about:blank");
+        browser.config.swipe_navigation = true;
+        browser.handle_swipe(6, now + Duration::from_secs(1));
+        assert_eq!(current(&browser), BOOKMARKS_URL);
+    }
+
+    #[test]
+    fn test_tab_moves_focus_through_page() {
+        use crate::event::{SCANCODE_RETURN, SCANCODE_SPACE, SCANCODE_TAB};
+        use gugalanna_render::{BorderColors, PaintCommand};
+
+        let mut browser = test_browser();
+        let html = "<a id='later' href='#end' tabindex='2'>Later</a>\
+                    <input id='name'>\
+                    <a id='hidden' href='/hidden' style='display: none'>Hidden</a>\
+                    <button id='off' disabled>Off</button>\
+                    <input id='agree' type='checkbox' tabindex='1'>\
+                    <div style='height: 3000px'></div>\
+                    <a id='end' href='/end'>End</a>";
+        browser.load_page(Url::parse("https://a.test/").unwrap(), html).unwrap();
+        let scroll_y = |browser: &Browser| browser.active_tab().unwrap().page.as_ref().unwrap().scroll_y;
+        let tab = |browser: &mut Browser, shift: bool| {
+            browser.handle_key(SCANCODE_TAB, Modifiers { shift, ..Modifiers::default() });
+            browser.focus
+        };
+
+        // Positive tabindex first, the hidden link and disabled button are
+        // skipped
+        assert_eq!(tab(&mut browser, false), FocusTarget::Element(element_id(&browser, "agree")));
+        browser.handle_key(SCANCODE_SPACE, Modifiers::default());
+        assert!(browser.active_tab().unwrap().form_state.borrow().is_checked(element_id(&browser, "agree")));
+        assert_eq!(tab(&mut browser, false), FocusTarget::Element(element_id(&browser, "later")));
+        assert_eq!(tab(&mut browser, false), FocusTarget::FormInput(element_id(&browser, "name")));
+
+        // The last link is scrolled into view and ringed, then focus goes
+        // around
+        assert_eq!(tab(&mut browser, false), FocusTarget::Element(element_id(&browser, "end")));
+        assert!(scroll_y(&browser) > 1000.0);
+        browser.render();
+        let ring = BorderColors::all(RenderColor::rgb(66, 133, 244));
+        assert!(browser.painted_frame.as_ref().unwrap().commands.iter().any(|command| matches!(
+            command,
+            PaintCommand::DrawBorder { rect, colors, .. } if *colors == ring && rect.y > CHROME_HEIGHT
+        )));
+        assert_eq!(tab(&mut browser, false), FocusTarget::Element(element_id(&browser, "agree")));
+        assert!(scroll_y(&browser) < 100.0);
+        assert_eq!(tab(&mut browser, true), FocusTarget::Element(element_id(&browser, "end")));
+
+        // Enter follows the focused link
+        assert_eq!(tab(&mut browser, true), FocusTarget::FormInput(element_id(&browser, "name")));
+        assert_eq!(tab(&mut browser, true), FocusTarget::Element(element_id(&browser, "later")));
+        assert!(scroll_y(&browser) < 100.0);
+        browser.handle_key(SCANCODE_RETURN, Modifiers::default());
+        assert!(scroll_y(&browser) > 1000.0);
+    }
+
+    #[test]
+    fn test_form_data_newlines_sent_as_crlf() {
+        let fields = [FormField { name: "note".to_string(), value: "one\ntwo\r\nthree\r".to_string(), file: None }];
+        assert_eq!(build_form_data_string(&fields), "note=one%0D%0Atwo%0D%0Athree%0D%0A");
+    }
+
+    #[test]
+    fn test_label_clicks_its_control() {
+        let mut browser = test_browser();
+        let html = "<form><p><label id='news-label' for='news'>Send me news</label></p>\
+                    <p><input type='checkbox' id='news'></p>\
+                    <p><label id='terms-label'>I agree <input type='checkbox' id='terms'> to the \
+                    <a id='terms-link' href='#terms'>terms</a></label></p>\
+                    <p><label id='name-label'>Name <input id='name'></label></p></form>\
+                    <script>globalThis.clicks = 0;\
+                        document.getElementById('news').addEventListener('click', function() { clicks++; });\
+                    </script>";
+        browser.load_page(Url::parse("http://127.0.0.1:1/app").unwrap(), html).unwrap();
+        let checked = |browser: &Browser, name: &str| {
+            browser.active_tab().unwrap().form_state.borrow().is_checked(element_id(browser, name))
+        };
+        // Click the first text in an element
+        let click_text = |browser: &mut Browser, name: &str| {
+            let element = element_id(browser, name);
+            let page = browser.active_tab().unwrap().page.as_ref().unwrap();
+            let dom = page.dom.borrow();
+            let region = page
+                .hit_regions
+                .iter()
+                .find(|r| {
+                    let node = NodeId(r.node_id);
+                    dom.parent(node) == Some(element) && dom.get(node).is_some_and(|n| n.as_element().is_none())
+                })
+                .unwrap();
+            let (x, y) = (region.x + 2.0, CHROME_HEIGHT + region.y + region.height / 2.0);
+            drop(dom);
+            browser.handle_click(x, y);
+        };
+
+        // A label with `for` toggles the control it names, which hears the
+        // click
+        click_text(&mut browser, "news-label");
+        assert!(checked(&browser, "news"));
+        assert_eq!(eval(&browser, "clicks").as_number(), Some(1.0));
+        click_text(&mut browser, "news-label");
+        assert!(!checked(&browser, "news"));
+
+        // A label around its control toggles it, but a link in it takes
+        // its own clicks
+        click_text(&mut browser, "terms-label");
+        assert!(checked(&browser, "terms"));
+        click_text(&mut browser, "terms-link");
+        assert!(checked(&browser, "terms"));
+
+        click_text(&mut browser, "name-label");
+        assert_eq!(browser.focus, FocusTarget::FormInput(element_id(&browser, "name")));
+    }
+
+    #[test]
+    fn test_file_input_uploads_chosen_files() {
+        let dir = std::env::temp_dir().join(format!("gugalanna-upload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "file body").unwrap();
+
+        let html = r#"<form id="f" method="post" enctype="multipart/form-data">
+            <input name="title" value="Trip">
+            <input id="upload" type="file" name="upload" multiple>
+            <input type="file" name="empty">
+        </form>"#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let form_id = dom.get_element_by_id("f").unwrap();
+        let upload = dom.get_element_by_id("upload").unwrap();
+        assert!(matches!(find_form_element(&dom, upload), Some(FormElementInfo::File { multiple: true, .. })));
+        let mut form_state = FormState::new();
+        form_state.set_files(upload, vec![path.clone()]);
+
+        // Sent urlencoded, a file input sends just the name of the file
+        let fields = collect_form_data(&dom, form_id, &form_state);
+        assert_eq!(build_form_data_string(&fields), "title=Trip&upload=notes.txt&empty=");
+
+        let form = build_multipart(&fields).unwrap();
+        let body = String::from_utf8(form.to_bytes()).unwrap();
+        let boundary = form.boundary();
+        assert!(body.contains("name=\"title\"\r\n\r\nTrip\r\n"));
+        assert!(body.contains(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"notes.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\nfile body\r\n",
+            boundary
+        )));
+        assert!(body.contains("name=\"empty\"; filename=\"\"\r\nContent-Type: application/octet-stream\r\n\r\n\r\n"));
+        assert!(body.ends_with(&format!("--{}--\r\n", boundary)));
+
+        // A file gone by the time the form is sent fails the submission
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(build_multipart(&fields).is_err());
+    }
+}
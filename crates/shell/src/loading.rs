@@ -2,6 +2,7 @@
 //!
 //! Types for tracking navigation state and errors.
 
+use gugalanna_net::{NetError, StreamingResponse};
 use url::Url;
 
 /// Loading state for the browser
@@ -14,6 +15,11 @@ pub enum LoadingState {
     Loading {
         /// URL being loaded
         url: Url,
+        /// How far the load has come, from 0 to 1
+        progress: f32,
+        /// Whether how far the body has come is not known, the response
+        /// not giving its length
+        indeterminate: bool,
     },
     /// Navigation failed
     Failed {
@@ -24,13 +30,77 @@ pub enum LoadingState {
     },
 }
 
+impl LoadingState {
+    /// Start loading `url`
+    pub fn loading(url: Url) -> Self {
+        Self::Loading { url, progress: 0.0, indeterminate: false }
+    }
+
+    /// Move a load on to a milestone, its progress never going back
+    pub fn reach(&mut self, milestone: LoadMilestone) {
+        if let Self::Loading { progress, indeterminate, .. } = self {
+            match milestone.progress() {
+                Some(reached) => {
+                    *progress = progress.max(reached);
+                    *indeterminate = false;
+                }
+                None => *indeterminate = true,
+            }
+        }
+    }
+
+    /// How far the load has come, None when nothing loads or how far is
+    /// not known
+    pub fn progress(&self) -> Option<f32> {
+        match self {
+            Self::Loading { progress, indeterminate: false, .. } => Some(*progress),
+            _ => None,
+        }
+    }
+}
+
+/// A step of a page load
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadMilestone {
+    /// The request was sent
+    RequestSent,
+    /// The response's headers arrived
+    HeadersReceived,
+    /// Part of the body arrived, `total` being its length when the response
+    /// gives it
+    BodyReceived { received: u64, total: Option<u64> },
+    /// The page was parsed
+    Parsed,
+    /// The page was painted
+    Painted,
+}
+
+impl LoadMilestone {
+    /// How far a load at this step has come, from 0 to 1, None when that
+    /// is not known
+    pub fn progress(&self) -> Option<f32> {
+        match *self {
+            Self::RequestSent => Some(0.1),
+            Self::HeadersReceived => Some(0.3),
+            Self::BodyReceived { received, total: Some(total) } if total > 0 => {
+                Some(0.3 + 0.4 * received.min(total) as f32 / total as f32)
+            }
+            Self::BodyReceived { .. } => None,
+            Self::Parsed => Some(0.85),
+            Self::Painted => Some(1.0),
+        }
+    }
+}
+
 /// Navigation error types
 #[derive(Debug, Clone)]
 pub enum NavigationError {
     /// HTTP error (404, 500, etc.)
     HttpError { status: u16 },
     /// Network unreachable / connection refused
-    NetworkError(String),
+    ConnectionFailed(String),
+    /// Any other failure to fetch the page, `kind` naming the kind of error
+    NetworkError { kind: &'static str, message: String },
     /// Request timed out
     Timeout,
     /// Navigation was cancelled
@@ -44,7 +114,8 @@ impl NavigationError {
             Self::HttpError { status } if *status == 404 => "Page Not Found",
             Self::HttpError { status } if *status >= 500 => "Server Error",
             Self::HttpError { .. } => "HTTP Error",
-            Self::NetworkError(_) => "Network Error",
+            Self::ConnectionFailed(_) => "Unable to Connect",
+            Self::NetworkError { .. } => "Network Error",
             Self::Timeout => "Connection Timed Out",
             Self::Cancelled => "Navigation Cancelled",
         }
@@ -54,11 +125,40 @@ impl NavigationError {
     pub fn details(&self) -> String {
         match self {
             Self::HttpError { status } => format!("The server returned status code {}", status),
-            Self::NetworkError(msg) => msg.clone(),
+            Self::ConnectionFailed(msg) => msg.clone(),
+            Self::NetworkError { message, .. } => message.clone(),
             Self::Timeout => "The connection took too long to respond.".into(),
             Self::Cancelled => "Navigation was cancelled.".into(),
         }
     }
+
+    /// Name of the kind of error, for the diagnostics of the error page
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::HttpError { .. } => "HTTP error",
+            Self::ConnectionFailed(_) => "Connection error",
+            Self::NetworkError { kind, .. } => kind,
+            Self::Timeout => "Timeout",
+            Self::Cancelled => "Cancelled",
+        }
+    }
+
+    /// Whether the navigation is tried again by itself, the network
+    /// likely to be back in a moment
+    pub fn retries_automatically(&self) -> bool {
+        matches!(self, Self::ConnectionFailed(_) | Self::Timeout)
+    }
+}
+
+impl From<NetError> for NavigationError {
+    fn from(e: NetError) -> Self {
+        match e {
+            NetError::Timeout => Self::Timeout,
+            NetError::ConnectionError(msg) => Self::ConnectionFailed(msg),
+            _ if e.to_string().contains("timed out") => Self::Timeout,
+            _ => Self::NetworkError { kind: e.kind(), message: e.to_string() },
+        }
+    }
 }
 
 /// Result from async navigation task
@@ -70,6 +170,11 @@ pub enum NavigationResult {
         /// HTML content
         html: String,
     },
+    /// The response is not a page, it is saved as a download
+    Download {
+        /// Response whose body has not been read yet
+        response: StreamingResponse,
+    },
     /// Navigation failed
     Failed {
         /// URL that failed
@@ -78,3 +183,65 @@ pub enum NavigationResult {
         error: NavigationError,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_through_milestones() {
+        let mut state = LoadingState::loading(Url::parse("https://example.com/").unwrap());
+        assert_eq!(state.progress(), Some(0.0));
+
+        let milestones = [
+            LoadMilestone::RequestSent,
+            LoadMilestone::HeadersReceived,
+            LoadMilestone::BodyReceived { received: 250, total: Some(1000) },
+            LoadMilestone::BodyReceived { received: 1000, total: Some(1000) },
+            LoadMilestone::Parsed,
+            LoadMilestone::Painted,
+        ];
+        let mut last = 0.0;
+        for milestone in milestones {
+            state.reach(milestone);
+            let progress = state.progress().unwrap();
+            assert!(progress > last, "{:?} went from {} to {}", milestone, last, progress);
+            last = progress;
+        }
+        assert_eq!(last, 1.0);
+
+        // A step arriving late does not take the load back
+        state.reach(LoadMilestone::HeadersReceived);
+        assert_eq!(state.progress(), Some(1.0));
+        LoadingState::Idle.reach(LoadMilestone::Painted);
+    }
+
+    #[test]
+    fn test_errors_from_network() {
+        let error = NavigationError::from(NetError::ConnectionError("connection refused".into()));
+        assert_eq!(error.title(), "Unable to Connect");
+        assert_eq!(error.kind(), "Connection error");
+        assert!(error.retries_automatically());
+        assert!(NavigationError::from(NetError::Timeout).retries_automatically());
+
+        let error = NavigationError::from(NetError::TlsError("bad certificate".into()));
+        assert_eq!(error.kind(), "TLS error");
+        assert_eq!(error.details(), "TLS error: bad certificate");
+        assert!(!error.retries_automatically());
+        assert!(!NavigationError::HttpError { status: 404 }.retries_automatically());
+    }
+
+    #[test]
+    fn test_progress_without_length() {
+        let mut state = LoadingState::loading(Url::parse("https://example.com/").unwrap());
+        state.reach(LoadMilestone::HeadersReceived);
+        state.reach(LoadMilestone::BodyReceived { received: 4096, total: None });
+        assert_eq!(state.progress(), None);
+        assert!(matches!(state, LoadingState::Loading { progress, .. } if progress == 0.3));
+
+        // Once parsed, how far it is is known again
+        state.reach(LoadMilestone::Parsed);
+        assert_eq!(state.progress(), Some(0.85));
+        assert_eq!(LoadingState::Idle.progress(), None);
+    }
+}
@@ -1,14 +1,36 @@
 //! Navigation state and history management
 //!
-//! Handles back/forward navigation with a history stack.
+//! Handles back/forward navigation with a history stack. Entries added by
+//! the History API belong to the document of the entry before them, moving
+//! between such entries doesn't load a document.
 
 use url::Url;
 
+/// An entry of the history stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub url: Url,
+    /// State object a script stored with the entry, serialized as JSON
+    pub state: Option<String>,
+    /// Added by pushState, so it shares the document of the entry before
+    pub same_document: bool,
+}
+
+/// Where a move through the history ends up
+#[derive(Debug, Clone, PartialEq)]
+pub struct Traversal {
+    pub url: Url,
+    pub state: Option<String>,
+    /// The entry belongs to the document shown before the move, so it is
+    /// not loaded again
+    pub same_document: bool,
+}
+
 /// Navigation state with history stack
 #[derive(Debug)]
 pub struct NavigationState {
     /// History stack (all visited URLs)
-    history: Vec<Url>,
+    history: Vec<HistoryEntry>,
     /// Current position in history (0-indexed, -1 if empty)
     current_index: i32,
 }
@@ -22,8 +44,34 @@ impl NavigationState {
         }
     }
 
+    /// A history of entries, at the one at `current_index` or else the last;
+    /// None without entries
+    pub fn from_entries(history: Vec<HistoryEntry>, current_index: usize) -> Option<Self> {
+        let last = history.len().checked_sub(1)?;
+        Some(Self { current_index: current_index.min(last) as i32, history })
+    }
+
+    /// The entries of the history, oldest first
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
     /// Get the current URL, if any
     pub fn current_url(&self) -> Option<&Url> {
+        self.current_entry().map(|entry| &entry.url)
+    }
+
+    /// Get the state object stored with the current entry
+    pub fn current_state(&self) -> Option<&str> {
+        self.current_entry().and_then(|entry| entry.state.as_deref())
+    }
+
+    /// Get the position of the current entry
+    pub fn current_index(&self) -> usize {
+        self.current_index.max(0) as usize
+    }
+
+    fn current_entry(&self) -> Option<&HistoryEntry> {
         if self.current_index >= 0 && (self.current_index as usize) < self.history.len() {
             Some(&self.history[self.current_index as usize])
         } else {
@@ -45,39 +93,72 @@ impl NavigationState {
     ///
     /// This clears any forward history (pages we went back from).
     pub fn navigate_to(&mut self, url: Url) {
+        self.push(HistoryEntry { url, state: None, same_document: false });
+    }
+
+    /// Add an entry for the current document, as `history.pushState` does
+    ///
+    /// This clears any forward history too.
+    pub fn push_state(&mut self, url: Url, state: Option<String>) {
+        self.push(HistoryEntry { url, state, same_document: true });
+    }
+
+    /// Change the URL and state of the current entry, as
+    /// `history.replaceState` does
+    pub fn replace_state(&mut self, url: Url, state: Option<String>) {
+        let index = self.current_index();
+        if let Some(entry) = self.history.get_mut(index) {
+            entry.url = url;
+            entry.state = state;
+        }
+    }
+
+    fn push(&mut self, entry: HistoryEntry) {
         // If we're not at the end of history, truncate forward history
         if self.current_index >= 0 {
             let new_len = (self.current_index + 1) as usize;
             self.history.truncate(new_len);
         }
 
-        // Add new URL to history
-        self.history.push(url);
+        // Add new entry to history
+        self.history.push(entry);
         self.current_index = (self.history.len() - 1) as i32;
     }
 
+    /// Move `delta` entries through the history, back when negative
+    ///
+    /// Returns None, leaving the position as it is, when there are not
+    /// that many entries.
+    pub fn traverse(&mut self, delta: i32) -> Option<Traversal> {
+        let from = self.current_index;
+        let to = from.checked_add(delta)?;
+        if delta == 0 || from < 0 || to < 0 || to as usize >= self.history.len() {
+            return None;
+        }
+        // Entries between are all of one document when the later of each
+        // pair was pushed on the earlier
+        let (low, high) = (from.min(to) as usize, from.max(to) as usize);
+        let same_document = self.history[low + 1..=high].iter().all(|entry| entry.same_document);
+
+        self.current_index = to;
+        let entry = &self.history[to as usize];
+        Some(Traversal { url: entry.url.clone(), state: entry.state.clone(), same_document })
+    }
+
     /// Go back in history
     ///
     /// Returns the URL to navigate to, or None if at the beginning.
     pub fn go_back(&mut self) -> Option<&Url> {
-        if self.can_go_back() {
-            self.current_index -= 1;
-            self.current_url()
-        } else {
-            None
-        }
+        self.traverse(-1)?;
+        self.current_url()
     }
 
     /// Go forward in history
     ///
     /// Returns the URL to navigate to, or None if at the end.
     pub fn go_forward(&mut self) -> Option<&Url> {
-        if self.can_go_forward() {
-            self.current_index += 1;
-            self.current_url()
-        } else {
-            None
-        }
+        self.traverse(1)?;
+        self.current_url()
     }
 
     /// Get the number of entries in history
@@ -205,4 +286,42 @@ mod tests {
         assert!(nav.go_forward().is_none());
         assert_eq!(nav.current_url().unwrap().as_str(), "https://page1.com/");
     }
+
+    #[test]
+    fn test_pushed_entries_share_document() {
+        let mut nav = NavigationState::new();
+        nav.navigate_to(url("https://app.test/"));
+        nav.push_state(url("https://app.test/inbox"), Some(r#"{"page":1}"#.to_string()));
+        nav.replace_state(url("https://app.test/inbox?page=2"), Some(r#"{"page":2}"#.to_string()));
+        nav.navigate_to(url("https://other.test/"));
+
+        // Back to the other document loads it
+        let back = nav.traverse(-1).unwrap();
+        assert_eq!(back.url.as_str(), "https://app.test/inbox?page=2");
+        assert_eq!(back.state.as_deref(), Some(r#"{"page":2}"#));
+        assert!(!back.same_document);
+
+        // Back across the pushed entry stays in the document
+        let back = nav.traverse(-1).unwrap();
+        assert_eq!(back.url.as_str(), "https://app.test/");
+        assert_eq!(back.state, None);
+        assert!(back.same_document);
+
+        assert!(nav.traverse(5).is_none());
+        assert_eq!(nav.current_index(), 0);
+        assert!(!nav.traverse(2).unwrap().same_document);
+    }
+
+    #[test]
+    fn test_from_entries() {
+        let entry = |s: &str| HistoryEntry { url: url(s), state: None, same_document: false };
+        let nav = NavigationState::from_entries(vec![entry("https://a.test/"), entry("https://b.test/")], 0).unwrap();
+        assert_eq!(nav.current_url().unwrap().as_str(), "https://a.test/");
+        assert!(nav.can_go_forward());
+
+        // A position past the end is the last entry
+        let nav = NavigationState::from_entries(vec![entry("https://a.test/")], 4).unwrap();
+        assert_eq!(nav.current_index(), 0);
+        assert!(NavigationState::from_entries(Vec::new(), 0).is_none());
+    }
 }
@@ -0,0 +1,416 @@
+//! Reader Mode
+//!
+//! The article of a page shown on its own, without the navigation,
+//! sidebars, comments and scripts around it. Paragraphs of some length
+//! score the element holding them, and half that its parent, by how long
+//! they are and how many commas they have; an element's score is then cut
+//! by the share of its text in links. The best scored element, if it
+//! holds enough text, is copied out with only the tags that carry its
+//! content into a page styled for reading.
+
+use gugalanna_dom::{DomTree, NodeId, NodeType, Queryable};
+use url::Url;
+
+use crate::bookmarks::escape_html;
+use crate::script_loader::base_url;
+
+/// Reader text size, and how far it can be changed
+pub const READER_FONT_SIZE: f32 = 18.0;
+pub const MIN_READER_FONT_SIZE: f32 = 12.0;
+pub const MAX_READER_FONT_SIZE: f32 = 32.0;
+
+/// Paragraphs shorter than this do not count toward a score
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// Text an element needs, and the score, to be taken as an article
+const MIN_ARTICLE_LENGTH: usize = 500;
+const MIN_ARTICLE_SCORE: f32 = 20.0;
+
+/// Elements left out of the article with all they hold
+const REMOVED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "nav", "aside", "footer", "header", "form", "button", "input", "select",
+    "textarea", "iframe", "object", "embed", "svg", "canvas",
+];
+
+/// Elements copied into the article, those of other tags are replaced by
+/// what they hold
+const KEPT_TAGS: &[&str] = &[
+    "p", "h2", "h3", "h4", "h5", "h6", "ul", "ol", "li", "dl", "dt", "dd", "blockquote", "pre", "code", "em", "i",
+    "strong", "b", "sub", "sup", "a", "img", "figure", "figcaption", "br", "hr", "table", "thead", "tbody", "tr",
+    "th", "td",
+];
+
+/// Class and id words of page furniture, and of content, which wins when
+/// an element has both
+const UNLIKELY_WORDS: &[&str] = &[
+    "comment", "sidebar", "footer", "share", "social", "related", "menu", "nav", "banner", "promo", "popup",
+    "advert", "sponsor",
+];
+const LIKELY_WORDS: &[&str] = &["article", "content", "main", "post", "entry", "story", "text", "body"];
+
+/// A page's article
+#[derive(Debug, Clone, PartialEq)]
+pub struct Article {
+    pub title: String,
+    /// Who wrote it, when the page says
+    pub byline: Option<String>,
+    /// The article's HTML, its links and images absolute
+    pub content: String,
+}
+
+/// The article of a page, None when it does not have one
+pub fn extract_article(dom: &DomTree, page_url: &Url) -> Option<Article> {
+    let body = dom.get_elements_by_tag_name("body").first().copied()?;
+    let best = best_candidate(dom, body)?;
+    if collapsed_text(dom, best).len() < MIN_ARTICLE_LENGTH {
+        return None;
+    }
+
+    // The heading and byline are shown above the content, not in it
+    let heading = dom.descendants(best).into_iter().find(|&id| tag_of(dom, id) == Some("h1"));
+    let byline_node = find_byline(dom, body);
+    let title = heading
+        .map(|id| collapsed_text(dom, id))
+        .filter(|title| !title.is_empty())
+        .or_else(|| dom.get_elements_by_tag_name("title").first().map(|&id| collapsed_text(dom, id)))
+        .unwrap_or_default();
+    let byline = byline_node.map(|id| collapsed_text(dom, id)).or_else(|| meta_author(dom));
+
+    let mut writer = Writer { dom, base: base_url(dom, page_url), skipped: [heading, byline_node], out: String::new() };
+    for child in dom.children(best) {
+        writer.write(child);
+    }
+    Some(Article { title, byline: byline.filter(|byline| !byline.is_empty()), content: writer.out })
+}
+
+/// The element most likely to hold the article, if it scores enough
+fn best_candidate(dom: &DomTree, body: NodeId) -> Option<NodeId> {
+    let mut scores: Vec<(NodeId, f32)> = Vec::new();
+    let mut add = |id: NodeId, score: f32| match scores.iter_mut().find(|(candidate, _)| *candidate == id) {
+        Some((_, total)) => *total += score,
+        None => scores.push((id, element_weight(dom, id) + score)),
+    };
+
+    for id in dom.descendants(body) {
+        if !matches!(tag_of(dom, id), Some("p" | "pre" | "td")) || is_excluded(dom, id, body) {
+            continue;
+        }
+        let text = collapsed_text(dom, id);
+        if text.len() < MIN_PARAGRAPH_LENGTH {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f32 + (text.len() as f32 / 100.0).min(3.0);
+        let parent = dom.parent(id).filter(|&parent| dom.get(parent).is_some_and(|node| node.is_element()));
+        if let Some(parent) = parent {
+            add(parent, score);
+            let grandparent = dom.parent(parent).filter(|&id| dom.get(id).is_some_and(|node| node.is_element()));
+            if let Some(grandparent) = grandparent {
+                add(grandparent, score / 2.0);
+            }
+        }
+    }
+
+    scores
+        .into_iter()
+        .map(|(id, score)| (id, score * (1.0 - link_density(dom, id))))
+        .filter(|&(_, score)| score >= MIN_ARTICLE_SCORE)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id)
+}
+
+/// Score an element starts with, for its tag and the words of its class
+/// and id
+fn element_weight(dom: &DomTree, id: NodeId) -> f32 {
+    let tag = match tag_of(dom, id) {
+        Some("article" | "main") => 10.0,
+        Some("div") => 5.0,
+        Some("pre" | "td" | "blockquote") => 3.0,
+        Some("ol" | "ul" | "dl" | "dd" | "dt" | "li" | "address") => -3.0,
+        Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th") => -5.0,
+        _ => 0.0,
+    };
+    let words = class_and_id(dom, id);
+    let class = if LIKELY_WORDS.iter().any(|word| words.contains(word)) {
+        25.0
+    } else if UNLIKELY_WORDS.iter().any(|word| words.contains(word)) {
+        -25.0
+    } else {
+        0.0
+    };
+    tag + class
+}
+
+/// Whether an element, or one it is inside below `body`, is left out of
+/// the article
+fn is_excluded(dom: &DomTree, id: NodeId, body: NodeId) -> bool {
+    let mut current = Some(id);
+    while let Some(id) = current.filter(|&id| id != body) {
+        if is_removed(dom, id) {
+            return true;
+        }
+        current = dom.parent(id);
+    }
+    false
+}
+
+/// Whether an element is left out of the article by its tag, or by its
+/// class and id naming page furniture
+fn is_removed(dom: &DomTree, id: NodeId) -> bool {
+    if tag_of(dom, id).is_some_and(|tag| REMOVED_TAGS.contains(&tag)) {
+        return true;
+    }
+    let words = class_and_id(dom, id);
+    UNLIKELY_WORDS.iter().any(|word| words.contains(word)) && !LIKELY_WORDS.iter().any(|word| words.contains(word))
+}
+
+/// Share of an element's text that is in links
+fn link_density(dom: &DomTree, id: NodeId) -> f32 {
+    let length = collapsed_text(dom, id).len();
+    if length == 0 {
+        return 0.0;
+    }
+    let links: usize = dom
+        .descendants(id)
+        .into_iter()
+        .filter(|&id| tag_of(dom, id) == Some("a"))
+        .map(|id| collapsed_text(dom, id).len())
+        .sum();
+    (links as f32 / length as f32).min(1.0)
+}
+
+/// The element naming the article's author, by its `rel` or its class and
+/// id, if short enough to be a byline
+fn find_byline(dom: &DomTree, body: NodeId) -> Option<NodeId> {
+    dom.descendants(body).into_iter().find(|&id| {
+        let Some(element) = dom.get(id).and_then(|node| node.as_element()) else {
+            return false;
+        };
+        let named = element.get_attribute("rel") == Some("author") || {
+            let words = class_and_id(dom, id);
+            words.contains("byline") || words.contains("author")
+        };
+        named && !is_excluded(dom, id, body) && (1..100).contains(&collapsed_text(dom, id).len())
+    })
+}
+
+/// Author from the page's `<meta name="author">`
+fn meta_author(dom: &DomTree) -> Option<String> {
+    dom.get_elements_by_tag_name("meta").into_iter().find_map(|id| {
+        let element = dom.get(id)?.as_element()?;
+        element.get_attribute("name").filter(|name| name.eq_ignore_ascii_case("author"))?;
+        Some(element.get_attribute("content")?.split_whitespace().collect::<Vec<_>>().join(" "))
+    })
+}
+
+fn tag_of(dom: &DomTree, id: NodeId) -> Option<&str> {
+    dom.get(id)?.tag_name()
+}
+
+/// Class and id of an element, lowercased
+fn class_and_id(dom: &DomTree, id: NodeId) -> String {
+    let Some(element) = dom.get(id).and_then(|node| node.as_element()) else {
+        return String::new();
+    };
+    let class = element.get_attribute("class").unwrap_or_default();
+    format!("{} {}", class, element.id().unwrap_or_default()).to_ascii_lowercase()
+}
+
+/// Text of a node with its runs of whitespace made single spaces
+fn collapsed_text(dom: &DomTree, id: NodeId) -> String {
+    dom.text_content(id).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Copies the content of the article out as HTML
+struct Writer<'a> {
+    dom: &'a DomTree,
+    /// URL the links and images of the page resolve against
+    base: Url,
+    /// Heading and byline, shown above the content
+    skipped: [Option<NodeId>; 2],
+    out: String,
+}
+
+impl Writer<'_> {
+    fn write(&mut self, id: NodeId) {
+        let Some(node) = self.dom.get(id) else {
+            return;
+        };
+        let element = match &node.node_type {
+            NodeType::Text(text) => {
+                self.out.push_str(&escape_html(text));
+                return;
+            }
+            NodeType::Element(element) => element,
+            _ => return,
+        };
+        if self.skipped.contains(&Some(id)) || is_removed(self.dom, id) {
+            return;
+        }
+
+        // The article's own title is the page's, so its headings start at h2
+        let tag = match element.tag_name.as_str() {
+            "h1" => "h2",
+            tag if KEPT_TAGS.contains(&tag) => tag,
+            _ => {
+                for child in self.dom.children(id) {
+                    self.write(child);
+                }
+                return;
+            }
+        };
+
+        self.out.push('<');
+        self.out.push_str(tag);
+        let url_attribute = match tag {
+            "a" => Some("href"),
+            "img" => Some("src"),
+            _ => None,
+        };
+        let url = url_attribute
+            .and_then(|name| element.get_attribute(name))
+            .and_then(|value| self.base.join(value.trim()).ok())
+            .filter(|url| url.scheme() != "javascript");
+        if let (Some(name), Some(url)) = (url_attribute, url) {
+            self.out.push_str(&format!(" {}=\"{}\"", name, escape_html(url.as_str())));
+        }
+        if let Some(alt) = element.get_attribute("alt").filter(|_| tag == "img") {
+            self.out.push_str(&format!(" alt=\"{}\"", escape_html(alt)));
+        }
+        self.out.push('>');
+        if matches!(tag, "img" | "br" | "hr") {
+            return;
+        }
+        for child in self.dom.children(id) {
+            self.write(child);
+        }
+        self.out.push_str(&format!("</{}>", tag));
+    }
+}
+
+/// The page an article is read in; `icon` is the original page's, so the
+/// tab keeps it
+pub fn reader_page(article: &Article, icon: Option<&Url>) -> String {
+    let icon = icon.map(|url| format!("<link rel=\"icon\" href=\"{}\">\n", escape_html(url.as_str())));
+    let byline = article.byline.as_ref().map(|byline| format!("<p class=\"byline\">{}</p>\n", escape_html(byline)));
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<title>{title}</title>\n{icon}</head>\n<body>\n\
+         <div class=\"reader\">\n<h1>{title}</h1>\n{byline}{content}\n</div>\n</body>\n</html>\n",
+        title = escape_html(&article.title),
+        icon = icon.unwrap_or_default(),
+        byline = byline.unwrap_or_default(),
+        content = article.content,
+    )
+}
+
+/// Stylesheet of the reader page, its text at `font_size` in a column
+/// about 36 ems wide, centered in a window `window_width` wide
+pub fn reader_css(font_size: f32, window_width: f32) -> String {
+    let measure = (font_size * 36.0).min(window_width - 48.0).max(font_size * 10.0);
+    format!(
+        "body {{ background-color: #fbf8f1; color: #222222; font-family: serif; font-size: {size}px; \
+             margin: 0; padding: 24px 0; }}\n\
+         .reader {{ width: {measure}px; margin: 0 auto; line-height: 1.6; }}\n\
+         h1 {{ font-size: {h1}px; line-height: 1.25; margin-top: 0; margin-bottom: 8px; }}\n\
+         h2 {{ font-size: {h2}px; margin-top: 28px; margin-bottom: 8px; }}\n\
+         h3, h4, h5, h6 {{ font-size: {h3}px; margin-top: 24px; margin-bottom: 6px; }}\n\
+         p {{ margin-top: 0; margin-bottom: 16px; }}\n\
+         .byline {{ color: #6b6b6b; font-size: {small}px; margin-bottom: 28px; }}\n\
+         a {{ color: #1a5fb4; }}\n\
+         blockquote {{ margin: 16px 0; padding-left: 16px; border-left: 3px solid #d0c8b8; color: #555555; }}\n\
+         pre, code {{ font-family: monospace; font-size: {small}px; }}\n\
+         pre {{ background-color: #f0ebe0; padding: 12px; white-space: pre-wrap; }}\n\
+         ul, ol {{ margin-bottom: 16px; }}\n",
+        size = font_size,
+        measure = measure.round(),
+        h1 = (font_size * 1.8).round(),
+        h2 = (font_size * 1.4).round(),
+        h3 = (font_size * 1.2).round(),
+        small = (font_size * 0.85).round(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_html::HtmlParser;
+
+    fn extract(html: &str, url: &str) -> Option<Article> {
+        let dom = HtmlParser::new().parse(html).unwrap();
+        extract_article(&dom, &Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_news_article_extracted() {
+        let html = include_str!("../../../test-pages/reader/news-article.html");
+        let article = extract(html, "https://ledger.test/2024/library.html").unwrap();
+        assert_eq!(article.title, "City Opens New Riverside Library");
+        assert_eq!(article.byline.as_deref(), Some("By Dana Whitfield, Staff Reporter"));
+
+        // The story, with its structure
+        assert!(article.content.contains("<p>The city opened its new riverside library on Saturday"));
+        assert!(article.content.contains("<h2>A building for the whole neighbourhood</h2>"));
+        assert!(article.content.contains("<blockquote>We wanted a place"));
+        assert!(article.content.contains("<li>Free meeting rooms for community groups</li>"));
+        assert!(article.content.contains("&quot;People told us"));
+
+        // Without what is around it, nor the heading and byline shown above
+        for furniture in [
+            "Subscribe",
+            "mattress",
+            "Share on Facebook",
+            "Most read",
+            "First!",
+            "Copyright",
+            "analytics",
+            "Staff Reporter",
+            "<h1>",
+            "class=",
+        ] {
+            assert!(!article.content.contains(furniture), "{} left in", furniture);
+        }
+    }
+
+    #[test]
+    fn test_blog_post_extracted() {
+        let html = include_str!("../../../test-pages/reader/blog-post.html");
+        let article = extract(html, "https://crumb.test/posts/week-six").unwrap();
+        assert_eq!(article.title, "Notes on sourdough, week six");
+        assert_eq!(article.byline.as_deref(), Some("Tomas Reyes"));
+        assert!(article.content.contains("<p>Six weeks in, the starter finally behaves."));
+        assert!(article.content.contains("<pre>Day 1: 50g flour, 50g water\nDay 2:"));
+        assert!(article.content.contains("<em>less</em>"));
+
+        // Images are kept with their URL made absolute
+        assert!(article.content.contains(r#"<img src="https://crumb.test/posts/images/crumb.jpg" alt="Cross-section"#));
+        assert!(!article.content.contains("Archive"));
+        assert!(!article.content.contains("Related posts"));
+    }
+
+    #[test]
+    fn test_pages_without_an_article() {
+        let html = include_str!("../../../test-pages/reader/link-index.html");
+        assert_eq!(extract(html, "https://ledger.test/"), None);
+        assert_eq!(extract("<p>Just a short page with a line of text.</p>", "https://a.test/"), None);
+        assert_eq!(extract("", "about:blank"), None);
+    }
+
+    #[test]
+    fn test_reader_page() {
+        let article = Article {
+            title: "Fish & chips".to_string(),
+            byline: None,
+            content: "<p>Salt, vinegar.</p>".to_string(),
+        };
+        let icon = Url::parse("https://a.test/icon.png").unwrap();
+        let page = reader_page(&article, Some(&icon));
+        assert!(page.contains("<title>Fish &amp; chips</title>"));
+        assert!(page.contains("<h1>Fish &amp; chips</h1>\n<p>Salt, vinegar.</p>"));
+        assert!(page.contains(r#"<link rel="icon" href="https://a.test/icon.png">"#));
+        assert!(!page.contains("byline"));
+
+        // The column narrows in a narrow window
+        assert!(reader_css(18.0, 1200.0).contains(".reader { width: 648px; margin: 0 auto;"));
+        assert!(reader_css(18.0, 500.0).contains(".reader { width: 452px;"));
+        assert!(reader_css(24.0, 1200.0).contains("font-size: 24px"));
+    }
+}
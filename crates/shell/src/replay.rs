@@ -0,0 +1,66 @@
+//! Display List Replay
+//!
+//! Paints display lists dumped with `DisplayList::dump`, so paint bugs can
+//! be reproduced without the page they came from.
+
+use std::path::Path;
+
+use gugalanna_render::{DisplayList, RenderBackend, RenderColor, SoftwareBackend};
+#[cfg(feature = "sdl")]
+use gugalanna_render::SdlBackend;
+
+#[cfg(feature = "sdl")]
+use crate::event::{poll_events, BrowserEvent};
+
+/// Paint a dumped display list over white to a PNG file of the given size
+pub fn replay_to_png(list_path: &Path, width: u32, height: u32, out: &Path) -> Result<(), String> {
+    let display_list = DisplayList::load(list_path)?;
+    let mut backend = SoftwareBackend::new(width, height);
+    backend.clear(RenderColor::white());
+    backend.render(&display_list);
+    backend.save_png(out)
+}
+
+/// Show a dumped display list over white in a window until it is closed
+#[cfg(feature = "sdl")]
+pub fn replay_in_window(list_path: &Path, width: u32, height: u32) -> Result<(), String> {
+    let display_list = DisplayList::load(list_path)?;
+    let mut backend = SdlBackend::new(&format!("Gugalanna - {}", list_path.display()), width, height)?;
+
+    // Presenting waits for vsync, which paces the loop
+    while !poll_events().iter().any(|event| matches!(event, BrowserEvent::Quit)) {
+        backend.clear(RenderColor::white());
+        backend.render(&display_list);
+        backend.present();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_layout::Rect;
+    use gugalanna_render::PaintCommand;
+
+    #[test]
+    fn test_replay_dumped_list() {
+        let dir = std::env::temp_dir();
+        let list_path = dir.join(format!("gugalanna-replay-{}.json", std::process::id()));
+        let png_path = dir.join(format!("gugalanna-replay-{}.png", std::process::id()));
+
+        let mut display_list = DisplayList::new();
+        display_list.push(PaintCommand::FillRect {
+            rect: Rect::new(10.0, 10.0, 20.0, 20.0),
+            color: RenderColor::rgb(0, 0, 255),
+        });
+        display_list.dump(&list_path).unwrap();
+        replay_to_png(&list_path, 40, 40, &png_path).unwrap();
+
+        let image = image::open(&png_path).unwrap().to_rgba8();
+        std::fs::remove_file(&list_path).unwrap();
+        std::fs::remove_file(&png_path).unwrap();
+
+        assert_eq!(image.get_pixel(15, 15).0, [0, 0, 255, 255]);
+        assert_eq!(image.get_pixel(5, 5).0, [255, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,59 @@
+//! Retained Layout
+//!
+//! A page keeps the tree it was last laid out as, together with the style
+//! tree its boxes borrow. Laying the page out again builds the tree anew
+//! and takes over the layout of every box that did not change, so only
+//! the changed boxes and their ancestors are laid out.
+
+use std::rc::Rc;
+
+use gugalanna_dom::{DomTree, NodeId};
+use gugalanna_layout::{
+    build_layout_tree, mark_node_dirty, relayout_document, reuse_layout_of, ContainingBlock, LayoutBox,
+};
+use gugalanna_style::StyleTree;
+use self_cell::self_cell;
+
+self_cell!(
+    /// The laid-out tree of a page and the style tree its boxes borrow
+    pub struct RetainedLayout {
+        owner: Rc<StyleTree>,
+
+        #[covariant]
+        dependent: LayoutBox,
+    }
+);
+
+impl RetainedLayout {
+    /// Build the layout tree of `root` from `styles`, None when the root
+    /// generates no box
+    pub fn build(dom: &DomTree, styles: Rc<StyleTree>, root: NodeId) -> Option<Self> {
+        Self::try_new(styles, |styles| build_layout_tree(dom, styles, root).ok_or(())).ok()
+    }
+
+    /// Lay the tree out in `viewport`, returns how many blocks were laid out
+    ///
+    /// Boxes that are the same as in `previous` keep their layout, except
+    /// those of the nodes in `dirty`, which scripts changed since.
+    pub fn lay_out(
+        &mut self,
+        previous: Option<&mut RetainedLayout>,
+        dirty: &[NodeId],
+        viewport: ContainingBlock,
+    ) -> usize {
+        self.with_dependent_mut(|_, tree| {
+            if let Some(previous) = previous {
+                previous.with_dependent_mut(|_, old| reuse_layout_of(tree, old));
+            }
+            for &node_id in dirty {
+                mark_node_dirty(tree, node_id);
+            }
+            relayout_document(tree, viewport)
+        })
+    }
+
+    /// The laid-out tree
+    pub fn tree(&self) -> &LayoutBox<'_> {
+        self.borrow_dependent()
+    }
+}
@@ -0,0 +1,269 @@
+//! Headless Screenshots
+//!
+//! Renders a page into an offscreen buffer and saves it as a PNG, or dumps
+//! its display list, without a window. Scripts aren't run and web fonts
+//! aren't loaded, so the result only depends on the HTML, CSS and images of
+//! the page. The browser saves captures of its pages the same way, as a
+//! PNG or as a PDF of a single page holding the image.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use gugalanna_css::Stylesheet;
+use gugalanna_dom::Queryable;
+use gugalanna_html::HtmlParser;
+use gugalanna_layout::{build_layout_tree, layout_document, ContainingBlock};
+use gugalanna_net::HttpClient;
+use gugalanna_render::{build_display_list, DisplayList, RenderBackend, RenderColor, SoftwareBackend};
+use gugalanna_style::{Cascade, StyleTree};
+use url::Url;
+
+use crate::image_loader::{self, ImageCache};
+use crate::{extract_style_content, DEFAULT_CSS};
+
+/// Render HTML styled with `css` to a PNG file of the given size
+pub fn render_to_png(html: &str, css: &str, width: u32, height: u32, path: &Path) -> Result<(), String> {
+    let url = Url::parse("about:blank").map_err(|e| e.to_string())?;
+    render_page(&url, html, css, width, height)?.save_png(path)
+}
+
+/// Fetch a URL, or read a local file, and render it to a PNG file
+pub fn screenshot_url(url_str: &str, width: u32, height: u32, path: &Path) -> Result<(), String> {
+    let (url, html) = load_page(url_str)?;
+    log::info!("Rendering {} at {}x{}", url, width, height);
+    render_page(&url, &html, DEFAULT_CSS, width, height)?.save_png(path)
+}
+
+/// Fetch a URL, or read a local file, and write the display list it is
+/// painted with to a JSON file
+pub fn dump_display_list(url_str: &str, width: u32, height: u32, path: &Path) -> Result<(), String> {
+    let (url, html) = load_page(url_str)?;
+    log::info!("Dumping the display list of {} at {}x{}", url, width, height);
+    page_display_list(&url, &html, DEFAULT_CSS, width, height)?.dump(path)
+}
+
+/// URL of a page given as a URL or a local path, and its HTML
+fn load_page(url_str: &str) -> Result<(Url, String), String> {
+    let local = Path::new(url_str);
+    let url = if local.exists() {
+        let abs_path = local.canonicalize().map_err(|e| e.to_string())?;
+        Url::from_file_path(&abs_path)
+            .map_err(|_| format!("Cannot create URL from path: {}", abs_path.display()))?
+    } else if url_str.contains("://") {
+        Url::parse(url_str).map_err(|e| e.to_string())?
+    } else {
+        Url::parse(&format!("https://{}", url_str)).map_err(|e| e.to_string())?
+    };
+
+    let html = if url.scheme() == "file" {
+        let file_path = url.to_file_path().map_err(|_| "Invalid file path".to_string())?;
+        std::fs::read_to_string(&file_path)
+            .map_err(|e| format!("Failed to read {}: {}", file_path.display(), e))?
+    } else {
+        let response = fetch(&url)?;
+        if !response.is_success() {
+            return Err(format!("HTTP error: {}", response.status));
+        }
+        response.text_lossy()
+    };
+    Ok((url, html))
+}
+
+/// Lay out a page in a viewport of the given size and paint it offscreen
+fn render_page(url: &Url, html: &str, css: &str, width: u32, height: u32) -> Result<SoftwareBackend, String> {
+    let display_list = page_display_list(url, html, css, width, height)?;
+    Ok(paint_display_list(&display_list, width, height))
+}
+
+/// Paint a display list offscreen, over white
+pub fn paint_display_list(display_list: &DisplayList, width: u32, height: u32) -> SoftwareBackend {
+    let mut backend = SoftwareBackend::new(width, height);
+    backend.clear(RenderColor::white());
+    backend.render(display_list);
+    backend
+}
+
+/// File format a capture of a page is saved in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Png,
+    Pdf,
+}
+
+impl CaptureFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            CaptureFormat::Png => "png",
+            CaptureFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// Save a capture painted offscreen, `width` by `height` pixels
+pub fn save_capture(
+    backend: &SoftwareBackend,
+    width: u32,
+    height: u32,
+    format: CaptureFormat,
+    path: &Path,
+) -> Result<(), String> {
+    match format {
+        CaptureFormat::Png => backend.save_png(path),
+        CaptureFormat::Pdf => {
+            let pdf = raster_pdf(backend.pixels(), width, height)?;
+            std::fs::write(path, pdf).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+        }
+    }
+}
+
+/// PDF pages can be no longer than 200 inches, in points
+const MAX_PDF_PAGE_SIZE: f32 = 14_400.0;
+
+/// A PDF of one page showing RGBA pixels, as a JPEG image drawn over the
+/// whole page at 96 pixels to the inch, smaller for pages too long for that
+fn raster_pdf(pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let image = image::RgbaImage::from_raw(width, height, pixels.to_vec())
+        .ok_or("Pixel data doesn't match the surface size")?;
+    let mut jpeg = Vec::new();
+    image::DynamicImage::ImageRgba8(image)
+        .into_rgb8()
+        .write_to(&mut Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+        .map_err(|e| e.to_string())?;
+
+    let scale = (72.0 / 96.0_f32).min(MAX_PDF_PAGE_SIZE / width.max(height) as f32);
+    let (page_width, page_height) = (width as f32 * scale, height as f32 * scale);
+    let contents = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q\n", page_width, page_height);
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    let mut object = |pdf: &mut Vec<u8>, body: &[u8]| {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", offsets.len()).as_bytes());
+        pdf.extend_from_slice(body);
+        pdf.extend_from_slice(b"\nendobj\n");
+    };
+    object(&mut pdf, b"<< /Type /Catalog /Pages 2 0 R >>");
+    object(&mut pdf, b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>");
+    let page = format!(
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+         /Resources << /XObject << /Im0 4 0 R >> >> /Contents 5 0 R >>",
+        page_width, page_height
+    );
+    object(&mut pdf, page.as_bytes());
+    let mut image_object = format!(
+        "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+         /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+        width,
+        height,
+        jpeg.len()
+    )
+    .into_bytes();
+    image_object.extend_from_slice(&jpeg);
+    image_object.extend_from_slice(b"\nendstream");
+    object(&mut pdf, &image_object);
+    let content_object = format!("<< /Length {} >>\nstream\n{}endstream", contents.len(), contents);
+    object(&mut pdf, content_object.as_bytes());
+
+    // Where each object starts, for readers to find them
+    let xref = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1).as_bytes());
+    for offset in &offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", offsets.len() + 1, xref).as_bytes(),
+    );
+    Ok(pdf)
+}
+
+/// Lay out a page in a viewport of the given size and build the display
+/// list it is painted with
+fn page_display_list(url: &Url, html: &str, css: &str, width: u32, height: u32) -> Result<DisplayList, String> {
+    let dom = HtmlParser::new().parse(html).map_err(|e| e.to_string())?;
+
+    let mut cascade = Cascade::new();
+    if let Ok(stylesheet) = Stylesheet::parse(css) {
+        cascade.add_author_stylesheet(stylesheet);
+    }
+    for style_id in dom.get_elements_by_tag_name("style") {
+        if let Some(style_css) = extract_style_content(&dom, style_id) {
+            if let Ok(stylesheet) = Stylesheet::parse(&style_css) {
+                cascade.add_author_stylesheet(stylesheet);
+            }
+        }
+    }
+
+    let (viewport_width, viewport_height) = (width as f32, height as f32);
+    let style_tree = StyleTree::build(&dom, &cascade, viewport_width, viewport_height);
+    let root_id = crate::layout_root(&dom);
+    let mut layout_tree =
+        build_layout_tree(&dom, &style_tree, root_id).ok_or("Failed to build layout tree")?;
+
+    let http_client = HttpClient::new().map_err(|e| e.to_string())?;
+    image_loader::load_images_in_tree(&mut layout_tree, &http_client, url, &mut ImageCache::new());
+    layout_document(&mut layout_tree, ContainingBlock::new(viewport_width, viewport_height));
+    Ok(build_display_list(&layout_tree))
+}
+
+/// Fetch a URL, handling both sync and async contexts
+fn fetch(url: &Url) -> Result<gugalanna_net::Response, String> {
+    let client = HttpClient::new().map_err(|e| e.to_string())?;
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(client.get(url))),
+        Err(_) => {
+            let rt = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+            rt.block_on(client.get(url))
+        }
+    }
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_to_png() {
+        let path = std::env::temp_dir().join(format!("gugalanna-screenshot-{}.png", std::process::id()));
+        render_to_png(
+            "<body><div class='banner'></div></body>",
+            "body { margin: 0; } .banner { height: 50px; background-color: #008000; }",
+            200,
+            100,
+            &path,
+        )
+        .unwrap();
+
+        let image = image::open(&path).unwrap().to_rgba8();
+        std::fs::remove_file(&path).unwrap();
+
+        // The banner spans the width of the page, white below it
+        assert_eq!(image.dimensions(), (200, 100));
+        assert_eq!(image.get_pixel(0, 0).0, [0, 128, 0, 255]);
+        assert_eq!(image.get_pixel(199, 49).0, [0, 128, 0, 255]);
+        assert_eq!(image.get_pixel(100, 50).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_raster_pdf() {
+        let pixels: Vec<u8> = [[255, 0, 0, 255], [0, 0, 255, 255]].repeat(4).concat();
+        let pdf = raster_pdf(&pixels, 4, 2).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4\n") && text.ends_with("%%EOF\n"));
+        assert!(text.contains("/MediaBox [0 0 3.00 1.50]"));
+        assert!(text.contains("/Width 4 /Height 2 /ColorSpace /DeviceRGB"));
+
+        // The table at the end points at each object
+        let startxref = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let xref: usize = text[startxref..].lines().next().unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with(b"xref\n0 6\n"));
+        for (i, entry) in String::from_utf8_lossy(&pdf[xref..]).lines().skip(3).take(5).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+
+        // Pages too long for PDF are drawn smaller
+        let tall = raster_pdf(&vec![255; 10 * 40_000 * 4], 10, 40_000).unwrap();
+        assert!(String::from_utf8_lossy(&tall).contains("/MediaBox [0 0 3.60 14400.00]"));
+    }
+}
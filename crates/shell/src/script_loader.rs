@@ -0,0 +1,370 @@
+//! External Scripts
+//!
+//! Fetches the files of `<script src>` elements and runs them in the order
+//! the page expects. Classic scripts are fetched while the page loads,
+//! since the scripts after them wait; deferred ones follow once the
+//! document is parsed. Async scripts are fetched in the background and
+//! handed to the page's runtime from the frame loop as they arrive.
+//!
+//! Module scripts, inline or not, run with the deferred ones; the modules
+//! they import are fetched the same way as classic scripts, while the
+//! import waits.
+
+use gugalanna_dom::{DomTree, Queryable};
+use gugalanna_js::{JsRuntime, ModuleFetcher, ScriptResult, ScriptSource, ScriptTiming};
+use gugalanna_net::HttpClient;
+use log::{debug, warn};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// The code of a script element's file, or why it could not be fetched
+#[derive(Debug)]
+pub struct FetchedScript {
+    pub node_id: u32,
+    pub url: Url,
+    /// HTTP status, None for local files and when no response came
+    pub status: Option<u16>,
+    pub code: Result<String, String>,
+    /// A module script
+    pub module: bool,
+}
+
+/// A script that runs once the document is parsed
+enum DeferredScript {
+    /// The file of a deferred classic script or a module script
+    External { node_id: u32, url: Url, module: bool },
+    /// The code of an inline module script
+    InlineModule { node_id: u32, code: String },
+}
+
+/// Fetches the modules page scripts import, with the client of their
+/// page's tab, until the page is gone
+pub struct ModuleFetch {
+    client: HttpClient,
+    cancel: CancellationToken,
+}
+
+impl ModuleFetcher for ModuleFetch {
+    fn fetch(&self, url: &Url) -> Result<String, String> {
+        // Not a script element's, no events go to one
+        fetch_script_blocking(&self.client, &self.cancel, 0, url.clone(), true).code
+    }
+}
+
+/// Async scripts of a page being fetched
+///
+/// Dropping it, as when the page is navigated away from or its tab closed,
+/// cancels them, and the page's other script fetches.
+pub struct AsyncScripts {
+    sender: UnboundedSender<FetchedScript>,
+    receiver: UnboundedReceiver<FetchedScript>,
+    /// Client of the page's tab, with its cookies and User-Agent
+    client: HttpClient,
+    cancel: CancellationToken,
+}
+
+impl AsyncScripts {
+    pub fn new(client: HttpClient) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Self { sender, receiver, client, cancel: CancellationToken::new() }
+    }
+
+    /// Fetcher of the modules the page's scripts import
+    pub fn module_fetch(&self) -> ModuleFetch {
+        ModuleFetch { client: self.client.clone(), cancel: self.cancel.clone() }
+    }
+
+    /// Fetch a script the scripts after it wait for
+    fn fetch_blocking(&self, node_id: u32, url: Url, module: bool) -> FetchedScript {
+        fetch_script_blocking(&self.client, &self.cancel, node_id, url, module)
+    }
+
+    /// Start fetching a script in the background
+    ///
+    /// Returns false when there is no async runtime to fetch it in.
+    fn start(&self, node_id: u32, url: Url, module: bool) -> bool {
+        let Ok(handle) = Handle::try_current() else {
+            return false;
+        };
+        let (client, cancel, sender) = (self.client.clone(), self.cancel.clone(), self.sender.clone());
+        handle.spawn(async move {
+            let script = tokio::select! {
+                _ = cancel.cancelled() => return,
+                script = fetch_script(&client, node_id, url, module) => script,
+            };
+            // The page may be gone
+            let _ = sender.send(script);
+        });
+        true
+    }
+
+    /// Scripts that arrived since the last call
+    pub fn arrived(&mut self) -> Vec<FetchedScript> {
+        let mut scripts = Vec::new();
+        while let Ok(script) = self.receiver.try_recv() {
+            scripts.push(script);
+        }
+        scripts
+    }
+}
+
+impl Drop for AsyncScripts {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// Run the scripts of a page that was just parsed: inline and classic
+/// scripts in document order, then deferred and module ones
+///
+/// Async scripts start fetching into `async_scripts`; without an async
+/// runtime they run after the deferred ones.
+pub fn run_page_scripts(rt: &JsRuntime, page_url: &Url, async_scripts: &AsyncScripts) -> Vec<ScriptResult> {
+    let base = rt.dom().map_or_else(|| page_url.clone(), |dom| base_url(&dom.borrow(), page_url));
+    let mut results = Vec::new();
+    let mut deferred = Vec::new();
+
+    for script in rt.scripts() {
+        let (src, timing) = match script.source {
+            ScriptSource::Inline(code) if script.module => {
+                deferred.push(DeferredScript::InlineModule { node_id: script.node_id, code });
+                continue;
+            }
+            ScriptSource::Inline(code) => {
+                results.push(rt.run_script(script.node_id, &code));
+                continue;
+            }
+            ScriptSource::External { src, timing } => (src, timing),
+        };
+        let url = match base.join(&src) {
+            Ok(url) => url,
+            Err(e) => {
+                results.push(rt.script_fetch_failed(script.node_id, &src, None, &e.to_string()));
+                continue;
+            }
+        };
+        let (node_id, module) = (script.node_id, script.module);
+        match timing {
+            ScriptTiming::Classic => results.push(run_fetched(rt, async_scripts.fetch_blocking(node_id, url, false))),
+            ScriptTiming::Defer => deferred.push(DeferredScript::External { node_id, url, module }),
+            ScriptTiming::Async => {
+                if !async_scripts.start(node_id, url.clone(), module) {
+                    deferred.push(DeferredScript::External { node_id, url, module });
+                }
+            }
+        }
+    }
+
+    for script in deferred {
+        results.push(match script {
+            DeferredScript::External { node_id, url, module } => {
+                run_fetched(rt, async_scripts.fetch_blocking(node_id, url, module))
+            }
+            DeferredScript::InlineModule { node_id, code } => rt.run_module(node_id, &base, &code),
+        });
+    }
+    results
+}
+
+/// Run a fetched script, or report why it could not be fetched
+pub fn run_fetched(rt: &JsRuntime, script: FetchedScript) -> ScriptResult {
+    match script.code {
+        Ok(code) if script.module => {
+            debug!("Running module {}", script.url);
+            rt.run_external_module(script.node_id, &script.url, script.status, &code)
+        }
+        Ok(code) => {
+            debug!("Running script {}", script.url);
+            rt.run_external_script(script.node_id, script.url.as_str(), script.status, &code)
+        }
+        Err(e) => {
+            warn!("Failed to load script {}: {}", script.url, e);
+            rt.script_fetch_failed(script.node_id, script.url.as_str(), script.status, &e)
+        }
+    }
+}
+
+/// URL relative URLs of the document resolve against, from its first
+/// `<base href>` or else the page's
+pub fn base_url(dom: &DomTree, page_url: &Url) -> Url {
+    dom.get_elements_by_tag_name("base")
+        .into_iter()
+        .find_map(|id| dom.get(id)?.as_element()?.get_attribute("href").map(str::to_string))
+        .and_then(|href| page_url.join(&href).ok())
+        .unwrap_or_else(|| page_url.clone())
+}
+
+/// Fetch the file of a script element
+async fn fetch_script(client: &HttpClient, node_id: u32, url: Url, module: bool) -> FetchedScript {
+    let (status, code) = if url.scheme() == "file" {
+        let code = url
+            .to_file_path()
+            .map_err(|_| "Invalid file path".to_string())
+            .and_then(|path| std::fs::read(path).map_err(|e| e.to_string()))
+            .map(|data| String::from_utf8_lossy(&data).into_owned());
+        (None, code)
+    } else {
+        match client.get(&url).await {
+            Ok(response) if response.is_success() => (Some(response.status), Ok(response.text_lossy())),
+            Ok(response) => (Some(response.status), Err(format!("HTTP error: {}", response.status))),
+            Err(e) => (None, Err(e.to_string())),
+        }
+    };
+    FetchedScript { node_id, url, status, code, module }
+}
+
+/// Fetch the file of a script element, blocking until it arrives as the
+/// scripts after it wait, or until `cancel` is cancelled
+fn fetch_script_blocking(
+    client: &HttpClient,
+    cancel: &CancellationToken,
+    node_id: u32,
+    url: Url,
+    module: bool,
+) -> FetchedScript {
+    let fetch = || async {
+        let code = Err("Cancelled".to_string());
+        let cancelled = FetchedScript { node_id, url: url.clone(), status: None, code, module };
+        tokio::select! {
+            _ = cancel.cancelled() => cancelled,
+            script = fetch_script(client, node_id, url.clone(), module) => script,
+        }
+    };
+    if let Ok(handle) = Handle::try_current() {
+        tokio::task::block_in_place(|| handle.block_on(fetch()))
+    } else {
+        match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime.block_on(fetch()),
+            Err(e) => FetchedScript { node_id, url: url.clone(), status: None, code: Err(e.to_string()), module },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_html::HtmlParser;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_external_scripts_run_in_order() {
+        // A server answering a script and a missing file
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let page_url = Url::parse(&format!("http://{}/app/index.html", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 1024];
+                let n = stream.read(&mut buf).unwrap();
+                let (status, body) = if String::from_utf8_lossy(&buf[..n]).starts_with("GET /app/main.js ") {
+                    ("200 OK", "document.getElementById('out').setAttribute('data-ran', order.join(','));")
+                } else {
+                    ("404 Not Found", "")
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: text/javascript\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        let html = r#"<p id="out"></p>
+            <script>var order = ['before']; var failed = false;</script>
+            <script src="missing.js"></script>
+            <script src="main.js"></script>
+            <script>order.push('after');</script>"#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let rt = JsRuntime::with_dom(dom).unwrap();
+        rt.exec("document.getElementsByTagName('script')[1].addEventListener('error', function() { failed = true; });")
+            .unwrap();
+
+        let results = run_page_scripts(&rt, &page_url, &AsyncScripts::new(HttpClient::new().unwrap()));
+        server.join().unwrap();
+
+        // The classic script ran between the inline ones, before the second
+        let ran = rt.eval("document.getElementById('out').getAttribute('data-ran')").unwrap();
+        assert_eq!(ran.as_str(), Some("before"));
+        assert_eq!(rt.eval("order.join(',')").unwrap().as_str(), Some("before,after"));
+
+        // The missing one failed without stopping the others
+        assert_eq!(rt.eval("failed").unwrap().as_bool(), Some(true));
+        let statuses: Vec<_> = results.iter().map(|r| (r.src.is_some(), r.fetch_status, r.success)).collect();
+        assert_eq!(
+            statuses,
+            vec![(false, None, true), (true, Some(404), false), (true, Some(200), true), (false, None, true)]
+        );
+    }
+
+    #[test]
+    fn test_inline_module_imports_helper() {
+        // A server answering the helper module
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let page_url = Url::parse(&format!("http://{}/app/index.html", listener.local_addr().unwrap())).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).unwrap();
+            assert!(String::from_utf8_lossy(&buf[..n]).starts_with("GET /app/lib/helper.js "));
+            let body = "export function mark(id, order) { document.getElementById(id).textContent = order.join(','); }";
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/javascript\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        let html = r#"<p id="out"></p>
+            <script type="module">
+                import { mark } from './lib/helper.js';
+                order.push('module');
+                mark('out', order);
+            </script>
+            <script>var order = ['classic'];</script>
+            <script nomodule>order.push('fallback');</script>"#;
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let rt = JsRuntime::with_dom(dom).unwrap();
+        let scripts = AsyncScripts::new(HttpClient::new().unwrap());
+        rt.set_module_fetcher(std::sync::Arc::new(scripts.module_fetch()));
+
+        let results = run_page_scripts(&rt, &page_url, &scripts);
+        server.join().unwrap();
+
+        // The module ran after the document's classic scripts, the
+        // fallback for browsers without modules not at all
+        let out = rt.eval("document.getElementById('out').textContent").unwrap();
+        assert_eq!(out.as_str(), Some("classic,module"));
+        let ran: Vec<_> = results.iter().map(|r| (r.module, r.success)).collect();
+        assert_eq!(ran, vec![(false, true), (true, true)]);
+    }
+
+    #[test]
+    fn test_blocking_fetch_stops_with_page() {
+        // A server that never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/slow.js", listener.local_addr().unwrap())).unwrap();
+
+        let scripts = AsyncScripts::new(HttpClient::new().unwrap());
+        scripts.cancel.cancel();
+        assert_eq!(scripts.fetch_blocking(0, url.clone(), false).code.unwrap_err(), "Cancelled");
+        assert_eq!(scripts.module_fetch().fetch(&url).unwrap_err(), "Cancelled");
+    }
+
+    #[test]
+    fn test_bare_module_specifier_fails() {
+        let dom = HtmlParser::new().parse("<script type='module'>import _ from 'lodash';</script>").unwrap();
+        let rt = JsRuntime::with_dom(dom).unwrap();
+        let scripts = AsyncScripts::new(HttpClient::new().unwrap());
+        let results = run_page_scripts(&rt, &Url::parse("https://a.test/").unwrap(), &scripts);
+        assert!(!results[0].success);
+        assert!(results[0].error.as_deref().unwrap().contains("lodash"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Scrollbars
+//!
+//! Geometry of the vertical scrollbars drawn over the page and its scroll
+//! containers, and the scroll offsets their thumbs are dragged to.
+
+use gugalanna_layout::Rect;
+use gugalanna_render::{PaintCommand, RenderColor};
+
+/// Width of a scrollbar
+pub const SCROLLBAR_WIDTH: f32 = 8.0;
+
+/// Shortest a thumb gets, however long the content
+const THUMB_MIN_HEIGHT: f32 = 16.0;
+
+/// A vertical scrollbar along the right edge of a scrolled area
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scrollbar {
+    /// Strip the thumb moves along
+    pub track: Rect,
+    /// Part of the track standing for the visible part of the content
+    pub thumb: Rect,
+    /// How far the content scrolls
+    range: f32,
+}
+
+impl Scrollbar {
+    /// Scrollbar of an area showing content `content_height` tall, scrolled
+    /// down by `scroll`, None when the content fits
+    pub fn new(area: Rect, content_height: f32, scroll: f32) -> Option<Self> {
+        let range = content_height - area.height;
+        if range <= 0.0 || area.height <= 0.0 {
+            return None;
+        }
+        let track = Rect::new(area.right() - SCROLLBAR_WIDTH, area.y, SCROLLBAR_WIDTH, area.height);
+
+        // The thumb is to the track what the visible part is to the content
+        let height = (track.height * area.height / content_height).clamp(THUMB_MIN_HEIGHT.min(track.height), track.height);
+        let y = track.y + scroll.clamp(0.0, range) / range * (track.height - height);
+        Some(Self { track, thumb: Rect::new(track.x, y, SCROLLBAR_WIDTH, height), range })
+    }
+
+    /// Scroll offset that puts the top of the thumb at `thumb_y`
+    pub fn scroll_for_thumb(&self, thumb_y: f32) -> f32 {
+        let travel = self.track.height - self.thumb.height;
+        if travel <= 0.0 {
+            return 0.0;
+        }
+        ((thumb_y - self.track.y) / travel * self.range).clamp(0.0, self.range)
+    }
+
+    /// Paint the track and the thumb over the content
+    pub fn paint(&self, commands: &mut Vec<PaintCommand>) {
+        commands.push(PaintCommand::FillRect { rect: self.track, color: RenderColor::new(0, 0, 0, 20) });
+        commands.push(PaintCommand::FillRect { rect: self.thumb, color: RenderColor::new(0, 0, 0, 100) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 400px tall viewport on content 1600px tall
+    fn page_bar(scroll: f32) -> Scrollbar {
+        Scrollbar::new(Rect::new(0.0, 80.0, 800.0, 400.0), 1600.0, scroll).unwrap()
+    }
+
+    #[test]
+    fn test_thumb_geometry() {
+        // A quarter of the content is visible, so the thumb is a quarter of
+        // the track and moves along the other three quarters
+        let top = page_bar(0.0);
+        assert_eq!(top.track, Rect::new(792.0, 80.0, 8.0, 400.0));
+        assert_eq!(top.thumb, Rect::new(792.0, 80.0, 8.0, 100.0));
+
+        let middle = page_bar(600.0);
+        assert_eq!(middle.thumb.y, 80.0 + 150.0);
+
+        let bottom = page_bar(1200.0);
+        assert_eq!(bottom.thumb.bottom(), top.track.bottom());
+
+        // Scrolling past the end doesn't move the thumb off the track
+        assert_eq!(page_bar(5000.0).thumb, bottom.thumb);
+    }
+
+    #[test]
+    fn test_thumb_min_height() {
+        let bar = Scrollbar::new(Rect::new(0.0, 0.0, 100.0, 100.0), 100_000.0, 0.0).unwrap();
+        assert_eq!(bar.thumb.height, THUMB_MIN_HEIGHT);
+    }
+
+    #[test]
+    fn test_no_scrollbar_when_content_fits() {
+        assert_eq!(Scrollbar::new(Rect::new(0.0, 0.0, 100.0, 400.0), 400.0, 0.0), None);
+        assert_eq!(Scrollbar::new(Rect::new(0.0, 0.0, 100.0, 400.0), 120.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_dragged_thumb_scrolls() {
+        let bar = page_bar(0.0);
+        assert_eq!(bar.scroll_for_thumb(80.0 + 150.0), 600.0);
+        assert_eq!(bar.scroll_for_thumb(80.0 + 300.0), 1200.0);
+        assert_eq!(bar.scroll_for_thumb(0.0), 0.0);
+        assert_eq!(bar.scroll_for_thumb(1000.0), 1200.0);
+    }
+}
@@ -0,0 +1,181 @@
+//! Session
+//!
+//! The tabs open when the browser quits, kept as JSON in the config
+//! directory: each tab's history, the entry it is at and how far its page
+//! was scrolled. A restored tab gets its history back at once, its page is
+//! loaded when the tab is first shown. A session file that cannot be read
+//! is ignored and the browser starts afresh.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::navigation::{HistoryEntry, NavigationState};
+
+/// An entry of a tab's history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionEntry {
+    pub url: String,
+    /// State object a script stored with the entry, serialized as JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Added by pushState, so it shares the document of the entry before
+    #[serde(default)]
+    pub same_document: bool,
+}
+
+/// A tab of the session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionTab {
+    pub history: Vec<SessionEntry>,
+    /// Position of the entry the tab is at
+    pub current: usize,
+    /// Vertical scroll offset of the tab's page
+    #[serde(default)]
+    pub scroll_y: f32,
+}
+
+impl SessionTab {
+    /// The tab a history is saved as, None for a tab that has none
+    pub fn new(navigation: &NavigationState, scroll_y: f32) -> Option<Self> {
+        if navigation.is_empty() {
+            return None;
+        }
+        let history = navigation
+            .entries()
+            .iter()
+            .map(|entry| SessionEntry {
+                url: entry.url.to_string(),
+                state: entry.state.clone(),
+                same_document: entry.same_document,
+            })
+            .collect();
+        Some(Self { history, current: navigation.current_index(), scroll_y })
+    }
+
+    /// The history the tab had, without the entries whose URL no longer
+    /// parses; None when no entry is left
+    pub fn navigation(&self) -> Option<NavigationState> {
+        let mut current = self.current;
+        let mut history = Vec::new();
+        for (i, entry) in self.history.iter().enumerate() {
+            match Url::parse(&entry.url) {
+                Ok(url) => history.push(HistoryEntry {
+                    url,
+                    state: entry.state.clone(),
+                    same_document: entry.same_document,
+                }),
+                // Entries dropped before the current one move it back
+                Err(_) if i < self.current => current -= 1,
+                Err(_) => {}
+            }
+        }
+        NavigationState::from_entries(history, current)
+    }
+}
+
+/// The tabs of the browser
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub tabs: Vec<SessionTab>,
+    /// Position of the tab shown
+    pub active: usize,
+}
+
+impl Session {
+    /// Load a session from a file, empty when the file does not exist
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save the session to a file, creating its directory
+    ///
+    /// The file is written next to its place and then moved there, so a
+    /// save cut short leaves the last session whole.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let partial = path.with_extension("json.part");
+        std::fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&partial, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let mut first = NavigationState::new();
+        first.navigate_to(url("https://a.test/"));
+        first.navigate_to(url("https://a.test/next"));
+        first.push_state(url("https://a.test/next#2"), Some("{\"n\":2}".to_string()));
+        first.navigate_to(url("https://b.test/"));
+        first.traverse(-2);
+
+        let mut second = NavigationState::new();
+        second.navigate_to(url("about:bookmarks"));
+
+        let session = Session {
+            tabs: vec![SessionTab::new(&first, 120.0).unwrap(), SessionTab::new(&second, 0.0).unwrap()],
+            active: 1,
+        };
+        assert_eq!(SessionTab::new(&NavigationState::new(), 0.0), None);
+
+        let dir = std::env::temp_dir().join(format!("gugalanna-session-{}", std::process::id()));
+        let path = dir.join("session.json");
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded, session);
+
+        // The history comes back at the entry it was at
+        let restored = loaded.tabs[0].navigation().unwrap();
+        assert_eq!(restored.entries(), first.entries());
+        assert_eq!(restored.current_index(), 1);
+        assert_eq!(restored.current_url(), Some(&url("https://a.test/next")));
+        assert!(restored.can_go_back() && restored.can_go_forward());
+        assert_eq!(loaded.tabs[0].scroll_y, 120.0);
+        assert_eq!(loaded.tabs[1].navigation().unwrap().current_url(), Some(&url("about:bookmarks")));
+    }
+
+    #[test]
+    fn test_bad_sessions_are_errors() {
+        let dir = std::env::temp_dir().join(format!("gugalanna-bad-session-{}", std::process::id()));
+        let path = dir.join("session.json");
+        assert_eq!(Session::load(&path).unwrap(), Session::default());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "{\"tabs\": [{\"history\": [{\"url\": \"https://a.te").unwrap();
+        let partial = Session::load(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(partial.is_err());
+
+        // Entries whose URL does not parse are left out
+        let tab = SessionTab {
+            history: ["not a url", "https://a.test/", "also not"]
+                .iter()
+                .map(|url| SessionEntry { url: url.to_string(), state: None, same_document: false })
+                .collect(),
+            current: 2,
+            scroll_y: 0.0,
+        };
+        let navigation = tab.navigation().unwrap();
+        assert_eq!(navigation.len(), 1);
+        assert_eq!(navigation.current_url(), Some(&url("https://a.test/")));
+        let empty = SessionTab { history: tab.history[..1].to_vec(), current: 0, scroll_y: 0.0 };
+        assert!(empty.navigation().is_none());
+    }
+}
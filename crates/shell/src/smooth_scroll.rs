@@ -0,0 +1,85 @@
+//! Smooth Scrolling
+//!
+//! Animates the page to the offset it is scrolled to, by the wheel, keys
+//! or a script asking for `behavior: 'smooth'`. The frame loop moves the
+//! scroll on by the time each frame took. Scrolling again while it is
+//! under way heads on from where the page is to the new offset.
+
+use gugalanna_style::TimingFunction;
+
+use crate::transition::apply_easing;
+
+/// How long a smooth scroll takes, however far it goes
+const SMOOTH_SCROLL_DURATION_MS: f32 = 200.0;
+
+/// A smooth scroll of the page under way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmoothScroll {
+    from: f32,
+    to: f32,
+    elapsed_ms: f32,
+}
+
+impl SmoothScroll {
+    pub fn new(from: f32, to: f32) -> Self {
+        Self { from, to, elapsed_ms: 0.0 }
+    }
+
+    /// Offset the scroll ends at
+    pub fn target(&self) -> f32 {
+        self.to
+    }
+
+    /// Head for another offset from `current`, where the page is now
+    pub fn retarget(&mut self, current: f32, to: f32) {
+        *self = Self::new(current, to);
+    }
+
+    /// Move on by `delta_ms`, returns where the page is and whether it has
+    /// arrived
+    pub fn advance(&mut self, delta_ms: f32) -> (f32, bool) {
+        self.elapsed_ms += delta_ms.max(0.0);
+        let progress = (self.elapsed_ms / SMOOTH_SCROLL_DURATION_MS).min(1.0);
+        if progress >= 1.0 {
+            return (self.to, true);
+        }
+        let eased = apply_easing(progress, TimingFunction::EaseOut);
+        (self.from + (self.to - self.from) * eased, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smooth_scroll_arrives() {
+        let mut scroll = SmoothScroll::new(100.0, 500.0);
+        assert_eq!(scroll.advance(0.0), (100.0, false));
+
+        // Each frame gets closer, by less as it slows down
+        let mut last = (100.0, 0.0);
+        for _ in 0..12 {
+            let (offset, done) = scroll.advance(16.0);
+            assert!(offset > last.0 && offset < 500.0 && !done);
+            assert!(offset - last.0 < last.1 || last.1 == 0.0);
+            last = (offset, offset - last.0);
+        }
+        assert_eq!(scroll.advance(16.0), (500.0, true));
+        assert_eq!(scroll.advance(16.0), (500.0, true));
+    }
+
+    #[test]
+    fn test_retarget_goes_on_from_the_page() {
+        let mut scroll = SmoothScroll::new(0.0, 400.0);
+        let (offset, _) = scroll.advance(50.0);
+
+        // Another wheel step adds to where the scroll was headed
+        scroll.retarget(offset, scroll.target() + 40.0);
+        assert_eq!(scroll.target(), 440.0);
+        assert_eq!(scroll.advance(0.0), (offset, false));
+        let (next, _) = scroll.advance(16.0);
+        assert!(next > offset);
+        assert_eq!(scroll.advance(SMOOTH_SCROLL_DURATION_MS), (440.0, true));
+    }
+}
@@ -0,0 +1,104 @@
+//! User Stylesheet
+//!
+//! `user.css` in the config directory, or the file the config names, added
+//! to the cascade of every page at the user origin: author rules win over
+//! its rules, unless they are `!important`. The file is read at startup and
+//! read again when a page loads after it changed, so edits show on reload.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use gugalanna_css::Stylesheet;
+
+/// Name of the user stylesheet in the config directory
+pub const USER_STYLESHEET_NAME: &str = "user.css";
+
+/// The user stylesheet as last read
+pub struct UserStylesheet {
+    path: Option<PathBuf>,
+    /// When the file read was modified, and its length
+    read: Option<(SystemTime, u64)>,
+    stylesheet: Option<Stylesheet>,
+}
+
+impl UserStylesheet {
+    /// Read the stylesheet at `path`, None for no user stylesheet
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut user = Self { path, read: None, stylesheet: None };
+        user.refresh();
+        user
+    }
+
+    pub fn stylesheet(&self) -> Option<&Stylesheet> {
+        self.stylesheet.as_ref()
+    }
+
+    /// Read the file again if it changed since it was last read
+    pub fn refresh(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound && self.read.is_some() {
+                    log::warn!("Failed to read the user stylesheet {}: {}", path.display(), e);
+                }
+                self.read = None;
+                self.stylesheet = None;
+                return;
+            }
+        };
+        let read = metadata.modified().ok().map(|modified| (modified, metadata.len()));
+        if read.is_some() && read == self.read {
+            return;
+        }
+        self.read = read;
+        self.stylesheet = read_stylesheet(path);
+    }
+}
+
+fn read_stylesheet(path: &Path) -> Option<Stylesheet> {
+    let css = std::fs::read_to_string(path)
+        .map_err(|e| log::warn!("Failed to read the user stylesheet {}: {}", path.display(), e))
+        .ok()?;
+    match Stylesheet::parse(&css) {
+        Ok(stylesheet) => {
+            log::info!("Loaded the user stylesheet {}", path.display());
+            Some(stylesheet)
+        }
+        Err(e) => {
+            log::warn!("Failed to parse the user stylesheet {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_stylesheet_read_again_when_changed() {
+        let dir = std::env::temp_dir().join(format!("gugalanna-user-css-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(USER_STYLESHEET_NAME);
+
+        // No file, no stylesheet
+        let mut user = UserStylesheet::load(Some(path.clone()));
+        assert!(user.stylesheet().is_none());
+
+        std::fs::write(&path, "body { background-color: black; }").unwrap();
+        user.refresh();
+        assert_eq!(user.stylesheet().map(|s| s.rules.len()), Some(1));
+
+        std::fs::write(&path, "body { background-color: black; } p { color: white; }").unwrap();
+        user.refresh();
+        assert_eq!(user.stylesheet().map(|s| s.rules.len()), Some(2));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        user.refresh();
+        assert!(user.stylesheet().is_none());
+        assert!(UserStylesheet::load(None).stylesheet().is_none());
+    }
+}
@@ -0,0 +1,361 @@
+//! Constraint Validation
+//!
+//! The checks the controls of a form must pass before it is sent, as their
+//! attributes ask: `required`, `minlength` and `maxlength`, `pattern`
+//! (matched against the whole value), the formats of email and URL
+//! fields, and the range and step of number fields. A control failing one
+//! gets the message the user is shown. Disabled and read-only controls are
+//! not checked, nor are the constraints other than `required` on empty
+//! values.
+
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use gugalanna_dom::{DomTree, ElementData, NodeId};
+use regex::Regex;
+use rustc_hash::FxHashMap;
+use url::Url;
+
+use crate::form::FormState;
+use crate::{find_radio_buttons_in_group, is_disabled};
+
+/// `pattern` attributes compiled to regular expressions, so each is
+/// compiled once however often its control is checked
+#[derive(Debug, Default, Clone)]
+pub struct PatternCache {
+    /// None for a pattern that isn't a valid regular expression
+    compiled: RefCell<FxHashMap<String, Option<Regex>>>,
+}
+
+impl PatternCache {
+    /// Whether `value` matches the whole of `pattern`, which a pattern that
+    /// isn't a valid regular expression always does
+    pub fn matches(&self, pattern: &str, value: &str) -> bool {
+        let mut compiled = self.compiled.borrow_mut();
+        let regex = compiled
+            .entry(pattern.to_string())
+            .or_insert_with(|| Regex::new(&format!("^(?:{})$", pattern)).ok());
+        regex.as_ref().is_none_or(|regex| regex.is_match(value))
+    }
+}
+
+/// The controls of a form that are validated, in document order
+pub fn validated_controls(dom: &DomTree, form_id: NodeId) -> Vec<NodeId> {
+    dom.descendants(form_id)
+        .into_iter()
+        .filter(|&id| {
+            dom.get(id)
+                .and_then(|n| n.as_element())
+                .is_some_and(|e| matches!(e.tag_name.as_str(), "input" | "select" | "textarea"))
+        })
+        .collect()
+}
+
+/// Why a control does not meet its constraints, None when it does or is
+/// not validated
+pub fn validation_message(dom: &DomTree, form_state: &FormState, node_id: NodeId) -> Option<String> {
+    let element = dom.get(node_id)?.as_element()?;
+    if is_disabled(dom, form_state, node_id) || element.get_attribute("readonly").is_some() {
+        return None;
+    }
+    let required = element.get_attribute("required").is_some();
+    let missing = |message: &str| required.then(|| message.to_string());
+
+    match element.tag_name.as_str() {
+        "textarea" => {
+            let value = form_state.get_value(node_id).map_or_else(|| dom.text_content(node_id), str::to_string);
+            text_message(element, "textarea", &value, required, form_state.patterns())
+        }
+        "select" if select_value(dom, form_state, node_id).is_empty() => {
+            missing("Please select an item in the list.")
+        }
+        "input" => {
+            let input_type = element.get_attribute("type").unwrap_or("text").to_ascii_lowercase();
+            match input_type.as_str() {
+                "checkbox" if !form_state.is_checked(node_id) => {
+                    missing("Please check this box if you want to proceed.")
+                }
+                // One radio button of a group checked is enough
+                "radio" => {
+                    let checked = match element.get_attribute("name") {
+                        Some(name) if !name.is_empty() => {
+                            find_radio_buttons_in_group(dom, name).into_iter().any(|id| form_state.is_checked(id))
+                        }
+                        _ => form_state.is_checked(node_id),
+                    };
+                    if checked {
+                        return None;
+                    }
+                    missing("Please select one of these options.")
+                }
+                "file" if form_state.get_files(node_id).is_empty() => missing("Please select a file."),
+                "checkbox" | "file" | "hidden" | "submit" | "button" | "reset" | "image" => None,
+                _ => {
+                    let value = form_state
+                        .get_value(node_id)
+                        .or_else(|| element.get_attribute("value"))
+                        .unwrap_or_default()
+                        .to_string();
+                    text_message(element, &input_type, &value, required, form_state.patterns())
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Check the value of a text field, a text area or a number field
+fn text_message(
+    element: &ElementData,
+    input_type: &str,
+    value: &str,
+    required: bool,
+    patterns: &PatternCache,
+) -> Option<String> {
+    if value.is_empty() {
+        return required.then(|| "Please fill out this field.".to_string());
+    }
+    match input_type {
+        "email" => {
+            // Several addresses are separated by commas
+            let valid = match element.get_attribute("multiple") {
+                Some(_) => value.split(',').all(|address| is_email(address.trim())),
+                None => is_email(value),
+            };
+            if !valid {
+                return Some(format!("Please enter an email address. \"{}\" is not one.", value));
+            }
+        }
+        "url" if Url::parse(value).is_err() => return Some("Please enter a URL.".to_string()),
+        "number" => return number_message(element, value),
+        _ => {}
+    }
+
+    if let Some(pattern) = element.get_attribute("pattern").filter(|_| input_type != "textarea") {
+        if !patterns.matches(pattern, value) {
+            return Some(match element.get_attribute("title") {
+                Some(title) => format!("Please match the requested format: {}", title),
+                None => "Please match the requested format.".to_string(),
+            });
+        }
+    }
+
+    let length = value.chars().count();
+    if let Some(max) = length_attribute(element, "maxlength").filter(|&max| length > max) {
+        return Some(format!(
+            "Please shorten this text to {} characters or less (you are currently using {} characters).",
+            max, length
+        ));
+    }
+    if let Some(min) = length_attribute(element, "minlength").filter(|&min| length < min) {
+        return Some(format!(
+            "Please lengthen this text to {} characters or more (you are currently using {} characters).",
+            min, length
+        ));
+    }
+    None
+}
+
+/// Check the value of a number field against its range and step
+fn number_message(element: &ElementData, value: &str) -> Option<String> {
+    let Some(number) = parse_number(value) else {
+        return Some("Please enter a number.".to_string());
+    };
+    let min = element.get_attribute("min").and_then(parse_number);
+    let max = element.get_attribute("max").and_then(parse_number);
+    if let Some(min) = min.filter(|&min| number < min) {
+        return Some(format!("Value must be greater than or equal to {}.", format_number(min)));
+    }
+    if let Some(max) = max.filter(|&max| number > max) {
+        return Some(format!("Value must be less than or equal to {}.", format_number(max)));
+    }
+
+    // Steps are counted from the minimum, `any` allows every value
+    let step = match element.get_attribute("step") {
+        Some(step) if step.trim().eq_ignore_ascii_case("any") => return None,
+        Some(step) => parse_number(step).filter(|&step| step > 0.0).unwrap_or(1.0),
+        None => 1.0,
+    };
+    let base = min.or_else(|| element.get_attribute("value").and_then(parse_number)).unwrap_or(0.0);
+    let steps = (number - base) / step;
+    if (steps - steps.round()).abs() <= 1e-7 {
+        return None;
+    }
+    let below = base + steps.floor() * step;
+    Some(format!(
+        "Please enter a valid value. The two nearest valid values are {} and {}.",
+        format_number(below),
+        format_number(below + step)
+    ))
+}
+
+/// A number as number fields take it, None for anything else
+fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|number| number.is_finite())
+}
+
+/// A number without the noise of floating point arithmetic
+fn format_number(number: f64) -> String {
+    let rounded = (number * 1e9).round() / 1e9;
+    format!("{}", rounded)
+}
+
+/// A length limit of a field, None when it is missing or negative
+fn length_attribute(element: &ElementData, name: &str) -> Option<usize> {
+    element.get_attribute(name)?.trim().parse().ok()
+}
+
+/// Check an email address the way the HTML standard does
+fn is_email(address: &str) -> bool {
+    static EMAIL: OnceLock<Regex> = OnceLock::new();
+    EMAIL
+        .get_or_init(|| {
+            Regex::new(concat!(
+                r"^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~-]+",
+                r"@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?",
+                r"(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)*$",
+            ))
+            .unwrap()
+        })
+        .is_match(address)
+}
+
+/// Value of the option selected in a select: its `value`, or its text
+fn select_value(dom: &DomTree, form_state: &FormState, node_id: NodeId) -> String {
+    let options: Vec<NodeId> = dom
+        .descendants(node_id)
+        .into_iter()
+        .filter(|&id| dom.get(id).and_then(|n| n.as_element()).is_some_and(|e| e.tag_name == "option"))
+        .collect();
+    let is_selected = |id: NodeId| {
+        dom.get(id).and_then(|n| n.as_element()).is_some_and(|e| e.get_attribute("selected").is_some())
+    };
+    let selected = match form_state.get_selected_index(node_id) {
+        Some(index) => usize::try_from(index).ok(),
+        None => options.iter().position(|&id| is_selected(id)).or(Some(0)),
+    };
+    let Some(&option) = selected.and_then(|index| options.get(index)) else {
+        return String::new();
+    };
+    match dom.get(option).and_then(|n| n.as_element()).and_then(|e| e.get_attribute("value")) {
+        Some(value) => value.to_string(),
+        None => dom.text_content(option).trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_dom::Queryable;
+    use gugalanna_html::HtmlParser;
+
+    /// The message of each element with an id, for values typed in
+    fn messages(html: &str, typed: &[(&str, &str)]) -> Vec<(String, Option<String>)> {
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let mut form_state = FormState::new();
+        for (id, value) in typed {
+            form_state.set_text(dom.get_element_by_id(id).unwrap(), value.to_string());
+        }
+        dom.descendants(dom.document_id())
+            .into_iter()
+            .filter_map(|id| {
+                let element_id = dom.get(id)?.as_element()?.id()?.to_string();
+                Some((element_id, validation_message(&dom, &form_state, id)))
+            })
+            .collect()
+    }
+
+    fn message(html: &str, value: &str) -> Option<String> {
+        messages(html, &[("field", value)]).remove(0).1
+    }
+
+    #[test]
+    fn test_required() {
+        let html = r#"<input id="name" required><input id="note"><textarea id="bio" required>Hi</textarea>
+            <input id="off" required disabled><input id="fixed" required readonly>
+            <input type="checkbox" id="terms" required><input type="radio" id="a" name="size" required>
+            <input type="radio" id="b" name="size"><select id="pick" required><option value="">Choose</option></select>
+            <input type="file" id="upload" required>"#;
+        let found = messages(html, &[]);
+        let message = |id: &str| found.iter().find(|(element, _)| element == id).unwrap().1.clone();
+        assert_eq!(message("name").as_deref(), Some("Please fill out this field."));
+        assert_eq!(message("note"), None);
+        assert_eq!(message("bio"), None);
+        assert_eq!(message("off"), None);
+        assert_eq!(message("fixed"), None);
+        assert_eq!(message("terms").as_deref(), Some("Please check this box if you want to proceed."));
+        assert_eq!(message("a").as_deref(), Some("Please select one of these options."));
+        assert_eq!(message("b"), None);
+        assert_eq!(message("pick").as_deref(), Some("Please select an item in the list."));
+        assert_eq!(message("upload").as_deref(), Some("Please select a file."));
+
+        // Checking another radio button of the group is enough
+        let dom = HtmlParser::new().parse(html).unwrap();
+        let mut form_state = FormState::new();
+        form_state.set_checked(dom.get_element_by_id("b").unwrap(), true);
+        assert_eq!(validation_message(&dom, &form_state, dom.get_element_by_id("a").unwrap()), None);
+    }
+
+    #[test]
+    fn test_length_and_pattern() {
+        let html = r#"<input id="field" minlength="3" maxlength="5" pattern="[a-z]+" title="Lowercase letters">"#;
+        assert_eq!(message(html, ""), None);
+        assert_eq!(message(html, "abc"), None);
+        assert_eq!(
+            message(html, "ab").as_deref(),
+            Some("Please lengthen this text to 3 characters or more (you are currently using 2 characters).")
+        );
+        assert_eq!(
+            message(html, "abcdef").as_deref(),
+            Some("Please shorten this text to 5 characters or less (you are currently using 6 characters).")
+        );
+        // The pattern has to match the whole value
+        assert_eq!(message(html, "abc1").as_deref(), Some("Please match the requested format: Lowercase letters"));
+        let either = r#"<input id="field" pattern="a|b">"#;
+        assert_eq!(message(either, "ab").as_deref(), Some("Please match the requested format."));
+        assert_eq!(message(r#"<input id="field" pattern="(">"#, "anything"), None);
+    }
+
+    #[test]
+    fn test_patterns_compiled_once() {
+        let patterns = PatternCache::default();
+        assert!(patterns.matches("[0-9]+", "42"));
+        assert!(!patterns.matches("[0-9]+", "4x"));
+        assert!(patterns.matches("(", "anything"));
+        assert_eq!(patterns.compiled.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_email_and_url() {
+        let email = r#"<input id="field" type="email">"#;
+        assert_eq!(message(email, "ana@example.com"), None);
+        assert_eq!(
+            message(email, "ana@").as_deref(),
+            Some("Please enter an email address. \"ana@\" is not one.")
+        );
+        assert!(message(email, "ana example.com").is_some());
+        let emails = r#"<input id="field" type="email" multiple>"#;
+        assert_eq!(message(emails, "a@x.org, b@y.org"), None);
+        assert!(message(emails, "a@x.org, b").is_some());
+
+        let url = r#"<input id="field" type="url">"#;
+        assert_eq!(message(url, "https://example.com/page"), None);
+        assert_eq!(message(url, "example.com").as_deref(), Some("Please enter a URL."));
+    }
+
+    #[test]
+    fn test_number() {
+        let html = r#"<input id="field" type="number" min="1" max="10" step="0.5">"#;
+        assert_eq!(message(html, "2.5"), None);
+        assert_eq!(message(html, "ten").as_deref(), Some("Please enter a number."));
+        assert_eq!(message(html, "0").as_deref(), Some("Value must be greater than or equal to 1."));
+        assert_eq!(message(html, "10.5").as_deref(), Some("Value must be less than or equal to 10."));
+        assert_eq!(
+            message(html, "2.2").as_deref(),
+            Some("Please enter a valid value. The two nearest valid values are 2 and 2.5.")
+        );
+        assert_eq!(message(r#"<input id="field" type="number" step="any">"#, "0.123"), None);
+        assert!(message(r#"<input id="field" type="number">"#, "1.5").is_some());
+        assert_eq!(message(r#"<input id="field" type="number" step="0.1">"#, "0.3"), None);
+    }
+}
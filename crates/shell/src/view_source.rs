@@ -0,0 +1,308 @@
+//! View Source
+//!
+//! `view-source:<url>` shows the source of a page as text instead of the
+//! page. The source is made into a page of its own, a numbered line per
+//! line of the source, with its markup escaped and tags, attributes and
+//! comments colored.
+
+use url::Url;
+
+use crate::bookmarks::escape_html;
+
+/// Scheme of the URLs showing the source of a page
+pub const VIEW_SOURCE_SCHEME: &str = "view-source";
+
+/// The URL whose source a `view-source:` URL shows, None for other URLs
+pub fn source_target(url: &Url) -> Option<Url> {
+    if url.scheme() != VIEW_SOURCE_SCHEME {
+        return None;
+    }
+    let target = Url::parse(&url.as_str()[VIEW_SOURCE_SCHEME.len() + 1..]).ok()?;
+    // Sources of sources are not shown
+    (target.scheme() != VIEW_SOURCE_SCHEME).then_some(target)
+}
+
+/// The `view-source:` URL showing the source of a page
+pub fn source_url(url: &Url) -> Option<Url> {
+    Url::parse(&format!("{}:{}", VIEW_SOURCE_SCHEME, url)).ok()
+}
+
+/// Read the source of a file: URL
+pub fn read_file_source(url: &Url) -> Result<String, String> {
+    let path = url.to_file_path().map_err(|_| format!("Invalid file URL: {}", url))?;
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// What a piece of the source is, for its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Text,
+    /// `<name`, `>`, `/>` and `</name>`
+    Tag,
+    AttributeName,
+    AttributeValue,
+    Comment,
+    Doctype,
+}
+
+impl Token {
+    /// Class of the span the piece is shown in, None for plain text
+    fn class(self) -> Option<&'static str> {
+        match self {
+            Token::Text => None,
+            Token::Tag => Some("tag"),
+            Token::AttributeName => Some("attr"),
+            Token::AttributeValue => Some("value"),
+            Token::Comment => Some("comment"),
+            Token::Doctype => Some("doctype"),
+        }
+    }
+}
+
+/// Split HTML into the pieces it is colored by
+///
+/// This is not a parser: it only finds where tags, comments and doctypes
+/// start and end. The text of scripts and styles is left plain up to
+/// their end tag.
+fn tokenize(source: &str) -> Vec<(Token, &str)> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while !rest.is_empty() {
+        let (token, len) = if rest.starts_with("<!--") {
+            (Token::Comment, rest.find("-->").map_or(rest.len(), |end| end + 3))
+        } else if rest.starts_with("<!") {
+            (Token::Doctype, rest.find('>').map_or(rest.len(), |end| end + 1))
+        } else if starts_tag(rest) {
+            let name = tag_name(rest);
+            let len = push_tag(rest, &mut tokens);
+            rest = &rest[len..];
+            // Scripts and styles hold text up to their end tag
+            if matches!(name.as_str(), "script" | "style") && !rest.is_empty() {
+                let end = find_end_tag(rest, &name).unwrap_or(rest.len());
+                if end > 0 {
+                    tokens.push((Token::Text, &rest[..end]));
+                }
+                rest = &rest[end..];
+            }
+            continue;
+        } else {
+            // Text runs to the next tag, a `<` that starts none is text
+            let next = rest.char_indices().skip(1).find(|&(i, c)| c == '<' && starts_markup(&rest[i..]));
+            (Token::Text, next.map_or(rest.len(), |(i, _)| i))
+        };
+        tokens.push((token, &rest[..len]));
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// Whether text starts a tag, comment or doctype
+fn starts_markup(text: &str) -> bool {
+    text.starts_with("<!") || starts_tag(text)
+}
+
+/// Whether text starts a start or end tag
+fn starts_tag(text: &str) -> bool {
+    let name = text.strip_prefix("</").or_else(|| text.strip_prefix('<'));
+    name.and_then(|name| name.chars().next()).is_some_and(|c| c.is_ascii_alphabetic())
+}
+
+/// Name of the start tag text starts with, lowercased; empty for end tags
+fn tag_name(text: &str) -> String {
+    match text.strip_prefix('<') {
+        Some(name) if !name.starts_with('/') => {
+            name.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '-').collect::<String>().to_lowercase()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Where the end tag of a script or style starts in its text
+fn find_end_tag(text: &str, name: &str) -> Option<usize> {
+    let lower = text.to_ascii_lowercase();
+    let end_tag = format!("</{}", name);
+    lower.find(&end_tag)
+}
+
+/// Push the pieces of the tag text starts with, returns its length
+fn push_tag<'a>(text: &'a str, tokens: &mut Vec<(Token, &'a str)>) -> usize {
+    let bytes = text.as_bytes();
+    let name_start = if text.starts_with("</") { 2 } else { 1 };
+    let mut i = name_start;
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' && bytes[i] != b'/' {
+        i += 1;
+    }
+    tokens.push((Token::Tag, &text[..i]));
+
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            b'>' => {
+                tokens.push((Token::Tag, &text[i..=i]));
+                return i + 1;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'>') => {
+                tokens.push((Token::Tag, &text[i..i + 2]));
+                return i + 2;
+            }
+            c if c.is_ascii_whitespace() || c == b'=' || c == b'/' => {
+                i += 1;
+                Token::Text
+            }
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+                i = (i + 1).min(bytes.len());
+                Token::AttributeValue
+            }
+            _ => {
+                // A value follows `=`, other words are names
+                let value = text[..start].trim_end().ends_with('=');
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"=>".contains(&bytes[i]) {
+                    if !value && bytes[i] == b'/' {
+                        break;
+                    }
+                    i += 1;
+                }
+                if value {
+                    Token::AttributeValue
+                } else {
+                    Token::AttributeName
+                }
+            }
+        };
+        tokens.push((token, &text[start..i]));
+    }
+    i
+}
+
+/// The page showing the source of a URL
+pub fn source_page(url: &Url, source: &str) -> String {
+    // Pieces spanning lines are split, each line closing its spans
+    let mut lines = vec![String::new()];
+    for (token, text) in tokenize(source) {
+        for (i, piece) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            if piece.is_empty() {
+                continue;
+            }
+            let piece = escape_html(piece.strip_suffix('\r').unwrap_or(piece));
+            let line = lines.last_mut().expect("lines start with one");
+            match token.class() {
+                Some(class) => line.push_str(&format!("<span class=\"{}\">{}</span>", class, piece)),
+                None => line.push_str(&piece),
+            }
+        }
+    }
+    if source.ends_with('\n') {
+        lines.pop();
+    }
+
+    let mut body = String::new();
+    for (number, line) in lines.iter().enumerate() {
+        body.push_str(&format!(
+            "<div class=\"line\"><span class=\"number\">{}</span>{}</div>\n",
+            number + 1,
+            line
+        ));
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>{VIEW_SOURCE_SCHEME}:{url}</title>
+    <style>
+        body {{ margin: 8px 0; font-family: monospace; font-size: 13px; }}
+        .line {{ white-space: pre-wrap; }}
+        .number {{ display: inline-block; width: 40px; margin-right: 12px; color: #999; text-align: right; }}
+        .tag {{ color: #881280; }}
+        .attr {{ color: #994500; }}
+        .value {{ color: #1a1aa6; }}
+        .comment {{ color: #236e25; }}
+        .doctype {{ color: #808080; }}
+    </style>
+</head>
+<body>
+{body}</body>
+</html>"#,
+        url = escape_html(url.as_str()),
+        body = body,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_dom::Queryable;
+    use gugalanna_html::HtmlParser;
+
+    #[test]
+    fn test_source_target() {
+        let url = Url::parse("view-source:https://example.com/a?q=1#top").unwrap();
+        assert_eq!(source_target(&url).unwrap().as_str(), "https://example.com/a?q=1#top");
+        assert_eq!(source_url(&source_target(&url).unwrap()), Some(url.clone()));
+
+        assert_eq!(source_target(&Url::parse("https://example.com/").unwrap()), None);
+        assert_eq!(source_target(&Url::parse("view-source:view-source:https://example.com/").unwrap()), None);
+    }
+
+    #[test]
+    fn test_tokens_color_markup() {
+        let tokens = tokenize("<!DOCTYPE html><a href=\"/x\" hidden>1 < 2</a><!-- c --><script>a<b</script>");
+        let colored: Vec<(Option<&str>, &str)> = tokens.iter().map(|&(token, text)| (token.class(), text)).collect();
+        assert_eq!(
+            colored,
+            [
+                (Some("doctype"), "<!DOCTYPE html>"),
+                (Some("tag"), "<a"),
+                (None, " "),
+                (Some("attr"), "href"),
+                (None, "="),
+                (Some("value"), "\"/x\""),
+                (None, " "),
+                (Some("attr"), "hidden"),
+                (Some("tag"), ">"),
+                (None, "1 < 2"),
+                (Some("tag"), "</a"),
+                (Some("tag"), ">"),
+                (Some("comment"), "<!-- c -->"),
+                (Some("tag"), "<script"),
+                (Some("tag"), ">"),
+                (None, "a<b"),
+                (Some("tag"), "</script"),
+                (Some("tag"), ">"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_page_escapes_markup() {
+        let url = Url::parse("https://a.test/").unwrap();
+        let source = "<p class=\"x\">Hi &amp; <b>bye</b></p>\n<!-- two\nlines -->\n<script>alert(1)</script>\n";
+        let page = source_page(&url, source);
+        let tree = HtmlParser::new().parse(&page).unwrap();
+
+        // The source's markup is text, not elements
+        assert!(tree.get_elements_by_tag_name("b").is_empty());
+        assert!(tree.get_elements_by_tag_name("script").is_empty());
+        let lines: Vec<String> = tree
+            .get_elements_by_tag_name("div")
+            .into_iter()
+            .map(|line| tree.text_content(line))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "1<p class=\"x\">Hi &amp; <b>bye</b></p>",
+                "2<!-- two",
+                "3lines -->",
+                "4<script>alert(1)</script>",
+            ]
+        );
+    }
+}
@@ -6,6 +6,7 @@
 use gugalanna_css::{Stylesheet, Rule, StyleRule, Declaration, Specificity, parse_inline_style};
 use gugalanna_dom::{DomTree, NodeId};
 
+use crate::fontface::WebFontFace;
 use crate::matching::{matches_selector_with_context, MatchingContext};
 
 /// Origin of a stylesheet
@@ -114,6 +115,18 @@ impl Cascade {
         self.author_stylesheets.push(stylesheet);
     }
 
+    /// Font faces declared by the author stylesheets
+    pub fn web_font_faces(&self) -> Vec<WebFontFace> {
+        self.author_stylesheets
+            .iter()
+            .flat_map(|stylesheet| &stylesheet.rules)
+            .filter_map(|rule| match rule {
+                Rule::FontFace(font_face) => WebFontFace::from_rule(font_face),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Get all matching declarations for an element, sorted by cascade priority
     pub fn get_matching_declarations(
         &self,
@@ -362,6 +375,9 @@ pub fn default_ua_stylesheet() -> Stylesheet {
         caption { display: table-caption; }
         colgroup { display: table-column-group; }
         col { display: table-column; }
+        table { border-spacing: 2px; }
+        td, th { padding-top: 1px; padding-right: 1px; padding-bottom: 1px; padding-left: 1px; vertical-align: middle; }
+        th { font-weight: bold; }
 
         /* Inline elements */
         a, abbr, acronym, b, bdo, big, br, cite, code, dfn, em, i, img, kbd,
@@ -380,8 +396,14 @@ pub fn default_ua_stylesheet() -> Stylesheet {
         ul, ol { margin-top: 1em; margin-bottom: 1em; padding-left: 40px; }
         li { margin-top: 0; margin-bottom: 0; }
 
+        /* Directionality */
+        [dir=ltr] { direction: ltr; }
+        [dir=rtl] { direction: rtl; }
+        [dir=rtl] ul, [dir=rtl] ol, ul[dir=rtl], ol[dir=rtl] { padding-left: 0; padding-right: 40px; }
+        [dir=ltr] ul, [dir=ltr] ol, ul[dir=ltr], ol[dir=ltr] { padding-left: 40px; padding-right: 0; }
+
         /* Links */
-        a { color: blue; }
+        a { color: blue; text-decoration: underline; }
         a:visited { color: purple; }
 
         /* Text formatting */
@@ -392,6 +414,7 @@ pub fn default_ua_stylesheet() -> Stylesheet {
 
         /* Monospace */
         pre, code, tt, kbd, samp { font-family: monospace; }
+        pre, listing, xmp { white-space: pre; }
 
         /* Form elements - inline-block so they flow with text but have box properties */
         button, input, select, textarea { display: inline-block; }
@@ -501,6 +524,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cascade_user_origin() {
+        let tree = parse_html("<p id='intro'>Hello</p>");
+        let p = tree.get_elements_by_tag_name("p")[0];
+        let color_of = |user: &str, author: &str| {
+            let mut cascade = Cascade::new();
+            cascade.add_user_stylesheet(Stylesheet::parse(user).unwrap());
+            cascade.add_author_stylesheet(Stylesheet::parse(author).unwrap());
+            match cascade.get_cascaded_value(&tree, p, "color").unwrap().value {
+                CssValue::Color(color) => (color.r, color.g, color.b),
+                value => panic!("Unexpected color {:?}", value),
+            }
+        };
+
+        // A user rule beats the UA's, and loses to the author's whatever
+        // its specificity
+        assert_eq!(color_of("p { color: white; }", ""), (255, 255, 255));
+        assert_eq!(color_of("#intro { color: white; }", "p { color: red; }"), (255, 0, 0));
+
+        // An important user rule beats the author's, important or not
+        assert_eq!(color_of("p { color: white !important; }", "#intro { color: red; }"), (255, 255, 255));
+        assert_eq!(color_of("p { color: white !important; }", "#intro { color: red !important; }"), (255, 255, 255));
+        assert_eq!(color_of("p { color: white; }", "p { color: red !important; }"), (255, 0, 0));
+    }
+
     #[test]
     fn test_default_ua_stylesheet() {
         let ua = default_ua_stylesheet();
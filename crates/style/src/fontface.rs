@@ -0,0 +1,138 @@
+//! Web Fonts
+//!
+//! Font faces declared by @font-face rules, for the browser to download
+//! and register under their family name.
+
+use gugalanna_css::{CssValue, FontFaceRule};
+
+use crate::{FontStyle, StyleResolver};
+
+/// A font face declared by an @font-face rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebFontFace {
+    /// Family name the face is used under
+    pub family: String,
+    pub weight: u16,
+    pub style: FontStyle,
+    /// Sources in order of preference
+    pub sources: Vec<WebFontSource>,
+}
+
+/// A downloadable source of a font face
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebFontSource {
+    /// URL, relative to the stylesheet
+    pub url: String,
+    /// Format hint from `format()`, if given
+    pub format: Option<String>,
+}
+
+impl WebFontSource {
+    /// Check if the source is in a format that can be loaded: TrueType and
+    /// OpenType, but not the compressed WOFF formats
+    pub fn is_supported(&self) -> bool {
+        match &self.format {
+            Some(format) => matches!(format.to_ascii_lowercase().as_str(), "truetype" | "opentype"),
+            None => {
+                let path = self.url.to_ascii_lowercase();
+                !(path.ends_with(".woff") || path.ends_with(".woff2"))
+            }
+        }
+    }
+}
+
+impl WebFontFace {
+    /// Read the face an @font-face rule declares, if it names a family and
+    /// has a URL to load it from
+    pub fn from_rule(rule: &FontFaceRule) -> Option<Self> {
+        let mut face = WebFontFace {
+            family: String::new(),
+            weight: 400,
+            style: FontStyle::Normal,
+            sources: Vec::new(),
+        };
+
+        for declaration in &rule.declarations {
+            let value = &declaration.value;
+            match declaration.property.to_ascii_lowercase().as_str() {
+                "font-family" => {
+                    if let CssValue::String(name) | CssValue::Keyword(name) = value {
+                        face.family = name.clone();
+                    }
+                }
+                "font-weight" => {
+                    if let Some(weight) = StyleResolver::resolve_font_weight(value) {
+                        face.weight = weight;
+                    }
+                }
+                "font-style" => {
+                    if let Some(style) = StyleResolver::resolve_font_style(value) {
+                        face.style = style;
+                    }
+                }
+                "src" => face.sources = sources(value),
+                _ => {}
+            }
+        }
+
+        (!face.family.is_empty() && !face.sources.is_empty()).then_some(face)
+    }
+}
+
+/// URL sources of a `src` descriptor, each with the format hint after it
+///
+/// `local()` sources name installed fonts, which aren't looked up.
+fn sources(value: &CssValue) -> Vec<WebFontSource> {
+    let values = match value {
+        CssValue::List(values) | CssValue::CommaSeparated(values) => values.as_slice(),
+        value => std::slice::from_ref(value),
+    };
+
+    let mut sources: Vec<WebFontSource> = Vec::new();
+    for value in values {
+        match value {
+            CssValue::Url(url) => sources.push(WebFontSource { url: url.clone(), format: None }),
+            CssValue::Function(name, args) if name.eq_ignore_ascii_case("format") => {
+                if let (Some(source), Some(CssValue::String(format) | CssValue::Keyword(format))) =
+                    (sources.last_mut(), args.first())
+                {
+                    source.format = Some(format.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cascade;
+    use gugalanna_css::Stylesheet;
+
+    #[test]
+    fn test_web_font_faces() {
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse(
+                "@font-face { font-family: 'Web Sans'; font-weight: bold; font-style: italic; \
+                 src: url(web.woff2) format('woff2'), url('web.ttf') format('truetype'), local(Web); } \
+                 @font-face { font-family: Nameless; } \
+                 p { font-family: 'Web Sans', sans-serif; }",
+            )
+            .unwrap(),
+        );
+
+        // The face without sources is skipped
+        let faces = cascade.web_font_faces();
+        assert_eq!(faces.len(), 1);
+        let face = &faces[0];
+        assert_eq!((face.family.as_str(), face.weight, face.style), ("Web Sans", 700, FontStyle::Italic));
+
+        // WOFF2 isn't supported, the TrueType source after it is
+        let supported: Vec<_> = face.sources.iter().filter(|s| s.is_supported()).map(|s| s.url.as_str()).collect();
+        assert_eq!(face.sources.len(), 2);
+        assert_eq!(supported, vec!["web.ttf"]);
+    }
+}
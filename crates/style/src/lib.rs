@@ -4,58 +4,100 @@
 
 pub mod matching;
 pub mod cascade;
+pub mod fontface;
 pub mod properties;
+pub mod query;
 pub mod resolver;
+pub mod serialize;
 pub mod styletree;
+pub mod transform;
 
 use gugalanna_css::Color;
+use serde::{Deserialize, Serialize};
 
 pub use matching::{matches_selector, matches_selector_with_context, MatchingContext};
 pub use cascade::{Cascade, Origin, MatchedDeclaration, default_ua_stylesheet};
+pub use fontface::{WebFontFace, WebFontSource};
 pub use properties::{Inheritance, is_inherited, get_inheritance};
+pub use query::SelectorQuery;
 pub use resolver::{ResolveContext, StyleResolver};
 pub use styletree::StyleTree;
+pub use transform::{interpolate_transform, Transform, TransformFunction};
 
 /// Computed style for an element
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ComputedStyle {
     // Display
     pub display: Display,
 
     // Box model
     pub width: Option<f32>,
-    pub height: Option<f32>,
-    pub margin_top: f32,
-    pub margin_right: f32,
-    pub margin_bottom: f32,
-    pub margin_left: f32,
-    pub padding_top: f32,
-    pub padding_right: f32,
-    pub padding_bottom: f32,
-    pub padding_left: f32,
+    pub height: Option<LengthPercentage>,
+    /// Margins, `None` when auto
+    pub margin_top: Option<LengthPercentage>,
+    pub margin_right: Option<LengthPercentage>,
+    pub margin_bottom: Option<LengthPercentage>,
+    pub margin_left: Option<LengthPercentage>,
+    pub padding_top: LengthPercentage,
+    pub padding_right: LengthPercentage,
+    pub padding_bottom: LengthPercentage,
+    pub padding_left: LengthPercentage,
     pub border_top_width: f32,
     pub border_right_width: f32,
     pub border_bottom_width: f32,
     pub border_left_width: f32,
+    pub border_top_style: BorderStyle,
+    pub border_right_style: BorderStyle,
+    pub border_bottom_style: BorderStyle,
+    pub border_left_style: BorderStyle,
+
+    // Tables
+    pub border_spacing_horizontal: f32,
+    pub border_spacing_vertical: f32,
 
     // Colors and background
     pub color: Color,
     pub background: Background,
-    pub border_color: Color,
+    /// URL of the image painted over the background, None for none
+    pub background_image: Option<String>,
+    pub background_repeat: BackgroundRepeat,
+    /// Where the background image sits within the padding box
+    pub background_position: ObjectPosition,
+    pub background_size: BackgroundSize,
+    pub border_top_color: Color,
+    pub border_right_color: Color,
+    pub border_bottom_color: Color,
+    pub border_left_color: Color,
 
     // Text
     pub font_size: f32,
     pub font_family: String,
     pub font_weight: u16,
+    pub font_style: FontStyle,
     pub line_height: f32,
+    /// Multiple of the font size for unitless and `normal` line heights,
+    /// inherited as a factor rather than as the computed length
+    pub line_height_factor: Option<f32>,
     pub text_align: TextAlign,
+    /// Indent of the first line of a block, percentages of its width
+    pub text_indent: LengthPercentage,
+    pub direction: Direction,
+    pub vertical_align: VerticalAlign,
+    pub white_space: WhiteSpace,
+    /// Lines drawn with the text of this box, not inherited but painted
+    /// across the text of its in-flow descendants
+    pub text_decoration_line: TextDecorationLine,
 
     // Position
     pub position: Position,
-    pub top: Option<f32>,
-    pub right: Option<f32>,
-    pub bottom: Option<f32>,
-    pub left: Option<f32>,
+    pub top: Option<LengthPercentage>,
+    pub right: Option<LengthPercentage>,
+    pub bottom: Option<LengthPercentage>,
+    pub left: Option<LengthPercentage>,
+
+    // Floats
+    pub float: Float,
+    pub clear: Clear,
 
     // Stacking and overflow
     pub z_index: i32,
@@ -65,13 +107,22 @@ pub struct ComputedStyle {
 
     // Visual effects
     pub opacity: f32,
+    /// Functions of the `transform` property, empty for none
+    pub transform: Vec<TransformFunction>,
     pub box_shadow: Option<BoxShadow>,
     pub border_radius: BorderRadius,
 
+    // Replaced content
+    pub object_fit: ObjectFit,
+    pub object_position: ObjectPosition,
+    pub image_rendering: ImageRendering,
+
     // Flex container properties
     pub flex_direction: FlexDirection,
+    pub flex_wrap: FlexWrap,
     pub justify_content: JustifyContent,
     pub align_items: AlignItems,
+    pub align_content: AlignContent,
 
     // Flex item properties
     pub flex_grow: f32,
@@ -80,6 +131,16 @@ pub struct ComputedStyle {
     pub align_self: AlignSelf,
     pub order: i32,
 
+    // Grid container properties
+    pub grid_template_columns: Vec<TrackSize>,
+    pub grid_template_rows: Vec<TrackSize>,
+    pub row_gap: f32,
+    pub column_gap: f32,
+
+    // Grid item properties (1-based start lines)
+    pub grid_column_start: Option<usize>,  // None = auto
+    pub grid_row_start: Option<usize>,     // None = auto
+
     // Transitions
     pub transitions: Vec<TransitionDef>,
 }
@@ -92,6 +153,12 @@ pub enum Display {
     Inline,
     InlineBlock,
     Flex,
+    Grid,
+    Table,
+    TableRowGroup,
+    TableRow,
+    TableCell,
+    TableCaption,
 }
 
 /// Position property values
@@ -103,15 +170,152 @@ pub enum Position {
     Fixed,
 }
 
+/// A length that may be relative to the containing block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LengthPercentage {
+    /// Absolute length in pixels
+    Px(f32),
+    /// Fraction of the containing block size (0.5 = 50%)
+    Percent(f32),
+}
+
+impl LengthPercentage {
+    /// Resolve to pixels against the containing block size
+    pub fn resolve(&self, basis: f32) -> f32 {
+        match self {
+            LengthPercentage::Px(px) => *px,
+            LengthPercentage::Percent(fraction) => fraction * basis,
+        }
+    }
+}
+
+/// Float property values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Float {
+    #[default]
+    None,
+    Left,
+    Right,
+}
+
+/// Clear property values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Clear {
+    #[default]
+    None,
+    Left,
+    Right,
+    Both,
+}
+
+/// Slant of the font
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
 /// Text alignment
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TextAlign {
+    /// Start edge of the line: left in ltr, right in rtl
+    Start,
+    /// End edge of the line: right in ltr, left in rtl
+    End,
     Left,
     Right,
     Center,
     Justify,
 }
 
+impl TextAlign {
+    /// Resolve start and end to a physical side for the given direction
+    pub fn physical(self, direction: Direction) -> TextAlign {
+        match (self, direction) {
+            (TextAlign::Start, Direction::Ltr) | (TextAlign::End, Direction::Rtl) => TextAlign::Left,
+            (TextAlign::Start, Direction::Rtl) | (TextAlign::End, Direction::Ltr) => TextAlign::Right,
+            (align, _) => align,
+        }
+    }
+}
+
+/// Inline base direction of a box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+/// Vertical alignment of an inline-level box within its line box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlign {
+    #[default]
+    Baseline,
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// How whitespace inside text is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhiteSpace {
+    /// Collapse whitespace, wrap at spaces
+    #[default]
+    Normal,
+    /// Collapse whitespace, never wrap
+    NoWrap,
+    /// Preserve whitespace, break only at newlines
+    Pre,
+    /// Preserve whitespace, also wrap at spaces
+    PreWrap,
+    /// Collapse spaces but keep newlines, wrap at spaces
+    PreLine,
+}
+
+impl WhiteSpace {
+    /// Check if runs of spaces collapse into one
+    pub fn collapses_spaces(&self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::NoWrap | WhiteSpace::PreLine)
+    }
+
+    /// Check if newlines are kept as forced line breaks
+    pub fn preserves_newlines(&self) -> bool {
+        matches!(self, WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+    }
+
+    /// Check if lines may wrap at the container edge
+    pub fn wraps(&self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+    }
+}
+
+/// Lines drawn over, under or through text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextDecorationLine {
+    pub underline: bool,
+    pub overline: bool,
+    pub line_through: bool,
+}
+
+impl TextDecorationLine {
+    /// Check if no lines are drawn
+    pub fn is_none(&self) -> bool {
+        !(self.underline || self.overline || self.line_through)
+    }
+
+    /// Lines of both decorations, for text decorated by several boxes
+    pub fn union(self, other: TextDecorationLine) -> TextDecorationLine {
+        TextDecorationLine {
+            underline: self.underline || other.underline,
+            overline: self.overline || other.overline,
+            line_through: self.line_through || other.line_through,
+        }
+    }
+}
+
 /// Overflow property values
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Overflow {
@@ -132,6 +336,15 @@ pub enum FlexDirection {
     ColumnReverse,
 }
 
+/// Flex wrap property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlexWrap {
+    #[default]
+    NoWrap,
+    Wrap,
+    WrapReverse,
+}
+
 /// Justify content (main axis alignment)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum JustifyContent {
@@ -155,6 +368,19 @@ pub enum AlignItems {
     Baseline,
 }
 
+/// Align content (cross axis distribution of flex lines)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlignContent {
+    FlexStart,
+    FlexEnd,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+    #[default]
+    Stretch,
+}
+
 /// Align self (per-item cross axis override)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum AlignSelf {
@@ -167,6 +393,17 @@ pub enum AlignSelf {
     Baseline,
 }
 
+/// Size of a grid track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackSize {
+    /// Fixed length or percentage of the grid container
+    Length(LengthPercentage),
+    /// Share of the space left by the other tracks (fr)
+    Flex(f32),
+    /// Sized to fit the items in the track
+    Auto,
+}
+
 /// Easing function for CSS transitions
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum TimingFunction {
@@ -180,7 +417,7 @@ pub enum TimingFunction {
 }
 
 /// A single transition definition
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct TransitionDef {
     /// Property to transition ("all" or specific property name)
     pub property: String,
@@ -193,7 +430,7 @@ pub struct TransitionDef {
 }
 
 /// Box shadow effect
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct BoxShadow {
     pub offset_x: f32,
     pub offset_y: f32,
@@ -203,8 +440,70 @@ pub struct BoxShadow {
     pub inset: bool,
 }
 
+/// How replaced content is sized within its content box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjectFit {
+    /// Stretched to fill the box
+    #[default]
+    Fill,
+    /// Scaled to fit inside the box, keeping its aspect ratio
+    Contain,
+    /// Scaled to cover the whole box, keeping its aspect ratio
+    Cover,
+    /// Kept at its natural size
+    None,
+    /// The smaller of none and contain
+    ScaleDown,
+}
+
+/// How images are filtered when they are scaled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ImageRendering {
+    /// Smoothly interpolated
+    #[default]
+    Auto,
+    /// Kept sharp, without blending colors
+    CrispEdges,
+    /// Scaled up as blocks of pixels
+    Pixelated,
+}
+
+/// Where replaced content sits within its content box
+///
+/// Percentages are of the space left over once the content is sized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectPosition {
+    pub x: LengthPercentage,
+    pub y: LengthPercentage,
+}
+
+impl Default for ObjectPosition {
+    fn default() -> Self {
+        Self {
+            x: LengthPercentage::Percent(0.5),
+            y: LengthPercentage::Percent(0.5),
+        }
+    }
+}
+
+/// How the line of a border side is drawn
+///
+/// Sides given a width without a style are drawn solid. Groove, ridge,
+/// inset and outset borders are drawn solid too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BorderStyle {
+    /// No border, its width is zero (`hidden` too)
+    None,
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+    /// Two lines with a gap between them
+    Double,
+}
+
 /// Border radius for rounded corners
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct BorderRadius {
     pub top_left: f32,
     pub top_right: f32,
@@ -223,14 +522,14 @@ impl BorderRadius {
 }
 
 /// A color stop in a gradient
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ColorStop {
     pub color: Color,
     pub position: Option<f32>, // 0.0 to 1.0, None = auto-distribute
 }
 
 /// Linear gradient direction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum GradientDirection {
     Angle(f32),         // Degrees (0 = to top, 90 = to right)
     ToTop,
@@ -250,7 +549,7 @@ impl Default for GradientDirection {
 }
 
 /// Radial gradient shape
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RadialShape {
     #[default]
     Ellipse,
@@ -258,7 +557,7 @@ pub enum RadialShape {
 }
 
 /// Radial gradient size
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RadialSize {
     #[default]
     FarthestCorner,
@@ -268,7 +567,7 @@ pub enum RadialSize {
 }
 
 /// A CSS gradient
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Gradient {
     Linear {
         direction: GradientDirection,
@@ -284,7 +583,7 @@ pub enum Gradient {
 }
 
 /// Background can be a solid color or gradient
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Background {
     Color(Color),
     Gradient(Gradient),
@@ -296,49 +595,126 @@ impl Default for Background {
     }
 }
 
+/// Axes a background image is repeated along
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BackgroundRepeat {
+    #[default]
+    Repeat,
+    RepeatX,
+    RepeatY,
+    NoRepeat,
+}
+
+impl BackgroundRepeat {
+    /// Check if tiles are repeated across the box
+    pub fn repeats_x(&self) -> bool {
+        matches!(self, BackgroundRepeat::Repeat | BackgroundRepeat::RepeatX)
+    }
+
+    /// Check if tiles are repeated down the box
+    pub fn repeats_y(&self) -> bool {
+        matches!(self, BackgroundRepeat::Repeat | BackgroundRepeat::RepeatY)
+    }
+}
+
+/// Size a background image is drawn at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundSize {
+    /// Scaled to cover the whole box, keeping its aspect ratio
+    Cover,
+    /// Scaled to fit inside the box, keeping its aspect ratio
+    Contain,
+    /// Width and height, percentages of the box, None for auto
+    Size(Option<LengthPercentage>, Option<LengthPercentage>),
+}
+
+impl Default for BackgroundSize {
+    fn default() -> Self {
+        BackgroundSize::Size(None, None)
+    }
+}
+
+impl ComputedStyle {
+    /// Font families to draw text with, in order of preference
+    pub fn font_families(&self) -> impl Iterator<Item = &str> {
+        self.font_family.split(',').map(str::trim).filter(|f| !f.is_empty())
+    }
+}
+
 impl Default for ComputedStyle {
     fn default() -> Self {
         Self {
             display: Display::Inline,
             width: None,
             height: None,
-            margin_top: 0.0,
-            margin_right: 0.0,
-            margin_bottom: 0.0,
-            margin_left: 0.0,
-            padding_top: 0.0,
-            padding_right: 0.0,
-            padding_bottom: 0.0,
-            padding_left: 0.0,
+            margin_top: Some(LengthPercentage::Px(0.0)),
+            margin_right: Some(LengthPercentage::Px(0.0)),
+            margin_bottom: Some(LengthPercentage::Px(0.0)),
+            margin_left: Some(LengthPercentage::Px(0.0)),
+            padding_top: LengthPercentage::Px(0.0),
+            padding_right: LengthPercentage::Px(0.0),
+            padding_bottom: LengthPercentage::Px(0.0),
+            padding_left: LengthPercentage::Px(0.0),
             border_top_width: 0.0,
             border_right_width: 0.0,
             border_bottom_width: 0.0,
             border_left_width: 0.0,
+            border_top_style: BorderStyle::Solid,
+            border_right_style: BorderStyle::Solid,
+            border_bottom_style: BorderStyle::Solid,
+            border_left_style: BorderStyle::Solid,
+            border_spacing_horizontal: 0.0,
+            border_spacing_vertical: 0.0,
             color: Color::black(),
             background: Background::default(),
-            border_color: Color::black(),
+            background_image: None,
+            background_repeat: BackgroundRepeat::Repeat,
+            background_position: ObjectPosition {
+                x: LengthPercentage::Percent(0.0),
+                y: LengthPercentage::Percent(0.0),
+            },
+            background_size: BackgroundSize::default(),
+            border_top_color: Color::black(),
+            border_right_color: Color::black(),
+            border_bottom_color: Color::black(),
+            border_left_color: Color::black(),
             font_size: 16.0,
             font_family: String::from("sans-serif"),
             font_weight: 400,
+            font_style: FontStyle::Normal,
             line_height: 19.2, // 16.0 * 1.2
-            text_align: TextAlign::Left,
+            line_height_factor: Some(1.2),
+            text_align: TextAlign::Start,
+            text_indent: LengthPercentage::Px(0.0),
+            direction: Direction::Ltr,
+            vertical_align: VerticalAlign::Baseline,
+            white_space: WhiteSpace::Normal,
+            text_decoration_line: TextDecorationLine::default(),
             position: Position::Static,
             top: None,
             right: None,
             bottom: None,
             left: None,
+            float: Float::None,
+            clear: Clear::None,
             z_index: 0,
             overflow: Overflow::Visible,
             overflow_x: Overflow::Visible,
             overflow_y: Overflow::Visible,
             opacity: 1.0,
+            transform: Vec::new(),
             box_shadow: None,
             border_radius: BorderRadius::default(),
+            object_fit: ObjectFit::Fill,
+            object_position: ObjectPosition::default(),
+            image_rendering: ImageRendering::Auto,
 
             // Flex container defaults
             flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::NoWrap,
             justify_content: JustifyContent::FlexStart,
             align_items: AlignItems::Stretch,
+            align_content: AlignContent::Stretch,
 
             // Flex item defaults
             flex_grow: 0.0,
@@ -347,6 +723,14 @@ impl Default for ComputedStyle {
             align_self: AlignSelf::Auto,
             order: 0,
 
+            // Grid defaults
+            grid_template_columns: Vec::new(),
+            grid_template_rows: Vec::new(),
+            row_gap: 0.0,
+            column_gap: 0.0,
+            grid_column_start: None,
+            grid_row_start: None,
+
             // Transition defaults
             transitions: Vec::new(),
         }
@@ -65,7 +65,10 @@ pub fn get_inheritance(property: &str) -> Option<Inheritance> {
         "word-spacing" |
         "cursor" |
         "direction" |
-        "quotes" => Some(Inheritance::Inherited),
+        "image-rendering" |
+        "quotes" |
+        "border-collapse" |
+        "border-spacing" => Some(Inheritance::Inherited),
 
         // Not inherited properties (box model, positioning, etc.)
         "display" |
@@ -134,6 +137,8 @@ pub fn get_inheritance(property: &str) -> Option<Inheritance> {
         "outline-width" |
         "outline-style" |
         "outline-color" |
+        "object-fit" |
+        "object-position" |
         "opacity" |
         "transform" |
         "transition" |
@@ -156,7 +161,9 @@ pub fn get_inheritance(property: &str) -> Option<Inheritance> {
         "grid-template-rows" |
         "grid-area" |
         "grid-column" |
+        "grid-column-start" |
         "grid-row" |
+        "grid-row-start" |
         "gap" |
         "row-gap" |
         "column-gap" => Some(Inheritance::NotInherited),
@@ -193,7 +200,10 @@ static PROPERTY_NAMES: &[&str] = &[
     "word-spacing",
     "cursor",
     "direction",
+    "image-rendering",
     "quotes",
+    "border-collapse",
+    "border-spacing",
     "display",
     "position",
     "top",
@@ -257,6 +267,8 @@ static PROPERTY_NAMES: &[&str] = &[
     "outline-width",
     "outline-style",
     "outline-color",
+    "object-fit",
+    "object-position",
     "opacity",
     "transform",
     "flex",
@@ -0,0 +1,76 @@
+//! Selector Queries
+//!
+//! querySelector() and querySelectorAll() over a DOM tree, with the
+//! selector engine used for styling.
+
+use gugalanna_css::{CssResult, Selector};
+use gugalanna_dom::{DomTree, NodeId};
+
+use crate::matching::matches_selector;
+
+/// Find elements by selectors
+///
+/// Elements are looked for among the descendants of `scope`, the rest of
+/// the selectors may match outside it, as with `element.querySelector()`.
+pub trait SelectorQuery {
+    /// Find the first element, in document order, matching any of the
+    /// comma-separated selectors
+    fn query_selector(&self, scope: NodeId, selectors: &str) -> CssResult<Option<NodeId>>;
+
+    /// Find all elements, in document order, matching any of the
+    /// comma-separated selectors
+    fn query_selector_all(&self, scope: NodeId, selectors: &str) -> CssResult<Vec<NodeId>>;
+}
+
+impl SelectorQuery for DomTree {
+    fn query_selector(&self, scope: NodeId, selectors: &str) -> CssResult<Option<NodeId>> {
+        let selectors = Selector::parse_list(selectors)?;
+        Ok(self.descendants(scope).into_iter().find(|&id| matches_any(self, id, &selectors)))
+    }
+
+    fn query_selector_all(&self, scope: NodeId, selectors: &str) -> CssResult<Vec<NodeId>> {
+        let selectors = Selector::parse_list(selectors)?;
+        Ok(self
+            .descendants(scope)
+            .into_iter()
+            .filter(|&id| matches_any(self, id, &selectors))
+            .collect())
+    }
+}
+
+fn matches_any(tree: &DomTree, id: NodeId, selectors: &[Selector]) -> bool {
+    tree.get(id).is_some_and(|n| n.as_element().is_some())
+        && selectors.iter().any(|selector| matches_selector(tree, id, selector))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gugalanna_dom::Queryable;
+    use gugalanna_html::HtmlParser;
+
+    #[test]
+    fn test_query_in_document_order() {
+        let tree = HtmlParser::new()
+            .parse(
+                "<nav id='top'><a id='home' class='active'>Home</a><a id='about'>About</a></nav>\
+                 <main><a id='more' class='active' href='/more'>More</a></main>",
+            )
+            .unwrap();
+        let id = |name: &str| tree.get_element_by_id(name).unwrap();
+        let document = tree.document_id();
+
+        assert_eq!(tree.query_selector(document, "nav a.active").unwrap(), Some(id("home")));
+        assert_eq!(
+            tree.query_selector_all(document, "a[href], #about").unwrap(),
+            vec![id("about"), id("more")]
+        );
+        assert_eq!(tree.query_selector_all(document, "a:not(.active)").unwrap(), vec![id("about")]);
+
+        // Only descendants of the scope are found, ancestors still match
+        assert_eq!(tree.query_selector_all(id("top"), "nav a").unwrap(), vec![id("home"), id("about")]);
+        assert_eq!(tree.query_selector(id("top"), "main a").unwrap(), None);
+
+        assert!(tree.query_selector(document, "a[").is_err());
+    }
+}
@@ -7,9 +7,11 @@ use gugalanna_css::{CssValue, Color, LengthUnit};
 
 use crate::properties::is_inherited;
 use crate::{
-    AlignItems, AlignSelf, Background, BorderRadius, BoxShadow, ColorStop, ComputedStyle,
-    Display, FlexDirection, Gradient, GradientDirection, JustifyContent, Overflow, Position,
-    RadialShape, RadialSize, TextAlign, TimingFunction, TransitionDef,
+    AlignContent, AlignItems, AlignSelf, Background, BackgroundRepeat, BackgroundSize, BorderRadius, BorderStyle, BoxShadow, Clear, ColorStop, ComputedStyle,
+    Direction, Display, FontStyle, FlexDirection, FlexWrap, Float, Gradient, GradientDirection, JustifyContent, LengthPercentage,
+    ImageRendering, ObjectFit, ObjectPosition, Overflow, Position,
+    RadialShape, RadialSize, TextAlign, TextDecorationLine, TimingFunction, TrackSize, Transform, TransformFunction,
+    TransitionDef, VerticalAlign, WhiteSpace,
 };
 
 /// Context for resolving styles
@@ -72,6 +74,9 @@ impl ResolveContext {
 /// Style value resolver
 pub struct StyleResolver;
 
+/// Largest repetition count honored in repeat()
+const MAX_TRACK_REPEAT: usize = 1000;
+
 impl StyleResolver {
     /// Resolve a CSS value to a computed length in pixels
     pub fn resolve_length(
@@ -104,6 +109,143 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve a CSS value to a length or a percentage of the containing block
+    pub fn resolve_length_percentage(
+        value: &CssValue,
+        context: &ResolveContext,
+    ) -> Option<LengthPercentage> {
+        match value {
+            CssValue::Percentage(p) => Some(LengthPercentage::Percent(p / 100.0)),
+            _ => Self::resolve_length(value, context).map(LengthPercentage::Px),
+        }
+    }
+
+    /// Resolve a margin to a length or percentage, `Some(None)` for auto
+    pub fn resolve_margin(
+        value: &CssValue,
+        context: &ResolveContext,
+    ) -> Option<Option<LengthPercentage>> {
+        match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("auto") => Some(None),
+            _ => Self::resolve_length_percentage(value, context).map(Some),
+        }
+    }
+
+    /// Resolve the margin shorthand to the top, right, bottom and left margins
+    ///
+    /// One to four values, with missing sides copied from the opposite one.
+    pub fn resolve_margin_shorthand(
+        value: &CssValue,
+        context: &ResolveContext,
+    ) -> Option<[Option<LengthPercentage>; 4]> {
+        let sides = match value {
+            CssValue::List(values) => values
+                .iter()
+                .map(|v| Self::resolve_margin(v, context))
+                .collect::<Option<Vec<_>>>()?,
+            _ => vec![Self::resolve_margin(value, context)?],
+        };
+        match sides[..] {
+            [all] => Some([all; 4]),
+            [vertical, horizontal] => Some([vertical, horizontal, vertical, horizontal]),
+            [top, horizontal, bottom] => Some([top, horizontal, bottom, horizontal]),
+            [top, right, bottom, left] => Some([top, right, bottom, left]),
+            _ => None,
+        }
+    }
+
+    /// Resolve a border width, keywords included
+    pub fn resolve_border_width(value: &CssValue, context: &ResolveContext) -> Option<f32> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "thin" => Some(1.0),
+                "medium" => Some(3.0),
+                "thick" => Some(5.0),
+                _ => Self::resolve_length(value, context),
+            },
+            _ => Self::resolve_length(value, context).filter(|w| *w >= 0.0),
+        }
+    }
+
+    /// Resolve a border style keyword
+    pub fn resolve_border_style(value: &CssValue) -> Option<BorderStyle> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "none" | "hidden" => Some(BorderStyle::None),
+                "solid" | "groove" | "ridge" | "inset" | "outset" => Some(BorderStyle::Solid),
+                "dashed" => Some(BorderStyle::Dashed),
+                "dotted" => Some(BorderStyle::Dotted),
+                "double" => Some(BorderStyle::Double),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolve border-style to the top, right, bottom and left styles
+    ///
+    /// One to four values, with missing sides copied from the opposite one.
+    pub fn resolve_border_style_shorthand(value: &CssValue) -> Option<[BorderStyle; 4]> {
+        let sides = match value {
+            CssValue::List(values) => values.iter().map(Self::resolve_border_style).collect::<Option<Vec<_>>>()?,
+            _ => vec![Self::resolve_border_style(value)?],
+        };
+        match sides[..] {
+            [all] => Some([all; 4]),
+            [vertical, horizontal] => Some([vertical, horizontal, vertical, horizontal]),
+            [top, horizontal, bottom] => Some([top, horizontal, bottom, horizontal]),
+            [top, right, bottom, left] => Some([top, right, bottom, left]),
+            _ => None,
+        }
+    }
+
+    /// Resolve border-color to the top, right, bottom and left colors
+    pub fn resolve_border_color_shorthand(value: &CssValue, context: &ResolveContext) -> Option<[Color; 4]> {
+        let sides = match value {
+            CssValue::List(values) => values.iter().map(|v| Self::resolve_color(v, context)).collect::<Option<Vec<_>>>()?,
+            _ => vec![Self::resolve_color(value, context)?],
+        };
+        match sides[..] {
+            [all] => Some([all; 4]),
+            [vertical, horizontal] => Some([vertical, horizontal, vertical, horizontal]),
+            [top, horizontal, bottom] => Some([top, horizontal, bottom, horizontal]),
+            [top, right, bottom, left] => Some([top, right, bottom, left]),
+            _ => None,
+        }
+    }
+
+    /// Resolve the border shorthand, or the shorthand of one side, to a
+    /// width, style and color
+    ///
+    /// Without a style (or with `none`/`hidden`) there is no border, so the
+    /// width is zero. The color is `None` when not given.
+    pub fn resolve_border_shorthand(
+        value: &CssValue,
+        context: &ResolveContext,
+    ) -> Option<(f32, BorderStyle, Option<Color>)> {
+        let parts = match value {
+            CssValue::List(values) => values.iter().collect(),
+            _ => vec![value],
+        };
+
+        let (mut width, mut style, mut color) = (None, BorderStyle::None, None);
+        for part in parts {
+            if let Some(s) = Self::resolve_border_style(part) {
+                style = s;
+            } else if let Some(w) = Self::resolve_border_width(part, context).filter(|_| width.is_none()) {
+                width = Some(w);
+            } else {
+                color = Some(Self::resolve_color(part, context)?);
+            }
+        }
+
+        let width = match style {
+            BorderStyle::None => 0.0,
+            _ => width.unwrap_or(3.0),
+        };
+        Some((width, style, color))
+    }
+
     /// Resolve a CSS color value
     pub fn resolve_color(
         value: &CssValue,
@@ -140,11 +282,16 @@ impl StyleResolver {
                 "inline" => Some(Display::Inline),
                 "inline-block" => Some(Display::InlineBlock),
                 "flex" => Some(Display::Flex),
+                "grid" => Some(Display::Grid),
                 "list-item" => Some(Display::Block), // Simplified
-                "table" | "table-row" | "table-cell" |
+                "table" | "inline-table" => Some(Display::Table),
                 "table-row-group" | "table-header-group" |
-                "table-footer-group" | "table-column" |
-                "table-column-group" | "table-caption" => Some(Display::Block), // Simplified
+                "table-footer-group" => Some(Display::TableRowGroup),
+                "table-row" => Some(Display::TableRow),
+                "table-cell" => Some(Display::TableCell),
+                "table-caption" => Some(Display::TableCaption),
+                // Columns only carry styling we don't support, no boxes
+                "table-column" | "table-column-group" => Some(Display::None),
                 _ => None,
             },
             _ => None,
@@ -165,10 +312,56 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve float value
+    pub fn resolve_float(value: &CssValue) -> Option<Float> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "none" => Some(Float::None),
+                "left" => Some(Float::Left),
+                "right" => Some(Float::Right),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolve the logical float values, inline-start and inline-end,
+    /// to a physical side for the given direction
+    pub fn resolve_logical_float(value: &CssValue, direction: Direction) -> Option<Float> {
+        let start = match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "inline-start" => true,
+                "inline-end" => false,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match (start, direction) {
+            (true, Direction::Ltr) | (false, Direction::Rtl) => Some(Float::Left),
+            (true, Direction::Rtl) | (false, Direction::Ltr) => Some(Float::Right),
+        }
+    }
+
+    /// Resolve clear value
+    pub fn resolve_clear(value: &CssValue) -> Option<Clear> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "none" => Some(Clear::None),
+                "left" => Some(Clear::Left),
+                "right" => Some(Clear::Right),
+                "both" => Some(Clear::Both),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Resolve text-align value
     pub fn resolve_text_align(value: &CssValue) -> Option<TextAlign> {
         match value {
             CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "start" => Some(TextAlign::Start),
+                "end" => Some(TextAlign::End),
                 "left" => Some(TextAlign::Left),
                 "right" => Some(TextAlign::Right),
                 "center" => Some(TextAlign::Center),
@@ -179,6 +372,72 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve vertical-align value
+    pub fn resolve_vertical_align(value: &CssValue) -> Option<VerticalAlign> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "baseline" => Some(VerticalAlign::Baseline),
+                "top" | "text-top" => Some(VerticalAlign::Top),
+                "middle" => Some(VerticalAlign::Middle),
+                "bottom" | "text-bottom" => Some(VerticalAlign::Bottom),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolve direction value
+    pub fn resolve_direction(value: &CssValue) -> Option<Direction> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "ltr" => Some(Direction::Ltr),
+                "rtl" => Some(Direction::Rtl),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolve white-space value
+    pub fn resolve_white_space(value: &CssValue) -> Option<WhiteSpace> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "normal" => Some(WhiteSpace::Normal),
+                "nowrap" => Some(WhiteSpace::NoWrap),
+                "pre" => Some(WhiteSpace::Pre),
+                "pre-wrap" | "break-spaces" => Some(WhiteSpace::PreWrap),
+                "pre-line" => Some(WhiteSpace::PreLine),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolve text-decoration-line, or the lines of the text-decoration
+    /// shorthand, whose style and color values are skipped
+    pub fn resolve_text_decoration_line(value: &CssValue) -> Option<TextDecorationLine> {
+        let values = match value {
+            CssValue::List(values) => values.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+
+        let mut lines = TextDecorationLine::default();
+        let mut valid = false;
+        for value in values {
+            if let CssValue::Keyword(k) = value {
+                match k.to_ascii_lowercase().as_str() {
+                    "none" => {}
+                    "underline" => lines.underline = true,
+                    "overline" => lines.overline = true,
+                    "line-through" => lines.line_through = true,
+                    _ => continue,
+                }
+                valid = true;
+            }
+        }
+        valid.then_some(lines)
+    }
+
     /// Resolve z-index value
     pub fn resolve_z_index(value: &CssValue) -> Option<i32> {
         match value {
@@ -216,6 +475,19 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve flex-wrap value
+    pub fn resolve_flex_wrap(value: &CssValue) -> Option<FlexWrap> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "nowrap" => Some(FlexWrap::NoWrap),
+                "wrap" => Some(FlexWrap::Wrap),
+                "wrap-reverse" => Some(FlexWrap::WrapReverse),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Resolve justify-content value
     pub fn resolve_justify_content(value: &CssValue) -> Option<JustifyContent> {
         match value {
@@ -247,6 +519,23 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve align-content value
+    pub fn resolve_align_content(value: &CssValue) -> Option<AlignContent> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "flex-start" | "start" => Some(AlignContent::FlexStart),
+                "flex-end" | "end" => Some(AlignContent::FlexEnd),
+                "center" => Some(AlignContent::Center),
+                "space-between" => Some(AlignContent::SpaceBetween),
+                "space-around" => Some(AlignContent::SpaceAround),
+                "space-evenly" => Some(AlignContent::SpaceEvenly),
+                "stretch" | "normal" => Some(AlignContent::Stretch),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Resolve align-self value
     pub fn resolve_align_self(value: &CssValue) -> Option<AlignSelf> {
         match value {
@@ -302,6 +591,85 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve a grid-template-columns/rows track list, expanding repeat()
+    pub fn resolve_track_list(value: &CssValue, context: &ResolveContext) -> Option<Vec<TrackSize>> {
+        match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("none") => Some(Vec::new()),
+            CssValue::List(values) => {
+                let mut tracks = Vec::new();
+                for value in values {
+                    tracks.extend(Self::resolve_tracks(value, context)?);
+                }
+                Some(tracks)
+            }
+            _ => Self::resolve_tracks(value, context),
+        }
+    }
+
+    /// Resolve a single track size or a repeat() of them
+    fn resolve_tracks(value: &CssValue, context: &ResolveContext) -> Option<Vec<TrackSize>> {
+        match value {
+            CssValue::Function(name, args) if name.eq_ignore_ascii_case("repeat") => {
+                let (count, tracks) = args.split_first()?;
+                let count = match count {
+                    // Keep absurd repetitions from allocating huge grids
+                    CssValue::Number(n) if *n >= 1.0 && n.fract() == 0.0 => (*n as usize).min(MAX_TRACK_REPEAT),
+                    _ => return None,
+                };
+                let tracks = tracks
+                    .iter()
+                    .map(|t| Self::resolve_track_size(t, context))
+                    .collect::<Option<Vec<_>>>()?;
+                if tracks.is_empty() {
+                    return None;
+                }
+                Some(tracks.repeat(count))
+            }
+            _ => Self::resolve_track_size(value, context).map(|t| vec![t]),
+        }
+    }
+
+    /// Resolve a single grid track size
+    pub fn resolve_track_size(value: &CssValue, context: &ResolveContext) -> Option<TrackSize> {
+        match value {
+            CssValue::Flex(fr) if *fr >= 0.0 => Some(TrackSize::Flex(*fr)),
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("auto") => Some(TrackSize::Auto),
+            _ => Self::resolve_length_percentage(value, context).map(TrackSize::Length),
+        }
+    }
+
+    /// Resolve gap value into (row gap, column gap)
+    pub fn resolve_gap(value: &CssValue, context: &ResolveContext) -> Option<(f32, f32)> {
+        match value {
+            CssValue::List(values) if values.len() == 2 => Some((
+                Self::resolve_gap_length(&values[0], context)?,
+                Self::resolve_gap_length(&values[1], context)?,
+            )),
+            _ => Self::resolve_gap_length(value, context).map(|g| (g, g)),
+        }
+    }
+
+    /// Resolve a single row-gap or column-gap value
+    pub fn resolve_gap_length(value: &CssValue, context: &ResolveContext) -> Option<f32> {
+        match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("normal") => Some(0.0),
+            _ => Self::resolve_length(value, context).filter(|g| *g >= 0.0),
+        }
+    }
+
+    /// Resolve a grid-column/grid-row placement to its 1-based start line
+    ///
+    /// Only numeric lines are supported; the end line of `<start> / <end>`
+    /// is ignored, so items occupy a single track.
+    pub fn resolve_grid_line(value: &CssValue) -> Option<Option<usize>> {
+        match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("auto") => Some(None),
+            CssValue::Number(n) if *n >= 1.0 && n.fract() == 0.0 => Some(Some(*n as usize)),
+            CssValue::List(values) => values.first().and_then(Self::resolve_grid_line),
+            _ => None,
+        }
+    }
+
     /// Resolve a time value to milliseconds
     pub fn resolve_time_ms(value: &CssValue) -> Option<f32> {
         match value {
@@ -429,6 +797,60 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve a transform value to its functions, empty for `none`
+    ///
+    /// Values with a function that isn't understood are invalid as a whole.
+    pub fn resolve_transform(value: &CssValue, context: &ResolveContext) -> Option<Vec<TransformFunction>> {
+        match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("none") => Some(Vec::new()),
+            CssValue::Function(name, args) => Some(vec![Self::resolve_transform_function(name, args, context)?]),
+            CssValue::List(values) => values
+                .iter()
+                .map(|value| match value {
+                    CssValue::Function(name, args) => Self::resolve_transform_function(name, args, context),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// Resolve one function of a transform value
+    fn resolve_transform_function(
+        name: &str,
+        args: &[CssValue],
+        context: &ResolveContext,
+    ) -> Option<TransformFunction> {
+        let length = |value: &CssValue| Self::resolve_length_percentage(value, context);
+        let number = |value: &CssValue| match value {
+            CssValue::Number(n) => Some(*n),
+            CssValue::Percentage(p) => Some(p / 100.0),
+            _ => None,
+        };
+        let zero = LengthPercentage::Px(0.0);
+        match (name.to_ascii_lowercase().as_str(), args) {
+            ("translate", [x]) => Some(TransformFunction::Translate(length(x)?, zero)),
+            ("translate", [x, y]) => Some(TransformFunction::Translate(length(x)?, length(y)?)),
+            ("translatex", [x]) => Some(TransformFunction::Translate(length(x)?, zero)),
+            ("translatey", [y]) => Some(TransformFunction::Translate(zero, length(y)?)),
+            ("scale", [s]) => number(s).map(|s| TransformFunction::Scale(s, s)),
+            ("scale", [x, y]) => Some(TransformFunction::Scale(number(x)?, number(y)?)),
+            ("scalex", [x]) => Some(TransformFunction::Scale(number(x)?, 1.0)),
+            ("scaley", [y]) => Some(TransformFunction::Scale(1.0, number(y)?)),
+            ("rotate", [angle]) => match angle {
+                CssValue::Angle(degrees) => Some(TransformFunction::Rotate(*degrees)),
+                // Only zero may be written without a unit
+                CssValue::Number(n) if *n == 0.0 => Some(TransformFunction::Rotate(0.0)),
+                _ => None,
+            },
+            ("matrix", [a, b, c, d, e, f]) => {
+                let [a, b, c, d, e, f] = [number(a)?, number(b)?, number(c)?, number(d)?, number(e)?, number(f)?];
+                Some(TransformFunction::Matrix(Transform { a, b, c, d, e, f }))
+            }
+            _ => None,
+        }
+    }
+
     /// Resolve box-shadow value
     /// Format: [inset] offset-x offset-y [blur-radius] [spread-radius] [color]
     pub fn resolve_box_shadow(value: &CssValue, context: &ResolveContext) -> Option<BoxShadow> {
@@ -540,6 +962,86 @@ impl StyleResolver {
         Some(radius)
     }
 
+    /// Resolve border-spacing value into (horizontal, vertical)
+    pub fn resolve_border_spacing(value: &CssValue, context: &ResolveContext) -> Option<(f32, f32)> {
+        match value {
+            CssValue::List(values) if values.len() == 2 => Some((
+                Self::resolve_length(&values[0], context)?,
+                Self::resolve_length(&values[1], context)?,
+            )),
+            _ => Self::resolve_length(value, context).map(|s| (s, s)),
+        }
+    }
+
+    /// Resolve object-fit value
+    pub fn resolve_object_fit(value: &CssValue) -> Option<ObjectFit> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "fill" => Some(ObjectFit::Fill),
+                "contain" => Some(ObjectFit::Contain),
+                "cover" => Some(ObjectFit::Cover),
+                "none" => Some(ObjectFit::None),
+                "scale-down" => Some(ObjectFit::ScaleDown),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolve image-rendering value
+    pub fn resolve_image_rendering(value: &CssValue) -> Option<ImageRendering> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "auto" | "smooth" | "high-quality" => Some(ImageRendering::Auto),
+                "crisp-edges" | "-moz-crisp-edges" => Some(ImageRendering::CrispEdges),
+                "pixelated" => Some(ImageRendering::Pixelated),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolve object-position: one or two keywords, lengths or percentages
+    ///
+    /// A single value positions horizontally and centers vertically; a
+    /// vertical keyword may come first.
+    pub fn resolve_object_position(value: &CssValue, context: &ResolveContext) -> Option<ObjectPosition> {
+        let center = LengthPercentage::Percent(0.5);
+        let component = |value: &CssValue| -> Option<(LengthPercentage, Option<bool>)> {
+            let keyword = |fraction, horizontal| Some((LengthPercentage::Percent(fraction), horizontal));
+            match value {
+                CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                    "left" => keyword(0.0, Some(true)),
+                    "right" => keyword(1.0, Some(true)),
+                    "top" => keyword(0.0, Some(false)),
+                    "bottom" => keyword(1.0, Some(false)),
+                    "center" => keyword(0.5, None),
+                    _ => None,
+                },
+                _ => Self::resolve_length_percentage(value, context).map(|v| (v, None)),
+            }
+        };
+
+        match value {
+            CssValue::List(values) if values.len() == 2 => {
+                let (first, first_axis) = component(&values[0])?;
+                let (second, second_axis) = component(&values[1])?;
+                match (first_axis, second_axis) {
+                    (Some(false), Some(false)) | (Some(true), Some(true)) => None,
+                    (Some(false), _) | (_, Some(true)) => Some(ObjectPosition { x: second, y: first }),
+                    _ => Some(ObjectPosition { x: first, y: second }),
+                }
+            }
+            _ => {
+                let (position, axis) = component(value)?;
+                Some(match axis {
+                    Some(false) => ObjectPosition { x: center, y: position },
+                    _ => ObjectPosition { x: position, y: center },
+                })
+            }
+        }
+    }
+
     /// Resolve a background value (color or gradient)
     pub fn resolve_background(value: &CssValue, context: &ResolveContext) -> Option<Background> {
         // Try as gradient first
@@ -555,6 +1057,68 @@ impl StyleResolver {
         None
     }
 
+    /// Resolve a background-image value: Some(None) for none, the URL of
+    /// an image otherwise
+    pub fn resolve_background_image(value: &CssValue) -> Option<Option<String>> {
+        match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("none") => Some(None),
+            CssValue::Url(url) => Some(Some(url.clone())),
+            CssValue::Function(name, args) if name.eq_ignore_ascii_case("url") => match args.as_slice() {
+                [CssValue::String(url)] => Some(Some(url.clone())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolve background-repeat: one keyword, or one per axis
+    ///
+    /// `space` and `round` repeat as `repeat` does.
+    pub fn resolve_background_repeat(value: &CssValue) -> Option<BackgroundRepeat> {
+        let repeats = |value: &CssValue| match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "repeat" | "space" | "round" => Some(true),
+                "no-repeat" => Some(false),
+                _ => None,
+            },
+            _ => None,
+        };
+        let (x, y) = match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("repeat-x") => (true, false),
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("repeat-y") => (false, true),
+            CssValue::List(values) if values.len() == 2 => (repeats(&values[0])?, repeats(&values[1])?),
+            _ => {
+                let both = repeats(value)?;
+                (both, both)
+            }
+        };
+        Some(match (x, y) {
+            (true, true) => BackgroundRepeat::Repeat,
+            (true, false) => BackgroundRepeat::RepeatX,
+            (false, true) => BackgroundRepeat::RepeatY,
+            (false, false) => BackgroundRepeat::NoRepeat,
+        })
+    }
+
+    /// Resolve background-size: cover, contain, or a width and height
+    /// that are each auto, a length or a percentage
+    ///
+    /// A single value sizes the width, the height is auto.
+    pub fn resolve_background_size(value: &CssValue, context: &ResolveContext) -> Option<BackgroundSize> {
+        let component = |value: &CssValue| match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("auto") => Some(None),
+            _ => Self::resolve_length_percentage(value, context).map(Some),
+        };
+        match value {
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("cover") => Some(BackgroundSize::Cover),
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("contain") => Some(BackgroundSize::Contain),
+            CssValue::List(values) if values.len() == 2 => {
+                Some(BackgroundSize::Size(component(&values[0])?, component(&values[1])?))
+            }
+            _ => Some(BackgroundSize::Size(component(value)?, None)),
+        }
+    }
+
     /// Resolve a gradient from a CssValue::Function
     pub fn resolve_gradient(value: &CssValue, context: &ResolveContext) -> Option<Gradient> {
         match value {
@@ -699,6 +1263,7 @@ impl StyleResolver {
                 // Bare number treated as degrees
                 Some(GradientDirection::Angle(*n))
             }
+            CssValue::Angle(degrees) => Some(GradientDirection::Angle(*degrees)),
             _ => None,
         }
     }
@@ -777,6 +1342,43 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve font-family value to its families separated by commas
+    ///
+    /// Each name in a list is taken as a family, unquoted names of several
+    /// words aren't joined.
+    pub fn resolve_font_family(value: &CssValue) -> Option<String> {
+        let values = match value {
+            CssValue::List(values) | CssValue::CommaSeparated(values) => values.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+        let families: Vec<&str> = values
+            .iter()
+            .filter_map(|v| match v {
+                CssValue::Keyword(name) | CssValue::String(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        (!families.is_empty()).then(|| families.join(", "))
+    }
+
+    /// Resolve font-style value
+    pub fn resolve_font_style(value: &CssValue) -> Option<FontStyle> {
+        match value {
+            CssValue::Keyword(k) => match k.to_ascii_lowercase().as_str() {
+                "normal" => Some(FontStyle::Normal),
+                "italic" => Some(FontStyle::Italic),
+                "oblique" => Some(FontStyle::Oblique),
+                _ => None,
+            },
+            // An oblique angle
+            CssValue::List(values) => match values.first() {
+                Some(CssValue::Keyword(k)) if k.eq_ignore_ascii_case("oblique") => Some(FontStyle::Oblique),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     /// Resolve font-size value (returns pixels)
     pub fn resolve_font_size(
         value: &CssValue,
@@ -853,6 +1455,15 @@ impl StyleResolver {
         }
     }
 
+    /// Resolve a unitless or `normal` line-height to its font size factor
+    pub fn resolve_line_height_factor(value: &CssValue) -> Option<f32> {
+        match value {
+            CssValue::Number(n) if *n >= 0.0 => Some(*n),
+            CssValue::Keyword(k) if k.eq_ignore_ascii_case("normal") => Some(1.2),
+            _ => None,
+        }
+    }
+
     /// Get the inherited value for a property
     pub fn get_inherited_value(
         property: &str,
@@ -864,10 +1475,23 @@ impl StyleResolver {
             "color" => Some(CssValue::Color(parent.color)),
             "font-size" => Some(CssValue::Length(parent.font_size, LengthUnit::Px)),
             "font-weight" => Some(CssValue::Number(parent.font_weight as f32)),
-            "line-height" => Some(CssValue::Length(parent.line_height, LengthUnit::Px)),
+            "font-style" => {
+                let value = match parent.font_style {
+                    FontStyle::Normal => "normal",
+                    FontStyle::Italic => "italic",
+                    FontStyle::Oblique => "oblique",
+                };
+                Some(CssValue::Keyword(value.to_string()))
+            }
+            "line-height" => match parent.line_height_factor {
+                Some(factor) => Some(CssValue::Number(factor)),
+                None => Some(CssValue::Length(parent.line_height, LengthUnit::Px)),
+            },
             "font-family" => Some(CssValue::Keyword(parent.font_family.clone())),
             "text-align" => {
                 let value = match parent.text_align {
+                    TextAlign::Start => "start",
+                    TextAlign::End => "end",
                     TextAlign::Left => "left",
                     TextAlign::Right => "right",
                     TextAlign::Center => "center",
@@ -875,6 +1499,39 @@ impl StyleResolver {
                 };
                 Some(CssValue::Keyword(value.to_string()))
             }
+            "text-indent" => Some(match parent.text_indent {
+                LengthPercentage::Px(px) => CssValue::Length(px, LengthUnit::Px),
+                LengthPercentage::Percent(fraction) => CssValue::Percentage(fraction * 100.0),
+            }),
+            "direction" => {
+                let value = match parent.direction {
+                    Direction::Ltr => "ltr",
+                    Direction::Rtl => "rtl",
+                };
+                Some(CssValue::Keyword(value.to_string()))
+            }
+            "border-spacing" => Some(CssValue::List(vec![
+                CssValue::Length(parent.border_spacing_horizontal, LengthUnit::Px),
+                CssValue::Length(parent.border_spacing_vertical, LengthUnit::Px),
+            ])),
+            "white-space" => {
+                let value = match parent.white_space {
+                    WhiteSpace::Normal => "normal",
+                    WhiteSpace::NoWrap => "nowrap",
+                    WhiteSpace::Pre => "pre",
+                    WhiteSpace::PreWrap => "pre-wrap",
+                    WhiteSpace::PreLine => "pre-line",
+                };
+                Some(CssValue::Keyword(value.to_string()))
+            }
+            "image-rendering" => {
+                let value = match parent.image_rendering {
+                    ImageRendering::Auto => "auto",
+                    ImageRendering::CrispEdges => "crisp-edges",
+                    ImageRendering::Pixelated => "pixelated",
+                };
+                Some(CssValue::Keyword(value.to_string()))
+            }
             _ => None,
         }
     }
@@ -975,6 +1632,246 @@ mod tests {
             StyleResolver::resolve_display(&CssValue::Keyword("none".to_string())),
             Some(Display::None)
         );
+        assert_eq!(
+            StyleResolver::resolve_display(&CssValue::Keyword("table-header-group".to_string())),
+            Some(Display::TableRowGroup)
+        );
+    }
+
+    #[test]
+    fn test_resolve_border_spacing() {
+        let ctx = ResolveContext::default();
+        let one = CssValue::Length(2.0, LengthUnit::Px);
+        assert_eq!(StyleResolver::resolve_border_spacing(&one, &ctx), Some((2.0, 2.0)));
+
+        let two = CssValue::List(vec![CssValue::Length(4.0, LengthUnit::Px), CssValue::Number(0.0)]);
+        assert_eq!(StyleResolver::resolve_border_spacing(&two, &ctx), Some((4.0, 0.0)));
+    }
+
+    #[test]
+    fn test_resolve_length_percentage() {
+        let ctx = ResolveContext::default();
+        assert_eq!(
+            StyleResolver::resolve_length_percentage(&CssValue::Percentage(25.0), &ctx),
+            Some(LengthPercentage::Percent(0.25))
+        );
+        assert_eq!(
+            StyleResolver::resolve_length_percentage(&CssValue::Length(-4.0, LengthUnit::Px), &ctx),
+            Some(LengthPercentage::Px(-4.0))
+        );
+        assert_eq!(
+            StyleResolver::resolve_length_percentage(&CssValue::Keyword("auto".to_string()), &ctx),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_margin_shorthand() {
+        let ctx = ResolveContext::default();
+        let auto = || CssValue::Keyword("auto".to_string());
+        let px = |n| Some(LengthPercentage::Px(n));
+
+        let centered = CssValue::List(vec![CssValue::Number(0.0), auto()]);
+        assert_eq!(
+            StyleResolver::resolve_margin_shorthand(&centered, &ctx),
+            Some([px(0.0), None, px(0.0), None])
+        );
+
+        let three = CssValue::List(vec![
+            CssValue::Length(1.0, LengthUnit::Px),
+            CssValue::Percentage(10.0),
+            CssValue::Length(3.0, LengthUnit::Px),
+        ]);
+        assert_eq!(
+            StyleResolver::resolve_margin_shorthand(&three, &ctx),
+            Some([px(1.0), Some(LengthPercentage::Percent(0.1)), px(3.0), Some(LengthPercentage::Percent(0.1))])
+        );
+
+        assert_eq!(StyleResolver::resolve_margin_shorthand(&auto(), &ctx), Some([None; 4]));
+        assert_eq!(StyleResolver::resolve_margin_shorthand(&CssValue::Keyword("wide".to_string()), &ctx), None);
+    }
+
+    #[test]
+    fn test_resolve_border_shorthand() {
+        let ctx = ResolveContext::default();
+        let keyword = |k: &str| CssValue::Keyword(k.to_string());
+
+        let rule = CssValue::List(vec![CssValue::Length(1.0, LengthUnit::Px), keyword("dashed"), keyword("gray")]);
+        assert_eq!(
+            StyleResolver::resolve_border_shorthand(&rule, &ctx),
+            Some((1.0, BorderStyle::Dashed, Color::from_name("gray")))
+        );
+
+        // The width defaults to medium, no style means no border
+        assert_eq!(
+            StyleResolver::resolve_border_shorthand(&keyword("solid"), &ctx),
+            Some((3.0, BorderStyle::Solid, None))
+        );
+        assert_eq!(
+            StyleResolver::resolve_border_shorthand(&keyword("none"), &ctx),
+            Some((0.0, BorderStyle::None, None))
+        );
+        let unstyled = CssValue::List(vec![keyword("thick"), keyword("red")]);
+        assert_eq!(
+            StyleResolver::resolve_border_shorthand(&unstyled, &ctx),
+            Some((0.0, BorderStyle::None, Color::from_name("red")))
+        );
+
+        assert_eq!(StyleResolver::resolve_border_shorthand(&keyword("wavy"), &ctx), None);
+    }
+
+    #[test]
+    fn test_resolve_border_style_shorthand() {
+        let keyword = |k: &str| CssValue::Keyword(k.to_string());
+        let styles = CssValue::List(vec![keyword("dotted"), keyword("double")]);
+        assert_eq!(
+            StyleResolver::resolve_border_style_shorthand(&styles),
+            Some([BorderStyle::Dotted, BorderStyle::Double, BorderStyle::Dotted, BorderStyle::Double])
+        );
+        assert_eq!(StyleResolver::resolve_border_style_shorthand(&keyword("hidden")), Some([BorderStyle::None; 4]));
+        assert_eq!(StyleResolver::resolve_border_style_shorthand(&keyword("ridge")), Some([BorderStyle::Solid; 4]));
+        assert_eq!(StyleResolver::resolve_border_style_shorthand(&keyword("wavy")), None);
+    }
+
+    #[test]
+    fn test_resolve_border_color_shorthand() {
+        let ctx = ResolveContext::default();
+        let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+        let colors = CssValue::List(vec![CssValue::Color(red), CssValue::Keyword("blue".to_string())]);
+        assert_eq!(StyleResolver::resolve_border_color_shorthand(&colors, &ctx), Some([red, blue, red, blue]));
+        assert_eq!(StyleResolver::resolve_border_color_shorthand(&CssValue::Color(red), &ctx), Some([red; 4]));
+        assert_eq!(StyleResolver::resolve_border_color_shorthand(&CssValue::Number(1.0), &ctx), None);
+    }
+
+    #[test]
+    fn test_resolve_track_list() {
+        let ctx = ResolveContext::default();
+        let value = CssValue::List(vec![
+            CssValue::Length(100.0, LengthUnit::Px),
+            CssValue::Function("repeat".to_string(), vec![CssValue::Number(2.0), CssValue::Flex(1.0)]),
+            CssValue::Keyword("auto".to_string()),
+            CssValue::Percentage(10.0),
+        ]);
+        assert_eq!(
+            StyleResolver::resolve_track_list(&value, &ctx),
+            Some(vec![
+                TrackSize::Length(LengthPercentage::Px(100.0)),
+                TrackSize::Flex(1.0),
+                TrackSize::Flex(1.0),
+                TrackSize::Auto,
+                TrackSize::Length(LengthPercentage::Percent(0.1)),
+            ])
+        );
+
+        let bad_repeat = CssValue::Function("repeat".to_string(), vec![CssValue::Number(0.0), CssValue::Flex(1.0)]);
+        assert_eq!(StyleResolver::resolve_track_list(&bad_repeat, &ctx), None);
+    }
+
+    #[test]
+    fn test_resolve_gap_and_grid_line() {
+        let ctx = ResolveContext::default();
+        let two = CssValue::List(vec![CssValue::Length(4.0, LengthUnit::Px), CssValue::Length(8.0, LengthUnit::Px)]);
+        assert_eq!(StyleResolver::resolve_gap(&two, &ctx), Some((4.0, 8.0)));
+
+        assert_eq!(StyleResolver::resolve_grid_line(&CssValue::Number(2.0)), Some(Some(2)));
+        assert_eq!(
+            StyleResolver::resolve_grid_line(&CssValue::List(vec![CssValue::Number(2.0), CssValue::Number(3.0)])),
+            Some(Some(2))
+        );
+        assert_eq!(StyleResolver::resolve_grid_line(&CssValue::Keyword("auto".to_string())), Some(None));
+        assert_eq!(StyleResolver::resolve_grid_line(&CssValue::Number(-1.0)), None);
+    }
+
+    #[test]
+    fn test_resolve_float_and_clear() {
+        assert_eq!(
+            StyleResolver::resolve_float(&CssValue::Keyword("right".to_string())),
+            Some(Float::Right)
+        );
+        assert_eq!(
+            StyleResolver::resolve_clear(&CssValue::Keyword("both".to_string())),
+            Some(Clear::Both)
+        );
+        assert_eq!(StyleResolver::resolve_float(&CssValue::Keyword("center".to_string())), None);
+    }
+
+    #[test]
+    fn test_resolve_white_space() {
+        assert_eq!(
+            StyleResolver::resolve_white_space(&CssValue::Keyword("pre-wrap".to_string())),
+            Some(WhiteSpace::PreWrap)
+        );
+        assert_eq!(
+            StyleResolver::resolve_white_space(&CssValue::Keyword("nowrap".to_string())),
+            Some(WhiteSpace::NoWrap)
+        );
+        assert!(WhiteSpace::Pre.preserves_newlines() && !WhiteSpace::Pre.wraps());
+        assert!(WhiteSpace::PreLine.collapses_spaces());
+    }
+
+    #[test]
+    fn test_resolve_text_decoration_line() {
+        let keyword = |k: &str| CssValue::Keyword(k.to_string());
+        let lines = StyleResolver::resolve_text_decoration_line(&CssValue::List(vec![
+            keyword("underline"),
+            keyword("line-through"),
+        ]))
+        .unwrap();
+        assert!(lines.underline && lines.line_through && !lines.overline);
+
+        // The shorthand's style and color are skipped
+        let shorthand = CssValue::List(vec![keyword("overline"), keyword("dotted"), CssValue::Color(Color::rgb(255, 0, 0))]);
+        assert_eq!(
+            StyleResolver::resolve_text_decoration_line(&shorthand),
+            Some(TextDecorationLine { overline: true, ..TextDecorationLine::default() })
+        );
+        assert!(StyleResolver::resolve_text_decoration_line(&keyword("none")).unwrap().is_none());
+        assert_eq!(StyleResolver::resolve_text_decoration_line(&keyword("wavy")), None);
+    }
+
+    #[test]
+    fn test_resolve_object_position() {
+        let ctx = ResolveContext::default();
+        let keyword = |k: &str| CssValue::Keyword(k.to_string());
+
+        // Vertical keyword first, swapped into place
+        let value = CssValue::List(vec![keyword("bottom"), keyword("left")]);
+        assert_eq!(
+            StyleResolver::resolve_object_position(&value, &ctx),
+            Some(ObjectPosition { x: LengthPercentage::Percent(0.0), y: LengthPercentage::Percent(1.0) })
+        );
+
+        // A single value centers the other axis
+        let value = CssValue::Length(10.0, LengthUnit::Px);
+        assert_eq!(
+            StyleResolver::resolve_object_position(&value, &ctx),
+            Some(ObjectPosition { x: LengthPercentage::Px(10.0), y: LengthPercentage::Percent(0.5) })
+        );
+
+        let value = CssValue::List(vec![keyword("left"), keyword("right")]);
+        assert_eq!(StyleResolver::resolve_object_position(&value, &ctx), None);
+        assert_eq!(StyleResolver::resolve_object_fit(&keyword("scale-down")), Some(ObjectFit::ScaleDown));
+    }
+
+    #[test]
+    fn test_resolve_background_image_properties() {
+        let ctx = ResolveContext::default();
+        let keyword = |k: &str| CssValue::Keyword(k.to_string());
+
+        let quoted = CssValue::Function("url".to_string(), vec![CssValue::String("tile.png".to_string())]);
+        assert_eq!(StyleResolver::resolve_background_image(&quoted), Some(Some("tile.png".to_string())));
+        assert_eq!(StyleResolver::resolve_background_image(&keyword("none")), Some(None));
+
+        let value = CssValue::List(vec![keyword("repeat"), keyword("no-repeat")]);
+        assert_eq!(StyleResolver::resolve_background_repeat(&value), Some(BackgroundRepeat::RepeatX));
+        assert_eq!(StyleResolver::resolve_background_repeat(&keyword("repeat-y")), Some(BackgroundRepeat::RepeatY));
+
+        let value = CssValue::List(vec![keyword("auto"), CssValue::Percentage(50.0)]);
+        assert_eq!(
+            StyleResolver::resolve_background_size(&value, &ctx),
+            Some(BackgroundSize::Size(None, Some(LengthPercentage::Percent(0.5))))
+        );
+        assert_eq!(StyleResolver::resolve_background_size(&keyword("cover"), &ctx), Some(BackgroundSize::Cover));
     }
 
     #[test]
@@ -989,6 +1886,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resolve_font_family() {
+        let families = CssValue::List(vec![
+            CssValue::String("Web Sans".to_string()),
+            CssValue::Keyword("serif".to_string()),
+        ]);
+        let family = StyleResolver::resolve_font_family(&families).unwrap();
+        assert_eq!(family, "Web Sans, serif");
+
+        let style = ComputedStyle { font_family: family, ..ComputedStyle::default() };
+        assert_eq!(style.font_families().collect::<Vec<_>>(), vec!["Web Sans", "serif"]);
+    }
+
+    #[test]
+    fn test_resolve_font_style() {
+        assert_eq!(
+            StyleResolver::resolve_font_style(&CssValue::Keyword("italic".to_string())),
+            Some(FontStyle::Italic)
+        );
+        let angle = CssValue::List(vec![CssValue::Keyword("oblique".to_string()), CssValue::Number(10.0)]);
+        assert_eq!(StyleResolver::resolve_font_style(&angle), Some(FontStyle::Oblique));
+        assert_eq!(StyleResolver::resolve_font_style(&CssValue::Keyword("slanted".to_string())), None);
+    }
+
     #[test]
     fn test_resolve_font_size() {
         let ctx = ResolveContext::default();
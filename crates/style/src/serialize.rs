@@ -0,0 +1,238 @@
+//! Computed Value Serialization
+//!
+//! Computed values as CSS text, the way getComputedStyle() reports them:
+//! lengths in pixels, colors as `rgb()` or `rgba()`.
+
+use gugalanna_css::Color;
+
+use crate::{
+    Background, BorderStyle, Clear, ComputedStyle, Direction, Display, Float, FontStyle, LengthPercentage, Overflow,
+    Position, TextAlign, VerticalAlign, WhiteSpace,
+};
+
+impl ComputedStyle {
+    /// Value of a property as CSS text, None for properties that are not
+    /// computed
+    pub fn property_value(&self, property: &str) -> Option<String> {
+        let value = match property {
+            "display" => display(self.display).to_string(),
+            "position" => position(self.position).to_string(),
+            "float" => float(self.float).to_string(),
+            "clear" => clear(self.clear).to_string(),
+            "width" => self.width.map_or("auto".to_string(), px),
+            "height" => self.height.map_or("auto".to_string(), length),
+            "margin-top" => auto_length(self.margin_top),
+            "margin-right" => auto_length(self.margin_right),
+            "margin-bottom" => auto_length(self.margin_bottom),
+            "margin-left" => auto_length(self.margin_left),
+            "padding-top" => length(self.padding_top),
+            "padding-right" => length(self.padding_right),
+            "padding-bottom" => length(self.padding_bottom),
+            "padding-left" => length(self.padding_left),
+            "top" => auto_length(self.top),
+            "right" => auto_length(self.right),
+            "bottom" => auto_length(self.bottom),
+            "left" => auto_length(self.left),
+            "border-top-width" => px(self.border_top_width),
+            "border-right-width" => px(self.border_right_width),
+            "border-bottom-width" => px(self.border_bottom_width),
+            "border-left-width" => px(self.border_left_width),
+            "border-top-style" => border_style(self.border_top_style).to_string(),
+            "border-right-style" => border_style(self.border_right_style).to_string(),
+            "border-bottom-style" => border_style(self.border_bottom_style).to_string(),
+            "border-left-style" => border_style(self.border_left_style).to_string(),
+            "border-top-color" => color(self.border_top_color),
+            "border-right-color" => color(self.border_right_color),
+            "border-bottom-color" => color(self.border_bottom_color),
+            "border-left-color" => color(self.border_left_color),
+            "color" => color(self.color),
+            "background-color" => match &self.background {
+                Background::Color(c) => color(*c),
+                Background::Gradient(_) => color(Color::transparent()),
+            },
+            "font-size" => px(self.font_size),
+            "font-family" => self.font_family.clone(),
+            "font-weight" => self.font_weight.to_string(),
+            "font-style" => font_style(self.font_style).to_string(),
+            "line-height" => px(self.line_height),
+            "text-align" => text_align(self.text_align).to_string(),
+            "direction" => direction(self.direction).to_string(),
+            "vertical-align" => vertical_align(self.vertical_align).to_string(),
+            "white-space" => white_space(self.white_space).to_string(),
+            "overflow-x" => overflow(self.overflow_x).to_string(),
+            "overflow-y" => overflow(self.overflow_y).to_string(),
+            "overflow" => overflow(self.overflow).to_string(),
+            "z-index" => self.z_index.to_string(),
+            "opacity" => self.opacity.to_string(),
+            "flex-grow" => self.flex_grow.to_string(),
+            "flex-shrink" => self.flex_shrink.to_string(),
+            "order" => self.order.to_string(),
+            "row-gap" => px(self.row_gap),
+            "column-gap" => px(self.column_gap),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// A color as `rgb()`, or `rgba()` when it is not opaque
+fn color(c: Color) -> String {
+    if c.a == 255 {
+        return format!("rgb({}, {}, {})", c.r, c.g, c.b);
+    }
+    // The shortest alpha that gives back the same byte
+    let alpha = [100.0, 1000.0]
+        .iter()
+        .map(|scale| (c.a as f32 / 255.0 * scale).round() / scale)
+        .find(|alpha| (alpha * 255.0).round() as u8 == c.a)
+        .unwrap_or(c.a as f32 / 255.0);
+    format!("rgba({}, {}, {}, {})", c.r, c.g, c.b, alpha)
+}
+
+fn px(value: f32) -> String {
+    format!("{}px", value)
+}
+
+fn length(value: LengthPercentage) -> String {
+    match value {
+        LengthPercentage::Px(value) => px(value),
+        LengthPercentage::Percent(fraction) => format!("{}%", fraction * 100.0),
+    }
+}
+
+fn auto_length(value: Option<LengthPercentage>) -> String {
+    value.map_or("auto".to_string(), length)
+}
+
+fn display(value: Display) -> &'static str {
+    match value {
+        Display::None => "none",
+        Display::Block => "block",
+        Display::Inline => "inline",
+        Display::InlineBlock => "inline-block",
+        Display::Flex => "flex",
+        Display::Grid => "grid",
+        Display::Table => "table",
+        Display::TableRowGroup => "table-row-group",
+        Display::TableRow => "table-row",
+        Display::TableCell => "table-cell",
+        Display::TableCaption => "table-caption",
+    }
+}
+
+fn position(value: Position) -> &'static str {
+    match value {
+        Position::Static => "static",
+        Position::Relative => "relative",
+        Position::Absolute => "absolute",
+        Position::Fixed => "fixed",
+    }
+}
+
+fn float(value: Float) -> &'static str {
+    match value {
+        Float::None => "none",
+        Float::Left => "left",
+        Float::Right => "right",
+    }
+}
+
+fn clear(value: Clear) -> &'static str {
+    match value {
+        Clear::None => "none",
+        Clear::Left => "left",
+        Clear::Right => "right",
+        Clear::Both => "both",
+    }
+}
+
+fn border_style(value: BorderStyle) -> &'static str {
+    match value {
+        BorderStyle::None => "none",
+        BorderStyle::Solid => "solid",
+        BorderStyle::Dashed => "dashed",
+        BorderStyle::Dotted => "dotted",
+        BorderStyle::Double => "double",
+    }
+}
+
+fn font_style(value: FontStyle) -> &'static str {
+    match value {
+        FontStyle::Normal => "normal",
+        FontStyle::Italic => "italic",
+        FontStyle::Oblique => "oblique",
+    }
+}
+
+fn text_align(value: TextAlign) -> &'static str {
+    match value {
+        TextAlign::Start => "start",
+        TextAlign::End => "end",
+        TextAlign::Left => "left",
+        TextAlign::Right => "right",
+        TextAlign::Center => "center",
+        TextAlign::Justify => "justify",
+    }
+}
+
+fn direction(value: Direction) -> &'static str {
+    match value {
+        Direction::Ltr => "ltr",
+        Direction::Rtl => "rtl",
+    }
+}
+
+fn vertical_align(value: VerticalAlign) -> &'static str {
+    match value {
+        VerticalAlign::Baseline => "baseline",
+        VerticalAlign::Top => "top",
+        VerticalAlign::Middle => "middle",
+        VerticalAlign::Bottom => "bottom",
+    }
+}
+
+fn white_space(value: WhiteSpace) -> &'static str {
+    match value {
+        WhiteSpace::Normal => "normal",
+        WhiteSpace::NoWrap => "nowrap",
+        WhiteSpace::Pre => "pre",
+        WhiteSpace::PreWrap => "pre-wrap",
+        WhiteSpace::PreLine => "pre-line",
+    }
+}
+
+fn overflow(value: Overflow) -> &'static str {
+    match value {
+        Overflow::Visible => "visible",
+        Overflow::Hidden => "hidden",
+        Overflow::Scroll => "scroll",
+        Overflow::Auto => "auto",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_property_values() {
+        let style = ComputedStyle {
+            display: Display::InlineBlock,
+            width: Some(120.5),
+            margin_left: None,
+            padding_top: LengthPercentage::Percent(0.25),
+            color: Color::rgb(255, 0, 0),
+            background: Background::Color(Color::rgba(0, 0, 0, 128)),
+            ..ComputedStyle::default()
+        };
+        assert_eq!(style.property_value("display").as_deref(), Some("inline-block"));
+        assert_eq!(style.property_value("width").as_deref(), Some("120.5px"));
+        assert_eq!(style.property_value("height").as_deref(), Some("auto"));
+        assert_eq!(style.property_value("margin-left").as_deref(), Some("auto"));
+        assert_eq!(style.property_value("padding-top").as_deref(), Some("25%"));
+        assert_eq!(style.property_value("color").as_deref(), Some("rgb(255, 0, 0)"));
+        assert_eq!(style.property_value("background-color").as_deref(), Some("rgba(0, 0, 0, 0.5)"));
+        assert_eq!(style.property_value("font-size").as_deref(), Some("16px"));
+        assert_eq!(style.property_value("no-such-property"), None);
+    }
+}
@@ -9,7 +9,7 @@ use gugalanna_dom::{DomTree, NodeId};
 
 use crate::cascade::Cascade;
 use crate::resolver::{ResolveContext, StyleResolver};
-use crate::{Background, ComputedStyle};
+use crate::{Background, BorderStyle, ComputedStyle};
 
 /// A tree of computed styles, parallel to the DOM tree
 pub struct StyleTree {
@@ -118,6 +118,21 @@ impl StyleTree {
             );
         }
 
+        // Border colors default to currentColor, the element's own color,
+        // which has to be known before any border color is applied
+        match property_values.get("color") {
+            Some(decl) => self.apply_property(&mut style, "color", &decl.value, context),
+            None => {
+                if let Some(parent) = &context.parent_style {
+                    style.color = parent.color;
+                }
+            }
+        }
+        style.border_top_color = style.color;
+        style.border_right_color = style.color;
+        style.border_bottom_color = style.color;
+        style.border_left_color = style.color;
+
         // Apply each property value
         for (property, decl) in &property_values {
             self.apply_property(&mut style, property, &decl.value, context);
@@ -128,6 +143,31 @@ impl StyleTree {
             self.apply_inheritance(&mut style, parent, &property_values);
         }
 
+        // Logical floats depend on the element's own direction
+        if let Some(decl) = property_values.get("float") {
+            if let Some(side) = StyleResolver::resolve_logical_float(&decl.value, style.direction) {
+                style.float = side;
+            }
+        }
+
+        // Sides without a border style have no border, whatever their width
+        let sides = [
+            (style.border_top_style, &mut style.border_top_width),
+            (style.border_right_style, &mut style.border_right_width),
+            (style.border_bottom_style, &mut style.border_bottom_width),
+            (style.border_left_style, &mut style.border_left_width),
+        ];
+        for (border_style, width) in sides {
+            if border_style == BorderStyle::None {
+                *width = 0.0;
+            }
+        }
+
+        // Unitless line heights scale with the element's own font size
+        if let Some(factor) = style.line_height_factor {
+            style.line_height = style.font_size * factor;
+        }
+
         style
     }
 
@@ -162,16 +202,28 @@ impl StyleTree {
 
             // Box positioning
             "top" => {
-                style.top = StyleResolver::resolve_length(&value, context);
+                style.top = StyleResolver::resolve_length_percentage(&value, context);
             }
             "right" => {
-                style.right = StyleResolver::resolve_length(&value, context);
+                style.right = StyleResolver::resolve_length_percentage(&value, context);
             }
             "bottom" => {
-                style.bottom = StyleResolver::resolve_length(&value, context);
+                style.bottom = StyleResolver::resolve_length_percentage(&value, context);
             }
             "left" => {
-                style.left = StyleResolver::resolve_length(&value, context);
+                style.left = StyleResolver::resolve_length_percentage(&value, context);
+            }
+
+            // Floats
+            "float" => {
+                if let Some(f) = StyleResolver::resolve_float(&value) {
+                    style.float = f;
+                }
+            }
+            "clear" => {
+                if let Some(c) = StyleResolver::resolve_clear(&value) {
+                    style.clear = c;
+                }
             }
 
             // Dimensions
@@ -179,75 +231,167 @@ impl StyleTree {
                 style.width = StyleResolver::resolve_length(&value, context);
             }
             "height" => {
-                style.height = StyleResolver::resolve_length(&value, context);
+                style.height = StyleResolver::resolve_length_percentage(&value, context);
             }
 
             // Margins
+            "margin" => {
+                if let Some([top, right, bottom, left]) = StyleResolver::resolve_margin_shorthand(&value, context) {
+                    style.margin_top = top;
+                    style.margin_right = right;
+                    style.margin_bottom = bottom;
+                    style.margin_left = left;
+                }
+            }
             "margin-top" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_margin(&value, context) {
                     style.margin_top = v;
                 }
             }
             "margin-right" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_margin(&value, context) {
                     style.margin_right = v;
                 }
             }
             "margin-bottom" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_margin(&value, context) {
                     style.margin_bottom = v;
                 }
             }
             "margin-left" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_margin(&value, context) {
                     style.margin_left = v;
                 }
             }
 
             // Padding
             "padding-top" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_length_percentage(&value, context) {
                     style.padding_top = v;
                 }
             }
             "padding-right" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_length_percentage(&value, context) {
                     style.padding_right = v;
                 }
             }
             "padding-bottom" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_length_percentage(&value, context) {
                     style.padding_bottom = v;
                 }
             }
             "padding-left" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_length_percentage(&value, context) {
                     style.padding_left = v;
                 }
             }
 
             // Border widths
+            "border" => {
+                if let Some((width, border_style, color)) = StyleResolver::resolve_border_shorthand(&value, context) {
+                    style.border_top_width = width;
+                    style.border_right_width = width;
+                    style.border_bottom_width = width;
+                    style.border_left_width = width;
+                    style.border_top_style = border_style;
+                    style.border_right_style = border_style;
+                    style.border_bottom_style = border_style;
+                    style.border_left_style = border_style;
+                    if let Some(color) = color {
+                        style.border_top_color = color;
+                        style.border_right_color = color;
+                        style.border_bottom_color = color;
+                        style.border_left_color = color;
+                    }
+                }
+            }
+            "border-top" => {
+                if let Some((width, border_style, color)) = StyleResolver::resolve_border_shorthand(&value, context) {
+                    style.border_top_width = width;
+                    style.border_top_style = border_style;
+                    if let Some(color) = color {
+                        style.border_top_color = color;
+                    }
+                }
+            }
+            "border-right" => {
+                if let Some((width, border_style, color)) = StyleResolver::resolve_border_shorthand(&value, context) {
+                    style.border_right_width = width;
+                    style.border_right_style = border_style;
+                    if let Some(color) = color {
+                        style.border_right_color = color;
+                    }
+                }
+            }
+            "border-bottom" => {
+                if let Some((width, border_style, color)) = StyleResolver::resolve_border_shorthand(&value, context) {
+                    style.border_bottom_width = width;
+                    style.border_bottom_style = border_style;
+                    if let Some(color) = color {
+                        style.border_bottom_color = color;
+                    }
+                }
+            }
+            "border-left" => {
+                if let Some((width, border_style, color)) = StyleResolver::resolve_border_shorthand(&value, context) {
+                    style.border_left_width = width;
+                    style.border_left_style = border_style;
+                    if let Some(color) = color {
+                        style.border_left_color = color;
+                    }
+                }
+            }
             "border-top-width" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_border_width(&value, context) {
                     style.border_top_width = v;
                 }
             }
             "border-right-width" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_border_width(&value, context) {
                     style.border_right_width = v;
                 }
             }
             "border-bottom-width" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_border_width(&value, context) {
                     style.border_bottom_width = v;
                 }
             }
             "border-left-width" => {
-                if let Some(v) = StyleResolver::resolve_length(&value, context) {
+                if let Some(v) = StyleResolver::resolve_border_width(&value, context) {
                     style.border_left_width = v;
                 }
             }
 
+            // Border styles
+            "border-style" => {
+                if let Some([top, right, bottom, left]) = StyleResolver::resolve_border_style_shorthand(&value) {
+                    style.border_top_style = top;
+                    style.border_right_style = right;
+                    style.border_bottom_style = bottom;
+                    style.border_left_style = left;
+                }
+            }
+            "border-top-style" => {
+                if let Some(s) = StyleResolver::resolve_border_style(&value) {
+                    style.border_top_style = s;
+                }
+            }
+            "border-right-style" => {
+                if let Some(s) = StyleResolver::resolve_border_style(&value) {
+                    style.border_right_style = s;
+                }
+            }
+            "border-bottom-style" => {
+                if let Some(s) = StyleResolver::resolve_border_style(&value) {
+                    style.border_bottom_style = s;
+                }
+            }
+            "border-left-style" => {
+                if let Some(s) = StyleResolver::resolve_border_style(&value) {
+                    style.border_left_style = s;
+                }
+            }
+
             // Colors
             "color" => {
                 if let Some(c) = StyleResolver::resolve_color(&value, context) {
@@ -260,20 +404,89 @@ impl StyleTree {
                 }
             }
             "background" => {
-                // Try to resolve as gradient first, then as color
-                if let Some(bg) = StyleResolver::resolve_background(&value, context) {
-                    style.background = bg;
+                // Only the color or gradient, image and repeat of the
+                // shorthand are read. Properties are applied in no
+                // particular order, so the ones left out aren't reset.
+                let components = match &value {
+                    CssValue::List(values) => values.as_slice(),
+                    value => std::slice::from_ref(value),
+                };
+                let mut background = None;
+                let mut image = None;
+                let mut repeat = None;
+                for component in components {
+                    if let Some(bg) = StyleResolver::resolve_background(component, context) {
+                        background = Some(bg);
+                    } else if let Some(url) = StyleResolver::resolve_background_image(component) {
+                        image = Some(url);
+                    } else if let Some(r) = StyleResolver::resolve_background_repeat(component) {
+                        repeat = Some(r);
+                    } else {
+                        background = None;
+                        image = None;
+                        break;
+                    }
+                }
+                if background.is_some() || image.is_some() {
+                    if let Some(bg) = background {
+                        style.background = bg;
+                    }
+                    if let Some(url) = image {
+                        style.background_image = url;
+                    }
+                    if let Some(r) = repeat {
+                        style.background_repeat = r;
+                    }
                 }
             }
             "background-image" => {
-                // Only gradients are supported, not URLs
                 if let Some(gradient) = StyleResolver::resolve_gradient(&value, context) {
                     style.background = Background::Gradient(gradient);
+                } else if let Some(url) = StyleResolver::resolve_background_image(&value) {
+                    style.background_image = url;
+                }
+            }
+            "background-repeat" => {
+                if let Some(r) = StyleResolver::resolve_background_repeat(&value) {
+                    style.background_repeat = r;
+                }
+            }
+            "background-position" => {
+                if let Some(p) = StyleResolver::resolve_object_position(&value, context) {
+                    style.background_position = p;
+                }
+            }
+            "background-size" => {
+                if let Some(s) = StyleResolver::resolve_background_size(&value, context) {
+                    style.background_size = s;
                 }
             }
             "border-color" => {
+                if let Some([top, right, bottom, left]) = StyleResolver::resolve_border_color_shorthand(&value, context) {
+                    style.border_top_color = top;
+                    style.border_right_color = right;
+                    style.border_bottom_color = bottom;
+                    style.border_left_color = left;
+                }
+            }
+            "border-top-color" => {
                 if let Some(c) = StyleResolver::resolve_color(&value, context) {
-                    style.border_color = c;
+                    style.border_top_color = c;
+                }
+            }
+            "border-right-color" => {
+                if let Some(c) = StyleResolver::resolve_color(&value, context) {
+                    style.border_right_color = c;
+                }
+            }
+            "border-bottom-color" => {
+                if let Some(c) = StyleResolver::resolve_color(&value, context) {
+                    style.border_bottom_color = c;
+                }
+            }
+            "border-left-color" => {
+                if let Some(c) = StyleResolver::resolve_color(&value, context) {
+                    style.border_left_color = c;
                 }
             }
 
@@ -288,16 +501,22 @@ impl StyleTree {
                     style.font_weight = w;
                 }
             }
+            "font-style" => {
+                if let Some(s) = StyleResolver::resolve_font_style(&value) {
+                    style.font_style = s;
+                }
+            }
             "font-family" => {
-                if let CssValue::Keyword(f) = &value {
-                    style.font_family = f.clone();
-                } else if let CssValue::String(f) = &value {
-                    style.font_family = f.clone();
+                if let Some(f) = StyleResolver::resolve_font_family(&value) {
+                    style.font_family = f;
                 }
             }
             "line-height" => {
-                if let Some(v) = StyleResolver::resolve_line_height(&value, context) {
+                if let Some(factor) = StyleResolver::resolve_line_height_factor(&value) {
+                    style.line_height_factor = Some(factor);
+                } else if let Some(v) = StyleResolver::resolve_line_height(&value, context) {
                     style.line_height = v;
+                    style.line_height_factor = None;
                 }
             }
             "text-align" => {
@@ -305,6 +524,33 @@ impl StyleTree {
                     style.text_align = a;
                 }
             }
+            "text-indent" => {
+                if let Some(v) = StyleResolver::resolve_length_percentage(&value, context) {
+                    style.text_indent = v;
+                }
+            }
+
+            "direction" => {
+                if let Some(d) = StyleResolver::resolve_direction(&value) {
+                    style.direction = d;
+                }
+            }
+
+            "vertical-align" => {
+                if let Some(v) = StyleResolver::resolve_vertical_align(&value) {
+                    style.vertical_align = v;
+                }
+            }
+            "white-space" => {
+                if let Some(w) = StyleResolver::resolve_white_space(&value) {
+                    style.white_space = w;
+                }
+            }
+            "text-decoration" | "text-decoration-line" => {
+                if let Some(lines) = StyleResolver::resolve_text_decoration_line(&value) {
+                    style.text_decoration_line = lines;
+                }
+            }
 
             // Stacking and overflow
             "z-index" => {
@@ -336,9 +582,35 @@ impl StyleTree {
                     style.opacity = o;
                 }
             }
+            "transform" => {
+                if let Some(functions) = StyleResolver::resolve_transform(&value, context) {
+                    style.transform = functions;
+                }
+            }
             "box-shadow" => {
                 style.box_shadow = StyleResolver::resolve_box_shadow(&value, context);
             }
+            "object-fit" => {
+                if let Some(f) = StyleResolver::resolve_object_fit(&value) {
+                    style.object_fit = f;
+                }
+            }
+            "image-rendering" => {
+                if let Some(r) = StyleResolver::resolve_image_rendering(&value) {
+                    style.image_rendering = r;
+                }
+            }
+            "object-position" => {
+                if let Some(p) = StyleResolver::resolve_object_position(&value, context) {
+                    style.object_position = p;
+                }
+            }
+            "border-spacing" => {
+                if let Some((h, v)) = StyleResolver::resolve_border_spacing(&value, context) {
+                    style.border_spacing_horizontal = h;
+                    style.border_spacing_vertical = v;
+                }
+            }
             "border-radius" => {
                 if let Some(r) = StyleResolver::resolve_border_radius(&value, context) {
                     style.border_radius = r;
@@ -371,6 +643,11 @@ impl StyleTree {
                     style.flex_direction = fd;
                 }
             }
+            "flex-wrap" => {
+                if let Some(fw) = StyleResolver::resolve_flex_wrap(&value) {
+                    style.flex_wrap = fw;
+                }
+            }
             "justify-content" => {
                 if let Some(jc) = StyleResolver::resolve_justify_content(&value) {
                     style.justify_content = jc;
@@ -381,6 +658,11 @@ impl StyleTree {
                     style.align_items = ai;
                 }
             }
+            "align-content" => {
+                if let Some(ac) = StyleResolver::resolve_align_content(&value) {
+                    style.align_content = ac;
+                }
+            }
 
             // Flexbox item properties
             "flex-grow" => {
@@ -409,6 +691,46 @@ impl StyleTree {
                 }
             }
 
+            // Grid container properties
+            "grid-template-columns" => {
+                if let Some(tracks) = StyleResolver::resolve_track_list(&value, context) {
+                    style.grid_template_columns = tracks;
+                }
+            }
+            "grid-template-rows" => {
+                if let Some(tracks) = StyleResolver::resolve_track_list(&value, context) {
+                    style.grid_template_rows = tracks;
+                }
+            }
+            "gap" | "grid-gap" => {
+                if let Some((row, column)) = StyleResolver::resolve_gap(&value, context) {
+                    style.row_gap = row;
+                    style.column_gap = column;
+                }
+            }
+            "row-gap" | "grid-row-gap" => {
+                if let Some(gap) = StyleResolver::resolve_gap_length(&value, context) {
+                    style.row_gap = gap;
+                }
+            }
+            "column-gap" | "grid-column-gap" => {
+                if let Some(gap) = StyleResolver::resolve_gap_length(&value, context) {
+                    style.column_gap = gap;
+                }
+            }
+
+            // Grid item properties
+            "grid-column" | "grid-column-start" => {
+                if let Some(line) = StyleResolver::resolve_grid_line(&value) {
+                    style.grid_column_start = line;
+                }
+            }
+            "grid-row" | "grid-row-start" => {
+                if let Some(line) = StyleResolver::resolve_grid_line(&value) {
+                    style.grid_row_start = line;
+                }
+            }
+
             // Transitions
             "transition" => {
                 if let Some(transitions) = StyleResolver::resolve_transition(&value) {
@@ -481,12 +803,32 @@ impl StyleTree {
         if !set_properties.contains_key("font-weight") {
             style.font_weight = parent.font_weight;
         }
+        if !set_properties.contains_key("font-style") {
+            style.font_style = parent.font_style;
+        }
         if !set_properties.contains_key("line-height") {
             style.line_height = parent.line_height;
+            style.line_height_factor = parent.line_height_factor;
         }
         if !set_properties.contains_key("text-align") {
             style.text_align = parent.text_align;
         }
+        if !set_properties.contains_key("text-indent") {
+            style.text_indent = parent.text_indent;
+        }
+        if !set_properties.contains_key("direction") {
+            style.direction = parent.direction;
+        }
+        if !set_properties.contains_key("border-spacing") {
+            style.border_spacing_horizontal = parent.border_spacing_horizontal;
+            style.border_spacing_vertical = parent.border_spacing_vertical;
+        }
+        if !set_properties.contains_key("white-space") {
+            style.white_space = parent.white_space;
+        }
+        if !set_properties.contains_key("image-rendering") {
+            style.image_rendering = parent.image_rendering;
+        }
     }
 }
 
@@ -499,7 +841,7 @@ impl Default for StyleTree {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Display;
+    use crate::{BackgroundRepeat, BackgroundSize, Color, Direction, Display, Float, ImageRendering, LengthPercentage, TextAlign, TransformFunction};
     use gugalanna_css::Stylesheet;
     use gugalanna_dom::Queryable;
     use gugalanna_html::HtmlParser;
@@ -563,11 +905,11 @@ mod tests {
 
         // Div should have the margin
         let div_style = style_tree.get_style(div_id).unwrap();
-        assert_eq!(div_style.margin_left, 50.0);
+        assert_eq!(div_style.margin_left, Some(LengthPercentage::Px(50.0)));
 
         // P should NOT inherit margin (margin is not inherited)
         let p_style = style_tree.get_style(p_id).unwrap();
-        assert_eq!(p_style.margin_left, 0.0);
+        assert_eq!(p_style.margin_left, Some(LengthPercentage::Px(0.0)));
     }
 
     #[test]
@@ -590,4 +932,208 @@ mod tests {
         let span_style = style_tree.get_style(span_id).unwrap();
         assert_eq!(span_style.font_size, 40.0);
     }
+
+    #[test]
+    fn test_line_height_inheritance() {
+        let tree = parse_html("<div><span>a</span></div><p><span>b</span></p>");
+        let spans = tree.get_elements_by_tag_name("span");
+
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse("div { line-height: 1.5; } p { line-height: 20px; } span { font-size: 32px; }").unwrap()
+        );
+
+        let style_tree = StyleTree::build(&tree, &cascade, 1024.0, 768.0);
+
+        // A unitless line height is inherited as a factor of the font size
+        assert_eq!(style_tree.get_style(spans[0]).unwrap().line_height, 48.0);
+        // A length is inherited as is
+        assert_eq!(style_tree.get_style(spans[1]).unwrap().line_height, 20.0);
+    }
+
+    #[test]
+    fn test_text_indent_inheritance() {
+        let tree = parse_html("<div><p>a</p></div><section><p>b</p></section>");
+        let ps = tree.get_elements_by_tag_name("p");
+
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse("div { text-indent: 2em; font-size: 16px; } section { text-indent: 10%; }").unwrap()
+        );
+
+        let style_tree = StyleTree::build(&tree, &cascade, 1024.0, 768.0);
+
+        // Lengths are inherited as computed, percentages stay relative to
+        // each block's own width
+        assert_eq!(style_tree.get_style(ps[0]).unwrap().text_indent, LengthPercentage::Px(32.0));
+        assert_eq!(style_tree.get_style(ps[1]).unwrap().text_indent, LengthPercentage::Percent(0.1));
+    }
+
+    #[test]
+    fn test_border_styles() {
+        let tree = parse_html("<div id='a'></div><div id='b'></div><div id='c'></div>");
+        let divs = tree.get_elements_by_tag_name("div");
+
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse(
+                "#a { border: 2px dashed red; } \
+                 #b { border-top-width: 4px; border-bottom-width: 4px; border-style: none dotted; } \
+                 #c { border-left-style: double; }",
+            )
+            .unwrap()
+        );
+
+        let style_tree = StyleTree::build(&tree, &cascade, 1024.0, 768.0);
+        let a = style_tree.get_style(divs[0]).unwrap();
+        assert_eq!((a.border_left_style, a.border_left_width), (BorderStyle::Dashed, 2.0));
+        let c = style_tree.get_style(divs[2]).unwrap();
+        assert_eq!((c.border_top_style, c.border_left_style), (BorderStyle::Solid, BorderStyle::Double));
+
+        // A side without a style has no width
+        let b = style_tree.get_style(divs[1]).unwrap();
+        assert_eq!((b.border_top_style, b.border_right_style), (BorderStyle::None, BorderStyle::Dotted));
+        assert_eq!((b.border_top_width, b.border_bottom_width), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_border_colors() {
+        let tree = parse_html("<div id='a'></div><div id='b'></div><div id='c'></div>");
+        let divs = tree.get_elements_by_tag_name("div");
+
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse(
+                "#a { border-color: red green blue; } \
+                 #b { color: purple; border-left: 4px solid #00aaff; border-top-color: yellow; } \
+                 #c { border-bottom: 1px dashed; }",
+            )
+            .unwrap()
+        );
+
+        let style_tree = StyleTree::build(&tree, &cascade, 1024.0, 768.0);
+        let a = style_tree.get_style(divs[0]).unwrap();
+        assert_eq!(
+            [a.border_top_color, a.border_right_color, a.border_bottom_color, a.border_left_color],
+            [Color::rgb(255, 0, 0), Color::rgb(0, 128, 0), Color::rgb(0, 0, 255), Color::rgb(0, 128, 0)]
+        );
+
+        // Sides without a color take the element's color
+        let b = style_tree.get_style(divs[1]).unwrap();
+        assert_eq!((b.border_left_width, b.border_left_color), (4.0, Color::rgb(0, 170, 255)));
+        assert_eq!(b.border_top_color, Color::rgb(255, 255, 0));
+        assert_eq!(b.border_right_color, Color::rgb(128, 0, 128));
+        let c = style_tree.get_style(divs[2]).unwrap();
+        assert_eq!((c.border_bottom_style, c.border_bottom_color), (BorderStyle::Dashed, c.color));
+    }
+
+    #[test]
+    fn test_transform() {
+        let tree = parse_html("<div id='a'><p></p></div><div id='b'></div><div id='c'></div>");
+        let divs = tree.get_elements_by_tag_name("div");
+        let p = tree.get_elements_by_tag_name("p")[0];
+
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse(
+                "#a { transform: translateY(-2px) scale(1.05); } \
+                 #b { transform: rotate(0.25turn) translate(50%, 1em); } \
+                 #c { transform: rotate(45deg) skew(10deg); }",
+            )
+            .unwrap()
+        );
+
+        let style_tree = StyleTree::build(&tree, &cascade, 1024.0, 768.0);
+        assert_eq!(
+            style_tree.get_style(divs[0]).unwrap().transform,
+            [
+                TransformFunction::Translate(LengthPercentage::Px(0.0), LengthPercentage::Px(-2.0)),
+                TransformFunction::Scale(1.05, 1.05),
+            ]
+        );
+        assert_eq!(
+            style_tree.get_style(divs[1]).unwrap().transform,
+            [
+                TransformFunction::Rotate(90.0),
+                TransformFunction::Translate(LengthPercentage::Percent(0.5), LengthPercentage::Px(16.0)),
+            ]
+        );
+
+        // Not inherited, and dropped as a whole for functions not supported
+        assert!(style_tree.get_style(p).unwrap().transform.is_empty());
+        assert!(style_tree.get_style(divs[2]).unwrap().transform.is_empty());
+    }
+
+    #[test]
+    fn test_background_image() {
+        let tree = parse_html("<div id='a'></div><div id='b'></div>");
+        let divs = tree.get_elements_by_tag_name("div");
+
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse(
+                "div { background-size: 8px; } \
+                 #a { background: url(tile.png) no-repeat #00ff00; background-position: right 10%; } \
+                 #b { background-image: url('dots.png'); background-repeat: repeat-x; background-size: cover; }",
+            )
+            .unwrap()
+        );
+
+        let style_tree = StyleTree::build(&tree, &cascade, 1024.0, 768.0);
+
+        let a = style_tree.get_style(divs[0]).unwrap();
+        assert_eq!(a.background_image.as_deref(), Some("tile.png"));
+        assert_eq!(a.background_repeat, BackgroundRepeat::NoRepeat);
+        assert!(matches!(a.background, Background::Color(c) if c == Color::rgb(0, 255, 0)));
+        assert_eq!(a.background_position.x, LengthPercentage::Percent(1.0));
+        assert_eq!(a.background_position.y, LengthPercentage::Percent(0.1));
+        assert_eq!(a.background_size, BackgroundSize::Size(Some(LengthPercentage::Px(8.0)), None));
+
+        let b = style_tree.get_style(divs[1]).unwrap();
+        assert_eq!(b.background_image.as_deref(), Some("dots.png"));
+        assert_eq!(b.background_repeat, BackgroundRepeat::RepeatX);
+        assert_eq!(b.background_size, BackgroundSize::Cover);
+    }
+
+    #[test]
+    fn test_image_rendering_inheritance() {
+        let tree = parse_html("<div><img><p><img></p></div>");
+        let imgs = tree.get_elements_by_tag_name("img");
+
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(
+            Stylesheet::parse("div { image-rendering: pixelated; } p { image-rendering: smooth; }").unwrap()
+        );
+
+        let style_tree = StyleTree::build(&tree, &cascade, 1024.0, 768.0);
+
+        assert_eq!(style_tree.get_style(imgs[0]).unwrap().image_rendering, ImageRendering::Pixelated);
+        assert_eq!(style_tree.get_style(imgs[1]).unwrap().image_rendering, ImageRendering::Auto);
+    }
+
+    #[test]
+    fn test_dir_attribute_sets_direction() {
+        let tree = parse_html("<div dir='rtl'><p>a</p><ul><li>b</li></ul><span>c</span></div>");
+        let p = tree.get_elements_by_tag_name("p")[0];
+        let ul = tree.get_elements_by_tag_name("ul")[0];
+        let span = tree.get_elements_by_tag_name("span")[0];
+
+        let mut cascade = Cascade::new();
+        cascade.add_author_stylesheet(Stylesheet::parse("span { float: inline-start; }").unwrap());
+
+        let style_tree = StyleTree::build(&tree, &cascade, 1024.0, 768.0);
+
+        // Inherited from the attribute, start alignment follows it
+        let p_style = style_tree.get_style(p).unwrap();
+        assert_eq!(p_style.direction, Direction::Rtl);
+        assert_eq!(p_style.text_align.physical(p_style.direction), TextAlign::Right);
+
+        // List indentation moves to the start side
+        let ul_style = style_tree.get_style(ul).unwrap();
+        assert_eq!(ul_style.padding_left, LengthPercentage::Px(0.0));
+        assert_eq!(ul_style.padding_right, LengthPercentage::Px(40.0));
+
+        // Logical floats resolve against the element's direction
+        assert_eq!(style_tree.get_style(span).unwrap().float, Float::Right);
+    }
 }
@@ -0,0 +1,242 @@
+//! Transforms
+//!
+//! The functions of the `transform` property and the 2D affine matrices
+//! they make up once the size of the transformed box is known.
+
+use serde::{Deserialize, Serialize};
+
+use crate::LengthPercentage;
+
+/// A function of the `transform` property
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransformFunction {
+    /// Move by a distance, percentages of the border box size
+    Translate(LengthPercentage, LengthPercentage),
+    /// Scale horizontally and vertically
+    Scale(f32, f32),
+    /// Rotate clockwise by degrees
+    Rotate(f32),
+    /// Apply a matrix
+    Matrix(Transform),
+}
+
+impl TransformFunction {
+    /// Matrix of the function for a border box of a size
+    fn matrix(&self, width: f32, height: f32) -> Transform {
+        match self {
+            TransformFunction::Translate(x, y) => Transform::translate(x.resolve(width), y.resolve(height)),
+            TransformFunction::Scale(x, y) => Transform::scale(*x, *y),
+            TransformFunction::Rotate(degrees) => Transform::rotate(*degrees),
+            TransformFunction::Matrix(matrix) => *matrix,
+        }
+    }
+
+    /// The function of the same kind that changes nothing
+    fn identity(&self) -> Self {
+        match self {
+            TransformFunction::Translate(..) => {
+                TransformFunction::Translate(LengthPercentage::Px(0.0), LengthPercentage::Px(0.0))
+            }
+            TransformFunction::Scale(..) => TransformFunction::Scale(1.0, 1.0),
+            TransformFunction::Rotate(_) => TransformFunction::Rotate(0.0),
+            TransformFunction::Matrix(_) => TransformFunction::Matrix(Transform::IDENTITY),
+        }
+    }
+
+    /// The function `t` of the way from this one to another of the same
+    /// kind, None when their kinds or units differ
+    fn interpolate(&self, to: &Self, t: f32) -> Option<Self> {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let lerp_length = |a: &LengthPercentage, b: &LengthPercentage| match (a, b) {
+            (LengthPercentage::Px(a), LengthPercentage::Px(b)) => Some(LengthPercentage::Px(lerp(*a, *b))),
+            (LengthPercentage::Percent(a), LengthPercentage::Percent(b)) => {
+                Some(LengthPercentage::Percent(lerp(*a, *b)))
+            }
+            _ => None,
+        };
+        Some(match (self, to) {
+            (TransformFunction::Translate(x1, y1), TransformFunction::Translate(x2, y2)) => {
+                TransformFunction::Translate(lerp_length(x1, x2)?, lerp_length(y1, y2)?)
+            }
+            (TransformFunction::Scale(x1, y1), TransformFunction::Scale(x2, y2)) => {
+                TransformFunction::Scale(lerp(*x1, *x2), lerp(*y1, *y2))
+            }
+            (TransformFunction::Rotate(a), TransformFunction::Rotate(b)) => TransformFunction::Rotate(lerp(*a, *b)),
+            (TransformFunction::Matrix(a), TransformFunction::Matrix(b)) => TransformFunction::Matrix(Transform {
+                a: lerp(a.a, b.a),
+                b: lerp(a.b, b.b),
+                c: lerp(a.c, b.c),
+                d: lerp(a.d, b.d),
+                e: lerp(a.e, b.e),
+                f: lerp(a.f, b.f),
+            }),
+            _ => return None,
+        })
+    }
+}
+
+/// A 2D affine transform, mapping `(x, y)` to
+/// `(a * x + c * y + e, b * x + d * y + f)` as CSS `matrix()` does
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    /// Transform that leaves points where they are
+    pub const IDENTITY: Transform = Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub fn translate(x: f32, y: f32) -> Self {
+        Self { e: x, f: y, ..Self::IDENTITY }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self { a: x, d: y, ..Self::IDENTITY }
+    }
+
+    /// Clockwise rotation by degrees, y pointing down
+    pub fn rotate(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+
+    /// Transform of the functions of a `transform` value for a border box
+    /// at `(x, y)`, applied about the center of the box
+    ///
+    /// The functions apply right to left, as they are written from the
+    /// outermost in.
+    pub fn of_box(functions: &[TransformFunction], x: f32, y: f32, width: f32, height: f32) -> Self {
+        let (cx, cy) = (x + width / 2.0, y + height / 2.0);
+        let matrix = functions
+            .iter()
+            .rev()
+            .fold(Self::IDENTITY, |matrix, function| matrix.then(&function.matrix(width, height)));
+        Self::translate(-cx, -cy).then(&matrix).then(&Self::translate(cx, cy))
+    }
+
+    /// This transform followed by another
+    pub fn then(&self, next: &Transform) -> Self {
+        Self {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    /// Where a point ends up
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    /// Transform undoing this one, None when it flattens points onto a
+    /// line
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        Some(Self {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+}
+
+/// The `transform` value `t` of the way from one to another
+///
+/// Lists of functions of the same kinds interpolate function by function,
+/// the shorter one padded with functions that change nothing. Other lists
+/// jump from one to the other halfway.
+pub fn interpolate_transform(from: &[TransformFunction], to: &[TransformFunction], t: f32) -> Vec<TransformFunction> {
+    let len = from.len().max(to.len());
+    let pad = |list: &[TransformFunction], other: &[TransformFunction]| -> Vec<TransformFunction> {
+        list.iter().copied().chain(other[list.len().min(other.len())..].iter().map(|f| f.identity())).collect()
+    };
+    let (padded_from, padded_to) = (pad(from, to), pad(to, from));
+    let functions: Option<Vec<_>> =
+        padded_from.iter().zip(&padded_to).take(len).map(|(a, b)| a.interpolate(b, t)).collect();
+    functions.unwrap_or_else(|| if t < 0.5 { from.to_vec() } else { to.to_vec() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_point(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_functions_about_box_center() {
+        // A 100x50 box at (0, 0) turned a quarter clockwise about (50, 25)
+        let rotated = Transform::of_box(&[TransformFunction::Rotate(90.0)], 0.0, 0.0, 100.0, 50.0);
+        assert_point(rotated.apply(50.0, 25.0), (50.0, 25.0));
+        assert_point(rotated.apply(100.0, 25.0), (50.0, 75.0));
+
+        // Percentages are of the box, and the rightmost function applies first
+        let functions = [
+            TransformFunction::Translate(LengthPercentage::Percent(0.5), LengthPercentage::Px(-2.0)),
+            TransformFunction::Scale(2.0, 2.0),
+        ];
+        let transform = Transform::of_box(&functions, 0.0, 0.0, 100.0, 50.0);
+        assert_point(transform.apply(0.0, 0.0), (0.0, -27.0));
+        assert_point(transform.apply(100.0, 50.0), (200.0, 73.0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let transform = Transform::rotate(30.0).then(&Transform::scale(2.0, 3.0)).then(&Transform::translate(5.0, 7.0));
+        let inverse = transform.inverse().unwrap();
+        let (x, y) = transform.apply(11.0, -4.0);
+        assert_point(inverse.apply(x, y), (11.0, -4.0));
+        assert_eq!(Transform::scale(0.0, 1.0).inverse(), None);
+    }
+
+    #[test]
+    fn test_interpolate_transform() {
+        let to = [
+            TransformFunction::Translate(LengthPercentage::Px(0.0), LengthPercentage::Px(-2.0)),
+            TransformFunction::Scale(1.2, 1.2),
+        ];
+        let halfway = interpolate_transform(&[], &to, 0.5);
+        assert_eq!(
+            halfway,
+            [
+                TransformFunction::Translate(LengthPercentage::Px(0.0), LengthPercentage::Px(-1.0)),
+                TransformFunction::Scale(1.1, 1.1),
+            ]
+        );
+
+        // Lists of different kinds jump halfway
+        let rotate = [TransformFunction::Rotate(45.0)];
+        assert_eq!(interpolate_transform(&rotate, &to, 0.4), rotate);
+        assert_eq!(interpolate_transform(&rotate, &to, 0.6), to);
+    }
+}